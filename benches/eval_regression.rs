@@ -56,6 +56,19 @@ fn parse_fixture_value() -> Value {
     flat.root().to_value()
 }
 
+/// Parse a deeply nested document (~11k nodes): 1000 levels of
+/// `{"id": n, "tags": [8 ints], "child": {...}}`.
+fn parse_nested_fixture() -> FlatBuffer {
+    let mut json = String::new();
+    for i in 0..1000 {
+        json.push_str(&format!(r#"{{"id":{i},"tags":[0,1,2,3,4,5,6,7],"child":"#));
+    }
+    json.push_str("null");
+    json.push_str(&"}".repeat(1000));
+    let padded = simdjson::pad_buffer(json.as_bytes());
+    simdjson::dom_parse_to_flat_buf_tape(&padded, json.len()).unwrap()
+}
+
 /// Parse a filter string.
 fn parse(expr: &str) -> Filter {
     filter::parse(expr).unwrap()
@@ -128,6 +141,13 @@ fn eval_flat_if_then_else() -> Vec<Value> {
     black_box(run_flat(&f, flat.root()))
 }
 
+#[library_benchmark]
+fn eval_flat_recurse_select() -> Vec<Value> {
+    let flat = parse_nested_fixture();
+    let f = parse("[.. | .id? | numbers | select(. % 100 == 0)]");
+    black_box(run_flat(&f, flat.root()))
+}
+
 #[library_benchmark]
 fn eval_standard_identity() -> Vec<Value> {
     let input = parse_fixture_value();
@@ -142,6 +162,35 @@ fn eval_standard_complex() -> Vec<Value> {
     black_box(run_standard(&f, &input))
 }
 
+#[library_benchmark]
+fn eval_standard_reduce_range() -> Vec<Value> {
+    let f = parse("reduce range(10000) as $i (0; . + $i)");
+    black_box(run_standard(&f, &Value::Null))
+}
+
+#[library_benchmark]
+fn eval_standard_foreach_items() -> Vec<Value> {
+    let input = parse_fixture_value();
+    let f = parse("[foreach .items[] as $item (0; . + $item.price * $item.qty)]");
+    black_box(run_standard(&f, &input))
+}
+
+#[library_benchmark]
+fn eval_standard_regex_per_record() -> Vec<Value> {
+    // One evaluation per record, as over NDJSON, with a constant pattern.
+    let f = parse(r#"select(.msg | test("(?i)timeout|connection reset|5\\d\\d")) | .id"#);
+    let records: Vec<Value> = (0..200)
+        .map(|i| {
+            let msg = ["ok", "Timeout talking to db", "HTTP 503"][i % 3];
+            Value::Object(std::sync::Arc::new(vec![
+                ("id".to_string(), Value::Int(i as i64)),
+                ("msg".to_string(), Value::String(msg.to_string())),
+            ]))
+        })
+        .collect();
+    black_box(records.iter().flat_map(|r| run_standard(&f, r)).collect())
+}
+
 #[library_benchmark]
 fn parse_filter_complex() -> Filter {
     black_box(
@@ -169,14 +218,18 @@ library_benchmark_group!(
         eval_flat_pipe_length,
         eval_flat_iterate_field,
         eval_flat_select,
-        eval_flat_if_then_else
+        eval_flat_if_then_else,
+        eval_flat_recurse_select
 );
 
 library_benchmark_group!(
     name = standard_eval_group;
     benchmarks =
         eval_standard_identity,
-        eval_standard_complex
+        eval_standard_complex,
+        eval_standard_reduce_range,
+        eval_standard_foreach_items,
+        eval_standard_regex_per_record
 );
 
 main!(
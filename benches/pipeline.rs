@@ -0,0 +1,146 @@
+//! Criterion benchmarks for each execution path: passthrough (minify and
+//! array-map copies), flat eval, tree eval, and NDJSON with and without its
+//! fast paths.
+//!
+//! Run locally:
+//!   cargo bench --bench pipeline
+//!
+//! Input sizes are record counts; override with `QJ_BENCH_RECORDS=1000,200000`.
+//! The path each filter must take is asserted by `tests/pipeline_paths.rs`.
+
+#[path = "pipeline_cases.rs"]
+mod cases;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+use qj::filter::{self, Env, EvalPlan, Filter, PassthroughPath};
+use qj::output::{self, OutputConfig, OutputMode};
+use qj::parallel::ndjson;
+use qj::simdjson;
+
+fn record_counts() -> Vec<usize> {
+    match std::env::var("QJ_BENCH_RECORDS") {
+        Ok(s) => s
+            .split(',')
+            .map(|n| n.trim().parse().expect("QJ_BENCH_RECORDS: record counts"))
+            .collect(),
+        Err(_) => vec![1_000, 50_000],
+    }
+}
+
+fn compact() -> OutputConfig {
+    OutputConfig {
+        mode: OutputMode::Compact,
+        ..OutputConfig::default()
+    }
+}
+
+fn strs(v: &[String]) -> Vec<&str> {
+    v.iter().map(String::as_str).collect()
+}
+
+/// Evaluate `filter` on one padded document the way `plan` says the CLI
+/// would, returning the output length.
+fn run_doc(plan: &EvalPlan, filter: &Filter, padded: &[u8], len: usize) -> usize {
+    match plan {
+        EvalPlan::Passthrough(PassthroughPath::Identity) => {
+            simdjson::minify(padded, len).unwrap().len()
+        }
+        EvalPlan::Passthrough(PassthroughPath::ArrayMapField {
+            prefix,
+            fields,
+            wrap_array,
+        }) => simdjson::dom_array_map_field(padded, len, &strs(prefix), &strs(fields), *wrap_array)
+            .unwrap()
+            .map_or(0, |out| out.len()),
+        EvalPlan::Passthrough(PassthroughPath::ArrayMapFieldsObj {
+            prefix,
+            entries,
+            wrap_array,
+        }) => {
+            let keys: Vec<&[u8]> = entries.iter().map(|k| k.as_bytes()).collect();
+            simdjson::dom_array_map_fields_obj(
+                padded,
+                len,
+                &strs(prefix),
+                &keys,
+                &strs(entries),
+                *wrap_array,
+            )
+            .unwrap()
+            .map_or(0, |out| out.len())
+        }
+        EvalPlan::Passthrough(other) => panic!("no benchmark for passthrough {other:?}"),
+        EvalPlan::Flat => {
+            let flat = simdjson::dom_parse_to_flat_buf_tape(padded, len).unwrap();
+            let mut out = Vec::new();
+            qj::flat_eval::eval_flat(filter, flat.root(), &Env::empty(), &mut |v| {
+                output::write_value(&mut out, &v, &compact()).unwrap();
+            });
+            out.len()
+        }
+        EvalPlan::Tree => {
+            let input = simdjson::dom_parse_to_value_fast(padded, len).unwrap();
+            let mut out = Vec::new();
+            filter::eval::eval_filter_with_env(filter, &input, &Env::empty(), &mut |v| {
+                output::write_value(&mut out, &v, &compact()).unwrap();
+            });
+            out.len()
+        }
+    }
+}
+
+fn bench_documents(c: &mut Criterion) {
+    let mut group = c.benchmark_group("document");
+    for records in record_counts() {
+        let json = cases::array_doc(records);
+        let padded = simdjson::pad_buffer(&json);
+        group.throughput(Throughput::Bytes(json.len() as u64));
+        for case in cases::DOC_CASES {
+            let filter = filter::parse(case.filter).unwrap();
+            let plan = filter::plan(&filter);
+            group.bench_with_input(
+                BenchmarkId::new(case.name, records),
+                &padded,
+                |b, padded| b.iter(|| run_doc(&plan, &filter, black_box(padded), json.len())),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_ndjson(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ndjson");
+    let config = compact();
+    let env = Env::empty();
+    for records in record_counts() {
+        let data = cases::ndjson(records);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        for case in cases::NDJSON_CASES {
+            let filter = filter::parse(case.filter).unwrap();
+            group.bench_with_input(BenchmarkId::new(case.name, records), &data, |b, data| {
+                b.iter(|| ndjson::process_ndjson(black_box(data), &filter, &config, &env).unwrap())
+            });
+            // The same filter through the evaluator, to show what the fast
+            // path saves.
+            if case.fast_path.is_some() {
+                let id = BenchmarkId::new(format!("{}_evaluator", case.name), records);
+                group.bench_with_input(id, &data, |b, data| {
+                    b.iter(|| {
+                        ndjson::process_ndjson_no_fast_path(black_box(data), &filter, &config, &env)
+                            .unwrap()
+                    })
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = bench_documents, bench_ndjson
+}
+criterion_main!(benches);
@@ -0,0 +1,154 @@
+#![allow(dead_code)]
+//! Filters and synthetic inputs shared by the `pipeline` criterion benchmark
+//! and the `pipeline_paths` test, which asserts the execution path each
+//! filter takes so a lost passthrough fails CI instead of showing up only
+//! as a slowdown.
+
+use qj::filter::{EvalPlan, PassthroughPath};
+
+/// A filter over one document: an array of records.
+pub struct DocCase {
+    pub name: &'static str,
+    pub filter: &'static str,
+    pub plan: fn(&EvalPlan) -> bool,
+}
+
+/// A filter over NDJSON records, with the fast path it must take (`None`
+/// for the evaluator).
+pub struct NdjsonCase {
+    pub name: &'static str,
+    pub filter: &'static str,
+    pub fast_path: Option<&'static str>,
+}
+
+pub const DOC_CASES: &[DocCase] = &[
+    DocCase {
+        name: "identity",
+        filter: ".",
+        plan: |p| matches!(p, EvalPlan::Passthrough(PassthroughPath::Identity)),
+    },
+    DocCase {
+        name: "map_field",
+        filter: "map(.name)",
+        plan: |p| {
+            matches!(
+                p,
+                EvalPlan::Passthrough(PassthroughPath::ArrayMapField { .. })
+            )
+        },
+    },
+    DocCase {
+        name: "map_fields_obj",
+        filter: "map({a, b})",
+        plan: |p| {
+            matches!(
+                p,
+                EvalPlan::Passthrough(PassthroughPath::ArrayMapFieldsObj { .. })
+            )
+        },
+    },
+    DocCase {
+        name: "field",
+        filter: ".[0].name",
+        plan: |p| matches!(p, EvalPlan::Flat),
+    },
+    DocCase {
+        name: "iterate_select",
+        filter: ".[] | select(.a > 3)",
+        plan: |p| matches!(p, EvalPlan::Flat),
+    },
+    DocCase {
+        name: "reduce",
+        filter: "reduce .[] as $r (0; . + $r.a)",
+        plan: |p| matches!(p, EvalPlan::Flat),
+    },
+    DocCase {
+        name: "sort_by",
+        filter: "sort_by(.a)",
+        plan: |p| matches!(p, EvalPlan::Tree),
+    },
+];
+
+pub const NDJSON_CASES: &[NdjsonCase] = &[
+    NdjsonCase {
+        name: "identity",
+        filter: ".",
+        fast_path: None,
+    },
+    NdjsonCase {
+        name: "field",
+        filter: ".name",
+        fast_path: Some("field"),
+    },
+    NdjsonCase {
+        name: "nested_field",
+        filter: ".nested.city",
+        fast_path: Some("field"),
+    },
+    NdjsonCase {
+        name: "select_eq",
+        filter: "select(.a == 3)",
+        fast_path: Some("select_eq"),
+    },
+    NdjsonCase {
+        name: "fields_obj",
+        filter: "{a, b}",
+        fast_path: Some("multi_field_obj"),
+    },
+    NdjsonCase {
+        name: "length",
+        filter: "length",
+        fast_path: Some("length"),
+    },
+    NdjsonCase {
+        name: "select_gt",
+        filter: "select(.a > 3)",
+        fast_path: Some("select_eq"),
+    },
+    NdjsonCase {
+        name: "computed_obj",
+        filter: "{id, total: (.a + .b)}",
+        fast_path: None,
+    },
+];
+
+/// One record; `i` varies every field.
+fn write_record(out: &mut String, i: usize) {
+    use std::fmt::Write;
+    write!(
+        out,
+        r#"{{"id":{i},"name":"user{i}","a":{},"b":{}.5,"active":{},"nested":{{"city":"c{}","zip":"{:05}"}},"tags":["t{}","t{}"]}}"#,
+        i % 7,
+        i % 100,
+        i.is_multiple_of(2),
+        i % 13,
+        i % 100_000,
+        i % 5,
+        i % 11,
+    )
+    .unwrap();
+}
+
+/// `records` records, one per line.
+pub fn ndjson(records: usize) -> Vec<u8> {
+    let mut out = String::new();
+    for i in 0..records {
+        write_record(&mut out, i);
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+/// One pretty-ish array document of `records` records.
+pub fn array_doc(records: usize) -> Vec<u8> {
+    let mut out = String::from("[\n");
+    for i in 0..records {
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        out.push_str("  ");
+        write_record(&mut out, i);
+    }
+    out.push_str("\n]\n");
+    out.into_bytes()
+}
@@ -273,7 +273,7 @@ impl<'a> Arbitrary<'a> for FlatFilter {
 /// Top-level JSON is always an object — this matches the NDJSON use case
 /// where flat_eval is actually used. Non-object top-level inputs can cause
 /// divergences in error state propagation between the two eval paths
-/// (thread-local LAST_ERROR interactions with Alternative/Pipe/Field), but
+/// (error-slot interactions with Alternative/Pipe/Field), but
 /// these don't affect real-world behavior since NDJSON records are objects.
 #[derive(Debug)]
 struct FuzzInput {
@@ -329,9 +329,6 @@ fuzz_target!(|input: FuzzInput| {
 
     let env = Env::empty();
 
-    // Clear any thread-local errors before each eval
-    let _ = qj::filter::eval::take_last_error();
-
     // Evaluate with regular evaluator
     let mut regular_results = Vec::new();
     filter::eval::eval_filter_with_env(&filter, &value, &env, &mut |v| {
@@ -339,8 +336,7 @@ fuzz_target!(|input: FuzzInput| {
             regular_results.push(v);
         }
     });
-    let regular_had_error = qj::filter::eval::has_last_error();
-    let _ = qj::filter::eval::take_last_error();
+    let regular_had_error = env.take_error().is_some();
 
     // Evaluate with flat evaluator
     let mut flat_results = Vec::new();
@@ -349,13 +345,12 @@ fuzz_target!(|input: FuzzInput| {
             flat_results.push(v);
         }
     });
-    let flat_had_error = qj::filter::eval::has_last_error();
-    let _ = qj::filter::eval::take_last_error();
+    let flat_had_error = env.take_error().is_some();
 
     // Skip comparison when errors occurred or when the filter interacts with
     // error state. The two eval paths have different error propagation:
     //
-    // - eval_filter_with_env clears LAST_ERROR on entry, so flat_eval's
+    // - eval_filter_with_env clears the env's error on entry, so flat_eval's
     //   delegated calls get fresh error state per sub-expression
     // - regular eval runs the entire filter in a single eval() call, so
     //   errors can leak across Comma branches and into Pipe's error check
@@ -0,0 +1,85 @@
+//! What this qj was built with and is running on, for bug reports. Printed
+//! by `qj --build-info` and returned by the `qj_build_info` builtin.
+
+use std::sync::Arc;
+
+use crate::value::Value;
+
+/// Cargo features, and whether each was compiled in.
+const FEATURES: &[(&str, bool)] = &[
+    ("yaml", cfg!(feature = "yaml")),
+    ("bench", cfg!(feature = "bench")),
+];
+
+/// The build and runtime details as an object:
+///
+/// - `version`: the qj version
+/// - `simdjson_version`, `simdjson_implementation`: the simdjson release and
+///   the SIMD kernel it selected for this CPU
+/// - `default_threads`: the thread count used without `--threads`, and
+///   `threads`: the size of the running thread pool
+/// - `pcore_detection`: whether the default counts only performance cores
+///   (Apple Silicon)
+/// - `allocator`: the global allocator of the `qj` binary
+/// - `features`: the enabled cargo features
+pub fn build_info() -> Value {
+    let string = |s: &str| Value::String(s.to_string());
+    let features = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| string(name))
+        .collect();
+    Value::Object(Arc::new(vec![
+        ("version".into(), string(env!("CARGO_PKG_VERSION"))),
+        (
+            "simdjson_version".into(),
+            string(crate::simdjson::version()),
+        ),
+        (
+            "simdjson_implementation".into(),
+            string(crate::simdjson::active_implementation()),
+        ),
+        (
+            "default_threads".into(),
+            Value::Int(crate::parallel::default_thread_count() as i64),
+        ),
+        (
+            "threads".into(),
+            Value::Int(rayon::current_num_threads() as i64),
+        ),
+        (
+            "pcore_detection".into(),
+            Value::Bool(cfg!(all(target_os = "macos", target_arch = "aarch64"))),
+        ),
+        ("allocator".into(), string("mimalloc")),
+        ("features".into(), Value::Array(Arc::new(features))),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_info_keys() {
+        let Value::Object(fields) = build_info() else {
+            panic!("expected an object");
+        };
+        let keys: Vec<&str> = fields.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "version",
+                "simdjson_version",
+                "simdjson_implementation",
+                "default_threads",
+                "threads",
+                "pcore_detection",
+                "allocator",
+                "features"
+            ]
+        );
+        assert!(!crate::simdjson::version().is_empty());
+        assert!(!crate::simdjson::active_implementation().is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+//! Shell completion (`--completions <SHELL>`).
+//!
+//! clap_complete generates the script for each shell from the CLI definition.
+//! The bash script also completes the filter argument: when the word being
+//! completed starts with `.` and a later word names an input file, it calls
+//! `qj --complete-filter PREFIX FILE`, which suggests the file's top-level keys
+//! via the `keys_unsorted` passthrough. Anything that goes wrong there (a
+//! missing, compressed, oversized or invalid file) just yields no suggestions.
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use clap_complete::Shell;
+
+/// Only this much of the input file is read to find its keys.
+pub const KEY_SCAN_LIMIT: u64 = 1 << 20;
+
+/// Added to the generated bash script: filter words starting with `.` are
+/// completed from the keys of the first existing file after them; everything
+/// else goes to the clap-generated `_qj`.
+const BASH_FILTER_KEYS: &str = r#"
+_qj_filter_keys() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" i
+    if [[ "$cur" == .* ]]; then
+        for ((i = COMP_CWORD + 1; i < ${#COMP_WORDS[@]}; i++)); do
+            if [[ -f "${COMP_WORDS[i]}" ]]; then
+                mapfile -t COMPREPLY < <("$1" --complete-filter "$cur" "${COMP_WORDS[i]}" 2>/dev/null)
+                return 0
+            fi
+        done
+    fi
+    _qj "$@"
+}
+
+complete -F _qj_filter_keys -o bashdefault -o default qj
+"#;
+
+/// Write the completion script for `shell` to `out`.
+pub fn write_script(shell: Shell, cmd: &mut clap::Command, out: &mut dyn Write) -> io::Result<()> {
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, cmd, name, out);
+    if shell == Shell::Bash {
+        out.write_all(BASH_FILTER_KEYS.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Filter completions for `prefix`: a path expression for each top-level key
+/// of `path` that starts with `prefix`.
+pub fn filter_candidates(prefix: &str, path: &Path) -> Vec<String> {
+    top_level_keys(path)
+        .iter()
+        .map(|key| key_path(key))
+        .filter(|candidate| candidate.starts_with(prefix))
+        .collect()
+}
+
+/// Top-level keys of the object in `path`, in document order. For NDJSON the
+/// first line is used. Files that can't be read within [`KEY_SCAN_LIMIT`],
+/// are compressed, or don't hold an object have no keys.
+pub fn top_level_keys(path: &Path) -> Vec<String> {
+    if path.to_str().is_some_and(crate::decompress::is_compressed) {
+        return Vec::new();
+    }
+    let mut bytes = Vec::new();
+    let read = std::fs::File::open(path)
+        .and_then(|file| file.take(KEY_SCAN_LIMIT).read_to_end(&mut bytes));
+    if read.is_err() {
+        return Vec::new();
+    }
+    if let Some(keys) = object_keys(&bytes) {
+        return keys;
+    }
+    match memchr::memchr(b'\n', &bytes) {
+        Some(end) => object_keys(&bytes[..end]).unwrap_or_default(),
+        None => Vec::new(),
+    }
+}
+
+/// `keys_unsorted` of a single JSON document, or `None` if it isn't one
+/// object.
+fn object_keys(json: &[u8]) -> Option<Vec<String>> {
+    let padded = crate::simdjson::pad_buffer(json);
+    let keys = crate::simdjson::dom_field_keys(&padded, json.len(), &[], false).ok()??;
+    serde_json::from_slice(&keys).ok()
+}
+
+/// `.key` for identifier-like keys, `."key"` otherwise.
+fn key_path(key: &str) -> String {
+    let mut chars = key.chars();
+    let is_ident = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_ident {
+        format!(".{key}")
+    } else {
+        format!(".{}", serde_json::Value::from(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str, contents: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn keys_of_object_file() {
+        let (_dir, path) = fixture(
+            "data.json",
+            r#"{"name": "x", "tags": [1], "a b": null, "_id": 3}"#,
+        );
+        assert_eq!(top_level_keys(&path), ["name", "tags", "a b", "_id"]);
+        assert_eq!(
+            filter_candidates(".", &path),
+            [".name", ".tags", r#"."a b""#, "._id"]
+        );
+        assert_eq!(filter_candidates(".t", &path), [".tags"]);
+        assert!(filter_candidates(".zz", &path).is_empty());
+    }
+
+    #[test]
+    fn ndjson_uses_first_line() {
+        let (_dir, path) = fixture("rows.ndjson", "{\"id\":1,\"v\":2}\n{\"other\":3}\n");
+        assert_eq!(top_level_keys(&path), ["id", "v"]);
+    }
+
+    #[test]
+    fn no_keys_without_an_object() {
+        let (_dir, path) = fixture("list.json", "[1, 2]");
+        assert!(top_level_keys(&path).is_empty());
+        let (_dir, path) = fixture("bad.json", "{\"a\": ");
+        assert!(top_level_keys(&path).is_empty());
+        let (_dir, path) = fixture("data.json.gz", "{\"a\": 1}");
+        assert!(top_level_keys(&path).is_empty());
+        assert!(top_level_keys(Path::new("/nonexistent/data.json")).is_empty());
+
+        // Past the scan limit the document is cut off
+        let big = format!("{{\"a\": \"{}\"}}", "x".repeat(KEY_SCAN_LIMIT as usize));
+        let (_dir, path) = fixture("big.json", &big);
+        assert!(top_level_keys(&path).is_empty());
+    }
+}
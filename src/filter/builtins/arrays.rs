@@ -2,9 +2,9 @@ use crate::filter::{ArithOp, Env, Filter};
 use crate::value::Value;
 use std::sync::Arc;
 
-use super::super::eval::{LAST_ERROR, eval};
+use super::super::eval::eval;
 use super::super::value_ops::{arith_values, recurse, to_f64, values_equal, values_order};
-use super::set_error;
+use super::{eval_until, set_error};
 
 /// Maximum iterations for `until`, `while`, and `repeat` builtins.
 const MAX_LOOP_ITERATIONS: usize = 1_000_000;
@@ -36,20 +36,17 @@ pub(super) fn eval_arrays(
                 let keys: Vec<Value> = (0..arr.len() as i64).map(Value::Int).collect();
                 output(Value::Array(Arc::new(keys)));
             }
-            Value::Null => output(Value::Null),
             _ => {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) has no keys",
                     input.type_name(),
                     input.short_desc()
                 ));
             }
         },
-        "values" => {
-            // values = select(. != null): passes through any non-null input
-            if !matches!(input, Value::Null) {
-                output(input.clone());
-            }
+        // values = select(. != null): passes through any non-null input
+        "values" if !matches!(input, Value::Null) => {
+            output(input.clone());
         }
         "map" => {
             if let Some(f) = args.first() {
@@ -58,6 +55,10 @@ pub(super) fn eval_arrays(
                         let mut result = Vec::with_capacity(arr.len());
                         for item in arr.iter() {
                             eval(f, item, env, &mut |v| result.push(v));
+                            // An error ends the map, with no partial array.
+                            if env.has_error() {
+                                return;
+                            }
                         }
                         output(Value::Array(Arc::new(result)));
                     }
@@ -66,11 +67,14 @@ pub(super) fn eval_arrays(
                         let mut result = Vec::with_capacity(obj.len());
                         for (_, v) in obj.iter() {
                             eval(f, v, env, &mut |v| result.push(v));
+                            if env.has_error() {
+                                return;
+                            }
                         }
                         output(Value::Array(Arc::new(result)));
                     }
                     _ => {
-                        set_error(format!(
+                        set_error(env, format!(
                             "Cannot iterate over {} ({})",
                             input.type_name(),
                             input.short_desc()
@@ -121,7 +125,7 @@ pub(super) fn eval_arrays(
                     }
                     Value::Array(_) => output(Value::Null),
                     _ => {
-                        set_error(format!(
+                        set_error(env, format!(
                             "Cannot iterate over {} ({})",
                             input.type_name(),
                             input.short_desc()
@@ -130,99 +134,43 @@ pub(super) fn eval_arrays(
                 }
             }
         }
-        "any" => match args.len() {
-            // any(generator; filter) — 2-arg form
-            2 => {
-                let mut found = false;
-                eval(&args[0], input, env, &mut |item| {
-                    if !found {
-                        eval(&args[1], &item, env, &mut |v| {
-                            if v.is_truthy() {
-                                found = true;
-                            }
-                        });
-                    }
-                });
-                output(Value::Bool(found));
-            }
-            // any(filter) — 1-arg form on array input
-            1 => {
-                if let Value::Array(arr) = input {
-                    let mut found = false;
-                    for item in arr.iter() {
-                        eval(&args[0], item, env, &mut |v| {
-                            if v.is_truthy() {
-                                found = true;
-                            }
-                        });
-                        if found {
-                            break;
-                        }
-                    }
-                    output(Value::Bool(found));
-                }
-            }
-            // any — 0-arg form on array input
-            _ => {
-                if let Value::Array(arr) = input {
-                    let found = arr.iter().any(|v| v.is_truthy());
-                    output(Value::Bool(found));
+        "any" | "all" => {
+            // any(gen; cond), any(cond) = any(.[]; cond), any = any(.[]; .),
+            // and likewise all: stop at the first output of `cond` that
+            // decides the answer, so infinite generators are fine.
+            let (iterate, identity) = (Filter::Iterate, Filter::Identity);
+            let (generator, condition) = match args {
+                [g, c] => (g, c),
+                [c] => (&iterate, c),
+                _ => (&iterate, &identity),
+            };
+            let wanted = name == "any";
+            let mut found = false;
+            eval_until(generator, input, env, &mut |item| {
+                if env.has_error() {
+                    return false;
                 }
-            }
-        },
-        "all" => match args.len() {
-            // all(generator; filter) — 2-arg form
-            2 => {
-                let mut all_true = true;
-                eval(&args[0], input, env, &mut |item| {
-                    if all_true {
-                        let mut item_true = false;
-                        eval(&args[1], &item, env, &mut |v| {
-                            if v.is_truthy() {
-                                item_true = true;
-                            }
-                        });
-                        if !item_true {
-                            all_true = false;
-                        }
-                    }
+                eval_until(condition, &item, env, &mut |v| {
+                    found = !env.has_error() && v.is_truthy() == wanted;
+                    !found && !env.has_error()
                 });
-                output(Value::Bool(all_true));
-            }
-            // all(filter) — 1-arg form on array input
-            1 => {
-                if let Value::Array(arr) = input {
-                    let mut all_true = true;
-                    for item in arr.iter() {
-                        let mut item_true = false;
-                        eval(&args[0], item, env, &mut |v| {
-                            if v.is_truthy() {
-                                item_true = true;
-                            }
-                        });
-                        if !item_true {
-                            all_true = false;
-                            break;
-                        }
-                    }
-                    output(Value::Bool(all_true));
-                }
+                !found && !env.has_error()
+            });
+            // As with `first`, nothing past the deciding value is evaluated.
+            if found {
+                env.take_error();
             }
-            // all — 0-arg form on array input
-            _ => {
-                if let Value::Array(arr) = input {
-                    let all_true = arr.iter().all(|v| v.is_truthy());
-                    output(Value::Bool(all_true));
-                }
+            if !env.has_error() {
+                output(Value::Bool(found == wanted));
             }
-        },
+        }
         "sort" => {
             if let Value::Array(arr) = input {
                 let mut sorted: Vec<Value> = arr.as_ref().clone();
                 sorted.sort_by(|a, b| values_order(a, b).unwrap_or(std::cmp::Ordering::Equal));
                 output(Value::Array(Arc::new(sorted)));
             } else {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) cannot be sorted, as it is not an array",
                     input.type_name(),
                     input.short_desc()
@@ -231,29 +179,14 @@ pub(super) fn eval_arrays(
         }
         "sort_by" => {
             if let (Value::Array(arr), Some(f)) = (input, args.first()) {
-                let mut pairs: Vec<(Vec<Value>, Value)> = arr
-                    .iter()
-                    .map(|item| {
-                        let mut keys = Vec::new();
-                        eval(f, item, env, &mut |v| keys.push(v));
-                        (keys, item.clone())
-                    })
-                    .collect();
-                pairs.sort_by(|(a, _), (b, _)| {
-                    // Lexicographic comparison of key tuples
-                    for (ak, bk) in a.iter().zip(b.iter()) {
-                        let ord = values_order(ak, bk).unwrap_or(std::cmp::Ordering::Equal);
-                        if ord != std::cmp::Ordering::Equal {
-                            return ord;
-                        }
-                    }
-                    a.len().cmp(&b.len())
-                });
+                let Some(pairs) = sorted_by_keys(f, arr, env) else {
+                    return;
+                };
                 output(Value::Array(Arc::new(
                     pairs.into_iter().map(|(_, v)| v).collect(),
                 )));
             } else if !matches!(input, Value::Array(_)) {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) cannot be sorted, as it is not an array",
                     input.type_name(),
                     input.short_desc()
@@ -262,37 +195,16 @@ pub(super) fn eval_arrays(
         }
         "group_by" => {
             if let (Value::Array(arr), Some(f)) = (input, args.first()) {
-                let mut pairs: Vec<(Value, Value)> = arr
-                    .iter()
-                    .map(|item| {
-                        let mut key = Value::Null;
-                        eval(f, item, env, &mut |v| key = v);
-                        (key, item.clone())
-                    })
+                let Some(pairs) = sorted_by_keys(f, arr, env) else {
+                    return;
+                };
+                let groups = group_sorted(pairs)
+                    .into_iter()
+                    .map(|group| Value::Array(Arc::new(group)))
                     .collect();
-                pairs.sort_by(|(a, _), (b, _)| {
-                    values_order(a, b).unwrap_or(std::cmp::Ordering::Equal)
-                });
-                let mut groups: Vec<Value> = Vec::new();
-                let mut current_key: Option<Value> = None;
-                let mut current_group: Vec<Value> = Vec::new();
-                for (key, val) in pairs {
-                    if current_key.as_ref().is_some_and(|k| values_equal(k, &key)) {
-                        current_group.push(val);
-                    } else {
-                        if !current_group.is_empty() {
-                            groups.push(Value::Array(Arc::new(std::mem::take(&mut current_group))));
-                        }
-                        current_key = Some(key);
-                        current_group.push(val);
-                    }
-                }
-                if !current_group.is_empty() {
-                    groups.push(Value::Array(Arc::new(current_group)));
-                }
                 output(Value::Array(Arc::new(groups)));
             } else if !matches!(input, Value::Array(_)) {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) cannot be grouped, as it is not an array",
                     input.type_name(),
                     input.short_desc()
@@ -306,7 +218,7 @@ pub(super) fn eval_arrays(
                 sorted.dedup_by(|a, b| values_equal(a, b));
                 output(Value::Array(Arc::new(sorted)));
             } else {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) cannot be unique'd, as it is not an array",
                     input.type_name(),
                     input.short_desc()
@@ -314,20 +226,18 @@ pub(super) fn eval_arrays(
             }
         }
         "unique_by" => {
+            // jq: [group_by(f)[] | .[0]] — first element of each group, in key order
             if let (Value::Array(arr), Some(f)) = (input, args.first()) {
-                let mut seen_keys: Vec<Value> = Vec::new();
-                let mut result: Vec<Value> = Vec::new();
-                for item in arr.iter() {
-                    let mut key = Value::Null;
-                    eval(f, item, env, &mut |v| key = v);
-                    if !seen_keys.iter().any(|k| values_equal(k, &key)) {
-                        seen_keys.push(key);
-                        result.push(item.clone());
-                    }
-                }
+                let Some(pairs) = sorted_by_keys(f, arr, env) else {
+                    return;
+                };
+                let result = group_sorted(pairs)
+                    .into_iter()
+                    .filter_map(|group| group.into_iter().next())
+                    .collect();
                 output(Value::Array(Arc::new(result)));
             } else if !matches!(input, Value::Array(_)) {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) cannot be unique'd, as it is not an array",
                     input.type_name(),
                     input.short_desc()
@@ -353,7 +263,7 @@ pub(super) fn eval_arrays(
                             let depth = match d {
                                 Value::Int(n) => {
                                     if n < 0 {
-                                        set_error("flatten depth must not be negative".to_string());
+                                        set_error(env, "flatten depth must not be negative".to_string());
                                         return;
                                     }
                                     n
@@ -368,7 +278,7 @@ pub(super) fn eval_arrays(
                     None // already handled
                 }
                 _ => {
-                    set_error(format!(
+                    set_error(env, format!(
                         "{} ({}) cannot be flattened, as it is not an array",
                         input.type_name(),
                         input.short_desc()
@@ -382,7 +292,7 @@ pub(super) fn eval_arrays(
                         let depth = match d {
                             Value::Int(n) => {
                                 if n < 0 {
-                                    set_error("flatten depth must not be negative".to_string());
+                                    set_error(env, "flatten depth must not be negative".to_string());
                                     return;
                                 }
                                 n
@@ -402,13 +312,16 @@ pub(super) fn eval_arrays(
         }
         "first" => {
             if let Some(f) = args.first() {
-                let mut found = false;
-                eval(f, input, env, &mut |v| {
-                    if !found {
-                        output(v);
-                        found = true;
-                    }
+                let mut clean_when_found = false;
+                eval_until(f, input, env, &mut |v| {
+                    clean_when_found = !env.has_error();
+                    output(v);
+                    false
                 });
+                // jq stops `f` at its first output, so later errors never happen.
+                if clean_when_found {
+                    env.take_error();
+                }
             } else {
                 match input {
                     Value::Array(arr) => {
@@ -416,7 +329,7 @@ pub(super) fn eval_arrays(
                     }
                     Value::Null => output(Value::Null),
                     _ => {
-                        set_error(format!("Cannot index {} with number", input.type_name()));
+                        set_error(env, format!("Cannot index {} with number", input.type_name()));
                     }
                 }
             }
@@ -435,7 +348,7 @@ pub(super) fn eval_arrays(
                     }
                     Value::Null => output(Value::Null),
                     _ => {
-                        set_error(format!("Cannot index {} with number", input.type_name()));
+                        set_error(env, format!("Cannot index {} with number", input.type_name()));
                     }
                 }
             }
@@ -451,7 +364,7 @@ pub(super) fn eval_arrays(
             }
             Value::Null => output(Value::Array(Arc::new(vec![]))),
             _ => {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) cannot be reversed, as it is not an array",
                     input.type_name(),
                     input.short_desc()
@@ -469,7 +382,7 @@ pub(super) fn eval_arrays(
                     output(Value::Null);
                 }
             } else {
-                set_error(format!(
+                set_error(env, format!(
                     "Cannot iterate over {} ({})",
                     input.type_name(),
                     input.short_desc()
@@ -487,7 +400,7 @@ pub(super) fn eval_arrays(
                     output(Value::Null);
                 }
             } else {
-                set_error(format!(
+                set_error(env, format!(
                     "Cannot iterate over {} ({})",
                     input.type_name(),
                     input.short_desc()
@@ -496,14 +409,15 @@ pub(super) fn eval_arrays(
         }
         "min_by" => {
             if let (Value::Array(arr), Some(f)) = (input, args.first()) {
+                let Some(pairs) = eval_keys(f, arr, env) else {
+                    return;
+                };
                 let mut best: Option<(Value, Value)> = None;
-                for item in arr.iter() {
-                    let mut key = Value::Null;
-                    eval(f, item, env, &mut |v| key = v);
+                for (key, item) in pairs {
                     if best.as_ref().is_none_or(|(bk, _)| {
                         values_order(&key, bk) == Some(std::cmp::Ordering::Less)
                     }) {
-                        best = Some((key, item.clone()));
+                        best = Some((key, item));
                     }
                 }
                 if let Some((_, v)) = best {
@@ -512,7 +426,7 @@ pub(super) fn eval_arrays(
                     output(Value::Null);
                 }
             } else if !matches!(input, Value::Array(_)) {
-                set_error(format!(
+                set_error(env, format!(
                     "Cannot iterate over {} ({})",
                     input.type_name(),
                     input.short_desc()
@@ -521,17 +435,18 @@ pub(super) fn eval_arrays(
         }
         "max_by" => {
             if let (Value::Array(arr), Some(f)) = (input, args.first()) {
+                let Some(pairs) = eval_keys(f, arr, env) else {
+                    return;
+                };
                 let mut best: Option<(Value, Value)> = None;
-                for item in arr.iter() {
-                    let mut key = Value::Null;
-                    eval(f, item, env, &mut |v| key = v);
+                for (key, item) in pairs {
                     if best.as_ref().is_none_or(|(bk, _)| {
                         matches!(
                             values_order(&key, bk),
                             Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal)
                         )
                     }) {
-                        best = Some((key, item.clone()));
+                        best = Some((key, item));
                     }
                 }
                 if let Some((_, v)) = best {
@@ -540,7 +455,7 @@ pub(super) fn eval_arrays(
                     output(Value::Null);
                 }
             } else if !matches!(input, Value::Array(_)) {
-                set_error(format!(
+                set_error(env, format!(
                     "Cannot iterate over {} ({})",
                     input.type_name(),
                     input.short_desc()
@@ -549,7 +464,8 @@ pub(super) fn eval_arrays(
         }
         "del" => {
             if let Some(path_f) = args.first() {
-                // Collect all paths to delete, resolving negative indices
+                // Collect every path the expression generates, then delete
+                // them together so earlier deletions can't shift later ones
                 let mut paths: Vec<Vec<Value>> = Vec::new();
                 super::super::value_ops::path_of_env(
                     path_f,
@@ -562,49 +478,13 @@ pub(super) fn eval_arrays(
                         }
                     },
                 );
-                if paths.is_empty() {
-                    output(input.clone());
+                if env.has_error() {
                     return;
                 }
-                // Resolve negative indices at each level relative to current container
-                for path in &mut paths {
-                    let mut container = input;
-                    for seg in path.iter_mut() {
-                        if let Value::Int(i) = seg {
-                            if *i < 0
-                                && let Value::Array(arr) = container
-                            {
-                                *i = (arr.len() as i64 + *i).max(0);
-                            }
-                            if let Value::Array(arr) = container {
-                                let idx = *i as usize;
-                                if idx < arr.len() {
-                                    container = &arr[idx];
-                                }
-                            }
-                        } else if let Value::String(k) = seg
-                            && let Value::Object(obj) = container
-                            && let Some((_, v)) = obj.iter().find(|(ek, _)| ek == k)
-                        {
-                            container = v;
-                        }
-                    }
-                }
-                // Sort in reverse order so deletions don't shift indices
-                paths.sort_by(|a, b| {
-                    super::super::value_ops::values_order(
-                        &Value::Array(Arc::new(b.clone())),
-                        &Value::Array(Arc::new(a.clone())),
-                    )
-                    .unwrap_or(std::cmp::Ordering::Equal)
-                });
-                // Deduplicate paths (a path that's a prefix of another already deletes it)
-                paths.dedup();
-                let mut result = input.clone();
-                for path in &paths {
-                    result = super::super::value_ops::del_path(&result, path);
+                match super::super::value_ops::delete_paths(input, paths) {
+                    Ok(result) => output(result),
+                    Err(msg) => set_error(env, msg),
                 }
-                output(result);
             } else {
                 output(input.clone());
             }
@@ -638,7 +518,7 @@ pub(super) fn eval_arrays(
                 }
                 output(Value::Array(Arc::new(result)));
             } else {
-                set_error(format!(
+                set_error(env, format!(
                     "{} ({}) cannot be transposed, as it is not an array",
                     input.type_name(),
                     input.short_desc()
@@ -668,34 +548,36 @@ pub(super) fn eval_arrays(
                 }
             }
         }
-        "limit" => {
-            if args.len() == 2 {
+        "limit"
+            if args.len() == 2 => {
                 eval(&args[0], input, env, &mut |n_val| {
-                    let n = to_f64(&n_val) as i64;
-                    if n < 0 {
-                        set_error("limit doesn't support negative count".into());
+                    // Fractional counts round up: jq emits, then stops once count >= n.
+                    let n = to_f64(&n_val);
+                    if n < 0.0 {
+                        set_error(env, "limit doesn't support negative count".into());
                         return;
                     }
-                    let mut count = 0i64;
-                    eval(&args[1], input, env, &mut |v| {
-                        if count < n {
-                            output(v);
-                            count += 1;
-                        }
+                    if n == 0.0 {
+                        return;
+                    }
+                    let mut count = 0.0;
+                    eval_until(&args[1], input, env, &mut |v| {
+                        output(v);
+                        count += 1.0;
+                        count < n
                     });
                     // Clear errors from generator values past the limit
                     if count >= n {
-                        LAST_ERROR.with(|e| e.borrow_mut().take());
+                        env.take_error();
                     }
                 });
             }
-        }
-        "skip" => {
-            if args.len() == 2 {
+        "skip"
+            if args.len() == 2 => {
                 eval(&args[0], input, env, &mut |n_val| {
                     let n = to_f64(&n_val) as i64;
                     if n < 0 {
-                        set_error("skip doesn't support negative count".into());
+                        set_error(env, "skip doesn't support negative count".into());
                         return;
                     }
                     let mut count = 0i64;
@@ -707,9 +589,8 @@ pub(super) fn eval_arrays(
                     });
                 });
             }
-        }
-        "until" => {
-            if args.len() == 2 {
+        "until"
+            if args.len() == 2 => {
                 let mut current = input.clone();
                 for _ in 0..MAX_LOOP_ITERATIONS {
                     let mut done = false;
@@ -726,9 +607,8 @@ pub(super) fn eval_arrays(
                 }
                 output(current);
             }
-        }
-        "while" => {
-            if args.len() == 2 {
+        "while"
+            if args.len() == 2 => {
                 let mut current = input.clone();
                 for _ in 0..MAX_LOOP_ITERATIONS {
                     let mut cont = false;
@@ -745,7 +625,6 @@ pub(super) fn eval_arrays(
                     current = next;
                 }
             }
-        }
         "repeat" => {
             if let Some(f) = args.first() {
                 for _ in 0..MAX_LOOP_ITERATIONS {
@@ -756,8 +635,16 @@ pub(super) fn eval_arrays(
         "isempty" => {
             if let Some(f) = args.first() {
                 let mut found = false;
-                eval(f, input, env, &mut |_| found = true);
-                output(Value::Bool(!found));
+                eval_until(f, input, env, &mut |_| {
+                    found = !env.has_error();
+                    false
+                });
+                if found {
+                    env.take_error();
+                }
+                if !env.has_error() {
+                    output(Value::Bool(!found));
+                }
             }
         }
         "nth" => {
@@ -766,7 +653,7 @@ pub(super) fn eval_arrays(
                 eval(&args[0], input, env, &mut |idx_val| {
                     let n = to_f64(&idx_val) as i64;
                     if n < 0 {
-                        set_error("nth doesn't support negative indices".into());
+                        set_error(env, "nth doesn't support negative indices".into());
                         return;
                     }
                     let mut count = 0i64;
@@ -778,14 +665,28 @@ pub(super) fn eval_arrays(
                     });
                 });
             } else if args.len() == 1 {
-                let mut n = 0i64;
-                eval(&args[0], input, env, &mut |v| n = to_f64(&v) as i64);
-                if let Value::Array(arr) = input
-                    && n >= 0
-                    && (n as usize) < arr.len()
-                {
-                    output(arr[n as usize].clone());
-                }
+                // nth($n) is `.[$n]`: negative indices count from the end.
+                eval(&args[0], input, env, &mut |idx_val| match (input, &idx_val) {
+                    (Value::Array(arr), Value::Int(_) | Value::Double(..)) => {
+                        let n = to_f64(&idx_val).floor() as i64;
+                        let idx = if n < 0 { arr.len() as i64 + n } else { n };
+                        output(
+                            usize::try_from(idx)
+                                .ok()
+                                .and_then(|i| arr.get(i).cloned())
+                                .unwrap_or(Value::Null),
+                        );
+                    }
+                    (Value::Null, Value::Int(_) | Value::Double(..)) => output(Value::Null),
+                    _ => set_error(
+                        env,
+                        format!(
+                            "Cannot index {} with {}",
+                            input.type_name(),
+                            idx_val.type_name()
+                        ),
+                    ),
+                });
             }
         }
         "recurse" => {
@@ -799,50 +700,7 @@ pub(super) fn eval_arrays(
         }
         "walk" => {
             if let Some(f) = args.first() {
-                fn walk_inner(value: &Value, f: &Filter, env: &Env, output: &mut dyn FnMut(Value)) {
-                    match value {
-                        Value::Array(arr) => {
-                            let mut new_arr = Vec::with_capacity(arr.len());
-                            for v in arr.iter() {
-                                // Take only first output from recursive walk
-                                let mut first = None;
-                                walk_inner(v, f, env, &mut |walked| {
-                                    if first.is_none() {
-                                        first = Some(walked);
-                                    }
-                                });
-                                if let Some(walked) = first {
-                                    new_arr.push(walked);
-                                }
-                                // No output → element removed (e.g., select filtered it)
-                            }
-                            let reconstructed = Value::Array(Arc::new(new_arr));
-                            eval(f, &reconstructed, env, output);
-                        }
-                        Value::Object(obj) => {
-                            let mut new_obj = Vec::with_capacity(obj.len());
-                            for (k, v) in obj.iter() {
-                                // Take only first output from recursive walk
-                                let mut first = None;
-                                walk_inner(v, f, env, &mut |walked| {
-                                    if first.is_none() {
-                                        first = Some(walked);
-                                    }
-                                });
-                                if let Some(walked) = first {
-                                    new_obj.push((k.clone(), walked));
-                                }
-                                // No output → key removed (e.g., select filtered it)
-                            }
-                            let reconstructed = Value::Object(Arc::new(new_obj));
-                            eval(f, &reconstructed, env, output);
-                        }
-                        _ => {
-                            eval(f, value, env, output);
-                        }
-                    }
-                }
-                walk_inner(input, f, env, output);
+                walk(input, f, env, output);
             }
         }
         "bsearch" => {
@@ -871,7 +729,7 @@ pub(super) fn eval_arrays(
                         });
                     }
                     _ => {
-                        set_error(format!(
+                        set_error(env, format!(
                             "{} ({}) cannot be searched from",
                             input.type_name(),
                             input.short_desc()
@@ -1043,22 +901,27 @@ pub(super) fn eval_arrays(
                     &mut Vec::new(),
                     env,
                     &mut |path_val| {
-                        if had_error {
+                        if had_error || env.has_error() {
                             return;
                         }
                         if let Value::Array(path_arr) = &path_val {
-                            let val = super::super::value_ops::get_path(input, path_arr);
-                            match super::super::value_ops::set_path(&acc, path_arr, &val) {
+                            let set = super::super::value_ops::get_path(input, path_arr)
+                                .and_then(|val| {
+                                    super::super::value_ops::set_path(&acc, path_arr, &val)
+                                });
+                            match set {
                                 Ok(v) => acc = v,
                                 Err(msg) => {
-                                    set_error(msg);
+                                    set_error(env, msg);
                                     had_error = true;
                                 }
                             }
                         }
                     },
                 );
-                if !had_error {
+                // An error in the path expression itself, e.g. `pick(keys)`
+                // or `.a.b` through a number, also means no output.
+                if !had_error && !env.has_error() {
                     output(acc);
                 }
             }
@@ -1107,11 +970,11 @@ pub(super) fn eval_arrays(
             }
             _ => {}
         },
-        "JOIN" => {
+        "JOIN"
             // JOIN(idx; key_expr) — join with lookup table
             // idx is a filter that produces an object (the index)
             // For each input element, looks up key_expr in the index
-            if args.len() == 2 {
+            if args.len() == 2 => {
                 // First evaluate the index
                 let mut index = Value::Null;
                 eval(&args[0], input, env, &mut |v| index = v);
@@ -1142,11 +1005,57 @@ pub(super) fn eval_arrays(
                 }
                 output(Value::Array(Arc::new(results)));
             }
-        }
         _ => {}
     }
 }
 
+/// Evaluate a `sort_by`-style key filter against each element. Like jq's
+/// `map([f])`, every output of `f` is collected, so `sort_by(.a, .b)` compares
+/// `[.a, .b]` lexicographically. Returns `None` if `f` raised an error, which is
+/// left in `env` for the caller to propagate. An error already pending when
+/// this is called isn't `f`'s, and is put back untouched.
+fn eval_keys(f: &Filter, arr: &[Value], env: &Env) -> Option<Vec<(Value, Value)>> {
+    let pending = env.take_error();
+    let mut pairs = Vec::with_capacity(arr.len());
+    for item in arr {
+        let mut keys = Vec::new();
+        eval(f, item, env, &mut |v| keys.push(v));
+        if env.has_error() {
+            return None;
+        }
+        pairs.push((Value::Array(Arc::new(keys)), item.clone()));
+    }
+    if let Some(err) = pending {
+        env.set_error(err);
+    }
+    Some(pairs)
+}
+
+/// `eval_keys` followed by a stable sort on the keys.
+fn sorted_by_keys(f: &Filter, arr: &[Value], env: &Env) -> Option<Vec<(Value, Value)>> {
+    let mut pairs = eval_keys(f, arr, env)?;
+    pairs.sort_by(|(a, _), (b, _)| values_order(a, b).unwrap_or(std::cmp::Ordering::Equal));
+    Some(pairs)
+}
+
+/// Split key-sorted pairs into runs of equal keys.
+fn group_sorted(pairs: Vec<(Value, Value)>) -> Vec<Vec<Value>> {
+    let mut groups: Vec<Vec<Value>> = Vec::new();
+    let mut current_key: Option<Value> = None;
+    for (key, val) in pairs {
+        match groups.last_mut() {
+            Some(group) if current_key.as_ref().is_some_and(|k| values_equal(k, &key)) => {
+                group.push(val);
+            }
+            _ => {
+                current_key = Some(key);
+                groups.push(vec![val]);
+            }
+        }
+    }
+    groups
+}
+
 fn flatten_array(arr: &[Value], depth: i64, result: &mut Vec<Value>) {
     for item in arr {
         if let Value::Array(inner) = item
@@ -1159,6 +1068,94 @@ fn flatten_array(arr: &[Value], depth: i64, result: &mut Vec<Value>) {
     }
 }
 
+/// A container `walk` has descended into: the children still to walk and
+/// those already walked (a child `f` produced no output for is dropped).
+enum WalkFrame {
+    Array {
+        pending: std::vec::IntoIter<Value>,
+        walked: Vec<Value>,
+    },
+    Object {
+        pending: std::vec::IntoIter<(String, Value)>,
+        walked: Vec<(String, Value)>,
+        key: String,
+    },
+}
+
+/// `walk(f)`: apply `f` bottom-up, to each child before its container. Runs
+/// on an explicit stack, so arbitrarily deep values don't reach the
+/// evaluator's depth limit. A child takes `f`'s first output; the root
+/// emits all of them. Containers nobody else holds are taken apart in place.
+fn walk(root: &Value, f: &Filter, env: &Env, output: &mut dyn FnMut(Value)) {
+    let mut stack: Vec<WalkFrame> = Vec::new();
+    let mut next = root.clone();
+    loop {
+        // `done` holds a value whose children are all walked, ready for `f`.
+        let mut done = match next {
+            Value::Array(arr) => {
+                let items = Arc::unwrap_or_clone(arr);
+                stack.push(WalkFrame::Array {
+                    walked: Vec::with_capacity(items.len()),
+                    pending: items.into_iter(),
+                });
+                None
+            }
+            Value::Object(obj) => {
+                let fields = Arc::unwrap_or_clone(obj);
+                stack.push(WalkFrame::Object {
+                    walked: Vec::with_capacity(fields.len()),
+                    pending: fields.into_iter(),
+                    key: String::new(),
+                });
+                None
+            }
+            leaf => Some(leaf),
+        };
+        next = loop {
+            if let Some(value) = done.take() {
+                let Some(parent) = stack.last_mut() else {
+                    eval(f, &value, env, output);
+                    return;
+                };
+                let mut first = None;
+                eval_until(f, &value, env, &mut |v| {
+                    first = Some(v);
+                    false
+                });
+                if env.has_error() {
+                    return;
+                }
+                if let Some(v) = first {
+                    match parent {
+                        WalkFrame::Array { walked, .. } => walked.push(v),
+                        WalkFrame::Object { walked, key, .. } => {
+                            walked.push((std::mem::take(key), v))
+                        }
+                    }
+                }
+            }
+            let child = match stack.last_mut() {
+                Some(WalkFrame::Array { pending, .. }) => pending.next(),
+                Some(WalkFrame::Object { pending, key, .. }) => pending.next().map(|(k, v)| {
+                    *key = k;
+                    v
+                }),
+                None => unreachable!("walk's stack empties only at the root"),
+            };
+            match child {
+                Some(child) => break child,
+                None => {
+                    done = Some(match stack.pop() {
+                        Some(WalkFrame::Array { walked, .. }) => Value::Array(Arc::new(walked)),
+                        Some(WalkFrame::Object { walked, .. }) => Value::Object(Arc::new(walked)),
+                        None => unreachable!(),
+                    });
+                }
+            }
+        };
+    }
+}
+
 fn recurse_with_filter(
     f: &Filter,
     value: &Value,
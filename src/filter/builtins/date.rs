@@ -1,13 +1,55 @@
-use crate::filter::{Env, Filter};
+use crate::filter::{ArithOp, Env, Filter};
 use crate::value::Value;
 
 use super::super::eval::eval;
 use super::super::value_ops::{
-    bdtime_strftime, bdtime_to_epoch, epoch_to_bdtime, format_strftime_jiff, format_strftime_local,
-    fromdate, input_as_f64, now_timestamp, strptime_to_bdtime, todate,
+    arith_values, bdtime_strftime, bdtime_to_epoch, epoch_to_bdtime, format_strftime_jiff,
+    format_strftime_local, fromdate, input_as_f64, now_timestamp, strptime_to_bdtime, todate,
 };
 use super::set_error;
 
+/// Seconds per unit accepted by `dateadd`/`datesub`.
+fn unit_seconds(unit: &str) -> Option<i64> {
+    match unit {
+        "seconds" => Some(1),
+        "minutes" => Some(60),
+        "hours" => Some(3600),
+        "days" => Some(86400),
+        _ => None,
+    }
+}
+
+/// `ts + n units` (or minus, for `datesub`), with `ts` in seconds since the epoch.
+fn date_offset(name: &str, ts: &Value, unit: &Value, n: &Value) -> Result<Value, String> {
+    let Value::String(u) = unit else {
+        return Err(format!(
+            "{name}/2 unit must be a string, not {} ({})",
+            unit.type_name(),
+            unit.short_desc()
+        ));
+    };
+    let Some(scale) = unit_seconds(u) else {
+        return Err(format!(
+            "{name}/2: unknown unit {} (expected \"seconds\", \"minutes\", \"hours\" or \"days\")",
+            unit.short_desc()
+        ));
+    };
+    if !matches!(ts, Value::Int(_) | Value::Double(..)) {
+        return Err(format!(
+            "{name}/2 requires a number of seconds since the epoch, not {} ({})",
+            ts.type_name(),
+            ts.short_desc()
+        ));
+    }
+    let delta = arith_values(n, &ArithOp::Mul, &Value::Int(scale))?;
+    let op = if name == "dateadd" {
+        ArithOp::Add
+    } else {
+        ArithOp::Sub
+    };
+    arith_values(ts, &op, &delta)
+}
+
 pub(super) fn eval_date(
     name: &str,
     args: &[Filter],
@@ -16,18 +58,51 @@ pub(super) fn eval_date(
     output: &mut dyn FnMut(Value),
 ) {
     match name {
-        "todate" => {
-            if let Some(ts) = input_as_f64(input)
-                && let Some(s) = todate(ts as i64)
+        "todate" | "todateiso8601" | "date" => {
+            if let Some(ts) = input_as_f64(input) {
+                if let Some(s) = todate(ts as i64) {
+                    output(Value::String(s));
+                }
+            } else if let Value::Array(arr) = input
+                && let Some(s) = bdtime_strftime(arr, "%Y-%m-%dT%H:%M:%SZ", true)
             {
                 output(Value::String(s));
+            } else {
+                set_error(
+                    env,
+                    "strftime/1 requires parsed datetime inputs".to_string(),
+                );
             }
         }
-        "fromdate" => {
-            if let Value::String(s) = input
-                && let Some(ts) = fromdate(s)
-            {
-                output(Value::Int(ts));
+        "fromdate" | "fromdateiso8601" => match input {
+            Value::String(s) => match fromdate(s) {
+                Some(ts) => output(Value::Int(ts)),
+                None => set_error(
+                    env,
+                    format!(
+                        "date {} does not match format \"%Y-%m-%dT%H:%M:%SZ\"",
+                        input.short_desc()
+                    ),
+                ),
+            },
+            _ => set_error(
+                env,
+                "strptime/1 requires string inputs and arguments".to_string(),
+            ),
+        },
+        "dateadd" | "datesub" => {
+            if let [unit_f, n_f] = args {
+                eval(unit_f, input, env, &mut |unit| {
+                    eval(
+                        n_f,
+                        input,
+                        env,
+                        &mut |n| match date_offset(name, input, &unit, &n) {
+                            Ok(v) => output(v),
+                            Err(msg) => set_error(env, msg),
+                        },
+                    );
+                });
             }
         }
         "now" => {
@@ -44,7 +119,7 @@ pub(super) fn eval_date(
                     }
                 });
                 if !fmt_ok {
-                    set_error("strftime/1 requires a string format".to_string());
+                    set_error(env, "strftime/1 requires a string format".to_string());
                     return;
                 }
                 // Input can be a number (epoch) or a broken-down time array
@@ -52,14 +127,20 @@ pub(super) fn eval_date(
                     if let Some(s) = bdtime_strftime(arr, &fmt, true) {
                         output(Value::String(s));
                     } else {
-                        set_error("strftime/1 requires parsed datetime inputs".to_string());
+                        set_error(
+                            env,
+                            "strftime/1 requires parsed datetime inputs".to_string(),
+                        );
                     }
                 } else if let Some(ts) = input_as_f64(input) {
                     if let Some(s) = format_strftime_jiff(&fmt, ts as i64) {
                         output(Value::String(s));
                     }
                 } else if !matches!(input, Value::Null) {
-                    set_error("strftime/1 requires parsed datetime inputs".to_string());
+                    set_error(
+                        env,
+                        "strftime/1 requires parsed datetime inputs".to_string(),
+                    );
                 }
             }
         }
@@ -82,10 +163,10 @@ pub(super) fn eval_date(
                 if let Some(epoch) = bdtime_to_epoch(arr) {
                     output(Value::Int(epoch));
                 } else {
-                    set_error("mktime requires parsed datetime inputs".to_string());
+                    set_error(env, "mktime requires parsed datetime inputs".to_string());
                 }
             } else {
-                set_error("mktime requires parsed datetime inputs".to_string());
+                set_error(env, "mktime requires array inputs".to_string());
             }
         }
         "strptime" => {
@@ -115,7 +196,7 @@ pub(super) fn eval_date(
                     }
                 });
                 if fmt_error {
-                    set_error("strflocaltime/1 requires a string format".to_string());
+                    set_error(env, "strflocaltime/1 requires a string format".to_string());
                 } else {
                     for fmt in &fmts {
                         match input {
@@ -124,6 +205,7 @@ pub(super) fn eval_date(
                                     output(Value::String(s));
                                 } else {
                                     set_error(
+                                        env,
                                         "strflocaltime/1 requires parsed datetime inputs"
                                             .to_string(),
                                     );
@@ -144,3 +226,64 @@ pub(super) fn eval_date(
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(name: &str, ts: Value, unit: &str, n: Value) -> Result<Value, String> {
+        date_offset(name, &ts, &Value::String(unit.into()), &n)
+    }
+
+    #[test]
+    fn dateadd_scales_by_unit() {
+        assert_eq!(
+            offset("dateadd", Value::Int(0), "seconds", Value::Int(5)),
+            Ok(Value::Int(5))
+        );
+        assert_eq!(
+            offset("dateadd", Value::Int(0), "minutes", Value::Int(2)),
+            Ok(Value::Int(120))
+        );
+        assert_eq!(
+            offset("dateadd", Value::Int(100), "hours", Value::Int(-1)),
+            Ok(Value::Int(-3500))
+        );
+        assert_eq!(
+            offset("dateadd", Value::Int(0), "days", Value::Double(0.5, None)),
+            Ok(Value::Double(43200.0, None))
+        );
+    }
+
+    #[test]
+    fn datesub_subtracts() {
+        assert_eq!(
+            offset("datesub", Value::Int(86400), "days", Value::Int(1)),
+            Ok(Value::Int(0))
+        );
+        assert_eq!(
+            offset(
+                "datesub",
+                Value::Double(1.5, None),
+                "seconds",
+                Value::Int(1)
+            ),
+            Ok(Value::Double(0.5, None))
+        );
+    }
+
+    #[test]
+    fn date_offset_errors() {
+        let err = offset("dateadd", Value::Int(0), "weeks", Value::Int(1)).unwrap_err();
+        assert!(
+            err.starts_with("dateadd/2: unknown unit \"weeks\""),
+            "{err}"
+        );
+        let err =
+            date_offset("datesub", &Value::Int(0), &Value::Int(1), &Value::Int(1)).unwrap_err();
+        assert_eq!(err, "datesub/2 unit must be a string, not number (1)");
+        let err = offset("dateadd", Value::String("x".into()), "days", Value::Int(1)).unwrap_err();
+        assert!(err.contains("requires a number of seconds"), "{err}");
+        assert!(offset("dateadd", Value::Int(0), "days", Value::String("1".into())).is_err());
+    }
+}
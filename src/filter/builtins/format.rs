@@ -1,37 +1,58 @@
-use super::set_error;
+use super::{set_error, value_to_string};
 use crate::filter::{Env, Filter};
 use crate::value::Value;
 
-/// Convert a value to string the way jq's `tostring` does:
-/// strings pass through, scalars become their text representation,
-/// arrays/objects become compact JSON.
-fn value_to_string(v: &Value) -> String {
-    match v {
-        Value::String(s) => s.clone(),
-        Value::Int(n) => itoa::Buffer::new().format(*n).to_string(),
-        Value::Double(f, _) => {
-            // Use write_compact to get the same formatting as output
-            let mut buf = Vec::new();
-            crate::output::write_compact(&mut buf, v, false).unwrap();
-            // write_compact wraps in quotes for strings, but for doubles
-            // it writes the bare number — that's what we want
-            String::from_utf8(buf).unwrap_or_else(|_| ryu::Buffer::new().format(*f).to_string())
-        }
-        Value::Bool(b) => if *b { "true" } else { "false" }.to_string(),
-        Value::Null => "null".to_string(),
-        Value::Array(_) | Value::Object(_) => {
-            let mut buf = Vec::new();
-            crate::output::write_compact(&mut buf, v, false).unwrap();
-            String::from_utf8(buf).unwrap_or_default()
-        }
+/// Shared @csv/@tsv row formatting. Numbers are rendered as by `tostring`
+/// (NaN as an empty field), booleans bare, null as an empty field, and strings
+/// through `quote`. Arrays and objects are errors, as is a non-array input.
+fn format_row(
+    input: &Value,
+    env: &Env,
+    kind: &str,
+    sep: &str,
+    output: &mut dyn FnMut(Value),
+    quote: impl Fn(&str) -> String,
+) {
+    let Value::Array(arr) = input else {
+        set_error(
+            env,
+            format!(
+                "{} ({}) cannot be {kind}-formatted, only array",
+                input.type_name(),
+                input.short_desc()
+            ),
+        );
+        return;
+    };
+    let mut parts: Vec<String> = Vec::with_capacity(arr.len());
+    for v in arr.iter() {
+        parts.push(match v {
+            Value::String(s) => quote(s),
+            Value::Double(f, _) if f.is_nan() => String::new(),
+            Value::Int(_) | Value::Double(..) | Value::Bool(_) => value_to_string(v),
+            Value::Null => String::new(),
+            Value::Array(_) | Value::Object(_) => {
+                // jq says "csv row" for @tsv too.
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) is not valid in a csv row",
+                        v.type_name(),
+                        v.short_desc()
+                    ),
+                );
+                return;
+            }
+        });
     }
+    output(Value::String(parts.join(sep)));
 }
 
 pub(super) fn eval_format(
     name: &str,
     _args: &[Filter],
     input: &Value,
-    _env: &Env,
+    env: &Env,
     output: &mut dyn FnMut(Value),
 ) {
     match name {
@@ -40,18 +61,7 @@ pub(super) fn eval_format(
             crate::output::write_compact(&mut buf, input, false).unwrap();
             output(Value::String(String::from_utf8(buf).unwrap_or_default()));
         }
-        "@text" => match input {
-            Value::String(_) => output(input.clone()),
-            Value::Int(n) => output(Value::String(itoa::Buffer::new().format(*n).into())),
-            Value::Double(f, _) => output(Value::String(ryu::Buffer::new().format(*f).into())),
-            Value::Bool(b) => output(Value::String(if *b { "true" } else { "false" }.into())),
-            Value::Null => output(Value::String("null".into())),
-            Value::Array(_) | Value::Object(_) => {
-                let mut buf = Vec::new();
-                crate::output::write_compact(&mut buf, input, false).unwrap();
-                output(Value::String(String::from_utf8(buf).unwrap_or_default()));
-            }
-        },
+        "@text" => output(Value::String(value_to_string(input))),
         "@html" => {
             let s = value_to_string(input);
             let mut out = String::with_capacity(s.len());
@@ -118,105 +128,55 @@ pub(super) fn eval_format(
                 String::from_utf8(decoded_bytes).unwrap_or_default(),
             ));
         }
-        "@csv" => {
-            if let Value::Array(arr) = input {
-                if let Some(bad) = arr
-                    .iter()
-                    .find(|v| matches!(v, Value::Array(_) | Value::Object(_)))
-                {
-                    set_error(format!(
-                        "{} ({}) is not valid in a csv row",
-                        bad.type_name(),
-                        bad.short_desc()
-                    ));
-                    return;
-                }
-                let parts: Vec<String> = arr
-                    .iter()
-                    .map(|v| match v {
-                        Value::String(s) => {
-                            let escaped = s.replace('"', "\"\"");
-                            format!("\"{escaped}\"")
-                        }
-                        Value::Int(n) => itoa::Buffer::new().format(*n).to_string(),
-                        Value::Double(f, _) => ryu::Buffer::new().format(*f).to_string(),
-                        Value::Bool(b) => (if *b { "true" } else { "false" }).to_string(),
-                        Value::Null => "".to_string(),
-                        _ => String::new(),
-                    })
-                    .collect();
-                output(Value::String(parts.join(",")));
-            } else {
-                set_error(format!(
-                    "{} ({}) cannot be csv-formatted, only array",
-                    input.type_name(),
-                    input.short_desc()
-                ));
-            }
-        }
-        "@tsv" => {
-            if let Value::Array(arr) = input {
-                if let Some(bad) = arr
-                    .iter()
-                    .find(|v| matches!(v, Value::Array(_) | Value::Object(_)))
-                {
-                    set_error(format!(
-                        "{} ({}) is not valid in a tsv row",
-                        bad.type_name(),
-                        bad.short_desc()
-                    ));
-                    return;
-                }
-                let parts: Vec<String> = arr
-                    .iter()
-                    .map(|v| match v {
-                        Value::String(s) => s
-                            .replace('\\', "\\\\")
-                            .replace('\t', "\\t")
-                            .replace('\n', "\\n")
-                            .replace('\r', "\\r"),
-                        Value::Int(n) => itoa::Buffer::new().format(*n).to_string(),
-                        Value::Double(f, _) => ryu::Buffer::new().format(*f).to_string(),
-                        Value::Bool(b) => (if *b { "true" } else { "false" }).to_string(),
-                        Value::Null => "".to_string(),
-                        _ => String::new(),
-                    })
-                    .collect();
-                output(Value::String(parts.join("\t")));
-            } else {
-                set_error(format!(
-                    "{} ({}) cannot be tsv-formatted, only array",
-                    input.type_name(),
-                    input.short_desc()
-                ));
-            }
-        }
+        "@csv" => format_row(input, env, "csv", ",", output, |s| {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        }),
+        "@tsv" => format_row(input, env, "tsv", "\t", output, |s| {
+            s.replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        }),
         "@sh" => {
-            fn sh_escape(v: &Value) -> Result<String, ()> {
+            // Strings are single-quoted with each `'` written as `'\''`;
+            // numbers, booleans and null go bare. An array gives its elements
+            // space-separated; anything nested is an error, as in jq. So is a
+            // NUL, which no shell word can carry.
+            fn sh_word(v: &Value, line: &mut String) -> Result<(), String> {
                 match v {
-                    Value::String(s) => {
-                        let escaped = s.replace('\'', "'\\''");
-                        Ok(format!("'{escaped}'"))
+                    Value::String(s) if !s.contains('\0') => {
+                        line.push('\'');
+                        line.push_str(&s.replace('\'', "'\\''"));
+                        line.push('\'');
                     }
-                    Value::Int(n) => Ok(itoa::Buffer::new().format(*n).to_string()),
-                    Value::Double(f, _) => {
-                        let mut buf = Vec::new();
-                        crate::output::write_compact(&mut buf, v, false).unwrap();
-                        Ok(String::from_utf8(buf)
-                            .unwrap_or_else(|_| ryu::Buffer::new().format(*f).to_string()))
+                    Value::Int(_) | Value::Double(..) | Value::Bool(_) | Value::Null => {
+                        line.push_str(&value_to_string(v));
                     }
-                    Value::Bool(b) => Ok(if *b { "true" } else { "false" }.to_string()),
-                    Value::Null => Ok("null".to_string()),
-                    Value::Array(arr) => {
-                        let parts: Result<Vec<String>, ()> = arr.iter().map(sh_escape).collect();
-                        Ok(parts?.join(" "))
+                    _ => {
+                        return Err(format!(
+                            "{} ({}) can not be escaped for shell",
+                            v.type_name(),
+                            v.short_desc()
+                        ));
                     }
-                    Value::Object(_) => Err(()),
                 }
+                Ok(())
             }
-            if let Ok(s) = sh_escape(input) {
-                output(Value::String(s));
+            let words = match input {
+                Value::Array(arr) => arr.as_slice(),
+                single => std::slice::from_ref(single),
+            };
+            let mut line = String::new();
+            for (i, word) in words.iter().enumerate() {
+                if i > 0 {
+                    line.push(' ');
+                }
+                if let Err(msg) = sh_word(word, &mut line) {
+                    set_error(env, msg);
+                    return;
+                }
             }
+            output(Value::String(line));
         }
         "@base64" => {
             use base64::Engine;
@@ -226,18 +186,87 @@ pub(super) fn eval_format(
             ));
         }
         "@base64d" => {
-            use base64::Engine;
             let s = value_to_string(input);
-            if let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(s.as_bytes())
-                && let Ok(text) = String::from_utf8(decoded)
-            {
-                output(Value::String(text));
+            match base64_decode(s.as_bytes()) {
+                Ok(decoded) => output(Value::String(utf8_lossy(&decoded))),
+                Err(reason) => {
+                    let s = Value::String(s);
+                    set_error(env, format!("string ({}) {reason}", s.short_desc()));
+                }
             }
         }
         _ => {}
     }
 }
 
+/// Decode base64 as jq does: input ends at the first `=`, so padding is
+/// optional and anything after it ignored; any other character outside the
+/// alphabet is an error, as is a lone 6-bit group at the end.
+fn base64_decode(data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3 + 2);
+    let mut code: u32 = 0;
+    let mut pending = 0;
+    for &b in data.iter().take_while(|&&b| b != b'=') {
+        let bits = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err("is not valid base64 data"),
+        };
+        code = code << 6 | u32::from(bits);
+        pending += 1;
+        if pending == 4 {
+            out.extend_from_slice(&code.to_be_bytes()[1..]);
+            code = 0;
+            pending = 0;
+        }
+    }
+    match pending {
+        3 => out.extend_from_slice(&(code >> 2).to_be_bytes()[2..]),
+        2 => out.push((code >> 4) as u8),
+        1 => return Err("trailing base64 byte found"),
+        _ => {}
+    }
+    Ok(out)
+}
+
+/// Decode UTF-8 as jq does, with one U+FFFD per invalid sequence: a stray
+/// byte, a sequence cut short, or one encoding an overlong form, a surrogate
+/// or a codepoint past U+10FFFF.
+fn utf8_lossy(bytes: &[u8]) -> String {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return s.to_owned();
+    }
+    let mut out = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let len = match bytes[i] {
+            0x00..=0x7f => 1,
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => {
+                out.push('\u{fffd}');
+                i += 1;
+                continue;
+            }
+        };
+        let end = (i + len).min(bytes.len());
+        let seq_len = bytes[i + 1..end]
+            .iter()
+            .position(|&b| b & 0xc0 != 0x80)
+            .map_or(end - i, |p| p + 1);
+        match std::str::from_utf8(&bytes[i..i + seq_len]) {
+            Ok(s) => out.push_str(s),
+            Err(_) => out.push('\u{fffd}'),
+        }
+        i += seq_len;
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -259,6 +288,9 @@ mod tests {
             out,
             vec![Value::String("&lt;b&gt;a &amp; b&lt;/b&gt;".into())]
         );
+        let input = Value::String(r#"it's "x""#.into());
+        let out = run_builtin("@html", &input);
+        assert_eq!(out, vec![Value::String("it&apos;s &quot;x&quot;".into())]);
     }
 
     #[test]
@@ -279,6 +311,35 @@ mod tests {
         assert_eq!(out, vec![Value::String("1,\"two\",3".into())]);
     }
 
+    #[test]
+    fn format_tsv_escapes_and_scalars() {
+        let input = Value::Array(Arc::new(vec![
+            Value::String("a\tb\r\n\\".into()),
+            Value::Null,
+            Value::Bool(false),
+            Value::Double(f64::NAN, None),
+        ]));
+        let out = run_builtin("@tsv", &input);
+        assert_eq!(
+            out,
+            vec![Value::String("a\\tb\\r\\n\\\\\t\tfalse\t".into())]
+        );
+    }
+
+    #[test]
+    fn format_tsv_rejects_nested_with_csv_message() {
+        let input = Value::Array(Arc::new(vec![Value::Array(Arc::new(vec![]))]));
+        let env = Env::empty();
+        eval_format("@tsv", &[], &input, &env, &mut |_| {
+            panic!("no output expected")
+        });
+        let err = env.take_error().unwrap();
+        assert_eq!(
+            err,
+            Value::String("array ([]) is not valid in a csv row".into())
+        );
+    }
+
     #[test]
     fn format_sh_quotes() {
         let input = Value::String("it's a test".into());
@@ -286,6 +347,29 @@ mod tests {
         assert_eq!(out, vec![Value::String("'it'\\''s a test'".into())]);
     }
 
+    #[test]
+    fn format_sh_rejects_nested_and_nul() {
+        for (input, message) in [
+            (
+                Value::Array(Arc::new(vec![
+                    Value::Int(1),
+                    Value::Array(Arc::new(vec![])),
+                ])),
+                "array ([]) can not be escaped for shell",
+            ),
+            (
+                Value::String("a\0b".into()),
+                "string (\"a\\u0000b\") can not be escaped for shell",
+            ),
+        ] {
+            let env = Env::empty();
+            eval_format("@sh", &[], &input, &env, &mut |_| {
+                panic!("no output expected")
+            });
+            assert_eq!(env.take_error().unwrap(), Value::String(message.into()));
+        }
+    }
+
     #[test]
     fn format_base64_roundtrip() {
         let input = Value::String("hello".into());
@@ -295,6 +379,25 @@ mod tests {
         assert_eq!(decoded, vec![Value::String("hello".into())]);
     }
 
+    #[test]
+    fn base64_decode_like_jq() {
+        assert_eq!(base64_decode(b"aGVsbG8"), Ok(b"hello".to_vec()));
+        assert_eq!(base64_decode(b"aGVs=x"), Ok(b"hel".to_vec()));
+        assert_eq!(base64_decode(b"//4="), Ok(vec![0xff, 0xfe]));
+        assert_eq!(base64_decode(b"aGVsb"), Err("trailing base64 byte found"));
+        assert_eq!(base64_decode(b"aGV sbG8"), Err("is not valid base64 data"));
+    }
+
+    #[test]
+    fn utf8_lossy_one_replacement_per_sequence() {
+        assert_eq!(utf8_lossy(b"caf\xc3\xa9"), "caf\u{e9}");
+        // Stray bytes, a surrogate, an overlong form and a cut-short tail.
+        assert_eq!(
+            utf8_lossy(b"\xff\x80a\xed\xa0\x80\xc0\x80\xe2\x9c"),
+            "\u{fffd}\u{fffd}a\u{fffd}\u{fffd}\u{fffd}\u{fffd}"
+        );
+    }
+
     #[test]
     fn format_json() {
         let input = Value::Array(Arc::new(vec![Value::Int(1), Value::Int(2)]));
@@ -3,25 +3,43 @@ use crate::value::Value;
 
 use super::super::eval::eval;
 use super::super::value_ops::{
-    f64_to_value, input_as_f64, libc_frexp, libc_j0, libc_j1, libc_ldexp, libc_logb, to_f64,
+    f64_to_value, input_as_f64, libc_frexp, libc_j0, libc_j1, libc_ldexp, libc_logb, negate_value,
+    to_f64,
 };
 use super::set_error;
 
 /// Get input as f64, or set an error for non-number types.
-fn require_number(input: &Value) -> Option<f64> {
+fn require_number(input: &Value, env: &Env) -> Option<f64> {
     match input_as_f64(input) {
         Some(f) => Some(f),
         None => {
-            set_error(format!(
-                "{} ({}) number required",
-                input.type_name(),
-                input.short_desc()
-            ));
+            set_error(
+                env,
+                format!(
+                    "{} ({}) number required",
+                    input.type_name(),
+                    input.short_desc()
+                ),
+            );
             None
         }
     }
 }
 
+/// A `range` argument as f64, or an error for non-numbers.
+fn range_bound(v: &Value, env: &Env) -> Option<f64> {
+    let f = input_as_f64(v);
+    if f.is_none() {
+        set_error(env, "Range bounds must be numeric".to_string());
+    }
+    f
+}
+
+/// Whether a `break` is unwinding, so generators should stop.
+fn breaking(env: &Env) -> bool {
+    env.break_signal().borrow().is_some()
+}
+
 pub(super) fn eval_math(
     name: &str,
     args: &[Filter],
@@ -33,9 +51,12 @@ pub(super) fn eval_math(
         "range" => match args.len() {
             1 => {
                 eval(&args[0], input, env, &mut |nv| {
-                    let n = to_f64(&nv);
+                    let Some(n) = range_bound(&nv, env) else {
+                        return;
+                    };
                     let mut i = 0.0;
-                    while i < n {
+                    // Stop once a `first`/`limit`/`label` above has broken out
+                    while i < n && !breaking(env) {
                         output(f64_to_value(i));
                         i += 1.0;
                     }
@@ -44,10 +65,13 @@ pub(super) fn eval_math(
             2 => {
                 eval(&args[0], input, env, &mut |from_v| {
                     eval(&args[1], input, env, &mut |to_v| {
-                        let from = to_f64(&from_v);
-                        let to = to_f64(&to_v);
+                        let (Some(from), Some(to)) =
+                            (range_bound(&from_v, env), range_bound(&to_v, env))
+                        else {
+                            return;
+                        };
                         let mut i = from;
-                        while i < to {
+                        while i < to && !breaking(env) {
                             output(f64_to_value(i));
                             i += 1.0;
                         }
@@ -58,20 +82,27 @@ pub(super) fn eval_math(
                 eval(&args[0], input, env, &mut |from_v| {
                     eval(&args[1], input, env, &mut |to_v| {
                         eval(&args[2], input, env, &mut |step_v| {
-                            let from = to_f64(&from_v);
-                            let to = to_f64(&to_v);
-                            let step = to_f64(&step_v);
+                            let (Some(from), Some(to), Some(step)) = (
+                                range_bound(&from_v, env),
+                                range_bound(&to_v, env),
+                                range_bound(&step_v, env),
+                            ) else {
+                                return;
+                            };
                             if step == 0.0 {
+                                set_error(env, "range cannot have a zero step".to_string());
                                 return;
                             }
+                            // Accumulate rather than multiply, so float steps
+                            // drift exactly as they do in jq.
                             let mut i = from;
                             if step > 0.0 {
-                                while i < to {
+                                while i < to && !breaking(env) {
                                     output(f64_to_value(i));
                                     i += step;
                                 }
                             } else {
-                                while i > to {
+                                while i > to && !breaking(env) {
                                     output(f64_to_value(i));
                                     i += step;
                                 }
@@ -83,132 +114,132 @@ pub(super) fn eval_math(
             _ => {}
         },
         "floor" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(f64_to_value(f.floor()));
             }
         }
         "ceil" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(f64_to_value(f.ceil()));
             }
         }
         "round" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(f64_to_value(f.round()));
             }
         }
         "trunc" | "truncate" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(f64_to_value(f.trunc()));
             }
         }
         "fabs" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.abs(), None));
             }
         }
         "sqrt" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.sqrt(), None));
             }
         }
         "cbrt" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.cbrt(), None));
             }
         }
         "log" | "log_e" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.ln(), None));
             }
         }
         "log2" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.log2(), None));
             }
         }
         "log10" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.log10(), None));
             }
         }
         "logb" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(libc_logb(f), None));
             }
         }
         "exp" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.exp(), None));
             }
         }
         "exp2" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.exp2(), None));
             }
         }
         "sin" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.sin(), None));
             }
         }
         "cos" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.cos(), None));
             }
         }
         "tan" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.tan(), None));
             }
         }
         "asin" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.asin(), None));
             }
         }
         "acos" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.acos(), None));
             }
         }
         "atan" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.atan(), None));
             }
         }
         "sinh" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.sinh(), None));
             }
         }
         "cosh" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.cosh(), None));
             }
         }
         "tanh" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.tanh(), None));
             }
         }
         "asinh" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.asinh(), None));
             }
         }
         "acosh" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.acosh(), None));
             }
         }
         "atanh" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(f.atanh(), None));
             }
         }
         "significand" | "nearbyint" | "rint" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 let result = match name {
                     "significand" => {
                         if f == 0.0 {
@@ -231,18 +262,18 @@ pub(super) fn eval_math(
             }
         }
         "exponent" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 let (_, exp) = libc_frexp(f);
                 output(Value::Int(exp as i64));
             }
         }
         "j0" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(libc_j0(f), None));
             }
         }
         "j1" => {
-            if let Some(f) = require_number(input) {
+            if let Some(f) = require_number(input, env) {
                 output(Value::Double(libc_j1(f), None));
             }
         }
@@ -330,24 +361,19 @@ pub(super) fn eval_math(
                 output(Value::Double(x.mul_add(y, z), None));
             }
         }
-        "abs" => match input {
-            Value::Int(n) => output(
-                n.checked_abs()
-                    .map_or_else(|| Value::Double((*n as f64).abs(), None), Value::Int),
-            ),
-            Value::Double(f, raw) => {
-                let abs_raw = if f.is_infinite() || *f == 0.0 {
-                    raw.as_ref().map(|s| {
-                        s.strip_prefix('-')
-                            .map_or_else(|| s.clone(), |rest| rest.into())
-                    })
-                } else {
-                    None
-                };
-                output(Value::Double(f.abs(), abs_raw));
+        // jq's definition: `if type == "number" and . < 0 then -. else . end`.
+        // Anything else, literal text included, passes through unchanged.
+        "abs" => {
+            let negative = match input {
+                Value::Int(n) => *n < 0,
+                Value::Double(f, _) => *f < 0.0,
+                _ => false,
+            };
+            match negate_value(input.clone()) {
+                Ok(negated) if negative => output(negated),
+                _ => output(input.clone()),
             }
-            _ => output(input.clone()),
-        },
+        }
         _ => {}
     }
 }
@@ -10,12 +10,336 @@ mod streaming;
 mod strings;
 mod types;
 
+pub use io::env_object;
+
 use crate::filter::{Env, Filter};
 use crate::value::Value;
 
+/// Every builtin the dispatcher implements, as `(name, arity)`. `builtins`
+/// lists these (less the internal `_` names), and `--check` rejects calls
+/// that match neither an entry here nor a definition in scope.
+pub(crate) const BUILTINS: &[(&str, usize)] = &[
+    ("length", 0),
+    ("utf8bytelength", 0),
+    ("keys", 0),
+    ("keys_unsorted", 0),
+    ("values", 0),
+    ("type", 0),
+    ("empty", 0),
+    ("not", 0),
+    ("null", 0),
+    ("true", 0),
+    ("false", 0),
+    ("numbers", 0),
+    ("strings", 0),
+    ("booleans", 0),
+    ("nulls", 0),
+    ("arrays", 0),
+    ("objects", 0),
+    ("iterables", 0),
+    ("scalars", 0),
+    ("map", 1),
+    ("select", 1),
+    ("add", 0),
+    ("any", 0),
+    ("any", 1),
+    ("any", 2),
+    ("all", 0),
+    ("all", 1),
+    ("all", 2),
+    ("has", 1),
+    ("to_entries", 0),
+    ("from_entries", 0),
+    ("with_entries", 1),
+    ("tostring", 0),
+    ("tonumber", 0),
+    ("toboolean", 0),
+    ("ascii_downcase", 0),
+    ("ascii_upcase", 0),
+    ("sort", 0),
+    ("sort_by", 1),
+    ("group_by", 1),
+    ("unique", 0),
+    ("unique_by", 1),
+    ("flatten", 0),
+    ("flatten", 1),
+    ("first", 0),
+    ("first", 1),
+    ("last", 0),
+    ("last", 1),
+    ("reverse", 0),
+    ("min", 0),
+    ("max", 0),
+    ("min_by", 1),
+    ("max_by", 1),
+    ("del", 1),
+    ("contains", 1),
+    ("inside", 1),
+    ("ltrimstr", 1),
+    ("rtrimstr", 1),
+    ("startswith", 1),
+    ("endswith", 1),
+    ("split", 1),
+    ("split", 2),
+    ("join", 1),
+    ("range", 1),
+    ("range", 2),
+    ("range", 3),
+    ("floor", 0),
+    ("ceil", 0),
+    ("round", 0),
+    ("sqrt", 0),
+    ("pow", 2),
+    ("log", 0),
+    ("log2", 0),
+    ("log10", 0),
+    ("exp", 0),
+    ("exp2", 0),
+    ("fabs", 0),
+    ("nan", 0),
+    ("infinite", 0),
+    ("isnan", 0),
+    ("isinfinite", 0),
+    ("isfinite", 0),
+    ("isnormal", 0),
+    ("abs", 0),
+    ("trim", 0),
+    ("ltrim", 0),
+    ("rtrim", 0),
+    ("index", 1),
+    ("rindex", 1),
+    ("indices", 1),
+    ("explode", 0),
+    ("implode", 0),
+    ("tojson", 0),
+    ("fromjson", 0),
+    ("transpose", 0),
+    ("map_values", 1),
+    ("limit", 2),
+    ("until", 2),
+    ("while", 2),
+    ("isempty", 1),
+    ("getpath", 1),
+    ("setpath", 2),
+    ("delpaths", 1),
+    ("paths", 0),
+    ("paths", 1),
+    ("leaf_paths", 0),
+    ("builtins", 0),
+    ("input", 0),
+    ("debug", 0),
+    ("debug", 1),
+    ("error", 0),
+    ("error", 1),
+    ("env", 0),
+    ("ascii", 0),
+    ("nth", 1),
+    ("nth", 2),
+    ("repeat", 1),
+    ("recurse", 0),
+    ("recurse", 1),
+    ("recurse", 2),
+    ("walk", 1),
+    ("bsearch", 1),
+    ("path", 1),
+    ("todate", 0),
+    ("fromdate", 0),
+    ("todateiso8601", 0),
+    ("fromdateiso8601", 0),
+    ("date", 0),
+    ("dateadd", 2),
+    ("datesub", 2),
+    ("now", 0),
+    ("test", 1),
+    ("test", 2),
+    ("match", 1),
+    ("match", 2),
+    ("capture", 1),
+    ("capture", 2),
+    ("scan", 1),
+    ("scan", 2),
+    ("sub", 2),
+    ("sub", 3),
+    ("gsub", 2),
+    ("gsub", 3),
+    ("splits", 1),
+    ("splits", 2),
+    ("@base64", 0),
+    ("@base64d", 0),
+    ("@uri", 0),
+    ("@csv", 0),
+    ("@tsv", 0),
+    ("@html", 0),
+    ("@sh", 0),
+    ("@json", 0),
+    ("@text", 0),
+    ("in", 1),
+    ("IN", 1),
+    ("IN", 2),
+    ("pick", 1),
+    ("combinations", 0),
+    ("combinations", 1),
+    ("logb", 0),
+    ("scalb", 2),
+    ("significand", 0),
+    ("j0", 0),
+    ("j1", 0),
+    ("rint", 0),
+    ("nearbyint", 0),
+    ("atan", 0),
+    ("acos", 0),
+    ("asin", 0),
+    ("cos", 0),
+    ("sin", 0),
+    ("tan", 0),
+    ("cbrt", 0),
+    ("remainder", 2),
+    ("fma", 3),
+    ("inputs", 0),
+    ("strftime", 1),
+    ("gmtime", 0),
+    ("localtime", 0),
+    ("mktime", 0),
+    ("strptime", 1),
+    ("strflocaltime", 1),
+    ("have_decnum", 0),
+    ("qj_build_info", 0),
+    ("have_literal_numbers", 0),
+    ("@urid", 0),
+    ("trimstr", 1),
+    ("tostream", 0),
+    ("fromstream", 1),
+    ("truncate_stream", 1),
+    ("add", 1),
+    ("skip", 2),
+    ("INDEX", 1),
+    ("INDEX", 2),
+    ("JOIN", 2),
+    ("atan2", 2),
+    ("hypot", 2),
+    ("sinh", 0),
+    ("cosh", 0),
+    ("tanh", 0),
+    ("asinh", 0),
+    ("acosh", 0),
+    ("atanh", 0),
+    ("trunc", 0),
+    ("truncate", 0),
+    ("exponent", 0),
+    ("inf", 0),
+    ("log_e", 0),
+    ("modulemeta", 0),
+    ("_indices", 1),
+];
+
+/// jq builtins qj doesn't implement. Calling one is an error of its own, so it
+/// isn't mistaken for a typo the way jq's "is not defined" would suggest.
+pub(crate) const UNSUPPORTED_BUILTINS: &[(&str, usize)] = &[
+    ("halt", 0),
+    ("halt_error", 0),
+    ("halt_error", 1),
+    ("input_filename", 0),
+    ("input_line_number", 0),
+    ("stderr", 0),
+    ("get_search_list", 0),
+    ("get_prog_origin", 0),
+    ("get_jq_origin", 0),
+    ("@base32d", 0),
+    ("toarray", 0),
+    ("format", 1),
+    ("finites", 0),
+    ("normals", 0),
+    ("JOIN", 3),
+    ("JOIN", 4),
+    ("frexp", 0),
+    ("modf", 0),
+    ("ldexp", 2),
+    ("scalbln", 2),
+    ("nextafter", 2),
+    ("nexttoward", 2),
+    ("copysign", 2),
+    ("drem", 2),
+    ("fdim", 2),
+    ("fmax", 2),
+    ("fmin", 2),
+    ("fmod", 2),
+    ("erf", 0),
+    ("erfc", 0),
+    ("exp10", 0),
+    ("expm1", 0),
+    ("log1p", 0),
+    ("gamma", 0),
+    ("lgamma", 0),
+    ("lgamma_r", 0),
+    ("tgamma", 0),
+    ("jn", 2),
+    ("y0", 0),
+    ("y1", 0),
+    ("yn", 2),
+];
+
+/// Whether `name/arity` is a builtin.
+pub(crate) fn is_builtin(name: &str, arity: usize) -> bool {
+    BUILTINS.contains(&(name, arity))
+}
+
+/// The arities `name` is a builtin with, in ascending order.
+pub(crate) fn builtin_arities(name: &str) -> Vec<usize> {
+    let mut arities: Vec<usize> = BUILTINS
+        .iter()
+        .filter(|(n, _)| *n == name)
+        .map(|&(_, arity)| arity)
+        .collect();
+    arities.sort_unstable();
+    arities
+}
+
 /// Set a runtime error value. Shared helper for all builtin modules.
-pub(super) fn set_error(msg: String) {
-    super::eval::LAST_ERROR.with(|e| *e.borrow_mut() = Some(Value::String(msg)));
+pub(super) fn set_error(env: &Env, msg: String) {
+    env.set_error(Value::String(msg));
+}
+
+/// The text `tostring` gives a value, also used by `@text`, the other `@`
+/// formats and string interpolation. Strings pass through; numbers are written
+/// as on output, so big integers and literals such as `75.80` keep their
+/// original text; arrays and objects become compact JSON.
+pub(super) fn value_to_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Int(n) => itoa::Buffer::new().format(*n).to_string(),
+        _ => {
+            let mut buf = Vec::new();
+            crate::output::write_compact(&mut buf, v, false).unwrap();
+            String::from_utf8(buf).unwrap_or_default()
+        }
+    }
+}
+
+/// Evaluate the generator `f`, handing each output to `take` until it returns
+/// false. The generator is then stopped the way `break` stops a `label` body,
+/// so generators with side effects, such as `inputs`, don't run past what was
+/// needed.
+pub(super) fn eval_until(
+    f: &Filter,
+    input: &Value,
+    env: &Env,
+    take: &mut dyn FnMut(Value) -> bool,
+) {
+    // One label per active call: nested calls sit at a greater depth.
+    let label = format!("*stop{}", env.depth().get());
+    let mut stopped = false;
+    super::eval::eval(f, input, env, &mut |v| {
+        if !stopped && !take(v) {
+            stopped = true;
+            env.break_signal()
+                .borrow_mut()
+                .get_or_insert_with(|| label.clone());
+        }
+    });
+    let signal = env.break_signal();
+    if signal.borrow().as_deref() == Some(label.as_str()) {
+        *signal.borrow_mut() = None;
+    }
 }
 
 pub(super) fn eval_builtin(
@@ -33,6 +357,9 @@ pub(super) fn eval_builtin(
             types::eval_types(name, args, input, env, output)
         }
 
+        // split/2 splits on a regex
+        "split" if args.len() == 2 => regex::eval_regex(name, args, input, env, output),
+
         // String operations
         "tostring" | "tonumber" | "toboolean" | "ascii_downcase" | "ascii_upcase" | "ltrimstr"
         | "rtrimstr" | "trimstr" | "startswith" | "endswith" | "split" | "join" | "trim"
@@ -70,8 +397,9 @@ pub(super) fn eval_builtin(
         }
 
         // Date/time operations
-        "todate" | "fromdate" | "now" | "strftime" | "gmtime" | "localtime" | "mktime"
-        | "strptime" | "strflocaltime" => date::eval_date(name, args, input, env, output),
+        "todate" | "fromdate" | "todateiso8601" | "fromdateiso8601" | "date" | "dateadd"
+        | "datesub" | "now" | "strftime" | "gmtime" | "localtime" | "mktime" | "strptime"
+        | "strflocaltime" => date::eval_date(name, args, input, env, output),
 
         // Format strings
         "@json" | "@text" | "@html" | "@uri" | "@urid" | "@csv" | "@tsv" | "@sh" | "@base64"
@@ -84,8 +412,8 @@ pub(super) fn eval_builtin(
         | "debug"
         | "error"
         | "env"
-        | "$ENV"
         | "have_decnum"
+        | "qj_build_info"
         | "have_literal_numbers"
         | "modulemeta" => io::eval_io(name, args, input, env, output),
 
@@ -94,8 +422,11 @@ pub(super) fn eval_builtin(
             streaming::eval_streaming(name, args, input, env, output)
         }
 
-        _ => {
-            // Unknown builtin — silently produce no output
+        _ if UNSUPPORTED_BUILTINS.contains(&(name, args.len())) => {
+            set_error(env, format!("{name}/{} is not supported by qj", args.len()))
         }
+        // jq rejects these when compiling; a user definition of the name
+        // would have been found before getting here.
+        _ => set_error(env, format!("{name}/{} is not defined", args.len())),
     }
 }
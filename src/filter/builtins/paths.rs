@@ -3,7 +3,7 @@ use crate::value::Value;
 
 use super::super::eval::eval;
 use super::super::value_ops::{
-    del_path, enum_leaf_paths, enum_paths, path_of_env, set_path, values_order,
+    delete_paths, enum_leaf_paths, enum_paths, get_path, path_of_env, set_path,
 };
 use super::set_error;
 
@@ -17,44 +17,30 @@ pub(super) fn eval_paths(
     match name {
         "getpath" => {
             if let Some(arg) = args.first() {
-                let mut path = Value::Null;
-                eval(arg, input, env, &mut |v| path = v);
-                if let Value::Array(path_arr) = path {
-                    let mut current = input.clone();
-                    for seg in path_arr.iter() {
-                        current = match (&current, seg) {
-                            (Value::Object(obj), Value::String(k)) => obj
-                                .iter()
-                                .find(|(ek, _)| ek == k)
-                                .map(|(_, v)| v.clone())
-                                .unwrap_or(Value::Null),
-                            (Value::Array(arr), Value::Int(i)) => {
-                                let idx = if *i < 0 { arr.len() as i64 + i } else { *i };
-                                if idx >= 0 && (idx as usize) < arr.len() {
-                                    arr[idx as usize].clone()
-                                } else {
-                                    Value::Null
-                                }
-                            }
-                            _ => Value::Null,
-                        };
+                eval(arg, input, env, &mut |path| {
+                    if env.has_error() {
+                        return;
                     }
-                    output(current);
-                }
+                    let Value::Array(path_arr) = path else {
+                        return set_error(env, "Path must be specified as an array".to_string());
+                    };
+                    match get_path(input, &path_arr) {
+                        Ok(v) => output(v),
+                        Err(msg) => set_error(env, msg),
+                    }
+                });
             }
         }
-        "setpath" => {
-            if args.len() == 2 {
-                let mut path = Value::Null;
-                let mut val = Value::Null;
-                eval(&args[0], input, env, &mut |v| path = v);
-                eval(&args[1], input, env, &mut |v| val = v);
-                if let Value::Array(path_arr) = path {
-                    match set_path(input, &path_arr, &val) {
-                        Ok(v) => output(v),
-                        Err(msg) => {
-                            set_error(msg);
-                        }
+        "setpath" if args.len() == 2 => {
+            let mut path = Value::Null;
+            let mut val = Value::Null;
+            eval(&args[0], input, env, &mut |v| path = v);
+            eval(&args[1], input, env, &mut |v| val = v);
+            if let Value::Array(path_arr) = path {
+                match set_path(input, &path_arr, &val) {
+                    Ok(v) => output(v),
+                    Err(msg) => {
+                        set_error(env, msg);
                     }
                 }
             }
@@ -63,28 +49,29 @@ pub(super) fn eval_paths(
             if let Some(arg) = args.first() {
                 let mut paths = Value::Null;
                 eval(arg, input, env, &mut |v| paths = v);
-                if let Value::Array(path_list) = paths {
-                    let mut current = input.clone();
-                    let mut sorted: Vec<_> = path_list
-                        .iter()
-                        .filter_map(|v| {
-                            if let Value::Array(p) = v {
-                                Some(p.clone())
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-                    sorted.sort_by(|a, b| {
-                        values_order(&Value::Array(b.clone()), &Value::Array(a.clone()))
-                            .unwrap_or(std::cmp::Ordering::Equal)
-                    });
-                    for path in &sorted {
-                        current = del_path(&current, path);
+                let Value::Array(path_list) = paths else {
+                    set_error(env, "Paths must be specified as an array".to_string());
+                    return;
+                };
+                let mut list = Vec::with_capacity(path_list.len());
+                for path in path_list.iter() {
+                    match path {
+                        Value::Array(p) => list.push(p.as_ref().clone()),
+                        other => {
+                            set_error(
+                                env,
+                                format!(
+                                    "Path must be specified as array, not {}",
+                                    other.type_name()
+                                ),
+                            );
+                            return;
+                        }
                     }
-                    output(current);
-                } else {
-                    set_error("Paths must be specified as an array".to_string());
+                }
+                match delete_paths(input, list) {
+                    Ok(result) => output(result),
+                    Err(msg) => set_error(env, msg),
                 }
             }
         }
@@ -1,8 +1,11 @@
 use crate::filter::{Env, Filter};
 use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use super::super::eval::eval;
+use super::set_error;
 
 pub(super) fn eval_regex(
     name: &str,
@@ -11,270 +14,447 @@ pub(super) fn eval_regex(
     env: &Env,
     output: &mut dyn FnMut(Value),
 ) {
+    // test/match/capture with one argument take either a pattern or a
+    // [pattern, flags] array, which jq unpacks before looking at the input.
+    let (pattern, flags) = if args.len() == 1 && matches!(name, "test" | "match" | "capture") {
+        match unpack_pattern_array(&args[0], input, env) {
+            Ok(pair) => pair,
+            Err(msg) => return set_error(env, msg),
+        }
+    } else {
+        let flags_idx = if matches!(name, "sub" | "gsub") { 2 } else { 1 };
+        let flags = args
+            .get(flags_idx)
+            .map_or(Value::Null, |f| last_output(f, input, env));
+        (
+            args.first()
+                .map_or(Value::Null, |f| last_output(f, input, env)),
+            flags,
+        )
+    };
+    // jq defines split/2 and splits as `match($re; "g" + flags)`, so bad
+    // flags fail on that addition before the input is looked at.
+    if matches!(name, "split" | "splits") && !matches!(flags, Value::Null | Value::String(_)) {
+        return set_error(
+            env,
+            format!(
+                "string (\"g\") and {} ({}) cannot be added",
+                flags.type_name(),
+                flags.short_desc()
+            ),
+        );
+    }
+    let Value::String(s) = input else {
+        return set_error(
+            env,
+            format!(
+                "{} ({}) cannot be matched, as it is not a string",
+                input.type_name(),
+                input.short_desc()
+            ),
+        );
+    };
+    let (re, flags) = match compile_args(&pattern, &flags) {
+        Ok(compiled) => compiled,
+        Err(msg) => return set_error(env, msg),
+    };
     match name {
         "test" => {
-            if let Value::String(s) = input {
-                let (pattern, flags) = eval_pattern_flags(args, input, env);
-                if let Some(re) = build_regex(&pattern, &flags) {
-                    output(Value::Bool(re.is_match(s)));
-                }
-            }
+            output(Value::Bool(!find_matches(&re, s, &flags, false).is_empty()));
         }
         "match" => {
-            if let Value::String(s) = input {
-                let (pattern, flags) = eval_pattern_flags(args, input, env);
-                let global = flags.contains('g');
-                if let Some(re) = build_regex(&pattern, &flags) {
-                    if global {
-                        for caps in re.captures_iter(s) {
-                            output(regex_match_object(&re, &caps, s));
-                        }
-                    } else if let Some(caps) = re.captures(s) {
-                        output(regex_match_object(&re, &caps, s));
-                    }
-                }
+            for caps in find_matches(&re, s, &flags, flags.global) {
+                output(regex_match_object(&re, &caps, s));
             }
         }
         "capture" => {
-            if let Value::String(s) = input {
-                let (pattern, flags) = eval_pattern_flags(args, input, env);
-                if let Some(re) = build_regex(&pattern, &flags)
-                    && let Some(caps) = re.captures(s)
-                {
-                    let mut obj = Vec::new();
-                    for (i, name) in re.capture_names().enumerate() {
-                        if let Some(name) = name {
-                            let val = caps
-                                .get(i)
-                                .map(|m| Value::String(m.as_str().to_string()))
-                                .unwrap_or(Value::Null);
-                            obj.push((name.to_string(), val));
-                        }
+            for caps in find_matches(&re, s, &flags, flags.global) {
+                let mut obj = Vec::new();
+                for (i, name) in re.capture_names().enumerate() {
+                    if let Some(name) = name {
+                        obj.push((name.to_string(), group_string(&caps, i)));
                     }
-                    output(Value::Object(Arc::new(obj)));
                 }
+                output(Value::Object(Arc::new(obj)));
             }
         }
         "scan" => {
-            if let Value::String(s) = input {
-                let (pattern, flags) = eval_pattern_flags(args, input, env);
-                if let Some(re) = build_regex(&pattern, &flags) {
-                    for caps in re.captures_iter(s) {
-                        if re.captures_len() > 1 {
-                            let arr: Vec<Value> = (1..caps.len())
-                                .map(|i| {
-                                    caps.get(i)
-                                        .map(|m| Value::String(m.as_str().to_string()))
-                                        .unwrap_or(Value::Null)
-                                })
-                                .collect();
-                            output(Value::Array(Arc::new(arr)));
-                        } else {
-                            output(Value::String(caps[0].to_string()));
-                        }
-                    }
+            for caps in find_matches(&re, s, &flags, true) {
+                if re.captures_len() > 1 {
+                    let arr: Vec<Value> = (1..caps.len()).map(|i| group_string(&caps, i)).collect();
+                    output(Value::Array(Arc::new(arr)));
+                } else {
+                    output(Value::String(caps[0].to_string()));
                 }
             }
         }
-        "sub" => {
-            if let Value::String(s) = input {
-                let (pattern, flags) = eval_sub_pattern_flags(args, 2, input, env);
-                if let Some(re) = build_regex(&pattern, &flags) {
-                    let mut repl_str = String::new();
-                    if let Some(repl_f) = args.get(1)
-                        && let Some(caps) = re.captures(s)
-                    {
-                        let match_obj = regex_match_object(&re, &caps, s);
-                        eval(repl_f, &match_obj, env, &mut |v| {
-                            if let Value::String(rs) = v {
-                                repl_str = rs;
-                            }
-                        });
-                    }
-                    if let Some(caps) = re.captures(s) {
-                        let m = caps.get(0).expect("regex capture group 0 always exists");
-                        let mut result = String::with_capacity(s.len());
-                        result.push_str(&s[..m.start()]);
-                        result.push_str(&repl_str);
-                        result.push_str(&s[m.end()..]);
-                        output(Value::String(result));
-                    } else {
-                        output(Value::String(s.clone()));
-                    }
-                }
-            }
-        }
-        "gsub" => {
-            if let Value::String(s) = input {
-                let (pattern, flags) = eval_sub_pattern_flags(args, 2, input, env);
-                if let Some(re) = build_regex(&pattern, &flags) {
-                    let mut result = String::with_capacity(s.len());
-                    let mut last_end = 0;
-                    for caps in re.captures_iter(s) {
-                        let m = caps.get(0).expect("regex capture group 0 always exists");
-                        result.push_str(&s[last_end..m.start()]);
-                        let mut repl_str = String::new();
-                        if let Some(repl_f) = args.get(1) {
-                            let match_obj = regex_match_object(&re, &caps, s);
-                            eval(repl_f, &match_obj, env, &mut |v| {
-                                if let Value::String(rs) = v {
-                                    repl_str = rs;
-                                }
-                            });
+        "sub" | "gsub" => {
+            let global = name == "gsub" || flags.global;
+            let mut result = String::with_capacity(s.len());
+            let mut last_end = 0;
+            for caps in find_matches(&re, s, &flags, global) {
+                let m = caps.get(0).expect("regex capture group 0 always exists");
+                result.push_str(&s[last_end..m.start()]);
+                let mut repl_str = String::new();
+                if let Some(repl_f) = args.get(1) {
+                    let match_obj = regex_match_object(&re, &caps, s);
+                    eval(repl_f, &match_obj, env, &mut |v| {
+                        if let Value::String(rs) = v {
+                            repl_str = rs;
                         }
-                        result.push_str(&repl_str);
-                        last_end = m.end();
-                    }
-                    result.push_str(&s[last_end..]);
-                    output(Value::String(result));
+                    });
                 }
+                result.push_str(&repl_str);
+                last_end = m.end();
             }
+            result.push_str(&s[last_end..]);
+            output(Value::String(result));
         }
-        "splits" => {
-            if let Value::String(s) = input {
-                let (pattern, flags) = eval_pattern_flags(args, input, env);
-                if let Some(re) = build_regex(&pattern, &flags) {
-                    let mut last_end = 0;
-                    for m in re.find_iter(s) {
-                        output(Value::String(s[last_end..m.start()].to_string()));
-                        last_end = m.end();
-                    }
-                    output(Value::String(s[last_end..].to_string()));
-                }
+        "splits" | "split" => {
+            let mut parts = Vec::new();
+            let mut last_end = 0;
+            for caps in find_matches(&re, s, &flags, true) {
+                let m = caps.get(0).expect("regex capture group 0 always exists");
+                parts.push(Value::String(s[last_end..m.start()].to_string()));
+                last_end = m.end();
+            }
+            parts.push(Value::String(s[last_end..].to_string()));
+            if name == "split" {
+                output(Value::Array(Arc::new(parts)));
+            } else {
+                parts.into_iter().for_each(output);
             }
         }
         _ => {}
     }
 }
 
-/// Compile a regex from a pattern string and jq-style flags string.
-fn build_regex(pattern: &str, flags: &str) -> Option<regex::Regex> {
+/// jq regex modifiers (the Oniguruma option letters jq accepts).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+struct RegexFlags {
+    /// `g`: every match rather than the first.
+    global: bool,
+    /// `i`: case-insensitive.
+    case_insensitive: bool,
+    /// `x`: extended syntax; whitespace and `#` comments are ignored.
+    extended: bool,
+    /// `m` (or `p`): `.` also matches newlines. Oniguruma calls this
+    /// multi-line mode. `s` is accepted too, but `^`/`$` already anchor to
+    /// the whole string, so it changes nothing.
+    dot_all: bool,
+    /// `n`: drop empty matches.
+    ignore_empty: bool,
+    /// `l`: take the longest match instead of the leftmost one.
+    longest: bool,
+}
+
+impl RegexFlags {
+    /// Parse a flags string, rejecting unknown letters with jq's message.
+    fn parse(flags: &str) -> Result<Self, String> {
+        let mut parsed = RegexFlags::default();
+        for c in flags.chars() {
+            match c {
+                'g' => parsed.global = true,
+                'i' => parsed.case_insensitive = true,
+                'x' => parsed.extended = true,
+                'm' | 'p' => parsed.dot_all = true,
+                's' => {}
+                'n' => parsed.ignore_empty = true,
+                'l' => parsed.longest = true,
+                _ => return Err(format!("{flags} is not a valid modifier string")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Compile a regex from a pattern string and parsed jq flags.
+fn build_regex(pattern: &str, flags: &RegexFlags) -> Result<regex::Regex, regex::Error> {
     let mut p = String::new();
-    let case_insensitive = flags.contains('i');
-    let multiline = flags.contains('m');
-    let single_line = flags.contains('s');
-    if case_insensitive || multiline || single_line {
+    if flags.case_insensitive || flags.dot_all {
         p.push_str("(?");
-        if case_insensitive {
+        if flags.case_insensitive {
             p.push('i');
         }
-        if multiline {
-            p.push('m');
-        }
-        if single_line {
+        if flags.dot_all {
             p.push('s');
         }
         p.push(')');
     }
-    if flags.contains('x') {
-        let mut chars = pattern.chars().peekable();
-        while let Some(c) = chars.next() {
-            if c == '\\' {
-                p.push(c);
+    if flags.extended {
+        strip_extended(pattern, &mut p);
+    } else {
+        p.push_str(pattern);
+    }
+    regex::Regex::new(&p)
+}
+
+/// Most compiled regexes kept per thread.
+const REGEX_CACHE_CAPACITY: usize = 64;
+
+/// Recently compiled regexes, least recently used evicted first.
+///
+/// `test`/`match`/`sub` and friends are called once per record, and
+/// compiling the pattern costs far more than matching a short string. The
+/// cache is per thread, so NDJSON workers never contend for it; patterns
+/// computed from the input hit it whenever they repeat.
+#[derive(Default)]
+struct RegexCache {
+    entries: HashMap<(String, RegexFlags), (regex::Regex, u64)>,
+    /// Use counter; an entry's stamp is the count at its last use.
+    clock: u64,
+}
+
+impl RegexCache {
+    fn get_or_build(
+        &mut self,
+        pattern: &str,
+        flags: RegexFlags,
+    ) -> Result<regex::Regex, regex::Error> {
+        self.clock += 1;
+        let key = (pattern.to_string(), flags);
+        if let Some((re, used)) = self.entries.get_mut(&key) {
+            *used = self.clock;
+            return Ok(re.clone());
+        }
+        let re = build_regex(pattern, &flags)?;
+        if self.entries.len() >= REGEX_CACHE_CAPACITY
+            && let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(k, _)| k.clone())
+        {
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert(key, (re.clone(), self.clock));
+        Ok(re)
+    }
+}
+
+thread_local! {
+    static REGEX_CACHE: RefCell<RegexCache> = RefCell::new(RegexCache::default());
+}
+
+/// [`build_regex`] through this thread's cache. Cloning a `Regex` is cheap.
+fn cached_regex(pattern: &str, flags: RegexFlags) -> Result<regex::Regex, regex::Error> {
+    REGEX_CACHE.with(|cache| cache.borrow_mut().get_or_build(pattern, flags))
+}
+
+/// Append `pattern` to `out` with extended-mode whitespace and comments
+/// removed. Inside a bracket class whitespace and `#` stay literal, as in
+/// Oniguruma (the regex crate's own `x` flag would drop them).
+fn strip_extended(pattern: &str, out: &mut String) {
+    let mut chars = pattern.chars().peekable();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
                 if let Some(next) = chars.next() {
-                    p.push(next);
+                    out.push(next);
                 }
-            } else if c == '#' {
+            }
+            '[' => {
+                in_class = true;
+                out.push(c);
+                // A leading `]` (after an optional `^`) is literal
+                if chars.peek() == Some(&'^') {
+                    out.push(chars.next().unwrap_or('^'));
+                }
+                if chars.peek() == Some(&']') {
+                    out.push(chars.next().unwrap_or(']'));
+                }
+            }
+            ']' if in_class => {
+                in_class = false;
+                out.push(c);
+            }
+            _ if in_class => out.push(c),
+            '#' => {
                 for nc in chars.by_ref() {
                     if nc == '\n' {
                         break;
                     }
                 }
-            } else if c.is_ascii_whitespace() {
-                // Skip
-            } else {
-                p.push(c);
             }
+            _ if c.is_ascii_whitespace() => {}
+            _ => out.push(c),
         }
-    } else {
-        p.push_str(pattern);
     }
-    regex::Regex::new(&p).ok()
 }
 
-/// Evaluate pattern and flags from the first two args.
-fn eval_pattern_flags(args: &[Filter], input: &Value, env: &Env) -> (String, String) {
-    let mut pattern = String::new();
-    let mut flags = String::new();
-    if let Some(pat_f) = args.first() {
-        eval(pat_f, input, env, &mut |v| {
-            if let Value::String(s) = v {
-                pattern = s;
-            }
-        });
+/// The last output of `f` on `input`, or null if it has none.
+fn last_output(f: &Filter, input: &Value, env: &Env) -> Value {
+    let mut last = Value::Null;
+    eval(f, input, env, &mut |v| last = v);
+    last
+}
+
+/// The pattern and flags given to one-argument test/match/capture: a string
+/// pattern, or an array of the pattern and optional flags.
+fn unpack_pattern_array(arg: &Filter, input: &Value, env: &Env) -> Result<(Value, Value), String> {
+    match last_output(arg, input, env) {
+        pattern @ Value::String(_) => Ok((pattern, Value::Null)),
+        Value::Array(items) if !items.is_empty() => Ok((
+            items[0].clone(),
+            items.get(1).cloned().unwrap_or(Value::Null),
+        )),
+        other => Err(format!("{} not a string or array", other.type_name())),
     }
-    if let Some(flags_f) = args.get(1) {
-        eval(flags_f, input, env, &mut |v| {
-            if let Value::String(s) = v {
-                flags = s;
+}
+
+/// Check and compile a pattern and its flags. Flags may be a string or null;
+/// anything else, an unknown flag letter, or a pattern that isn't a valid
+/// regex is an error with jq's message.
+fn compile_args(pattern: &Value, flags: &Value) -> Result<(regex::Regex, RegexFlags), String> {
+    let not_a_string =
+        |v: &Value| format!("{} ({}) is not a string", v.type_name(), v.short_desc());
+    let Value::String(pattern) = pattern else {
+        return Err(not_a_string(pattern));
+    };
+    let flags = match flags {
+        Value::String(s) => RegexFlags::parse(s)?,
+        Value::Null => RegexFlags::default(),
+        other => return Err(not_a_string(other)),
+    };
+    let re = cached_regex(pattern, flags).map_err(|err| {
+        // The regex crate's message ends with an "error: ..." line after a
+        // diagram of the pattern; jq reports just the reason.
+        let text = err.to_string();
+        let reason = text
+            .lines()
+            .rev()
+            .find_map(|line| line.strip_prefix("error: "))
+            .unwrap_or(&text);
+        format!("{pattern} (at offset 0) is not a valid regex: {reason}")
+    })?;
+    Ok((re, flags))
+}
+
+/// Collect the matches of `re` in `s`: all of them when `global`, else the
+/// first. Honors the `n` and `l` flags.
+///
+/// Global matching steps the way jq does: after an empty match the search
+/// resumes one character on, after any other at the match's end, and it
+/// stops once that reaches the end of the string. So `""` matches `"abc"`
+/// at offsets 0, 1 and 2, and `"b*"` matches empty at 2 right after `"b"`.
+fn find_matches<'h>(
+    re: &regex::Regex,
+    s: &'h str,
+    flags: &RegexFlags,
+    global: bool,
+) -> Vec<regex::Captures<'h>> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while from <= s.len() {
+        let caps = if flags.longest {
+            longest_match_from(re, s, from, flags.ignore_empty)
+        } else {
+            re.captures_at(s, from)
+        };
+        let Some(caps) = caps else {
+            break;
+        };
+        let m = caps.get(0).expect("regex capture group 0 always exists");
+        let empty = m.is_empty();
+        from = if empty {
+            m.end() + s[m.end()..].chars().next().map_or(1, char::len_utf8)
+        } else {
+            m.end()
+        };
+        if !(flags.ignore_empty && empty) {
+            found.push(caps);
+            if !global {
+                break;
             }
-        });
+        }
+        if from == s.len() {
+            break;
+        }
     }
-    (pattern, flags)
+    found
 }
 
-/// Evaluate pattern and flags for sub/gsub where flags is at `flags_idx`.
-fn eval_sub_pattern_flags(
-    args: &[Filter],
-    flags_idx: usize,
-    input: &Value,
-    env: &Env,
-) -> (String, String) {
-    let mut pattern = String::new();
-    let mut flags = String::new();
-    if let Some(pat_f) = args.first() {
-        eval(pat_f, input, env, &mut |v| {
-            if let Value::String(s) = v {
-                pattern = s;
-            }
-        });
+/// The longest match starting at or after `from`, earliest on ties. Like
+/// Oniguruma's find-longest option, each candidate start position uses the
+/// ordinary (leftmost-first) match there.
+fn longest_match_from<'h>(
+    re: &regex::Regex,
+    s: &'h str,
+    from: usize,
+    ignore_empty: bool,
+) -> Option<regex::Captures<'h>> {
+    let mut best: Option<regex::Captures<'h>> = None;
+    let mut at = from;
+    while at <= s.len()
+        && let Some(caps) = re.captures_at(s, at)
+    {
+        let m = caps.get(0).expect("regex capture group 0 always exists");
+        let next = m.start() + s[m.start()..].chars().next().map_or(1, char::len_utf8);
+        let best_len = best.as_ref().map(|b| b[0].len());
+        if !(ignore_empty && m.is_empty()) && best_len.is_none_or(|len| m.len() > len) {
+            best = Some(caps);
+        }
+        at = next;
     }
-    if let Some(flags_f) = args.get(flags_idx) {
-        eval(flags_f, input, env, &mut |v| {
-            if let Value::String(s) = v {
-                flags = s;
-            }
-        });
+    best
+}
+
+/// The text of capture group `i`, or null if it took no part in the match.
+/// For an empty overall match jq reports every group as matching empty.
+fn group_string(caps: &regex::Captures, i: usize) -> Value {
+    if caps[0].is_empty() {
+        return Value::String(String::new());
     }
-    (pattern, flags)
+    caps.get(i)
+        .map_or(Value::Null, |m| Value::String(m.as_str().to_string()))
 }
 
 /// Build a jq-compatible match result object from a regex::Captures.
-fn regex_match_object(re: &regex::Regex, caps: &regex::Captures, _input: &str) -> Value {
+/// Offsets and lengths count codepoints, not bytes.
+fn regex_match_object(re: &regex::Regex, caps: &regex::Captures, input: &str) -> Value {
+    let ascii = input.is_ascii();
+    let chars = |text: &str| {
+        if ascii {
+            text.len()
+        } else {
+            text.chars().count()
+        }
+    };
     let m = caps.get(0).expect("regex capture group 0 always exists");
     let mut captures = Vec::new();
     for (i, name) in re.capture_names().enumerate() {
         if i == 0 {
             continue;
         }
-        let cap_val = if let Some(cm) = caps.get(i) {
-            Value::Object(Arc::new(vec![
-                ("offset".to_string(), Value::Int(cm.start() as i64)),
-                ("length".to_string(), Value::Int(cm.len() as i64)),
-                ("string".to_string(), Value::String(cm.as_str().to_string())),
-                (
-                    "name".to_string(),
-                    name.map(|n| Value::String(n.to_string()))
-                        .unwrap_or(Value::Null),
-                ),
-            ]))
-        } else {
-            Value::Object(Arc::new(vec![
-                ("offset".to_string(), Value::Int(-1)),
-                ("length".to_string(), Value::Int(0)),
-                ("string".to_string(), Value::Null),
-                (
-                    "name".to_string(),
-                    name.map(|n| Value::String(n.to_string()))
-                        .unwrap_or(Value::Null),
-                ),
-            ]))
+        let name = name.map_or(Value::Null, |n| Value::String(n.to_string()));
+        let offset = |byte: usize| {
+            (
+                "offset".to_string(),
+                Value::Int(chars(&input[..byte]) as i64),
+            )
+        };
+        let length = |len: usize| ("length".to_string(), Value::Int(len as i64));
+        let string = ("string".to_string(), group_string(caps, i));
+        // jq lists the keys of groups without a match of their own in a
+        // different order.
+        let mut fields = match caps.get(i) {
+            Some(cm) if !m.is_empty() => {
+                vec![offset(cm.start()), length(chars(cm.as_str())), string]
+            }
+            _ if m.is_empty() => vec![offset(m.start()), string, length(0)],
+            _ => vec![("offset".to_string(), Value::Int(-1)), string, length(0)],
         };
-        captures.push(cap_val);
+        fields.push(("name".to_string(), name));
+        captures.push(Value::Object(Arc::new(fields)));
     }
     Value::Object(Arc::new(vec![
-        ("offset".to_string(), Value::Int(m.start() as i64)),
-        ("length".to_string(), Value::Int(m.len() as i64)),
+        (
+            "offset".to_string(),
+            Value::Int(chars(&input[..m.start()]) as i64),
+        ),
+        ("length".to_string(), Value::Int(chars(m.as_str()) as i64)),
         ("string".to_string(), Value::String(m.as_str().to_string())),
         ("captures".to_string(), Value::Array(Arc::new(captures))),
     ]))
@@ -284,35 +464,94 @@ fn regex_match_object(re: &regex::Regex, caps: &regex::Captures, _input: &str) -
 mod tests {
     use super::*;
 
+    fn compile(pattern: &str, flags: &str) -> Option<regex::Regex> {
+        build_regex(pattern, &RegexFlags::parse(flags).unwrap()).ok()
+    }
+
     #[test]
     fn build_regex_basic() {
-        let re = build_regex("^foo", "").unwrap();
+        let re = compile("^foo", "").unwrap();
         assert!(re.is_match("foobar"));
         assert!(!re.is_match("barfoo"));
     }
 
     #[test]
     fn build_regex_case_insensitive() {
-        let re = build_regex("FOO", "i").unwrap();
+        let re = compile("FOO", "i").unwrap();
         assert!(re.is_match("foobar"));
     }
 
     #[test]
     fn build_regex_extended_mode() {
-        let re = build_regex("foo  # match foo\n  bar", "x").unwrap();
+        let re = compile("foo  # match foo\n  bar", "x").unwrap();
         assert!(re.is_match("foobar"));
         assert!(!re.is_match("foo bar"));
+        // Whitespace inside a bracket class is literal
+        let re = compile("a[ #]b", "x").unwrap();
+        assert!(re.is_match("a b"));
+        assert!(re.is_match("a#b"));
+        assert!(!re.is_match("ab"));
     }
 
     #[test]
     fn build_regex_combined_flags() {
-        let re = build_regex("^foo$", "im").unwrap();
-        assert!(re.is_match("bar\nfoo\nbaz"));
+        // Oniguruma's "m" lets `.` cross newlines; `^`/`$` stay string anchors
+        let re = compile("^foo.BAR$", "im").unwrap();
+        assert!(re.is_match("foo\nbar"));
+        assert!(!re.is_match("x\nfoo\nbar"));
+        assert!(compile("a.b", "p").unwrap().is_match("a\nb"));
+        assert!(!compile("a.b", "s").unwrap().is_match("a\nb"));
+    }
+
+    #[test]
+    fn find_matches_steps_past_empty_matches() {
+        let spans = |pattern: &str, flags: &str, s: &str| -> Vec<(usize, usize)> {
+            let flags = RegexFlags::parse(flags).unwrap();
+            let re = build_regex(pattern, &flags).unwrap();
+            find_matches(&re, s, &flags, true)
+                .iter()
+                .map(|caps| caps.get(0).unwrap())
+                .map(|m| (m.start(), m.len()))
+                .collect()
+        };
+        assert_eq!(spans("", "", "abc"), [(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(spans("b*", "", "abc"), [(0, 0), (1, 1), (2, 0)]);
+        assert_eq!(spans("$", "", "abc"), [(3, 0)]);
+        assert_eq!(spans("", "", ""), [(0, 0)]);
+        assert_eq!(spans("", "", "éa"), [(0, 0), (2, 0)]);
+        assert_eq!(spans("X*", "n", "aXbXX"), [(1, 1), (3, 2)]);
     }
 
     #[test]
     fn build_regex_invalid_pattern() {
-        assert!(build_regex("[invalid", "").is_none());
+        assert!(compile("[invalid", "").is_none());
+    }
+
+    #[test]
+    fn parse_flags_rejects_unknown_letters() {
+        let flags = RegexFlags::parse("gixsmnlp").unwrap();
+        assert!(flags.global && flags.case_insensitive && flags.extended);
+        assert!(flags.dot_all && flags.ignore_empty && flags.longest);
+        assert_eq!(
+            RegexFlags::parse("gq").unwrap_err(),
+            "gq is not a valid modifier string"
+        );
+    }
+
+    #[test]
+    fn find_matches_ignore_empty_and_longest() {
+        let offsets = |pattern: &str, flags: &str, s: &str| -> Vec<(usize, usize)> {
+            let flags = RegexFlags::parse(flags).unwrap();
+            let re = build_regex(pattern, &flags).unwrap();
+            find_matches(&re, s, &flags, flags.global)
+                .iter()
+                .map(|c| (c[0].as_ptr() as usize - s.as_ptr() as usize, c[0].len()))
+                .collect()
+        };
+        assert_eq!(offsets("X*", "gn", "aXbXX"), vec![(1, 1), (3, 2)]);
+        assert_eq!(offsets("a+|ab", "l", "ab aaaa"), vec![(3, 4)]);
+        assert_eq!(offsets("a|ab", "l", "ab aaaa"), vec![(0, 1)]);
+        assert_eq!(offsets("a+|b", "gl", "ab aaaa"), vec![(3, 4)]);
     }
 
     #[test]
@@ -376,4 +615,49 @@ mod tests {
             panic!("expected object");
         }
     }
+
+    #[test]
+    fn regex_cache_reuses_identical_patterns() {
+        let mut cache = RegexCache::default();
+        let flags = RegexFlags::parse("i").unwrap();
+        for _ in 0..3 {
+            let re = cache.get_or_build("time(out)?", flags).unwrap();
+            assert!(re.is_match("TIMEOUT"));
+        }
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.get_or_build("(", flags).is_err());
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn regex_cache_keys_on_flags() {
+        let mut cache = RegexCache::default();
+        let plain = cache.get_or_build("a.b", RegexFlags::default()).unwrap();
+        let global = cache
+            .get_or_build("a.b", RegexFlags::parse("g").unwrap())
+            .unwrap();
+        let dot_all = cache
+            .get_or_build("a.b", RegexFlags::parse("m").unwrap())
+            .unwrap();
+        assert_eq!(cache.entries.len(), 3);
+        assert!(plain.is_match("axb") && !plain.is_match("a\nb"));
+        assert!(!global.is_match("a\nb"));
+        assert!(dot_all.is_match("a\nb"));
+    }
+
+    #[test]
+    fn regex_cache_evicts_least_recently_used() {
+        let mut cache = RegexCache::default();
+        let flags = RegexFlags::default();
+        // One pattern per "record", with one that keeps recurring.
+        for i in 0..REGEX_CACHE_CAPACITY * 3 {
+            let pattern = format!("^{i}$");
+            let re = cache.get_or_build(&pattern, flags).unwrap();
+            assert!(re.is_match(&i.to_string()) && !re.is_match(&(i + 1).to_string()));
+            assert!(cache.get_or_build("^hot", flags).unwrap().is_match("hot"));
+            assert!(cache.entries.len() <= REGEX_CACHE_CAPACITY);
+        }
+        assert!(cache.entries.contains_key(&("^hot".to_string(), flags)));
+        assert!(!cache.entries.contains_key(&("^0$".to_string(), flags)));
+    }
 }
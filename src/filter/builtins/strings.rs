@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 use super::super::eval::eval;
 use super::super::value_ops::values_equal;
-use super::set_error;
+use super::{set_error, value_to_string};
 
 /// Truncate a string to at most `max` bytes, ensuring the cut falls on a UTF-8
 /// char boundary. Returns the full string if it's already short enough.
@@ -19,6 +19,70 @@ fn safe_truncate(s: &str, max: usize) -> &str {
     &s[..end]
 }
 
+/// `join($sep)` as jq defines it: `reduce .[] as $i (null; (if . == null
+/// then "" else . + $sep end) + ($i | if type == "boolean" or type == "number"
+/// then tojson elif . == null then "" else . end)) // ""`. The errors are the
+/// ones those two additions raise.
+fn join_values(items: &[Value], sep: &Value) -> Result<String, String> {
+    fn cannot_add(acc: &str, v: &Value) -> String {
+        format!(
+            "string ({}) and {} ({}) cannot be added",
+            Value::String(acc.to_string()).short_desc(),
+            v.type_name(),
+            v.short_desc()
+        )
+    }
+    let mut result = String::new();
+    for (i, v) in items.iter().enumerate() {
+        if i > 0 {
+            match sep {
+                Value::String(p) => result.push_str(p),
+                Value::Null => {}
+                _ => return Err(cannot_add(&result, sep)),
+            }
+        }
+        match v {
+            Value::String(s) => result.push_str(s),
+            Value::Null => {}
+            Value::Int(_) | Value::Double(..) | Value::Bool(_) => {
+                let mut buf = Vec::new();
+                crate::output::write_compact(&mut buf, v, false).unwrap();
+                result.push_str(&String::from_utf8(buf).unwrap_or_default());
+            }
+            Value::Array(_) | Value::Object(_) => return Err(cannot_add(&result, v)),
+        }
+    }
+    Ok(result)
+}
+
+/// `tonumber` of a string: plain decimal syntax with an optional sign, and
+/// nothing else — no surrounding whitespace, hex, or `nan`/`infinity`.
+/// Integral text within i64 stays an exact `Int` (beyond it, an exact big
+/// integer literal); magnitudes past f64 saturate to ±f64::MAX, as in jq.
+fn parse_number_text(s: &str) -> Option<Value> {
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if !unsigned.bytes().any(|b| b.is_ascii_digit())
+        || !unsigned
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'))
+    {
+        return None;
+    }
+    match s.parse::<i64>() {
+        // `-0` keeps its sign as a double
+        Ok(0) if s.starts_with('-') => {}
+        Ok(n) => return Some(Value::Int(n)),
+        Err(_) => {
+            let text = s.strip_prefix('+').unwrap_or(s);
+            if let Some(big) = crate::value::bigint::BigInt::parse(text) {
+                return Some(big.into_value());
+            }
+        }
+    }
+    let f: f64 = s.parse().ok()?;
+    Some(Value::Double(f.clamp(f64::MIN, f64::MAX), None))
+}
+
 pub(super) fn eval_strings(
     name: &str,
     args: &[Filter],
@@ -27,43 +91,31 @@ pub(super) fn eval_strings(
     output: &mut dyn FnMut(Value),
 ) {
     match name {
-        "tostring" => match input {
-            Value::String(_) => output(input.clone()),
-            Value::Int(n) => output(Value::String(itoa::Buffer::new().format(*n).into())),
-            Value::Double(_, _) => {
-                // Use write_compact for consistent formatting (integer expansion, etc.)
-                let mut buf = Vec::new();
-                crate::output::write_compact(&mut buf, input, false).unwrap();
-                output(Value::String(String::from_utf8(buf).unwrap_or_default()));
-            }
-            Value::Bool(b) => output(Value::String(if *b { "true" } else { "false" }.into())),
-            Value::Null => output(Value::String("null".into())),
-            Value::Array(_) | Value::Object(_) => {
-                let mut buf = Vec::new();
-                crate::output::write_compact(&mut buf, input, false).unwrap();
-                output(Value::String(String::from_utf8(buf).unwrap_or_default()));
-            }
-        },
+        "tostring" => output(Value::String(value_to_string(input))),
         "tonumber" => match input {
             Value::Int(_) | Value::Double(..) => output(input.clone()),
             Value::String(s) => {
-                if let Ok(n) = s.parse::<i64>() {
-                    output(Value::Int(n));
-                } else if let Ok(f) = s.parse::<f64>() {
-                    output(Value::Double(f, None));
+                if let Some(n) = parse_number_text(s) {
+                    output(n);
                 } else {
-                    set_error(format!(
-                        "string ({}) cannot be parsed as a number",
-                        Value::String(s.clone()).short_desc()
-                    ));
+                    set_error(
+                        env,
+                        format!(
+                            "string ({}) cannot be parsed as a number",
+                            Value::String(s.clone()).short_desc()
+                        ),
+                    );
                 }
             }
             _ => {
-                set_error(format!(
-                    "{} ({}) cannot be parsed as a number",
-                    input.type_name(),
-                    input.short_desc()
-                ));
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) cannot be parsed as a number",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         },
         "ascii_downcase" => {
@@ -72,11 +124,14 @@ pub(super) fn eval_strings(
                     s.chars().map(|c| c.to_ascii_lowercase()).collect(),
                 ));
             } else if !matches!(input, Value::Null) {
-                set_error(format!(
-                    "{} ({}) cannot be ascii_downcased",
-                    input.type_name(),
-                    input.short_desc()
-                ));
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) cannot be ascii_downcased",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         }
         "ascii_upcase" => {
@@ -85,43 +140,31 @@ pub(super) fn eval_strings(
                     s.chars().map(|c| c.to_ascii_uppercase()).collect(),
                 ));
             } else if !matches!(input, Value::Null) {
-                set_error(format!(
-                    "{} ({}) cannot be ascii_upcased",
-                    input.type_name(),
-                    input.short_desc()
-                ));
-            }
-        }
-        "ltrimstr" => {
-            if let Some(arg) = args.first() {
-                let mut prefix = Value::Null;
-                eval(arg, input, env, &mut |v| prefix = v);
-                match (input, &prefix) {
-                    (Value::String(s), Value::String(p)) => {
-                        output(Value::String(
-                            s.strip_prefix(p.as_str()).unwrap_or(s).to_string(),
-                        ));
-                    }
-                    _ => {
-                        set_error("startswith() requires string inputs".to_string());
-                    }
-                }
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) cannot be ascii_upcased",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         }
-        "rtrimstr" => {
+        // jq implements these on top of startswith/endswith but swallows
+        // their type errors: anything other than string/string passes through.
+        "ltrimstr" | "rtrimstr" => {
             if let Some(arg) = args.first() {
-                let mut suffix = Value::Null;
-                eval(arg, input, env, &mut |v| suffix = v);
-                match (input, &suffix) {
+                eval(arg, input, env, &mut |pat| match (input, &pat) {
                     (Value::String(s), Value::String(p)) => {
-                        output(Value::String(
-                            s.strip_suffix(p.as_str()).unwrap_or(s).to_string(),
-                        ));
-                    }
-                    _ => {
-                        set_error("endswith() requires string inputs".to_string());
+                        let trimmed = if name == "ltrimstr" {
+                            s.strip_prefix(p.as_str())
+                        } else {
+                            s.strip_suffix(p.as_str())
+                        };
+                        output(Value::String(trimmed.unwrap_or(s).to_string()));
                     }
-                }
+                    _ => output(input.clone()),
+                });
             }
         }
         "trimstr" => {
@@ -140,47 +183,41 @@ pub(super) fn eval_strings(
                     output(input.clone());
                 }
             } else if !args.is_empty() && !matches!(input, Value::String(_)) {
-                set_error(format!(
-                    "{} ({}) and string cannot have their strings trimmed",
-                    input.type_name(),
-                    input.short_desc()
-                ));
-            }
-        }
-        "startswith" => {
-            if let Some(arg) = args.first() {
-                let mut prefix = Value::Null;
-                eval(arg, input, env, &mut |v| prefix = v);
-                match (input, &prefix) {
-                    (Value::String(s), Value::String(p)) => {
-                        output(Value::Bool(s.starts_with(p.as_str())));
-                    }
-                    _ => {
-                        set_error("startswith() requires string inputs".to_string());
-                    }
-                }
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) and string cannot have their strings trimmed",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         }
-        "endswith" => {
+        "startswith" | "endswith" => {
             if let Some(arg) = args.first() {
-                let mut suffix = Value::Null;
-                eval(arg, input, env, &mut |v| suffix = v);
-                match (input, &suffix) {
+                eval(arg, input, env, &mut |pat| match (input, &pat) {
                     (Value::String(s), Value::String(p)) => {
-                        output(Value::Bool(s.ends_with(p.as_str())));
-                    }
-                    _ => {
-                        set_error("endswith() requires string inputs".to_string());
+                        output(Value::Bool(if name == "startswith" {
+                            s.starts_with(p.as_str())
+                        } else {
+                            s.ends_with(p.as_str())
+                        }));
                     }
-                }
+                    _ => set_error(env, format!("{name}() requires string inputs")),
+                });
             }
         }
         "split" => {
-            if let (Value::String(s), Some(arg)) = (input, args.first()) {
-                let mut sep = Value::Null;
-                eval(arg, input, env, &mut |v| sep = v);
-                if let Value::String(p) = sep {
-                    let parts: Vec<Value> = if p.is_empty() {
+            let Some(arg) = args.first() else {
+                // jq has no split/0; it is rejected as an undefined function.
+                return set_error(env, "split/0 is not defined".to_string());
+            };
+            eval(arg, input, env, &mut |sep| match (input, &sep) {
+                (Value::String(s), Value::String(p)) => {
+                    // jq splits "" into no parts at all, whatever the separator.
+                    let parts: Vec<Value> = if s.is_empty() {
+                        Vec::new()
+                    } else if p.is_empty() {
                         s.chars().map(|c| Value::String(c.to_string())).collect()
                     } else {
                         s.split(p.as_str())
@@ -189,70 +226,48 @@ pub(super) fn eval_strings(
                     };
                     output(Value::Array(Arc::new(parts)));
                 }
-            } else if !args.is_empty() && !matches!(input, Value::String(_)) {
-                set_error(format!(
-                    "{} ({}) cannot be split",
-                    input.type_name(),
-                    input.short_desc()
-                ));
-            }
+                _ => set_error(env, "split input and separator must be strings".to_string()),
+            });
         }
         "join" => {
-            if let (Value::Array(arr), Some(arg)) = (input, args.first()) {
-                eval(arg, input, env, &mut |sep| {
-                    if let Value::String(p) = sep {
-                        let mut result = String::new();
-                        let mut first = true;
-                        for v in arr.iter() {
-                            if !first {
-                                result.push_str(&p);
-                            }
-                            first = false;
-                            match v {
-                                Value::String(s) => result.push_str(s),
-                                Value::Int(n) => result.push_str(itoa::Buffer::new().format(*n)),
-                                Value::Double(f, _) => {
-                                    result.push_str(ryu::Buffer::new().format(*f))
-                                }
-                                Value::Null => {}
-                                Value::Bool(b) => {
-                                    result.push_str(if *b { "true" } else { "false" })
-                                }
-                                _ => {
-                                    // jq error: string ("partial,") and object ({...}) cannot be added
-                                    let partial_desc = if result.len() > 10 {
-                                        format!(
-                                            "\"{}...",
-                                            result.chars().take(10).collect::<String>()
-                                        )
-                                    } else {
-                                        format!("\"{result}\"")
-                                    };
-                                    set_error(format!(
-                                        "string ({}) and {} ({}) cannot be added",
-                                        partial_desc,
-                                        v.type_name(),
-                                        v.short_desc()
-                                    ));
-                                    return;
-                                }
-                            }
-                        }
-                        output(Value::String(result));
-                    }
+            let object_values: Vec<Value>;
+            let items: &[Value] = match input {
+                Value::Array(arr) => arr,
+                Value::Object(obj) => {
+                    object_values = obj.iter().map(|(_, v)| v.clone()).collect();
+                    &object_values
+                }
+                _ => {
+                    set_error(
+                        env,
+                        format!(
+                            "Cannot iterate over {} ({})",
+                            input.type_name(),
+                            input.short_desc()
+                        ),
+                    );
+                    return;
+                }
+            };
+            if let Some(arg) = args.first() {
+                eval(arg, input, env, &mut |sep| match join_values(items, &sep) {
+                    Ok(s) => output(Value::String(s)),
+                    Err(msg) => set_error(env, msg),
                 });
             }
         }
         "trim" | "ltrim" | "rtrim" => {
             if let Value::String(s) = input {
+                // jq trims ASCII whitespace only; U+00A0, U+2028 etc. stay put
+                let is_space = |c: char| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c');
                 let trimmed = match name {
-                    "ltrim" => s.trim_start(),
-                    "rtrim" => s.trim_end(),
-                    _ => s.trim(),
+                    "ltrim" => s.trim_start_matches(is_space),
+                    "rtrim" => s.trim_end_matches(is_space),
+                    _ => s.trim_matches(is_space),
                 };
                 output(Value::String(trimmed.to_string()));
             } else {
-                set_error("trim input must be a string".to_string());
+                set_error(env, "trim input must be a string".to_string());
             }
         }
         "index" => {
@@ -268,14 +283,17 @@ pub(super) fn eval_strings(
                             output(Value::Null);
                         }
                     }
-                    (Value::Array(arr), _) => {
-                        let pos = arr.iter().position(|v| values_equal(v, &needle));
+                    (Value::Array(arr), n) if !matches!(n, Value::Array(_)) => {
+                        let pos = arr.iter().position(|v| values_equal(v, n));
                         match pos {
                             Some(i) => output(Value::Int(i as i64)),
                             None => output(Value::Null),
                         }
                     }
-                    _ => output(Value::Null),
+                    // jq: def index($i): indices($i) | .[0];
+                    _ => indices(input, needle, env, &mut |found| {
+                        index_at(&found, 0, env, output)
+                    }),
                 });
             }
         }
@@ -291,62 +309,24 @@ pub(super) fn eval_strings(
                             output(Value::Null);
                         }
                     }
-                    (Value::Array(arr), _) => {
-                        let pos = arr.iter().rposition(|v| values_equal(v, &needle));
+                    (Value::Array(arr), n) if !matches!(n, Value::Array(_)) => {
+                        let pos = arr.iter().rposition(|v| values_equal(v, n));
                         match pos {
                             Some(i) => output(Value::Int(i as i64)),
                             None => output(Value::Null),
                         }
                     }
-                    _ => output(Value::Null),
+                    // jq: def rindex($i): indices($i) | .[-1:][0];
+                    _ => indices(input, needle, env, &mut |found| {
+                        index_at(&found, -1, env, output)
+                    }),
                 });
             }
         }
         "indices" | "_indices" => {
             if let Some(arg) = args.first() {
-                eval(arg, input, env, &mut |needle| match (input, &needle) {
-                    (Value::String(s), Value::String(n)) => {
-                        let mut positions = Vec::new();
-                        if !n.is_empty() {
-                            let mut start = 0;
-                            while let Some(byte_pos) = s[start..].find(n.as_str()) {
-                                let abs_byte = start + byte_pos;
-                                positions.push(Value::Int(s[..abs_byte].chars().count() as i64));
-                                // Advance by 1 character to allow overlapping matches
-                                let next_char_len =
-                                    s[abs_byte..].chars().next().map_or(1, |c| c.len_utf8());
-                                start = abs_byte + next_char_len;
-                            }
-                        }
-                        output(Value::Array(Arc::new(positions)));
-                    }
-                    (Value::Array(arr), Value::Array(needle_arr)) => {
-                        // Subsequence search for array needles
-                        let mut positions = Vec::new();
-                        let nlen = needle_arr.len();
-                        if nlen > 0 && nlen <= arr.len() {
-                            for i in 0..=arr.len() - nlen {
-                                let matches = arr[i..i + nlen]
-                                    .iter()
-                                    .zip(needle_arr.iter())
-                                    .all(|(a, b)| values_equal(a, b));
-                                if matches {
-                                    positions.push(Value::Int(i as i64));
-                                }
-                            }
-                        }
-                        output(Value::Array(Arc::new(positions)));
-                    }
-                    (Value::Array(arr), _) => {
-                        let positions: Vec<Value> = arr
-                            .iter()
-                            .enumerate()
-                            .filter(|(_, v)| values_equal(v, &needle))
-                            .map(|(i, _)| Value::Int(i as i64))
-                            .collect();
-                        output(Value::Array(Arc::new(positions)));
-                    }
-                    _ => output(Value::Array(Arc::new(Vec::new()))),
+                eval(arg, input, env, &mut |needle| {
+                    indices(input, needle, env, output)
                 });
             }
         }
@@ -355,11 +335,14 @@ pub(super) fn eval_strings(
                 let codepoints: Vec<Value> = s.chars().map(|c| Value::Int(c as i64)).collect();
                 output(Value::Array(Arc::new(codepoints)));
             } else {
-                set_error(format!(
-                    "{} ({}) cannot be exploded",
-                    input.type_name(),
-                    input.short_desc()
-                ));
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) cannot be exploded",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         }
         "implode" => {
@@ -378,11 +361,14 @@ pub(super) fn eval_strings(
                                 }
                                 _ => v.short_desc(),
                             };
-                            set_error(format!(
-                                "{} ({}) can't be imploded, unicode codepoint needs to be numeric",
-                                v.type_name(),
-                                desc
-                            ));
+                            set_error(
+                                env,
+                                format!(
+                                    "{} ({}) can't be imploded, unicode codepoint needs to be numeric",
+                                    v.type_name(),
+                                    desc
+                                ),
+                            );
                             return;
                         }
                     };
@@ -397,7 +383,7 @@ pub(super) fn eval_strings(
                 }
                 output(Value::String(s));
             } else {
-                set_error("implode input must be an array".to_string());
+                set_error(env, "implode input must be an array".to_string());
             }
         }
         "tojson" => {
@@ -434,11 +420,14 @@ pub(super) fn eval_strings(
                     && trimmed != "-NaN"
                     && trimmed != "-nan"
                 {
-                    set_error(format!(
-                        "Invalid numeric literal at EOF at line 1, column {} (while parsing '{}')",
-                        trimmed.len(),
-                        safe_truncate(s, 40)
-                    ));
+                    set_error(
+                        env,
+                        format!(
+                            "Invalid numeric literal at EOF at line 1, column {} (while parsing '{}')",
+                            trimmed.len(),
+                            safe_truncate(s, 40)
+                        ),
+                    );
                     return;
                 }
                 let padded = crate::simdjson::pad_buffer(s.as_bytes());
@@ -469,67 +458,157 @@ pub(super) fn eval_strings(
                                 .find('\'')
                                 .map(|p| after_first + p + 1 + 1) // +1 past close quote, +1 for 1-indexed
                                 .unwrap_or(after_first);
-                            set_error(format!(
-                                "Invalid string literal; expected \", but got ' at line 1, column {} (while parsing '{}')",
-                                col,
-                                safe_truncate(s, 40)
-                            ));
+                            set_error(
+                                env,
+                                format!(
+                                    "Invalid string literal; expected \", but got ' at line 1, column {} (while parsing '{}')",
+                                    col,
+                                    safe_truncate(s, 40)
+                                ),
+                            );
                         } else {
-                            set_error(format!(
-                                "Invalid numeric literal at EOF at line 1, column {} (while parsing '{}')",
-                                s.len(),
-                                safe_truncate(s, 40)
-                            ));
+                            set_error(
+                                env,
+                                format!(
+                                    "Invalid numeric literal at EOF at line 1, column {} (while parsing '{}')",
+                                    s.len(),
+                                    safe_truncate(s, 40)
+                                ),
+                            );
                         }
                     }
                 }
             } else {
-                set_error(format!(
-                    "{} ({}) is not a string and cannot be parsed as JSON",
-                    input.type_name(),
-                    input.short_desc()
-                ));
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) is not a string and cannot be parsed as JSON",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         }
         "utf8bytelength" => {
             if let Value::String(s) = input {
                 output(Value::Int(s.len() as i64));
             } else {
-                set_error(format!(
-                    "{} ({}) only strings have UTF-8 byte length",
-                    input.type_name(),
-                    input.short_desc()
-                ));
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) only strings have UTF-8 byte length",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         }
-        "ascii" => {
-            if let Value::String(s) = input
-                && let Some(c) = s.chars().next()
-            {
-                output(Value::Int(c as i64));
+        "ascii" => match input {
+            Value::Int(n @ 0..=127) => output(Value::String((*n as u8 as char).to_string())),
+            Value::Double(f, _) if (0.0..128.0).contains(f) => {
+                output(Value::String((*f as u8 as char).to_string()))
             }
-        }
+            _ => set_error(
+                env,
+                format!(
+                    "{} ({}) is not an ASCII codepoint (0-127)",
+                    input.type_name(),
+                    input.short_desc()
+                ),
+            ),
+        },
         "toboolean" => match input {
             Value::Bool(_) => output(input.clone()),
             Value::String(s) => match s.as_str() {
                 "true" => output(Value::Bool(true)),
                 "false" => output(Value::Bool(false)),
                 _ => {
-                    set_error(format!(
-                        "{} ({}) cannot be parsed as a boolean",
-                        input.type_name(),
-                        input.short_desc()
-                    ));
+                    set_error(
+                        env,
+                        format!(
+                            "{} ({}) cannot be parsed as a boolean",
+                            input.type_name(),
+                            input.short_desc()
+                        ),
+                    );
                 }
             },
             _ => {
-                set_error(format!(
-                    "{} ({}) cannot be parsed as a boolean",
-                    input.type_name(),
-                    input.short_desc()
-                ));
+                set_error(
+                    env,
+                    format!(
+                        "{} ({}) cannot be parsed as a boolean",
+                        input.type_name(),
+                        input.short_desc()
+                    ),
+                );
             }
         },
         _ => {}
     }
 }
+
+/// `indices($i)` as jq defines it: every (overlapping) match position for a
+/// string in a string, a subarray in an array or an element of an array,
+/// and otherwise `.[$i]`, so `null` input gives `null` and a string searched
+/// for a number is an indexing error.
+fn indices(input: &Value, needle: Value, env: &Env, output: &mut dyn FnMut(Value)) {
+    match (input, &needle) {
+        (Value::String(s), Value::String(n)) => {
+            let mut positions = Vec::new();
+            if !n.is_empty() {
+                let mut start = 0;
+                while let Some(byte_pos) = s[start..].find(n.as_str()) {
+                    let abs_byte = start + byte_pos;
+                    positions.push(Value::Int(s[..abs_byte].chars().count() as i64));
+                    // Advance by 1 character to allow overlapping matches
+                    let next_char_len = s[abs_byte..].chars().next().map_or(1, |c| c.len_utf8());
+                    start = abs_byte + next_char_len;
+                }
+            }
+            output(Value::Array(Arc::new(positions)));
+        }
+        (Value::Array(arr), Value::Array(needle_arr)) => {
+            // Subsequence search for array needles; an empty needle matches nowhere
+            let mut positions = Vec::new();
+            let nlen = needle_arr.len();
+            if nlen > 0 && nlen <= arr.len() {
+                for i in 0..=arr.len() - nlen {
+                    let matches = arr[i..i + nlen]
+                        .iter()
+                        .zip(needle_arr.iter())
+                        .all(|(a, b)| values_equal(a, b));
+                    if matches {
+                        positions.push(Value::Int(i as i64));
+                    }
+                }
+            }
+            output(Value::Array(Arc::new(positions)));
+        }
+        (Value::Array(arr), _) => {
+            let positions: Vec<Value> = arr
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| values_equal(v, &needle))
+                .map(|(i, _)| Value::Int(i as i64))
+                .collect();
+            output(Value::Array(Arc::new(positions)));
+        }
+        _ => eval(
+            &Filter::Index(Box::new(Filter::Literal(needle))),
+            input,
+            env,
+            output,
+        ),
+    }
+}
+
+/// `.[i]` on a result of [`indices`].
+fn index_at(found: &Value, i: i64, env: &Env, output: &mut dyn FnMut(Value)) {
+    eval(
+        &Filter::Index(Box::new(Filter::Literal(Value::Int(i)))),
+        found,
+        env,
+        output,
+    );
+}
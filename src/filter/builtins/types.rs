@@ -1,9 +1,12 @@
 use crate::filter::{Env, Filter};
-use crate::value::Value;
+use crate::value::{Value, bigint};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use super::super::eval::eval;
+use super::super::eval::set_object_key_error;
 use super::super::value_ops::value_contains;
+use super::{set_error, value_to_string};
 
 pub(super) fn eval_types(
     name: &str,
@@ -23,7 +26,7 @@ pub(super) fn eval_types(
                     .map_or_else(|| Value::Double((*n as f64).abs(), None), Value::Int),
             ),
             Value::Double(f, raw) => {
-                let abs_raw = if f.is_infinite() || *f == 0.0 {
+                let abs_raw = if f.is_infinite() || *f == 0.0 || bigint::is_big_literal(input) {
                     raw.as_ref().map(|s| {
                         s.strip_prefix('-')
                             .map_or_else(|| s.clone(), |rest| rest.into())
@@ -33,7 +36,10 @@ pub(super) fn eval_types(
                 };
                 output(Value::Double(f.abs(), abs_raw));
             }
-            Value::Bool(_) => output(Value::Null),
+            Value::Bool(_) => set_error(
+                env,
+                format!("boolean ({}) has no length", input.short_desc()),
+            ),
         },
         "type" => {
             output(Value::String(input.type_name().to_string()));
@@ -80,96 +86,201 @@ pub(super) fn eval_types(
                 output(input.clone());
             }
         }
-        "scalars" => {
-            if !matches!(input, Value::Array(_) | Value::Object(_)) {
-                output(input.clone());
-            }
+        "scalars" if !matches!(input, Value::Array(_) | Value::Object(_)) => {
+            output(input.clone());
         }
         "has" => {
             if let Some(key_filter) = args.first() {
-                let mut key_val = Value::Null;
-                eval(key_filter, input, env, &mut |v| key_val = v);
-                match (input, &key_val) {
-                    (Value::Object(obj), Value::String(key)) => {
-                        let found = obj.iter().any(|(k, _)| k == key);
-                        output(Value::Bool(found));
-                    }
-                    (Value::Array(arr), Value::Int(idx)) => {
-                        let found = *idx >= 0 && (*idx as usize) < arr.len();
-                        output(Value::Bool(found));
-                    }
-                    _ => output(Value::Bool(false)),
-                }
+                eval(
+                    key_filter,
+                    input,
+                    env,
+                    &mut |key| match has_key(input, &key) {
+                        Ok(found) => output(Value::Bool(found)),
+                        Err(msg) => set_error(env, msg),
+                    },
+                );
             }
         }
         "contains" => {
-            if let (Some(arg), _) = (args.first(), input) {
-                let mut pattern = Value::Null;
-                eval(arg, input, env, &mut |v| pattern = v);
-                output(Value::Bool(value_contains(input, &pattern)));
+            if let Some(arg) = args.first() {
+                eval(
+                    arg,
+                    input,
+                    env,
+                    &mut |pattern| match contains_checked(input, &pattern) {
+                        Ok(found) => output(Value::Bool(found)),
+                        Err(msg) => set_error(env, msg),
+                    },
+                );
             }
         }
         "inside" => {
             if let Some(arg) = args.first() {
-                let mut container = Value::Null;
-                eval(arg, input, env, &mut |v| container = v);
-                output(Value::Bool(value_contains(&container, input)));
+                eval(
+                    arg,
+                    input,
+                    env,
+                    &mut |container| match contains_checked(&container, input) {
+                        Ok(found) => output(Value::Bool(found)),
+                        Err(msg) => set_error(env, msg),
+                    },
+                );
             }
         }
         "in" => {
             if let Some(arg) = args.first() {
-                let mut container = Value::Null;
-                eval(arg, input, env, &mut |v| container = v);
-                match (&container, input) {
-                    (Value::Object(obj), Value::String(key)) => {
-                        output(Value::Bool(obj.iter().any(|(k, _)| k == key)));
-                    }
-                    (Value::Array(arr), Value::Int(idx)) => {
-                        output(Value::Bool(*idx >= 0 && (*idx as usize) < arr.len()));
-                    }
-                    _ => output(Value::Bool(false)),
-                }
+                eval(
+                    arg,
+                    input,
+                    env,
+                    &mut |container| match has_key(&container, input) {
+                        Ok(found) => output(Value::Bool(found)),
+                        Err(msg) => set_error(env, msg),
+                    },
+                );
             }
         }
         "to_entries" => {
-            if let Value::Object(obj) = input {
-                let entries: Vec<Value> = obj
-                    .iter()
-                    .map(|(k, v)| {
-                        Value::Object(Arc::new(vec![
-                            ("key".into(), Value::String(k.clone())),
-                            ("value".into(), v.clone()),
-                        ]))
-                    })
-                    .collect();
-                output(Value::Array(Arc::new(entries)));
+            let entry = |key: Value, value: &Value| {
+                Value::Object(Arc::new(vec![
+                    ("key".into(), key),
+                    ("value".into(), value.clone()),
+                ]))
+            };
+            match input {
+                Value::Object(obj) => {
+                    let entries = obj
+                        .iter()
+                        .map(|(k, v)| entry(Value::String(k.clone()), v))
+                        .collect();
+                    output(Value::Array(Arc::new(entries)));
+                }
+                Value::Array(arr) => {
+                    let entries = arr
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| entry(Value::Int(i as i64), v))
+                        .collect();
+                    output(Value::Array(Arc::new(entries)));
+                }
+                _ => set_error(
+                    env,
+                    format!("{} ({}) has no keys", input.type_name(), input.short_desc()),
+                ),
             }
         }
         "from_entries" => {
-            if let Value::Array(arr) = input {
-                let mut obj = Vec::new();
-                for entry in arr.iter() {
-                    if let Value::Object(fields) = entry {
-                        let key = fields
-                            .iter()
-                            .find(|(k, _)| k == "key" || k == "Key" || k == "name" || k == "Name")
-                            .map(|(_, v)| match v {
-                                Value::String(s) => s.clone(),
-                                Value::Int(n) => n.to_string(),
-                                _ => String::new(),
-                            })
-                            .unwrap_or_default();
-                        let val = fields
-                            .iter()
-                            .find(|(k, _)| k == "value" || k == "Value")
-                            .map(|(_, v)| v.clone())
-                            .unwrap_or(Value::Null);
-                        obj.push((key, val));
+            let entries: Vec<&Value> = match input {
+                Value::Array(arr) => arr.iter().collect(),
+                Value::Object(obj) => obj.iter().map(|(_, v)| v).collect(),
+                _ => {
+                    set_error(
+                        env,
+                        format!(
+                            "Cannot iterate over {} ({})",
+                            input.type_name(),
+                            input.short_desc()
+                        ),
+                    );
+                    return;
+                }
+            };
+            let mut obj: Vec<(String, Value)> = Vec::with_capacity(entries.len());
+            let mut slots: HashMap<String, usize> = HashMap::new();
+            for entry in entries {
+                let Value::Object(fields) = entry else {
+                    set_error(
+                        env,
+                        format!("Cannot index {} with \"key\"", entry.type_name()),
+                    );
+                    return;
+                };
+                let (key, value) = entry_key_value(fields);
+                let key = match key {
+                    Value::String(s) => s,
+                    Value::Object(_) | Value::Array(_) => {
+                        set_object_key_error(env, &key);
+                        return;
+                    }
+                    other => value_to_string(&other),
+                };
+                // Later entries overwrite earlier ones in place, like `+`.
+                match slots.get(&key) {
+                    Some(&i) => obj[i].1 = value,
+                    None => {
+                        slots.insert(key.clone(), obj.len());
+                        obj.push((key, value));
                     }
                 }
-                output(Value::Object(Arc::new(obj)));
             }
+            output(Value::Object(Arc::new(obj)));
         }
         _ => {}
     }
 }
+
+/// Key and value of one `from_entries` entry, following jq 1.7: `key`
+/// unless it is null, else the first truthy of `k`/`name`/`Name`/`K`/`Key`/`N`;
+/// the value is `value`, else `v`, else `Value`, with missing fields as null.
+fn entry_key_value(fields: &[(String, Value)]) -> (Value, Value) {
+    let field = |name: &str| fields.iter().find(|(k, _)| k == name).map(|(_, v)| v);
+    let key = match field("key") {
+        Some(k) if !matches!(k, Value::Null) => k.clone(),
+        _ => {
+            let alternatives = ["k", "name", "Name", "K", "Key", "N"];
+            alternatives
+                .iter()
+                .filter_map(|name| field(name))
+                .find(|v| !matches!(v, Value::Null | Value::Bool(false)))
+                .or_else(|| field("N"))
+                .cloned()
+                .unwrap_or(Value::Null)
+        }
+    };
+    let value = field("value")
+        .or_else(|| field("v"))
+        .or_else(|| field("Value"))
+        .cloned()
+        .unwrap_or(Value::Null);
+    (key, value)
+}
+
+/// jq's `has`: a string key of an object or a numeric index of an array
+/// (fractional indices truncate). `null` has no keys; other pairs are errors.
+fn has_key(container: &Value, key: &Value) -> Result<bool, String> {
+    let index_in = |len: usize, i: i64| i >= 0 && (i as u64) < len as u64;
+    match (container, key) {
+        (Value::Null, _) => Ok(false),
+        (Value::Object(obj), Value::String(k)) => Ok(obj.iter().any(|(name, _)| name == k)),
+        (Value::Array(arr), Value::Int(i)) => Ok(index_in(arr.len(), *i)),
+        (Value::Array(arr), Value::Double(f, _)) => {
+            Ok(!f.is_nan() && index_in(arr.len(), *f as i64))
+        }
+        _ => Err(format!(
+            "Cannot check whether {} has a {} key",
+            container.type_name(),
+            key.type_name()
+        )),
+    }
+}
+
+/// jq's `contains`, which refuses to compare values of different kinds at
+/// the top level. `true` and `false` are distinct kinds there, as in jq.
+fn contains_checked(haystack: &Value, needle: &Value) -> Result<bool, String> {
+    let same_kind = match (haystack, needle) {
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        _ => haystack.type_name() == needle.type_name(),
+    };
+    if same_kind {
+        Ok(value_contains(haystack, needle))
+    } else {
+        Err(format!(
+            "{} ({}) and {} ({}) cannot have their containment checked",
+            haystack.type_name(),
+            haystack.short_desc(),
+            needle.type_name(),
+            needle.short_desc()
+        ))
+    }
+}
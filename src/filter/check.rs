@@ -0,0 +1,358 @@
+//! Static validation for `--check`.
+//!
+//! The evaluator only finds out that a function or variable doesn't exist
+//! when an input reaches the call, if one ever does. `check` walks the whole
+//! filter instead, tracking what each `def`, `as` binding,
+//! `reduce`/`foreach` and `label` brings into scope, and reports every call
+//! to an undefined function (including a builtin called with the wrong number
+//! of arguments), every unbound `$variable` and every `break` without its
+//! `label`.
+
+use super::builtins::{UNSUPPORTED_BUILTINS, builtin_arities, is_builtin};
+use super::{Env, Filter, ObjKey, Pattern, PatternKey, StringPart};
+
+/// The problems in `filter`, in source order, as messages like jq's compile
+/// errors. `env` holds what's defined outside the filter: `--arg`-style
+/// variables, `$ENV`, `$ARGS` and module imports.
+pub fn check(filter: &Filter, env: &Env) -> Vec<String> {
+    let mut checker = Checker {
+        env,
+        labels_only: false,
+        vars: Vec::new(),
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        problems: Vec::new(),
+    };
+    checker.walk(filter);
+    checker.problems
+}
+
+/// The `break`s in `filter` without an enclosing `label` of their name. jq
+/// rejects these before running anything, whether or not `--check` is given.
+pub fn unbound_labels(filter: &Filter) -> Vec<String> {
+    let env = Env::empty();
+    let mut checker = Checker {
+        env: &env,
+        labels_only: true,
+        vars: Vec::new(),
+        funcs: Vec::new(),
+        labels: Vec::new(),
+        problems: Vec::new(),
+    };
+    checker.walk(filter);
+    checker.problems
+}
+
+struct Checker<'a> {
+    env: &'a Env,
+    /// Only report `break`s to undefined labels.
+    labels_only: bool,
+    vars: Vec<String>,
+    funcs: Vec<(String, usize)>,
+    labels: Vec<String>,
+    problems: Vec<String>,
+}
+
+impl Checker<'_> {
+    fn walk(&mut self, filter: &Filter) {
+        match filter {
+            Filter::Identity
+            | Filter::Field(_)
+            | Filter::FieldPath(_)
+            | Filter::Iterate
+            | Filter::Literal(_)
+            | Filter::Recurse => {}
+            Filter::Var(name) => {
+                let defined = self.labels_only
+                    || matches!(name.as_str(), "$ENV" | "$__loc__")
+                    || self.vars.contains(name)
+                    || self.env.get_var(name).is_some();
+                if !defined {
+                    self.report(format!("{name} is not defined"));
+                }
+            }
+            Filter::Break(name) => {
+                if !self.labels.contains(name) {
+                    let label = name.trim_start_matches('$');
+                    self.report(format!("$*label-{label} is not defined"));
+                }
+            }
+            Filter::Builtin(name, args) => {
+                self.check_call(name, args.len());
+                args.iter().for_each(|a| self.walk(a));
+            }
+            Filter::Index(f)
+            | Filter::Select(f)
+            | Filter::ArrayConstruct(f)
+            | Filter::Not(f)
+            | Filter::Try(f)
+            | Filter::Neg(f) => self.walk(f),
+            Filter::Pipe(a, b)
+            | Filter::Compare(a, _, b)
+            | Filter::Arith(a, _, b)
+            | Filter::BoolOp(a, _, b)
+            | Filter::Alternative(a, b)
+            | Filter::TryCatch(a, b)
+            | Filter::Assign(a, _, b) => {
+                self.walk(a);
+                self.walk(b);
+            }
+            Filter::Comma(items) => items.iter().for_each(|f| self.walk(f)),
+            Filter::ObjectConstruct(pairs) => {
+                for (key, value) in pairs {
+                    if let ObjKey::Expr(f) = key {
+                        self.walk(f);
+                    }
+                    self.walk(value);
+                }
+            }
+            Filter::StringInterp(parts) => {
+                for part in parts {
+                    if let StringPart::Expr(f) = part {
+                        self.walk(f);
+                    }
+                }
+            }
+            Filter::IfThenElse(cond, then, otherwise) => {
+                self.walk(cond);
+                self.walk(then);
+                if let Some(f) = otherwise {
+                    self.walk(f);
+                }
+            }
+            Filter::Slice(start, end) => {
+                start.iter().chain(end).for_each(|f| self.walk(f));
+            }
+            Filter::PostfixIndex(base, idx) => {
+                self.walk(base);
+                self.walk(idx);
+            }
+            Filter::PostfixSlice(base, start, end) => {
+                self.walk(base);
+                start.iter().chain(end).for_each(|f| self.walk(f));
+            }
+            Filter::Label(name, body) => {
+                self.labels.push(name.clone());
+                self.walk(body);
+                self.labels.pop();
+            }
+            Filter::Bind(src, pat, body) => {
+                self.walk(src);
+                self.scoped(std::slice::from_ref(pat), |c| c.walk(body));
+            }
+            Filter::AltBind(src, pats, body) => {
+                self.walk(src);
+                self.scoped(pats, |c| c.walk(body));
+            }
+            Filter::Reduce(src, pat, init, update) => {
+                self.walk(src);
+                self.walk(init);
+                self.scoped(std::slice::from_ref(pat), |c| c.walk(update));
+            }
+            Filter::Foreach(src, pat, init, update, extract) => {
+                self.walk(src);
+                self.walk(init);
+                self.scoped(std::slice::from_ref(pat), |c| {
+                    c.walk(update);
+                    if let Some(f) = extract {
+                        c.walk(f);
+                    }
+                });
+            }
+            Filter::Def {
+                name,
+                params,
+                body,
+                rest,
+            } => {
+                let (vars, funcs) = (self.vars.len(), self.funcs.len());
+                self.funcs.push((name.clone(), params.len()));
+                for param in params {
+                    // `$x` is both a variable and a filter in the body.
+                    if param.starts_with('$') {
+                        self.vars.push(param.clone());
+                    }
+                    self.funcs
+                        .push((param.trim_start_matches('$').to_string(), 0));
+                }
+                self.walk(body);
+                self.vars.truncate(vars);
+                self.funcs.truncate(funcs + 1);
+                self.walk(rest);
+                self.funcs.truncate(funcs);
+            }
+            Filter::Import { rest, .. }
+            | Filter::Include { rest, .. }
+            | Filter::ModuleDecl { rest, .. } => self.walk(rest),
+        }
+    }
+
+    fn check_call(&mut self, name: &str, arity: usize) {
+        let defined = self.labels_only
+            || self.funcs.iter().any(|(n, a)| n == name && *a == arity)
+            || self.env.get_func(name, arity).is_some()
+            || is_builtin(name, arity);
+        if defined {
+            return;
+        }
+        if UNSUPPORTED_BUILTINS.contains(&(name, arity)) {
+            self.report(format!("{name}/{arity} is not supported by qj"));
+            return;
+        }
+        let arities = builtin_arities(name);
+        if arities.is_empty() {
+            self.report(format!("{name}/{arity} is not defined"));
+        } else {
+            let takes = arities
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join(" or ");
+            let plural = if arities == [1] { "" } else { "s" };
+            self.report(format!(
+                "{name}/{arity} is not defined ({name} takes {takes} argument{plural})"
+            ));
+        }
+    }
+
+    /// Walk with the variables bound by `patterns` in scope. Computed keys
+    /// in object patterns are checked in that scope too.
+    fn scoped(&mut self, patterns: &[Pattern], body: impl FnOnce(&mut Self)) {
+        let depth = self.vars.len();
+        for pat in patterns {
+            bind_pattern(pat, &mut self.vars);
+        }
+        for pat in patterns {
+            self.walk_pattern_keys(pat);
+        }
+        body(self);
+        self.vars.truncate(depth);
+    }
+
+    fn walk_pattern_keys(&mut self, pat: &Pattern) {
+        match pat {
+            Pattern::Var(_) => {}
+            Pattern::Array(pats) => pats.iter().for_each(|p| self.walk_pattern_keys(p)),
+            Pattern::Object(pairs) => {
+                for (key, p) in pairs {
+                    if let PatternKey::Expr(f) = key {
+                        self.walk(f);
+                    }
+                    self.walk_pattern_keys(p);
+                }
+            }
+        }
+    }
+
+    fn report(&mut self, problem: String) {
+        if !self.problems.contains(&problem) {
+            self.problems.push(problem);
+        }
+    }
+}
+
+fn bind_pattern(pat: &Pattern, vars: &mut Vec<String>) {
+    match pat {
+        Pattern::Var(name) => vars.push(name.clone()),
+        Pattern::Array(pats) => pats.iter().for_each(|p| bind_pattern(p, vars)),
+        Pattern::Object(pairs) => {
+            for (key, p) in pairs {
+                if let PatternKey::Var(name) = key {
+                    vars.push(name.clone());
+                }
+                bind_pattern(p, vars);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::parse;
+    use crate::value::Value;
+
+    fn problems(filter: &str) -> Vec<String> {
+        check(&parse(filter).unwrap(), &Env::empty())
+    }
+
+    #[test]
+    fn valid_filters_have_no_problems() {
+        for filter in [
+            ".a | map(select(.b > 1)) | length",
+            "def f(g; $x): g + $x + x; f(.; 1)",
+            "def fac: if . <= 1 then 1 else . * (. - 1 | fac) end; fac",
+            ". as [$a, {b: $b, $c}] | $a + $b + $c",
+            ". as {a: $x} ?// [$x] | $x",
+            "reduce .[] as $x (0; . + $x)",
+            "foreach .[] as $x (0; . + $x; [$x, .])",
+            "label $out | .[] | if . then break $out else . end",
+            "$ENV.PATH, $__loc__, @base64, range(1; 10; 2), splits(\"a\")",
+            "{$__loc__, a: 1} | to_entries",
+        ] {
+            assert_eq!(problems(filter), Vec::<String>::new(), "{filter}");
+        }
+    }
+
+    #[test]
+    fn undefined_functions_and_arities() {
+        assert_eq!(
+            problems("foo | bar(1)"),
+            ["foo/0 is not defined", "bar/1 is not defined"]
+        );
+        assert_eq!(
+            problems("length(1), test"),
+            [
+                "length/1 is not defined (length takes 0 arguments)",
+                "test/0 is not defined (test takes 1 or 2 arguments)",
+            ]
+        );
+        // A definition is only visible after it, and its parameters only in its body.
+        assert_eq!(problems("def f(g): g; g"), ["g/0 is not defined"]);
+        assert_eq!(problems("(def f: 1; f) | f"), ["f/0 is not defined"]);
+        assert_eq!(problems("def f: 1; f(2)"), ["f/1 is not defined"]);
+        assert_eq!(
+            problems("halt_error(1)"),
+            ["halt_error/1 is not supported by qj"]
+        );
+    }
+
+    #[test]
+    fn unbound_variables_and_labels() {
+        assert_eq!(problems("$x"), ["$x is not defined"]);
+        assert_eq!(problems("(. as $x | $x) | $x"), ["$x is not defined"]);
+        assert_eq!(problems("reduce .[] as $x ($x; .)"), ["$x is not defined"]);
+        assert_eq!(problems("break $out"), ["$*label-out is not defined"]);
+        let env = Env::empty().bind_var("$x".into(), Value::Int(1));
+        assert!(check(&parse("$x").unwrap(), &env).is_empty());
+    }
+
+    #[test]
+    fn unbound_labels_ignore_other_problems() {
+        let labels = |f: &str| unbound_labels(&parse(f).unwrap());
+        assert!(labels("foo | $x | label $a | break $a").is_empty());
+        assert_eq!(
+            labels("(label $a | 1), break $a, label $b | break $c"),
+            ["$*label-a is not defined", "$*label-c is not defined"]
+        );
+        // A break in a function body needs the label around the definition.
+        assert_eq!(
+            labels("def f: break $a; label $a | f"),
+            ["$*label-a is not defined"]
+        );
+    }
+
+    #[test]
+    fn registry_has_no_duplicates() {
+        let mut seen = std::collections::HashSet::new();
+        for entry in super::super::builtins::BUILTINS {
+            assert!(seen.insert(entry), "{entry:?} listed twice");
+        }
+        for entry in UNSUPPORTED_BUILTINS {
+            assert!(
+                seen.insert(entry),
+                "{entry:?} is implemented or listed twice"
+            );
+        }
+    }
+}
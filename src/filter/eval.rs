@@ -2,7 +2,9 @@
 ///
 /// Uses generator semantics: each filter operation calls `output` for
 /// each result, avoiding intermediate Vec allocations.
-use crate::filter::{ArithOp, AssignOp, BoolOp, Env, Filter, ObjKey, Pattern, PatternKey};
+use crate::filter::{
+    ArithOp, AssignOp, BoolOp, Env, Filter, InputSource, ObjKey, Pattern, PatternKey,
+};
 use crate::value::Value;
 use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, VecDeque};
@@ -10,7 +12,7 @@ use std::sync::Arc;
 
 const MAX_EVAL_DEPTH: usize = 256;
 
-use super::value_ops::{arith_values, compare_values, recurse};
+use super::value_ops::{arith_values, compare_values, index_error, negate_value, recurse};
 
 /// Match a destructuring pattern against a value (lenient mode for `as`).
 /// Always succeeds — missing fields/indices produce null.
@@ -18,6 +20,45 @@ pub fn match_pattern(pattern: &Pattern, value: &Value, env: &Env) -> Option<Env>
     match_pattern_inner(pattern, value, env, false)
 }
 
+/// Bind one element of a `reduce`/`foreach` source to the loop pattern. A
+/// plain `$name` pattern overwrites the previous iteration's binding in place
+/// when the update didn't capture it, so long loops don't allocate a scope
+/// node per element; destructuring patterns bind from scratch each time.
+fn bind_loop_pattern<'a>(
+    pattern: &Pattern,
+    val: Value,
+    env: &Env,
+    loop_env: &'a mut Option<Env>,
+) -> Option<&'a Env> {
+    match pattern {
+        Pattern::Var(name) => {
+            let unbound = match loop_env {
+                Some(bound) => bound.rebind_innermost(val).err(),
+                None => Some(val),
+            };
+            if let Some(val) = unbound {
+                *loop_env = Some(env.bind_var(name.clone(), val));
+            }
+        }
+        _ => *loop_env = match_pattern(pattern, &val, env),
+    }
+    loop_env.as_ref()
+}
+
+/// Run a `reduce`/`foreach` update on the current state, which is moved in
+/// rather than cloned. The last output becomes the new state; an update that
+/// outputs nothing keeps the current one.
+/// The variable a `label $name` binds to the identity of its current run.
+fn label_var(name: &str) -> String {
+    format!("*label-{name}")
+}
+
+fn update_loop_state(update: &Filter, state: Value, env: &Env) -> Value {
+    let mut next = None;
+    eval(update, &state, env, &mut |v| next = Some(v));
+    next.unwrap_or(state)
+}
+
 /// Match a destructuring pattern against a value (strict mode for `?//`).
 /// Returns None if the value's structure doesn't match the pattern.
 fn try_match_pattern(pattern: &Pattern, value: &Value, env: &Env) -> Option<Env> {
@@ -111,17 +152,6 @@ fn collect_pattern_vars(pattern: &Pattern, vars: &mut Vec<String>) {
     }
 }
 
-thread_local! {
-    /// Last error value set by `error` / `error(msg)` builtins.
-    pub(super) static LAST_ERROR: RefCell<Option<Value>> = const { RefCell::new(None) };
-    /// Break signal for label-break unwinding.
-    static BREAK_SIGNAL: RefCell<Option<String>> = const { RefCell::new(None) };
-    /// Input queue for `input`/`inputs` builtins.
-    pub(super) static INPUT_QUEUE: RefCell<VecDeque<Value>> = const { RefCell::new(VecDeque::new()) };
-    /// Current eval() recursion depth for stack overflow protection.
-    static EVAL_DEPTH: Cell<usize> = const { Cell::new(0) };
-}
-
 // HashMap::new() is not const on this Rust version, so these are separate.
 thread_local! {
     /// Module metadata cache for `modulemeta` builtin, set before evaluation.
@@ -130,29 +160,67 @@ thread_local! {
     static MODULE_SEARCH_PATHS: RefCell<Vec<std::path::PathBuf>> = const { RefCell::new(Vec::new()) };
 }
 
+/// The step `A[B]` takes after `A`, for path and update purposes. `B` is
+/// evaluated against the input of the whole expression, not `A`'s output, so
+/// its values are fixed first: `.x[.i]` is `.x | .[v]` for each `v` of `.i`.
+pub(super) fn postfix_index_step(idx: &Filter, input: &Value, env: &Env) -> Filter {
+    let mut values = Vec::new();
+    eval(idx, input, env, &mut |v| values.push(Filter::Literal(v)));
+    let idx = match values.len() {
+        1 => values.pop().unwrap_or(Filter::Identity),
+        _ => Filter::Comma(values),
+    };
+    Filter::Index(Box::new(idx))
+}
+
+/// The step `A[s:e]` takes after `A`, with `s` and `e` evaluated against the
+/// input of the whole expression as in [`postfix_index_step`].
+pub(super) fn postfix_slice_step(
+    start: &Option<Box<Filter>>,
+    end: &Option<Box<Filter>>,
+    input: &Value,
+    env: &Env,
+) -> Filter {
+    let bound = |f: &Option<Box<Filter>>| {
+        f.as_ref().map(|f| {
+            let mut last = Value::Null;
+            eval(f, input, env, &mut |v| last = v);
+            Box::new(Filter::Literal(last))
+        })
+    };
+    Filter::Slice(bound(start), bound(end))
+}
+
 /// RAII guard that decrements the eval depth counter on drop.
-struct EvalDepthGuard;
+struct EvalDepthGuard<'a>(&'a Cell<usize>);
 
-impl Drop for EvalDepthGuard {
+impl Drop for EvalDepthGuard<'_> {
     fn drop(&mut self) {
-        EVAL_DEPTH.with(|d| d.set(d.get() - 1));
+        self.0.set(self.0.get() - 1);
     }
 }
 
-/// Take the last error value, if any, clearing the thread-local state.
+/// Take the last error value, if any, clearing it from `env`'s context.
 /// Called after evaluation to check for uncaught runtime errors.
-pub fn take_last_error() -> Option<Value> {
-    LAST_ERROR.with(|e| e.borrow_mut().take())
+pub fn take_last_error(env: &Env) -> Option<Value> {
+    env.take_error()
 }
 
 /// Set a runtime error (used by flat_eval for type errors).
-pub fn set_last_error(err: Value) {
-    LAST_ERROR.with(|e| *e.borrow_mut() = Some(err));
+pub fn set_last_error(env: &Env, err: Value) {
+    env.set_error(err);
+}
+
+/// Abandon the current evaluation: outputs stop and generators wind down as
+/// when breaking out to a `label`, but no label catches it. For when output
+/// can no longer be written.
+pub fn stop_evaluation(env: &Env) {
+    *env.break_signal().borrow_mut() = Some("*abort".to_string());
 }
 
 /// Check if a runtime error is currently set (non-consuming).
-pub fn has_last_error() -> bool {
-    LAST_ERROR.with(|e| e.borrow().is_some())
+pub fn has_last_error(env: &Env) -> bool {
+    env.has_error()
 }
 
 /// Set the module metadata cache and search paths (called before evaluation by main).
@@ -195,24 +263,35 @@ pub(super) fn load_module_metadata_on_demand(name: &str, output: &mut dyn FnMut(
 }
 
 /// Set the input queue for `input`/`inputs` builtins.
-pub fn set_input_queue(values: VecDeque<Value>) {
-    INPUT_QUEUE.with(|q| *q.borrow_mut() = values);
+pub fn set_input_queue(env: &Env, values: VecDeque<Value>) {
+    env.set_input_queue(values);
+}
+
+/// Install a lazy source for `input`/`inputs` builtins.
+pub fn set_input_source(env: &Env, source: InputSource) {
+    env.set_input_source(source);
+}
+
+/// Pull the next value from the `input`/`inputs` source, if any remain.
+pub fn next_input(env: &Env) -> Option<Value> {
+    env.next_input()
 }
 
 /// Take back the input queue (returns remaining unconsumed values).
-pub fn take_input_queue() -> VecDeque<Value> {
-    INPUT_QUEUE.with(|q| std::mem::take(&mut *q.borrow_mut()))
+pub fn take_input_queue(env: &Env) -> VecDeque<Value> {
+    env.take_input_queue()
 }
 
-/// Public entry point — creates an empty env for top-level evaluation.
-pub fn eval_filter(filter: &Filter, input: &Value, output: &mut dyn FnMut(Value)) {
-    // Clear stale state from any previous evaluation
-    LAST_ERROR.with(|e| *e.borrow_mut() = None);
-    BREAK_SIGNAL.with(|b| *b.borrow_mut() = None);
-    eval(filter, input, &Env::empty(), output);
+/// Public entry point — evaluates with an empty env and returns the
+/// uncaught runtime error, if any.
+pub fn eval_filter(filter: &Filter, input: &Value, output: &mut dyn FnMut(Value)) -> Option<Value> {
+    let env = Env::empty();
+    eval(filter, input, &env, output);
+    env.take_error()
 }
 
 /// Public entry point with a pre-populated environment (for --arg / --argjson).
+/// Uncaught errors are left in `env` for `take_last_error`.
 pub fn eval_filter_with_env(
     filter: &Filter,
     input: &Value,
@@ -220,27 +299,80 @@ pub fn eval_filter_with_env(
     output: &mut dyn FnMut(Value),
 ) {
     // Clear stale state from any previous evaluation
-    LAST_ERROR.with(|e| *e.borrow_mut() = None);
-    BREAK_SIGNAL.with(|b| *b.borrow_mut() = None);
+    env.take_error();
+    *env.break_signal().borrow_mut() = None;
     eval(filter, input, env, output);
 }
 
+/// Like [`eval_filter_with_env`], but stops the filter as soon as `take`
+/// returns false, the way `limit` stops its generator.
+pub(crate) fn eval_filter_until(
+    filter: &Filter,
+    input: &Value,
+    env: &Env,
+    take: &mut dyn FnMut(Value) -> bool,
+) {
+    env.take_error();
+    *env.break_signal().borrow_mut() = None;
+    super::builtins::eval_until(filter, input, env, take);
+}
+
+/// Run `f` with `key: value` set in a partially built object (a repeated key
+/// keeps its first position but takes the later value, as in jq), then undo.
+pub(crate) fn with_object_entry<F>(
+    current: &mut Vec<(String, Value)>,
+    key: &str,
+    value: Value,
+    f: F,
+) where
+    F: FnOnce(&mut Vec<(String, Value)>),
+{
+    if let Some(i) = current.iter().position(|(k, _)| k == key) {
+        let prev = std::mem::replace(&mut current[i].1, value);
+        f(current);
+        current[i].1 = prev;
+    } else {
+        current.push((key.to_string(), value));
+        f(current);
+        current.pop();
+    }
+}
+
+/// Error for a computed object key that is not a string.
+pub(crate) fn set_object_key_error(env: &Env, key: &Value) {
+    env.set_error(Value::String(format!(
+        "Cannot use {} ({}) as object key",
+        key.type_name(),
+        key.short_desc()
+    )));
+}
+
 /// Evaluate a filter against an input value, calling `output` for each result.
 pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Value)) {
+    if super::profile::is_enabled() {
+        super::profile::record(filter, output, |output| {
+            eval_node(filter, input, env, output)
+        });
+    } else {
+        eval_node(filter, input, env, output);
+    }
+}
+
+/// [`eval`] without the `--profile` instrumentation.
+fn eval_node(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Value)) {
     // Check for break signal — stop producing output during label-break unwind.
-    if BREAK_SIGNAL.with(|b| b.borrow().is_some()) {
+    if env.break_signal().borrow().is_some() {
         return;
     }
 
     // Recursion depth limit — prevents stack overflow from infinite recursion.
-    EVAL_DEPTH.with(|d| d.set(d.get() + 1));
-    let _guard = EvalDepthGuard;
-    if EVAL_DEPTH.with(|d| d.get()) > MAX_EVAL_DEPTH {
-        LAST_ERROR.with(|e| {
-            *e.borrow_mut() = Some(Value::String(format!(
-                "Evaluation depth limit exceeded ({MAX_EVAL_DEPTH})"
-            )));
-        });
+    let depth = env.depth();
+    depth.set(depth.get() + 1);
+    let _guard = EvalDepthGuard(depth);
+    if depth.get() > MAX_EVAL_DEPTH {
+        env.set_error(Value::String(format!(
+            "Evaluation depth limit exceeded ({MAX_EVAL_DEPTH})"
+        )));
         return;
     }
     match filter {
@@ -258,16 +390,43 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
             }
             Value::Null => output(Value::Null),
             _ => {
-                LAST_ERROR.with(|e| {
-                    *e.borrow_mut() = Some(Value::String(format!(
-                        "Cannot index {} with string \"{}\"",
-                        input.type_name(),
-                        name
-                    )));
-                });
+                env.set_error(Value::String(format!(
+                    "Cannot index {} with string \"{}\"",
+                    input.type_name(),
+                    name
+                )));
             }
         },
 
+        Filter::FieldPath(names) => {
+            let mut current = input;
+            for name in names {
+                match current {
+                    Value::Object(obj) => match obj.iter().find(|(k, _)| k == name) {
+                        Some((_, v)) => current = v,
+                        None => {
+                            output(Value::Null);
+                            return;
+                        }
+                    },
+                    // Every later field of null is null too
+                    Value::Null => {
+                        output(Value::Null);
+                        return;
+                    }
+                    _ => {
+                        env.set_error(Value::String(format!(
+                            "Cannot index {} with string \"{}\"",
+                            current.type_name(),
+                            name
+                        )));
+                        return;
+                    }
+                }
+            }
+            output(current.clone());
+        }
+
         Filter::Index(idx_filter) => {
             // Evaluate the index expression — iterate all outputs for generator semantics
             eval(idx_filter, input, env, &mut |idx| {
@@ -301,19 +460,21 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                         }
                         output(Value::Null);
                     }
-                    (Value::Null, _) => output(Value::Null),
+                    // jq: null indexes to null by string, number or slice only
+                    (
+                        Value::Null,
+                        Value::String(_) | Value::Int(_) | Value::Double(..) | Value::Object(_),
+                    ) => output(Value::Null),
                     _ => {
-                        LAST_ERROR.with(|e| {
-                            let idx_desc = match &idx {
-                                Value::String(s) => format!("string \"{}\"", s),
-                                _ => idx.type_name().to_string(),
-                            };
-                            *e.borrow_mut() = Some(Value::String(format!(
-                                "Cannot index {} with {}",
-                                input.type_name(),
-                                idx_desc
-                            )));
-                        });
+                        let idx_desc = match &idx {
+                            Value::String(s) => format!("string \"{}\"", s),
+                            _ => idx.type_name().to_string(),
+                        };
+                        env.set_error(Value::String(format!(
+                            "Cannot index {} with {}",
+                            input.type_name(),
+                            idx_desc
+                        )));
                     }
                 }
             });
@@ -322,7 +483,7 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
         Filter::Pipe(left, right) => {
             eval(left, input, env, &mut |intermediate| {
                 // Stop if an error was raised (e.g., by `error` builtin)
-                if LAST_ERROR.with(|e| e.borrow().is_some()) {
+                if env.has_error() {
                     return;
                 }
                 eval(right, &intermediate, env, output);
@@ -340,21 +501,7 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                     output(v.clone());
                 }
             }
-            Value::Null => {
-                LAST_ERROR.with(|e| {
-                    *e.borrow_mut() =
-                        Some(Value::String("null is not iterable (null)".to_string()));
-                });
-            }
-            _ => {
-                LAST_ERROR.with(|e| {
-                    *e.borrow_mut() = Some(Value::String(format!(
-                        "Cannot iterate over {} ({})",
-                        input.type_name(),
-                        input.short_desc()
-                    )));
-                });
-            }
+            _ => env.set_error(Value::String(iterate_error(input))),
         },
 
         Filter::Select(cond) => {
@@ -387,21 +534,36 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                     ObjKey::Name(s) => {
                         let key_str = s.clone();
                         eval(val_filter, input, env, &mut |v| {
-                            current.push((key_str.clone(), v));
-                            build_object(pairs, idx + 1, current, input, env, output);
-                            current.pop();
+                            if env.has_error() {
+                                return;
+                            }
+                            with_object_entry(current, &key_str, v, |current| {
+                                build_object(pairs, idx + 1, current, input, env, output)
+                            });
                         });
                     }
                     ObjKey::Expr(expr) => {
+                        // Keys are the outer loop and values the inner one, as
+                        // in jq; an error (such as a non-string key) ends the
+                        // construction rather than skipping to the next key.
                         eval(expr, input, env, &mut |kv| {
+                            if env.has_error() {
+                                return;
+                            }
                             let key_str = match kv {
                                 Value::String(s) => s,
-                                _ => return,
+                                _ => {
+                                    set_object_key_error(env, &kv);
+                                    return;
+                                }
                             };
                             eval(val_filter, input, env, &mut |v| {
-                                current.push((key_str.clone(), v));
-                                build_object(pairs, idx + 1, current, input, env, output);
-                                current.pop();
+                                if env.has_error() {
+                                    return;
+                                }
+                                with_object_entry(current, &key_str, v, |current| {
+                                    build_object(pairs, idx + 1, current, input, env, output)
+                                });
                             });
                         });
                     }
@@ -418,7 +580,7 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
             });
             // If an error occurred during collection, don't produce the array
             // (let the error propagate to try/catch)
-            let has_error = LAST_ERROR.with(|e| e.borrow().is_some());
+            let has_error = env.has_error();
             if !has_error {
                 output(Value::Array(Arc::new(arr)));
             }
@@ -446,9 +608,7 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                     &mut |lval| match arith_values(&lval, op, &rval) {
                         Ok(result) => output(result),
                         Err(msg) => {
-                            LAST_ERROR.with(|e| {
-                                *e.borrow_mut() = Some(Value::String(msg));
-                            });
+                            env.set_error(Value::String(msg));
                         }
                     },
                 );
@@ -520,12 +680,19 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
         Filter::Alternative(left, right) => {
             // Collect all outputs from left, filter to truthy (not null/false).
             // If any truthy values exist, output them all; otherwise eval right.
+            // Errors on the left are suppressed, as in jq: `error("x") // 1`
+            // is 1.
+            let pending = env.take_error();
             let mut truthy_vals = Vec::new();
             eval(left, input, env, &mut |v| {
                 if v != Value::Null && v != Value::Bool(false) {
                     truthy_vals.push(v);
                 }
             });
+            env.take_error();
+            if let Some(err) = pending {
+                env.set_error(err);
+            }
             if !truthy_vals.is_empty() {
                 for v in truthy_vals {
                     output(v);
@@ -539,101 +706,75 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
             // Try: suppress body errors but preserve downstream errors.
             // Downstream errors occur inside the output callback (after body
             // successfully produces a value), so we capture them separately.
-            LAST_ERROR.with(|e| e.borrow_mut().take());
+            env.take_error();
             let mut downstream_error: Option<Value> = None;
             eval(inner, input, env, &mut |v| {
                 // Body produced a value — clear any partial body error
-                LAST_ERROR.with(|e| e.borrow_mut().take());
+                env.take_error();
                 output(v);
                 // Capture error set by downstream processing
-                if let Some(err) = LAST_ERROR.with(|e| e.borrow_mut().take()) {
+                if let Some(err) = env.take_error() {
                     downstream_error = Some(err);
                 }
             });
             // Suppress remaining body error
-            LAST_ERROR.with(|e| e.borrow_mut().take());
+            env.take_error();
             // Restore downstream error
             if let Some(err) = downstream_error {
-                LAST_ERROR.with(|e| *e.borrow_mut() = Some(err));
+                env.set_error(err);
             }
         }
 
         Filter::TryCatch(body, handler) => {
-            LAST_ERROR.with(|e| e.borrow_mut().take());
+            env.take_error();
             let mut downstream_error: Option<Value> = None;
             eval(body, input, env, &mut |v| {
-                LAST_ERROR.with(|e| e.borrow_mut().take());
+                env.take_error();
                 output(v);
-                if let Some(err) = LAST_ERROR.with(|e| e.borrow_mut().take()) {
+                if let Some(err) = env.take_error() {
                     downstream_error = Some(err);
                 }
             });
-            if let Some(err_val) = LAST_ERROR.with(|e| e.borrow_mut().take()) {
+            if let Some(err_val) = env.take_error() {
                 eval(handler, &err_val, env, output);
             }
             // Restore downstream error
             if let Some(err) = downstream_error {
-                LAST_ERROR.with(|e| *e.borrow_mut() = Some(err));
+                env.set_error(err);
             }
         }
 
         Filter::StringInterp(parts) => {
-            let mut result = String::new();
-            for part in parts {
-                match part {
-                    crate::filter::StringPart::Lit(s) => result.push_str(s),
-                    crate::filter::StringPart::Expr(f) => {
-                        eval(f, input, env, &mut |v| match v {
-                            Value::String(s) => result.push_str(&s),
-                            Value::Int(n) => result.push_str(itoa::Buffer::new().format(n)),
-                            Value::Double(f, _) => result.push_str(ryu::Buffer::new().format(f)),
-                            Value::Bool(b) => result.push_str(if b { "true" } else { "false" }),
-                            Value::Null => result.push_str("null"),
-                            Value::Array(_) | Value::Object(_) => {
-                                let mut buf = Vec::new();
-                                crate::output::write_compact(&mut buf, &v, false).unwrap();
-                                result.push_str(&String::from_utf8(buf).unwrap_or_default());
-                            }
-                        });
+            // Each generator part multiplies the outputs. Like jq, the last
+            // part is the outermost loop, so build the string right to left.
+            fn build_interp(
+                parts: &[crate::filter::StringPart],
+                suffix: &str,
+                input: &Value,
+                env: &Env,
+                output: &mut dyn FnMut(Value),
+            ) {
+                let Some((last, rest)) = parts.split_last() else {
+                    output(Value::String(suffix.to_string()));
+                    return;
+                };
+                match last {
+                    crate::filter::StringPart::Lit(s) => {
+                        build_interp(rest, &format!("{s}{suffix}"), input, env, output)
                     }
+                    crate::filter::StringPart::Expr(f) => eval(f, input, env, &mut |v| {
+                        let text = super::builtins::value_to_string(&v);
+                        build_interp(rest, &format!("{text}{suffix}"), input, env, output);
+                    }),
                 }
             }
-            output(Value::String(result));
+            build_interp(parts, "", input, env, output);
         }
 
         Filter::Neg(inner) => {
-            eval(inner, input, env, &mut |v| match v {
-                Value::Int(n) => output(
-                    n.checked_neg()
-                        .map_or_else(|| Value::Double(-(n as f64), None), Value::Int),
-                ),
-                Value::Double(f, raw) => {
-                    // In compat mode (have_decnum=true), preserve raw text through
-                    // negation to maintain precision. Otherwise, only preserve for
-                    // extreme exponents (inf/0) where f64 loses the representation.
-                    let preserve = crate::value::jq_compat() || f.is_infinite() || f == 0.0;
-                    let neg_raw = if preserve {
-                        raw.as_ref().map(|s| {
-                            if let Some(rest) = s.strip_prefix('-') {
-                                rest.into()
-                            } else {
-                                format!("-{s}").into_boxed_str()
-                            }
-                        })
-                    } else {
-                        None
-                    };
-                    output(Value::Double(-f, neg_raw));
-                }
-                _ => {
-                    LAST_ERROR.with(|e| {
-                        *e.borrow_mut() = Some(Value::String(format!(
-                            "{} ({}) cannot be negated",
-                            v.type_name(),
-                            v.short_desc()
-                        )));
-                    });
-                }
+            eval(inner, input, env, &mut |v| match negate_value(v) {
+                Ok(result) => output(result),
+                Err(msg) => env.set_error(Value::String(msg)),
             });
         }
 
@@ -686,13 +827,11 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                 }
                 Value::Null => output(Value::Null),
                 _ => {
-                    LAST_ERROR.with(|e| {
-                        *e.borrow_mut() = Some(Value::String(format!(
-                            "{} ({}) cannot be sliced",
-                            input.type_name(),
-                            input.short_desc()
-                        )));
-                    });
+                    env.set_error(Value::String(format!(
+                        "{} ({}) cannot be sliced",
+                        input.type_name(),
+                        input.short_desc()
+                    )));
                 }
             }
         }
@@ -707,9 +846,10 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                 output(loc);
             } else if let Some(val) = env.get_var(name) {
                 output(val.clone());
-            } else {
-                // Fall through to builtins for special variables like $ENV
-                super::builtins::eval_builtin(name, &[], input, env, output);
+            } else if name == "$ENV" {
+                // Bound at startup by the CLI; an environment without it
+                // (library callers, parallel workers) sees the same snapshot.
+                output(super::env_object());
             }
         }
 
@@ -725,14 +865,25 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
             let mut acc = Value::Null;
             eval(init, input, env, &mut |v| acc = v);
 
+            let mut loop_env = None;
             eval(source, input, env, &mut |val| {
-                if let Some(new_env) = match_pattern(pattern, &val, env) {
-                    let cur = acc.clone();
-                    eval(update, &cur, &new_env, &mut |v| acc = v);
+                if env.has_error() {
+                    return;
+                }
+                if let Some(new_env) = bind_loop_pattern(pattern, val, env, &mut loop_env) {
+                    acc = update_loop_state(
+                        update,
+                        std::mem::replace(&mut acc, Value::Null),
+                        new_env,
+                    );
                 }
             });
 
-            output(acc);
+            // As with array construction, an error in the loop (say, `input`
+            // running out) leaves no result for try/catch to see past.
+            if !env.has_error() {
+                output(acc);
+            }
         }
 
         Filter::Foreach(source, pattern, init, update, extract) => {
@@ -744,18 +895,26 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
             }
             for init_val in init_vals {
                 let mut acc = init_val;
+                let mut loop_env = None;
                 eval(source, input, env, &mut |val| {
-                    if BREAK_SIGNAL.with(|b| b.borrow().is_some()) {
+                    if env.break_signal().borrow().is_some() || env.has_error() {
                         return;
                     }
-                    if let Some(new_env) = match_pattern(pattern, &val, env) {
-                        let cur = acc.clone();
-                        eval(update, &cur, &new_env, &mut |v| acc = v);
-                        if let Some(ext) = extract {
-                            eval(ext, &acc, &new_env, output);
-                        } else {
-                            output(acc.clone());
-                        }
+                    if let Some(new_env) = bind_loop_pattern(pattern, val, env, &mut loop_env) {
+                        // Every output of the update is extracted in turn and
+                        // the last becomes the state; with none it is null.
+                        let state = std::mem::replace(&mut acc, Value::Null);
+                        eval(update, &state, new_env, &mut |next| {
+                            if env.has_error() {
+                                return;
+                            }
+                            if let Some(ext) = extract {
+                                eval(ext, &next, new_env, output);
+                            } else {
+                                output(next.clone());
+                            }
+                            acc = next;
+                        });
                     }
                 });
             }
@@ -797,18 +956,24 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                 for var in &all_vars {
                     base_env = base_env.bind_var(var.clone(), Value::Null);
                 }
-                for pat in patterns {
-                    if let Some(new_env) = try_match_pattern(pat, &val, &base_env) {
-                        eval(body, input, &new_env, output);
-                        return;
+                for (i, pat) in patterns.iter().enumerate() {
+                    let Some(new_env) = try_match_pattern(pat, &val, &base_env) else {
+                        continue;
+                    };
+                    let had_error = env.has_error();
+                    eval(body, input, &new_env, output);
+                    // An error in the body retries with the next pattern; only
+                    // the last pattern's errors escape. Outputs already produced stay.
+                    if i + 1 < patterns.len() && !had_error && env.has_error() {
+                        env.take_error();
+                        continue;
                     }
+                    return;
                 }
                 // No pattern matched — error
-                LAST_ERROR.with(|e| {
-                    *e.borrow_mut() = Some(Value::String(
-                        "No pattern matched in ?// expression".to_string(),
-                    ));
-                });
+                env.set_error(Value::String(
+                    "No pattern matched in ?// expression".to_string(),
+                ));
             });
         }
 
@@ -846,19 +1011,20 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                             }
                             output(Value::Null);
                         }
-                        (Value::Null, _) => output(Value::Null),
+                        (
+                            Value::Null,
+                            Value::String(_) | Value::Int(_) | Value::Double(..) | Value::Object(_),
+                        ) => output(Value::Null),
                         _ => {
-                            LAST_ERROR.with(|e| {
-                                let idx_desc = match &idx {
-                                    Value::String(s) => format!("string \"{}\"", s),
-                                    _ => idx.type_name().to_string(),
-                                };
-                                *e.borrow_mut() = Some(Value::String(format!(
-                                    "Cannot index {} with {}",
-                                    base_val.type_name(),
-                                    idx_desc
-                                )));
-                            });
+                            let idx_desc = match &idx {
+                                Value::String(s) => format!("string \"{}\"", s),
+                                _ => idx.type_name().to_string(),
+                            };
+                            env.set_error(Value::String(format!(
+                                "Cannot index {} with {}",
+                                base_val.type_name(),
+                                idx_desc
+                            )));
                         }
                     }
                 });
@@ -915,42 +1081,47 @@ pub fn eval(filter: &Filter, input: &Value, env: &Env, output: &mut dyn FnMut(Va
                     }
                     Value::Null => output(Value::Null),
                     _ => {
-                        LAST_ERROR.with(|e| {
-                            *e.borrow_mut() = Some(Value::String(format!(
-                                "{} ({}) cannot be sliced",
-                                base_val.type_name(),
-                                base_val.short_desc()
-                            )));
-                        });
+                        env.set_error(Value::String(format!(
+                            "{} ({}) cannot be sliced",
+                            base_val.type_name(),
+                            base_val.short_desc()
+                        )));
                     }
                 }
             });
         }
 
         Filter::Label(name, body) => {
-            eval(body, input, env, &mut |v| {
-                if BREAK_SIGNAL.with(|b| b.borrow().is_none()) {
+            // The body sees this run's label under `*label-$name`; closures
+            // keep the env they were defined in, so breaks resolve lexically.
+            let id = env.next_label(name);
+            let body_env = env.bind_var(label_var(name), Value::String(id.clone()));
+            eval(body, input, &body_env, &mut |v| {
+                if env.break_signal().borrow().is_none() {
                     output(v);
                 }
             });
             // Clear break signal if it matches our label
-            BREAK_SIGNAL.with(|b| {
-                if b.borrow().as_deref() == Some(name.as_str()) {
-                    *b.borrow_mut() = None;
-                }
-            });
+            let signal = env.break_signal();
+            if signal.borrow().as_deref() == Some(id.as_str()) {
+                *signal.borrow_mut() = None;
+            }
         }
 
-        Filter::Break(name) => {
-            BREAK_SIGNAL.with(|b| {
-                *b.borrow_mut() = Some(name.clone());
-            });
-        }
+        // A break isn't an error: it sets the signal rather than the error
+        // slot, so `try` lets it through to its label.
+        Filter::Break(name) => match env.get_var(&label_var(name)) {
+            Some(Value::String(id)) => *env.break_signal().borrow_mut() = Some(id.clone()),
+            _ => {
+                let label = name.trim_start_matches('$');
+                env.set_error(Value::String(format!("$*label-{label} is not defined")));
+            }
+        },
 
         // Module system nodes should be resolved before evaluation.
         // If we reach here, the module loader was not invoked.
         Filter::Import { .. } | Filter::Include { .. } | Filter::ModuleDecl { .. } => {
-            set_last_error(Value::String(
+            env.set_error(Value::String(
                 "module system (import/include) requires -L library path".into(),
             ));
         }
@@ -1082,9 +1253,14 @@ fn eval_assign(
         eval(rhs, input, env, &mut |v| rhs_vals.push(v));
 
         for rhs_val in rhs_vals {
+            if env.has_error() {
+                return;
+            }
             let set_updater = |_current: &Value| -> Option<Value> { Some(rhs_val.clone()) };
             if is_update_path_supported(path_filter) {
-                if let Some(result) = update_recursive(path_filter, input, env, &set_updater) {
+                if let Some(result) = update_recursive(path_filter, input, env, &set_updater)
+                    && !env.has_error()
+                {
                     output(result);
                 }
             } else {
@@ -1139,9 +1315,7 @@ fn eval_assign(
                         match arith_values(current, &arith_op, &rhs_val) {
                             Ok(v) => result = Some(v),
                             Err(msg) => {
-                                LAST_ERROR.with(|e| {
-                                    *e.borrow_mut() = Some(Value::String(msg));
-                                });
+                                env.set_error(Value::String(msg));
                             }
                         }
                     }
@@ -1153,7 +1327,9 @@ fn eval_assign(
 
     // Fast path: recursive single-pass update (O(N) for iterators).
     if is_update_path_supported(path_filter) {
-        if let Some(result) = update_recursive(path_filter, input, env, &*updater) {
+        if let Some(result) = update_recursive(path_filter, input, env, &*updater)
+            && !env.has_error()
+        {
             output(result);
         }
         return;
@@ -1167,6 +1343,7 @@ fn eval_assign(
 fn is_update_path_supported(f: &Filter) -> bool {
     match f {
         Filter::Identity | Filter::Field(_) | Filter::Iterate | Filter::Select(_) => true,
+        Filter::FieldPath(_) => true,
         Filter::Index(_) | Filter::Slice(_, _) => true,
         Filter::PostfixIndex(a, _) | Filter::PostfixSlice(a, _, _) => is_update_path_supported(a),
         Filter::Pipe(a, b) => is_update_path_supported(a) && is_update_path_supported(b),
@@ -1182,7 +1359,9 @@ fn is_update_path_supported(f: &Filter) -> bool {
 /// cloning the entire root — O(N²)), this navigates the path structure and
 /// updates each container in a single pass.
 ///
-/// Returns `Some(updated_value)` or `None` (deletion at this level).
+/// Returns `Some(updated_value)` or `None` (deletion at this level). A key
+/// of the wrong type for its container sets jq's indexing error; the caller
+/// then discards the result rather than output a partial update.
 fn update_recursive(
     path_filter: &Filter,
     input: &Value,
@@ -1210,7 +1389,7 @@ fn update_recursive(
                 if !found {
                     if let Some(new_v) = updater(&Value::Null) {
                         result.push((name.clone(), new_v));
-                    } else if LAST_ERROR.with(|e| e.borrow().is_some()) {
+                    } else if env.has_error() {
                         return None; // propagate error
                     }
                 }
@@ -1219,15 +1398,25 @@ fn update_recursive(
             Value::Null => {
                 if let Some(new_v) = updater(&Value::Null) {
                     Some(Value::Object(Arc::new(vec![(name.clone(), new_v)])))
-                } else if LAST_ERROR.with(|e| e.borrow().is_some()) {
+                } else if env.has_error() {
                     None // propagate error
                 } else {
                     Some(Value::Null)
                 }
             }
-            _ => Some(input.clone()),
+            _ => {
+                env.set_error(Value::String(index_error(
+                    input,
+                    &Value::String(name.clone()),
+                )));
+                None
+            }
         },
 
+        Filter::FieldPath(names) => {
+            update_recursive(&super::field_path_pipe(names), input, env, updater)
+        }
+
         Filter::Iterate => match input {
             Value::Array(arr) => {
                 let mut result = Vec::with_capacity(arr.len());
@@ -1248,7 +1437,10 @@ fn update_recursive(
                 }
                 Some(Value::Object(Arc::new(result)))
             }
-            _ => Some(input.clone()),
+            _ => {
+                env.set_error(Value::String(iterate_error(input)));
+                None
+            }
         },
 
         Filter::Index(idx_f) => {
@@ -1260,11 +1452,9 @@ fn update_recursive(
             for v in raw_indices {
                 match &v {
                     Value::Double(f, _) if f.is_nan() => {
-                        LAST_ERROR.with(|e| {
-                            *e.borrow_mut() = Some(Value::String(
-                                "Cannot set array element at NaN index".into(),
-                            ));
-                        });
+                        env.set_error(Value::String(
+                            "Cannot set array element at NaN index".into(),
+                        ));
                         return None;
                     }
                     Value::Double(f, _) if f.is_finite() => {
@@ -1280,19 +1470,28 @@ fn update_recursive(
                     let mut to_delete = Vec::new();
 
                     for idx_val in &indices {
-                        if let Value::Int(i) = idx_val {
-                            let idx = if *i < 0 {
-                                (result.len() as i64 + i).max(0) as usize
-                            } else {
-                                *i as usize
-                            };
-                            while result.len() <= idx {
-                                result.push(Value::Null);
-                            }
-                            match updater(&result[idx]) {
-                                Some(new_v) => result[idx] = new_v,
-                                None => to_delete.push(idx),
+                        let Value::Int(i) = idx_val else {
+                            env.set_error(Value::String(index_error(input, idx_val)));
+                            return None;
+                        };
+                        let idx = if *i < 0 {
+                            let resolved = result.len() as i64 + i;
+                            if resolved < 0 {
+                                env.set_error(Value::String(
+                                    "Out of bounds negative array index".into(),
+                                ));
+                                return None;
                             }
+                            resolved as usize
+                        } else {
+                            *i as usize
+                        };
+                        while result.len() <= idx {
+                            result.push(Value::Null);
+                        }
+                        match updater(&result[idx]) {
+                            Some(new_v) => result[idx] = new_v,
+                            None => to_delete.push(idx),
                         }
                     }
 
@@ -1311,15 +1510,17 @@ fn update_recursive(
                     let mut keys_to_delete = Vec::new();
 
                     for idx_val in &indices {
-                        if let Value::String(k) = idx_val {
-                            if let Some(entry) = result.iter_mut().find(|(ek, _)| ek == k) {
-                                match updater(&entry.1) {
-                                    Some(new_v) => entry.1 = new_v,
-                                    None => keys_to_delete.push(k.clone()),
-                                }
-                            } else if let Some(new_v) = updater(&Value::Null) {
-                                result.push((k.clone(), new_v));
+                        let Value::String(k) = idx_val else {
+                            env.set_error(Value::String(index_error(input, idx_val)));
+                            return None;
+                        };
+                        if let Some(entry) = result.iter_mut().find(|(ek, _)| ek == k) {
+                            match updater(&entry.1) {
+                                Some(new_v) => entry.1 = new_v,
+                                None => keys_to_delete.push(k.clone()),
                             }
+                        } else if let Some(new_v) = updater(&Value::Null) {
+                            result.push((k.clone(), new_v));
                         }
                     }
 
@@ -1332,57 +1533,68 @@ fn update_recursive(
                         match idx_val {
                             Value::Int(i) => {
                                 if *i < 0 {
-                                    LAST_ERROR.with(|e| {
-                                        *e.borrow_mut() = Some(Value::String(
-                                            "Out of bounds negative array index".into(),
-                                        ));
-                                    });
+                                    env.set_error(Value::String(
+                                        "Out of bounds negative array index".into(),
+                                    ));
                                     return None;
                                 }
                                 if *i > 1_000_000 {
-                                    LAST_ERROR.with(|e| {
-                                        *e.borrow_mut() =
-                                            Some(Value::String("Array index too large".into()));
-                                    });
+                                    env.set_error(Value::String("Array index too large".into()));
                                     return None;
                                 }
                                 let idx = *i as usize;
                                 let mut arr = vec![Value::Null; idx + 1];
                                 if let Some(new_v) = updater(&Value::Null) {
                                     arr[idx] = new_v;
-                                } else if LAST_ERROR.with(|e| e.borrow().is_some()) {
+                                } else if env.has_error() {
                                     return None;
+                                } else {
+                                    return Some(Value::Null);
                                 }
                                 Some(Value::Array(Arc::new(arr)))
                             }
                             Value::String(k) => {
                                 if let Some(new_v) = updater(&Value::Null) {
                                     Some(Value::Object(Arc::new(vec![(k.clone(), new_v)])))
-                                } else if LAST_ERROR.with(|e| e.borrow().is_some()) {
+                                } else if env.has_error() {
                                     None
                                 } else {
                                     Some(Value::Null)
                                 }
                             }
-                            _ => Some(Value::Null),
+                            other => {
+                                env.set_error(Value::String(index_error(input, other)));
+                                None
+                            }
                         }
                     } else {
                         Some(Value::Null)
                     }
                 }
-                _ => Some(input.clone()),
+                _ => match indices.first() {
+                    Some(idx_val) => {
+                        env.set_error(Value::String(index_error(input, idx_val)));
+                        None
+                    }
+                    None => Some(input.clone()),
+                },
             }
         }
 
         Filter::Slice(start_f, end_f) => {
             match input {
                 Value::String(_) => {
-                    LAST_ERROR.with(|e| {
-                        *e.borrow_mut() = Some(Value::String("Cannot update string slices".into()));
-                    });
+                    env.set_error(Value::String(
+                        "Cannot update field at object index of string".into(),
+                    ));
                     None
                 }
-                Value::Array(arr) => {
+                // A slice of null is an empty array, as in jq.
+                Value::Array(_) | Value::Null => {
+                    let arr: &[Value] = match input {
+                        Value::Array(arr) => arr,
+                        _ => &[],
+                    };
                     let len = arr.len() as i64;
                     let s = resolve_slice_index(
                         start_f
@@ -1427,15 +1639,19 @@ fn update_recursive(
                     {
                         let mut result = Vec::new();
                         result.extend_from_slice(&arr[..s]);
-                        if let Value::Array(new_arr) = &new_val {
-                            result.extend_from_slice(new_arr);
-                        } else {
-                            result.push(new_val);
-                        }
+                        let Value::Array(new_arr) = &new_val else {
+                            env.set_error(Value::String(
+                                "A slice of an array can only be assigned another array".into(),
+                            ));
+                            return None;
+                        };
+                        result.extend_from_slice(new_arr);
                         if e < arr.len() {
                             result.extend_from_slice(&arr[e..]);
                         }
                         Some(Value::Array(Arc::new(result)))
+                    } else if matches!(input, Value::Null) {
+                        Some(Value::Null)
                     } else {
                         // Deletion: remove the slice
                         let mut result = Vec::new();
@@ -1446,13 +1662,19 @@ fn update_recursive(
                         Some(Value::Array(Arc::new(result)))
                     }
                 }
-                _ => Some(input.clone()),
+                _ => {
+                    env.set_error(Value::String(format!(
+                        "Cannot index {} with object",
+                        input.type_name()
+                    )));
+                    None
+                }
             }
         }
 
         Filter::PostfixIndex(base, idx) => {
             // PostfixIndex(base, idx) — navigate base, then update with Index
-            let idx_filter = Filter::Index(idx.clone());
+            let idx_filter = postfix_index_step(idx, input, env);
             update_recursive(base, input, env, &|val: &Value| -> Option<Value> {
                 update_recursive(&idx_filter, val, env, updater)
             })
@@ -1460,7 +1682,7 @@ fn update_recursive(
 
         Filter::PostfixSlice(base, s, e) => {
             // PostfixSlice(base, s, e) — navigate base, then update with Slice
-            let slice_filter = Filter::Slice(s.clone(), e.clone());
+            let slice_filter = postfix_slice_step(s, e, input, env);
             update_recursive(base, input, env, &|val: &Value| -> Option<Value> {
                 update_recursive(&slice_filter, val, env, updater)
             })
@@ -1487,15 +1709,9 @@ fn update_recursive(
             }
         }
 
-        Filter::Comma(items) => {
-            let mut result = input.clone();
-            for item in items {
-                if let Some(updated) = update_recursive(item, &result, env, updater) {
-                    result = updated;
-                }
-            }
-            Some(result)
-        }
+        // One item's deletions mustn't shift the indices the next one
+        // updates, e.g. `(.[0], .[1]) |= empty`.
+        Filter::Comma(_) => update_via_paths(path_filter, input, env, updater),
 
         Filter::Bind(source, pattern, body) => {
             // Evaluate source, bind to pattern, then update through body
@@ -1522,6 +1738,23 @@ fn eval_assign_via_paths(
     updater: &dyn Fn(&Value) -> Option<Value>,
     output: &mut dyn FnMut(Value),
 ) {
+    if let Some(result) = update_via_paths(path_filter, input, env, updater)
+        && !env.has_error()
+    {
+        output(result);
+    }
+}
+
+/// Update each path of `path_filter` in turn, as jq's `_modify` does: the
+/// value at a path is read from the result so far, and the paths whose
+/// update produced nothing are deleted together at the end, so that no
+/// deletion shifts the array indices of the paths after it. `None` on error.
+fn update_via_paths(
+    path_filter: &Filter,
+    input: &Value,
+    env: &Env,
+    updater: &dyn Fn(&Value) -> Option<Value>,
+) -> Option<Value> {
     use super::value_ops;
 
     let mut paths: Vec<Vec<Value>> = Vec::new();
@@ -1530,41 +1763,47 @@ fn eval_assign_via_paths(
             paths.push(arr.as_ref().clone());
         }
     });
-
-    // If path_of failed with an error (invalid path expression), don't output
-    if paths.is_empty() && has_last_error() {
-        return;
+    // An invalid path expression updates nothing.
+    if env.has_error() {
+        return None;
     }
 
     let mut result = input.clone();
     let mut deletions: Vec<Vec<Value>> = Vec::new();
-
-    for path in &paths {
-        let current = value_ops::get_path(&result, path);
-        match updater(&current) {
-            Some(new_val) => match value_ops::set_path(&result, path, &new_val) {
-                Ok(v) => result = v,
-                Err(msg) => {
-                    LAST_ERROR.with(|e| *e.borrow_mut() = Some(Value::String(msg)));
-                    return;
-                }
-            },
-            None => {
-                deletions.push(path.clone());
+    for path in paths {
+        let updated =
+            value_ops::get_path(&result, &path).and_then(|current| match updater(&current) {
+                Some(new_val) => value_ops::set_path(&result, &path, &new_val).map(Some),
+                None => Ok(None),
+            });
+        match updated {
+            Ok(Some(v)) => result = v,
+            Ok(None) => deletions.push(path),
+            Err(msg) => {
+                env.set_error(Value::String(msg));
+                return None;
             }
         }
     }
-
-    // Process deletions in reverse index order to avoid shifting
-    deletions.sort_by(|a, b| match (a.last(), b.last()) {
-        (Some(Value::Int(ai)), Some(Value::Int(bi))) => bi.cmp(ai),
-        _ => std::cmp::Ordering::Equal,
-    });
-    for path in &deletions {
-        result = value_ops::del_path(&result, path);
+    match value_ops::delete_paths(&result, deletions) {
+        Ok(v) => Some(v),
+        Err(msg) => {
+            env.set_error(Value::String(msg));
+            None
+        }
     }
+}
 
-    output(result);
+/// The error for `.[]` on a value that is neither an array nor an object.
+fn iterate_error(input: &Value) -> String {
+    match input {
+        Value::Null => "null is not iterable (null)".to_string(),
+        _ => format!(
+            "Cannot iterate over {} ({})",
+            input.type_name(),
+            input.short_desc()
+        ),
+    }
 }
 
 /// Resolve a slice index: handle negatives (wrap with len), clamp to [0, len].
@@ -1580,6 +1819,7 @@ pub(crate) fn resolve_slice_index(val: Option<&Value>, default: i64, len: i64) -
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::*;
     use crate::filter::value_ops::values_order;
@@ -1906,6 +2146,47 @@ mod tests {
         assert_eq!(values_order(&c, &a), Some(std::cmp::Ordering::Less));
     }
 
+    #[test]
+    fn eval_values_order_objects() {
+        let obj = |pairs: &[(&str, i64)]| {
+            Value::Object(Arc::new(
+                pairs
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), Value::Int(*v)))
+                    .collect(),
+            ))
+        };
+        // ["a","b"] < ["b"] even though it's longer.
+        assert_eq!(
+            values_order(&obj(&[("b", 1), ("a", 0)]), &obj(&[("b", 0)])),
+            Some(std::cmp::Ordering::Less)
+        );
+        assert_eq!(
+            values_order(&obj(&[("a", 1), ("b", 2)]), &obj(&[("b", 2), ("a", 1)])),
+            Some(std::cmp::Ordering::Equal)
+        );
+        assert_eq!(
+            values_order(&obj(&[("b", 1), ("a", 2)]), &obj(&[("a", 2), ("b", 0)])),
+            Some(std::cmp::Ordering::Greater)
+        );
+    }
+
+    #[test]
+    fn eval_values_order_nan_is_smallest_number() {
+        let nan = Value::Double(f64::NAN, None);
+        let neg_inf = Value::Double(f64::NEG_INFINITY, None);
+        assert_eq!(values_order(&nan, &neg_inf), Some(std::cmp::Ordering::Less));
+        assert_eq!(
+            values_order(&Value::Int(0), &nan),
+            Some(std::cmp::Ordering::Greater)
+        );
+        assert_eq!(values_order(&nan, &nan), Some(std::cmp::Ordering::Equal));
+        assert_eq!(
+            values_order(&Value::Bool(true), &nan),
+            Some(std::cmp::Ordering::Less)
+        );
+    }
+
     #[test]
     fn eval_unique_sorts() {
         let input = Value::Array(Arc::new(vec![
@@ -2229,6 +2510,14 @@ mod tests {
             eval_one(&parse("indices(\"o\")"), &Value::String("foobar".into())),
             Value::Array(Arc::new(vec![Value::Int(1), Value::Int(2)]))
         );
+        assert_eq!(
+            eval_one(
+                &parse("index([1,2])"),
+                &Value::Array(Arc::new(vec![Value::Int(0), Value::Int(1), Value::Int(2)]))
+            ),
+            Value::Int(1)
+        );
+        assert_eq!(eval_one(&parse("indices(1)"), &Value::Null), Value::Null);
     }
 
     #[test]
@@ -2291,6 +2580,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn eval_from_entries_later_duplicates_win() {
+        let entry = |k: &str, v: i64| {
+            Value::Object(Arc::new(vec![
+                ("key".into(), Value::String(k.into())),
+                ("value".into(), Value::Int(v)),
+            ]))
+        };
+        let input = Value::Array(Arc::new(vec![entry("a", 1), entry("b", 2), entry("a", 3)]));
+        assert_eq!(
+            eval_one(&parse("from_entries"), &input),
+            obj(&[("a", Value::Int(3)), ("b", Value::Int(2))])
+        );
+    }
+
     #[test]
     fn eval_values_iterates() {
         // values acts as select(. != null), passing through non-null input
@@ -2473,6 +2777,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn eval_any_all_stop_early() {
+        assert_eq!(
+            eval_all(
+                &parse("any(range(1e9); . == 3), all(range(1e9); . < 3)"),
+                &Value::Null
+            ),
+            vec![Value::Bool(true), Value::Bool(false)]
+        );
+        assert_eq!(
+            eval_one(&parse("isempty(1, error(\"x\"))"), &Value::Null),
+            Value::Bool(false)
+        );
+    }
+
     #[test]
     fn eval_isempty_false() {
         assert_eq!(
@@ -2697,7 +3016,7 @@ mod tests {
 
     #[test]
     fn eval_var_undefined() {
-        // Undefined variable produces no output (falls through to builtins)
+        // Undefined variable produces no output
         let f = parse("$nonexistent");
         let results = eval_all(&f, &Value::Null);
         // $nonexistent isn't a known builtin either, so no output
@@ -2709,6 +3028,9 @@ mod tests {
         // $ENV should still work after variable support was added
         let f = parse("$ENV | type");
         assert_eq!(eval_one(&f, &Value::Null), Value::String("object".into()));
+        // Unbound, it is the same snapshot as `env`.
+        let f = parse("env == $ENV");
+        assert_eq!(eval_one(&f, &Value::Null), Value::Bool(true));
     }
 
     // --- Phase 2: slicing ---
@@ -2962,6 +3284,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn eval_walk_child_takes_first_output() {
+        let f = parse(r#"walk(if type == "number" then (. * 10, . * 100) else . end)"#);
+        let input = Value::Array(Arc::new(vec![Value::Int(1), Value::Int(2)]));
+        assert_eq!(
+            eval_one(&f, &input),
+            Value::Array(Arc::new(vec![Value::Int(10), Value::Int(20)]))
+        );
+    }
+
     // --- Integer overflow promotion tests ---
 
     #[test]
@@ -3058,6 +3390,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn abs_passes_non_negative_and_non_numbers_through() {
+        let f = parse("abs");
+        let raw = Value::Double(1.1, Some("1.10".into()));
+        assert!(matches!(eval_one(&f, &raw), Value::Double(_, Some(s)) if &*s == "1.10"));
+        assert_eq!(eval_one(&f, &Value::Int(-3)), Value::Int(3));
+        assert_eq!(eval_one(&f, &Value::Null), Value::Null);
+    }
+
     #[test]
     fn length_i64_min_promotes_to_double() {
         // length on negative int = abs, same overflow
@@ -3094,7 +3435,7 @@ mod tests {
 
     #[test]
     fn int_div_by_zero_error() {
-        // Division by zero produces an error (no output, sets LAST_ERROR)
+        // Division by zero produces an error (no output, sets the env error)
         assert!(eval_all(&parse("1 / 0"), &Value::Null).is_empty());
     }
 
@@ -3259,6 +3600,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn split_empty_input() {
+        // jq gives no parts for "", even where the separator never matches.
+        let input = Value::String("".into());
+        let empty = Value::Array(Arc::new(vec![]));
+        assert_eq!(eval_one(&parse(r#"split(",")"#), &input), empty);
+        assert_eq!(eval_one(&parse(r#"split("")"#), &input), empty);
+    }
+
     #[test]
     fn join_empty_array() {
         let input = Value::Array(Arc::new(vec![]));
@@ -3420,6 +3770,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sort_by_runs_with_an_error_already_pending() {
+        // `1, error("x"), 2` keeps running its siblings with the error set;
+        // sort_by mustn't take that error for one of its own.
+        let env = Env::empty();
+        env.set_error(Value::String("earlier".into()));
+        let input = Value::Array(Arc::new(vec![Value::Int(2), Value::Int(1)]));
+        let mut results = Vec::new();
+        eval(&parse("sort_by(.)"), &input, &env, &mut |v| results.push(v));
+        assert_eq!(
+            results,
+            vec![Value::Array(Arc::new(vec![Value::Int(1), Value::Int(2)]))]
+        );
+        assert_eq!(env.take_error(), Some(Value::String("earlier".into())));
+        // An error from the key filter is still reported.
+        let mut results = Vec::new();
+        eval(&parse("sort_by(error)"), &input, &env, &mut |v| {
+            results.push(v)
+        });
+        assert!(results.is_empty());
+        assert_eq!(env.take_error(), Some(Value::Int(2)));
+    }
+
     #[test]
     fn min_by_empty() {
         let input = Value::Array(Arc::new(vec![]));
@@ -3484,14 +3857,14 @@ mod tests {
     fn setpath_creates_nested() {
         let result = eval_one(&parse(r#"setpath(["a","b"]; 1)"#), &Value::Null);
         // Should create {"a":{"b":1}}
-        if let Value::Object(obj) = &result {
-            if let Some((_, Value::Object(inner))) = obj.iter().find(|(k, _)| k == "a") {
-                assert_eq!(
-                    inner.iter().find(|(k, _)| k == "b").map(|(_, v)| v),
-                    Some(&Value::Int(1))
-                );
-                return;
-            }
+        if let Value::Object(obj) = &result
+            && let Some((_, Value::Object(inner))) = obj.iter().find(|(k, _)| k == "a")
+        {
+            assert_eq!(
+                inner.iter().find(|(k, _)| k == "b").map(|(_, v)| v),
+                Some(&Value::Int(1))
+            );
+            return;
         }
         panic!("expected {{\"a\":{{\"b\":1}}}}, got {result:?}");
     }
@@ -3722,23 +4095,53 @@ mod tests {
     }
 
     #[test]
-    fn eval_filter_clears_stale_last_error() {
-        // Deliberately set a stale error
-        LAST_ERROR.with(|e| *e.borrow_mut() = Some(Value::String("stale".into())));
-        // eval_filter should clear it before evaluating
-        eval_filter(&parse("."), &Value::Int(1), &mut |_| {});
-        let has_error = LAST_ERROR.with(|e| e.borrow().is_some());
-        assert!(!has_error, "LAST_ERROR should be cleared after eval_filter");
+    fn alternative_suppresses_left_errors() {
+        let env = Env::empty();
+        let mut out = Vec::new();
+        let filter = parse(r#"[(1, error("x"), null) // 2, (.a // 3)]"#);
+        eval(&filter, &Value::Int(0), &env, &mut |v| out.push(v));
+        assert_eq!(
+            out,
+            [Value::Array(Arc::new(vec![Value::Int(1), Value::Int(3)]))]
+        );
+        assert!(env.take_error().is_none());
+    }
+
+    #[test]
+    fn eval_filter_returns_uncaught_error() {
+        assert!(eval_filter(&parse("."), &Value::Int(1), &mut |_| {}).is_none());
+        let err = eval_filter(&parse(".a"), &Value::Int(1), &mut |_| {});
+        assert_eq!(
+            err,
+            Some(Value::String(
+                "Cannot index number with string \"a\"".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn separate_envs_have_separate_error_slots() {
+        let a = Env::empty();
+        let b = Env::empty();
+        eval_filter_with_env(&parse(".x"), &Value::Int(1), &a, &mut |_| {});
+        eval_filter_with_env(&parse(".x"), &Value::Null, &b, &mut |_| {});
+        assert!(a.has_error());
+        assert!(!b.has_error());
+        // Bindings derived from an env share its context.
+        let child = b.bind_var("$v".into(), Value::Null);
+        child.set_error(Value::Null);
+        assert!(b.has_error());
     }
 
     #[test]
     fn eval_filter_with_env_clears_stale_error() {
-        LAST_ERROR.with(|e| *e.borrow_mut() = Some(Value::String("stale".into())));
-        eval_filter_with_env(&parse("."), &Value::Int(1), &Env::empty(), &mut |_| {});
-        let has_error = LAST_ERROR.with(|e| e.borrow().is_some());
+        let env = Env::empty();
+        // Deliberately leave a stale error from an earlier evaluation
+        env.set_error(Value::String("stale".into()));
+        eval_filter_with_env(&parse("."), &Value::Int(1), &env, &mut |_| {});
         assert!(
-            !has_error,
-            "LAST_ERROR should be cleared after eval_filter_with_env"
+            !env.has_error(),
+            "error should be cleared by eval_filter_with_env"
         );
     }
 }
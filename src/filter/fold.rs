@@ -0,0 +1,346 @@
+//! Constant folding of environment lookups.
+//!
+//! `$ENV.NAME`, `env.NAME` and `$ENV["NAME"]` read a process environment
+//! that doesn't change while qj runs, so they are replaced by the value
+//! they would produce before the filter is planned. The fast paths then see
+//! a plain literal, e.g. `select(.host == env.HOSTNAME) | .msg` is the same
+//! shape as `select(.host == "web1") | .msg`. A string interpolation whose
+//! parts all become constant is folded into a single string.
+
+use super::builtins::value_to_string;
+use super::{Filter, ObjKey, Pattern, PatternKey, StringPart, env_object};
+use crate::value::Value;
+
+/// Which of `$ENV` and `env` still mean the process environment: a binding
+/// or definition of the same name shadows them.
+#[derive(Clone, Copy)]
+struct Scope {
+    env_var: bool,
+    env_fn: bool,
+}
+
+/// Replace the environment lookups in `filter` with their values.
+pub fn fold_env(filter: &Filter) -> Filter {
+    fold(
+        filter,
+        Scope {
+            env_var: true,
+            env_fn: true,
+        },
+    )
+}
+
+fn fold(filter: &Filter, scope: Scope) -> Filter {
+    let go = |f: &Filter| fold(f, scope);
+    let boxed = |f: &Filter| Box::new(fold(f, scope));
+    let opt = |f: &Option<Box<Filter>>| f.as_ref().map(|f| Box::new(fold(f, scope)));
+    match filter {
+        Filter::Pipe(a, b) => {
+            let a = go(a);
+            if let Filter::Field(name) = b.as_ref()
+                && let Some(value) = env_lookup(&a, name, scope)
+            {
+                return Filter::Literal(value);
+            }
+            Filter::Pipe(Box::new(a), boxed(b))
+        }
+        Filter::PostfixIndex(base, idx) => {
+            let base = go(base);
+            if let Filter::Literal(Value::String(name)) = idx.as_ref()
+                && let Some(value) = env_lookup(&base, name, scope)
+            {
+                return Filter::Literal(value);
+            }
+            Filter::PostfixIndex(Box::new(base), boxed(idx))
+        }
+        Filter::StringInterp(parts) => {
+            let parts: Vec<StringPart> = parts
+                .iter()
+                .map(|p| match p {
+                    StringPart::Lit(s) => StringPart::Lit(s.clone()),
+                    StringPart::Expr(f) => StringPart::Expr(go(f)),
+                })
+                .collect();
+            let mut text = String::new();
+            for part in &parts {
+                match part {
+                    StringPart::Lit(s) => text.push_str(s),
+                    StringPart::Expr(Filter::Literal(v)) => text.push_str(&value_to_string(v)),
+                    StringPart::Expr(_) => return Filter::StringInterp(parts),
+                }
+            }
+            Filter::Literal(Value::String(text))
+        }
+        Filter::Identity
+        | Filter::Field(_)
+        | Filter::FieldPath(_)
+        | Filter::Iterate
+        | Filter::Literal(_)
+        | Filter::Recurse
+        | Filter::Var(_)
+        | Filter::Break(_) => filter.clone(),
+        Filter::Index(f) => Filter::Index(boxed(f)),
+        Filter::Select(f) => Filter::Select(boxed(f)),
+        Filter::ArrayConstruct(f) => Filter::ArrayConstruct(boxed(f)),
+        Filter::Not(f) => Filter::Not(boxed(f)),
+        Filter::Try(f) => Filter::Try(boxed(f)),
+        Filter::Neg(f) => Filter::Neg(boxed(f)),
+        Filter::ObjectConstruct(pairs) => Filter::ObjectConstruct(
+            pairs
+                .iter()
+                .map(|(k, v)| {
+                    let k = match k {
+                        ObjKey::Name(n) => ObjKey::Name(n.clone()),
+                        ObjKey::Expr(f) => ObjKey::Expr(boxed(f)),
+                    };
+                    (k, boxed(v))
+                })
+                .collect(),
+        ),
+        Filter::Compare(a, op, b) => Filter::Compare(boxed(a), *op, boxed(b)),
+        Filter::Arith(a, op, b) => Filter::Arith(boxed(a), *op, boxed(b)),
+        Filter::BoolOp(a, op, b) => Filter::BoolOp(boxed(a), *op, boxed(b)),
+        Filter::Alternative(a, b) => Filter::Alternative(boxed(a), boxed(b)),
+        Filter::TryCatch(a, b) => Filter::TryCatch(boxed(a), boxed(b)),
+        Filter::Assign(a, op, b) => Filter::Assign(boxed(a), *op, boxed(b)),
+        Filter::Comma(items) => Filter::Comma(items.iter().map(go).collect()),
+        Filter::Builtin(name, args) => Filter::Builtin(name.clone(), args.iter().map(go).collect()),
+        Filter::IfThenElse(c, t, e) => Filter::IfThenElse(boxed(c), boxed(t), opt(e)),
+        Filter::Slice(s, e) => Filter::Slice(opt(s), opt(e)),
+        Filter::PostfixSlice(base, s, e) => Filter::PostfixSlice(boxed(base), opt(s), opt(e)),
+        Filter::Label(name, body) => Filter::Label(name.clone(), boxed(body)),
+        Filter::Bind(src, pat, body) => {
+            let inner = bound_by(std::slice::from_ref(pat), scope);
+            Filter::Bind(
+                boxed(src),
+                fold_pattern(pat, scope),
+                Box::new(fold(body, inner)),
+            )
+        }
+        Filter::AltBind(src, pats, body) => {
+            let inner = bound_by(pats, scope);
+            Filter::AltBind(
+                boxed(src),
+                pats.iter().map(|p| fold_pattern(p, scope)).collect(),
+                Box::new(fold(body, inner)),
+            )
+        }
+        Filter::Reduce(src, pat, init, update) => {
+            let inner = bound_by(std::slice::from_ref(pat), scope);
+            Filter::Reduce(
+                boxed(src),
+                fold_pattern(pat, scope),
+                boxed(init),
+                Box::new(fold(update, inner)),
+            )
+        }
+        Filter::Foreach(src, pat, init, update, extract) => {
+            let inner = bound_by(std::slice::from_ref(pat), scope);
+            Filter::Foreach(
+                boxed(src),
+                fold_pattern(pat, scope),
+                boxed(init),
+                Box::new(fold(update, inner)),
+                extract.as_ref().map(|f| Box::new(fold(f, inner))),
+            )
+        }
+        Filter::Def {
+            name,
+            params,
+            body,
+            rest,
+        } => {
+            let defines_env = name == "env" && params.is_empty();
+            let outer = Scope {
+                env_fn: scope.env_fn && !defines_env,
+                ..scope
+            };
+            let inner = Scope {
+                env_var: outer.env_var && !params.iter().any(|p| p == "$ENV"),
+                env_fn: outer.env_fn && !params.iter().any(|p| p == "env"),
+            };
+            Filter::Def {
+                name: name.clone(),
+                params: params.clone(),
+                body: Box::new(fold(body, inner)),
+                rest: Box::new(fold(rest, outer)),
+            }
+        }
+        Filter::Import {
+            path,
+            alias,
+            is_data,
+            metadata,
+            rest,
+        } => {
+            let inner = Scope {
+                env_var: scope.env_var && !(*is_data && alias.trim_start_matches('$') == "ENV"),
+                ..scope
+            };
+            Filter::Import {
+                path: path.clone(),
+                alias: alias.clone(),
+                is_data: *is_data,
+                metadata: metadata.clone(),
+                rest: Box::new(fold(rest, inner)),
+            }
+        }
+        Filter::Include {
+            path,
+            metadata,
+            rest,
+        } => Filter::Include {
+            path: path.clone(),
+            metadata: metadata.clone(),
+            rest: boxed(rest),
+        },
+        Filter::ModuleDecl { metadata, rest } => Filter::ModuleDecl {
+            metadata: metadata.clone(),
+            rest: boxed(rest),
+        },
+    }
+}
+
+/// The value of `root.name` when `root` is an environment lookup in scope.
+fn env_lookup(root: &Filter, name: &str, scope: Scope) -> Option<Value> {
+    let is_env = match root {
+        Filter::Var(var) => scope.env_var && var == "$ENV",
+        Filter::Builtin(f, args) => scope.env_fn && f == "env" && args.is_empty(),
+        _ => false,
+    };
+    if !is_env {
+        return None;
+    }
+    let Value::Object(vars) = env_object() else {
+        return None;
+    };
+    let value = vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+    Some(value.unwrap_or(Value::Null))
+}
+
+/// `scope` inside the body of a binding of `patterns`.
+fn bound_by(patterns: &[Pattern], scope: Scope) -> Scope {
+    let mut vars = Vec::new();
+    for pat in patterns {
+        pattern_vars(pat, &mut vars);
+    }
+    Scope {
+        env_var: scope.env_var && !vars.contains(&"$ENV"),
+        ..scope
+    }
+}
+
+fn pattern_vars<'a>(pat: &'a Pattern, vars: &mut Vec<&'a str>) {
+    match pat {
+        Pattern::Var(name) => vars.push(name),
+        Pattern::Array(pats) => pats.iter().for_each(|p| pattern_vars(p, vars)),
+        Pattern::Object(pairs) => {
+            for (key, p) in pairs {
+                if let PatternKey::Var(name) = key {
+                    vars.push(name);
+                }
+                pattern_vars(p, vars);
+            }
+        }
+    }
+}
+
+/// Computed keys in object patterns are expressions too.
+fn fold_pattern(pat: &Pattern, scope: Scope) -> Pattern {
+    match pat {
+        Pattern::Var(_) => pat.clone(),
+        Pattern::Array(pats) => {
+            Pattern::Array(pats.iter().map(|p| fold_pattern(p, scope)).collect())
+        }
+        Pattern::Object(pairs) => Pattern::Object(
+            pairs
+                .iter()
+                .map(|(key, p)| {
+                    let key = match key {
+                        PatternKey::Expr(f) => PatternKey::Expr(Box::new(fold(f, scope))),
+                        _ => key.clone(),
+                    };
+                    (key, fold_pattern(p, scope))
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{CmpOp, parse};
+
+    fn folded(filter: &str) -> Filter {
+        fold_env(&parse(filter).unwrap())
+    }
+
+    fn env_var(name: &str) -> Filter {
+        let Value::Object(vars) = env_object() else {
+            unreachable!()
+        };
+        let value = vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+        Filter::Literal(value.unwrap_or(Value::Null))
+    }
+
+    #[test]
+    fn env_field_chains_become_literals() {
+        assert_eq!(folded("$ENV.PATH"), env_var("PATH"));
+        assert_eq!(folded("env.PATH"), env_var("PATH"));
+        assert_eq!(folded(r#"$ENV["PATH"]"#), env_var("PATH"));
+        assert_eq!(
+            folded("$ENV.QJ_SURELY_UNSET_VARIABLE"),
+            Filter::Literal(Value::Null)
+        );
+        assert_eq!(
+            folded("select(.host == env.PATH) | .msg"),
+            Filter::Pipe(
+                Box::new(Filter::Select(Box::new(Filter::Compare(
+                    Box::new(Filter::Field("host".into())),
+                    CmpOp::Eq,
+                    Box::new(env_var("PATH")),
+                )))),
+                Box::new(Filter::Field("msg".into())),
+            )
+        );
+    }
+
+    #[test]
+    fn constant_interpolation_becomes_a_string() {
+        let Filter::Literal(Value::String(path)) = env_var("PATH") else {
+            return;
+        };
+        assert_eq!(
+            folded(r#""p=\($ENV.PATH) n=\(1)""#),
+            Filter::Literal(Value::String(format!("p={path} n=1")))
+        );
+        assert!(matches!(
+            folded(r#""\(.a) \(env.PATH)""#),
+            Filter::StringInterp(_)
+        ));
+    }
+
+    #[test]
+    fn shadowed_env_is_left_alone() {
+        for filter in [
+            "{} as $ENV | $ENV.PATH",
+            "def env: {}; env.PATH",
+            "def f(env): env.PATH; f({})",
+            "def f($ENV): $ENV.PATH; f({})",
+            "reduce . as $ENV (null; $ENV.PATH)",
+        ] {
+            let f = folded(filter);
+            assert_eq!(f, parse(filter).unwrap(), "{filter}");
+        }
+        // Only the body of the binding is shadowed.
+        assert_eq!(
+            folded("env.PATH as $ENV | $ENV.PATH"),
+            Filter::Bind(
+                Box::new(env_var("PATH")),
+                Pattern::Var("$ENV".into()),
+                Box::new(parse("$ENV.PATH").unwrap()),
+            )
+        );
+    }
+}
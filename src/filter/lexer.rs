@@ -93,6 +93,14 @@ pub fn lex(input: &str) -> Result<Vec<Token>> {
             continue;
         }
 
+        // `#` comments run to end of line (this also covers a `#!` shebang).
+        if bytes[i] == b'#' {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+
         // Single-char tokens
         match bytes[i] {
             b'(' => {
@@ -383,10 +391,18 @@ pub fn lex(input: &str) -> Result<Vec<Token>> {
             continue;
         }
 
-        bail!(
-            "unexpected character '{}' at position {i}",
-            bytes[i] as char
-        );
+        let c = input
+            .get(i..)
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or(bytes[i] as char);
+        if c.is_alphabetic() && i > 0 && bytes[i - 1] == b'.' {
+            // Field names are ASCII identifiers; anything else is quoted
+            bail!(
+                "unexpected character '{c}' at position {i}; \
+                 try .[\"field\"] instead of .field for unusually named fields"
+            );
+        }
+        bail!("unexpected character '{c}' at position {i}");
     }
 
     Ok(tokens)
@@ -607,9 +623,11 @@ fn lex_number(bytes: &[u8], start: usize) -> Result<(Token, usize)> {
         match text.parse::<i64>() {
             Ok(n) => Ok((Token::Int(n), consumed)),
             Err(_) => {
-                // Beyond i64 range — promote to f64 (matches jq behavior)
+                // Beyond i64 range: f64 for arithmetic with floats, plus the
+                // digits so the literal prints and compares exactly
                 let f: f64 = text.parse()?;
-                Ok((Token::Float(f, None), consumed))
+                let digits = text.trim_start_matches('0');
+                Ok((Token::Float(f, Some(digits.into())), consumed))
             }
         }
     }
@@ -681,6 +699,7 @@ fn normalize_extreme_number(text: &str) -> String {
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::*;
 
@@ -689,6 +708,30 @@ mod tests {
         assert_eq!(lex(".").unwrap(), vec![Token::Dot]);
     }
 
+    #[test]
+    fn lex_comments_run_to_end_of_line() {
+        assert_eq!(
+            lex("#!/usr/bin/env qj\n.a # trailing | .b\n# whole line\n| .c").unwrap(),
+            vec![
+                Token::Dot,
+                Token::Ident("a".into()),
+                Token::Pipe,
+                Token::Dot,
+                Token::Ident("c".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_hash_inside_string_is_not_a_comment() {
+        assert_eq!(lex("\"a#b\" # c").unwrap(), vec![Token::Str("a#b".into())]);
+    }
+
+    #[test]
+    fn lex_comments_only_is_empty() {
+        assert_eq!(lex("# nothing here\n\n#!more\n").unwrap(), vec![]);
+    }
+
     #[test]
     fn lex_field_access() {
         assert_eq!(
@@ -1089,6 +1132,15 @@ mod tests {
         assert_eq!(lex("@csv").unwrap(), vec![Token::Format("@csv".into())]);
     }
 
+    #[test]
+    fn lex_unexpected_multibyte_character() {
+        let err = lex(".é").unwrap_err().to_string();
+        assert!(err.contains("unexpected character 'é'"), "{err}");
+        assert!(err.contains(r#"try .["field"]"#), "{err}");
+        let err = lex(". | €").unwrap_err().to_string();
+        assert_eq!(err, "unexpected character '€' at position 4");
+    }
+
     #[test]
     fn lex_format_in_pipe() {
         assert_eq!(
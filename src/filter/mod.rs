@@ -1,14 +1,21 @@
 mod builtins;
+pub mod check;
 pub mod eval;
+pub mod fold;
 pub mod lexer;
 pub mod module;
+pub mod optimize;
 pub mod parser;
+pub mod profile;
 mod value_ops;
-pub use value_ops::{arith_values, compare_values, values_order};
+pub use builtins::env_object;
+pub use value_ops::{arith_values, compare_values, negate_value, values_order};
 
 use crate::value::Value;
-use std::collections::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 /// A destructuring pattern for variable binding.
 #[derive(Debug, Clone, PartialEq)]
@@ -39,6 +46,9 @@ pub enum Filter {
     Identity,
     /// Field access: `.foo`
     Field(String),
+    /// Field path: `.a.b.c`, looked up in one step. The parser writes these
+    /// as pipes of `Field`; [`optimize::optimize`] collapses them.
+    FieldPath(Vec<String>),
     /// Array/object index: `.[0]`, `.[-1]`
     Index(Box<Filter>),
     /// Pipe: `a | b`
@@ -228,19 +238,57 @@ impl VarScope {
     }
 }
 
+/// Pull-based source for the `input`/`inputs` builtins. Each call yields the
+/// next input value, or `None` once the inputs are exhausted. It is handed
+/// the `Env` reading it, to move the input location along.
+pub type InputSource = Box<dyn FnMut(&Env) -> Option<Value>>;
+
+/// Mutable state for one evaluation: the uncaught-error slot, label-break
+/// unwinding, the `input`/`inputs` source, the location of the current input
+/// and the recursion depth. Every `Env`
+/// derived from the same root shares one context, so builtins record errors
+/// through the `env` they are handed.
+#[derive(Default)]
+pub struct EvalCtx {
+    error: RefCell<Option<Value>>,
+    break_signal: RefCell<Option<String>>,
+    labels: Cell<u64>,
+    input_source: RefCell<Option<InputSource>>,
+    /// The input being evaluated, as (source, line): a file path or
+    /// `<stdin>`, and jq's line number of the current value.
+    location: RefCell<Option<(Rc<str>, usize)>>,
+    depth: Cell<usize>,
+}
+
+impl std::fmt::Debug for EvalCtx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EvalCtx")
+            .field("error", &self.error)
+            .field("break_signal", &self.break_signal)
+            .field("labels", &self.labels)
+            .field("input_source", &self.input_source.borrow().is_some())
+            .field("location", &self.location)
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
+
 /// Evaluation environment: variable bindings + user-defined functions.
 #[derive(Debug, Clone)]
 pub struct Env {
     vars: Rc<VarScope>,
     /// User-defined functions keyed by (name, arity).
     funcs: Rc<HashMap<(String, usize), UserFunc>>,
+    ctx: Rc<EvalCtx>,
 }
 
 impl Env {
+    /// A root environment with no bindings and a fresh evaluation context.
     pub fn empty() -> Self {
         Env {
             vars: Rc::new(VarScope::Empty),
             funcs: Rc::new(HashMap::new()),
+            ctx: Rc::new(EvalCtx::default()),
         }
     }
 
@@ -264,6 +312,21 @@ impl Env {
                 parent: self.vars.clone(),
             }),
             funcs: self.funcs.clone(),
+            ctx: self.ctx.clone(),
+        }
+    }
+
+    /// Replace the value of the innermost binding in place, for loops that
+    /// rebind the same variable every iteration. Only possible while nothing
+    /// else holds this scope (no closure or child scope captured it); otherwise
+    /// the value is handed back and the caller should `bind_var` afresh.
+    pub(crate) fn rebind_innermost(&mut self, new_value: Value) -> Result<(), Value> {
+        match Rc::get_mut(&mut self.vars) {
+            Some(VarScope::Cons { value, .. }) => {
+                *value = new_value;
+                Ok(())
+            }
+            _ => Err(new_value),
         }
     }
 
@@ -274,6 +337,7 @@ impl Env {
         Env {
             vars: self.vars.clone(),
             funcs: Rc::new(new_funcs),
+            ctx: self.ctx.clone(),
         }
     }
 
@@ -281,6 +345,104 @@ impl Env {
     pub fn get_func(&self, name: &str, arity: usize) -> Option<&UserFunc> {
         self.funcs.get(&(name.to_string(), arity))
     }
+
+    /// Record an uncaught runtime error, replacing any earlier one.
+    pub fn set_error(&self, err: Value) {
+        *self.ctx.error.borrow_mut() = Some(err);
+    }
+
+    /// Check whether a runtime error is pending (non-consuming).
+    pub fn has_error(&self) -> bool {
+        self.ctx.error.borrow().is_some()
+    }
+
+    /// Take the pending runtime error, clearing it.
+    pub fn take_error(&self) -> Option<Value> {
+        self.ctx.error.borrow_mut().take()
+    }
+
+    /// Install the source read by the `input`/`inputs` builtins.
+    pub fn set_input_source(&self, source: InputSource) {
+        *self.ctx.input_source.borrow_mut() = Some(source);
+    }
+
+    /// Read `input`/`inputs` from an in-memory queue.
+    pub fn set_input_queue(&self, values: VecDeque<Value>) {
+        let mut values = values.into_iter();
+        self.set_input_source(Box::new(move |_| values.next()));
+    }
+
+    /// Remove the input source, draining any unconsumed values into a queue.
+    pub fn take_input_queue(&self) -> VecDeque<Value> {
+        let source = self.ctx.input_source.borrow_mut().take();
+        source.map_or_else(VecDeque::new, |mut next| {
+            std::iter::from_fn(|| next(self)).collect()
+        })
+    }
+
+    /// Pull the next input value. The source is only borrowed for the call,
+    /// so consumers may re-enter `input` while handling the value.
+    pub fn next_input(&self) -> Option<Value> {
+        self.ctx
+            .input_source
+            .borrow_mut()
+            .as_mut()
+            .and_then(|next| next(self))
+    }
+
+    /// Start reading from `source` (a file path or `<stdin>`), for the
+    /// location uncaught runtime errors are reported at. The line goes back
+    /// to 0.
+    pub fn set_input_name(&self, source: &str) {
+        self.set_input_location(source.into(), 0);
+    }
+
+    /// Set both the source and the line of the current input.
+    pub fn set_input_location(&self, source: Rc<str>, line: usize) {
+        *self.ctx.location.borrow_mut() = Some((source, line));
+    }
+
+    /// Set the line of the current source that the value being evaluated
+    /// came from. See [`crate::input::line_of_value`] for how jq numbers it.
+    pub fn set_input_line(&self, line: usize) {
+        if let Some((_, current)) = self.ctx.location.borrow_mut().as_mut() {
+            *current = line;
+        }
+    }
+
+    /// The current source, or `<unknown>` before any input was read.
+    pub fn input_name(&self) -> Rc<str> {
+        match &*self.ctx.location.borrow() {
+            Some((source, _)) => source.clone(),
+            None => "<unknown>".into(),
+        }
+    }
+
+    /// Where the value being evaluated came from, as jq prints it in error
+    /// messages: `data.json:3`, `<stdin>:1`, or `<unknown>` without input.
+    pub fn input_location(&self) -> String {
+        match &*self.ctx.location.borrow() {
+            Some((source, line)) => format!("{source}:{line}"),
+            None => "<unknown>".to_string(),
+        }
+    }
+
+    pub(crate) fn break_signal(&self) -> &RefCell<Option<String>> {
+        &self.ctx.break_signal
+    }
+
+    /// A name for one run of a `label`, distinct from every other run, so a
+    /// `break` unwinds to the label it was written under even when an inner
+    /// label (or a recursive call) reuses the name.
+    pub(crate) fn next_label(&self, name: &str) -> String {
+        let n = self.ctx.labels.get() + 1;
+        self.ctx.labels.set(n);
+        format!("{name}#{n}")
+    }
+
+    pub(crate) fn depth(&self) -> &Cell<usize> {
+        &self.ctx.depth
+    }
 }
 
 /// Builtin operations for `map(builtin)` / `.[] | builtin` passthrough.
@@ -299,6 +461,8 @@ pub enum PassthroughBuiltin {
 pub enum PassthroughPath {
     /// `.` — identity; with compact output, use simdjson::minify() directly.
     Identity,
+    /// `.a.b.c` — navigate with On-Demand and minify just the value found.
+    FieldChain(Vec<String>),
     /// `.field | length` or bare `length` — compute length in C++.
     FieldLength(Vec<String>),
     /// `.field | keys` or bare `keys` — compute keys in C++.
@@ -333,12 +497,14 @@ pub enum PassthroughPath {
 
 impl PassthroughPath {
     /// Whether this passthrough requires compact output mode (`-c`).
-    /// Scalar results like `length` look the same in any mode.
+    /// Scalar results like `length` look the same in any mode; arrays such
+    /// as the result of `keys` don't.
     pub fn requires_compact(&self) -> bool {
         match self {
             PassthroughPath::Identity => true,
+            PassthroughPath::FieldChain(_) => true,
             PassthroughPath::FieldLength(_) => false,
-            PassthroughPath::FieldKeys { .. } => false,
+            PassthroughPath::FieldKeys { .. } => true,
             PassthroughPath::FieldType(_) => false,
             PassthroughPath::FieldHas { .. } => false,
             PassthroughPath::ArrayMapField { .. } => true,
@@ -346,6 +512,37 @@ impl PassthroughPath {
             PassthroughPath::ArrayMapBuiltin { .. } => true,
         }
     }
+
+    /// Whether this passthrough can honour `-S`. Identity re-serializes with
+    /// sorted keys; results that are numbers, strings, booleans or arrays of
+    /// keys look the same either way. Field chains and the array-map paths
+    /// copy values from the input verbatim, unsorted objects included.
+    pub fn supports_sort_keys(&self) -> bool {
+        match self {
+            PassthroughPath::Identity
+            | PassthroughPath::FieldLength(_)
+            | PassthroughPath::FieldKeys { .. }
+            | PassthroughPath::FieldType(_)
+            | PassthroughPath::FieldHas { .. }
+            | PassthroughPath::ArrayMapBuiltin { .. } => true,
+            PassthroughPath::FieldChain(_)
+            | PassthroughPath::ArrayMapField { .. }
+            | PassthroughPath::ArrayMapFieldsObj { .. } => false,
+        }
+    }
+
+    /// Whether this passthrough can honour `-r` and `-j`. Numbers and
+    /// booleans print the same raw; a type name is written without quotes.
+    /// The other paths can produce strings or containers, which raw output
+    /// prints differently from the JSON text they copy.
+    pub fn supports_raw(&self) -> bool {
+        matches!(
+            self,
+            PassthroughPath::FieldLength(_)
+                | PassthroughPath::FieldType(_)
+                | PassthroughPath::FieldHas { .. }
+        )
+    }
 }
 
 /// Collect a chain of Field accesses from a Pipe tree.
@@ -357,11 +554,23 @@ pub fn collect_field_chain(filter: &Filter, fields: &mut Vec<String>) -> bool {
             fields.push(name.clone());
             true
         }
+        Filter::FieldPath(names) => {
+            fields.extend(names.iter().cloned());
+            true
+        }
         Filter::Pipe(a, b) => collect_field_chain(a, fields) && collect_field_chain(b, fields),
         _ => false,
     }
 }
 
+/// The pipe of single field accesses that `FieldPath(names)` stands for,
+/// for the path-based code that works a step at a time.
+pub(crate) fn field_path_pipe(names: &[String]) -> Filter {
+    let mut fields = names.iter().map(|name| Filter::Field(name.clone()));
+    let first = fields.next().unwrap_or(Filter::Identity);
+    fields.fold(first, |acc, f| Filter::Pipe(Box::new(acc), Box::new(f)))
+}
+
 /// Decompose `Pipe(field_chain, Builtin(name, []))` patterns.
 /// Returns `Some((fields, builtin_name))` if the filter is a field chain piped
 /// into a zero-arg builtin; `None` otherwise.
@@ -428,6 +637,8 @@ fn try_passthrough_builtin(filter: &Filter) -> Option<PassthroughBuiltin> {
 pub fn passthrough_path(filter: &Filter) -> Option<PassthroughPath> {
     match filter {
         Filter::Identity => Some(PassthroughPath::Identity),
+        Filter::Field(name) => Some(PassthroughPath::FieldChain(vec![name.clone()])),
+        Filter::FieldPath(names) => Some(PassthroughPath::FieldChain(names.clone())),
         // Bare builtins (no field prefix): length, keys, keys_unsorted, type, has("f")
         Filter::Builtin(name, args) => {
             match (name.as_str(), args.as_slice()) {
@@ -660,12 +871,78 @@ pub fn passthrough_path(filter: &Filter) -> Option<PassthroughPath> {
                     }
                 }
             }
+            // .a | .b.c
+            let mut fields = Vec::new();
+            if collect_field_chain(filter, &mut fields) {
+                return Some(PassthroughPath::FieldChain(fields));
+            }
             None
         }
         _ => None,
     }
 }
 
+/// How a single JSON document is evaluated with a filter, from [`plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalPlan {
+    /// Raw bytes go from simdjson to the output without building values.
+    Passthrough(PassthroughPath),
+    /// Lazy evaluation over the parsed flat buffer (see
+    /// [`crate::flat_eval::is_flat_safe`]).
+    Flat,
+    /// The document is built into a `Value` tree for the evaluator.
+    Tree,
+}
+
+/// Work out which path a single document would take with `filter`, without
+/// evaluating anything. A passthrough assumes output flags it can honour
+/// (see [`PassthroughPath::requires_compact`]); the CLI drops to the next
+/// path when they rule it out. NDJSON input has its own fast paths, named by
+/// `parallel::ndjson::fast_path_name`.
+pub fn plan(filter: &Filter) -> EvalPlan {
+    if let Some(path) = passthrough_path(filter) {
+        EvalPlan::Passthrough(path)
+    } else if crate::flat_eval::is_flat_safe(filter) {
+        EvalPlan::Flat
+    } else {
+        EvalPlan::Tree
+    }
+}
+
+/// Which execution strategies may run, set by the `--eval-mode` debugging
+/// flag. Each mode turns off one more layer than the one before it, so when
+/// two neighbouring modes disagree the layer in between is at fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EvalMode {
+    /// Every path that applies (the default).
+    Auto,
+    /// No single-document passthroughs.
+    PassthroughOff,
+    /// No NDJSON line fast paths or slurp aggregates either: every record
+    /// goes through flat evaluation where it applies.
+    Flat,
+    /// No flat evaluation either: every input is built into a `Value` tree.
+    Tree,
+}
+
+static EVAL_MODE: AtomicU8 = AtomicU8::new(EvalMode::Auto as u8);
+
+/// Restrict the execution strategies for every evaluation that follows.
+pub fn set_eval_mode(mode: EvalMode) {
+    EVAL_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+/// The mode set by [`set_eval_mode`].
+#[inline]
+pub fn eval_mode() -> EvalMode {
+    match EVAL_MODE.load(Ordering::Relaxed) {
+        0 => EvalMode::Auto,
+        1 => EvalMode::PassthroughOff,
+        2 => EvalMode::Flat,
+        _ => EvalMode::Tree,
+    }
+}
+
 /// Parse a jq filter expression string into a `Filter` AST.
 pub fn parse(input: &str) -> anyhow::Result<Filter> {
     let tokens = lexer::lex(input)?;
@@ -697,6 +974,7 @@ impl Filter {
             | Filter::Iterate
             | Filter::Recurse
             | Filter::Field(_)
+            | Filter::FieldPath(_)
             | Filter::Var(_)
             | Filter::Literal(_)
             | Filter::Break(_) => false,
@@ -777,6 +1055,7 @@ impl Filter {
             | Filter::Iterate
             | Filter::Recurse
             | Filter::Field(_)
+            | Filter::FieldPath(_)
             | Filter::Literal(_)
             | Filter::Break(_) => {}
             Filter::Index(f)
@@ -912,6 +1191,17 @@ mod tests {
     use super::*;
     use std::sync::Arc;
 
+    #[test]
+    fn plan_picks_passthrough_then_flat_then_tree() {
+        let plan_of = |s: &str| plan(&parse(s).unwrap());
+        assert_eq!(
+            plan_of("."),
+            EvalPlan::Passthrough(PassthroughPath::Identity)
+        );
+        assert_eq!(plan_of(".[] | select(.a > 1)"), EvalPlan::Flat);
+        assert_eq!(plan_of("sort_by(.a)"), EvalPlan::Tree);
+    }
+
     #[test]
     fn passthrough_map_field() {
         let f = parse("map(.name)").unwrap();
@@ -977,6 +1267,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn passthrough_field_chain() {
+        for (filter, fields) in [
+            (".a", &["a"][..]),
+            (".a.b.c", &["a", "b", "c"]),
+            (".a | .b", &["a", "b"]),
+            (r#"."a b".c"#, &["a b", "c"]),
+        ] {
+            let f = parse(filter).unwrap();
+            let fields = fields.iter().map(|s| s.to_string()).collect();
+            assert_eq!(
+                passthrough_path(&f),
+                Some(PassthroughPath::FieldChain(fields)),
+                "{filter}"
+            );
+        }
+        for filter in [".a[0]", ".a?", ".a | .b?", ".[0].a"] {
+            assert!(
+                !matches!(
+                    passthrough_path(&parse(filter).unwrap()),
+                    Some(PassthroughPath::FieldChain(_))
+                ),
+                "{filter}"
+            );
+        }
+    }
+
     #[test]
     fn passthrough_map_complex_not_detected() {
         // map(.a + .b) should NOT be detected as passthrough
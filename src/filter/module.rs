@@ -79,11 +79,21 @@ impl ModuleLoader {
             } => {
                 // Compute search paths (metadata {search: "./"} overrides)
                 let extra_search = metadata.as_ref().and_then(extract_search);
+                let effective_search = if let Some(ref s) = extra_search {
+                    // Relative search path: resolve against each base search path
+                    let mut paths = Vec::new();
+                    for base in search_paths {
+                        paths.push(base.join(s));
+                    }
+                    paths
+                } else {
+                    search_paths.to_vec()
+                };
 
                 if *is_data {
                     // Data import: load JSON file, bind as variable
-                    let resolved = resolve_data_path(path, search_paths, extra_search.as_deref())?;
-                    let data = load_json_file(&resolved)?;
+                    let resolved = resolve_data_path(path, &effective_search)?;
+                    let data = load_json_file(path, &resolved)?;
                     // Bind as $alias (e.g., $d) and also $alias::member (e.g., $d::d)
                     // The namespace member is the alias without the $ prefix.
                     env = env.bind_var(alias.clone(), data.clone());
@@ -92,16 +102,6 @@ impl ModuleLoader {
                     env = env.bind_var(ns_var, data);
                 } else {
                     // Code import: load .jq file, bind defs under namespace
-                    let effective_search = if let Some(ref s) = extra_search {
-                        // Relative search path: resolve against each base search path
-                        let mut paths = Vec::new();
-                        for base in search_paths {
-                            paths.push(base.join(s));
-                        }
-                        paths
-                    } else {
-                        search_paths.to_vec()
-                    };
                     let module = self.load_module(path, &effective_search)?;
                     env = self.bind_module_defs(&module, Some(alias), env, search_paths)?;
                 }
@@ -283,15 +283,14 @@ impl ModuleLoader {
 
             if dep.is_data {
                 let resolved =
-                    resolve_data_path(&dep.relpath, &effective_search, dep.search.as_deref())
-                        .with_context(|| {
-                            format!(
-                                "resolving data dep '{}' from module {}",
-                                dep.relpath,
-                                module.resolved_path.display()
-                            )
-                        })?;
-                let data = load_json_file(&resolved)?;
+                    resolve_data_path(&dep.relpath, &effective_search).with_context(|| {
+                        format!(
+                            "resolving data dep '{}' from module {}",
+                            dep.relpath,
+                            module.resolved_path.display()
+                        )
+                    })?;
+                let data = load_json_file(&dep.relpath, &resolved)?;
                 if let Some(ref alias) = dep.alias {
                     module_env = module_env.bind_var(alias.clone(), data.clone());
                     let member = alias.trim_start_matches('$');
@@ -467,56 +466,61 @@ fn extract_search(metadata: &Value) -> Option<String> {
     None
 }
 
+/// Find a module file with extension `ext`.
+/// Searches in order: `{search}/{path}.{ext}`, `{search}/{path}/{basename}.{ext}`
+fn find_module_file(path: &str, search_paths: &[PathBuf], ext: &str) -> Option<PathBuf> {
+    let basename = Path::new(path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(path);
+    search_paths.iter().find_map(|base| {
+        [
+            base.join(format!("{path}.{ext}")),
+            base.join(path).join(format!("{basename}.{ext}")),
+        ]
+        .into_iter()
+        .find(|candidate| candidate.exists())
+    })
+}
+
 /// Resolve a module path (code module, .jq file).
-/// Searches in order: `{search}/{path}.jq`, `{search}/{path}/{path}.jq`
 fn resolve_module_path(path: &str, search_paths: &[PathBuf]) -> Result<PathBuf> {
-    for base in search_paths {
-        // Try {base}/{path}.jq
-        let candidate = base.join(format!("{}.jq", path));
-        if candidate.exists() {
-            return Ok(candidate);
-        }
-        // Try {base}/{path}/{basename}.jq
-        let basename = Path::new(path)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or(path);
-        let candidate = base.join(path).join(format!("{}.jq", basename));
-        if candidate.exists() {
-            return Ok(candidate);
-        }
+    match find_module_file(path, search_paths, "jq") {
+        Some(found) => Ok(found),
+        None => bail!(
+            "module not found: \"{path}\" (searched: {:?})",
+            search_paths
+        ),
     }
-    bail!(
-        "module not found: \"{path}\" (searched: {:?})",
-        search_paths
-    )
 }
 
 /// Resolve a data module path (.json file).
-fn resolve_data_path(
-    path: &str,
-    search_paths: &[PathBuf],
-    _extra_search: Option<&str>,
-) -> Result<PathBuf> {
-    for base in search_paths {
-        let candidate = base.join(format!("{}.json", path));
-        if candidate.exists() {
-            return Ok(candidate);
-        }
+fn resolve_data_path(path: &str, search_paths: &[PathBuf]) -> Result<PathBuf> {
+    match find_module_file(path, search_paths, "json") {
+        Some(found) => Ok(found),
+        None => bail!(
+            "data module not found: \"{path}\" (searched: {:?})",
+            search_paths
+        ),
     }
-    bail!(
-        "data module not found: \"{path}\" (searched: {:?})",
-        search_paths
-    )
 }
 
-/// Load and parse a JSON file for data imports.
-fn load_json_file(path: &Path) -> Result<Value> {
-    let content = std::fs::read_to_string(path)
-        .with_context(|| format!("failed to read data module: {}", path.display()))?;
-    let padded = crate::simdjson::pad_buffer(content.as_bytes());
-    let val = crate::simdjson::dom_parse_to_value(&padded, content.len())
-        .with_context(|| format!("failed to parse data module: {}", path.display()))?;
-    // jq wraps data module values in an array
-    Ok(Value::Array(Arc::new(vec![val])))
+/// Load a JSON file for the data import `name`. Like jq, every value in the
+/// file is collected into one array.
+fn load_json_file(name: &str, path: &Path) -> Result<Value> {
+    let content = std::fs::read(path)
+        .with_context(|| format!("failed to read data module \"{name}\": {}", path.display()))?;
+    let mut values = Vec::new();
+    if let Err(err) = crate::input::collect_values_from_buf(&content, false, &mut values) {
+        // serde_json says where the text goes wrong; simdjson only has a code.
+        let reason = serde_json::Deserializer::from_slice(&content)
+            .into_iter::<serde_json::Value>()
+            .find_map(Result::err)
+            .map_or_else(|| format!("{err:#}"), |e| e.to_string());
+        bail!(
+            "failed to parse data module \"{name}\": {}: {reason}",
+            path.display()
+        );
+    }
+    Ok(Value::Array(Arc::new(values)))
 }
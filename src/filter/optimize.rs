@@ -0,0 +1,360 @@
+//! Simplification of a parsed filter before it is planned and run.
+//!
+//! Generated filters often carry work that doesn't depend on the input, like
+//! `if "prod" == "prod" then .x else .y end` or `select(true)`, and over an
+//! NDJSON file it would be redone for every record. [`optimize`] does it
+//! once instead:
+//!
+//! - comparisons, arithmetic, `and`/`or`, `not` and negation of literals are
+//!   replaced by their value, unless evaluating them raises an error;
+//! - `. | f` and `f | .` become `f`;
+//! - an `if` with a literal condition becomes the branch it takes;
+//! - `select` of a truthy literal becomes `.`;
+//! - pipes of fields, `.a.b.c` or `f | .a | .b`, become one [`Filter::FieldPath`].
+//!
+//! Every rewrite gives the same outputs and errors as the filter it replaces.
+
+use super::eval::eval;
+use super::{Env, Filter, ObjKey, Pattern, PatternKey, StringPart};
+use crate::value::Value;
+
+/// Simplify `filter`, bottom-up.
+pub fn optimize(filter: &Filter) -> Filter {
+    let go = |f: &Filter| optimize(f);
+    let boxed = |f: &Filter| Box::new(optimize(f));
+    let opt = |f: &Option<Box<Filter>>| f.as_ref().map(|f| Box::new(optimize(f)));
+    let node = match filter {
+        Filter::Identity
+        | Filter::Field(_)
+        | Filter::FieldPath(_)
+        | Filter::Iterate
+        | Filter::Literal(_)
+        | Filter::Recurse
+        | Filter::Var(_)
+        | Filter::Break(_) => return filter.clone(),
+        Filter::Pipe(a, b) => Filter::Pipe(boxed(a), boxed(b)),
+        Filter::Index(f) => Filter::Index(boxed(f)),
+        Filter::Select(f) => Filter::Select(boxed(f)),
+        Filter::ArrayConstruct(f) => Filter::ArrayConstruct(boxed(f)),
+        Filter::Not(f) => Filter::Not(boxed(f)),
+        Filter::Try(f) => Filter::Try(boxed(f)),
+        Filter::Neg(f) => Filter::Neg(boxed(f)),
+        Filter::ObjectConstruct(pairs) => Filter::ObjectConstruct(
+            pairs
+                .iter()
+                .map(|(k, v)| {
+                    let k = match k {
+                        ObjKey::Name(n) => ObjKey::Name(n.clone()),
+                        ObjKey::Expr(f) => ObjKey::Expr(boxed(f)),
+                    };
+                    (k, boxed(v))
+                })
+                .collect(),
+        ),
+        Filter::StringInterp(parts) => Filter::StringInterp(
+            parts
+                .iter()
+                .map(|p| match p {
+                    StringPart::Lit(s) => StringPart::Lit(s.clone()),
+                    StringPart::Expr(f) => StringPart::Expr(go(f)),
+                })
+                .collect(),
+        ),
+        Filter::Compare(a, op, b) => Filter::Compare(boxed(a), *op, boxed(b)),
+        Filter::Arith(a, op, b) => Filter::Arith(boxed(a), *op, boxed(b)),
+        Filter::BoolOp(a, op, b) => Filter::BoolOp(boxed(a), *op, boxed(b)),
+        Filter::Alternative(a, b) => Filter::Alternative(boxed(a), boxed(b)),
+        Filter::TryCatch(a, b) => Filter::TryCatch(boxed(a), boxed(b)),
+        Filter::Assign(a, op, b) => Filter::Assign(boxed(a), *op, boxed(b)),
+        Filter::Comma(items) => Filter::Comma(items.iter().map(go).collect()),
+        Filter::Builtin(name, args) => Filter::Builtin(name.clone(), args.iter().map(go).collect()),
+        Filter::IfThenElse(c, t, e) => Filter::IfThenElse(boxed(c), boxed(t), opt(e)),
+        Filter::Slice(s, e) => Filter::Slice(opt(s), opt(e)),
+        Filter::PostfixIndex(base, idx) => Filter::PostfixIndex(boxed(base), boxed(idx)),
+        Filter::PostfixSlice(base, s, e) => Filter::PostfixSlice(boxed(base), opt(s), opt(e)),
+        Filter::Label(name, body) => Filter::Label(name.clone(), boxed(body)),
+        Filter::Bind(src, pat, body) => {
+            Filter::Bind(boxed(src), optimize_pattern(pat), boxed(body))
+        }
+        Filter::AltBind(src, pats, body) => Filter::AltBind(
+            boxed(src),
+            pats.iter().map(optimize_pattern).collect(),
+            boxed(body),
+        ),
+        Filter::Reduce(src, pat, init, update) => Filter::Reduce(
+            boxed(src),
+            optimize_pattern(pat),
+            boxed(init),
+            boxed(update),
+        ),
+        Filter::Foreach(src, pat, init, update, extract) => Filter::Foreach(
+            boxed(src),
+            optimize_pattern(pat),
+            boxed(init),
+            boxed(update),
+            opt(extract),
+        ),
+        Filter::Def {
+            name,
+            params,
+            body,
+            rest,
+        } => Filter::Def {
+            name: name.clone(),
+            params: params.clone(),
+            body: boxed(body),
+            rest: boxed(rest),
+        },
+        Filter::Import {
+            path,
+            alias,
+            is_data,
+            metadata,
+            rest,
+        } => Filter::Import {
+            path: path.clone(),
+            alias: alias.clone(),
+            is_data: *is_data,
+            metadata: metadata.clone(),
+            rest: boxed(rest),
+        },
+        Filter::Include {
+            path,
+            metadata,
+            rest,
+        } => Filter::Include {
+            path: path.clone(),
+            metadata: metadata.clone(),
+            rest: boxed(rest),
+        },
+        Filter::ModuleDecl { metadata, rest } => Filter::ModuleDecl {
+            metadata: metadata.clone(),
+            rest: boxed(rest),
+        },
+    };
+    simplify(node)
+}
+
+/// Apply the rewrites to a node whose children are already simplified.
+fn simplify(node: Filter) -> Filter {
+    match node {
+        Filter::Pipe(a, b) => match (*a, *b) {
+            (Filter::Identity, f) | (f, Filter::Identity) => f,
+            (a, b) => {
+                if let (Some(mut names), Some(more)) = (field_names(&a), field_names(&b)) {
+                    names.extend(more);
+                    return Filter::FieldPath(names);
+                }
+                // `(f | .a) | .b` is `f | .a.b`
+                if let Filter::Pipe(f, last) = &a
+                    && let (Some(mut names), Some(more)) = (field_names(last), field_names(&b))
+                {
+                    names.extend(more);
+                    return Filter::Pipe(f.clone(), Box::new(Filter::FieldPath(names)));
+                }
+                if let Filter::Literal(input) = &a
+                    && closed(&b)
+                    && let Some(value) = constant(&b, input)
+                {
+                    return Filter::Literal(value);
+                }
+                Filter::Pipe(Box::new(a), Box::new(b))
+            }
+        },
+        Filter::BoolOp(a, op, b) => {
+            // `false and f` and `true or f` don't run f
+            if let Filter::Literal(lhs) = a.as_ref() {
+                let decided = match op {
+                    super::BoolOp::And => !lhs.is_truthy(),
+                    super::BoolOp::Or => lhs.is_truthy(),
+                };
+                if decided {
+                    return Filter::Literal(Value::Bool(lhs.is_truthy()));
+                }
+            }
+            fold_literals(Filter::BoolOp(a, op, b))
+        }
+        node @ (Filter::Compare(..) | Filter::Arith(..) | Filter::Not(_) | Filter::Neg(_)) => {
+            fold_literals(node)
+        }
+        Filter::IfThenElse(cond, then, otherwise) => match *cond {
+            Filter::Literal(c) if c.is_truthy() => *then,
+            Filter::Literal(_) => otherwise.map_or(Filter::Identity, |e| *e),
+            cond => Filter::IfThenElse(Box::new(cond), then, otherwise),
+        },
+        Filter::Select(cond) => match *cond {
+            Filter::Literal(c) if c.is_truthy() => Filter::Identity,
+            cond => Filter::Select(Box::new(cond)),
+        },
+        node => node,
+    }
+}
+
+/// Replace an operator node whose operands are all literals by its value.
+fn fold_literals(node: Filter) -> Filter {
+    let literal_operands = match &node {
+        Filter::Compare(a, _, b) | Filter::Arith(a, _, b) | Filter::BoolOp(a, _, b) => {
+            matches!(**a, Filter::Literal(_)) && matches!(**b, Filter::Literal(_))
+        }
+        Filter::Not(f) | Filter::Neg(f) => matches!(**f, Filter::Literal(_)),
+        _ => false,
+    };
+    if !literal_operands {
+        return node;
+    }
+    match constant(&node, &Value::Null) {
+        Some(value) => Filter::Literal(value),
+        None => node,
+    }
+}
+
+/// Whether `filter` is built only from literals, `.` and the operators
+/// above, so that its value depends on nothing but its input.
+fn closed(filter: &Filter) -> bool {
+    match filter {
+        Filter::Literal(_) | Filter::Identity => true,
+        Filter::Compare(a, _, b) | Filter::Arith(a, _, b) | Filter::BoolOp(a, _, b) => {
+            closed(a) && closed(b)
+        }
+        Filter::Not(f) | Filter::Neg(f) => closed(f),
+        _ => false,
+    }
+}
+
+/// The single value `filter` produces on `input`, if it produces exactly one
+/// and raises no error. Errors are left for run time, where they belong.
+fn constant(filter: &Filter, input: &Value) -> Option<Value> {
+    let env = Env::empty();
+    let mut outputs = Vec::new();
+    eval(filter, input, &env, &mut |v| outputs.push(v));
+    if env.has_error() || outputs.len() != 1 {
+        return None;
+    }
+    outputs.pop()
+}
+
+/// The fields of `.a` or `.a.b.c`.
+fn field_names(filter: &Filter) -> Option<Vec<String>> {
+    match filter {
+        Filter::Field(name) => Some(vec![name.clone()]),
+        Filter::FieldPath(names) => Some(names.clone()),
+        _ => None,
+    }
+}
+
+/// Computed keys in object patterns are expressions too.
+fn optimize_pattern(pat: &Pattern) -> Pattern {
+    match pat {
+        Pattern::Var(_) => pat.clone(),
+        Pattern::Array(pats) => Pattern::Array(pats.iter().map(optimize_pattern).collect()),
+        Pattern::Object(pairs) => Pattern::Object(
+            pairs
+                .iter()
+                .map(|(key, p)| {
+                    let key = match key {
+                        PatternKey::Expr(f) => PatternKey::Expr(Box::new(optimize(f))),
+                        _ => key.clone(),
+                    };
+                    (key, optimize_pattern(p))
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::parse;
+
+    fn optimized(filter: &str) -> Filter {
+        optimize(&parse(filter).unwrap())
+    }
+
+    fn lit(v: Value) -> Filter {
+        Filter::Literal(v)
+    }
+
+    fn path(names: &[&str]) -> Filter {
+        Filter::FieldPath(names.iter().map(|n| n.to_string()).collect())
+    }
+
+    #[test]
+    fn literal_operators_fold() {
+        assert_eq!(optimized(r#""prod" == "prod""#), lit(Value::Bool(true)));
+        assert_eq!(optimized("1 + 2 * 3"), lit(Value::Int(7)));
+        assert_eq!(optimized("(1 < 2) and (2 < 1)"), lit(Value::Bool(false)));
+        assert_eq!(optimized("true | not"), lit(Value::Bool(false)));
+        assert_eq!(
+            optimized(r#""a" | . + "b""#),
+            lit(Value::String("ab".into()))
+        );
+        assert_eq!(optimized("-(1 + 1)"), lit(Value::Int(-2)));
+        // `false and f` is false without running f
+        assert_eq!(optimized("false and .a"), lit(Value::Bool(false)));
+        assert_eq!(optimized("1 or error"), lit(Value::Bool(true)));
+    }
+
+    #[test]
+    fn erroring_or_input_dependent_operators_stay() {
+        for filter in [
+            r#"1 + "a""#,
+            "1 / 0",
+            "[] | .[0] - 1",
+            ".a == 1",
+            "true and .a",
+        ] {
+            assert_eq!(optimized(filter), parse(filter).unwrap(), "{filter}");
+        }
+    }
+
+    #[test]
+    fn identity_pipes_drop() {
+        assert_eq!(optimized(". | .a"), Filter::Field("a".into()));
+        assert_eq!(optimized("length | ."), parse("length").unwrap());
+        assert_eq!(optimized(". | . | ."), Filter::Identity);
+    }
+
+    #[test]
+    fn constant_conditions_pick_a_branch() {
+        assert_eq!(
+            optimized(r#"if "prod" == "prod" then .x else .y end"#),
+            Filter::Field("x".into())
+        );
+        assert_eq!(optimized("if null then .x end"), Filter::Identity);
+        assert_eq!(
+            optimized("if false then .x elif 1 then .y else .z end"),
+            Filter::Field("y".into())
+        );
+        assert_eq!(optimized("select(true)"), Filter::Identity);
+        assert_eq!(optimized(".[] | select(1 < 2)"), Filter::Iterate);
+        // A false condition still has to drop the input
+        assert_eq!(optimized("select(false)"), parse("select(false)").unwrap());
+    }
+
+    #[test]
+    fn field_chains_become_paths() {
+        assert_eq!(optimized(".a.b.c"), path(&["a", "b", "c"]));
+        assert_eq!(optimized(".a | .b"), path(&["a", "b"]));
+        assert_eq!(optimized(".a"), Filter::Field("a".into()));
+        assert_eq!(
+            optimized(".[] | .a | .b"),
+            Filter::Pipe(Box::new(Filter::Iterate), Box::new(path(&["a", "b"])))
+        );
+        assert_eq!(
+            optimized(".a.b | length"),
+            Filter::Pipe(
+                Box::new(path(&["a", "b"])),
+                Box::new(parse("length").unwrap())
+            )
+        );
+        assert_eq!(
+            optimized(".a.b |= 1"),
+            Filter::Assign(
+                Box::new(path(&["a", "b"])),
+                crate::filter::AssignOp::Update,
+                Box::new(lit(Value::Int(1))),
+            )
+        );
+    }
+}
@@ -675,6 +675,14 @@ impl<'a> Parser<'a> {
                             };
                             node = Filter::Pipe(Box::new(node), Box::new(Filter::Field(name)));
                         }
+                        Some(Token::InterpStr(_)) => {
+                            // ."\(expr)" — index by the interpolated key
+                            let key = self.parse_primary()?;
+                            node = Filter::Pipe(
+                                Box::new(node),
+                                Box::new(Filter::Index(Box::new(key))),
+                            );
+                        }
                         _ if self.peek_keyword_as_field().is_some() => {
                             let name = self.peek_keyword_as_field().unwrap().to_string();
                             self.advance();
@@ -752,6 +760,11 @@ impl<'a> Parser<'a> {
                         };
                         Ok(Filter::Field(name))
                     }
+                    Some(Token::InterpStr(_)) => {
+                        // ."\(expr)" — index by the interpolated key, like .["\(expr)"]
+                        let key = self.parse_primary()?;
+                        Ok(Filter::Index(Box::new(key)))
+                    }
                     Some(Token::LBrack) => {
                         self.advance();
                         if self.peek() == Some(&Token::RBrack) {
@@ -944,7 +957,8 @@ impl<'a> Parser<'a> {
                     Token::Format(s) => s.clone(),
                     _ => unreachable!(),
                 };
-                // @format "string_interp" — apply format to interpolated expressions
+                // @format "string_interp" — apply format to interpolated
+                // expressions only; the literal text is left as written
                 if matches!(self.peek(), Some(Token::Str(_) | Token::InterpStr(_))) {
                     let str_filter = self.parse_primary()?;
                     // Wrap each interpolated Expr part with the format builtin
@@ -965,11 +979,8 @@ impl<'a> Parser<'a> {
                             .collect();
                         Ok(Filter::StringInterp(wrapped_parts))
                     } else {
-                        // Plain string, no interpolation — apply format to whole thing
-                        Ok(Filter::Pipe(
-                            Box::new(str_filter),
-                            Box::new(Filter::Builtin(name, vec![])),
-                        ))
+                        // Plain string, no interpolation — nothing to format
+                        Ok(str_filter)
                     }
                 } else {
                     Ok(Filter::Builtin(name, vec![]))
@@ -1094,6 +1105,10 @@ impl<'a> Parser<'a> {
                 break;
             }
             self.advance(); // consume comma
+            // jq allows a trailing comma: {a: 1,}
+            if self.peek() == Some(&Token::RBrace) {
+                break;
+            }
         }
 
         self.expect(&Token::RBrace)?;
@@ -1198,6 +1213,9 @@ impl<'a> Parser<'a> {
                 self.expect(&Token::RParen)?;
                 ObjKey::Expr(Box::new(expr))
             }
+            // Format string key: {@base64 "x\(.a)": v}, or a bare {@base64: v}
+            // keyed by the formatted input.
+            Some(Token::Format(_)) => ObjKey::Expr(Box::new(self.parse_primary()?)),
             _ if self.peek_keyword_as_obj_key().is_some() => {
                 let name = self.peek_keyword_as_obj_key().unwrap().to_string();
                 self.advance();
@@ -1272,6 +1290,16 @@ mod tests {
         assert_eq!(p(".name"), Filter::Field("name".into()));
     }
 
+    #[test]
+    fn parse_ignores_comments_in_multiline_def() {
+        let commented = "#!/usr/bin/env qj\n\
+                         # double every element\n\
+                         def double: # the helper\n\
+                           . * 2;\n\
+                         map(double) # \"#\" in a comment\n";
+        assert_eq!(p(commented), p("def double: . * 2; map(double)"));
+    }
+
     #[test]
     fn parse_nested_field() {
         assert_eq!(
@@ -1328,6 +1356,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_object_mixed_key_forms() {
+        let Filter::ObjectConstruct(pairs) =
+            p(r#"{a: 1, $x, "b": 2, "c\(.n)": 3, @base64 "d": 4, (.k): 5,}"#)
+        else {
+            panic!("expected object construction");
+        };
+        assert_eq!(pairs.len(), 6);
+        assert_eq!(pairs[1].0, ObjKey::Name("x".into()));
+        assert_eq!(*pairs[1].1, Filter::Var("$x".into()));
+        assert_eq!(pairs[2].0, ObjKey::Name("b".into()));
+        assert!(matches!(&pairs[3].0, ObjKey::Expr(e) if matches!(**e, Filter::StringInterp(_))));
+        // A format prefix only applies to interpolations, so `@base64 "d"` is "d"
+        assert_eq!(pairs[4].0, ObjKey::Expr(Box::new(p(r#""d""#))));
+        assert!(matches!(&pairs[5].0, ObjKey::Expr(_)));
+    }
+
     #[test]
     fn parse_object_shorthand() {
         assert_eq!(
@@ -1501,6 +1546,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_try_postfix_on_any_term() {
+        let try_ = |f: Filter| Filter::Try(Box::new(f));
+        assert_eq!(
+            p("fromjson?"),
+            try_(Filter::Builtin("fromjson".into(), vec![]))
+        );
+        assert_eq!(
+            p("map(.x)?"),
+            try_(Filter::Builtin(
+                "map".into(),
+                vec![Filter::Field("x".into())]
+            ))
+        );
+        assert_eq!(
+            p("(1 / 0)?"),
+            try_(Filter::Arith(
+                Box::new(Filter::Literal(Value::Int(1))),
+                ArithOp::Div,
+                Box::new(Filter::Literal(Value::Int(0))),
+            ))
+        );
+        // `?` covers the term before it, and accesses after it apply to its output.
+        assert_eq!(
+            p(".a?.b"),
+            Filter::Pipe(
+                Box::new(try_(Filter::Field("a".into()))),
+                Box::new(Filter::Field("b".into())),
+            )
+        );
+        assert_eq!(
+            p(".a?.b?"),
+            try_(Filter::Pipe(
+                Box::new(try_(Filter::Field("a".into()))),
+                Box::new(Filter::Field("b".into())),
+            ))
+        );
+        // It binds tighter than unary minus and `*`.
+        assert_eq!(
+            p("-.a?"),
+            Filter::Neg(Box::new(try_(Filter::Field("a".into()))))
+        );
+        assert_eq!(
+            p(".a? * 2"),
+            Filter::Arith(
+                Box::new(try_(Filter::Field("a".into()))),
+                ArithOp::Mul,
+                Box::new(Filter::Literal(Value::Int(2))),
+            )
+        );
+    }
+
     #[test]
     fn parse_complex_pipeline() {
         // .items[] | select(.active == true) | {name: .name, score: .score}
@@ -1854,6 +1951,41 @@ mod tests {
         assert_eq!(p("@base64"), Filter::Builtin("@base64".into(), vec![]));
     }
 
+    #[test]
+    fn parse_format_prefixed_string() {
+        // The format wraps each interpolated expression, not the literal text
+        match p(r#"@csv "row: \(.a)""#) {
+            Filter::StringInterp(parts) => {
+                assert_eq!(parts[0], StringPart::Lit("row: ".into()));
+                assert_eq!(
+                    parts[1],
+                    StringPart::Expr(Filter::Pipe(
+                        Box::new(Filter::Field("a".into())),
+                        Box::new(Filter::Builtin("@csv".into(), vec![])),
+                    ))
+                );
+            }
+            other => panic!("expected StringInterp, got {other:?}"),
+        }
+        // Without interpolation the literal is left as written
+        assert_eq!(p(r#"@base64 "abc""#), p(r#""abc""#));
+    }
+
+    #[test]
+    fn parse_quoted_field_access() {
+        assert_eq!(p(r#"."foo bar""#), Filter::Field("foo bar".into()));
+        assert_eq!(
+            p(r#".["foo bar"]"#),
+            Filter::Index(Box::new(p(r#""foo bar""#)))
+        );
+        // An interpolated key indexes like .["..."]
+        assert_eq!(p(r#"."k\(.n)""#), Filter::Index(Box::new(p(r#""k\(.n)""#))));
+        assert_eq!(
+            p(r#".a."k\(.n)""#),
+            Filter::Pipe(Box::new(p(".a")), Box::new(p(r#"."k\(.n)""#)))
+        );
+    }
+
     #[test]
     fn parse_format_in_pipe() {
         let f = p(". | @csv");
@@ -0,0 +1,656 @@
+//! Per-node evaluation profiling (`--profile`).
+//!
+//! When enabled, every [`eval`](super::eval::eval) call is timed and counted
+//! against the AST node it evaluates. A node's time covers the node and its
+//! subexpressions but not the downstream consumers of its outputs, so in
+//! `a | b` the time of `a` excludes `b`. Recursive calls into the same node
+//! count toward it again, as in any inclusive profile.
+//!
+//! Counters live in a thread-local table keyed by node address; [`report`]
+//! merges the tables of the calling thread and every rayon worker and prints
+//! one row per distinct node, so NDJSON runs aggregate across records.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::mem::Discriminant;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use super::{ArithOp, AssignOp, BoolOp, CmpOp, Filter, ObjKey, Pattern, PatternKey, StringPart};
+use crate::value::Value;
+
+/// Longest source snippet shown in the report.
+const SNIPPET_MAX_CHARS: usize = 60;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Rows merged from finished threads, keyed by (node kind, snippet).
+static MERGED: Mutex<Vec<Row>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static NODES: RefCell<HashMap<usize, NodeStats>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone)]
+struct NodeStats {
+    variant: Discriminant<Filter>,
+    kind: &'static str,
+    snippet: String,
+    calls: u64,
+    outputs: u64,
+    time: Duration,
+}
+
+/// One line of the report.
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    kind: &'static str,
+    snippet: String,
+    calls: u64,
+    outputs: u64,
+    time: Duration,
+}
+
+/// Turn on instrumentation for every evaluation that follows.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--profile` instrumentation is on.
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Evaluate one node via `run`, recording a call, its outputs and its time
+/// minus the time spent in `output`.
+pub(super) fn record(
+    filter: &Filter,
+    output: &mut dyn FnMut(Value),
+    run: impl FnOnce(&mut dyn FnMut(Value)),
+) {
+    let start = Instant::now();
+    let mut outputs = 0u64;
+    let mut downstream = Duration::ZERO;
+    run(&mut |v| {
+        outputs += 1;
+        let t = Instant::now();
+        output(v);
+        downstream += t.elapsed();
+    });
+    let time = start.elapsed().saturating_sub(downstream);
+
+    NODES.with(|nodes| {
+        let mut nodes = nodes.borrow_mut();
+        let key = filter as *const Filter as usize;
+        let variant = std::mem::discriminant(filter);
+        let stats = nodes.entry(key).or_insert_with(|| NodeStats::new(filter));
+        // Temporary nodes built during evaluation can reuse a freed address
+        if stats.variant != variant {
+            stats.flush_into(&mut MERGED.lock().unwrap_or_else(|e| e.into_inner()));
+            *stats = NodeStats::new(filter);
+        }
+        stats.calls += 1;
+        stats.outputs += outputs;
+        stats.time += time;
+    });
+}
+
+impl NodeStats {
+    fn new(filter: &Filter) -> Self {
+        NodeStats {
+            variant: std::mem::discriminant(filter),
+            kind: kind(filter),
+            snippet: snippet(filter),
+            calls: 0,
+            outputs: 0,
+            time: Duration::ZERO,
+        }
+    }
+
+    fn flush_into(&self, rows: &mut Vec<Row>) {
+        if self.calls == 0 {
+            return;
+        }
+        match rows
+            .iter_mut()
+            .find(|r| r.kind == self.kind && r.snippet == self.snippet)
+        {
+            Some(row) => {
+                row.calls += self.calls;
+                row.outputs += self.outputs;
+                row.time += self.time;
+            }
+            None => rows.push(Row {
+                kind: self.kind,
+                snippet: self.snippet.clone(),
+                calls: self.calls,
+                outputs: self.outputs,
+                time: self.time,
+            }),
+        }
+    }
+}
+
+/// Move this thread's counters into the shared table.
+fn flush_thread() {
+    let nodes = NODES.with(|nodes| std::mem::take(&mut *nodes.borrow_mut()));
+    let mut merged = MERGED.lock().unwrap_or_else(|e| e.into_inner());
+    for stats in nodes.values() {
+        stats.flush_into(&mut merged);
+    }
+}
+
+/// Collect the counters from this thread and all rayon workers, slowest
+/// node first. Clears them.
+fn take_rows() -> Vec<Row> {
+    flush_thread();
+    rayon::broadcast(|_| flush_thread());
+    let mut rows = std::mem::take(&mut *MERGED.lock().unwrap_or_else(|e| e.into_inner()));
+    rows.sort_by(|a, b| b.time.cmp(&a.time).then(b.calls.cmp(&a.calls)));
+    rows
+}
+
+/// Print the profile table.
+pub fn report(w: &mut dyn Write) -> io::Result<()> {
+    let rows = take_rows();
+    writeln!(w, "--- profile ---")?;
+    writeln!(
+        w,
+        "{:>10}  {:>9}  {:>9}  {:<14}  node",
+        "time (ms)", "calls", "outputs", "kind"
+    )?;
+    for row in rows {
+        writeln!(
+            w,
+            "{:>10.3}  {:>9}  {:>9}  {:<14}  {}",
+            row.time.as_secs_f64() * 1000.0,
+            row.calls,
+            row.outputs,
+            row.kind,
+            row.snippet
+        )?;
+    }
+    Ok(())
+}
+
+/// The AST variant name shown in the `kind` column.
+fn kind(filter: &Filter) -> &'static str {
+    match filter {
+        Filter::Identity => "Identity",
+        Filter::Field(_) => "Field",
+        Filter::FieldPath(_) => "FieldPath",
+        Filter::Index(_) => "Index",
+        Filter::Pipe(..) => "Pipe",
+        Filter::Iterate => "Iterate",
+        Filter::Select(_) => "Select",
+        Filter::ObjectConstruct(_) => "Object",
+        Filter::ArrayConstruct(_) => "Array",
+        Filter::Literal(_) => "Literal",
+        Filter::Compare(..) => "Compare",
+        Filter::Arith(..) => "Arith",
+        Filter::Comma(_) => "Comma",
+        Filter::Recurse => "Recurse",
+        Filter::Builtin(..) => "Call",
+        Filter::Not(_) => "Not",
+        Filter::BoolOp(..) => "BoolOp",
+        Filter::IfThenElse(..) => "If",
+        Filter::Alternative(..) => "Alternative",
+        Filter::Try(_) => "Try",
+        Filter::StringInterp(_) => "StringInterp",
+        Filter::Neg(_) => "Neg",
+        Filter::TryCatch(..) => "TryCatch",
+        Filter::Slice(..) => "Slice",
+        Filter::Var(_) => "Var",
+        Filter::Bind(..) => "Bind",
+        Filter::Reduce(..) => "Reduce",
+        Filter::Foreach(..) => "Foreach",
+        Filter::Assign(..) => "Assign",
+        Filter::Def { .. } => "Def",
+        Filter::AltBind(..) => "AltBind",
+        Filter::Label(..) => "Label",
+        Filter::Break(_) => "Break",
+        Filter::PostfixIndex(..) => "PostfixIndex",
+        Filter::PostfixSlice(..) => "PostfixSlice",
+        Filter::Import { .. } => "Import",
+        Filter::Include { .. } => "Include",
+        Filter::ModuleDecl { .. } => "Module",
+    }
+}
+
+/// jq-like source text for a node, shortened to [`SNIPPET_MAX_CHARS`].
+fn snippet(filter: &Filter) -> String {
+    let mut s = String::new();
+    render(filter, &mut s);
+    if s.chars().count() > SNIPPET_MAX_CHARS {
+        s = s.chars().take(SNIPPET_MAX_CHARS - 3).collect();
+        s.push_str("...");
+    }
+    s
+}
+
+/// Binding strength of a node's rendered form, loosest (pipe) first.
+const TERM: u8 = 9;
+
+fn precedence(filter: &Filter) -> u8 {
+    match filter {
+        Filter::Pipe(..)
+        | Filter::Bind(..)
+        | Filter::AltBind(..)
+        | Filter::Def { .. }
+        | Filter::Label(..)
+        | Filter::Import { .. }
+        | Filter::Include { .. }
+        | Filter::ModuleDecl { .. } => 0,
+        Filter::Not(inner) if **inner != Filter::Identity => 0,
+        Filter::Comma(_) => 1,
+        Filter::Alternative(..) | Filter::Assign(..) => 2,
+        Filter::BoolOp(_, BoolOp::Or, _) => 3,
+        Filter::BoolOp(_, BoolOp::And, _) => 4,
+        Filter::Compare(..) => 5,
+        Filter::Arith(_, ArithOp::Add | ArithOp::Sub, _) => 6,
+        Filter::Arith(..) => 7,
+        Filter::Neg(_) | Filter::TryCatch(..) => 8,
+        _ => TERM,
+    }
+}
+
+/// Render `filter`, parenthesized if it binds looser than `min`.
+fn render_operand(filter: &Filter, min: u8, out: &mut String) {
+    if precedence(filter) < min {
+        out.push('(');
+        render(filter, out);
+        out.push(')');
+    } else {
+        render(filter, out);
+    }
+}
+
+fn render_args(args: &[Filter], out: &mut String) {
+    out.push('(');
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            out.push_str("; ");
+        }
+        render(arg, out);
+    }
+    out.push(')');
+}
+
+fn render_pattern(pattern: &Pattern, out: &mut String) {
+    match pattern {
+        Pattern::Var(name) => out.push_str(name),
+        Pattern::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                render_pattern(item, out);
+            }
+            out.push(']');
+        }
+        Pattern::Object(pairs) => {
+            out.push('{');
+            for (i, (key, pat)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                match key {
+                    PatternKey::Var(name) if *pat == Pattern::Var(name.clone()) => {
+                        out.push_str(name);
+                        continue;
+                    }
+                    PatternKey::Var(name) | PatternKey::Name(name) => out.push_str(name),
+                    PatternKey::Expr(expr) => {
+                        out.push('(');
+                        render(expr, out);
+                        out.push(')');
+                    }
+                }
+                out.push_str(": ");
+                render_pattern(pat, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn render_field(name: &str, out: &mut String) {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        out.push_str(name);
+    } else {
+        out.push_str(&crate::output::format_compact(&Value::String(
+            name.to_string(),
+        )));
+    }
+}
+
+fn render(filter: &Filter, out: &mut String) {
+    match filter {
+        Filter::Identity => out.push('.'),
+        Filter::Field(name) => {
+            out.push('.');
+            render_field(name, out);
+        }
+        Filter::FieldPath(names) => {
+            for name in names {
+                out.push('.');
+                render_field(name, out);
+            }
+        }
+        Filter::Index(idx) => {
+            out.push_str(".[");
+            render(idx, out);
+            out.push(']');
+        }
+        Filter::Pipe(a, b) => {
+            render(a, out);
+            out.push_str(" | ");
+            render(b, out);
+        }
+        Filter::Iterate => out.push_str(".[]"),
+        Filter::Select(cond) => {
+            out.push_str("select(");
+            render(cond, out);
+            out.push(')');
+        }
+        Filter::ObjectConstruct(pairs) => {
+            out.push('{');
+            for (i, (key, val)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                match key {
+                    ObjKey::Name(name) => render_field(name, out),
+                    ObjKey::Expr(expr) => {
+                        out.push('(');
+                        render(expr, out);
+                        out.push(')');
+                    }
+                }
+                out.push_str(": ");
+                render_operand(val, TERM, out);
+            }
+            out.push('}');
+        }
+        Filter::ArrayConstruct(inner) => {
+            out.push('[');
+            if !matches!(**inner, Filter::Builtin(ref name, ref args) if name == "empty" && args.is_empty())
+            {
+                render(inner, out);
+            }
+            out.push(']');
+        }
+        Filter::Literal(v) => out.push_str(&crate::output::format_compact(v)),
+        Filter::Compare(a, op, b) => {
+            let op = match op {
+                CmpOp::Eq => "==",
+                CmpOp::Ne => "!=",
+                CmpOp::Lt => "<",
+                CmpOp::Le => "<=",
+                CmpOp::Gt => ">",
+                CmpOp::Ge => ">=",
+            };
+            render_binary(a, op, b, (6, 6), out);
+        }
+        Filter::Arith(a, op, b) => {
+            let p = precedence(filter);
+            let op = match op {
+                ArithOp::Add => "+",
+                ArithOp::Sub => "-",
+                ArithOp::Mul => "*",
+                ArithOp::Div => "/",
+                ArithOp::Mod => "%",
+            };
+            render_binary(a, op, b, (p, p + 1), out);
+        }
+        Filter::Comma(items) => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                render_operand(item, 2, out);
+            }
+        }
+        Filter::Recurse => out.push_str(".."),
+        Filter::Builtin(name, args) => {
+            out.push_str(name);
+            if !args.is_empty() {
+                render_args(args, out);
+            }
+        }
+        Filter::Not(inner) => {
+            if **inner != Filter::Identity {
+                render(inner, out);
+                out.push_str(" | ");
+            }
+            out.push_str("not");
+        }
+        Filter::BoolOp(a, op, b) => {
+            let p = precedence(filter);
+            let op = match op {
+                BoolOp::And => "and",
+                BoolOp::Or => "or",
+            };
+            render_binary(a, op, b, (p, p + 1), out);
+        }
+        Filter::IfThenElse(cond, then, els) => {
+            out.push_str("if ");
+            render(cond, out);
+            out.push_str(" then ");
+            render(then, out);
+            if let Some(els) = els {
+                out.push_str(" else ");
+                render(els, out);
+            }
+            out.push_str(" end");
+        }
+        Filter::Alternative(a, b) => render_binary(a, "//", b, (3, 2), out),
+        Filter::Try(inner) => {
+            render_operand(inner, TERM, out);
+            out.push('?');
+        }
+        Filter::StringInterp(parts) => {
+            out.push('"');
+            for part in parts {
+                match part {
+                    StringPart::Lit(text) => {
+                        let quoted = crate::output::format_compact(&Value::String(text.clone()));
+                        out.push_str(&quoted[1..quoted.len() - 1]);
+                    }
+                    StringPart::Expr(expr) => {
+                        out.push_str("\\(");
+                        render(expr, out);
+                        out.push(')');
+                    }
+                }
+            }
+            out.push('"');
+        }
+        Filter::Neg(inner) => {
+            out.push('-');
+            render_operand(inner, TERM, out);
+        }
+        Filter::TryCatch(body, handler) => {
+            out.push_str("try ");
+            render_operand(body, TERM, out);
+            out.push_str(" catch ");
+            render_operand(handler, TERM, out);
+        }
+        Filter::Slice(start, end) => {
+            out.push_str(".[");
+            render_slice(start.as_deref(), end.as_deref(), out);
+        }
+        Filter::Var(name) => out.push_str(name),
+        Filter::Bind(source, pattern, body) => {
+            render_operand(source, TERM, out);
+            out.push_str(" as ");
+            render_pattern(pattern, out);
+            out.push_str(" | ");
+            render(body, out);
+        }
+        Filter::Reduce(source, pattern, init, update) => {
+            out.push_str("reduce ");
+            render_operand(source, TERM, out);
+            out.push_str(" as ");
+            render_pattern(pattern, out);
+            out.push_str(" (");
+            render(init, out);
+            out.push_str("; ");
+            render(update, out);
+            out.push(')');
+        }
+        Filter::Foreach(source, pattern, init, update, extract) => {
+            out.push_str("foreach ");
+            render_operand(source, TERM, out);
+            out.push_str(" as ");
+            render_pattern(pattern, out);
+            out.push_str(" (");
+            render(init, out);
+            out.push_str("; ");
+            render(update, out);
+            if let Some(extract) = extract {
+                out.push_str("; ");
+                render(extract, out);
+            }
+            out.push(')');
+        }
+        Filter::Assign(path, op, value) => {
+            let op = match op {
+                AssignOp::Update => "|=",
+                AssignOp::Set => "=",
+                AssignOp::Add => "+=",
+                AssignOp::Sub => "-=",
+                AssignOp::Mul => "*=",
+                AssignOp::Div => "/=",
+                AssignOp::Mod => "%=",
+                AssignOp::Alt => "//=",
+            };
+            render_binary(path, op, value, (3, 2), out);
+        }
+        Filter::Def {
+            name, params, rest, ..
+        } => {
+            out.push_str("def ");
+            out.push_str(name);
+            if !params.is_empty() {
+                let _ = write!(out, "({})", params.join("; "));
+            }
+            out.push_str(": ...; ");
+            render(rest, out);
+        }
+        Filter::AltBind(source, patterns, body) => {
+            render_operand(source, TERM, out);
+            out.push_str(" as ");
+            for (i, pattern) in patterns.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(" ?// ");
+                }
+                render_pattern(pattern, out);
+            }
+            out.push_str(" | ");
+            render(body, out);
+        }
+        Filter::Label(name, body) => {
+            let _ = write!(out, "label {name} | ");
+            render(body, out);
+        }
+        Filter::Break(name) => {
+            let _ = write!(out, "break {name}");
+        }
+        Filter::PostfixIndex(base, idx) => {
+            render_operand(base, TERM, out);
+            out.push('[');
+            render(idx, out);
+            out.push(']');
+        }
+        Filter::PostfixSlice(base, start, end) => {
+            render_operand(base, TERM, out);
+            out.push('[');
+            render_slice(start.as_deref(), end.as_deref(), out);
+        }
+        Filter::Import {
+            path, alias, rest, ..
+        } => {
+            let _ = write!(out, "import {path:?} as {alias}; ");
+            render(rest, out);
+        }
+        Filter::Include { path, rest, .. } => {
+            let _ = write!(out, "include {path:?}; ");
+            render(rest, out);
+        }
+        Filter::ModuleDecl { rest, .. } => {
+            out.push_str("module {...}; ");
+            render(rest, out);
+        }
+    }
+}
+
+/// Render `a op b`, each side parenthesized below its minimum precedence.
+fn render_binary(a: &Filter, op: &str, b: &Filter, mins: (u8, u8), out: &mut String) {
+    let (left_min, right_min) = mins;
+    render_operand(a, left_min, out);
+    let _ = write!(out, " {op} ");
+    render_operand(b, right_min, out);
+}
+
+/// Render `s:e]` (the opening bracket is already written).
+fn render_slice(start: Option<&Filter>, end: Option<&Filter>, out: &mut String) {
+    if let Some(start) = start {
+        render(start, out);
+    }
+    out.push(':');
+    if let Some(end) = end {
+        render(end, out);
+    }
+    out.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::parse;
+
+    fn round_trip(src: &str) -> String {
+        snippet(&parse(src).unwrap())
+    }
+
+    #[test]
+    fn snippets_read_like_jq() {
+        assert_eq!(round_trip(".a.b"), ".a | .b");
+        assert_eq!(round_trip(r#"."x y""#), r#"."x y""#);
+        assert_eq!(
+            round_trip("[.[] | select(.n > 1)]"),
+            "[.[] | select(.n > 1)]"
+        );
+        assert_eq!(round_trip("(1 + 2) * 3"), "(1 + 2) * 3");
+        assert_eq!(round_trip("1 - (2 - 3)"), "1 - (2 - 3)");
+        assert_eq!(round_trip(".a % 10 == 0 and .b"), ".a % 10 == 0 and .b");
+        assert_eq!(round_trip("(.a // .b) // 1"), "(.a // .b) // 1");
+        assert_eq!(round_trip("[(.a | .b), 1]"), "[(.a | .b), 1]");
+        assert_eq!(round_trip("{a: .x, (.k): 1}"), "{a: .x, (.k): 1}");
+        assert_eq!(
+            round_trip("reduce .[] as $x (0; . + $x)"),
+            "reduce .[] as $x (0; . + $x)"
+        );
+        assert_eq!(round_trip(r#""v\(.a)""#), r#""v\(.a)""#);
+        assert_eq!(round_trip("test(\"a\"; \"i\")"), "test(\"a\"; \"i\")");
+    }
+
+    #[test]
+    fn long_snippets_are_shortened() {
+        let src = format!(".{}", "a".repeat(100));
+        let s = round_trip(&src);
+        assert_eq!(s.chars().count(), SNIPPET_MAX_CHARS);
+        assert!(s.ends_with("..."));
+    }
+}
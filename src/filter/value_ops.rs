@@ -2,6 +2,7 @@
 /// and pure value-manipulation functions used by both eval.rs and builtins.
 use crate::filter::{ArithOp, CmpOp, Env, Filter};
 use crate::value::Value;
+use crate::value::bigint::{self, BigInt};
 use std::sync::Arc;
 
 use super::eval::eval;
@@ -100,8 +101,14 @@ pub(super) fn todate(secs: i64) -> Option<String> {
 
 pub(super) fn fromdate(s: &str) -> Option<i64> {
     // Try Timestamp first (handles "2024-01-15T11:30:45Z" etc)
+    // Fractional seconds are dropped (rounding down, like jq's mktime).
     if let Ok(ts) = s.parse::<Timestamp>() {
-        return Some(ts.as_second());
+        let secs = ts.as_second();
+        return Some(if ts.subsec_nanosecond() < 0 {
+            secs - 1
+        } else {
+            secs
+        });
     }
     // Fallback: try parsing as civil datetime and assume UTC
     if let Ok(dt) = s.parse::<jiff::civil::DateTime>() {
@@ -118,28 +125,48 @@ pub(super) fn now_timestamp() -> f64 {
     ts.as_second() as f64 + ts.subsec_nanosecond() as f64 / 1_000_000_000.0
 }
 
+/// Format with jiff, spelling out the conversions whose C-locale output
+/// (what jq prints) differs from jiff's. Returns `None` for conversions jiff
+/// cannot format rather than panicking in `Display`.
+fn strftime_zoned(zdt: &jiff::Zoned, fmt: &str) -> Option<String> {
+    let mut expanded = String::with_capacity(fmt.len());
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('c') => expanded.push_str("%a %b %e %H:%M:%S %Y"),
+            Some('x') => expanded.push_str("%m/%d/%y"),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    jiff::fmt::strtime::format(&expanded, zdt).ok()
+}
+
 pub(super) fn format_strftime_jiff(fmt: &str, secs: i64) -> Option<String> {
     let ts = Timestamp::from_second(secs).ok()?;
-    Some(
-        ts.to_zoned(jiff::tz::TimeZone::UTC)
-            .strftime(fmt)
-            .to_string(),
-    )
+    strftime_zoned(&ts.to_zoned(jiff::tz::TimeZone::UTC), fmt)
 }
 
 pub(super) fn format_strftime_local(fmt: &str, secs: i64) -> Option<String> {
     let ts = Timestamp::from_second(secs).ok()?;
-    Some(
-        ts.to_zoned(jiff::tz::TimeZone::system())
-            .strftime(fmt)
-            .to_string(),
-    )
+    strftime_zoned(&ts.to_zoned(jiff::tz::TimeZone::system()), fmt)
 }
 
 /// Convert epoch seconds → jq broken-down time array (UTC).
 /// `[year, month(0-11), day(1-31), hour, min, sec, weekday(0-6 Sun=0), yearday(0-365)]`
+///
+/// Like jq, the epoch is truncated toward zero and the fractional part
+/// (`secs - floor(secs)`) is added back onto the seconds element.
 pub(super) fn epoch_to_bdtime(secs: f64, utc: bool) -> Option<Value> {
     let whole = secs as i64;
+    let frac = secs - secs.floor();
     let ts = Timestamp::from_second(whole).ok()?;
     let tz = if utc {
         jiff::tz::TimeZone::UTC
@@ -154,7 +181,11 @@ pub(super) fn epoch_to_bdtime(secs: f64, utc: bool) -> Option<Value> {
     let day = dt.day() as i64;
     let hour = dt.hour() as i64;
     let min = dt.minute() as i64;
-    let sec = dt.second() as i64;
+    let sec = if frac > 0.0 {
+        Value::Double(dt.second() as f64 + frac, None)
+    } else {
+        Value::Int(dt.second() as i64)
+    };
     // jq weekday: 0=Sunday. jiff: Monday=1..Sunday=7
     let wday = zdt.weekday().to_sunday_zero_offset() as i64;
     // yearday: 0-based day of year
@@ -165,52 +196,73 @@ pub(super) fn epoch_to_bdtime(secs: f64, utc: bool) -> Option<Value> {
         Value::Int(day),
         Value::Int(hour),
         Value::Int(min),
-        Value::Int(sec),
+        sec,
         Value::Int(wday),
         Value::Int(yday),
     ])))
 }
 
-/// Convert jq broken-down time array → epoch seconds (UTC).
+/// Days from 1970-01-01 to the given proleptic Gregorian date (month 1-12).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Convert jq broken-down time array → epoch seconds, treating the fields as UTC.
+///
+/// Like jq (and `timegm`), this needs at least the 8 numeric fields `gmtime`
+/// produces, ignores weekday/yearday, drops fractional seconds and normalizes
+/// out-of-range fields (day 0 is the last day of the previous month).
 pub(super) fn bdtime_to_epoch(arr: &[Value]) -> Option<i64> {
-    // Validate that all elements are numeric (jq rejects non-numeric components)
-    for v in arr.iter().take(8) {
-        match v {
-            Value::Int(_) | Value::Double(_, _) => {}
+    if arr.len() < 8 {
+        return None;
+    }
+    let mut fields = [0i64; 6];
+    for (field, v) in fields.iter_mut().zip(arr) {
+        *field = match v {
+            Value::Int(n) => *n,
+            Value::Double(f, _) if f.is_finite() => *f as i64,
             _ => return None,
-        }
+        };
     }
-    let get_i = |idx: usize| -> i64 {
-        arr.get(idx)
-            .map(|v| match v {
-                Value::Int(n) => *n,
-                Value::Double(f, _) => *f as i64,
-                _ => 0,
-            })
-            .unwrap_or(0)
-    };
-    let year = get_i(0) as i16;
-    let month = (get_i(1) + 1) as i8; // jq is 0-indexed
-    let day = get_i(2) as i8;
-    let hour = get_i(3) as i8;
-    let min = get_i(4) as i8;
-    let sec = get_i(5) as i8;
-    let dt = jiff::civil::DateTime::new(year, month, day, hour, min, sec, 0).ok()?;
-    let zdt = dt.to_zoned(jiff::tz::TimeZone::UTC).ok()?;
-    Some(zdt.timestamp().as_second())
+    if !arr[6..8]
+        .iter()
+        .all(|v| matches!(v, Value::Int(_) | Value::Double(..)))
+    {
+        return None;
+    }
+    let [year, month, day, hour, min, sec] = fields;
+    let year = year.checked_add(month.div_euclid(12))?;
+    // Keep the day arithmetic well inside i64 (and jiff's range downstream).
+    if year.abs() > 1_000_000 || day.abs() > 1_000_000_000 {
+        return None;
+    }
+    let days = days_from_civil(year, month.rem_euclid(12) + 1, 1) + day - 1;
+    days.checked_mul(86400)?
+        .checked_add(hour.checked_mul(3600)?)?
+        .checked_add(min.checked_mul(60)?)?
+        .checked_add(sec)
 }
 
-/// Format a broken-down time array using strftime with the given timezone.
+/// Format a broken-down time array using strftime. The fields are wall-clock
+/// time: UTC for `strftime`, the system zone for `strflocaltime`.
 pub(super) fn bdtime_strftime(arr: &[Value], fmt: &str, utc: bool) -> Option<String> {
     let epoch = bdtime_to_epoch(arr)?;
-    let ts = Timestamp::from_second(epoch).ok()?;
+    let civil = Timestamp::from_second(epoch)
+        .ok()?
+        .to_zoned(jiff::tz::TimeZone::UTC)
+        .datetime();
     let tz = if utc {
         jiff::tz::TimeZone::UTC
     } else {
         jiff::tz::TimeZone::system()
     };
-    let zdt = ts.to_zoned(tz);
-    Some(zdt.strftime(fmt).to_string())
+    strftime_zoned(&civil.to_zoned(tz).ok()?, fmt)
 }
 
 /// Parse a datetime string using strptime format → broken-down time array.
@@ -235,11 +287,28 @@ pub(super) fn strptime_to_bdtime(s: &str, fmt: &str) -> Option<Value> {
 // Path operations
 // ---------------------------------------------------------------------------
 
-/// Maximum path depth for set_path/del_path recursion to prevent stack overflow.
+/// Maximum path depth for set_path/delete_paths recursion to prevent stack overflow.
 const MAX_PATH_DEPTH: usize = 1000;
 /// Maximum array index that set_path will allocate when creating from null.
 const MAX_ARRAY_ALLOC: i64 = 1_000_000;
 
+/// jq's error for looking up `key` in `container`, as `.[key]` raises it.
+/// Only string keys are shown, so `"0"` and `0` can be told apart.
+pub(super) fn index_error(container: &Value, key: &Value) -> String {
+    match key {
+        Value::String(s) => format!(
+            "Cannot index {} with string \"{}\"",
+            container.type_name(),
+            s
+        ),
+        _ => format!(
+            "Cannot index {} with {}",
+            container.type_name(),
+            key.type_name()
+        ),
+    }
+}
+
 pub(super) fn set_path(value: &Value, path: &[Value], new_val: &Value) -> Result<Value, String> {
     if path.len() > MAX_PATH_DEPTH {
         return Err("Path too deep".to_string());
@@ -262,7 +331,11 @@ pub(super) fn set_path(value: &Value, path: &[Value], new_val: &Value) -> Result
         (Value::Array(arr), Value::Int(i)) => {
             let mut result = arr.as_ref().clone();
             let idx = if *i < 0 {
-                (result.len() as i64 + i).max(0) as usize
+                let resolved = result.len() as i64 + i;
+                if resolved < 0 {
+                    return Err("Out of bounds negative array index".to_string());
+                }
+                resolved as usize
             } else {
                 *i as usize
             };
@@ -288,96 +361,205 @@ pub(super) fn set_path(value: &Value, path: &[Value], new_val: &Value) -> Result
             arr[idx] = set_path(&Value::Null, rest, new_val)?;
             Ok(Value::Array(Arc::new(arr)))
         }
+        // A `{"start","end"}` key replaces that slice of an array; null
+        // becomes an empty array first.
+        (Value::Array(_) | Value::Null, Value::Object(_)) => {
+            let arr: &[Value] = match value {
+                Value::Array(arr) => arr,
+                _ => &[],
+            };
+            let (start, end) = slice_key_bounds(seg, arr.len())?;
+            let slice = Value::Array(Arc::new(arr[start..end].to_vec()));
+            let Value::Array(replacement) = set_path(&slice, rest, new_val)? else {
+                return Err("A slice of an array can only be assigned another array".to_string());
+            };
+            let mut result = arr[..start].to_vec();
+            result.extend_from_slice(&replacement);
+            result.extend_from_slice(&arr[end..]);
+            Ok(Value::Array(Arc::new(result)))
+        }
+        (Value::String(_), Value::Object(_)) => {
+            Err("Cannot update field at object index of string".to_string())
+        }
         // Type mismatch errors
-        (Value::Object(_), Value::Int(_)) => Err("Cannot index object with number".to_string()),
-        (Value::Array(_), Value::String(_)) => Err("Cannot index array with string".to_string()),
+        (Value::Object(_) | Value::Array(_), Value::Int(_) | Value::String(_)) => {
+            Err(index_error(value, seg))
+        }
         (_, Value::Array(_)) => Err("Cannot update field at array index of array".to_string()),
-        (_, Value::String(s)) => Err(format!(
-            "Cannot index {} with string \"{}\"",
-            value.type_name(),
-            s
-        )),
-        _ => Err(format!(
-            "Cannot index {} with {}",
-            value.type_name(),
-            seg.type_name()
-        )),
+        _ => Err(index_error(value, seg)),
     }
 }
 
-pub(super) fn del_path(value: &Value, path: &[Value]) -> Value {
-    if path.is_empty() {
-        return Value::Null;
+/// Delete every path in `paths` from `value`, as jq's `delpaths` does: the
+/// paths are sorted and grouped by their first key, and each container drops
+/// all of its doomed keys at once, so no deletion shifts the indices another
+/// one refers to. Array keys may be negative or `{"start","end"}` slices.
+pub(super) fn delete_paths(value: &Value, mut paths: Vec<Vec<Value>>) -> Result<Value, String> {
+    if paths.iter().any(Vec::is_empty) {
+        return Ok(Value::Null);
     }
-    if path.len() > MAX_PATH_DEPTH {
-        return value.clone(); // Too deep — no-op rather than stack overflow
+    paths.sort_by(|a, b| path_order(a, b));
+    delete_sorted_paths(value, &paths, 0)
+}
+
+/// The order `values_order` gives two path arrays, without wrapping them.
+fn path_order(a: &[Value], b: &[Value]) -> std::cmp::Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| values_order(x, y).unwrap_or(std::cmp::Ordering::Equal))
+        .find(|ord| ord.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// `paths` are sorted, and each is longer than `depth`.
+fn delete_sorted_paths(value: &Value, paths: &[Vec<Value>], depth: usize) -> Result<Value, String> {
+    if depth > MAX_PATH_DEPTH {
+        return Err("Path too deep".to_string());
     }
-    if path.len() == 1 {
-        match (value, &path[0]) {
-            (Value::Object(obj), Value::String(k)) => {
-                let result: Vec<_> = obj.iter().filter(|(ek, _)| ek != k).cloned().collect();
-                return Value::Object(Arc::new(result));
-            }
-            (Value::Array(arr), Value::Int(i)) => {
-                let resolved = if *i < 0 { arr.len() as i64 + i } else { *i };
-                if resolved < 0 || resolved as usize >= arr.len() {
-                    return value.clone(); // out of bounds: no-op
-                }
-                let idx = resolved as usize;
-                let mut result = arr.as_ref().clone();
-                result.remove(idx);
-                return Value::Array(Arc::new(result));
+    let mut result = value.clone();
+    let mut doomed = Vec::new();
+    let mut i = 0;
+    while i < paths.len() {
+        let key = &paths[i][depth];
+        let group_len = paths[i..]
+            .iter()
+            .take_while(|p| values_equal(&p[depth], key))
+            .count();
+        // Sorting puts `[k]` before `[k, ...]`: deleting k covers the rest
+        if paths[i].len() == depth + 1 {
+            doomed.push(key.clone());
+        } else {
+            // Below a slice key, the slice is written back with its
+            // deletions, so `del(.[1:3][0])` removes the element at 1.
+            let child = get_path(&result, std::slice::from_ref(key))?;
+            if !matches!(child, Value::Null) {
+                let updated = delete_sorted_paths(&child, &paths[i..i + group_len], depth + 1)?;
+                result = set_path(&result, std::slice::from_ref(key), &updated)?;
             }
-            _ => return value.clone(),
         }
+        i += group_len;
     }
-    let seg = &path[0];
-    let rest = &path[1..];
-    match (value, seg) {
-        (Value::Object(obj), Value::String(k)) => {
-            let mut result: Vec<(String, Value)> = obj.as_ref().clone();
-            if let Some(existing) = result.iter_mut().find(|(ek, _)| ek == k) {
-                existing.1 = del_path(&existing.1, rest);
+    delete_keys(result, &doomed)
+}
+
+/// Remove `keys` from one container, all at once.
+fn delete_keys(value: Value, keys: &[Value]) -> Result<Value, String> {
+    if keys.is_empty() {
+        return Ok(value);
+    }
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Array(arr) => {
+            let len = arr.len();
+            let mut doomed = vec![false; len];
+            for key in keys {
+                match key {
+                    Value::Int(_) | Value::Double(..) => {
+                        let i = match key {
+                            Value::Int(n) => *n,
+                            _ => to_f64(key) as i64,
+                        };
+                        let i = if i < 0 { i + len as i64 } else { i };
+                        if (0..len as i64).contains(&i) {
+                            doomed[i as usize] = true;
+                        }
+                    }
+                    Value::Object(_) => {
+                        let (start, end) = slice_key_bounds(key, len)?;
+                        doomed[start..end].fill(true);
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Cannot delete {} element of array",
+                            key.type_name()
+                        ));
+                    }
+                }
             }
-            Value::Object(Arc::new(result))
+            let kept = arr
+                .iter()
+                .zip(doomed)
+                .filter(|(_, gone)| !gone)
+                .map(|(v, _)| v.clone())
+                .collect();
+            Ok(Value::Array(Arc::new(kept)))
         }
-        (Value::Array(arr), Value::Int(i)) => {
-            let idx = if *i < 0 {
-                (arr.len() as i64 + i).max(0) as usize
-            } else {
-                *i as usize
-            };
-            let mut result = arr.as_ref().clone();
-            if idx < result.len() {
-                result[idx] = del_path(&result[idx], rest);
+        Value::Object(obj) => {
+            if let Some(key) = keys.iter().find(|k| !matches!(k, Value::String(_))) {
+                return Err(format!("Cannot delete {} field of object", key.type_name()));
             }
-            Value::Array(Arc::new(result))
+            let kept = obj
+                .iter()
+                .filter(|(k, _)| !keys.iter().any(|d| matches!(d, Value::String(s) if s == k)))
+                .cloned()
+                .collect();
+            Ok(Value::Object(Arc::new(kept)))
         }
-        _ => value.clone(),
+        other => Err(format!("Cannot delete fields from {}", other.type_name())),
     }
 }
 
-pub(super) fn get_path(value: &Value, path: &[Value]) -> Value {
+/// Resolve a `{"start": s, "end": e}` path key against an array length the
+/// way jq resolves slices: negatives count from the end, null means open,
+/// and a fractional end rounds up.
+fn slice_key_bounds(key: &Value, len: usize) -> Result<(usize, usize), String> {
+    let Value::Object(obj) = key else {
+        unreachable!("slice keys are objects")
+    };
+    let bound = |name: &str, open: f64| match obj.iter().find(|(k, _)| k == name) {
+        Some((_, Value::Null)) => Ok(open),
+        Some((_, v @ (Value::Int(_) | Value::Double(..)))) => Ok(to_f64(v)),
+        _ => Err("Start and end indices of an array slice must be numbers".to_string()),
+    };
+    let len_f = len as f64;
+    let (mut start, mut end) = (bound("start", 0.0)?, bound("end", len_f)?);
+    if start < 0.0 {
+        start += len_f;
+    }
+    if end < 0.0 {
+        end += len_f;
+    }
+    let start = start.clamp(0.0, len_f);
+    let end = end.clamp(start, len_f);
+    Ok((start as usize, end.ceil() as usize))
+}
+
+/// Follow `path` into `value` the way `getpath` does. Strings only index
+/// objects and numbers only index arrays, so a key such as `"0"` never
+/// reaches an array element; a mismatch is jq's indexing error. Anything
+/// below a `null` is `null`.
+pub(super) fn get_path(value: &Value, path: &[Value]) -> Result<Value, String> {
     let mut current = value.clone();
     for seg in path {
         current = match (&current, seg) {
+            (Value::Null, _) => return Ok(Value::Null),
             (Value::Object(obj), Value::String(k)) => obj
                 .iter()
                 .find(|(ek, _)| ek == k)
                 .map(|(_, v)| v.clone())
                 .unwrap_or(Value::Null),
-            (Value::Array(arr), Value::Int(i)) => {
-                let idx = if *i < 0 { arr.len() as i64 + i } else { *i };
+            (Value::Array(arr), Value::Int(_) | Value::Double(..)) => {
+                let i = match seg {
+                    Value::Int(n) => *n,
+                    _ => to_f64(seg) as i64,
+                };
+                let idx = if i < 0 { arr.len() as i64 + i } else { i };
                 if idx >= 0 && (idx as usize) < arr.len() {
                     arr[idx as usize].clone()
                 } else {
                     Value::Null
                 }
             }
-            _ => Value::Null,
+            (Value::Array(arr), Value::Object(_)) => {
+                let (start, end) = slice_key_bounds(seg, arr.len())?;
+                Value::Array(Arc::new(arr[start..end].to_vec()))
+            }
+            // Array keys look up sub-array positions, which paths never use.
+            (Value::Array(_), Value::Array(_)) => Value::Null,
+            _ => return Err(index_error(&current, seg)),
         };
     }
-    current
+    Ok(current)
 }
 
 pub(super) fn enum_paths(
@@ -388,6 +570,8 @@ pub(super) fn enum_paths(
 ) {
     let env = Env::empty();
     match filter {
+        // Like `paths`, `paths(f)` never includes the root.
+        Some(_) if current.is_empty() => {}
         Some(f) => {
             let mut is_match = false;
             eval(f, value, &env, &mut |v| {
@@ -448,6 +632,8 @@ pub(super) fn enum_leaf_paths(
                 current.pop();
             }
         }
+        // `leaf_paths` is `paths(scalars)`, which never includes the root.
+        _ if current.is_empty() => {}
         _ => {
             output(Value::Array(Arc::new(current.clone())));
         }
@@ -466,59 +652,81 @@ pub(super) fn path_of_env(
 ) {
     match filter {
         Filter::Field(name) => {
+            if !matches!(input, Value::Object(_) | Value::Null) {
+                env.set_error(Value::String(format!(
+                    "Cannot index {} with string \"{}\"",
+                    input.type_name(),
+                    name
+                )));
+                return;
+            }
             current.push(Value::String(name.clone()));
             output(Value::Array(Arc::new(current.clone())));
             current.pop();
         }
+        Filter::FieldPath(names) => {
+            path_of_env(&super::field_path_pipe(names), input, current, env, output);
+        }
         Filter::Index(idx_f) => {
             eval(idx_f, input, env, &mut |idx| {
+                let indexable = matches!(
+                    (input, &idx),
+                    (Value::Null, _)
+                        | (Value::Object(_), Value::String(_))
+                        | (
+                            Value::Array(_),
+                            Value::Int(_) | Value::Double(..) | Value::Array(_) | Value::Object(_)
+                        )
+                );
+                if !indexable {
+                    let idx_desc = match &idx {
+                        Value::String(s) => format!("string \"{s}\""),
+                        _ => idx.type_name().to_string(),
+                    };
+                    env.set_error(Value::String(format!(
+                        "Cannot index {} with {}",
+                        input.type_name(),
+                        idx_desc
+                    )));
+                    return;
+                }
                 current.push(idx);
                 output(Value::Array(Arc::new(current.clone())));
                 current.pop();
             });
         }
         Filter::Slice(s_expr, e_expr) => {
-            if let Value::Array(arr) = input {
-                let len = arr.len() as i64;
-                let val_to_i64 = |v: &Value| -> Option<i64> {
-                    match v {
-                        Value::Int(n) => Some(*n),
-                        Value::Double(f, _) if f.is_finite() => Some(*f as i64),
-                        _ => None,
-                    }
-                };
-                let s = s_expr
-                    .as_ref()
-                    .map(|sf| {
-                        let mut v = 0i64;
-                        eval(sf, input, env, &mut |sv| {
-                            if let Some(n) = val_to_i64(&sv) {
-                                v = n;
-                            }
-                        });
-                        if v < 0 { (len + v).max(0) } else { v.min(len) }
-                    })
-                    .unwrap_or(0) as usize;
-                let e = e_expr
-                    .as_ref()
-                    .map(|ef| {
-                        let mut v = len;
-                        eval(ef, input, env, &mut |ev| {
-                            if let Some(n) = val_to_i64(&ev) {
-                                v = n;
-                            }
-                        });
-                        if v < 0 { (len + v).max(0) } else { v.min(len) }
-                    })
-                    .unwrap_or(len) as usize;
-                let start = s.min(arr.len());
-                let end = e.min(arr.len());
-                for i in start..end {
-                    current.push(Value::Int(i as i64));
-                    output(Value::Array(Arc::new(current.clone())));
-                    current.pop();
+            // The path of a slice is a `{"start","end"}` key, as in jq, so
+            // `getpath`, `setpath` and `delpaths` see the slice, not its
+            // elements. Bounds are kept as given; null means open.
+            if !matches!(input, Value::Array(_) | Value::String(_) | Value::Null) {
+                env.set_error(Value::String(format!(
+                    "Cannot index {} with object",
+                    input.type_name()
+                )));
+                return;
+            }
+            let bound = |f: &Option<Box<Filter>>| {
+                let mut last = Value::Null;
+                if let Some(f) = f {
+                    eval(f, input, env, &mut |v| last = v);
                 }
+                last
+            };
+            let (start, end) = (bound(s_expr), bound(e_expr));
+            let is_bound = |v: &Value| matches!(v, Value::Null | Value::Int(_) | Value::Double(..));
+            if !is_bound(&start) || !is_bound(&end) {
+                env.set_error(Value::String(
+                    "Start and end indices of an array slice must be numbers".to_string(),
+                ));
+                return;
             }
+            current.push(Value::Object(Arc::new(vec![
+                ("start".to_string(), start),
+                ("end".to_string(), end),
+            ])));
+            output(Value::Array(Arc::new(current.clone())));
+            current.pop();
         }
         Filter::Iterate => match input {
             Value::Array(arr) => {
@@ -535,7 +743,13 @@ pub(super) fn path_of_env(
                     current.pop();
                 }
             }
-            _ => {}
+            _ => {
+                env.set_error(Value::String(format!(
+                    "Cannot iterate over {} ({})",
+                    input.type_name(),
+                    input.short_desc()
+                )));
+            }
         },
         Filter::Pipe(a, b) => {
             let saved_len = current.len();
@@ -546,11 +760,11 @@ pub(super) fn path_of_env(
                     lhs_paths.push(arr.as_ref().clone());
                 }
             });
-            if lhs_paths.is_empty() && super::eval::has_last_error() {
+            if lhs_paths.is_empty() && env.has_error() {
                 // LHS was an invalid path expression. Check if the error is
                 // already specific ("near attempt to...") — if so, keep it.
                 // Otherwise, refine it based on what RHS was trying to do.
-                let existing = super::eval::take_last_error();
+                let existing = env.take_error();
                 let already_specific = existing
                     .as_ref()
                     .and_then(|e| {
@@ -564,14 +778,23 @@ pub(super) fn path_of_env(
                 if already_specific {
                     // Preserve the more specific inner error
                     if let Some(err) = existing {
-                        super::eval::set_last_error(err);
+                        env.set_error(err);
                     }
                 } else {
                     let mut lhs_vals = Vec::new();
                     eval(a, input, env, &mut |v| lhs_vals.push(v));
                     if let Some(val) = lhs_vals.first() {
                         let formatted = crate::output::format_compact(val);
-                        let msg = match b.as_ref() {
+                        // `1 | .a.b` fails at its first step, `.a`
+                        let mut step = b.as_ref();
+                        while let Filter::Pipe(first, _) = step {
+                            step = first;
+                        }
+                        let msg = match step {
+                            Filter::FieldPath(names) if !names.is_empty() => format!(
+                                "Invalid path expression near attempt to access element \"{}\" of {}",
+                                names[0], formatted
+                            ),
                             Filter::Field(name) => format!(
                                 "Invalid path expression near attempt to access element \"{}\" of {}",
                                 name, formatted
@@ -595,15 +818,17 @@ pub(super) fn path_of_env(
                             ),
                             _ => format!("Invalid path expression with result {}", formatted),
                         };
-                        super::eval::set_last_error(Value::String(msg));
+                        env.set_error(Value::String(msg));
                     }
                 }
             } else {
                 for lhs_path in &lhs_paths {
                     current.truncate(saved_len);
                     current.extend_from_slice(lhs_path);
-                    let next = get_path(input, lhs_path);
-                    path_of_env(b, &next, current, env, output);
+                    match get_path(input, lhs_path) {
+                        Ok(next) => path_of_env(b, &next, current, env, output),
+                        Err(msg) => env.set_error(Value::String(msg)),
+                    }
                 }
             }
             current.truncate(saved_len);
@@ -627,6 +852,44 @@ pub(super) fn path_of_env(
                 path_of_env(item, input, current, env, output);
             }
         }
+        Filter::Try(inner) => {
+            // An error ends the path expression quietly; paths already
+            // produced stand
+            path_of_env(inner, input, current, env, output);
+            env.take_error();
+        }
+        Filter::IfThenElse(cond, then_f, else_f) => {
+            eval(cond, input, env, &mut |c| match (c.is_truthy(), else_f) {
+                (true, _) => path_of_env(then_f, input, current, env, output),
+                (false, Some(else_f)) => path_of_env(else_f, input, current, env, output),
+                (false, None) => output(Value::Array(Arc::new(current.clone()))),
+            });
+        }
+        Filter::Alternative(left, right) => {
+            // Paths of `left` whose values are truthy, else paths of `right`
+            let base = current.len();
+            let mut truthy = Vec::new();
+            path_of_env(left, input, current, env, &mut |p| {
+                if let Value::Array(arr) = &p
+                    && get_path(input, &arr[base..]).is_ok_and(|v| v.is_truthy())
+                {
+                    truthy.push(p);
+                }
+            });
+            env.take_error();
+            if truthy.is_empty() {
+                path_of_env(right, input, current, env, output);
+            } else {
+                truthy.into_iter().for_each(output);
+            }
+        }
+        Filter::Bind(expr, pattern, body) => {
+            eval(expr, input, env, &mut |val| {
+                if let Some(new_env) = super::eval::match_pattern(pattern, &val, env) {
+                    path_of_env(body, input, current, &new_env, output);
+                }
+            });
+        }
         Filter::Recurse => {
             fn recurse_paths(
                 value: &Value,
@@ -655,12 +918,15 @@ pub(super) fn path_of_env(
             recurse_paths(input, current, output);
         }
         Filter::PostfixIndex(base, idx) => {
-            // PostfixIndex(base, idx) — same as Pipe(base, Index(idx)) for path purposes
-            let pipe = Filter::Pipe(base.clone(), Box::new(Filter::Index(idx.clone())));
+            // PostfixIndex(base, idx) — Pipe(base, Index(idx)) for path purposes,
+            // with idx evaluated against this input
+            let step = super::eval::postfix_index_step(idx, input, env);
+            let pipe = Filter::Pipe(base.clone(), Box::new(step));
             path_of_env(&pipe, input, current, env, output);
         }
         Filter::PostfixSlice(base, s, e) => {
-            let pipe = Filter::Pipe(base.clone(), Box::new(Filter::Slice(s.clone(), e.clone())));
+            let step = super::eval::postfix_slice_step(s, e, input, env);
+            let pipe = Filter::Pipe(base.clone(), Box::new(step));
             path_of_env(&pipe, input, current, env, output);
         }
         Filter::Builtin(name, args) if name == "getpath" && args.len() == 1 => {
@@ -675,6 +941,41 @@ pub(super) fn path_of_env(
                 }
             });
         }
+        Filter::Builtin(name, args) if name == "first" && args.len() == 1 => {
+            paths_until(&args[0], input, current, env, &mut |p| {
+                output(p);
+                false
+            });
+        }
+        Filter::Builtin(name, args) if name == "last" && args.len() == 1 => {
+            let mut last = None;
+            path_of_env(&args[0], input, current, env, &mut |p| last = Some(p));
+            if let Some(p) = last {
+                output(p);
+            }
+        }
+        Filter::Builtin(name, args) if name == "limit" && args.len() == 2 => {
+            eval(&args[0], input, env, &mut |n_val| {
+                let n = match n_val {
+                    Value::Int(n) => n as f64,
+                    Value::Double(f, _) => f,
+                    _ => 0.0,
+                };
+                if n < 0.0 {
+                    env.set_error(Value::String("limit doesn't support negative count".into()));
+                    return;
+                }
+                if n == 0.0 {
+                    return;
+                }
+                let mut count = 0.0;
+                paths_until(&args[1], input, current, env, &mut |p| {
+                    output(p);
+                    count += 1.0;
+                    count < n
+                });
+            });
+        }
         Filter::Builtin(name, args) if args.is_empty() => {
             match name.as_str() {
                 "first" => {
@@ -703,7 +1004,7 @@ pub(super) fn path_of_env(
                         eval(filter, input, env, &mut |v| results.push(v));
                         if let Some(val) = results.first() {
                             let formatted = crate::output::format_compact(val);
-                            super::eval::set_last_error(Value::String(format!(
+                            env.set_error(Value::String(format!(
                                 "Invalid path expression with result {}",
                                 formatted
                             )));
@@ -718,7 +1019,7 @@ pub(super) fn path_of_env(
             eval(filter, input, env, &mut |v| results.push(v));
             if let Some(val) = results.first() {
                 let formatted = crate::output::format_compact(val);
-                super::eval::set_last_error(Value::String(format!(
+                env.set_error(Value::String(format!(
                     "Invalid path expression with result {}",
                     formatted
                 )));
@@ -727,6 +1028,29 @@ pub(super) fn path_of_env(
     }
 }
 
+/// Paths of `f`, handed to `take` until it returns false; later paths are
+/// dropped. Like the `first`/`limit` builtins, an error raised after the
+/// last wanted path is cleared, since jq stops `f` before reaching it.
+fn paths_until(
+    f: &Filter,
+    input: &Value,
+    current: &mut Vec<Value>,
+    env: &Env,
+    take: &mut dyn FnMut(Value) -> bool,
+) {
+    let mut stopped = false;
+    let mut clean_when_stopped = false;
+    path_of_env(f, input, current, env, &mut |p| {
+        if !stopped {
+            clean_when_stopped = !env.has_error();
+            stopped = !take(p);
+        }
+    });
+    if stopped && clean_when_stopped {
+        env.take_error();
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Value comparison, ordering, arithmetic, and traversal
 // ---------------------------------------------------------------------------
@@ -753,6 +1077,11 @@ pub(super) fn values_equal(left: &Value, right: &Value) -> bool {
         (Value::Null, Value::Null) => true,
         (Value::Bool(a), Value::Bool(b)) => a == b,
         (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Double(..), _) | (_, Value::Double(..))
+            if let Some(ord) = bigint::exact_cmp(left, right) =>
+        {
+            ord == std::cmp::Ordering::Equal
+        }
         (Value::Double(a, _), Value::Double(b, _)) => a == b,
         (Value::Int(a), Value::Double(b, _)) => (*a as f64) == *b,
         (Value::Double(a, _), Value::Int(b)) => *a == (*b as f64),
@@ -762,16 +1091,35 @@ pub(super) fn values_equal(left: &Value, right: &Value) -> bool {
                 || (a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| values_equal(x, y)))
         }
         (Value::Object(a), Value::Object(b)) => {
-            Arc::ptr_eq(a, b)
-                || (a.len() == b.len()
-                    && a.iter()
-                        .zip(b.iter())
-                        .all(|((k1, v1), (k2, v2))| k1 == k2 && values_equal(v1, v2)))
+            if Arc::ptr_eq(a, b) {
+                return true;
+            }
+            if a.len() != b.len() {
+                return false;
+            }
+            // Key order doesn't matter; when it's the same there's no need to sort.
+            if a.iter().zip(b.iter()).all(|((k1, _), (k2, _))| k1 == k2) {
+                return a
+                    .iter()
+                    .zip(b.iter())
+                    .all(|((_, v1), (_, v2))| values_equal(v1, v2));
+            }
+            let (a, b) = (sorted_entries(a), sorted_entries(b));
+            a.iter()
+                .zip(b.iter())
+                .all(|((k1, v1), (k2, v2))| k1 == k2 && values_equal(v1, v2))
         }
         _ => false,
     }
 }
 
+/// An object's entries ordered by key, as jq compares objects.
+fn sorted_entries(obj: &[(String, Value)]) -> Vec<&(String, Value)> {
+    let mut entries: Vec<_> = obj.iter().collect();
+    entries.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    entries
+}
+
 /// jq total ordering: null < false < true < numbers < strings < arrays < objects
 fn type_order(v: &Value) -> u8 {
     match v {
@@ -785,6 +1133,18 @@ fn type_order(v: &Value) -> u8 {
     }
 }
 
+/// Total order on numbers as jq sorts them: NaN sorts below every other
+/// number (including -infinity) and NaNs are equal to each other, so sorting
+/// never sees an inconsistent comparison.
+fn f64_order(a: f64, b: f64) -> std::cmp::Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => std::cmp::Ordering::Equal,
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        (false, false) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+    }
+}
+
 pub fn values_order(left: &Value, right: &Value) -> Option<std::cmp::Ordering> {
     let lt = type_order(left);
     let rt = type_order(right);
@@ -795,9 +1155,14 @@ pub fn values_order(left: &Value, right: &Value) -> Option<std::cmp::Ordering> {
         (Value::Null, Value::Null) => Some(std::cmp::Ordering::Equal),
         (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
         (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
-        (Value::Double(a, _), Value::Double(b, _)) => a.partial_cmp(b),
-        (Value::Int(a), Value::Double(b, _)) => (*a as f64).partial_cmp(b),
-        (Value::Double(a, _), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::Double(..), _) | (_, Value::Double(..))
+            if let Some(ord) = bigint::exact_cmp(left, right) =>
+        {
+            Some(ord)
+        }
+        (Value::Double(a, _), Value::Double(b, _)) => Some(f64_order(*a, *b)),
+        (Value::Int(a), Value::Double(b, _)) => Some(f64_order(*a as f64, *b)),
+        (Value::Double(a, _), Value::Int(b)) => Some(f64_order(*a, *b as f64)),
         (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
         (Value::Array(a), Value::Array(b)) => {
             for (av, bv) in a.iter().zip(b.iter()) {
@@ -809,27 +1174,26 @@ pub fn values_order(left: &Value, right: &Value) -> Option<std::cmp::Ordering> {
             Some(a.len().cmp(&b.len()))
         }
         (Value::Object(a), Value::Object(b)) => {
-            // Compare by length first, then sorted keys+values
+            // The sorted key lists compare first, as arrays; objects with the
+            // same keys then compare by their values in key order.
+            let (a, b) = (sorted_entries(a), sorted_entries(b));
+            for ((ka, _), (kb, _)) in a.iter().zip(b.iter()) {
+                match ka.cmp(kb) {
+                    std::cmp::Ordering::Equal => {}
+                    other => return Some(other),
+                }
+            }
             match a.len().cmp(&b.len()) {
-                std::cmp::Ordering::Equal => {
-                    let mut ak: Vec<_> = a.iter().collect();
-                    let mut bk: Vec<_> = b.iter().collect();
-                    ak.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
-                    bk.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
-                    for ((ka, va), (kb, vb)) in ak.iter().zip(bk.iter()) {
-                        match ka.cmp(kb) {
-                            std::cmp::Ordering::Equal => {}
-                            other => return Some(other),
-                        }
-                        match values_order(va, vb) {
-                            Some(std::cmp::Ordering::Equal) => continue,
-                            other => return other,
-                        }
-                    }
-                    Some(std::cmp::Ordering::Equal)
+                std::cmp::Ordering::Equal => {}
+                other => return Some(other),
+            }
+            for ((_, va), (_, vb)) in a.iter().zip(b.iter()) {
+                match values_order(va, vb) {
+                    Some(std::cmp::Ordering::Equal) => continue,
+                    other => return other,
                 }
-                other => Some(other),
             }
+            Some(std::cmp::Ordering::Equal)
         }
         _ => Some(std::cmp::Ordering::Equal),
     }
@@ -840,7 +1204,63 @@ fn value_desc(v: &Value) -> String {
     v.short_desc()
 }
 
+/// Unary minus.
+pub fn negate_value(v: Value) -> Result<Value, String> {
+    match v {
+        Value::Int(n) => Ok(n
+            .checked_neg()
+            .map_or_else(|| Value::Double(-(n as f64), None), Value::Int)),
+        Value::Double(f, raw) => {
+            // In compat mode (have_decnum=true), preserve raw text through
+            // negation to maintain precision. Otherwise, only preserve for
+            // extreme exponents (inf/0) where f64 loses the representation,
+            // and for big integer literals, which are exact.
+            let preserve = crate::value::jq_compat()
+                || f.is_infinite()
+                || f == 0.0
+                || raw.as_deref().is_some_and(bigint::is_big_integer_text);
+            let neg_raw = if preserve {
+                raw.as_ref().map(|s| {
+                    if let Some(rest) = s.strip_prefix('-') {
+                        rest.into()
+                    } else {
+                        format!("-{s}").into_boxed_str()
+                    }
+                })
+            } else {
+                None
+            };
+            Ok(Value::Double(-f, neg_raw))
+        }
+        _ => Err(format!(
+            "{} ({}) cannot be negated",
+            v.type_name(),
+            v.short_desc()
+        )),
+    }
+}
+
+/// `+`, `-` and `*` between integers where at least one side is a big
+/// integer literal, computed exactly. `None` leaves the operation to f64.
+fn exact_int_arith(left: &Value, op: &ArithOp, right: &Value) -> Option<Value> {
+    if crate::value::jq_compat() || !(bigint::is_big_literal(left) || bigint::is_big_literal(right))
+    {
+        return None;
+    }
+    let (a, b) = (BigInt::from_value(left)?, BigInt::from_value(right)?);
+    let result = match op {
+        ArithOp::Add => &a + &b,
+        ArithOp::Sub => &a - &b,
+        ArithOp::Mul => a.checked_mul(&b)?,
+        _ => return None,
+    };
+    Some(result.into_value())
+}
+
 pub fn arith_values(left: &Value, op: &ArithOp, right: &Value) -> Result<Value, String> {
+    if let Some(exact) = exact_int_arith(left, op, right) {
+        return Ok(exact);
+    }
     match op {
         ArithOp::Add => match (left, right) {
             (Value::Int(a), Value::Int(b)) => {
@@ -1165,6 +1585,7 @@ pub(super) fn value_contains(haystack: &Value, needle: &Value) -> bool {
 // ---------------------------------------------------------------------------
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::*;
 
@@ -1257,23 +1678,45 @@ mod tests {
     }
 
     #[test]
-    fn test_del_path_object() {
+    fn get_path_keeps_keys_and_indices_apart() {
+        let input = obj(&[("0", Value::Array(Arc::new(vec![Value::Int(7)])))]);
+        let key = |s: &str| Value::String(s.into());
+        assert_eq!(
+            get_path(&input, &[key("0"), Value::Int(0)]),
+            Ok(Value::Int(7))
+        );
+        assert_eq!(
+            get_path(&input, &[Value::Int(0)]),
+            Err("Cannot index object with number".to_string())
+        );
+        assert_eq!(
+            get_path(&input, &[key("0"), key("0")]),
+            Err("Cannot index array with string \"0\"".to_string())
+        );
+        assert_eq!(
+            get_path(&Value::Null, &[Value::Int(0), key("a")]),
+            Ok(Value::Null)
+        );
+    }
+
+    #[test]
+    fn test_delete_paths_object() {
         let input = obj(&[("a", Value::Int(1)), ("b", Value::Int(2))]);
-        let result = del_path(&input, &[Value::String("a".into())]);
-        assert_eq!(result, obj(&[("b", Value::Int(2))]));
+        let result = delete_paths(&input, vec![vec![Value::String("a".into())]]);
+        assert_eq!(result, Ok(obj(&[("b", Value::Int(2))])));
     }
 
     #[test]
-    fn test_del_path_array() {
+    fn test_delete_paths_array() {
         let input = Value::Array(Arc::new(vec![
             Value::Int(10),
             Value::Int(20),
             Value::Int(30),
         ]));
-        let result = del_path(&input, &[Value::Int(1)]);
+        let result = delete_paths(&input, vec![vec![Value::Int(1)]]);
         assert_eq!(
             result,
-            Value::Array(Arc::new(vec![Value::Int(10), Value::Int(30)]))
+            Ok(Value::Array(Arc::new(vec![Value::Int(10), Value::Int(30)])))
         );
     }
 
@@ -1308,19 +1751,22 @@ mod tests {
 
     #[test]
     fn set_path_rejects_deep_path() {
-        let path: Vec<Value> = (0..1500).map(|i| Value::Int(i)).collect();
+        let path: Vec<Value> = (0..1500).map(Value::Int).collect();
         let result = set_path(&Value::Null, &path, &Value::Int(1));
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Path too deep"));
     }
 
     #[test]
-    fn del_path_deep_path_returns_value() {
-        let path: Vec<Value> = (0..1500).map(|i| Value::Int(i)).collect();
-        let input = Value::Int(42);
-        let result = del_path(&input, &path);
-        // Should return the value unchanged (no-op) rather than stack overflow
-        assert_eq!(result, input);
+    fn delete_paths_deep_path_is_an_error() {
+        // An error rather than a stack overflow
+        let path: Vec<Value> = (0..1500).map(|_| Value::Int(0)).collect();
+        let mut input = Value::Int(42);
+        for _ in 0..1500 {
+            input = Value::Array(Arc::new(vec![input]));
+        }
+        let result = delete_paths(&input, vec![path]);
+        assert!(result.unwrap_err().contains("Path too deep"));
     }
 
     #[test]
@@ -5,8 +5,8 @@
 //! allocation) and only materializes at the point where a concrete Value is
 //! needed (output boundary, complex computation, etc.).
 
-use crate::filter::eval::set_last_error;
-use crate::filter::{BoolOp, Env, Filter, ObjKey, Pattern};
+use crate::filter::eval::{set_object_key_error, with_object_entry};
+use crate::filter::{BoolOp, Env, EvalMode, Filter, ObjKey, Pattern};
 use crate::flat_value::FlatValue;
 use crate::value::Value;
 use std::collections::HashSet;
@@ -26,6 +26,32 @@ enum NavResult<'a> {
     Values(Vec<Value>),
 }
 
+/// Follow the fields of `.a.b.c` through the buffer. `Ok(None)` means the
+/// result is null: a field was missing or a container along the way was null.
+fn flat_field_path<'a>(
+    flat: FlatValue<'a>,
+    names: &[String],
+) -> Result<Option<FlatValue<'a>>, String> {
+    let mut current = flat;
+    for name in names {
+        if current.is_object() {
+            match current.get_field(name) {
+                Some(child) => current = child,
+                None => return Ok(None),
+            }
+        } else if current.is_null() {
+            return Ok(None);
+        } else {
+            return Err(format!(
+                "Cannot index {} with string \"{}\"",
+                current.type_name(),
+                name
+            ));
+        }
+    }
+    Ok(Some(current))
+}
+
 /// Check if any variable bound by `pattern` is referenced in `filter`.
 fn pattern_var_used_in(pattern: &Pattern, filter: &Filter) -> bool {
     let mut pat_vars = HashSet::new();
@@ -39,7 +65,9 @@ fn pattern_var_used_in(pattern: &Pattern, filter: &Filter) -> bool {
 /// Returns `Some(count)` for navigable patterns (`.[]`, `.field[]`), `None` otherwise.
 fn flat_source_count(filter: &Filter, flat: FlatValue<'_>, env: &Env) -> Option<usize> {
     match filter {
-        Filter::Iterate => flat.len(),
+        // Scalars have a length but can't be iterated; leave their error to
+        // the materializing path.
+        Filter::Iterate if flat.is_array() || flat.is_object() => flat.len(),
         Filter::Pipe(left, right) => match eval_flat_nav(left, flat, env) {
             NavResult::Flat(child) => flat_source_count(right, child, env),
             NavResult::FlatMany(children) => {
@@ -56,6 +84,53 @@ fn flat_source_count(filter: &Filter, flat: FlatValue<'_>, env: &Env) -> Option<
     }
 }
 
+/// Raise the error `.[]` gives on a scalar, worded as in the tree evaluator.
+fn set_iterate_error(flat: FlatValue<'_>, env: &Env) {
+    let msg = if flat.is_null() {
+        "null is not iterable (null)".to_string()
+    } else {
+        let value = flat.to_value();
+        format!(
+            "Cannot iterate over {} ({})",
+            value.type_name(),
+            value.short_desc()
+        )
+    };
+    env.set_error(Value::String(msg));
+}
+
+/// True if `filter` is jq's recursive descent: `..` or the zero-arg builtin
+/// `recurse` (unless a user definition shadows it).
+fn is_recurse(filter: &Filter, env: &Env) -> bool {
+    match filter {
+        Filter::Recurse => true,
+        Filter::Builtin(name, args) => {
+            name == "recurse" && args.is_empty() && env.get_func("recurse", 0).is_none()
+        }
+        _ => false,
+    }
+}
+
+/// Visit `root` and all of its descendants in jq's `..` order (each node
+/// before its children, children depth-first) without materializing any of
+/// them. Uses an explicit stack so deeply nested documents can't overflow.
+/// Stops as soon as `visit` returns false.
+fn walk_flat<'a>(root: FlatValue<'a>, visit: &mut dyn FnMut(FlatValue<'a>) -> bool) {
+    let mut stack = vec![root];
+    let mut children = Vec::new();
+    while let Some(node) = stack.pop() {
+        if !visit(node) {
+            return;
+        }
+        if node.is_array() {
+            children.extend(node.array_iter());
+        } else if node.is_object() {
+            children.extend(node.object_iter().map(|(_, v)| v));
+        }
+        stack.extend(children.drain(..).rev());
+    }
+}
+
 /// Try to evaluate a filter as flat navigation, avoiding materialization.
 ///
 /// For field chains and simple navigation, returns `NavResult::Flat`.
@@ -64,6 +139,15 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
     match filter {
         Filter::Identity => NavResult::Flat(flat),
 
+        _ if is_recurse(filter, env) => {
+            let mut nodes = Vec::new();
+            walk_flat(flat, &mut |node| {
+                nodes.push(node);
+                true
+            });
+            NavResult::FlatMany(nodes)
+        }
+
         Filter::Field(name) => {
             if flat.is_object() {
                 match flat.get_field(name) {
@@ -73,7 +157,7 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
             } else if flat.is_null() {
                 NavResult::Values(vec![Value::Null])
             } else {
-                set_last_error(Value::String(format!(
+                env.set_error(Value::String(format!(
                     "Cannot index {} with string \"{}\"",
                     flat.type_name(),
                     name
@@ -82,16 +166,22 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
             }
         }
 
+        Filter::FieldPath(names) => match flat_field_path(flat, names) {
+            Ok(Some(child)) => NavResult::Flat(child),
+            Ok(None) => NavResult::Values(vec![Value::Null]),
+            Err(msg) => {
+                env.set_error(Value::String(msg));
+                NavResult::Values(vec![])
+            }
+        },
+
         Filter::Iterate => {
             if flat.is_array() {
                 NavResult::FlatMany(flat.array_iter().collect())
             } else if flat.is_object() {
                 NavResult::FlatMany(flat.object_iter().map(|(_, v)| v).collect())
             } else {
-                set_last_error(Value::String(format!(
-                    "{} is not iterable",
-                    flat.type_name()
-                )));
+                set_iterate_error(flat, env);
                 NavResult::Values(vec![])
             }
         }
@@ -112,7 +202,7 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
                 } else if flat.is_null() {
                     NavResult::Values(vec![Value::Null])
                 } else {
-                    set_last_error(Value::String(format!(
+                    env.set_error(Value::String(format!(
                         "Cannot index {} with number",
                         flat.type_name()
                     )));
@@ -140,7 +230,7 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
                 let mut has_values = false;
                 for mid in mids {
                     // Stop if an error was raised (match regular eval Pipe behavior)
-                    if crate::filter::eval::has_last_error() {
+                    if env.has_error() {
                         break;
                     }
                     let r = eval_flat_nav(right, mid, env);
@@ -180,7 +270,7 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
                 let mut results = Vec::new();
                 for v in &values {
                     // Stop if an error was raised (match regular eval Pipe behavior)
-                    if crate::filter::eval::has_last_error() {
+                    if env.has_error() {
                         break;
                     }
                     crate::filter::eval::eval_filter_with_env(right, v, env, &mut |out| {
@@ -191,7 +281,7 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
             }
         },
 
-        Filter::Alternative(left, right) => match eval_flat_nav(left, flat, env) {
+        Filter::Alternative(left, right) => match eval_alternative_left(left, flat, env) {
             NavResult::Flat(child) => {
                 if child.is_truthy() {
                     NavResult::Flat(child)
@@ -221,7 +311,7 @@ fn eval_flat_nav<'a>(filter: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResu
         Filter::Try(inner) => {
             // Try: suppress errors, treat as navigation
             let result = eval_flat_nav(inner, flat, env);
-            let _ = crate::filter::eval::take_last_error();
+            let _ = env.take_error();
             result
         }
 
@@ -336,26 +426,46 @@ fn eval_flat_obj_entries(
     match key {
         ObjKey::Name(name) => {
             eval_flat(val_filter, flat, env, &mut |v| {
-                partial.push((name.clone(), v));
-                eval_flat_obj_entries(rest, flat, env, partial, output);
-                partial.pop();
+                with_object_entry(partial, name, v, |partial| {
+                    eval_flat_obj_entries(rest, flat, env, partial, output)
+                });
             });
         }
         ObjKey::Expr(expr) => {
             let value = flat.to_value();
             crate::filter::eval::eval_filter_with_env(expr, &value, env, &mut |key_val| {
-                if let Value::String(key_str) = key_val {
-                    eval_flat(val_filter, flat, env, &mut |v| {
-                        partial.push((key_str.clone(), v));
-                        eval_flat_obj_entries(rest, flat, env, partial, output);
-                        partial.pop();
+                let Value::String(key_str) = key_val else {
+                    set_object_key_error(env, &key_val);
+                    return;
+                };
+                eval_flat(val_filter, flat, env, &mut |v| {
+                    with_object_entry(partial, &key_str, v, |partial| {
+                        eval_flat_obj_entries(rest, flat, env, partial, output)
                     });
-                }
+                });
             });
         }
     }
 }
 
+/// Compute `[f]` sort keys for `sort_by`/`group_by` and return `(key, index)`
+/// pairs stably sorted by key. Returns `None` if `f` raised an error.
+fn flat_sorted_keys(f: &Filter, elems: &[FlatValue<'_>], env: &Env) -> Option<Vec<(Value, usize)>> {
+    let mut pairs = Vec::with_capacity(elems.len());
+    for (i, elem) in elems.iter().enumerate() {
+        let mut keys = Vec::new();
+        eval_flat(f, *elem, env, &mut |v| keys.push(v));
+        if env.has_error() {
+            return None;
+        }
+        pairs.push((Value::Array(Arc::new(keys)), i));
+    }
+    pairs.sort_by(|(a, _), (b, _)| {
+        crate::filter::values_order(a, b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Some(pairs)
+}
+
 /// Check whether a filter is safe for flat evaluation in single-document mode.
 ///
 /// Returns true if the filter only uses operations that flat eval handles
@@ -367,11 +477,11 @@ fn eval_flat_obj_entries(
 ///
 /// This is conservative: it returns false for any filter that would fall
 /// through to the regular evaluator's catch-all, since that path may set
-/// thread-local errors that the caller expects to check.
+/// errors in the env that the caller expects to check.
 pub fn is_flat_safe(filter: &Filter) -> bool {
     match filter {
         Filter::Identity | Filter::Literal(_) | Filter::Iterate | Filter::Select(_) => true,
-        Filter::Field(_) | Filter::Index(_) => true,
+        Filter::Field(_) | Filter::FieldPath(_) | Filter::Index(_) => true,
         Filter::Pipe(l, r) => is_flat_safe(l) && is_flat_safe(r),
         Filter::Comma(fs) => fs.iter().all(is_flat_safe),
         Filter::ObjectConstruct(entries) => entries
@@ -402,7 +512,24 @@ pub fn is_flat_safe(filter: &Filter) -> bool {
 ///
 /// This is the main entry point for lazy NDJSON evaluation. It navigates
 /// the flat buffer as much as possible, only materializing when needed.
+/// Navigate the left side of `//`. Its errors are suppressed, as in jq.
+fn eval_alternative_left<'a>(left: &Filter, flat: FlatValue<'a>, env: &Env) -> NavResult<'a> {
+    let pending = env.take_error();
+    let result = eval_flat_nav(left, flat, env);
+    env.take_error();
+    if let Some(err) = pending {
+        env.set_error(err);
+    }
+    result
+}
+
 pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut dyn FnMut(Value)) {
+    // Profiling times `eval`, so run every node through it; so does
+    // `--eval-mode tree`.
+    if crate::filter::profile::is_enabled() || crate::filter::eval_mode() == EvalMode::Tree {
+        crate::filter::eval::eval_filter_with_env(filter, &flat.to_value(), env, output);
+        return;
+    }
     match filter {
         Filter::Identity => {
             output(flat.to_value());
@@ -417,7 +544,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
             } else if flat.is_null() {
                 output(Value::Null);
             } else {
-                crate::filter::eval::set_last_error(Value::String(format!(
+                env.set_error(Value::String(format!(
                     "Cannot index {} with string \"{}\"",
                     flat.type_name(),
                     name
@@ -425,6 +552,21 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
             }
         }
 
+        Filter::FieldPath(names) => match flat_field_path(flat, names) {
+            Ok(Some(child)) => output(child.to_value()),
+            Ok(None) => output(Value::Null),
+            Err(msg) => env.set_error(Value::String(msg)),
+        },
+
+        // `.. | f`: stream nodes straight from the buffer so only what `f`
+        // keeps gets decoded, and stop at the first error.
+        Filter::Pipe(left, right) if is_recurse(left, env) => {
+            walk_flat(flat, &mut |node| {
+                eval_flat(right, node, env, output);
+                !env.has_error()
+            });
+        }
+
         Filter::Pipe(left, right) => {
             // Try to navigate left side without materializing
             match eval_flat_nav(left, flat, env) {
@@ -434,7 +576,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 NavResult::FlatMany(children) => {
                     for child in children {
                         // Stop if an error was raised (match regular eval behavior)
-                        if crate::filter::eval::has_last_error() {
+                        if env.has_error() {
                             return;
                         }
                         eval_flat(right, child, env, output);
@@ -443,7 +585,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 NavResult::Values(values) => {
                     for v in &values {
                         // Stop if an error was raised (match regular eval behavior)
-                        if crate::filter::eval::has_last_error() {
+                        if env.has_error() {
                             return;
                         }
                         crate::filter::eval::eval_filter_with_env(right, v, env, output);
@@ -464,7 +606,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
             eval_flat(inner, flat, env, &mut |v| arr.push(v));
             // If an error occurred during collection, don't produce the array
             // (let the error propagate to try/catch)
-            if !crate::filter::eval::has_last_error() {
+            if !env.has_error() {
                 output(Value::Array(Arc::new(arr)));
             }
         }
@@ -478,12 +620,9 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 for (_, val) in flat.object_iter() {
                     output(val.to_value());
                 }
+            } else {
+                set_iterate_error(flat, env);
             }
-            // Non-iterable types: no output, no error.
-            // Note: regular eval sets an error here, but flat_eval is only
-            // used for NDJSON where input is always an object. Keeping the
-            // error-free behavior avoids interactions with Alternative/Try
-            // error clearing that differ between the two eval paths.
         }
 
         Filter::Select(cond) => {
@@ -498,7 +637,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
             }
         }
 
-        Filter::Alternative(left, right) => match eval_flat_nav(left, flat, env) {
+        Filter::Alternative(left, right) => match eval_alternative_left(left, flat, env) {
             NavResult::Flat(child) => {
                 if child.is_truthy() {
                     output(child.to_value());
@@ -535,6 +674,10 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 let mut result = Vec::new();
                 for elem in flat.array_iter() {
                     eval_flat(f, elem, env, &mut |v| result.push(v));
+                    // An error ends the map, with no partial array.
+                    if env.has_error() {
+                        return;
+                    }
                 }
                 output(Value::Array(Arc::new(result)));
             } else if flat.is_object() {
@@ -546,7 +689,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 crate::filter::eval::eval_filter_with_env(filter, &value, env, output);
             } else {
                 let value = flat.to_value();
-                crate::filter::eval::set_last_error(Value::String(format!(
+                env.set_error(Value::String(format!(
                     "Cannot iterate over {} ({})",
                     value.type_name(),
                     value.short_desc()
@@ -615,11 +758,9 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 output(Value::Array(Arc::new(
                     (0..len as i64).map(Value::Int).collect(),
                 )));
-            } else if flat.is_null() {
-                output(Value::Null);
             } else {
                 let value = flat.to_value();
-                crate::filter::eval::set_last_error(Value::String(format!(
+                env.set_error(Value::String(format!(
                     "{} ({}) has no keys",
                     value.type_name(),
                     value.short_desc()
@@ -656,6 +797,9 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 if let Some(count) = flat_source_count(source, flat, env) {
                     // Zero-materialization path: just run update N times.
                     for _ in 0..count {
+                        if env.has_error() {
+                            break;
+                        }
                         let cur = acc.clone();
                         crate::filter::eval::eval_filter_with_env(update, &cur, env, &mut |v| {
                             acc = v
@@ -665,6 +809,9 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                     // Uncountable source (e.g., contains select): materialize
                     // elements but skip match_pattern/binding.
                     eval_flat(source, flat, env, &mut |_val| {
+                        if env.has_error() {
+                            return;
+                        }
                         let cur = acc.clone();
                         crate::filter::eval::eval_filter_with_env(update, &cur, env, &mut |v| {
                             acc = v
@@ -674,6 +821,9 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
             } else {
                 // Live variable: materialize each element and bind pattern.
                 eval_flat(source, flat, env, &mut |val| {
+                    if env.has_error() {
+                        return;
+                    }
                     if let Some(new_env) = crate::filter::eval::match_pattern(pattern, &val, env) {
                         let cur = acc.clone();
                         crate::filter::eval::eval_filter_with_env(
@@ -685,13 +835,27 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                     }
                 });
             }
-            output(acc);
+            // An error in the loop leaves no result, as in the tree evaluator.
+            if !env.has_error() {
+                output(acc);
+            }
         }
 
         Filter::Try(inner) => {
-            eval_flat(inner, flat, env, output);
-            // Try suppresses errors — clear any set by the inner expression
-            let _ = crate::filter::eval::take_last_error();
+            // Only the body's errors are suppressed: one raised by whatever
+            // consumes an output, e.g. the negation in `-.a?`, still stands.
+            let mut downstream_error = None;
+            eval_flat(inner, flat, env, &mut |v| {
+                let _ = env.take_error();
+                output(v);
+                if let Some(err) = env.take_error() {
+                    downstream_error = Some(err);
+                }
+            });
+            let _ = env.take_error();
+            if let Some(err) = downstream_error {
+                env.set_error(err);
+            }
         }
 
         Filter::Not(inner) => {
@@ -779,7 +943,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                     &mut |lval| match crate::filter::arith_values(&lval, op, &rval) {
                         Ok(result) => output(result),
                         Err(msg) => {
-                            crate::filter::eval::set_last_error(Value::String(msg));
+                            env.set_error(Value::String(msg));
                         }
                     },
                 );
@@ -787,45 +951,23 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
         }
 
         Filter::Neg(inner) => {
-            eval_flat(inner, flat, env, &mut |v| match v {
-                Value::Int(n) => output(
-                    n.checked_neg()
-                        .map_or_else(|| Value::Double(-(n as f64), None), Value::Int),
-                ),
-                Value::Double(f, _) => output(Value::Double(-f, None)),
-                _ => {
-                    crate::filter::eval::set_last_error(Value::String(format!(
-                        "{} cannot be negated",
-                        v.type_name()
-                    )));
-                }
-            });
+            eval_flat(
+                inner,
+                flat,
+                env,
+                &mut |v| match crate::filter::negate_value(v) {
+                    Ok(result) => output(result),
+                    Err(msg) => env.set_error(Value::String(msg)),
+                },
+            );
         }
 
         Filter::Builtin(name, args) if name == "sort_by" && args.len() == 1 => {
             if flat.is_array() {
-                let f = &args[0];
-                // Collect (sort_key, index) pairs from flat space
                 let elems: Vec<FlatValue<'_>> = flat.array_iter().collect();
-                let mut pairs: Vec<(Vec<Value>, usize)> = elems
-                    .iter()
-                    .enumerate()
-                    .map(|(i, elem)| {
-                        let mut keys = Vec::new();
-                        eval_flat(f, *elem, env, &mut |v| keys.push(v));
-                        (keys, i)
-                    })
-                    .collect();
-                pairs.sort_by(|(a, _), (b, _)| {
-                    for (ak, bk) in a.iter().zip(b.iter()) {
-                        let ord = crate::filter::values_order(ak, bk)
-                            .unwrap_or(std::cmp::Ordering::Equal);
-                        if ord != std::cmp::Ordering::Equal {
-                            return ord;
-                        }
-                    }
-                    a.len().cmp(&b.len())
-                });
+                let Some(pairs) = flat_sorted_keys(&args[0], &elems, env) else {
+                    return;
+                };
                 // Materialize only in sorted order
                 let sorted: Vec<Value> = pairs
                     .into_iter()
@@ -834,7 +976,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 output(Value::Array(Arc::new(sorted)));
             } else {
                 let value = flat.to_value();
-                crate::filter::eval::set_last_error(Value::String(format!(
+                env.set_error(Value::String(format!(
                     "{} ({}) cannot be sorted, as it is not an array",
                     value.type_name(),
                     value.short_desc()
@@ -844,20 +986,10 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
 
         Filter::Builtin(name, args) if name == "group_by" && args.len() == 1 => {
             if flat.is_array() {
-                let f = &args[0];
                 let elems: Vec<FlatValue<'_>> = flat.array_iter().collect();
-                let mut pairs: Vec<(Value, usize)> = elems
-                    .iter()
-                    .enumerate()
-                    .map(|(i, elem)| {
-                        let mut key = Value::Null;
-                        eval_flat(f, *elem, env, &mut |v| key = v);
-                        (key, i)
-                    })
-                    .collect();
-                pairs.sort_by(|(a, _), (b, _)| {
-                    crate::filter::values_order(a, b).unwrap_or(std::cmp::Ordering::Equal)
-                });
+                let Some(pairs) = flat_sorted_keys(&args[0], &elems, env) else {
+                    return;
+                };
                 // Group consecutive equal keys
                 let mut groups: Vec<Value> = Vec::new();
                 let mut current_group: Vec<Value> = Vec::new();
@@ -877,7 +1009,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                 output(Value::Array(Arc::new(groups)));
             } else {
                 let value = flat.to_value();
-                crate::filter::eval::set_last_error(Value::String(format!(
+                env.set_error(Value::String(format!(
                     "{} ({}) cannot be grouped, as it is not an array",
                     value.type_name(),
                     value.short_desc()
@@ -938,7 +1070,7 @@ pub fn eval_flat(filter: &Filter, flat: FlatValue<'_>, env: &Env, output: &mut d
                     }
                     Value::Null => output(Value::Null),
                     _ => {
-                        crate::filter::eval::set_last_error(Value::String(format!(
+                        env.set_error(Value::String(format!(
                             "{} cannot be sliced",
                             base_val.type_name()
                         )));
@@ -982,6 +1114,11 @@ mod tests {
 
     /// Helper: evaluate with flat evaluator.
     fn eval_with_flat(filter: &Filter, json: &[u8]) -> Vec<Value> {
+        eval_with_flat_err(filter, json).0
+    }
+
+    /// Like `eval_with_flat`, also returning the uncaught error.
+    fn eval_with_flat_err(filter: &Filter, json: &[u8]) -> (Vec<Value>, Option<Value>) {
         let buf = pad_buffer(json);
         let flat_buf = dom_parse_to_flat_buf(&buf, json.len()).unwrap();
         let env = Env::empty();
@@ -989,7 +1126,7 @@ mod tests {
         eval_flat(filter, flat_buf.root(), &env, &mut |v| {
             results.push(v);
         });
-        results
+        (results, env.take_error())
     }
 
     /// Assert flat eval produces same output as regular eval.
@@ -1211,6 +1348,21 @@ mod tests {
         assert_equiv("keys", b"[10,20,30]");
     }
 
+    #[test]
+    fn builtin_keys_null_errors() {
+        let (results, err) = eval_with_flat_err(&parse_filter("keys"), b"null");
+        assert!(results.is_empty());
+        assert_eq!(err, Some(Value::String("null (null) has no keys".into())));
+    }
+
+    #[test]
+    fn map_error_leaves_no_partial_array() {
+        assert_equiv("map(length)", b"[[1],true,[2]]");
+        let (results, err) = eval_with_flat_err(&parse_filter("map(keys)"), br#"[{"a":1},3,"x"]"#);
+        assert!(results.is_empty());
+        assert_eq!(err, Some(Value::String("number (3) has no keys".into())));
+    }
+
     #[test]
     fn builtin_not() {
         assert_equiv("not", b"true");
@@ -1405,10 +1557,9 @@ mod tests {
     fn field_on_non_object_sets_error() {
         // .foo on an array should produce no output but set an error
         let filter = parse_filter(".foo");
-        let result = eval_with_flat(&filter, b"[1,2,3]");
+        let (result, err) = eval_with_flat_err(&filter, b"[1,2,3]");
         assert!(result.is_empty());
         // Error should have been set
-        let err = crate::filter::eval::take_last_error();
         assert!(err.is_some(), "expected error for .foo on array");
     }
 
@@ -1416,12 +1567,71 @@ mod tests {
     fn try_clears_error() {
         // .foo? on a non-object should produce no output and NO error
         let filter = parse_filter(".foo?");
-        let result = eval_with_flat(&filter, b"[1,2,3]");
+        let (result, err) = eval_with_flat_err(&filter, b"[1,2,3]");
         assert!(result.is_empty());
-        let err = crate::filter::eval::take_last_error();
         assert!(err.is_none(), "try should have cleared the error");
     }
 
+    // --- Recursive descent ---
+
+    #[test]
+    fn recurse_bare() {
+        assert_equiv("..", br#"{"a":[1,{"b":null}],"c":"x"}"#);
+        assert_equiv("..", b"42");
+    }
+
+    #[test]
+    fn recurse_select_matches_tree_order() {
+        assert_equiv(
+            r#"[.. | select(.type? == "error") | .msg]"#,
+            br#"{"type":"root","logs":[{"type":"error","msg":"a","sub":{"type":"error","msg":"b"}},{"type":"info"},[{"type":"error","msg":"c"}]]}"#,
+        );
+    }
+
+    #[test]
+    fn recurse_builtin_zero_arg() {
+        assert_equiv("[recurse | numbers]", br#"[1,[2,[3,{"x":4}]],5]"#);
+    }
+
+    #[test]
+    fn recurse_inside_pipe_chain() {
+        assert_equiv(
+            "[.a | .. | .b? | values]",
+            br#"{"a":{"b":1,"c":[{"b":2}]}}"#,
+        );
+    }
+
+    #[test]
+    fn recurse_stops_at_first_error() {
+        let filter = parse_filter(".. | .x");
+        let json = br#"{"x":{"y":1}}"#;
+        let (result, err) = eval_with_flat_err(&filter, json);
+        assert_eq!(result, eval_regular(&filter, json));
+        assert!(err.is_some(), "expected error for .x on a number");
+    }
+
+    #[test]
+    fn recurse_user_definition_shadows_builtin() {
+        assert_equiv("def recurse: .a; [recurse]", br#"{"a":{"a":1}}"#);
+    }
+
+    #[test]
+    fn recurse_deeply_nested_document() {
+        // 1000 levels of {"id", "tags": [8 ints], "child"} — about 11k nodes.
+        let depth = 1000;
+        let mut json = String::new();
+        for i in 0..depth {
+            json.push_str(&format!(r#"{{"id":{i},"tags":[0,1,2,3,4,5,6,7],"child":"#));
+        }
+        json.push_str("null");
+        json.push_str(&"}".repeat(depth));
+        // Only the ids get decoded; the nested objects stay in the buffer.
+        let filter = parse_filter("[.. | .id? | numbers | select(. % 100 == 0)]");
+        let result = eval_with_flat(&filter, json.as_bytes());
+        let expected: Vec<Value> = (0..depth as i64).step_by(100).map(Value::Int).collect();
+        assert_eq!(result, vec![Value::Array(Arc::new(expected))]);
+    }
+
     // --- Mixed complex ---
 
     #[test]
@@ -1796,6 +2006,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sort_and_group_by_multiple_keys() {
+        let input = br#"[{"t":2,"n":"b"},{"t":1,"n":"c"},{"t":2,"n":"a"},{"t":1}]"#;
+        assert_equiv("sort_by(.t, .n)", input);
+        assert_equiv("group_by(.t, .n)", input);
+        assert_equiv("sort_by(.n // empty)", input);
+    }
+
     // --- PostfixSlice ---
 
     #[test]
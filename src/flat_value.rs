@@ -377,6 +377,7 @@ impl ExactSizeIterator for FlatObjectIter<'_> {}
 // ---------------------------------------------------------------------------
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::*;
     use std::sync::Arc;
@@ -1,10 +1,17 @@
 //! Input preprocessing: BOM stripping, JSON/NDJSON parsing into Values.
 
 use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Read};
+use std::rc::Rc;
 use std::sync::Arc;
 
 use crate::value::Value;
 
+pub mod csv;
+pub mod relaxed;
+pub mod yaml;
+
 // Sentinel f64 values used to encode NaN/Infinity in JSON text preprocessing.
 // These are subnormal numbers extremely unlikely to appear in real data.
 const NAN_SENTINEL: f64 = 1.23e-321;
@@ -21,9 +28,55 @@ pub fn strip_bom(buf: &mut Vec<u8>) {
     }
 }
 
+/// jq's line number for a value that ends at byte `end` of `buf`: jq reads
+/// its input a line at a time, so this counts the newlines up to and
+/// including the first one at or after `end`. A value on the last line of
+/// a file without a final newline is on line 0 of a one-line file.
+pub fn line_of_value(buf: &[u8], end: usize) -> usize {
+    LineCounter::default().line_of_value(buf, end)
+}
+
+/// [`line_of_value`] for values met in order in one buffer, counting each
+/// newline only once.
+#[derive(Debug, Default)]
+pub struct LineCounter {
+    counted: usize,
+    lines: usize,
+}
+
+impl LineCounter {
+    pub fn line_of_value(&mut self, buf: &[u8], end: usize) -> usize {
+        let end = end.min(buf.len());
+        let through = match memchr::memchr(b'\n', &buf[end..]) {
+            Some(nl) => end + nl + 1,
+            None => buf.len(),
+        };
+        if through > self.counted {
+            // A plain count vectorizes; memchr_iter is slower when newlines
+            // are dense.
+            self.lines += buf[self.counted..through]
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count();
+            self.counted = through;
+        }
+        self.lines
+    }
+}
+
+/// [`line_of_value`] for the last value in `buf`, e.g. a file's only document.
+pub fn line_of_last_value(buf: &[u8]) -> usize {
+    let end = buf
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(0, |p| p + 1);
+    line_of_value(buf, end)
+}
+
 /// Collect parsed JSON values from a buffer (single doc or NDJSON lines).
 /// Tries single-doc parse first; if that fails and the buffer has newlines,
-/// falls back to line-by-line parsing (handles `1\n2\n3` style multi-value input).
+/// falls back to line-by-line parsing (handles `1\n2\n3` style multi-value input),
+/// and then to a stream of values with any layout.
 pub fn collect_values_from_buf(
     buf: &[u8],
     force_jsonl: bool,
@@ -36,8 +89,10 @@ pub fn collect_values_from_buf(
     {
         return Ok(());
     }
-    if force_jsonl || crate::parallel::ndjson::is_ndjson(buf) {
+    if force_jsonl {
         parse_lines(buf, values)?;
+    } else if crate::parallel::ndjson::is_ndjson(buf) && parse_lines_or_undo(buf, values) {
+        return Ok(());
     } else {
         let json_len = buf.len();
         let padded = crate::simdjson::pad_buffer(buf);
@@ -54,11 +109,13 @@ pub fn collect_values_from_buf(
                     .context("failed to parse JSON (serde_json fallback for >4GB input)")?;
                 values.push(Value::from(serde_val));
             }
-            Err(_) if memchr::memchr(b'\n', buf).is_some() => {
-                // Single-doc parse failed but buffer has newlines — try line-by-line
-                parse_lines(buf, values)?;
-            }
             Err(e) => {
+                // Single-doc parse failed but buffer has newlines — try
+                // line-by-line, then the fallbacks below (a line may hold
+                // several values).
+                if memchr::memchr(b'\n', buf).is_some() && parse_lines_or_undo(buf, values) {
+                    return Ok(());
+                }
                 // Try special float preprocessing (NaN, Infinity, etc.)
                 if has_special_float_tokens(buf) {
                     let preprocessed = preprocess_special_floats(buf);
@@ -99,6 +156,16 @@ pub fn collect_values_from_buf(
     Ok(())
 }
 
+/// Parse `buf` as NDJSON lines, leaving `values` as it was if a line fails.
+fn parse_lines_or_undo(buf: &[u8], values: &mut Vec<Value>) -> bool {
+    let before = values.len();
+    let parsed = parse_lines(buf, values).is_ok();
+    if !parsed {
+        values.truncate(before);
+    }
+    parsed
+}
+
 /// Parse newline-delimited JSON lines into values.
 pub fn parse_lines(buf: &[u8], values: &mut Vec<Value>) -> Result<()> {
     for line in buf.split(|&b| b == b'\n') {
@@ -110,20 +177,400 @@ pub fn parse_lines(buf: &[u8], values: &mut Vec<Value>) -> Result<()> {
         if trimmed.is_empty() {
             continue;
         }
-        let padded = crate::simdjson::pad_buffer(trimmed);
-        match crate::simdjson::dom_parse_to_value(&padded, trimmed.len()) {
-            Ok(val) => values.push(val),
-            Err(_) if has_special_float_tokens(trimmed) => {
-                let pp = preprocess_special_floats(trimmed);
-                let pp_padded = crate::simdjson::pad_buffer(&pp);
-                let val = crate::simdjson::dom_parse_to_value(&pp_padded, pp.len())
-                    .context("failed to parse NDJSON line (after special float preprocessing)")?;
-                values.push(fixup_special_float_sentinels(val));
+        values.push(parse_line(trimmed)?);
+    }
+    Ok(())
+}
+
+/// Parse one trimmed, non-empty NDJSON line.
+fn parse_line(line: &[u8]) -> Result<Value> {
+    let padded = crate::simdjson::pad_buffer(line);
+    match crate::simdjson::dom_parse_to_value(&padded, line.len()) {
+        Ok(val) => Ok(val),
+        Err(_) if has_special_float_tokens(line) => {
+            let pp = preprocess_special_floats(line);
+            let pp_padded = crate::simdjson::pad_buffer(&pp);
+            let val = crate::simdjson::dom_parse_to_value(&pp_padded, pp.len())
+                .context("failed to parse NDJSON line (after special float preprocessing)")?;
+            Ok(fixup_special_float_sentinels(val))
+        }
+        Err(e) => Err(e).context("failed to parse NDJSON line"),
+    }
+}
+
+/// One input for [`LazyInputs`]: standard input or a (possibly compressed) file.
+pub enum InputFile {
+    Stdin,
+    Path(String),
+}
+
+/// Where [`LazyInputs`] is reading from within the current input.
+enum Cursor {
+    /// Between inputs: open the next one on demand.
+    Idle,
+    /// NDJSON or raw text, read one line (or raw record) at a time.
+    Lines(Box<dyn BufRead>),
+    /// Any other JSON text, read a line at a time and handed out as each
+    /// value is complete.
+    Values(Box<ValueStream>),
+}
+
+/// JSON text that isn't NDJSON: any number of values, laid out any way.
+/// Lines are read only until the next value is complete, so `first(inputs)`
+/// returns without waiting for the rest of the input.
+struct ValueStream {
+    reader: Box<dyn BufRead>,
+    /// Text read but not yet handed out, starting at the value being scanned.
+    pending: Vec<u8>,
+    scan: ValueScan,
+    /// Values parsed from one complete value's text (usually just one).
+    ready: std::collections::VecDeque<Value>,
+    /// jq's line number of the values in `ready`.
+    line: usize,
+}
+
+impl ValueStream {
+    fn next(&mut self, lines_read: &mut usize) -> Result<Option<(Value, usize)>> {
+        loop {
+            if let Some(value) = self.ready.pop_front() {
+                return Ok(Some((value, self.line)));
+            }
+            let end = match self.scan.find_end(&self.pending) {
+                Some(end) => end,
+                None => {
+                    self.compact();
+                    if self.reader.read_until(b'\n', &mut self.pending)? > 0 {
+                        *lines_read += usize::from(self.pending.last() == Some(&b'\n'));
+                        continue;
+                    }
+                    match self.scan.start {
+                        None => return Ok(None),
+                        // A scalar runs to the end of the input.
+                        Some(_) if self.scan.scalar => self.pending.len(),
+                        Some(_) => anyhow::bail!("unfinished JSON term at EOF"),
+                    }
+                }
+            };
+            let start = self.scan.start.take().unwrap_or(end);
+            self.line = *lines_read;
+            let mut values = Vec::new();
+            collect_values_from_buf(&self.pending[start..end], false, &mut values)?;
+            self.ready.extend(values);
+            self.scan = ValueScan {
+                pos: end,
+                ..ValueScan::default()
+            };
+        }
+    }
+
+    /// Drop the text of values already handed out.
+    fn compact(&mut self) {
+        let done = self.scan.start.unwrap_or(self.scan.pos);
+        if done > 0 {
+            self.pending.drain(..done);
+            self.scan.pos -= done;
+            if let Some(start) = self.scan.start.as_mut() {
+                *start = 0;
+            }
+        }
+    }
+}
+
+/// Finds where the next top-level JSON value ends, picking up where the last
+/// call stopped as more text arrives. Only the nesting is tracked; the
+/// value's text is left for the parser to validate.
+#[derive(Debug, Default)]
+struct ValueScan {
+    /// Where the value being scanned begins, once one has.
+    start: Option<usize>,
+    pos: usize,
+    depth: usize,
+    in_string: bool,
+    /// The last byte scanned was a backslash in a string.
+    escaped: bool,
+    /// The value is a number, literal or other bare word.
+    scalar: bool,
+}
+
+impl ValueScan {
+    fn find_end(&mut self, buf: &[u8]) -> Option<usize> {
+        while self.pos < buf.len() {
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                    self.pos += 1;
+                    continue;
+                }
+                let Some(i) = memchr::memchr2(b'"', b'\\', &buf[self.pos..]) else {
+                    self.pos = buf.len();
+                    break;
+                };
+                self.pos += i + 1;
+                if buf[self.pos - 1] == b'\\' {
+                    self.escaped = true;
+                    continue;
+                }
+                self.in_string = false;
+                if self.depth == 0 {
+                    return Some(self.pos);
+                }
+                continue;
+            }
+            let b = buf[self.pos];
+            if self.scalar {
+                if b.is_ascii_whitespace() || b"{}[]\",:".contains(&b) {
+                    return Some(self.pos);
+                }
+                self.pos += 1;
+                continue;
+            }
+            if self.start.is_none() {
+                if b.is_ascii_whitespace() {
+                    self.pos += 1;
+                    continue;
+                }
+                self.start = Some(self.pos);
+                self.scalar = !b"{}[]\"".contains(&b);
+            }
+            self.pos += 1;
+            match b {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                // A stray closing bracket is a value of its own, for the
+                // parser to reject.
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 0 {
+                        return Some(self.pos);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Lazily parsed input values, for feeding `input`/`inputs` without holding
+/// every record in memory.
+///
+/// NDJSON inputs (detected the same way as [`collect_values_from_buf`], or
+/// forced with `force_jsonl`) are read one line per value. With a
+/// `raw_delimiter` (`\n` for `--raw-input`, NUL for `--raw-input0`) every
+/// record becomes a string instead. Other JSON text is read until each value
+/// is complete. Either way memory stays proportional to the largest record,
+/// and no more is read than the filter asks for. Compressed files are
+/// decompressed whole.
+///
+/// A line of NDJSON that isn't one JSON value is reported and skipped, as
+/// the NDJSON path does for `.`; [`Self::bad_input`] records that it
+/// happened. Any other read or parse error ends the stream; it is kept in
+/// [`Self::error`] for the caller to report after evaluation. Where the last
+/// value came from is kept in [`Self::location`].
+pub struct LazyInputs {
+    files: std::collections::VecDeque<InputFile>,
+    cursor: Cursor,
+    force_jsonl: bool,
+    raw_delimiter: Option<u8>,
+    line: Vec<u8>,
+    /// Newlines read so far from the current input.
+    lines_read: usize,
+    location: Option<(Rc<str>, usize)>,
+    bad_input: Rc<std::cell::Cell<bool>>,
+    error: Rc<RefCell<Option<anyhow::Error>>>,
+}
+
+impl LazyInputs {
+    pub fn new(files: Vec<InputFile>, force_jsonl: bool, raw_delimiter: Option<u8>) -> Self {
+        LazyInputs {
+            files: files.into(),
+            cursor: Cursor::Idle,
+            force_jsonl,
+            raw_delimiter,
+            line: Vec::new(),
+            lines_read: 0,
+            location: None,
+            bad_input: Rc::default(),
+            error: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Shared slot holding the error that ended the stream, if any.
+    pub fn error(&self) -> Rc<RefCell<Option<anyhow::Error>>> {
+        self.error.clone()
+    }
+
+    /// Shared flag set once a malformed NDJSON line has been skipped.
+    pub fn bad_input(&self) -> Rc<std::cell::Cell<bool>> {
+        self.bad_input.clone()
+    }
+
+    /// The input opened last (a file path or `<stdin>`) and jq's line number
+    /// of the value read last from it, for the location uncaught errors are
+    /// reported at. `None` before any input was opened.
+    pub fn location(&self) -> Option<(Rc<str>, usize)> {
+        self.location.clone()
+    }
+
+    fn open(&mut self, file: InputFile) -> Result<Cursor> {
+        let reader: Box<dyn Read> = match file {
+            InputFile::Stdin => {
+                self.location = Some(("<stdin>".into(), 0));
+                Box::new(std::io::stdin().lock())
+            }
+            InputFile::Path(path) if crate::decompress::is_compressed(&path) => {
+                self.location = Some((path.as_str().into(), 0));
+                Box::new(std::io::Cursor::new(crate::decompress::decompress_file(
+                    &path,
+                )?))
+            }
+            InputFile::Path(path) => {
+                self.location = Some((path.as_str().into(), 0));
+                Box::new(
+                    std::fs::File::open(&path)
+                        .with_context(|| format!("failed to read file: {path}"))?,
+                )
+            }
+        };
+        let mut reader = BufReader::with_capacity(1 << 16, reader);
+        let mut head = Vec::new();
+        reader.read_until(b'\n', &mut head)?;
+        strip_bom(&mut head);
+        let line_per_value = self.raw_delimiter.is_some() || self.force_jsonl;
+        // A first line that could start NDJSON needs the next non-blank byte
+        // too, for `is_ndjson` to decide how the rest is parsed. Otherwise
+        // don't wait for more: stdin may not have sent it yet.
+        let first_line = head.strip_suffix(b"\n").unwrap_or(&head);
+        if line_per_value || !crate::parallel::ndjson::starts_ndjson(first_line) {
+            return Ok(self.stream(head, reader, line_per_value));
+        }
+        loop {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(pos) => {
+                    head.extend_from_slice(&buf[..=pos]);
+                    reader.consume(pos + 1);
+                    break;
+                }
+                None => {
+                    let len = buf.len();
+                    head.extend_from_slice(buf);
+                    reader.consume(len);
+                }
+            }
+        }
+        let is_ndjson = crate::parallel::ndjson::is_ndjson(&head);
+        Ok(self.stream(head, reader, is_ndjson))
+    }
+
+    /// The cursor for an input whose first bytes, `head`, were already read
+    /// from `reader`.
+    fn stream(&mut self, head: Vec<u8>, reader: impl BufRead + 'static, lines: bool) -> Cursor {
+        if lines {
+            self.lines_read = 0;
+            let reader = std::io::Cursor::new(head).chain(reader);
+            return Cursor::Lines(Box::new(BufReader::new(reader)));
+        }
+        self.lines_read = head.iter().filter(|&&b| b == b'\n').count();
+        Cursor::Values(Box::new(ValueStream {
+            reader: Box::new(reader),
+            pending: head,
+            scan: ValueScan::default(),
+            ready: std::collections::VecDeque::new(),
+            line: 0,
+        }))
+    }
+
+    fn set_line(&mut self, line: usize) {
+        if let Some((_, current)) = &mut self.location {
+            *current = line;
+        }
+    }
+
+    fn next_value(&mut self) -> Result<Option<Value>> {
+        loop {
+            match &mut self.cursor {
+                Cursor::Idle => match self.files.pop_front() {
+                    Some(file) => self.cursor = self.open(file)?,
+                    None => return Ok(None),
+                },
+                Cursor::Values(values) => match values.next(&mut self.lines_read)? {
+                    Some((v, line)) => {
+                        self.set_line(line);
+                        return Ok(Some(v));
+                    }
+                    None => self.cursor = Cursor::Idle,
+                },
+                Cursor::Lines(reader) => {
+                    let delimiter = self.raw_delimiter.unwrap_or(b'\n');
+                    self.line.clear();
+                    if reader.read_until(delimiter, &mut self.line)? == 0 {
+                        self.cursor = Cursor::Idle;
+                        continue;
+                    }
+                    self.lines_read += if delimiter == b'\n' {
+                        usize::from(self.line.last() == Some(&b'\n'))
+                    } else {
+                        self.line.iter().filter(|&&b| b == b'\n').count()
+                    };
+                    self.set_line(self.lines_read);
+                    if self.raw_delimiter.is_some() {
+                        if self.line.last() == Some(&delimiter) {
+                            self.line.pop();
+                            if delimiter == b'\n' && self.line.last() == Some(&b'\r') {
+                                self.line.pop();
+                            }
+                        }
+                        let text =
+                            std::str::from_utf8(&self.line).context("input is not valid UTF-8")?;
+                        return Ok(Some(Value::String(text.to_string())));
+                    }
+                    let end = self
+                        .line
+                        .iter()
+                        .rposition(|&b| !matches!(b, b' ' | b'\t' | b'\r' | b'\n'))
+                        .map_or(0, |p| p + 1);
+                    if end > 0 {
+                        let line = &self.line[..end];
+                        match parse_line(line) {
+                            Ok(value) => return Ok(Some(value)),
+                            Err(err) => {
+                                let message =
+                                    crate::parallel::ndjson::describe_bad_line(line, &err);
+                                // The line being read, counting one without a final newline.
+                                let number =
+                                    self.lines_read + usize::from(self.line.last() != Some(&b'\n'));
+                                eprintln!("qj: error (at line {number}): {message}");
+                                self.bad_input.set(true);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for LazyInputs {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        if self.error.borrow().is_some() {
+            return None;
+        }
+        match self.next_value() {
+            Ok(v) => v,
+            Err(e) => {
+                self.files.clear();
+                self.cursor = Cursor::Idle;
+                *self.error.borrow_mut() = Some(e);
+                None
             }
-            Err(e) => return Err(e).context("failed to parse NDJSON line"),
         }
     }
-    Ok(())
 }
 
 /// Public wrapper for `has_special_float_tokens`.
@@ -141,6 +588,154 @@ pub fn fixup_special_float_sentinels_pub(val: Value) -> Value {
     fixup_special_float_sentinels(val)
 }
 
+/// Check if a byte buffer contains `\uXXXX` escapes for UTF-16 surrogates that
+/// are not part of a valid high+low pair. simdjson rejects these with a string
+/// error; jq accepts them and decodes each one as U+FFFD.
+pub fn has_lone_surrogates(buf: &[u8]) -> bool {
+    lone_surrogate_escapes(buf).next().is_some()
+}
+
+/// Rewrite every lone surrogate escape (see `has_lone_surrogates`) to `\uFFFD`.
+///
+/// Returns `None` if there is nothing to replace. The rewrite is length-preserving
+/// (each 6-byte escape becomes another 6-byte escape), so byte offsets into the
+/// original buffer stay valid for error reporting.
+pub fn replace_lone_surrogates(buf: &[u8]) -> Option<Vec<u8>> {
+    let mut positions = lone_surrogate_escapes(buf).peekable();
+    positions.peek()?;
+    let mut result = buf.to_vec();
+    for pos in positions {
+        result[pos..pos + 6].copy_from_slice(b"\\uFFFD");
+    }
+    Some(result)
+}
+
+/// Yield the byte offset of each `\u` escape that encodes an unpaired surrogate.
+fn lone_surrogate_escapes(buf: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let mut paired_low = None;
+    memchr::memmem::find_iter(buf, b"\\u").filter(move |&pos| {
+        // An odd run of backslashes before this one means it is itself escaped
+        // (e.g. `"\\ud800"` is a literal backslash followed by text).
+        let preceding = buf[..pos].iter().rev().take_while(|&&b| b == b'\\').count();
+        if preceding % 2 == 1 {
+            return false;
+        }
+        if paired_low == Some(pos) {
+            return false;
+        }
+        match surrogate_kind(buf, pos) {
+            Some(Surrogate::High) => {
+                if surrogate_kind(buf, pos + 6) == Some(Surrogate::Low) {
+                    paired_low = Some(pos + 6);
+                    false
+                } else {
+                    true
+                }
+            }
+            Some(Surrogate::Low) => true,
+            None => false,
+        }
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Surrogate {
+    High,
+    Low,
+}
+
+/// Classify the `\uXXXX` escape starting at `pos` as a high or low surrogate.
+fn surrogate_kind(buf: &[u8], pos: usize) -> Option<Surrogate> {
+    let esc = buf.get(pos..pos + 6)?;
+    if &esc[..2] != b"\\u" || !esc[2..].iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    if !esc[2].eq_ignore_ascii_case(&b'd') {
+        return None;
+    }
+    match esc[3].to_ascii_lowercase() {
+        b'8' | b'9' | b'a' | b'b' => Some(Surrogate::High),
+        b'c' | b'd' | b'e' | b'f' => Some(Surrogate::Low),
+        _ => None,
+    }
+}
+
+/// Check if any object in a JSON text repeats a key. jq keeps only the last
+/// of repeated keys, so fast paths that copy input bytes to the output hand
+/// such text to the parser instead. Keys written with escapes are treated as
+/// possible repeats, since another spelling of the same key would be missed.
+pub fn has_duplicate_keys(buf: &[u8]) -> bool {
+    /// Objects with more keys than this are checked with a hash set.
+    const LINEAR_LIMIT: usize = 16;
+
+    // Keys of the open objects, innermost last; `frames` holds where each
+    // object's keys start in `keys`, plus a set once it grows large.
+    let mut keys: Vec<&[u8]> = Vec::new();
+    let mut frames: Vec<(usize, Option<std::collections::HashSet<&[u8]>>)> = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = memchr::memchr3(b'"', b'{', b'}', &buf[pos..]) {
+        let at = pos + found;
+        match buf[at] {
+            b'{' => {
+                frames.push((keys.len(), None));
+                pos = at + 1;
+            }
+            b'}' => {
+                if let Some((start, _)) = frames.pop() {
+                    keys.truncate(start);
+                }
+                pos = at + 1;
+            }
+            _ => {
+                let start = at + 1;
+                let mut end = start;
+                let mut escaped = false;
+                while let Some(q) = memchr::memchr2(b'"', b'\\', &buf[end..]) {
+                    end += q;
+                    if buf[end] == b'"' {
+                        break;
+                    }
+                    escaped = true;
+                    end += 2;
+                }
+                if end >= buf.len() {
+                    return false;
+                }
+                pos = end + 1;
+                let after = buf[pos..]
+                    .iter()
+                    .position(|b| !matches!(b, b' ' | b'\t' | b'\n' | b'\r'));
+                if after.map(|off| buf[pos + off]) != Some(b':') {
+                    continue;
+                }
+                if escaped {
+                    return true;
+                }
+                let Some((first, set)) = frames.last_mut() else {
+                    continue;
+                };
+                let key = &buf[start..end];
+                let repeated = match set {
+                    Some(set) => !set.insert(key),
+                    None if keys.len() - *first < LINEAR_LIMIT => keys[*first..].contains(&key),
+                    None => {
+                        let mut seen: std::collections::HashSet<&[u8]> =
+                            keys[*first..].iter().copied().collect();
+                        let repeated = !seen.insert(key);
+                        *set = Some(seen);
+                        repeated
+                    }
+                };
+                if repeated {
+                    return true;
+                }
+                keys.push(key);
+            }
+        }
+    }
+    false
+}
+
 /// Check if a byte buffer contains non-standard float tokens (NaN, Infinity, etc.)
 /// outside of JSON strings. These are accepted by jq but not by standard JSON parsers.
 fn has_special_float_tokens(buf: &[u8]) -> bool {
@@ -337,6 +932,36 @@ mod tests {
         assert!(buf.is_empty());
     }
 
+    // --- ValueScan ---
+
+    #[test]
+    fn value_scan_splits_values_as_text_arrives() {
+        let ends = |text: &[u8]| {
+            let mut scan = ValueScan::default();
+            let mut ends = Vec::new();
+            while let Some(end) = scan.find_end(text) {
+                ends.push((scan.start.unwrap(), end));
+                scan = ValueScan {
+                    pos: end,
+                    ..ValueScan::default()
+                };
+            }
+            ends
+        };
+        assert_eq!(ends(b"1 -2.5\ntrue "), [(0, 1), (2, 6), (7, 11)]);
+        assert_eq!(
+            ends(br#" "a\"]" {"b":["}"]}[]"#),
+            [(1, 7), (8, 19), (19, 21)]
+        );
+        // A scalar may go on in text not read yet.
+        assert_eq!(ends(b"[1] nul"), [(0, 3)]);
+        // Continues where it stopped, across a backslash at the end.
+        let mut scan = ValueScan::default();
+        assert_eq!(scan.find_end(br#"{"a\"#), None);
+        assert_eq!(scan.find_end(br#"{"a\"}": 1"#), None);
+        assert_eq!(scan.find_end(br#"{"a\"}": 1}"#), Some(11));
+    }
+
     // --- parse_lines ---
 
     #[test]
@@ -436,6 +1061,72 @@ mod tests {
         assert!(!has_special_float_tokens(b"[1,2,3]"));
     }
 
+    // --- lone surrogates ---
+
+    #[test]
+    fn lone_surrogates_detected() {
+        assert!(has_lone_surrogates(br#""\udead""#));
+        assert!(has_lone_surrogates(br#""\uD83D""#));
+        assert!(has_lone_surrogates(br#"{"\ud800":1}"#));
+        assert!(has_lone_surrogates(br#""\ud800A""#));
+        assert!(has_lone_surrogates(br#""\ude00\ud83d""#));
+    }
+
+    #[test]
+    fn lone_surrogates_ignores_pairs_and_escaped_backslashes() {
+        assert!(!has_lone_surrogates(br#""\ud83d\ude00""#));
+        assert!(!has_lone_surrogates(br#""\\udead""#));
+        assert!(!has_lone_surrogates(br#""caf\u00e9 \u0041""#));
+        assert!(has_lone_surrogates(br#""\\\udead""#));
+    }
+
+    // --- duplicate keys ---
+
+    #[test]
+    fn duplicate_keys_detected_per_object() {
+        assert!(has_duplicate_keys(br#"{"a":1,"b":2,"a":3}"#));
+        assert!(has_duplicate_keys(br#"[{"x":{"k":1, "k" : 2}}]"#));
+        assert!(has_duplicate_keys(br#"{"a\u0062":1}"#));
+        assert!(!has_duplicate_keys(
+            br#"{"a":{"a":1},"b":[{"a":2},{"a":3}]}"#
+        ));
+        assert!(!has_duplicate_keys(
+            br#"{"a":"a","b":"\"a\":1","c":["a","a"]}"#
+        ));
+        assert!(!has_duplicate_keys(br#"{"a":1}{"a":2}"#));
+        assert!(!has_duplicate_keys(b"[1,2,{}]"));
+
+        // Large objects switch to a hash set
+        let mut big: String = (0..40).map(|i| format!("\"k{i}\":{i},")).collect();
+        assert!(!has_duplicate_keys(
+            format!("{{{}\"z\":0}}", big).as_bytes()
+        ));
+        big.push_str("\"k7\":1");
+        assert!(has_duplicate_keys(format!("{{{big}}}").as_bytes()));
+    }
+
+    #[test]
+    fn replace_lone_surrogates_preserves_length() {
+        let input = br#"{"\ud800":"a\udc00\ud83d\ude00\ud83d"}"#;
+        let fixed = replace_lone_surrogates(input).unwrap();
+        assert_eq!(fixed.len(), input.len());
+        assert_eq!(fixed, br#"{"\uFFFD":"a\uFFFD\ud83d\ude00\uFFFD"}"#.to_vec());
+        assert_eq!(replace_lone_surrogates(br#""\ud83d\ude00""#), None);
+    }
+
+    #[test]
+    fn parse_with_lone_surrogates() {
+        let mut vals = Vec::new();
+        collect_values_from_buf(br#"{"\udead":"x\ud800"}"#, false, &mut vals).unwrap();
+        assert_eq!(
+            vals,
+            vec![Value::Object(Arc::new(vec![(
+                "\u{FFFD}".to_string(),
+                Value::String("x\u{FFFD}".to_string())
+            )]))]
+        );
+    }
+
     // --- preprocess_special_floats ---
 
     #[test]
@@ -614,7 +1305,7 @@ mod tests {
                 a.iter().all(|(ka, va)| {
                     b.iter()
                         .find(|(kb, _)| kb == ka)
-                        .map_or(false, |(_, vb)| values_equal_unordered(va, vb))
+                        .is_some_and(|(_, vb)| values_equal_unordered(va, vb))
                 }) && b.iter().all(|(kb, _)| a.iter().any(|(ka, _)| ka == kb))
             }
             _ => false,
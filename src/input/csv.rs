@@ -0,0 +1,344 @@
+//! Delimited text input (`--csv-input` / `--tsv-input`).
+//!
+//! The first row of each input is the header; every following row becomes an
+//! object mapping header names to field strings (or numbers with
+//! `--csv-numbers`). CSV follows RFC 4180: fields may be double-quoted, and
+//! quoted fields can contain delimiters, newlines, and `""` escapes. TSV has
+//! no quoting; instead the `\t`, `\n`, `\r` and `\\` escapes that `@tsv`
+//! produces are decoded.
+
+use anyhow::{Result, bail};
+use std::sync::Arc;
+
+use crate::value::Value;
+
+/// Which delimited format to parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelimitedFormat {
+    Csv,
+    Tsv,
+}
+
+impl DelimitedFormat {
+    fn name(self) -> &'static str {
+        match self {
+            DelimitedFormat::Csv => "CSV",
+            DelimitedFormat::Tsv => "TSV",
+        }
+    }
+}
+
+/// Parse delimited `text` into one object per data row.
+///
+/// Blank lines are skipped. A row with a different number of fields than the
+/// header is an error, as is a repeated header name.
+pub fn parse_records(text: &str, format: DelimitedFormat, numbers: bool) -> Result<Vec<Value>> {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let rows = match format {
+        DelimitedFormat::Csv => csv_rows(text)?,
+        DelimitedFormat::Tsv => tsv_rows(text),
+    };
+    let mut rows = rows.into_iter();
+    let Some((_, header)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    for (i, name) in header.iter().enumerate() {
+        if header[..i].contains(name) {
+            bail!("{} header: duplicate column name {:?}", format.name(), name);
+        }
+    }
+
+    let mut records = Vec::new();
+    for (line, fields) in rows {
+        if fields.len() != header.len() {
+            bail!(
+                "{} line {}: expected {} fields, found {}",
+                format.name(),
+                line,
+                header.len(),
+                fields.len()
+            );
+        }
+        let entries = header
+            .iter()
+            .zip(fields)
+            .map(|(name, field)| {
+                let value = if numbers {
+                    parse_number(&field).unwrap_or(Value::String(field))
+                } else {
+                    Value::String(field)
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        records.push(Value::Object(Arc::new(entries)));
+    }
+    Ok(records)
+}
+
+/// Split CSV text into rows of unescaped fields, each tagged with the
+/// 1-based line number the row starts on.
+fn csv_rows(text: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let mut rows = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start_line = line;
+        let mut fields = Vec::new();
+        loop {
+            let mut field = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => bail!("CSV line {start_line}: unterminated quoted field"),
+                    }
+                }
+                if chars.peek() == Some(&'\r') {
+                    chars.next();
+                }
+                if !matches!(chars.peek(), None | Some(',' | '\n')) {
+                    bail!("CSV line {line}: unexpected character after closing quote");
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c == ',' || c == '\n' {
+                        break;
+                    }
+                    field.push(c);
+                    chars.next();
+                }
+                if field.ends_with('\r') && chars.peek() != Some(&',') {
+                    field.pop();
+                }
+            }
+            fields.push(field);
+            match chars.next() {
+                Some(',') => continue,
+                Some('\n') => line += 1,
+                _ => {}
+            }
+            break;
+        }
+        if !(fields.len() == 1 && fields[0].is_empty()) {
+            rows.push((start_line, fields));
+        }
+    }
+    Ok(rows)
+}
+
+/// Split TSV text into rows of decoded fields, tagged with line numbers.
+fn tsv_rows(text: &str) -> Vec<(usize, Vec<String>)> {
+    text.split('\n')
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.strip_suffix('\r').unwrap_or(line)))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(n, line)| (n, line.split('\t').map(tsv_unescape).collect()))
+        .collect()
+}
+
+/// Decode the backslash escapes `@tsv` writes; other backslashes stay as-is.
+fn tsv_unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parse a field as a number if it is written exactly like a JSON number.
+fn parse_number(field: &str) -> Option<Value> {
+    let bytes = field.as_bytes();
+    let mut i = usize::from(bytes.first() == Some(&b'-'));
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while bytes.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        *i - start
+    };
+    let int_start = i;
+    match digits(&mut i) {
+        0 => return None,
+        n if n > 1 && bytes[int_start] == b'0' => return None,
+        _ => {}
+    }
+    let mut is_int = true;
+    if bytes.get(i) == Some(&b'.') {
+        i += 1;
+        if digits(&mut i) == 0 {
+            return None;
+        }
+        is_int = false;
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        if digits(&mut i) == 0 {
+            return None;
+        }
+        is_int = false;
+    }
+    if i != bytes.len() {
+        return None;
+    }
+    if is_int && let Ok(n) = field.parse::<i64>() {
+        return Some(Value::Int(n));
+    }
+    let f: f64 = field.parse().ok()?;
+    Some(Value::Double(f, Some(field.into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obj(pairs: &[(&str, Value)]) -> Value {
+        Value::Object(Arc::new(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        ))
+    }
+
+    fn s(v: &str) -> Value {
+        Value::String(v.to_string())
+    }
+
+    #[test]
+    fn csv_header_and_rows() {
+        let records = parse_records("a,b\n1,x\n2,y\n", DelimitedFormat::Csv, false).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                obj(&[("a", s("1")), ("b", s("x"))]),
+                obj(&[("a", s("2")), ("b", s("y"))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_quoted_fields() {
+        let text = "name,note\r\n\"Smith, J\",\"said \"\"hi\"\"\nthen left\"\r\n\"\",plain\r\n";
+        let records = parse_records(text, DelimitedFormat::Csv, false).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                obj(&[
+                    ("name", s("Smith, J")),
+                    ("note", s("said \"hi\"\nthen left"))
+                ]),
+                obj(&[("name", s("")), ("note", s("plain"))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_empty_fields_and_no_trailing_newline() {
+        let records = parse_records("a,b,c\n,,\nx,,z", DelimitedFormat::Csv, false).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                obj(&[("a", s("")), ("b", s("")), ("c", s(""))]),
+                obj(&[("a", s("x")), ("b", s("")), ("c", s("z"))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_errors() {
+        let err = parse_records("a,b\n1,2\n3\n", DelimitedFormat::Csv, false).unwrap_err();
+        assert_eq!(err.to_string(), "CSV line 3: expected 2 fields, found 1");
+        let err = parse_records("a,b\n\"x\ny,2\n", DelimitedFormat::Csv, false).unwrap_err();
+        assert_eq!(err.to_string(), "CSV line 2: unterminated quoted field");
+        let err = parse_records("a,b\n\"x\"y,2\n", DelimitedFormat::Csv, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "CSV line 2: unexpected character after closing quote"
+        );
+        let err = parse_records("a,a\n1,2\n", DelimitedFormat::Csv, false).unwrap_err();
+        assert_eq!(err.to_string(), "CSV header: duplicate column name \"a\"");
+    }
+
+    #[test]
+    fn csv_line_numbers_count_quoted_newlines() {
+        let text = "a,b\n\"1\n2\",x\n3\n";
+        let err = parse_records(text, DelimitedFormat::Csv, false).unwrap_err();
+        assert_eq!(err.to_string(), "CSV line 4: expected 2 fields, found 1");
+    }
+
+    #[test]
+    fn tsv_rows_and_escapes() {
+        let text = "a\tb\r\nx\\ty\tline\\nbreak\n\nc:\\\\d\t\n";
+        let records = parse_records(text, DelimitedFormat::Tsv, false).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                obj(&[("a", s("x\ty")), ("b", s("line\nbreak"))]),
+                obj(&[("a", s("c:\\d")), ("b", s(""))]),
+            ]
+        );
+        let err = parse_records("a\tb\n1\n", DelimitedFormat::Tsv, false).unwrap_err();
+        assert_eq!(err.to_string(), "TSV line 2: expected 2 fields, found 1");
+    }
+
+    #[test]
+    fn numbers_opt_in() {
+        let text = "i,f,e,s,z,lead\n-3,1.50,2e3,1.,0,007\n";
+        let records = parse_records(text, DelimitedFormat::Csv, true).unwrap();
+        assert_eq!(
+            records,
+            vec![obj(&[
+                ("i", Value::Int(-3)),
+                ("f", Value::Double(1.5, Some("1.50".into()))),
+                ("e", Value::Double(2000.0, Some("2e3".into()))),
+                ("s", s("1.")),
+                ("z", Value::Int(0)),
+                ("lead", s("007")),
+            ])]
+        );
+    }
+
+    #[test]
+    fn header_only_and_empty_input() {
+        assert!(
+            parse_records("a,b\n", DelimitedFormat::Csv, false)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            parse_records("", DelimitedFormat::Tsv, false)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
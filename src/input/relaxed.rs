@@ -0,0 +1,394 @@
+//! Relaxed JSON input (`--relaxed`).
+//!
+//! Accepts the JSON people write in config files: `//` and `/* */` comments,
+//! trailing commas in arrays and objects, single-quoted strings, and unquoted
+//! identifier object keys. The text is rewritten as strict JSON, one value per
+//! line, and parsed by the normal JSON path, so values come out exactly as
+//! they would from the equivalent strict input. Syntax errors are found during
+//! the rewrite and report positions in the original text.
+
+use anyhow::Result;
+
+use crate::value::Value;
+
+/// Arrays and objects nested deeper than this are rejected rather than
+/// risking the stack.
+const MAX_DEPTH: usize = 10_000;
+
+/// Parse every whitespace-separated value in relaxed JSON `text`.
+pub fn parse_documents(text: &str) -> Result<Vec<Value>> {
+    let strict = to_strict_json(text)?;
+    let mut values = Vec::new();
+    super::collect_values_from_buf(strict.as_bytes(), false, &mut values)?;
+    Ok(values)
+}
+
+/// Rewrite relaxed JSON as strict JSON with each top-level value on its own
+/// line.
+pub fn to_strict_json(text: &str) -> Result<String> {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let mut rewriter = Rewriter {
+        text,
+        pos: 0,
+        out: String::with_capacity(text.len()),
+    };
+    loop {
+        rewriter.skip_blank()?;
+        if rewriter.pos == text.len() {
+            return Ok(rewriter.out);
+        }
+        rewriter.value(0)?;
+        rewriter.out.push('\n');
+    }
+}
+
+struct Rewriter<'a> {
+    text: &'a str,
+    pos: usize,
+    out: String,
+}
+
+impl<'a> Rewriter<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    /// Skip whitespace and comments.
+    fn skip_blank(&mut self) -> Result<()> {
+        loop {
+            let rest = &self.text[self.pos..];
+            if let Some(c) = rest.chars().next()
+                && c.is_whitespace()
+            {
+                self.pos += c.len_utf8();
+            } else if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if let Some(body) = rest.strip_prefix("/*") {
+                match body.find("*/") {
+                    Some(end) => self.pos += end + 4,
+                    None => return Err(self.error_at(self.pos, "unterminated comment")),
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some(quote @ ('"' | '\'')) => self.string(quote),
+            Some('-' | '0'..='9') => self.number(),
+            Some(c) if is_ident_start(c) => {
+                let start = self.pos;
+                let word = self.ident();
+                match word {
+                    "true" | "false" | "null" | "NaN" | "Infinity" => {
+                        self.out.push_str(word);
+                        Ok(())
+                    }
+                    _ => Err(self.error_at(start, &format!("unexpected identifier '{word}'"))),
+                }
+            }
+            Some(c) => Err(self.error(&format!("unexpected character '{c}'"))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<()> {
+        self.pos += 1;
+        self.out.push('{');
+        loop {
+            self.skip_blank()?;
+            match self.peek() {
+                Some('}') => break,
+                Some(quote @ ('"' | '\'')) => self.string(quote)?,
+                Some(c) if is_ident_start(c) => {
+                    let key = self.ident();
+                    self.out.push('"');
+                    self.out.push_str(key);
+                    self.out.push('"');
+                }
+                Some(c) => return Err(self.error(&format!("expected object key, found '{c}'"))),
+                None => return Err(self.error("unterminated object")),
+            }
+            self.skip_blank()?;
+            if self.peek() != Some(':') {
+                return Err(self.error("expected ':' after object key"));
+            }
+            self.pos += 1;
+            self.out.push(':');
+            self.skip_blank()?;
+            self.value(depth + 1)?;
+            self.skip_blank()?;
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => break,
+                Some(c) => return Err(self.error(&format!("expected ',' or '}}', found '{c}'"))),
+                None => return Err(self.error("unterminated object")),
+            }
+            self.out.push(',');
+        }
+        if self.out.ends_with(',') {
+            self.out.pop();
+        }
+        self.pos += 1;
+        self.out.push('}');
+        Ok(())
+    }
+
+    fn array(&mut self, depth: usize) -> Result<()> {
+        self.pos += 1;
+        self.out.push('[');
+        loop {
+            self.skip_blank()?;
+            match self.peek() {
+                Some(']') => break,
+                None => return Err(self.error("unterminated array")),
+                _ => {}
+            }
+            self.value(depth + 1)?;
+            self.skip_blank()?;
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => break,
+                Some(c) => return Err(self.error(&format!("expected ',' or ']', found '{c}'"))),
+                None => return Err(self.error("unterminated array")),
+            }
+            self.out.push(',');
+        }
+        if self.out.ends_with(',') {
+            self.out.pop();
+        }
+        self.pos += 1;
+        self.out.push(']');
+        Ok(())
+    }
+
+    /// Copy a `"` or `'` quoted string as a double-quoted JSON string.
+    fn string(&mut self, quote: char) -> Result<()> {
+        let start = self.pos;
+        self.pos += 1;
+        self.out.push('"');
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error_at(start, "unterminated string"));
+            };
+            self.pos += c.len_utf8();
+            match c {
+                _ if c == quote => break,
+                '\\' => self.escape()?,
+                '"' => self.out.push_str("\\\""),
+                '\u{0}'..='\u{1f}' => {
+                    return Err(self.error_at(
+                        self.pos - 1,
+                        "control characters in strings must be escaped",
+                    ));
+                }
+                _ => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+        Ok(())
+    }
+
+    /// Copy the escape after a backslash; `\'` becomes a plain quote.
+    fn escape(&mut self) -> Result<()> {
+        let at = self.pos - 1;
+        match self.peek() {
+            Some('\'') => {
+                self.pos += 1;
+                self.out.push('\'');
+            }
+            Some(c @ ('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't')) => {
+                self.pos += 1;
+                self.out.push('\\');
+                self.out.push(c);
+            }
+            Some('u') => {
+                let hex = self.text.get(self.pos + 1..self.pos + 5);
+                if !hex.is_some_and(|h| h.bytes().all(|b| b.is_ascii_hexdigit())) {
+                    return Err(self.error_at(at, "invalid \\u escape"));
+                }
+                self.out.push_str(&self.text[at..self.pos + 5]);
+                self.pos += 5;
+            }
+            Some(c) => return Err(self.error_at(at, &format!("invalid escape '\\{c}'"))),
+            None => return Err(self.error_at(at, "unterminated string")),
+        }
+        Ok(())
+    }
+
+    /// Copy a JSON number, or `-Infinity`.
+    fn number(&mut self) -> Result<()> {
+        let start = self.pos;
+        let bytes = self.text.as_bytes();
+        let digits = |pos: &mut usize| {
+            let from = *pos;
+            while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+                *pos += 1;
+            }
+            *pos - from
+        };
+        let mut pos = start;
+        if bytes[pos] == b'-' {
+            pos += 1;
+            if self.text[pos..].starts_with("Infinity") {
+                self.pos = pos + "Infinity".len();
+                self.out.push_str("-Infinity");
+                return Ok(());
+            }
+        }
+        let int_start = pos;
+        match digits(&mut pos) {
+            0 => return Err(self.error_at(start, "invalid number")),
+            n if n > 1 && bytes[int_start] == b'0' => {
+                return Err(self.error_at(start, "numbers cannot have leading zeros"));
+            }
+            _ => {}
+        }
+        if bytes.get(pos) == Some(&b'.') {
+            pos += 1;
+            if digits(&mut pos) == 0 {
+                return Err(self.error_at(start, "invalid number"));
+            }
+        }
+        if matches!(bytes.get(pos), Some(b'e' | b'E')) {
+            pos += 1;
+            if matches!(bytes.get(pos), Some(b'+' | b'-')) {
+                pos += 1;
+            }
+            if digits(&mut pos) == 0 {
+                return Err(self.error_at(start, "invalid number"));
+            }
+        }
+        self.out.push_str(&self.text[start..pos]);
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn ident(&mut self) -> &'a str {
+        let text = self.text;
+        let start = self.pos;
+        while self.peek().is_some_and(is_ident_char) {
+            self.pos += 1;
+        }
+        &text[start..self.pos]
+    }
+
+    fn error(&self, msg: &str) -> anyhow::Error {
+        self.error_at(self.pos, msg)
+    }
+
+    /// An error at byte offset `pos`, reported as a 1-based line and
+    /// character column.
+    fn error_at(&self, pos: usize, msg: &str) -> anyhow::Error {
+        let before = &self.text[..pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+        anyhow::anyhow!("relaxed JSON line {line}, column {column}: {msg}")
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strict(text: &str) -> String {
+        to_strict_json(text).unwrap()
+    }
+
+    fn error(text: &str) -> String {
+        to_strict_json(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn comments() {
+        let text = "// header\n{\"a\": 1, /* inline */ \"b\": [2 /* x */]} // tail\n/* end */";
+        assert_eq!(strict(text), "{\"a\":1,\"b\":[2]}\n");
+    }
+
+    #[test]
+    fn trailing_commas() {
+        assert_eq!(strict("[1, 2, ]"), "[1,2]\n");
+        assert_eq!(
+            strict("{\"a\": [1,], b: {c: 2,},}"),
+            "{\"a\":[1],\"b\":{\"c\":2}}\n"
+        );
+    }
+
+    #[test]
+    fn quotes_and_keys() {
+        assert_eq!(
+            strict(r#"{name: 'it\'s "x"', $id_2: 'a\nb', "q": "don\'t"}"#),
+            r#"{"name":"it's \"x\"","$id_2":"a\nb","q":"don't"}"#.to_string() + "\n"
+        );
+    }
+
+    #[test]
+    fn comment_markers_inside_strings_are_kept() {
+        assert_eq!(
+            strict(r#"{"url": "http://x/*y*/", 'c': '// not a comment'}"#),
+            "{\"url\":\"http://x/*y*/\",\"c\":\"// not a comment\"}\n"
+        );
+    }
+
+    #[test]
+    fn multiple_values_one_per_line() {
+        assert_eq!(strict("1 'two' [3,]\n{}"), "1\n\"two\"\n[3]\n{}\n");
+        assert_eq!(strict("  // only a comment\n"), "");
+    }
+
+    #[test]
+    fn errors_point_into_original_text() {
+        assert_eq!(
+            error("{\n  // note\n  a: 1,\n  b: tru,\n}"),
+            "relaxed JSON line 4, column 6: unexpected identifier 'tru'"
+        );
+        assert_eq!(
+            error("[1, 2\n /* open"),
+            "relaxed JSON line 2, column 2: unterminated comment"
+        );
+        assert_eq!(
+            error("{é: 1}"),
+            "relaxed JSON line 1, column 2: expected object key, found 'é'"
+        );
+        assert_eq!(
+            error("['a\\q']"),
+            "relaxed JSON line 1, column 4: invalid escape '\\q'"
+        );
+        assert_eq!(
+            error("[1 2]"),
+            "relaxed JSON line 1, column 4: expected ',' or ']', found '2'"
+        );
+        assert_eq!(
+            error("{\"a\" 1}"),
+            "relaxed JSON line 1, column 6: expected ':' after object key"
+        );
+        assert_eq!(
+            error("  'open"),
+            "relaxed JSON line 1, column 3: unterminated string"
+        );
+        assert_eq!(
+            error("[01]"),
+            "relaxed JSON line 1, column 2: numbers cannot have leading zeros"
+        );
+        assert_eq!(
+            error("[,1]"),
+            "relaxed JSON line 1, column 2: unexpected character ','"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! YAML input (`--yaml-input`).
+//!
+//! Each `---`-separated document becomes one input value. Anchors, aliases and
+//! `<<` merge keys are expanded; mapping keys must be strings, and tagged
+//! values such as `!!binary` have no JSON equivalent and are rejected. Needs
+//! the `yaml` cargo feature.
+
+use anyhow::Result;
+
+use crate::value::Value;
+
+/// Whether this build can read YAML.
+pub const ENABLED: bool = cfg!(feature = "yaml");
+
+/// Parse every document in `text` into a [`Value`].
+#[cfg(feature = "yaml")]
+pub fn parse_documents(text: &str) -> Result<Vec<Value>> {
+    use serde::Deserialize;
+
+    if let Some(line) = binary_tag_line(text) {
+        anyhow::bail!("YAML line {line}: !!binary values have no JSON equivalent");
+    }
+    let mut docs = Vec::new();
+    for (i, de) in serde_yaml::Deserializer::from_str(text).enumerate() {
+        let mut doc = serde_yaml::Value::deserialize(de)
+            .map_err(|e| anyhow::anyhow!("YAML document {}: {e}", i + 1))?;
+        doc.apply_merge()
+            .map_err(|e| anyhow::anyhow!("YAML document {}: {e}", i + 1))?;
+        docs.push(convert(doc).map_err(|e| e.context(format!("YAML document {}", i + 1)))?);
+    }
+    Ok(docs)
+}
+
+/// Line number of the first `!!binary` tag. serde_yaml resolves the standard
+/// `!!` tags itself and hands binary scalars over as their base64 text, so this
+/// is found by scanning for the tag token where a tag can start.
+#[cfg(feature = "yaml")]
+fn binary_tag_line(text: &str) -> Option<usize> {
+    const TAGS: [&str; 2] = ["!!binary", "!<tag:yaml.org,2002:binary>"];
+    text.lines().enumerate().find_map(|(i, line)| {
+        let code = match line.find(" #") {
+            Some(end) => &line[..end],
+            None => line,
+        };
+        TAGS.iter()
+            .flat_map(|tag| code.match_indices(tag))
+            .any(|(at, tag)| {
+                let before = code[..at].chars().next_back();
+                let after = code[at + tag.len()..].chars().next();
+                matches!(
+                    before,
+                    None | Some(' ' | '\t' | '-' | ':' | ',' | '[' | '{')
+                ) && matches!(after, None | Some(' ' | '\t' | ',' | ']' | '}'))
+            })
+            .then_some(i + 1)
+    })
+}
+
+#[cfg(not(feature = "yaml"))]
+pub fn parse_documents(_text: &str) -> Result<Vec<Value>> {
+    anyhow::bail!("qj was built without YAML support (the `yaml` feature)")
+}
+
+#[cfg(feature = "yaml")]
+fn convert(value: serde_yaml::Value) -> Result<Value> {
+    use serde_yaml::Value as Yaml;
+    use std::sync::Arc;
+
+    Ok(match value {
+        Yaml::Null => Value::Null,
+        Yaml::Bool(b) => Value::Bool(b),
+        Yaml::Number(n) => match n.as_i64() {
+            Some(i) => Value::Int(i),
+            None => Value::Double(n.as_f64().unwrap_or(f64::NAN), None),
+        },
+        Yaml::String(s) => Value::String(s),
+        Yaml::Sequence(items) => Value::Array(Arc::new(
+            items.into_iter().map(convert).collect::<Result<_>>()?,
+        )),
+        Yaml::Mapping(map) => {
+            let mut entries = Vec::with_capacity(map.len());
+            for (key, val) in map {
+                let Yaml::String(key) = key else {
+                    anyhow::bail!("mapping keys must be strings, found {}", yaml_kind(&key));
+                };
+                entries.push((key, convert(val)?));
+            }
+            Value::Object(Arc::new(entries))
+        }
+        Yaml::Tagged(tagged) => anyhow::bail!("unsupported YAML tag {}", tagged.tag),
+    })
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_kind(value: &serde_yaml::Value) -> &'static str {
+    use serde_yaml::Value as Yaml;
+
+    match value {
+        Yaml::Null => "null",
+        Yaml::Bool(_) => "boolean",
+        Yaml::Number(_) => "number",
+        Yaml::String(_) => "string",
+        Yaml::Sequence(_) => "sequence",
+        Yaml::Mapping(_) => "mapping",
+        Yaml::Tagged(_) => "tagged value",
+    }
+}
+
+#[cfg(all(test, feature = "yaml"))]
+mod tests {
+    use super::*;
+    use crate::output::format_compact;
+
+    fn compact_docs(text: &str) -> Vec<String> {
+        parse_documents(text)
+            .unwrap()
+            .iter()
+            .map(format_compact)
+            .collect()
+    }
+
+    #[test]
+    fn multiple_documents() {
+        let text = "a: 1\n---\n- x\n- y\n---\nplain\n";
+        assert_eq!(
+            compact_docs(text),
+            vec![r#"{"a":1}"#, r#"["x","y"]"#, r#""plain""#]
+        );
+    }
+
+    #[test]
+    fn scalars_map_to_json_types() {
+        let text = "i: 42\nf: 1.5\nb: yes\nt: true\nn: ~\ns: \"42\"\nq: '007'\nv: 1.10\n";
+        assert_eq!(
+            compact_docs(text),
+            vec![r#"{"i":42,"f":1.5,"b":"yes","t":true,"n":null,"s":"42","q":"007","v":1.1}"#]
+        );
+    }
+
+    #[test]
+    fn anchors_aliases_and_merge_keys() {
+        let text = "base: &b {x: 1, y: 2}\ncopy: *b\nmerged:\n  <<: *b\n  y: 3\n";
+        assert_eq!(
+            compact_docs(text),
+            vec![r#"{"base":{"x":1,"y":2},"copy":{"x":1,"y":2},"merged":{"y":3,"x":1}}"#]
+        );
+    }
+
+    #[test]
+    fn rejects_non_string_keys_and_tags() {
+        let err = parse_documents("1: a\n").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "YAML document 1: mapping keys must be strings, found number"
+        );
+        let err = parse_documents("a: ok\n---\nb: !!binary aGk=\n").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "YAML line 3: !!binary values have no JSON equivalent"
+        );
+        let err = parse_documents("a: !Ref other\n").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "YAML document 1: unsupported YAML tag !Ref"
+        );
+        // The tag text inside a value isn't a tag
+        assert_eq!(
+            compact_docs("a: see!!binary\n"),
+            vec![r#"{"a":"see!!binary"}"#]
+        );
+    }
+}
@@ -1,3 +1,5 @@
+pub mod build_info;
+pub mod complete;
 pub mod decompress;
 pub mod filter;
 pub mod flat_eval;
@@ -5,5 +7,6 @@ pub mod flat_value;
 pub mod input;
 pub mod output;
 pub mod parallel;
+pub mod run;
 pub mod simdjson;
 pub mod value;
@@ -1,9 +1,10 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueHint};
 use mimalloc::MiMalloc;
 use std::io::{self, BufWriter, IsTerminal, Read, Write};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
 // ---------------------------------------------------------------------------
 // File I/O helpers (compression-aware)
@@ -50,6 +51,12 @@ fn collect_file_values(
     if qj::decompress::is_compressed(path) {
         let bytes = qj::decompress::decompress_file(path)?;
         qj::input::collect_values_from_buf(&bytes, force_jsonl, values)
+    } else if qj::parallel::ndjson::slurp_ndjson_file(
+        std::path::Path::new(path),
+        force_jsonl,
+        values,
+    )? {
+        Ok(())
     } else {
         let (padded, json_len) = qj::simdjson::read_padded_file(std::path::Path::new(path))
             .with_context(|| format!("failed to read file: {path}"))?;
@@ -57,6 +64,58 @@ fn collect_file_values(
     }
 }
 
+/// Lazy `input`/`inputs` values over the input files, or stdin if there are none.
+fn lazy_inputs(
+    input_files: &[String],
+    force_jsonl: bool,
+    raw_delimiter: Option<u8>,
+) -> qj::input::LazyInputs {
+    let files = if input_files.is_empty() {
+        vec![qj::input::InputFile::Stdin]
+    } else {
+        input_files
+            .iter()
+            .map(|p| qj::input::InputFile::Path(p.clone()))
+            .collect()
+    };
+    qj::input::LazyInputs::new(files, force_jsonl, raw_delimiter)
+}
+
+/// Adapt lazily read inputs to the evaluator's pull-based input source,
+/// moving the location uncaught errors are reported at along with them.
+fn into_input_source(mut inputs: qj::input::LazyInputs) -> qj::filter::InputSource {
+    Box::new(move |env| {
+        let value = inputs.next();
+        if let Some((source, line)) = inputs.location() {
+            env.set_input_location(source, line);
+        }
+        value
+    })
+}
+
+/// Fold the status of a batch of NDJSON output into the running state: the
+/// `-e` status of its values, whether any of its lines was malformed (exit 2
+/// once all input has been processed) and whether its last line raised an
+/// uncaught error (exit 5, whatever `-e` would say).
+fn record_ndjson_status(
+    status: qj::parallel::ndjson::OutputStatus,
+    had_output: &mut bool,
+    last_was_falsy: &mut bool,
+    had_bad_input: &mut bool,
+    had_error: &mut ErrorStatus,
+) {
+    if status.had_output {
+        *had_output = true;
+        *last_was_falsy = status.last_was_falsy;
+    }
+    if status.bad_lines > 0 {
+        *had_bad_input = true;
+    }
+    if let Some(failed) = status.last_line_failed {
+        had_error.input_evaluated(failed);
+    }
+}
+
 /// Read a file as a UTF-8 string, decompressing if needed.
 fn read_file_text(path: &str) -> Result<String> {
     if qj::decompress::is_compressed(path) {
@@ -67,6 +126,46 @@ fn read_file_text(path: &str) -> Result<String> {
     }
 }
 
+/// Parses one input's text into values for `--csv-input` and friends.
+type TextRecordParser = dyn Fn(&str) -> Result<Vec<qj::value::Value>>;
+
+/// Read every input file (or stdin) as text, paired with its name for errors.
+fn read_input_texts(input_files: &[String]) -> Result<Vec<(String, String)>> {
+    if input_files.is_empty() {
+        let mut buf = Vec::new();
+        io::stdin()
+            .read_to_end(&mut buf)
+            .context("failed to read stdin")?;
+        let text = String::from_utf8(buf).context("stdin is not valid UTF-8")?;
+        return Ok(vec![("<stdin>".to_string(), text)]);
+    }
+    input_files
+        .iter()
+        .map(|path| Ok((path.clone(), read_file_text(path)?)))
+        .collect()
+}
+
+/// Parse every input with `parse` (CSV/TSV rows, YAML documents or relaxed
+/// JSON values). A malformed input is reported and skipped, setting
+/// `had_error`, like an unparseable JSON document.
+fn read_text_records(
+    input_files: &[String],
+    parse: &TextRecordParser,
+    had_error: &mut ErrorStatus,
+) -> Result<Vec<qj::value::Value>> {
+    let mut records = Vec::new();
+    for (name, text) in read_input_texts(input_files)? {
+        match parse(&text) {
+            Ok(parsed) => records.extend(parsed),
+            Err(e) => {
+                eprintln!("qj: error (at {name}): {e:#}");
+                had_error.input_rejected();
+            }
+        }
+    }
+    Ok(records)
+}
+
 /// Extract RS-delimited (RFC 7464) JSON values from a buffer.
 /// Each segment after an RS byte (0x1E) up to the next RS or end of buffer
 /// is parsed as a JSON value. Segments that fail to parse are silently skipped
@@ -103,32 +202,6 @@ fn collect_seq_values(buf: &[u8], values: &mut Vec<qj::value::Value>) -> Result<
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-/// Detect P-core count on Apple Silicon via sysctlbyname(3), fall back to available_parallelism.
-/// Only runs on aarch64 macOS — Intel Macs don't have P/E core distinction.
-fn default_thread_count() -> usize {
-    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-    {
-        let mut val: i32 = 0;
-        let mut size = std::mem::size_of::<i32>();
-        let name = b"hw.perflevel0.logicalcpu\0";
-        let ret = unsafe {
-            libc::sysctlbyname(
-                name.as_ptr() as *const libc::c_char,
-                &mut val as *mut i32 as *mut libc::c_void,
-                &mut size,
-                std::ptr::null_mut(),
-                0,
-            )
-        };
-        if ret == 0 && val > 0 {
-            return val as usize;
-        }
-    }
-    std::thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(4)
-}
-
 #[derive(Parser)]
 #[command(
     name = "qj",
@@ -141,12 +214,21 @@ struct Cli {
     filter: Option<String>,
 
     /// Input file(s); defaults to stdin
+    #[arg(value_hint = ValueHint::FilePath)]
     files: Vec<String>,
 
     /// Compact output (no pretty-printing)
     #[arg(short = 'c', long = "compact-output")]
     compact: bool,
 
+    /// Exactly one compact JSON value per line, for line-oriented pipelines
+    #[arg(long = "jsonl-output", conflicts_with_all = ["raw", "raw_output0", "join_output", "seq"])]
+    jsonl_output: bool,
+
+    /// Print each output object as one line of logfmt `key=value` pairs
+    #[arg(long = "logfmt", conflicts_with_all = ["raw", "raw_output0", "join_output", "jsonl_output"])]
+    logfmt: bool,
+
     /// Raw output (strings without quotes)
     #[arg(short = 'r', long = "raw-output")]
     raw: bool,
@@ -155,6 +237,15 @@ struct Cli {
     #[arg(long = "raw-output0")]
     raw_output0: bool,
 
+    /// Print array outputs as their elements joined with SEP, as `join(SEP)`
+    /// would (implies -r)
+    #[arg(
+        long = "join",
+        value_name = "SEP",
+        conflicts_with_all = ["compact", "raw_output0", "jsonl_output", "logfmt"]
+    )]
+    join: Option<String>,
+
     /// Escape non-ASCII characters to \uXXXX sequences
     #[arg(short = 'a', long = "ascii-output")]
     ascii_output: bool,
@@ -163,6 +254,30 @@ struct Cli {
     #[arg(long = "unbuffered")]
     unbuffered: bool,
 
+    /// Write output to FILE instead of stdout; FILE is replaced only once qj
+    /// finishes without error. `-o -` means stdout
+    #[arg(short = 'o', long = "output", value_name = "FILE", value_hint = ValueHint::FilePath)]
+    output: Option<String>,
+
+    /// Rewrite each input file with the filter's output for that file; a file
+    /// is left as it was if the filter fails on it
+    #[arg(short = 'i', long = "in-place", conflicts_with_all = ["output", "slurp", "null_input", "csv_input", "tsv_input", "yaml_input", "relaxed", "raw_input", "raw_input0", "seq", "stream", "stream_errors"])]
+    in_place: bool,
+
+    /// Run again whenever an input file changes, until interrupted; a
+    /// terminal is cleared before each run
+    #[arg(long = "watch", conflicts_with_all = ["output", "in_place"])]
+    watch: bool,
+
+    /// With --watch, how often to check the input files, in milliseconds
+    #[arg(long = "watch-interval", value_name = "MS", default_value_t = 250)]
+    watch_interval: u64,
+
+    /// With --watch, the line written between runs when stdout is not a
+    /// terminal
+    #[arg(long = "watch-separator", value_name = "TEXT", default_value = "---")]
+    watch_separator: String,
+
     /// Use tab for indentation
     #[arg(long)]
     tab: bool,
@@ -203,6 +318,36 @@ struct Cli {
     #[arg(short = 'R', long = "raw-input")]
     raw_input: bool,
 
+    /// Read NUL-delimited raw strings instead of lines (implies -R); with -s,
+    /// the records are slurped into an array of strings
+    #[arg(long = "raw-input0")]
+    raw_input0: bool,
+
+    /// Read CSV records (RFC 4180); the first row names the fields of each
+    /// record object
+    #[arg(long = "csv-input", conflicts_with_all = ["tsv_input", "raw_input", "raw_input0", "seq", "stream", "stream_errors"])]
+    csv_input: bool,
+
+    /// Read tab-separated records; the first row names the fields of each
+    /// record object
+    #[arg(long = "tsv-input", conflicts_with_all = ["raw_input", "raw_input0", "seq", "stream", "stream_errors"])]
+    tsv_input: bool,
+
+    /// Read YAML documents (separated by ---) as input values (needs the
+    /// `yaml` build feature)
+    #[arg(long = "yaml-input", conflicts_with_all = ["csv_input", "tsv_input", "raw_input", "raw_input0", "seq", "stream", "stream_errors", "jsonl"])]
+    yaml_input: bool,
+
+    /// Accept relaxed JSON: comments, trailing commas, single-quoted strings
+    /// and unquoted object keys
+    #[arg(long = "relaxed", conflicts_with_all = ["csv_input", "tsv_input", "yaml_input", "raw_input", "raw_input0", "seq", "stream", "stream_errors", "jsonl"])]
+    relaxed: bool,
+
+    /// With --csv-input/--tsv-input, turn fields that look like JSON numbers
+    /// into numbers
+    #[arg(long = "csv-numbers")]
+    csv_numbers: bool,
+
     /// Sort object keys
     #[arg(short = 'S', long = "sort-keys")]
     sort_keys: bool,
@@ -227,29 +372,90 @@ struct Cli {
     #[arg(long = "argjson", num_args = 2, value_names = ["NAME", "VALUE"], action = clap::ArgAction::Append)]
     argjson: Vec<String>,
 
+    /// Bind $name to VALUE parsed as a JSON number
+    #[arg(long = "argnum", num_args = 2, value_names = ["NAME", "VALUE"], action = clap::ArgAction::Append)]
+    argnum: Vec<String>,
+
+    /// Bind $name to a boolean; VALUE is true, false, 1 or 0
+    #[arg(long = "argbool", num_args = 2, value_names = ["NAME", "VALUE"], action = clap::ArgAction::Append)]
+    argbool: Vec<String>,
+
+    /// Bind $name to the environment variable PREFIXname, as a string, for
+    /// every variable starting with PREFIX (--arg and friends take precedence)
+    #[arg(long = "env-args", value_name = "PREFIX", action = clap::ArgAction::Append)]
+    env_args: Vec<String>,
+
     /// Bind $NAME to raw string contents of FILE
-    #[arg(long = "rawfile", num_args = 2, value_names = ["NAME", "FILE"], action = clap::ArgAction::Append)]
+    #[arg(long = "rawfile", num_args = 2, value_names = ["NAME", "FILE"], value_hint = ValueHint::FilePath, action = clap::ArgAction::Append)]
     rawfile: Vec<String>,
 
+    /// Bind $NAME to the contents of FILE, which may be binary, as a base64 string
+    #[arg(long = "base64file", num_args = 2, value_names = ["NAME", "FILE"], value_hint = ValueHint::FilePath, action = clap::ArgAction::Append)]
+    base64file: Vec<String>,
+
     /// Bind $NAME to array of JSON values parsed from FILE
-    #[arg(long = "slurpfile", num_args = 2, value_names = ["NAME", "FILE"], action = clap::ArgAction::Append)]
+    #[arg(long = "slurpfile", num_args = 2, value_names = ["NAME", "FILE"], value_hint = ValueHint::FilePath, action = clap::ArgAction::Append)]
     slurpfile: Vec<String>,
 
+    /// Bind $NAME to the single JSON value in FILE
+    #[arg(long = "argfile", num_args = 2, value_names = ["NAME", "FILE"], value_hint = ValueHint::FilePath, action = clap::ArgAction::Append)]
+    argfile: Vec<String>,
+
     /// Read filter from file instead of first argument
-    #[arg(short = 'f', long = "from-file", value_name = "FILE")]
+    #[arg(short = 'f', long = "from-file", value_name = "FILE", value_hint = ValueHint::FilePath)]
     from_file: Option<String>,
 
+    /// Validate the filter without reading input: report calls to undefined
+    /// functions, builtins given the wrong number of arguments and unbound
+    /// $variables, and exit 3 if there are any
+    #[arg(long = "check", visible_alias = "dry-run")]
+    check: bool,
+
     /// Print timing breakdown to stderr (for profiling)
     #[arg(long = "debug-timing", hide = true)]
     debug_timing: bool,
 
-    /// Number of threads for parallel NDJSON processing
+    /// Print per-filter-node timing and output counts to stderr
+    #[arg(long = "profile", hide = true)]
+    profile: bool,
+
+    /// Report NDJSON and array parallel/sequential scheduling to stderr
+    #[arg(long = "debug-parallel", hide = true)]
+    debug_parallel: bool,
+
+    /// For debugging only: restrict how filters run. passthrough-off skips
+    /// the raw single-document passthroughs, flat also skips the NDJSON fast
+    /// paths, and tree also skips flat evaluation. Output should not change
+    #[arg(
+        long = "eval-mode",
+        value_name = "MODE",
+        value_parser = ["auto", "passthrough-off", "flat", "tree"],
+        default_value = "auto",
+        hide_short_help = true
+    )]
+    eval_mode: String,
+
+    /// Number of threads for parallel NDJSON, array and multi-file processing
     #[arg(long, value_name = "N")]
     threads: Option<usize>,
 
+    /// Print the qj and simdjson versions, SIMD kernel, thread default,
+    /// allocator and cargo features as JSON, for bug reports
+    #[arg(long = "build-info")]
+    build_info: bool,
+
     /// Library search path for jq modules (import/include)
-    #[arg(short = 'L', value_name = "DIR")]
+    #[arg(short = 'L', value_name = "DIR", value_hint = ValueHint::DirPath)]
     library_paths: Vec<String>,
+
+    /// Print a completion script for SHELL (bash, zsh, fish, powershell)
+    #[arg(long, value_name = "SHELL", hide = true)]
+    completions: Option<clap_complete::Shell>,
+
+    /// Print filter completions for PREFIX from the top-level keys of FILE;
+    /// called by the bash completion script
+    #[arg(long = "complete-filter", num_args = 2, value_names = ["PREFIX", "FILE"], hide = true)]
+    complete_filter: Vec<String>,
 }
 
 /// Check if a filter AST contains import/include/module statements.
@@ -270,6 +476,22 @@ fn main() -> Result<()> {
         libc::signal(libc::SIGPIPE, libc::SIG_DFL);
     }
 
+    // A failed write is reported here, once, whichever path ran into it and
+    // whatever error that path went on to return.
+    let write_failure = qj::output::WriteFailure::default();
+    let result = run(&write_failure);
+    if let Some(e) = write_failure.take() {
+        if e.kind() == io::ErrorKind::BrokenPipe {
+            // The reader went away: stop quietly, as SIGPIPE would.
+            std::process::exit(0);
+        }
+        eprintln!("qj: error writing output: {e}");
+        std::process::exit(2);
+    }
+    result
+}
+
+fn run(write_failure: &qj::output::WriteFailure) -> Result<()> {
     // Pre-scan for --args / --jsonargs: split argv before clap sees them.
     // Everything after --args or --jsonargs becomes positional string/JSON values.
     let raw_args: Vec<String> = std::env::args().collect();
@@ -291,12 +513,50 @@ fn main() -> Result<()> {
 
     let cli = Cli::parse_from(&clap_args);
 
+    if let Some(shell) = cli.completions {
+        let mut stdout = io::stdout().lock();
+        qj::complete::write_script(shell, &mut Cli::command(), &mut stdout)?;
+        return Ok(());
+    }
+    if let [prefix, file] = cli.complete_filter.as_slice() {
+        for candidate in qj::complete::filter_candidates(prefix, std::path::Path::new(file)) {
+            println!("{candidate}");
+        }
+        return Ok(());
+    }
+
     // Configure Rayon thread pool to use P-cores only on Apple Silicon.
     // E-cores add contention without throughput benefit for I/O-bound NDJSON work.
     rayon::ThreadPoolBuilder::new()
-        .num_threads(cli.threads.unwrap_or_else(default_thread_count))
+        .num_threads(
+            cli.threads
+                .unwrap_or_else(qj::parallel::default_thread_count),
+        )
         .build_global()
         .ok(); // Ignore error if pool already initialized (e.g., in tests)
+    qj::parallel::ndjson::set_debug_parallel(cli.debug_parallel);
+    qj::filter::set_eval_mode(match cli.eval_mode.as_str() {
+        "passthrough-off" => qj::filter::EvalMode::PassthroughOff,
+        "flat" => qj::filter::EvalMode::Flat,
+        "tree" => qj::filter::EvalMode::Tree,
+        _ => qj::filter::EvalMode::Auto,
+    });
+    if cli.build_info {
+        let config = qj::output::OutputConfig {
+            mode: if cli.compact {
+                qj::output::OutputMode::Compact
+            } else {
+                qj::output::OutputMode::Pretty
+            },
+            ..Default::default()
+        };
+        let mut stdout = io::stdout().lock();
+        qj::output::write_value(&mut stdout, &qj::build_info::build_info(), &config)?;
+        return Ok(());
+    }
+    if cli.profile {
+        qj::filter::profile::enable();
+    }
 
     // Resolve filter string and input files.
     // With --from-file, all positional args are input files.
@@ -305,6 +565,10 @@ fn main() -> Result<()> {
     let (filter_str, input_files) = if let Some(ref path) = cli.from_file {
         let filter_str = std::fs::read_to_string(path)
             .with_context(|| format!("failed to read filter file: {path}"))?;
+        if qj::filter::lexer::lex(&filter_str).is_ok_and(|tokens| tokens.is_empty()) {
+            eprintln!("qj: error: empty filter: {path} contains only whitespace and comments");
+            std::process::exit(3);
+        }
         let mut files = cli.files.clone();
         if let Some(ref f) = cli.filter {
             files.insert(0, f.clone());
@@ -362,9 +626,63 @@ fn main() -> Result<()> {
     } else {
         (filter, None)
     };
+    // Like jq, a `break` with no `label` of its name is a compile error.
+    // `--check` lists it along with everything else it finds.
+    if !cli.check {
+        let problems = qj::filter::check::unbound_labels(&filter);
+        if !problems.is_empty() {
+            for problem in &problems {
+                eprintln!("qj: error: {problem}");
+            }
+            let plural = if problems.len() == 1 { "" } else { "s" };
+            eprintln!("qj: {} compile error{plural}", problems.len());
+            std::process::exit(3);
+        }
+    }
+    // --check looks at the filter as written, before optimizing can drop
+    // dead branches that hold mistakes.
+    let unoptimized = cli.check.then(|| filter.clone());
+    // `$ENV.NAME` and `env.NAME` can't change during the run: fold them into
+    // literals so the fast paths treat them like any other constant. A module
+    // that defines its own `env` keeps the call.
+    let filter = match &module_loader {
+        Some((_, module_env)) if module_env.get_func("env", 0).is_some() => filter,
+        _ => qj::filter::fold::fold_env(&filter),
+    };
+    // Work that doesn't depend on the input is done once here rather than
+    // per record, and `.a.b.c` chains become single lookups.
+    let filter = qj::filter::optimize::optimize(&filter);
 
     // --stream-errors implies --stream behavior
     let effective_stream = cli.stream || cli.stream_errors;
+    // --raw-input0 implies --raw-input
+    let raw_input = cli.raw_input || cli.raw_input0;
+    // --csv-input / --tsv-input / --yaml-input / --relaxed: each input is
+    // parsed up front into record values (CSV/TSV rows, YAML documents or
+    // relaxed JSON values).
+    let csv_numbers = cli.csv_numbers;
+    let text_records: Option<Box<TextRecordParser>> = if cli.csv_input || cli.tsv_input {
+        let format = if cli.csv_input {
+            qj::input::csv::DelimitedFormat::Csv
+        } else {
+            qj::input::csv::DelimitedFormat::Tsv
+        };
+        Some(Box::new(move |text| {
+            qj::input::csv::parse_records(text, format, csv_numbers)
+        }))
+    } else if cli.yaml_input {
+        if !qj::input::yaml::ENABLED {
+            eprintln!(
+                "qj: error: --yaml-input is not available: qj was built without the `yaml` feature"
+            );
+            std::process::exit(2);
+        }
+        Some(Box::new(qj::input::yaml::parse_documents))
+    } else if cli.relaxed {
+        Some(Box::new(qj::input::relaxed::parse_documents))
+    } else {
+        None
+    };
 
     // --stream: wrap filter with `tostream |` for the common case (non-slurp, non-null-input).
     // For slurp and null-input, the expansion happens later at the value level.
@@ -382,51 +700,108 @@ fn main() -> Result<()> {
     } else {
         filter
     };
+    // --logfmt: only objects have a logfmt form, so anything else becomes a
+    // runtime error through the usual error reporting on every input path.
+    let filter = if cli.logfmt {
+        qj::filter::Filter::Pipe(
+            Box::new(filter),
+            Box::new(qj::filter::parse(
+                r#"if type == "object" then . else error("Cannot format \(type) (\(tojson)) as logfmt") end"#,
+            )?),
+        )
+    } else {
+        filter
+    };
+    // --join: arrays holding containers have no joined form. Hand those to
+    // `join` itself so they fail with its error, the same as `join(SEP)`.
+    let filter = if let Some(sep) = &cli.join {
+        qj::filter::Filter::Pipe(
+            Box::new(filter),
+            Box::new(qj::filter::parse(&format!(
+                r#"if type == "array" and any(.[]; type == "array" or type == "object") then join({}) else . end"#,
+                serde_json::to_string(sep)?
+            ))?),
+        )
+    } else {
+        filter
+    };
 
-    // Build environment from --arg / --argjson
-    // Variable names in the AST include the '$' prefix (e.g., "$name"),
-    // so we prepend '$' when binding.
+    // Build environment from --arg / --argjson / --argnum / --argbool /
+    // --env-args. As in jq, the first binding of a name wins; these are also
+    // $ARGS.named. Variable names in the AST include the '$' prefix (e.g.,
+    // "$name"), so we prepend '$' when binding.
     let mut env = if let Some((_, ref module_env)) = module_loader {
         module_env.clone()
     } else {
         qj::filter::Env::empty()
     };
-    for pair in cli.args.chunks(2) {
-        if pair.len() == 2 {
-            env = env.bind_var(
-                format!("${}", pair[0]),
-                qj::value::Value::String(pair[1].clone()),
-            );
+    let mut named_args: Vec<(String, qj::value::Value)> = Vec::new();
+    let typed_args: [(&str, &[String], ParseNamedArg); 4] = [
+        ("--arg", &cli.args, parse_arg),
+        ("--argjson", &cli.argjson, parse_argjson),
+        ("--argnum", &cli.argnum, parse_argnum),
+        ("--argbool", &cli.argbool, parse_argbool),
+    ];
+    for (flag, pairs, parse) in typed_args {
+        for pair in pairs.chunks(2) {
+            if pair.len() == 2 {
+                let val = parse(&pair[1]).unwrap_or_else(|reason| {
+                    eprintln!("qj: error: {flag} {}: {reason}", pair[0]);
+                    std::process::exit(2);
+                });
+                add_named_arg(&mut named_args, &pair[0], val);
+            }
         }
     }
-    for pair in cli.argjson.chunks(2) {
-        if pair.len() == 2 {
-            let padded = qj::simdjson::pad_buffer(pair[1].as_bytes());
-            let val = qj::simdjson::dom_parse_to_value(&padded, pair[1].len())
-                .with_context(|| format!("invalid JSON for --argjson {}: {}", pair[0], pair[1]))?;
-            env = env.bind_var(format!("${}", pair[0]), val);
-        }
+    for (name, val) in env_args(&cli.env_args) {
+        add_named_arg(&mut named_args, &name, val);
+    }
+    for (name, val) in &named_args {
+        env = env.bind_var(format!("${name}"), val.clone());
     }
+    // Values bound from files, which also go in `$ARGS.named`.
+    let mut file_values: Vec<(String, qj::value::Value)> = Vec::new();
     for pair in cli.rawfile.chunks(2) {
         if pair.len() == 2 {
-            let content = std::fs::read_to_string(&pair[1])
-                .with_context(|| format!("failed to read --rawfile {}: {}", pair[0], pair[1]))?;
-            env = env.bind_var(format!("${}", pair[0]), qj::value::Value::String(content));
+            let content = String::from_utf8(read_bound_file(&pair[1])).unwrap_or_else(|_| {
+                eprintln!(
+                    "qj: error: --rawfile {}: {} is not valid UTF-8 (use --base64file for binary data)",
+                    pair[0], pair[1]
+                );
+                std::process::exit(2);
+            });
+            file_values.push((pair[0].clone(), qj::value::Value::String(content)));
         }
     }
     for pair in cli.slurpfile.chunks(2) {
         if pair.len() == 2 {
-            let buf = std::fs::read(&pair[1])
-                .with_context(|| format!("failed to read --slurpfile {}: {}", pair[0], pair[1]))?;
+            let buf = read_bound_file(&pair[1]);
             let mut values = Vec::new();
             qj::input::collect_values_from_buf(&buf, false, &mut values)
                 .with_context(|| format!("failed to parse --slurpfile {}: {}", pair[0], pair[1]))?;
-            env = env.bind_var(
-                format!("${}", pair[0]),
-                qj::value::Value::Array(Arc::new(values)),
-            );
+            file_values.push((pair[0].clone(), qj::value::Value::Array(Arc::new(values))));
+        }
+    }
+    for pair in cli.base64file.chunks(2) {
+        if pair.len() == 2 {
+            use base64::Engine;
+            let encoded =
+                base64::engine::general_purpose::STANDARD.encode(read_bound_file(&pair[1]));
+            file_values.push((pair[0].clone(), qj::value::Value::String(encoded)));
         }
     }
+    for (name, val) in &file_values {
+        env = env.bind_var(format!("${name}"), val.clone());
+    }
+    let argfile_values: Vec<(String, qj::value::Value)> = cli
+        .argfile
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (pair[0].clone(), read_argfile(&pair[0], &pair[1])))
+        .collect();
+    for (name, val) in &argfile_values {
+        env = env.bind_var(format!("${name}"), val.clone());
+    }
 
     // Build $ARGS: {positional: [...], named: {...}}
     {
@@ -452,23 +827,9 @@ fn main() -> Result<()> {
                 .collect()
         };
 
-        let named_pairs: Vec<(String, qj::value::Value)> = {
-            let mut pairs = Vec::new();
-            for pair in cli.args.chunks(2) {
-                if pair.len() == 2 {
-                    pairs.push((pair[0].clone(), qj::value::Value::String(pair[1].clone())));
-                }
-            }
-            for pair in cli.argjson.chunks(2) {
-                if pair.len() == 2 {
-                    let padded = qj::simdjson::pad_buffer(pair[1].as_bytes());
-                    if let Ok(val) = qj::simdjson::dom_parse_to_value(&padded, pair[1].len()) {
-                        pairs.push((pair[0].clone(), val));
-                    }
-                }
-            }
-            pairs
-        };
+        let mut named_pairs = named_args;
+        named_pairs.extend(file_values);
+        named_pairs.extend(argfile_values);
 
         let args_obj = qj::value::Value::Object(Arc::new(vec![
             (
@@ -482,44 +843,106 @@ fn main() -> Result<()> {
         ]));
         env = env.bind_var("$ARGS".to_string(), args_obj);
     }
+    // Like jq, `$ENV` takes precedence over `--arg ENV ...`.
+    env = env.bind_var("$ENV".to_string(), qj::filter::env_object());
+
+    if let Some(filter) = unoptimized {
+        let problems = qj::filter::check::check(&filter, &env);
+        if problems.is_empty() {
+            return Ok(());
+        }
+        for problem in &problems {
+            eprintln!("qj: error: {problem}");
+        }
+        let plural = if problems.len() == 1 { "" } else { "s" };
+        eprintln!("qj: {} compile error{plural}", problems.len());
+        std::process::exit(3);
+    }
+
+    // -o FILE (but not `-o -`) sends output to a file instead of stdout.
+    let output_path = cli.output.as_deref().filter(|path| *path != "-");
 
     // Color: on by default for TTY, overridden by -C (force on) or -M (force off).
     // NO_COLOR env var (https://no-color.org/) disables color by default,
-    // but -C still overrides it (matches jq behavior).
+    // but -C still overrides it (matches jq behavior). Output to a file (-o,
+    // -i) is never a terminal.
     // Check before locking stdout.
     let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
     let use_color = if cli.monochrome {
         false
     } else if cli.color {
         true
-    } else if no_color_env {
+    } else if no_color_env || output_path.is_some() || cli.in_place {
         false
     } else {
         io::stdout().is_terminal()
     };
     let color_scheme = if use_color {
-        qj::output::ColorScheme::jq_default()
+        qj::output::ColorScheme::from_env()
     } else {
         qj::output::ColorScheme::none()
     };
 
-    let stdout = io::stdout().lock();
-    let mut out = BufWriter::with_capacity(128 * 1024, stdout);
+    let (mut output_file, sink): (Option<AtomicOutput>, Box<dyn Write>) = match output_path {
+        Some(path) => {
+            let (pending, file) = match AtomicOutput::create(path) {
+                Ok(created) => created,
+                Err(e) => {
+                    eprintln!("qj: error: Could not open {path} for writing: {e:#}");
+                    std::process::exit(2);
+                }
+            };
+            (Some(pending), Box::new(file))
+        }
+        None => (None, Box::new(io::stdout().lock())),
+    };
+    let sink = qj::output::TrackedWriter::new(sink, write_failure.clone());
+    let mut out = BufWriter::with_capacity(128 * 1024, sink);
+
+    // --jsonl-output wins over the pretty defaults (--tab, --indent); clap
+    // rejects the flags that would break one-value-per-line.
+    // --indent 0 is compact output, as in jq.
+    let compact = cli.compact || cli.jsonl_output || (cli.indent == 0 && !cli.tab);
 
-    // -j / --join-output implies raw output (matches jq behavior)
-    let config = if cli.raw || cli.raw_output0 || cli.join_output {
+    let pretty_indent = if cli.tab {
+        "\t".to_string()
+    } else {
+        " ".repeat(cli.indent as usize)
+    };
+
+    // -j / --join-output and --join imply raw output (matches jq behavior). Non-string
+    // values still print as JSON, pretty unless -c.
+    let config = if cli.logfmt {
         qj::output::OutputConfig {
-            mode: qj::output::OutputMode::Raw,
+            mode: qj::output::OutputMode::Logfmt,
             indent: String::new(),
             sort_keys: cli.sort_keys,
+            join_output: false,
+            color: qj::output::ColorScheme::none(),
+            null_separator: false,
+            ascii_output: cli.ascii_output,
+            unbuffered: cli.unbuffered,
+            seq: cli.seq,
+            join_separator: None,
+        }
+    } else if cli.raw || cli.raw_output0 || cli.join_output || cli.join.is_some() {
+        qj::output::OutputConfig {
+            mode: qj::output::OutputMode::Raw,
+            indent: if compact {
+                String::new()
+            } else {
+                pretty_indent
+            },
+            sort_keys: cli.sort_keys,
             join_output: cli.join_output,
             color: color_scheme,
             null_separator: cli.raw_output0,
             ascii_output: cli.ascii_output,
             unbuffered: cli.unbuffered,
             seq: cli.seq,
+            join_separator: cli.join.clone(),
         }
-    } else if cli.compact {
+    } else if compact {
         qj::output::OutputConfig {
             mode: qj::output::OutputMode::Compact,
             indent: String::new(),
@@ -530,15 +953,12 @@ fn main() -> Result<()> {
             ascii_output: cli.ascii_output,
             unbuffered: cli.unbuffered,
             seq: cli.seq,
+            join_separator: None,
         }
     } else {
         qj::output::OutputConfig {
             mode: qj::output::OutputMode::Pretty,
-            indent: if cli.tab {
-                "\t".to_string()
-            } else {
-                " ".repeat(cli.indent as usize)
-            },
+            indent: pretty_indent,
             sort_keys: cli.sort_keys,
             join_output: cli.join_output,
             color: color_scheme,
@@ -546,48 +966,229 @@ fn main() -> Result<()> {
             ascii_output: cli.ascii_output,
             unbuffered: cli.unbuffered,
             seq: cli.seq,
+            join_separator: None,
         }
     };
 
     // Detect passthrough-eligible patterns. Disable when semantic-changing
-    // flags are active (slurp, raw_input, sort_keys, join_output) or when
-    // color is enabled (passthrough bypasses the output formatter).
-    // Also disable when -e is active — we need full eval to inspect output values.
+    // flags are active (slurp, raw_input) or when color or -a is enabled
+    // (passthrough bypasses the output formatter). Only some paths can sort
+    // keys or write raw output. This only gates the single-document
+    // passthrough: the NDJSON and other parallel paths format every value
+    // with `config`, so -a, -S and indentation keep them in play.
     let passthrough = if cli.slurp
-        || cli.raw_input
-        || cli.sort_keys
-        || cli.join_output
+        || cli.profile
+        || qj::filter::eval_mode() != qj::filter::EvalMode::Auto
+        || cli.logfmt
+        || raw_input
         || use_color
         || cli.ascii_output
-        || cli.raw
         || cli.raw_output0
-        || cli.exit_status
+        || cli.join.is_some()
         || effective_stream
         || cli.seq
     {
         None
     } else {
-        qj::filter::passthrough_path(&filter).filter(|p| !p.requires_compact() || cli.compact)
+        let raw = cli.raw || cli.join_output;
+        qj::filter::passthrough_path(&filter).filter(|p| {
+            (!p.requires_compact() || compact)
+                && (!cli.sort_keys || p.supports_sort_keys())
+                && (!raw || p.supports_raw())
+        })
     };
 
     let uses_input = filter.uses_input_builtins();
+    if cli.in_place && input_files.is_empty() {
+        eprintln!("qj: error: --in-place needs input files to rewrite");
+        std::process::exit(2);
+    }
+    if cli.in_place && uses_input {
+        eprintln!("qj: error: --in-place cannot be used with input or inputs");
+        std::process::exit(2);
+    }
+    if cli.watch && input_files.is_empty() {
+        eprintln!("qj: error: --watch needs input files to watch");
+        std::process::exit(2);
+    }
+    let pipeline = Pipeline {
+        cli: &cli,
+        filter: &filter,
+        unwrapped_filter: &unwrapped_filter,
+        env: &env,
+        config: &config,
+        input_files: &input_files,
+        text_records: &text_records,
+        raw_input,
+        effective_stream,
+        passthrough: &passthrough,
+        uses_input,
+        write_failure,
+    };
+    if cli.watch {
+        return watch(&pipeline, &mut out);
+    }
+    let status = run_pipeline(&pipeline, &mut out)?;
+    if status.had_file_error {
+        // Flush buffered output from successfully processed files before
+        // exiting; -o output from a failed run is discarded.
+        let _ = out.flush();
+        drop(output_file);
+        report_profile(cli.profile);
+        std::process::exit(2);
+    }
+
+    out.flush()?;
+    drop(out);
+    report_profile(cli.profile);
+
+    if status.had_bad_input {
+        drop(output_file);
+        std::process::exit(2);
+    }
+    if status.had_error.failed() {
+        drop(output_file);
+        std::process::exit(5);
+    }
+    // The -e statuses below are not failures: the output is kept.
+    if let Some(pending) = output_file.take() {
+        pending.commit()?;
+    }
+
+    if cli.exit_status {
+        if !status.had_output {
+            std::process::exit(4);
+        }
+        if status.last_was_falsy {
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Everything one pass over the inputs needs. `--watch` makes several passes
+/// with the same pipeline.
+struct Pipeline<'a> {
+    cli: &'a Cli,
+    filter: &'a qj::filter::Filter,
+    unwrapped_filter: &'a Option<qj::filter::Filter>,
+    env: &'a qj::filter::Env,
+    config: &'a qj::output::OutputConfig,
+    input_files: &'a [String],
+    text_records: &'a Option<Box<TextRecordParser>>,
+    raw_input: bool,
+    effective_stream: bool,
+    passthrough: &'a Option<qj::filter::PassthroughPath>,
+    uses_input: bool,
+    write_failure: &'a qj::output::WriteFailure,
+}
+
+/// What a pass over the inputs saw, which decides qj's exit status.
+struct RunStatus {
+    had_output: bool,
+    had_error: ErrorStatus,
+    last_was_falsy: bool,
+    /// An NDJSON line wasn't valid JSON (exit 2).
+    had_bad_input: bool,
+    /// An input file couldn't be read (exit 2).
+    had_file_error: bool,
+}
+
+/// The errors that make qj exit 5. Like jq, which exits with the status of
+/// its last input, an uncaught error only counts when the last input raised
+/// it, whether inputs are NDJSON lines, documents of one text or files. An
+/// input that is reported and skipped because it can't be parsed always
+/// counts.
+#[derive(Debug, Default, Clone, Copy)]
+struct ErrorStatus {
+    /// `None` until an input has been evaluated.
+    last_input_failed: Option<bool>,
+    /// Any input raised an uncaught error, for `--in-place`.
+    any_input_failed: bool,
+    input_rejected: bool,
+}
+
+impl ErrorStatus {
+    /// Record whether the input just evaluated raised an uncaught error.
+    fn input_evaluated(&mut self, failed: bool) {
+        self.last_input_failed = Some(failed);
+        self.any_input_failed |= failed;
+    }
+
+    /// Record an input that was reported and skipped, or a file `--in-place`
+    /// left as it was.
+    fn input_rejected(&mut self) {
+        self.input_rejected = true;
+    }
+
+    /// Fold in the errors of the inputs that followed.
+    fn merge(&mut self, next: ErrorStatus) {
+        self.last_input_failed = next.last_input_failed.or(self.last_input_failed);
+        self.any_input_failed |= next.any_input_failed;
+        self.input_rejected |= next.input_rejected;
+    }
+
+    /// Whether qj exits 5.
+    fn failed(&self) -> bool {
+        self.last_input_failed == Some(true) || self.input_rejected
+    }
+
+    /// Whether anything failed at all, so `--in-place` leaves a file as it was.
+    fn any_failed(&self) -> bool {
+        self.any_input_failed || self.input_rejected
+    }
+}
+
+/// Read the inputs, evaluate the filter and write the results to `out`.
+fn run_pipeline(p: &Pipeline, out: &mut impl Write) -> Result<RunStatus> {
+    let Pipeline {
+        cli,
+        filter,
+        unwrapped_filter,
+        env,
+        config,
+        input_files,
+        text_records,
+        raw_input,
+        effective_stream,
+        passthrough,
+        uses_input,
+        write_failure,
+    } = *p;
     let mut had_output = false;
-    let mut had_error = false;
+    let mut had_error = ErrorStatus::default();
     let mut last_was_falsy = false;
-
+    // Set when an NDJSON line isn't valid JSON: the line is reported and
+    // skipped, and qj exits 2 at the end.
+    let mut had_bad_input = false;
+    let mut had_file_error = false;
+    // The record separator `input`/`inputs` split raw input on.
+    let raw_delimiter = raw_input.then_some(if cli.raw_input0 { b'\0' } else { b'\n' });
+
+    if input_files.is_empty() && !cli.null_input {
+        env.set_input_name("<stdin>");
+    }
     if cli.null_input {
-        // With -n: collect all input values into the input queue (for input/inputs),
-        // then eval with null input.
-        if uses_input {
+        // With -n: eval once with null input; input/inputs read the inputs,
+        // lazily unless --seq or --stream needs them all up front.
+        let mut input_error = None;
+        let mut bad_input = None;
+        if let Some(parse) = text_records
+            && uses_input
+        {
+            let records = read_text_records(input_files, parse, &mut had_error)?;
+            qj::filter::eval::set_input_queue(env, records.into());
+        } else if uses_input && !cli.seq && !effective_stream {
+            let inputs = lazy_inputs(input_files, cli.jsonl, raw_delimiter);
+            input_error = Some(inputs.error());
+            bad_input = Some(inputs.bad_input());
+            qj::filter::eval::set_input_source(env, into_input_source(inputs));
+        } else if uses_input {
             let mut values = Vec::new();
             if !input_files.is_empty() {
-                for path in &input_files {
-                    if cli.raw_input {
-                        let content = read_file_text(path)?;
-                        for line in content.lines() {
-                            values.push(qj::value::Value::String(line.to_string()));
-                        }
-                    } else if cli.seq {
+                for path in input_files {
+                    if cli.seq {
                         let buf = if qj::decompress::is_compressed(path) {
                             qj::decompress::decompress_file(path)?
                         } else {
@@ -604,12 +1205,7 @@ fn main() -> Result<()> {
                 io::stdin()
                     .read_to_end(&mut buf)
                     .context("failed to read stdin")?;
-                if cli.raw_input {
-                    let text = std::str::from_utf8(&buf).context("stdin is not valid UTF-8")?;
-                    for line in text.lines() {
-                        values.push(qj::value::Value::String(line.to_string()));
-                    }
-                } else if cli.seq {
+                if cli.seq {
                     collect_seq_values(&buf, &mut values)?;
                 } else {
                     qj::input::strip_bom(&mut buf);
@@ -622,20 +1218,154 @@ fn main() -> Result<()> {
                 values
             };
             use std::collections::VecDeque;
-            qj::filter::eval::set_input_queue(VecDeque::from(values));
+            qj::filter::eval::set_input_queue(env, VecDeque::from(values));
         }
         let input = qj::value::Value::Null;
         eval_and_output(
-            &filter,
+            filter,
             &input,
-            &env,
-            &mut out,
-            &config,
+            env,
+            out,
+            config,
             &mut had_output,
             &mut had_error,
             &mut last_was_falsy,
         );
-    } else if cli.raw_input {
+        had_bad_input |= bad_input.is_some_and(|b| b.get());
+        if let Some(err) = input_error.and_then(|e| e.borrow_mut().take()) {
+            out.flush()?;
+            return Err(err);
+        }
+    } else if let Some(parse) = text_records {
+        // --csv-input / --tsv-input / --yaml-input / --relaxed: each record is processed
+        // like an NDJSON line (in parallel when the filter allows); -s
+        // collects them into one array.
+        let records = read_text_records(input_files, parse, &mut had_error)?;
+        if cli.slurp {
+            let input = qj::value::Value::Array(Arc::new(records));
+            eval_and_output(
+                filter,
+                &input,
+                env,
+                out,
+                config,
+                &mut had_output,
+                &mut had_error,
+                &mut last_was_falsy,
+            );
+        } else if uses_input {
+            qj::filter::eval::set_input_queue(env, records.into());
+            while let Some(input) = qj::filter::eval::next_input(env) {
+                eval_and_output(
+                    filter,
+                    &input,
+                    env,
+                    out,
+                    config,
+                    &mut had_output,
+                    &mut had_error,
+                    &mut last_was_falsy,
+                );
+            }
+        } else {
+            let line_config = qj::output::OutputConfig {
+                mode: qj::output::OutputMode::Compact,
+                ..Default::default()
+            };
+            let mut ndjson = Vec::new();
+            for record in &records {
+                qj::output::write_value(&mut ndjson, record, &line_config)?;
+            }
+            let (output, status, errs) =
+                qj::parallel::ndjson::process_ndjson_with_status(&ndjson, filter, config, env)
+                    .context("failed to process delimited records")?;
+            qj::output::write_formatted(out, &output, config)?;
+            record_ndjson_status(
+                status,
+                &mut had_output,
+                &mut last_was_falsy,
+                &mut had_bad_input,
+                &mut had_error,
+            );
+            if !errs.is_empty() {
+                std::io::Write::write_all(&mut std::io::stderr(), &errs)?;
+            }
+        }
+    } else if uses_input && !cli.slurp && !cli.seq && !cli.stream_errors {
+        // input/inputs: values (or raw records under -R) are read on demand
+        // and shared between the main loop and the builtins, so each value
+        // `input` doesn't consume runs the filter once, and huge NDJSON
+        // inputs are never held in memory at once.
+        let inputs = lazy_inputs(input_files, cli.jsonl, raw_delimiter);
+        let input_error = inputs.error();
+        let bad_input = inputs.bad_input();
+        qj::filter::eval::set_input_source(env, into_input_source(inputs));
+        while !write_failure.is_set()
+            && let Some(input) = qj::filter::eval::next_input(env)
+        {
+            eval_and_output(
+                filter,
+                &input,
+                env,
+                out,
+                config,
+                &mut had_output,
+                &mut had_error,
+                &mut last_was_falsy,
+            );
+        }
+        had_bad_input |= bad_input.get();
+        if let Some(err) = input_error.borrow_mut().take() {
+            out.flush()?;
+            return Err(err);
+        }
+    } else if cli.raw_input0 {
+        // --raw-input0: NUL-delimited records; -s collects them into an array
+        let mut texts = Vec::new();
+        if input_files.is_empty() {
+            let mut buf = Vec::new();
+            io::stdin()
+                .read_to_end(&mut buf)
+                .context("failed to read stdin")?;
+            texts.push(String::from_utf8(buf).context("stdin is not valid UTF-8")?);
+        } else {
+            for path in input_files {
+                env.set_input_name(path);
+                texts.push(read_file_text(path)?);
+            }
+        }
+        let records = texts.iter().flat_map(|text| nul_records(text));
+        if cli.slurp {
+            let records = records
+                .map(|r| qj::value::Value::String(r.to_string()))
+                .collect();
+            let input = qj::value::Value::Array(Arc::new(records));
+            eval_and_output(
+                filter,
+                &input,
+                env,
+                out,
+                config,
+                &mut had_output,
+                &mut had_error,
+                &mut last_was_falsy,
+            );
+        } else {
+            for record in records {
+                let input = qj::value::Value::String(record.to_string());
+                eval_and_output(
+                    filter,
+                    &input,
+                    env,
+                    out,
+                    config,
+                    &mut had_output,
+                    &mut had_error,
+                    &mut last_was_falsy,
+                );
+            }
+        }
+    } else if raw_input {
         // --raw-input: read lines as strings instead of parsing JSON
         if input_files.is_empty() {
             let mut buf = Vec::new();
@@ -646,10 +1376,10 @@ fn main() -> Result<()> {
             process_raw_input(
                 text,
                 cli.slurp,
-                &filter,
-                &env,
-                &mut out,
-                &config,
+                filter,
+                env,
+                out,
+                config,
                 &mut had_output,
                 &mut had_error,
                 &mut last_was_falsy,
@@ -658,31 +1388,33 @@ fn main() -> Result<()> {
             // --raw-input --slurp with files: concatenate all file contents
             // into a single string (matches jq -Rs behavior)
             let mut all_text = String::new();
-            for path in &input_files {
+            for path in input_files {
+                env.set_input_name(path);
                 let content = read_file_text(path)?;
                 all_text.push_str(&content);
             }
             let input = qj::value::Value::String(all_text);
             eval_and_output(
-                &filter,
+                filter,
                 &input,
-                &env,
-                &mut out,
-                &config,
+                env,
+                out,
+                config,
                 &mut had_output,
                 &mut had_error,
                 &mut last_was_falsy,
             );
         } else {
-            for path in &input_files {
+            for path in input_files {
+                env.set_input_name(path);
                 let content = read_file_text(path)?;
                 process_raw_input(
                     &content,
                     false,
-                    &filter,
-                    &env,
-                    &mut out,
-                    &config,
+                    filter,
+                    env,
+                    out,
+                    config,
                     &mut had_output,
                     &mut had_error,
                     &mut last_was_falsy,
@@ -699,7 +1431,8 @@ fn main() -> Result<()> {
                 .context("failed to read stdin")?;
             collect_seq_values(&buf, &mut values)?;
         } else {
-            for path in &input_files {
+            for path in input_files {
+                env.set_input_name(path);
                 let buf = if qj::decompress::is_compressed(path) {
                     qj::decompress::decompress_file(path)?
                 } else {
@@ -711,23 +1444,37 @@ fn main() -> Result<()> {
         if cli.slurp {
             let input = qj::value::Value::Array(Arc::new(values));
             eval_and_output(
-                &filter,
+                filter,
                 &input,
-                &env,
-                &mut out,
-                &config,
+                env,
+                out,
+                config,
                 &mut had_output,
                 &mut had_error,
                 &mut last_was_falsy,
             );
+        } else if uses_input {
+            qj::filter::eval::set_input_queue(env, values.into());
+            while let Some(input) = qj::filter::eval::next_input(env) {
+                eval_and_output(
+                    filter,
+                    &input,
+                    env,
+                    out,
+                    config,
+                    &mut had_output,
+                    &mut had_error,
+                    &mut last_was_falsy,
+                );
+            }
         } else {
             for value in &values {
                 eval_and_output(
-                    &filter,
+                    filter,
                     value,
-                    &env,
-                    &mut out,
-                    &config,
+                    env,
+                    out,
+                    config,
                     &mut had_output,
                     &mut had_error,
                     &mut last_was_falsy,
@@ -746,7 +1493,8 @@ fn main() -> Result<()> {
             qj::input::strip_bom(&mut buf);
             bufs.push(buf);
         } else {
-            for path in &input_files {
+            for path in input_files {
+                env.set_input_name(path);
                 let buf = if qj::decompress::is_compressed(path) {
                     qj::decompress::decompress_file(path)?
                 } else {
@@ -767,11 +1515,11 @@ fn main() -> Result<()> {
                     Ok(value) => {
                         // Success: apply wrapped filter (tostream | user_filter)
                         eval_and_output(
-                            &filter,
+                            filter,
                             &value,
-                            &env,
-                            &mut out,
-                            &config,
+                            env,
+                            out,
+                            config,
                             &mut had_output,
                             &mut had_error,
                             &mut last_was_falsy,
@@ -783,9 +1531,9 @@ fn main() -> Result<()> {
                         eval_and_output(
                             error_filter,
                             &error_entry,
-                            &env,
-                            &mut out,
-                            &config,
+                            env,
+                            out,
+                            config,
                             &mut had_output,
                             &mut had_error,
                             &mut last_was_falsy,
@@ -794,18 +1542,51 @@ fn main() -> Result<()> {
                 }
             }
         }
+    } else if cli.slurp
+        && !effective_stream
+        && !input_files.is_empty()
+        && let Some(plan) = qj::parallel::aggregate::detect_group_plan(filter, env)
+        && let Some(grouped) = qj::parallel::aggregate::slurp_groups(input_files, cli.jsonl, &plan)
+    {
+        // --slurp into `group_by(f) | map(...)` or `unique_by(f)` over NDJSON
+        // files: group chunk by chunk in parallel instead of holding every
+        // record, then run the rest of the filter on the groups.
+        eval_and_output(
+            plan.rest(),
+            &grouped,
+            env,
+            out,
+            config,
+            &mut had_output,
+            &mut had_error,
+            &mut last_was_falsy,
+        );
     } else if cli.slurp {
-        // --slurp: collect all values into an array, eval once
+        // --slurp: collect all values into an array, eval once. NDJSON is
+        // parsed window by window so the raw text is never held whole.
         let mut values = Vec::new();
         if input_files.is_empty() {
-            let mut buf = Vec::new();
-            io::stdin()
-                .read_to_end(&mut buf)
-                .context("failed to read stdin")?;
-            qj::input::strip_bom(&mut buf);
-            qj::input::collect_values_from_buf(&buf, cli.jsonl, &mut values)?;
+            let mut stdin = io::stdin().lock();
+            let (is_ndjson, head) = if cli.jsonl {
+                (true, Vec::new())
+            } else {
+                qj::parallel::ndjson::sniff_ndjson(&mut stdin).context("failed to read stdin")?
+            };
+            if is_ndjson {
+                let mut reader = io::Cursor::new(head).chain(stdin);
+                qj::parallel::ndjson::slurp_ndjson_streaming(&mut reader, &mut values)
+                    .context("failed to process NDJSON from stdin")?;
+            } else {
+                let mut buf = head;
+                stdin
+                    .read_to_end(&mut buf)
+                    .context("failed to read stdin")?;
+                qj::input::strip_bom(&mut buf);
+                qj::input::collect_values_from_buf(&buf, cli.jsonl, &mut values)?;
+            }
         } else {
-            for path in &input_files {
+            for path in input_files {
+                env.set_input_name(path);
                 collect_file_values(path, cli.jsonl, &mut values)?;
             }
         }
@@ -816,11 +1597,11 @@ fn main() -> Result<()> {
         };
         let input = qj::value::Value::Array(Arc::new(values));
         eval_and_output(
-            &filter,
+            filter,
             &input,
-            &env,
-            &mut out,
-            &config,
+            env,
+            out,
+            config,
             &mut had_output,
             &mut had_error,
             &mut last_was_falsy,
@@ -837,57 +1618,58 @@ fn main() -> Result<()> {
             .iter()
             .all(|&b| matches!(b, b' ' | b'\t' | b'\r' | b'\n'));
         if !is_empty {
-            if !uses_input
-                && !cli.exit_status
-                && (cli.jsonl || qj::parallel::ndjson::is_ndjson(&buf))
-            {
-                let (output, ho, errs) =
-                    qj::parallel::ndjson::process_ndjson(&buf, &filter, &config, &env)
+            if cli.jsonl || qj::parallel::ndjson::is_ndjson(&buf) {
+                let (output, status, errs) =
+                    qj::parallel::ndjson::process_ndjson_with_status(&buf, filter, config, env)
                         .context("failed to process NDJSON from stdin")?;
-                out.write_all(&output)?;
-                had_output |= ho;
-                if !errs.is_empty() {
-                    // Always surface per-line errors to stderr (matching jq).
-                    // Only set had_error (exit 5) when no output was produced —
-                    // jq exits 0 for mixed success/error NDJSON.
-                    if !ho {
-                        had_error = true;
-                    }
-                    std::io::Write::write_all(&mut std::io::stderr(), &errs)?;
-                }
-            } else if uses_input {
-                // Collect all values; first becomes input, rest go to queue
-                let mut values = Vec::new();
-                qj::input::collect_values_from_buf(&buf, cli.jsonl, &mut values)?;
-                let mut queue: std::collections::VecDeque<_> = values.into();
-                let input = queue.pop_front().unwrap_or(qj::value::Value::Null);
-                qj::filter::eval::set_input_queue(queue);
-                eval_and_output(
-                    &filter,
-                    &input,
-                    &env,
-                    &mut out,
-                    &config,
+                qj::output::write_formatted(out, &output, config)?;
+                record_ndjson_status(
+                    status,
                     &mut had_output,
-                    &mut had_error,
                     &mut last_was_falsy,
+                    &mut had_bad_input,
+                    &mut had_error,
                 );
+                if !errs.is_empty() {
+                    std::io::Write::write_all(&mut std::io::stderr(), &errs)?;
+                }
             } else {
                 let json_len = buf.len();
                 let padded = qj::simdjson::pad_buffer(&buf);
                 let mut handled = false;
-                if let Some(pt) = &passthrough {
-                    handled = try_passthrough(&padded, json_len, pt, &mut out, &mut had_output)
-                        .context("passthrough failed")?;
+                if let Some(pt) = passthrough {
+                    handled = try_passthrough(
+                        &padded,
+                        json_len,
+                        pt,
+                        out,
+                        config,
+                        &mut had_output,
+                        &mut had_error,
+                        &mut last_was_falsy,
+                    )
+                    .context("passthrough failed")?;
+                }
+                if !handled {
+                    handled = try_parallel_array(
+                        &buf,
+                        filter,
+                        env,
+                        out,
+                        config,
+                        &mut had_output,
+                        &mut had_error,
+                        &mut last_was_falsy,
+                    )?;
                 }
                 if !handled {
                     process_padded(
                         &padded,
                         json_len,
-                        &filter,
-                        &env,
-                        &mut out,
-                        &config,
+                        filter,
+                        env,
+                        out,
+                        config,
                         &mut had_output,
                         &mut had_error,
                         &mut last_was_falsy,
@@ -897,86 +1679,228 @@ fn main() -> Result<()> {
         }
     } else {
         // files
-        if uses_input {
-            // Collect all values from all files; first becomes input, rest go to queue
-            let mut values = Vec::new();
-            for path in &input_files {
-                collect_file_values(path, cli.jsonl, &mut values)?;
-            }
-            let mut queue: std::collections::VecDeque<_> = values.into();
-            let input = queue.pop_front().unwrap_or(qj::value::Value::Null);
-            qj::filter::eval::set_input_queue(queue);
-            eval_and_output(
-                &filter,
-                &input,
-                &env,
-                &mut out,
-                &config,
-                &mut had_output,
-                &mut had_error,
-                &mut last_was_falsy,
-            );
+        let ctx = ProcessCtx {
+            passthrough,
+            force_jsonl: cli.jsonl,
+            filter,
+            env,
+            config,
+            debug_timing: cli.debug_timing,
+        };
+        // Several plain files and a filter with no cross-document state:
+        // evaluate a batch of files at a time in parallel, writing each
+        // file's buffered output in argv order. Files the parallel path
+        // declines (unreadable, NDJSON, compressed, ...) go through
+        // process_file in their turn.
+        let batch_size = if !cli.in_place
+            && !cli.debug_timing
+            && input_files.len() >= 2
+            && qj::parallel::files::can_eval_files(filter, config, env)
+        {
+            rayon::current_num_threads() * 4
         } else {
-            let ctx = ProcessCtx {
-                passthrough: &passthrough,
-                force_jsonl: cli.jsonl,
-                filter: &filter,
-                env: &env,
-                config: &config,
-                debug_timing: cli.debug_timing,
-            };
-            let mut had_file_error = false;
-            for path in &input_files {
-                match process_file(
-                    path,
-                    &ctx,
-                    &mut out,
-                    &mut had_output,
-                    &mut had_error,
-                    &mut last_was_falsy,
-                ) {
-                    Ok(()) => {}
-                    Err(e) => {
-                        // Strip the redundant anyhow context wrapping — just show root cause
-                        let root = e.root_cause();
+            1
+        };
+        for batch in input_files.chunks(batch_size) {
+            let mut parallel = if batch.len() >= 2 {
+                qj::parallel::files::eval_files(batch, filter, config, cli.jsonl)
+            } else {
+                Vec::new()
+            }
+            .into_iter();
+            for path in batch {
+                env.set_input_name(path);
+                if let Some(Some(file)) = parallel.next() {
+                    qj::output::write_formatted(out, &file.out, config)?;
+                    if file.had_output {
+                        had_output = true;
+                        last_was_falsy = file.last_was_falsy;
+                    }
+                    had_error.input_evaluated(file.error.is_some());
+                    if let Some(err) = file.error {
+                        env.set_input_line(file.line);
+                        eprintln!("{}", qj::output::format_uncaught_error(&err, env));
+                    }
+                    if write_failure.is_set() {
+                        break;
+                    }
+                    continue;
+                }
+                let result = if cli.in_place {
+                    rewrite_in_place(
+                        path,
+                        &ctx,
+                        &mut had_output,
+                        &mut had_error,
+                        &mut last_was_falsy,
+                        &mut had_bad_input,
+                    )
+                } else {
+                    process_file(
+                        path,
+                        &ctx,
+                        out,
+                        &mut had_output,
+                        &mut had_error,
+                        &mut last_was_falsy,
+                        &mut had_bad_input,
+                    )
+                };
+                if write_failure.is_set() {
+                    break;
+                }
+                if let Err(e) = result {
+                    // Strip the redundant anyhow context wrapping — just show root cause
+                    let root = e.root_cause();
+                    if cli.in_place {
+                        eprintln!("qj: error: Could not rewrite {path}: {root}");
+                    } else {
                         eprintln!("qj: error: Could not open file {path}: {root}");
-                        had_file_error = true;
                     }
+                    had_file_error = true;
                 }
             }
-            if had_file_error {
-                // Flush buffered output from successfully processed files before exiting
-                let _ = out.flush();
-                std::process::exit(2);
+            if write_failure.is_set() {
+                break;
             }
         }
     }
 
-    out.flush()?;
+    Ok(RunStatus {
+        had_output,
+        had_error,
+        last_was_falsy,
+        had_bad_input,
+        had_file_error,
+    })
+}
 
-    if had_error {
-        std::process::exit(5);
-    }
+/// Set by the SIGINT handler `--watch` installs, so Ctrl-C ends the loop
+/// with status 0.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-    if cli.exit_status {
-        if !had_output {
-            std::process::exit(4);
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// The modification time and size of each input file, or `None` while it is
+/// missing (an editor replacing the file).
+fn file_stamps(paths: &[String]) -> Vec<Option<(Option<SystemTime>, u64)>> {
+    paths
+        .iter()
+        .map(|path| {
+            std::fs::metadata(path)
+                .ok()
+                .map(|meta| (meta.modified().ok(), meta.len()))
+        })
+        .collect()
+}
+
+/// `--watch`: run the pipeline, then poll the input files and run it again
+/// whenever one changes. A failing run is reported on stderr and the loop
+/// keeps watching; Ctrl-C stops it.
+fn watch(p: &Pipeline, out: &mut impl Write) -> Result<()> {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+    let clear = io::stdout().is_terminal();
+    let interval = Duration::from_millis(p.cli.watch_interval.max(1));
+    let mut stamps = file_stamps(p.input_files);
+    let mut first = true;
+    loop {
+        if clear {
+            out.write_all(b"\x1b[2J\x1b[H")?;
+        } else if !first {
+            writeln!(out, "{}", p.cli.watch_separator)?;
         }
-        if last_was_falsy {
-            std::process::exit(1);
+        if let Err(e) = run_pipeline(p, out) {
+            eprintln!("qj: error: {e:#}");
+        }
+        out.flush()?;
+        first = false;
+        loop {
+            if INTERRUPTED.load(Ordering::Relaxed) || p.write_failure.is_set() {
+                return Ok(());
+            }
+            std::thread::sleep(interval);
+            let current = file_stamps(p.input_files);
+            if current != stamps {
+                stamps = current;
+                break;
+            }
         }
     }
+}
 
-    Ok(())
+/// `-o FILE` output, and each file rewritten by `-i`. Values are written to a
+/// temporary file in FILE's directory, which [`AtomicOutput::commit`] renames
+/// over FILE, so a run that fails part way leaves FILE as it was. Dropping it
+/// uncommitted (an error return) removes the temporary file.
+struct AtomicOutput {
+    temp: std::path::PathBuf,
+    dest: std::path::PathBuf,
+    committed: bool,
+}
+
+impl AtomicOutput {
+    fn create(dest: &str) -> Result<(AtomicOutput, std::fs::File)> {
+        let dest = std::path::PathBuf::from(dest);
+        let name = dest
+            .file_name()
+            .with_context(|| format!("{} is not a file name", dest.display()))?;
+        let temp = dest.with_file_name(format!(
+            ".{}.qj-{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        let file = std::fs::File::create(&temp)?;
+        // Keep an existing file's permissions across the rename.
+        if let Ok(meta) = std::fs::metadata(&dest) {
+            let _ = std::fs::set_permissions(&temp, meta.permissions());
+        }
+        let pending = AtomicOutput {
+            temp,
+            dest,
+            committed: false,
+        };
+        Ok((pending, file))
+    }
+
+    fn commit(mut self) -> Result<()> {
+        std::fs::rename(&self.temp, &self.dest)
+            .with_context(|| format!("failed to write {}", self.dest.display()))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
 // Core processing helpers
 // ---------------------------------------------------------------------------
 
+/// Print the `--profile` table to stderr.
+fn report_profile(enabled: bool) {
+    if enabled {
+        let _ = qj::filter::profile::report(&mut std::io::stderr().lock());
+    }
+}
+
 /// Evaluate a filter against an input value and write all outputs.
 /// After evaluation, checks for uncaught runtime errors and reports them
-/// to stderr (like jq's exit-code-5 behavior).
+/// to stderr (like jq's exit-code-5 behavior), at the current input location.
 #[allow(clippy::too_many_arguments)]
 fn eval_and_output(
     filter: &qj::filter::Filter,
@@ -985,8 +1909,36 @@ fn eval_and_output(
     out: &mut impl Write,
     config: &qj::output::OutputConfig,
     had_output: &mut bool,
-    had_error: &mut bool,
+    had_error: &mut ErrorStatus,
+    last_was_falsy: &mut bool,
+) {
+    eval_and_output_located(
+        filter,
+        input,
+        env,
+        out,
+        config,
+        had_output,
+        had_error,
+        last_was_falsy,
+        || {},
+    );
+}
+
+/// [`eval_and_output`], calling `locate` to set the input line before an
+/// uncaught error is reported. Counting lines is left until then so that
+/// large documents don't pay for it.
+#[allow(clippy::too_many_arguments)]
+fn eval_and_output_located(
+    filter: &qj::filter::Filter,
+    input: &qj::value::Value,
+    env: &qj::filter::Env,
+    out: &mut impl Write,
+    config: &qj::output::OutputConfig,
+    had_output: &mut bool,
+    had_error: &mut ErrorStatus,
     last_was_falsy: &mut bool,
+    locate: impl FnOnce(),
 ) {
     let mut nul_error = false;
     let mut write_failed = false;
@@ -1006,25 +1958,18 @@ fn eval_and_output(
         *had_output = true;
         if qj::output::write_value(out, &v, config).is_err() {
             write_failed = true;
+            qj::filter::eval::stop_evaluation(env);
         }
     });
     if nul_error {
-        *had_error = true;
         eprintln!("qj: error: Cannot dump a string containing NUL with --raw-output0 option");
     }
     // Check for uncaught runtime errors
-    if let Some(err) = qj::filter::eval::take_last_error() {
-        *had_error = true;
-        let msg = format_error(&err);
-        eprintln!("qj: error: {msg}");
-    }
-}
-
-/// Format an error value for display on stderr.
-fn format_error(err: &qj::value::Value) -> String {
-    match err {
-        qj::value::Value::String(s) => s.clone(),
-        other => other.short_desc(),
+    let uncaught = qj::filter::eval::take_last_error(env);
+    had_error.input_evaluated(nul_error || uncaught.is_some());
+    if let Some(err) = uncaught {
+        locate();
+        eprintln!("{}", qj::output::format_uncaught_error(&err, env));
     }
 }
 
@@ -1040,6 +1985,122 @@ fn make_stream_error_entry(msg: &str) -> qj::value::Value {
 /// Each successfully parsed document is wrapped in `Ok(value)`.
 /// Parse failures produce `Err(error_message)`.
 /// For NDJSON, each line is tried independently.
+/// Read `--argfile NAME FILE`: the file must hold exactly one JSON value.
+/// Errors name the file and the byte offset of the problem, and exit 2 like
+/// other invalid-argument errors.
+/// Record a named argument for binding and `$ARGS.named`, unless an
+/// earlier flag already bound the name (jq keeps the first).
+fn add_named_arg(named: &mut Vec<(String, qj::value::Value)>, name: &str, val: qj::value::Value) {
+    if !named.iter().any(|(n, _)| n == name) {
+        named.push((name.to_string(), val));
+    }
+}
+
+/// Parses the VALUE of a named-argument flag, or says why it's invalid.
+type ParseNamedArg = fn(&str) -> Result<qj::value::Value, String>;
+
+/// `--arg` value: the string as given.
+fn parse_arg(text: &str) -> Result<qj::value::Value, String> {
+    Ok(qj::value::Value::String(text.to_string()))
+}
+
+/// `--argjson` value: a single JSON text.
+fn parse_argjson(text: &str) -> Result<qj::value::Value, String> {
+    let padded = qj::simdjson::pad_buffer(text.as_bytes());
+    qj::simdjson::dom_validate(&padded, text.len())
+        .and_then(|()| qj::simdjson::dom_parse_to_value(&padded, text.len()))
+        .map_err(|_| format!("invalid JSON text {text:?}"))
+}
+
+/// `--argnum` value: a JSON number, surrounding whitespace allowed.
+fn parse_argnum(text: &str) -> Result<qj::value::Value, String> {
+    match parse_argjson(text) {
+        Ok(val @ (qj::value::Value::Int(_) | qj::value::Value::Double(..))) => Ok(val),
+        _ => Err(format!("expected a number, got {text:?}")),
+    }
+}
+
+/// `--argbool` value: `true`, `false`, `1` or `0`.
+fn parse_argbool(text: &str) -> Result<qj::value::Value, String> {
+    match text {
+        "true" | "1" => Ok(qj::value::Value::Bool(true)),
+        "false" | "0" => Ok(qj::value::Value::Bool(false)),
+        _ => Err(format!("expected true, false, 1 or 0, got {text:?}")),
+    }
+}
+
+/// `--env-args PREFIX`: each environment variable named PREFIX<name>, as a
+/// string bound to `$name`, sorted by name. Variables whose name is just the
+/// prefix, or isn't valid Unicode, are skipped.
+fn env_args(prefixes: &[String]) -> Vec<(String, qj::value::Value)> {
+    let mut vars: Vec<(String, qj::value::Value)> = Vec::new();
+    for prefix in prefixes {
+        let mut matched: Vec<(String, qj::value::Value)> = std::env::vars_os()
+            .filter_map(|(key, val)| {
+                let name = key.to_str()?.strip_prefix(prefix.as_str())?;
+                let val = val.into_string().ok()?;
+                (!name.is_empty()).then(|| (name.to_string(), qj::value::Value::String(val)))
+            })
+            .collect();
+        matched.sort_by(|a, b| a.0.cmp(&b.0));
+        vars.extend(matched);
+    }
+    vars
+}
+
+/// Read the FILE of a file-binding flag, exiting like jq if it can't be.
+fn read_bound_file(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_else(|e| {
+        eprintln!("qj: error: Could not open {path}: {e}");
+        std::process::exit(2);
+    })
+}
+
+/// Read the one JSON value `--argfile` binds, exiting with status 2 if the file
+/// is empty or holds more than one value.
+fn read_argfile(name: &str, path: &str) -> qj::value::Value {
+    let buf = read_bound_file(path);
+    let padded = qj::simdjson::pad_buffer(&buf);
+    // Validate first — dom_parse_to_value stops after the first document.
+    if qj::simdjson::dom_validate(&padded, buf.len()).is_ok()
+        && let Ok(val) = qj::simdjson::dom_parse_to_value(&padded, buf.len())
+    {
+        return val;
+    }
+    // simdjson doesn't report where it failed; serde_json's stream reader does.
+    let mut stream = serde_json::Deserializer::from_slice(&buf).into_iter::<serde_json::Value>();
+    let first = stream.next();
+    let end = stream.byte_offset();
+    let trailing = buf[end..]
+        .iter()
+        .take_while(|b| b.is_ascii_whitespace())
+        .count();
+    let msg = match first {
+        None => "no JSON value found".to_string(),
+        Some(Ok(val)) if end + trailing == buf.len() => return val.into(),
+        Some(Ok(_)) => format!(
+            "expected a single JSON value, found more data at byte offset {}",
+            end + trailing
+        ),
+        Some(Err(e)) => format!(
+            "invalid JSON at byte offset {}: {e}",
+            line_col_to_offset(&buf, e.line(), e.column())
+        ),
+    };
+    eprintln!("qj: error: Bad JSON in --argfile {name} {path}: {msg}");
+    std::process::exit(2);
+}
+
+/// Convert serde_json's 1-based line/column to a 0-based byte offset.
+fn line_col_to_offset(buf: &[u8], line: usize, column: usize) -> usize {
+    let line_start: usize = buf
+        .split_inclusive(|&b| b == b'\n')
+        .take(line.saturating_sub(1))
+        .map(|l| l.len())
+        .sum();
+    (line_start + column.saturating_sub(1)).min(buf.len())
+}
+
 /// Try to parse a single JSON document, validating first to catch malformed input.
 fn parse_single_doc(trimmed: &[u8]) -> std::result::Result<qj::value::Value, String> {
     let padded = qj::simdjson::pad_buffer(trimmed);
@@ -1108,14 +2169,61 @@ fn stream_expand_values(values: &[qj::value::Value]) -> Vec<qj::value::Value> {
 
 /// Try the passthrough fast path on a padded buffer.
 /// Returns `Ok(true)` if handled, `Ok(false)` if the caller should fall back.
+#[allow(clippy::too_many_arguments)]
 fn try_passthrough(
     padded: &[u8],
     json_len: usize,
     passthrough: &qj::filter::PassthroughPath,
     out: &mut impl Write,
+    config: &qj::output::OutputConfig,
+    had_output: &mut bool,
+    had_error: &mut ErrorStatus,
+    last_was_falsy: &mut bool,
+) -> Result<bool> {
+    let handled = match try_passthrough_dom(
+        padded,
+        json_len,
+        passthrough,
+        out,
+        config,
+        had_output,
+        last_was_falsy,
+    ) {
+        // simdjson's DOM rejects lone surrogate escapes and integers beyond
+        // 64 bits, which the regular pipeline accepts, and the regular
+        // pipeline reports malformed input itself. Only a failed write is
+        // final.
+        Err(e) if e.downcast_ref::<io::Error>().is_none() => Ok(false),
+        result => result,
+    }?;
+    if handled {
+        // An input the passthrough handles evaluated without an error.
+        had_error.input_evaluated(false);
+    }
+    Ok(handled)
+}
+
+fn try_passthrough_dom(
+    padded: &[u8],
+    json_len: usize,
+    passthrough: &qj::filter::PassthroughPath,
+    out: &mut impl Write,
+    config: &qj::output::OutputConfig,
     had_output: &mut bool,
+    last_was_falsy: &mut bool,
 ) -> Result<bool> {
     match passthrough {
+        qj::filter::PassthroughPath::Identity if config.sort_keys => {
+            // Re-serialize from the tape with sorted keys. The parse also
+            // rejects multi-document input.
+            let Ok(flat) = qj::simdjson::dom_parse_to_flat_buf_tape(padded, json_len) else {
+                return Ok(false);
+            };
+            let mut sorted = Vec::with_capacity(json_len);
+            qj::output::write_flat_sorted(&mut sorted, flat.root())?;
+            write_passthrough(out, &sorted, config, had_output, last_was_falsy)?;
+            Ok(true)
+        }
         qj::filter::PassthroughPath::Identity => {
             // Validate that this is a single JSON document before minifying.
             // simdjson's minify doesn't reject multi-doc input (e.g., {"a":1}{"b":2}),
@@ -1123,22 +2231,46 @@ fn try_passthrough(
             if qj::simdjson::dom_validate(padded, json_len).is_err() {
                 return Ok(false);
             }
+            // minify copies escapes through verbatim, but jq prints the decoded
+            // characters (e.g. a surrogate pair as the emoji itself) and
+            // escapes DEL.
+            if qj::output::has_rewritten_escapes(&padded[..json_len]) {
+                return Ok(false);
+            }
             let minified = match qj::simdjson::minify(padded, json_len) {
                 Ok(m) => m,
                 Err(_) => return Ok(false),
             };
-            out.write_all(&minified)?;
-            out.write_all(b"\n")?;
-            *had_output = true;
+            // jq keeps only the last of repeated keys; the parser drops the rest.
+            if qj::input::has_duplicate_keys(&minified) {
+                return Ok(false);
+            }
+            write_passthrough(out, &minified, config, had_output, last_was_falsy)?;
             Ok(true)
         }
+        qj::filter::PassthroughPath::FieldChain(fields) => {
+            // As for identity: a single document, and no text the evaluator
+            // would print differently.
+            if qj::simdjson::dom_validate(padded, json_len).is_err() {
+                return Ok(false);
+            }
+            let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
+            match qj::simdjson::dom_field_chain(padded, json_len, &field_refs)? {
+                Some(result)
+                    if !qj::output::has_rewritten_escapes(&result)
+                        && !qj::input::has_duplicate_keys(&result) =>
+                {
+                    write_passthrough(out, &result, config, had_output, last_was_falsy)?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        }
         qj::filter::PassthroughPath::FieldLength(fields) => {
             let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
             match qj::simdjson::dom_field_length(padded, json_len, &field_refs)? {
                 Some(result) => {
-                    out.write_all(&result)?;
-                    out.write_all(b"\n")?;
-                    *had_output = true;
+                    write_passthrough(out, &result, config, had_output, last_was_falsy)?;
                     Ok(true)
                 }
                 None => Ok(false),
@@ -1148,9 +2280,7 @@ fn try_passthrough(
             let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
             match qj::simdjson::dom_field_keys(padded, json_len, &field_refs, *sorted)? {
                 Some(result) => {
-                    out.write_all(&result)?;
-                    out.write_all(b"\n")?;
-                    *had_output = true;
+                    write_passthrough(out, &result, config, had_output, last_was_falsy)?;
                     Ok(true)
                 }
                 None => Ok(false),
@@ -1182,25 +2312,29 @@ fn try_passthrough(
                     Some(b'[') => "\"array\"",
                     Some(b'"') => "\"string\"",
                     Some(b't') | Some(b'f') => "\"boolean\"",
-                    // "null" as raw result means field missing OR actual null value
-                    // jq returns "null" for both, so this is correct
-                    Some(b'n') => "\"null\"",
+                    // "null" also stands for indexing a scalar on the way,
+                    // which jq reports as an error: let the evaluator decide.
+                    Some(b'n') => return Ok(false),
                     Some(b'0'..=b'9') | Some(b'-') => "\"number\"",
                     _ => return Ok(false),
                 }
             };
-            out.write_all(raw.as_bytes())?;
-            out.write_all(b"\n")?;
-            *had_output = true;
+            let name = if config.mode == qj::output::OutputMode::Raw {
+                raw.trim_matches('"')
+            } else {
+                raw
+            };
+            qj::output::write_json_text(out, name.as_bytes(), config)?;
+            // -r leaves the name unquoted, but it is a string: truthy for -e.
+            record_passthrough_output(raw.as_bytes(), had_output, last_was_falsy);
             Ok(true)
         }
         qj::filter::PassthroughPath::FieldHas { fields, key } => {
             let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
             match qj::simdjson::dom_field_has(padded, json_len, &field_refs, key)? {
                 Some(result) => {
-                    out.write_all(if result { b"true" } else { b"false" })?;
-                    out.write_all(b"\n")?;
-                    *had_output = true;
+                    let json: &[u8] = if result { b"true" } else { b"false" };
+                    write_passthrough(out, json, config, had_output, last_was_falsy)?;
                     Ok(true)
                 }
                 None => Ok(false),
@@ -1220,13 +2354,12 @@ fn try_passthrough(
                 &field_refs,
                 *wrap_array,
             )? {
-                Some(result) => {
-                    out.write_all(&result)?;
-                    out.write_all(b"\n")?;
-                    *had_output = true;
+                // Values are copied from the input, repeated keys and all
+                Some(result) if !qj::input::has_duplicate_keys(&result) => {
+                    write_passthrough(out, &result, config, had_output, last_was_falsy)?;
                     Ok(true)
                 }
-                None => Ok(false),
+                _ => Ok(false),
             }
         }
         qj::filter::PassthroughPath::ArrayMapFieldsObj {
@@ -1256,13 +2389,12 @@ fn try_passthrough(
                 &field_refs,
                 *wrap_array,
             )? {
-                Some(result) => {
-                    out.write_all(&result)?;
-                    out.write_all(b"\n")?;
-                    *had_output = true;
+                // Values are copied from the input, repeated keys and all
+                Some(result) if !qj::input::has_duplicate_keys(&result) => {
+                    write_passthrough(out, &result, config, had_output, last_was_falsy)?;
                     Ok(true)
                 }
-                None => Ok(false),
+                _ => Ok(false),
             }
         }
         qj::filter::PassthroughPath::ArrayMapBuiltin {
@@ -1288,9 +2420,7 @@ fn try_passthrough(
                 *wrap_array,
             )? {
                 Some(result) => {
-                    out.write_all(&result)?;
-                    out.write_all(b"\n")?;
-                    *had_output = true;
+                    write_passthrough(out, &result, config, had_output, last_was_falsy)?;
                     Ok(true)
                 }
                 None => Ok(false),
@@ -1299,6 +2429,31 @@ fn try_passthrough(
     }
 }
 
+/// Write a passthrough result with the same separator and flushing as any
+/// other output value, and record it for `-e`. Results that hold several
+/// values (`.[] | .f`) are newline-separated JSON, which is why passthrough
+/// is off under `-j`, `--seq` and `--raw-output0`.
+fn write_passthrough(
+    out: &mut impl Write,
+    json: &[u8],
+    config: &qj::output::OutputConfig,
+    had_output: &mut bool,
+    last_was_falsy: &mut bool,
+) -> io::Result<()> {
+    qj::output::write_json_text(out, json, config)?;
+    record_passthrough_output(json, had_output, last_was_falsy);
+    Ok(())
+}
+
+/// Record a passthrough write for `-e`. Passthrough output is plain JSON, so
+/// the last value is falsy exactly when its last line is `null` or `false`.
+fn record_passthrough_output(written: &[u8], had_output: &mut bool, last_was_falsy: &mut bool) {
+    *had_output = true;
+    if let Some(last) = written.split(|&b| b == b'\n').rfind(|l| !l.is_empty()) {
+        *last_was_falsy = last == b"null" || last == b"false";
+    }
+}
+
 /// Bundled processing context to avoid too-many-arguments in process_file.
 struct ProcessCtx<'a> {
     passthrough: &'a Option<qj::filter::PassthroughPath>,
@@ -1309,6 +2464,45 @@ struct ProcessCtx<'a> {
     debug_timing: bool,
 }
 
+/// `-i`: process one file and replace it with the output, through an
+/// [`AtomicOutput`]. The file keeps its contents if it can't be read, the
+/// filter fails on it, or it has malformed NDJSON lines.
+fn rewrite_in_place(
+    path: &str,
+    ctx: &ProcessCtx,
+    had_output: &mut bool,
+    had_error: &mut ErrorStatus,
+    last_was_falsy: &mut bool,
+    had_bad_input: &mut bool,
+) -> Result<()> {
+    if qj::decompress::is_compressed(path) {
+        anyhow::bail!("compressed files can't be rewritten in place");
+    }
+    let (pending, file) = AtomicOutput::create(path)?;
+    let mut out = BufWriter::with_capacity(128 * 1024, file);
+    let mut file_error = ErrorStatus::default();
+    let mut bad_input = false;
+    process_file(
+        path,
+        ctx,
+        &mut out,
+        had_output,
+        &mut file_error,
+        last_was_falsy,
+        &mut bad_input,
+    )?;
+    out.flush()?;
+    drop(out);
+    had_error.merge(file_error);
+    *had_bad_input |= bad_input;
+    if file_error.any_failed() || bad_input {
+        // The file is skipped, which fails the run wherever it came.
+        had_error.input_rejected();
+        return Ok(());
+    }
+    pending.commit()
+}
+
 /// Process a single file: read, detect NDJSON, try passthrough, or run the
 /// normal DOM parse → eval → output pipeline. Optionally prints timing.
 fn process_file(
@@ -1316,8 +2510,9 @@ fn process_file(
     ctx: &ProcessCtx,
     out: &mut impl Write,
     had_output: &mut bool,
-    had_error: &mut bool,
+    had_error: &mut ErrorStatus,
     last_was_falsy: &mut bool,
+    had_bad_input: &mut bool,
 ) -> Result<()> {
     // ---- Compressed file handling ----
     // Decompress to memory, then process the decompressed buffer.
@@ -1332,7 +2527,7 @@ fn process_file(
         if !ctx.debug_timing && (ctx.force_jsonl || qj::parallel::ndjson::is_ndjson(&decompressed))
         {
             let mut cursor = std::io::Cursor::new(decompressed);
-            let ho = qj::parallel::ndjson::process_ndjson_streaming(
+            let status = qj::parallel::ndjson::process_ndjson_streaming(
                 &mut cursor,
                 ctx.filter,
                 ctx.config,
@@ -1340,7 +2535,7 @@ fn process_file(
                 out,
             )
             .with_context(|| format!("failed to process NDJSON: {path}"))?;
-            *had_output |= ho;
+            record_ndjson_status(status, had_output, last_was_falsy, had_bad_input, had_error);
             return Ok(());
         }
 
@@ -1352,13 +2547,35 @@ fn process_file(
             .with_context(|| format!("file is not valid UTF-8: {path}"))?;
 
         if let Some(pt) = ctx.passthrough {
-            let handled = try_passthrough(&padded, json_len, pt, out, had_output)
-                .with_context(|| format!("passthrough failed: {path}"))?;
+            let handled = try_passthrough(
+                &padded,
+                json_len,
+                pt,
+                out,
+                ctx.config,
+                had_output,
+                had_error,
+                last_was_falsy,
+            )
+            .with_context(|| format!("passthrough failed: {path}"))?;
             if handled {
                 return Ok(());
             }
         }
 
+        if try_parallel_array(
+            &padded[..json_len],
+            ctx.filter,
+            ctx.env,
+            out,
+            ctx.config,
+            had_output,
+            had_error,
+            last_was_falsy,
+        )? {
+            return Ok(());
+        }
+
         process_padded(
             &padded,
             json_len,
@@ -1378,18 +2595,29 @@ fn process_file(
     // NDJSON: mmap the file directly (no simdjson padding needed) and process
     // in parallel windows. Falls back to streaming read() if mmap is unavailable.
     // Works for files larger than physical RAM — kernel pages in on demand.
-    if !ctx.debug_timing
-        && let Some(ho) = qj::parallel::ndjson::process_ndjson_file(
-            std::path::Path::new(path),
-            ctx.filter,
-            ctx.config,
-            ctx.env,
-            ctx.force_jsonl,
-            out,
-        )
-        .with_context(|| format!("failed to process NDJSON: {path}"))?
+    let t0 = Instant::now();
+    if let Some(status) = qj::parallel::ndjson::process_ndjson_file(
+        std::path::Path::new(path),
+        ctx.filter,
+        ctx.config,
+        ctx.env,
+        ctx.force_jsonl,
+        out,
+    )
+    .with_context(|| format!("failed to process NDJSON: {path}"))?
     {
-        *had_output |= ho;
+        if ctx.debug_timing {
+            let total = t0.elapsed();
+            let mb = std::fs::metadata(path).map_or(0, |m| m.len()) as f64 / (1024.0 * 1024.0);
+            let label = match qj::parallel::ndjson::fast_path_name(ctx.filter, ctx.config, ctx.env)
+            {
+                Some(name) => format!("ndjson {name} fast path"),
+                None => "ndjson".to_string(),
+            };
+            eprintln!("--- debug-timing ({label}): {path} ({mb:.1} MB) ---");
+            print_timing_total(total, mb);
+        }
+        record_ndjson_status(status, had_output, last_was_falsy, had_bad_input, had_error);
         return Ok(());
     }
 
@@ -1407,8 +2635,17 @@ fn process_file(
     // Passthrough fast path
     if let Some(pt) = ctx.passthrough {
         let t1 = Instant::now();
-        let handled = try_passthrough(&padded, json_len, pt, out, had_output)
-            .with_context(|| format!("passthrough failed: {path}"))?;
+        let handled = try_passthrough(
+            &padded,
+            json_len,
+            pt,
+            out,
+            ctx.config,
+            had_output,
+            had_error,
+            last_was_falsy,
+        )
+        .with_context(|| format!("passthrough failed: {path}"))?;
         if handled {
             if ctx.debug_timing {
                 let t_op = t1.elapsed();
@@ -1416,6 +2653,7 @@ fn process_file(
                 let mb = json_len as f64 / (1024.0 * 1024.0);
                 let label = match pt {
                     qj::filter::PassthroughPath::Identity => "minify",
+                    qj::filter::PassthroughPath::FieldChain(_) => "field",
                     qj::filter::PassthroughPath::FieldLength(_) => "length",
                     qj::filter::PassthroughPath::FieldKeys { .. } => "keys",
                     qj::filter::PassthroughPath::FieldType(_) => "type",
@@ -1434,6 +2672,30 @@ fn process_file(
         // Passthrough returned None (unsupported type) — fall through to normal pipeline
     }
 
+    // A big top-level array iterated by the filter: elements in parallel.
+    let t1 = Instant::now();
+    if try_parallel_array(
+        &padded[..json_len],
+        ctx.filter,
+        ctx.env,
+        out,
+        ctx.config,
+        had_output,
+        had_error,
+        last_was_falsy,
+    )? {
+        if ctx.debug_timing {
+            let t_op = t1.elapsed();
+            let total = t_read + t_op;
+            let mb = json_len as f64 / (1024.0 * 1024.0);
+            eprintln!("--- debug-timing (array parallel): {path} ({mb:.1} MB) ---");
+            print_timing_line("read", t_read, total);
+            print_timing_line("eval", t_op, total);
+            print_timing_total(total, mb);
+        }
+        return Ok(());
+    }
+
     // Normal pipeline: DOM parse → eval → output
     std::str::from_utf8(&padded[..json_len])
         .with_context(|| format!("file is not valid UTF-8: {path}"))?;
@@ -1460,16 +2722,17 @@ fn process_file(
 
         let t2 = Instant::now();
         let mut values = Vec::new();
-        qj::filter::eval::eval_filter(ctx.filter, &input, &mut |v| {
+        let uncaught = qj::filter::eval::eval_filter(ctx.filter, &input, &mut |v| {
             values.push(v);
         });
         let t_eval = t2.elapsed();
 
         // Check for uncaught runtime errors from the debug-timing eval path
-        if let Some(err) = qj::filter::eval::take_last_error() {
-            *had_error = true;
-            let msg = format_error(&err);
-            eprintln!("qj: error: {msg}");
+        had_error.input_evaluated(uncaught.is_some());
+        if let Some(err) = uncaught {
+            ctx.env
+                .set_input_line(qj::input::line_of_last_value(&padded[..json_len]));
+            eprintln!("{}", qj::output::format_uncaught_error(&err, ctx.env));
         }
 
         let t3 = Instant::now();
@@ -1513,6 +2776,14 @@ fn process_file(
 
 /// Process --raw-input text: each line becomes a Value::String.
 /// If slurp is true, concatenate all input into a single string (matches jq -Rs).
+/// Split `--raw-input0` text into records. Every NUL ends a record, so
+/// consecutive NULs give empty records; text after the last NUL is a final
+/// record of its own.
+fn nul_records(text: &str) -> impl Iterator<Item = &str> {
+    let records = (!text.is_empty()).then(|| text.strip_suffix('\0').unwrap_or(text));
+    records.into_iter().flat_map(|t| t.split('\0'))
+}
+
 #[allow(clippy::too_many_arguments)]
 fn process_raw_input(
     text: &str,
@@ -1522,13 +2793,13 @@ fn process_raw_input(
     out: &mut impl Write,
     config: &qj::output::OutputConfig,
     had_output: &mut bool,
-    had_error: &mut bool,
+    had_error: &mut ErrorStatus,
     last_was_falsy: &mut bool,
 ) -> Result<()> {
     if slurp {
         // jq's -Rs concatenates all input into a single string value (not an array)
         let input = qj::value::Value::String(text.to_string());
-        eval_and_output(
+        eval_and_output_located(
             filter,
             &input,
             env,
@@ -1537,11 +2808,12 @@ fn process_raw_input(
             had_output,
             had_error,
             last_was_falsy,
+            || env.set_input_line(text.bytes().filter(|&b| b == b'\n').count()),
         );
     } else {
-        for line in text.lines() {
+        for (i, line) in text.lines().enumerate() {
             let input = qj::value::Value::String(line.to_string());
-            eval_and_output(
+            eval_and_output_located(
                 filter,
                 &input,
                 env,
@@ -1550,12 +2822,78 @@ fn process_raw_input(
                 had_output,
                 had_error,
                 last_was_falsy,
+                || env.set_input_line(i + 1),
             );
         }
     }
     Ok(())
 }
 
+/// Evaluate `.[] | f` or `map(f)` over a large top-level array in parallel.
+/// Returns `false`, having written nothing, when that path doesn't apply.
+#[allow(clippy::too_many_arguments)]
+fn try_parallel_array(
+    json: &[u8],
+    filter: &qj::filter::Filter,
+    env: &qj::filter::Env,
+    out: &mut impl Write,
+    config: &qj::output::OutputConfig,
+    had_output: &mut bool,
+    had_error: &mut ErrorStatus,
+    last_was_falsy: &mut bool,
+) -> Result<bool> {
+    let Some(status) = qj::parallel::array::process_array(json, filter, config, env, out)? else {
+        return Ok(false);
+    };
+    if status.had_output {
+        *had_output = true;
+        *last_was_falsy = status.last_was_falsy;
+    }
+    had_error.input_evaluated(status.error.is_some());
+    if let Some(err) = status.error {
+        env.set_input_line(qj::input::line_of_last_value(json));
+        eprintln!("{}", qj::output::format_uncaught_error(&err, env));
+    }
+    Ok(true)
+}
+
+/// Evaluate each document of a concatenated stream, in parallel where the
+/// filter allows. Returns `false`, having written nothing, when the input
+/// isn't a stream of valid documents.
+#[allow(clippy::too_many_arguments)]
+fn try_parallel_documents(
+    json: &[u8],
+    filter: &qj::filter::Filter,
+    env: &qj::filter::Env,
+    out: &mut impl Write,
+    config: &qj::output::OutputConfig,
+    had_output: &mut bool,
+    had_error: &mut ErrorStatus,
+    last_was_falsy: &mut bool,
+) -> Result<bool> {
+    let Some(status) = qj::parallel::documents::process_documents(
+        json,
+        filter,
+        config,
+        env,
+        out,
+        &mut io::stderr(),
+    )?
+    else {
+        return Ok(false);
+    };
+    if status.had_output {
+        *had_output = true;
+        *last_was_falsy = status.last_was_falsy;
+    }
+    had_error.merge(ErrorStatus {
+        last_input_failed: Some(status.last_failed),
+        any_input_failed: status.had_error,
+        input_rejected: false,
+    });
+    Ok(true)
+}
+
 #[allow(clippy::too_many_arguments)]
 fn process_padded(
     padded: &[u8],
@@ -1565,7 +2903,7 @@ fn process_padded(
     out: &mut impl Write,
     config: &qj::output::OutputConfig,
     had_output: &mut bool,
-    had_error: &mut bool,
+    had_error: &mut ErrorStatus,
     last_was_falsy: &mut bool,
 ) -> Result<()> {
     // Use flat evaluation (lazy, zero-copy) when the filter is safe for it.
@@ -1589,19 +2927,21 @@ fn process_padded(
             *had_output = true;
             if qj::output::write_value(out, &v, config).is_err() {
                 write_failed = true;
+                qj::filter::eval::stop_evaluation(env);
             }
         });
         if nul_error {
-            *had_error = true;
             eprintln!("qj: error: Cannot dump a string containing NUL with --raw-output0 option");
         }
-        if let Some(err) = qj::filter::eval::take_last_error() {
-            *had_error = true;
-            let msg = format_error(&err);
-            eprintln!("qj: error: {msg}");
+        let uncaught = qj::filter::eval::take_last_error(env);
+        had_error.input_evaluated(nul_error || uncaught.is_some());
+        if let Some(err) = uncaught {
+            env.set_input_line(qj::input::line_of_last_value(&padded[..json_len]));
+            eprintln!("{}", qj::output::format_uncaught_error(&err, env));
         }
         return Ok(());
     }
+    let locate = || env.set_input_line(qj::input::line_of_last_value(&padded[..json_len]));
 
     // Regular pipeline: DOM tape walk → flat buffer → Value tree → eval → output
     let input = match qj::simdjson::dom_parse_to_value_fast(padded, json_len) {
@@ -1625,7 +2965,7 @@ fn process_padded(
                 let pp_padded = qj::simdjson::pad_buffer(&pp);
                 if let Ok(val) = qj::simdjson::dom_parse_to_value_fast(&pp_padded, pp.len()) {
                     let input = qj::input::fixup_special_float_sentinels_pub(val);
-                    eval_and_output(
+                    eval_and_output_located(
                         filter,
                         &input,
                         env,
@@ -1634,17 +2974,32 @@ fn process_padded(
                         had_output,
                         had_error,
                         last_was_falsy,
+                        locate,
                     );
                     return Ok(());
                 }
             }
+            // A stream of whole documents, e.g. pretty-printed records:
+            // evaluate them in parallel when the filter allows.
+            if try_parallel_documents(
+                raw,
+                filter,
+                env,
+                out,
+                config,
+                had_output,
+                had_error,
+                last_was_falsy,
+            )? {
+                return Ok(());
+            }
             // Try multi-doc fallback: serde_json's StreamDeserializer handles
             // concatenated JSON like {"a":1}{"b":2} and whitespace-separated values.
             let text = match std::str::from_utf8(&padded[..json_len]) {
                 Ok(t) => t,
                 Err(_) => {
                     eprintln!("qj: error (at <stdin>): {e:#}");
-                    *had_error = true;
+                    had_error.input_rejected();
                     return Ok(());
                 }
             };
@@ -1652,12 +3007,14 @@ fn process_padded(
                 serde_json::Deserializer::from_str(text).into_iter::<serde_json::Value>();
             let mut count = 0usize;
             let mut last_stream_err = None;
-            for result in &mut stream {
+            let mut lines = qj::input::LineCounter::default();
+            while let Some(result) = stream.next() {
                 match result {
                     Ok(serde_val) => {
                         count += 1;
                         let input = qj::value::Value::from(serde_val);
-                        eval_and_output(
+                        let end = stream.byte_offset();
+                        eval_and_output_located(
                             filter,
                             &input,
                             env,
@@ -1666,6 +3023,7 @@ fn process_padded(
                             had_output,
                             had_error,
                             last_was_falsy,
+                            || env.set_input_line(lines.line_of_value(text.as_bytes(), end)),
                         );
                     }
                     Err(se) => {
@@ -1677,16 +3035,16 @@ fn process_padded(
             if count == 0 {
                 // Stream produced nothing — report the original simdjson error
                 eprintln!("qj: error (at <stdin>): {e:#}");
-                *had_error = true;
+                had_error.input_rejected();
             } else if let Some(se) = last_stream_err {
                 // Partial parse — some docs succeeded, then an error
                 eprintln!("qj: error (at <stdin>): {se}");
-                *had_error = true;
+                had_error.input_rejected();
             }
             return Ok(());
         }
     };
-    eval_and_output(
+    eval_and_output_located(
         filter,
         &input,
         env,
@@ -1695,6 +3053,7 @@ fn process_padded(
         had_output,
         had_error,
         last_was_falsy,
+        locate,
     );
     Ok(())
 }
@@ -2,8 +2,14 @@
 ///
 /// Writes `Value` directly to a `Write` sink — no intermediate `String`
 /// allocation. Uses `itoa` for integers and `ryu` for floats.
+use std::cell::RefCell;
 use std::io::{self, Write};
+use std::rc::Rc;
 
+use crate::flat_value::FlatValue;
+use crate::simdjson::{
+    TAG_ARRAY_START, TAG_BOOL, TAG_DOUBLE, TAG_INT, TAG_OBJECT_START, TAG_STRING,
+};
 use crate::value::Value;
 
 /// Output formatting mode.
@@ -15,51 +21,104 @@ pub enum OutputMode {
     Compact,
     /// Raw string output (`-r`) — strings without quotes.
     Raw,
+    /// One line of `key=value` pairs per object (`--logfmt`).
+    Logfmt,
 }
 
 /// ANSI color scheme for JSON output (matches jq's defaults).
 #[derive(Debug, Clone)]
 pub struct ColorScheme {
-    pub null: &'static str,
-    pub bool_val: &'static str,
-    pub number: &'static str,
-    pub string: &'static str,
-    pub array_bracket: &'static str,
-    pub object_brace: &'static str,
-    pub object_key: &'static str,
-    pub reset: &'static str,
+    pub null: String,
+    pub false_val: String,
+    pub true_val: String,
+    pub number: String,
+    pub string: String,
+    pub array_bracket: String,
+    pub object_brace: String,
+    pub object_key: String,
+    pub reset: String,
 }
 
 impl ColorScheme {
     /// jq's default color scheme (matches jq 1.7+ output).
     pub fn jq_default() -> Self {
         Self {
-            null: "\x1b[0;90m",
-            bool_val: "\x1b[0;39m",
-            number: "\x1b[0;39m",
-            string: "\x1b[0;32m",
-            array_bracket: "\x1b[1;39m",
-            object_brace: "\x1b[1;39m",
-            object_key: "\x1b[1;34m",
-            reset: "\x1b[0m",
+            null: "\x1b[0;90m".into(),
+            false_val: "\x1b[0;39m".into(),
+            true_val: "\x1b[0;39m".into(),
+            number: "\x1b[0;39m".into(),
+            string: "\x1b[0;32m".into(),
+            array_bracket: "\x1b[1;39m".into(),
+            object_brace: "\x1b[1;39m".into(),
+            object_key: "\x1b[1;34m".into(),
+            reset: "\x1b[0m".into(),
         }
     }
 
+    /// jq's default scheme with any overrides from `$JQ_COLORS` applied.
+    ///
+    /// Like jq, a malformed value prints a warning and leaves the defaults.
+    pub fn from_env() -> Self {
+        match std::env::var("JQ_COLORS") {
+            Err(std::env::VarError::NotPresent) => Self::jq_default(),
+            spec => spec
+                .ok()
+                .and_then(|spec| Self::from_jq_colors(&spec))
+                .unwrap_or_else(|| {
+                    eprintln!("qj: warning: Failed to set $JQ_COLORS");
+                    Self::jq_default()
+                }),
+        }
+    }
+
+    /// Parse a `JQ_COLORS` value: colon-separated SGR parameter lists for
+    /// null:false:true:numbers:strings:arrays:objects:object keys.
+    ///
+    /// Fields left off the end keep their default and fields past the eighth
+    /// are ignored. Returns `None` if a field contains anything other than
+    /// digits and `;`, or is longer than jq's 12-character limit.
+    pub fn from_jq_colors(spec: &str) -> Option<Self> {
+        let mut scheme = Self::jq_default();
+        let spec = spec.strip_suffix(':').unwrap_or(spec);
+        if spec.is_empty() {
+            return Some(scheme);
+        }
+        let slots = [
+            &mut scheme.null,
+            &mut scheme.false_val,
+            &mut scheme.true_val,
+            &mut scheme.number,
+            &mut scheme.string,
+            &mut scheme.array_bracket,
+            &mut scheme.object_brace,
+            &mut scheme.object_key,
+        ];
+        for (slot, field) in slots.into_iter().zip(spec.split(':')) {
+            if field.len() > 12 || !field.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                return None;
+            }
+            *slot = format!("\x1b[{field}m");
+        }
+        Some(scheme)
+    }
+
     /// No-color scheme (all empty strings).
     pub fn none() -> Self {
         Self {
-            null: "",
-            bool_val: "",
-            number: "",
-            string: "",
-            array_bracket: "",
-            object_brace: "",
-            object_key: "",
-            reset: "",
+            null: String::new(),
+            false_val: String::new(),
+            true_val: String::new(),
+            number: String::new(),
+            string: String::new(),
+            array_bracket: String::new(),
+            object_brace: String::new(),
+            object_key: String::new(),
+            reset: String::new(),
         }
     }
 
-    fn is_enabled(&self) -> bool {
+    /// Whether this scheme emits any escape codes.
+    pub fn is_enabled(&self) -> bool {
         !self.reset.is_empty()
     }
 }
@@ -84,6 +143,9 @@ pub struct OutputConfig {
     pub unbuffered: bool,
     /// Prefix each output value with ASCII RS (0x1E) for RFC 7464 (`--seq`).
     pub seq: bool,
+    /// In raw mode, print arrays as their elements joined with this
+    /// separator, the way `join` would (`--join`).
+    pub join_separator: Option<String>,
 }
 
 impl Default for OutputConfig {
@@ -98,6 +160,7 @@ impl Default for OutputConfig {
             ascii_output: false,
             unbuffered: false,
             seq: false,
+            join_separator: None,
         }
     }
 }
@@ -114,11 +177,130 @@ pub fn format_compact(value: &Value) -> String {
     String::from_utf8(buf).unwrap().trim_end().to_string()
 }
 
+/// Format an uncaught runtime error for stderr, without the trailing newline,
+/// located at the input `env` is evaluating (see
+/// [`crate::filter::Env::input_location`]).
+pub fn format_uncaught_error(err: &Value, env: &crate::filter::Env) -> String {
+    format_uncaught_error_at(err, &env.input_location())
+}
+
+/// Format an uncaught runtime error raised by the input at `location`, as
+/// jq does: string errors print their text, e.g.
+/// `qj: error (at data.json:3): boom`; any other value is dumped as compact
+/// JSON with a "(not a string)" marker, e.g.
+/// `qj: error (at <stdin>:1) (not a string): {"a":1}`.
+pub fn format_uncaught_error_at(err: &Value, location: &str) -> String {
+    match err {
+        Value::String(s) => format!("qj: error (at {location}): {s}"),
+        other => format!(
+            "qj: error (at {location}) (not a string): {}",
+            format_compact(other)
+        ),
+    }
+}
+
 /// Write a value to the output sink, followed by a newline (unless join_output).
 pub fn write_value<W: Write>(w: &mut W, value: &Value, config: &OutputConfig) -> io::Result<()> {
     if config.seq {
         w.write_all(b"\x1e")?;
     }
+    write_value_body(w, value, config)?;
+    end_value(w, config)
+}
+
+/// Write one value already serialized as JSON text (a passthrough result),
+/// with the same `--seq` marker, separator and flushing as [`write_value`].
+pub fn write_json_text<W: Write>(w: &mut W, json: &[u8], config: &OutputConfig) -> io::Result<()> {
+    if config.seq {
+        w.write_all(b"\x1e")?;
+    }
+    w.write_all(json)?;
+    end_value(w, config)
+}
+
+/// Write output already formatted for `config`, such as a chunk evaluated on
+/// another thread, flushing it under `--unbuffered` as [`write_value`] does.
+pub fn write_formatted<W: Write>(w: &mut W, bytes: &[u8], config: &OutputConfig) -> io::Result<()> {
+    w.write_all(bytes)?;
+    if config.unbuffered && !bytes.is_empty() {
+        w.flush()?;
+    }
+    Ok(())
+}
+
+/// Separator and `--unbuffered` flush after each value.
+fn end_value<W: Write>(w: &mut W, config: &OutputConfig) -> io::Result<()> {
+    if !config.join_output {
+        if config.null_separator {
+            w.write_all(b"\0")?;
+        } else {
+            w.write_all(b"\n")?;
+        }
+    }
+    if config.unbuffered {
+        w.flush()?;
+    }
+    Ok(())
+}
+
+/// The first error hit writing output, shared between a [`TrackedWriter`] and
+/// whoever reports it.
+#[derive(Debug, Clone, Default)]
+pub struct WriteFailure(Rc<RefCell<Option<io::Error>>>);
+
+impl WriteFailure {
+    pub fn is_set(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    pub fn take(&self) -> Option<io::Error> {
+        self.0.borrow_mut().take()
+    }
+}
+
+/// A writer that records its first error in a [`WriteFailure`]. Output
+/// reaches the sink by several routes (passthrough, NDJSON chunks, one value
+/// at a time) that each surface a failed write their own way; the record lets
+/// the caller stop early and report it once, as a write error.
+pub struct TrackedWriter<W> {
+    inner: W,
+    failure: WriteFailure,
+}
+
+impl<W: Write> TrackedWriter<W> {
+    pub fn new(inner: W, failure: WriteFailure) -> Self {
+        Self { inner, failure }
+    }
+
+    fn record<T>(&self, result: io::Result<T>) -> io::Result<T> {
+        if let Err(e) = &result
+            && e.kind() != io::ErrorKind::Interrupted
+            && !self.failure.is_set()
+        {
+            *self.failure.0.borrow_mut() = Some(io::Error::new(e.kind(), e.to_string()));
+        }
+        result
+    }
+}
+
+impl<W: Write> Write for TrackedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.record(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.record(result)
+    }
+}
+
+/// Write just the formatted value: no `--seq` prefix, separator, or flush.
+pub fn write_value_body<W: Write>(
+    w: &mut W,
+    value: &Value,
+    config: &OutputConfig,
+) -> io::Result<()> {
     match config.mode {
         OutputMode::Pretty => {
             let fmt = PrettyFmt {
@@ -145,23 +327,25 @@ pub fn write_value<W: Write>(w: &mut W, value: &Value, config: &OutputConfig) ->
                 config.ascii_output,
             )?;
         }
-        OutputMode::Raw => write_raw(
-            w,
-            value,
-            config.sort_keys,
-            &config.color,
-            config.ascii_output,
-        )?,
-    }
-    if !config.join_output {
-        if config.null_separator {
-            w.write_all(b"\0")?;
-        } else {
-            w.write_all(b"\n")?;
-        }
-    }
-    if config.unbuffered {
-        w.flush()?;
+        OutputMode::Raw => match (value, &config.join_separator) {
+            (Value::Array(items), Some(sep)) => write_raw(
+                w,
+                &Value::String(join_raw(items, sep)?),
+                &config.indent,
+                config.sort_keys,
+                &config.color,
+                config.ascii_output,
+            )?,
+            _ => write_raw(
+                w,
+                value,
+                &config.indent,
+                config.sort_keys,
+                &config.color,
+                config.ascii_output,
+            )?,
+        },
+        OutputMode::Logfmt => write_logfmt(w, value, config.sort_keys)?,
     }
     Ok(())
 }
@@ -278,7 +462,12 @@ fn write_value_inner<W: Write, F: JsonFormatter>(
         }
         Value::Bool(b) => {
             if c {
-                w.write_all(color.bool_val.as_bytes())?;
+                let code = if *b {
+                    &color.true_val
+                } else {
+                    &color.false_val
+                };
+                w.write_all(code.as_bytes())?;
             }
             w.write_all(if *b { b"true" } else { b"false" })?;
             if c {
@@ -322,14 +511,14 @@ fn write_value_inner<W: Write, F: JsonFormatter>(
             Ok(())
         }
         Value::Array(arr) if arr.is_empty() => {
-            write_colored(w, b"[]", color.array_bracket, color.reset)
+            write_colored(w, b"[]", &color.array_bracket, &color.reset)
         }
         Value::Array(arr) => {
-            write_colored(w, b"[", color.array_bracket, color.reset)?;
+            write_colored(w, b"[", &color.array_bracket, &color.reset)?;
             fmt.after_open(w, depth)?;
             for (i, v) in arr.iter().enumerate() {
                 if i > 0 {
-                    write_colored(w, b",", color.array_bracket, color.reset)?;
+                    write_colored(w, b",", &color.array_bracket, &color.reset)?;
                     fmt.after_sep(w, depth)?;
                 } else {
                     fmt.before_first(w, depth)?;
@@ -337,13 +526,13 @@ fn write_value_inner<W: Write, F: JsonFormatter>(
                 write_value_inner(w, v, fmt, depth + 1, sort_keys, color, ascii_output)?;
             }
             fmt.before_close(w, depth)?;
-            write_colored(w, b"]", color.array_bracket, color.reset)
+            write_colored(w, b"]", &color.array_bracket, &color.reset)
         }
         Value::Object(obj) if obj.is_empty() => {
-            write_colored(w, b"{}", color.object_brace, color.reset)
+            write_colored(w, b"{}", &color.object_brace, &color.reset)
         }
         Value::Object(obj) => {
-            write_colored(w, b"{", color.object_brace, color.reset)?;
+            write_colored(w, b"{", &color.object_brace, &color.reset)?;
             fmt.after_open(w, depth)?;
             let sorted;
             let pairs: &[(String, Value)] = if sort_keys {
@@ -358,7 +547,7 @@ fn write_value_inner<W: Write, F: JsonFormatter>(
             };
             for (i, (k, v)) in pairs.iter().enumerate() {
                 if i > 0 {
-                    write_colored(w, b",", color.object_brace, color.reset)?;
+                    write_colored(w, b",", &color.object_brace, &color.reset)?;
                     fmt.after_sep(w, depth)?;
                 } else {
                     fmt.before_first(w, depth)?;
@@ -374,12 +563,12 @@ fn write_value_inner<W: Write, F: JsonFormatter>(
                 if c {
                     w.write_all(color.reset.as_bytes())?;
                 }
-                write_colored(w, b":", color.object_brace, color.reset)?;
+                write_colored(w, b":", &color.object_brace, &color.reset)?;
                 fmt.after_colon(w)?;
                 write_value_inner(w, v, fmt, depth + 1, sort_keys, color, ascii_output)?;
             }
             fmt.before_close(w, depth)?;
-            write_colored(w, b"}", color.object_brace, color.reset)
+            write_colored(w, b"}", &color.object_brace, &color.reset)
         }
     }
 }
@@ -400,6 +589,49 @@ pub(crate) fn write_compact<W: Write>(w: &mut W, value: &Value, sort_keys: bool)
     )
 }
 
+/// Write a value from a flat token buffer as compact JSON with object keys
+/// sorted (`-c -S`), without materializing a `Value` tree. The flat buffer
+/// already keeps only the last of repeated keys, as jq does.
+pub fn write_flat_sorted<W: Write>(w: &mut W, value: FlatValue<'_>) -> io::Result<()> {
+    match value.tag() {
+        TAG_BOOL => w.write_all(if value.is_truthy() { b"true" } else { b"false" }),
+        TAG_INT => {
+            let mut buf = itoa::Buffer::new();
+            w.write_all(buf.format(value.as_int().unwrap_or(0)).as_bytes())
+        }
+        TAG_DOUBLE => {
+            let (f, raw) = value.as_f64().unwrap_or((0.0, None));
+            write_double(w, f, raw)
+        }
+        TAG_STRING => write_json_string(w, value.as_str().unwrap_or("")),
+        TAG_ARRAY_START => {
+            w.write_all(b"[")?;
+            for (i, item) in value.array_iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                write_flat_sorted(w, item)?;
+            }
+            w.write_all(b"]")
+        }
+        TAG_OBJECT_START => {
+            let mut pairs: Vec<(&str, FlatValue<'_>)> = value.object_iter().collect();
+            pairs.sort_by(|a, b| a.0.cmp(b.0));
+            w.write_all(b"{")?;
+            for (i, (k, v)) in pairs.into_iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                write_json_string(w, k)?;
+                w.write_all(b":")?;
+                write_flat_sorted(w, v)?;
+            }
+            w.write_all(b"}")
+        }
+        _ => w.write_all(b"null"),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Raw output (-r)
 // ---------------------------------------------------------------------------
@@ -407,17 +639,108 @@ pub(crate) fn write_compact<W: Write>(w: &mut W, value: &Value, sort_keys: bool)
 fn write_raw<W: Write>(
     w: &mut W,
     value: &Value,
+    indent: &str,
     sort_keys: bool,
     color: &ColorScheme,
     ascii_output: bool,
 ) -> io::Result<()> {
     match value {
-        // Raw mode: strings are output without quotes.
-        // With --ascii-output, jq outputs the full JSON-encoded string (with quotes),
-        // so we fall through to the compact path which handles ascii escaping.
+        // Raw mode: strings are output without quotes, control characters
+        // and all. With --ascii-output, jq outputs the full JSON-encoded
+        // string (with quotes), so we fall through to the JSON path which
+        // handles ascii escaping.
         Value::String(s) if !ascii_output => w.write_all(s.as_bytes()),
-        // Everything else is the same as compact (with color)
-        _ => write_value_inner(w, value, &CompactFmt, 0, sort_keys, color, ascii_output),
+        // Everything else prints as JSON, pretty unless -c left the indent empty
+        _ if indent.is_empty() => {
+            write_value_inner(w, value, &CompactFmt, 0, sort_keys, color, ascii_output)
+        }
+        _ => {
+            let fmt = PrettyFmt { indent };
+            write_value_inner(w, value, &fmt, 0, sort_keys, color, ascii_output)
+        }
+    }
+}
+
+/// Join array elements as `join` does: strings as-is, `null` as nothing, and
+/// numbers and booleans as JSON. Nested containers are rejected with
+/// `InvalidInput`; the CLI reports those as runtime errors before they get here.
+fn join_raw(items: &[Value], sep: &str) -> io::Result<String> {
+    let mut buf = Vec::new();
+    for (i, v) in items.iter().enumerate() {
+        if i > 0 {
+            buf.extend_from_slice(sep.as_bytes());
+        }
+        match v {
+            Value::String(s) => buf.extend_from_slice(s.as_bytes()),
+            Value::Null => {}
+            Value::Array(_) | Value::Object(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Cannot join {} ({})", v.type_name(), v.short_desc()),
+                ));
+            }
+            _ => write_compact(&mut buf, v, false)?,
+        }
+    }
+    Ok(String::from_utf8(buf).unwrap_or_default())
+}
+
+// ---------------------------------------------------------------------------
+// logfmt output (--logfmt)
+// ---------------------------------------------------------------------------
+
+/// Write an object as a single logfmt line: space-separated `key=value` pairs.
+///
+/// Strings print bare, other scalars as JSON, and nested containers as compact
+/// JSON. Any key or value that is empty or contains a space, `=`, `"` or a
+/// control character is JSON-quoted. Non-objects are rejected with
+/// `InvalidInput`; the CLI reports those as runtime errors before they get here.
+fn write_logfmt<W: Write>(w: &mut W, value: &Value, sort_keys: bool) -> io::Result<()> {
+    let Value::Object(obj) = value else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Cannot format {} as logfmt", value.type_name()),
+        ));
+    };
+    let sorted;
+    let pairs: &[(String, Value)] = if sort_keys {
+        sorted = {
+            let mut v = obj.as_ref().clone();
+            v.sort_by(|a, b| a.0.cmp(&b.0));
+            v
+        };
+        &sorted
+    } else {
+        obj.as_ref()
+    };
+    for (i, (k, v)) in pairs.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b" ")?;
+        }
+        write_logfmt_token(w, k)?;
+        w.write_all(b"=")?;
+        match v {
+            Value::String(s) => write_logfmt_token(w, s)?,
+            Value::Array(_) | Value::Object(_) => {
+                let mut buf = Vec::new();
+                write_compact(&mut buf, v, sort_keys)?;
+                write_logfmt_token(w, &String::from_utf8_lossy(&buf))?;
+            }
+            _ => write_compact(w, v, sort_keys)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write a logfmt key or value, JSON-quoting it only when it would be ambiguous.
+fn write_logfmt_token<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    let needs_quotes = s.is_empty()
+        || s.bytes()
+            .any(|b| matches!(b, b' ' | b'=' | b'"') || b < 0x20 || b == 0x7f);
+    if needs_quotes {
+        write_json_string(w, s)
+    } else {
+        w.write_all(s.as_bytes())
     }
 }
 
@@ -432,7 +755,18 @@ fn write_indent<W: Write>(w: &mut W, depth: usize, indent: &str) -> io::Result<(
     Ok(())
 }
 
-/// Write a JSON-escaped string (with surrounding quotes).
+/// Whether raw JSON text has string contents jq prints differently from
+/// how they're written: `\uXXXX` and `\/` escapes (jq prints the decoded
+/// character, or its own escape) and a raw DEL byte (printed as `\u007f`).
+/// Fast paths that copy input bytes through re-serialize such text.
+pub fn has_rewritten_escapes(raw: &[u8]) -> bool {
+    memchr::memchr(0x7f, raw).is_some()
+        || memchr::memmem::find(raw, b"\\u").is_some()
+        || memchr::memmem::find(raw, b"\\/").is_some()
+}
+
+/// Write a JSON-escaped string (with surrounding quotes), escaping the same
+/// characters as jq: `"`, `\\`, control characters and DEL.
 pub fn write_json_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
     w.write_all(b"\"")?;
     let bytes = s.as_bytes();
@@ -446,7 +780,7 @@ pub fn write_json_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
             b'\t' => Some(b"\\t"),
             b'\x08' => Some(b"\\b"),
             b'\x0c' => Some(b"\\f"),
-            0..=0x1f => None, // handled below
+            0..=0x1f | 0x7f => None, // handled below
             _ => continue,
         };
         if let Some(esc) = escape {
@@ -456,8 +790,8 @@ pub fn write_json_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
             }
             w.write_all(esc)?;
             start = i + 1;
-        } else if b <= 0x1f {
-            // Control character — \u00XX
+        } else {
+            // Control character or DEL — \u00XX, lowercase like jq
             if start < i {
                 w.write_all(&bytes[start..i])?;
             }
@@ -485,7 +819,7 @@ fn write_json_string_ascii<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
             '\t' => w.write_all(b"\\t")?,
             '\x08' => w.write_all(b"\\b")?,
             '\x0c' => w.write_all(b"\\f")?,
-            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c if (c as u32) < 0x20 || c == '\x7f' => write!(w, "\\u{:04x}", c as u32)?,
             c if c.is_ascii() => w.write_all(&[c as u8])?,
             c if (c as u32) <= 0xFFFF => write!(w, "\\u{:04x}", c as u32)?,
             c => {
@@ -569,6 +903,7 @@ fn write_double<W: Write>(w: &mut W, f: f64, raw: Option<&str>) -> io::Result<()
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::*;
     use std::sync::Arc;
@@ -829,6 +1164,42 @@ mod tests {
         assert_eq!(buf, b"hello"); // no trailing newline
     }
 
+    #[test]
+    fn json_text_ends_like_write_value() {
+        let v = Value::Array(Arc::new(vec![Value::Int(1), Value::Bool(false)]));
+        for (seq, join_output, null_separator) in [
+            (false, false, false),
+            (true, false, false),
+            (false, true, false),
+            (false, false, true),
+        ] {
+            let config = OutputConfig {
+                mode: OutputMode::Compact,
+                seq,
+                join_output,
+                null_separator,
+                ..Default::default()
+            };
+            let (mut from_value, mut from_text) = (Vec::new(), Vec::new());
+            write_value(&mut from_value, &v, &config).unwrap();
+            write_json_text(&mut from_text, b"[1,false]", &config).unwrap();
+            assert_eq!(from_text, from_value, "{config:?}");
+        }
+    }
+
+    #[test]
+    fn flat_sorted_matches_sorted_compact() {
+        let json = br#"{"b":[{"y":1,"x":"\u00e9\n"},2.50,true],"a":{},"":null,"a":[]}"#;
+        let padded = crate::simdjson::pad_buffer(json);
+        let flat = crate::simdjson::dom_parse_to_flat_buf_tape(&padded, json.len()).unwrap();
+        let mut from_flat = Vec::new();
+        write_flat_sorted(&mut from_flat, flat.root()).unwrap();
+        let value = crate::simdjson::dom_parse_to_value_fast(&padded, json.len()).unwrap();
+        let mut from_value = Vec::new();
+        write_compact(&mut from_value, &value, true).unwrap();
+        assert_eq!(from_flat, from_value);
+    }
+
     // --- Output formatting edge cases (TEST_TODOS_3 #10) ---
 
     #[test]
@@ -1031,6 +1402,28 @@ mod tests {
         assert_eq!(buf, b"hello\0");
     }
 
+    #[test]
+    fn raw_join_separator() {
+        let config = OutputConfig {
+            mode: OutputMode::Raw,
+            join_separator: Some(", ".into()),
+            ..Default::default()
+        };
+        let v = Value::Array(Arc::new(vec![
+            Value::String("a".into()),
+            Value::Null,
+            Value::Int(1),
+            Value::Bool(false),
+        ]));
+        let mut buf = Vec::new();
+        write_value(&mut buf, &v, &config).unwrap();
+        assert_eq!(buf, b"a, , 1, false\n");
+
+        let nested = Value::Array(Arc::new(vec![Value::Array(Arc::new(vec![]))]));
+        let err = write_value(&mut Vec::new(), &nested, &config).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
     #[test]
     fn pretty_tab_indent() {
         let v = Value::Object(Arc::new(vec![("a".into(), Value::Int(1))]));
@@ -1044,4 +1437,73 @@ mod tests {
         let s = String::from_utf8(buf).unwrap();
         assert!(s.contains("\t\"a\""));
     }
+
+    #[test]
+    fn jq_colors_overrides_leading_fields() {
+        let scheme = ColorScheme::from_jq_colors("1;31:0;35:4").unwrap();
+        assert_eq!(scheme.null, "\x1b[1;31m");
+        assert_eq!(scheme.false_val, "\x1b[0;35m");
+        assert_eq!(scheme.true_val, "\x1b[4m");
+        assert_eq!(scheme.number, ColorScheme::jq_default().number);
+
+        let scheme = ColorScheme::from_jq_colors("0:0:0:0:0:0:0:7;33:junk").unwrap();
+        assert_eq!(scheme.object_key, "\x1b[7;33m");
+    }
+
+    #[test]
+    fn jq_colors_rejects_malformed_fields() {
+        assert!(ColorScheme::from_jq_colors("1;31:red").is_none());
+        assert!(ColorScheme::from_jq_colors("1;2;3;4;5;6;7").is_none());
+        assert!(ColorScheme::from_jq_colors("").is_some());
+    }
+
+    /// Accepts `room` bytes, then fails the way a full disk does.
+    struct FullDisk {
+        room: usize,
+        interrupted: bool,
+    }
+
+    impl Write for FullDisk {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if std::mem::take(&mut self.interrupted) {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            if self.room == 0 {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+            }
+            let n = buf.len().min(self.room);
+            self.room -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tracked_writer_records_first_error() {
+        let failure = WriteFailure::default();
+        let disk = FullDisk {
+            room: 5,
+            interrupted: true,
+        };
+        let mut out = io::BufWriter::new(TrackedWriter::new(disk, failure.clone()));
+        let config = OutputConfig {
+            mode: OutputMode::Compact,
+            ..OutputConfig::default()
+        };
+        for i in 0..3 {
+            write_value(&mut out, &Value::Int(i), &config).unwrap();
+        }
+        assert!(!failure.is_set());
+        // The retried interruption isn't a failure; running out of room is.
+        write_value(&mut out, &Value::String("long enough".into()), &config).unwrap();
+        assert!(out.flush().is_err());
+        assert!(out.flush().is_err());
+        let err = failure.take().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+        assert_eq!(err.to_string(), "disk full");
+        assert!(!failure.is_set());
+    }
 }
@@ -0,0 +1,400 @@
+/// Parallel `group_by` / `unique_by` over slurped NDJSON files.
+///
+/// `qj -s 'group_by(.user) | map(length)' big.ndjson` would otherwise hold
+/// every record in one array and group it on one thread. For the shapes
+/// below, each ~1MB chunk of lines is parsed and keyed on its own rayon
+/// thread into a map from group key to the group's size and first record.
+/// The maps are merged in input order, so each group keeps the record jq's
+/// stable sort would put first, and the groups come out sorted by key. Only
+/// the first record of each group is ever held.
+use rayon::prelude::*;
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::io::Seek;
+
+use super::ndjson::{
+    CHUNK_TARGET_SIZE, SharedFilter, can_parallelize, debug_parallel_enabled,
+    detect_ndjson_from_reader, for_each_window, split_chunks,
+};
+use crate::filter::{Env, Filter, ObjKey, values_order};
+use crate::value::Value;
+
+/// What is built from each group.
+#[derive(Debug, PartialEq)]
+enum GroupOutput {
+    /// `group_by(f) | map(length)`
+    Lengths,
+    /// `group_by(f) | map({user: .[0].user, n: length})`
+    Objects(Vec<(String, GroupField)>),
+    /// `unique_by(f)`: each group's first record.
+    Firsts,
+}
+
+/// A value of a `map({...})` object.
+#[derive(Debug, PartialEq)]
+enum GroupField {
+    /// `length`
+    Length,
+    /// `.[0]` or `first`, then a field chain
+    First(Vec<String>),
+}
+
+/// A slurped-input filter that groups its records: the grouping key, what's
+/// built from the groups, and the rest of the filter, which runs on that.
+#[derive(Debug, PartialEq)]
+pub struct GroupPlan {
+    key: Filter,
+    output: GroupOutput,
+    rest: Filter,
+}
+
+impl GroupPlan {
+    /// The part of the filter after the grouping, to run on the result of
+    /// [`slurp_groups`].
+    pub fn rest(&self) -> &Filter {
+        &self.rest
+    }
+}
+
+/// Recognize `group_by(f) | map(length)`, `group_by(f) | map({...})` whose
+/// values are `length` or fields of `.[0]`, and `unique_by(f)`, each
+/// optionally followed by more of the pipeline. `f` must be safe to run on
+/// worker threads.
+pub fn detect_group_plan(filter: &Filter, env: &Env) -> Option<GroupPlan> {
+    if crate::filter::profile::is_enabled()
+        || std::env::var_os("QJ_NO_FAST_PATH").is_some()
+        || crate::filter::eval_mode() >= crate::filter::EvalMode::Flat
+    {
+        return None;
+    }
+    let mut stages = Vec::new();
+    pipe_stages(filter, &mut stages);
+    let (key, output, rest) = match stages.as_slice() {
+        [Filter::Builtin(name, args), rest @ ..] if name == "unique_by" && args.len() == 1 => {
+            (&args[0], GroupOutput::Firsts, rest)
+        }
+        [
+            Filter::Builtin(name, args),
+            Filter::Builtin(map, body),
+            rest @ ..,
+        ] if name == "group_by" && args.len() == 1 && map == "map" && body.len() == 1 => {
+            (&args[0], detect_group_output(&body[0])?, rest)
+        }
+        _ => return None,
+    };
+    if key.uses_input_builtins() || !can_parallelize(key, env) {
+        return None;
+    }
+    let rest = rest
+        .iter()
+        .map(|&f| f.clone())
+        .reduce(|acc, f| Filter::Pipe(Box::new(acc), Box::new(f)))
+        .unwrap_or(Filter::Identity);
+    Some(GroupPlan {
+        key: key.clone(),
+        output,
+        rest,
+    })
+}
+
+/// The stages of a pipeline, left to right.
+fn pipe_stages<'a>(filter: &'a Filter, stages: &mut Vec<&'a Filter>) {
+    match filter {
+        Filter::Pipe(lhs, rhs) => {
+            pipe_stages(lhs, stages);
+            pipe_stages(rhs, stages);
+        }
+        _ => stages.push(filter),
+    }
+}
+
+fn detect_group_output(body: &Filter) -> Option<GroupOutput> {
+    if is_length(body) {
+        return Some(GroupOutput::Lengths);
+    }
+    let Filter::ObjectConstruct(pairs) = body else {
+        return None;
+    };
+    let mut fields = Vec::with_capacity(pairs.len());
+    for (key, value) in pairs {
+        let ObjKey::Name(name) = key else {
+            return None;
+        };
+        let field = if is_length(value) {
+            GroupField::Length
+        } else {
+            GroupField::First(first_field_chain(value)?)
+        };
+        fields.push((name.clone(), field));
+    }
+    (!fields.is_empty()).then_some(GroupOutput::Objects(fields))
+}
+
+fn is_length(filter: &Filter) -> bool {
+    matches!(filter, Filter::Builtin(name, args) if name == "length" && args.is_empty())
+}
+
+/// The fields `f` reads from the first element, for `.[0]`, `first` and
+/// either followed by `.a.b`.
+fn first_field_chain(filter: &Filter) -> Option<Vec<String>> {
+    let is_first = |f: &Filter| match f {
+        Filter::Index(idx) => matches!(idx.as_ref(), Filter::Literal(Value::Int(0))),
+        Filter::Builtin(name, args) => name == "first" && args.is_empty(),
+        _ => false,
+    };
+    let mut stages = Vec::new();
+    pipe_stages(filter, &mut stages);
+    let (head, tail) = stages.split_first()?;
+    if !is_first(head) {
+        return None;
+    }
+    let mut fields = Vec::new();
+    for stage in tail {
+        match stage {
+            Filter::Field(name) => fields.push(name.clone()),
+            Filter::FieldPath(names) => fields.extend(names.iter().cloned()),
+            _ => return None,
+        }
+    }
+    Some(fields)
+}
+
+/// A group key, ordered the way `group_by` sorts keys.
+struct GroupKey(Value);
+
+impl Ord for GroupKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        values_order(&self.0, &other.0).unwrap_or(std::cmp::Ordering::Equal)
+    }
+}
+
+impl PartialOrd for GroupKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for GroupKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for GroupKey {}
+
+struct Group {
+    len: usize,
+    /// Kept only when the output reads it.
+    first: Option<Value>,
+}
+
+type Groups = BTreeMap<GroupKey, Group>;
+
+/// Group the records of NDJSON `paths` as `plan` says, returning what the
+/// grouping stage outputs (the input to [`GroupPlan::rest`]).
+///
+/// Returns `None` when this path doesn't apply or can't reproduce what the
+/// sequential path would do: a file that's compressed, not NDJSON or can't
+/// be read, a line that doesn't parse, a key that raises an error, or a
+/// `.[0].field` of a record that isn't an object. The caller then slurps as
+/// usual, which reports any error.
+pub fn slurp_groups(paths: &[String], force_jsonl: bool, plan: &GroupPlan) -> Option<Value> {
+    let keep_first = plan.output != GroupOutput::Lengths;
+    let shared = SharedFilter::new(&plan.key);
+    let mut groups = Groups::new();
+    let mut chunk_count = 0;
+    for path in paths {
+        if crate::decompress::is_compressed(path) {
+            return None;
+        }
+        let mut file = std::fs::File::open(path).ok()?;
+        if !force_jsonl && !detect_ndjson_from_reader(&mut file).ok()? {
+            return None;
+        }
+        file.seek(std::io::SeekFrom::Start(0)).ok()?;
+        let mut first_window = true;
+        for_each_window(&mut file, |mut window| {
+            if std::mem::take(&mut first_window) {
+                window = window.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(window);
+            }
+            let chunks = split_chunks(window, CHUNK_TARGET_SIZE);
+            chunk_count += chunks.len();
+            let partials: Option<Vec<Groups>> = chunks
+                .par_iter()
+                .map(|&chunk| group_chunk(chunk, shared.get(), keep_first))
+                .collect();
+            let partials = partials.ok_or_else(|| anyhow::anyhow!("ungroupable chunk"))?;
+            for partial in partials {
+                merge_groups(&mut groups, partial);
+            }
+            Ok(())
+        })
+        .ok()?;
+    }
+    let group_count = groups.len();
+    let output = build_output(groups, &plan.output)?;
+    if debug_parallel_enabled() {
+        eprintln!("qj: debug: ndjson group aggregate ({chunk_count} chunks, {group_count} groups)");
+    }
+    Some(output)
+}
+
+/// Parse and key one chunk of lines. `None` if a line doesn't parse or the
+/// key raises an error.
+fn group_chunk(chunk: &[u8], key: &Filter, keep_first: bool) -> Option<Groups> {
+    let mut records = Vec::new();
+    crate::input::parse_lines(chunk, &mut records).ok()?;
+    let env = Env::empty();
+    let mut groups = Groups::new();
+    for record in records {
+        // As in `group_by`, the key is every output of `f`.
+        let mut keys = Vec::new();
+        crate::filter::eval::eval_filter_with_env(key, &record, &env, &mut |v| keys.push(v));
+        if crate::filter::eval::has_last_error(&env) {
+            return None;
+        }
+        match groups.entry(GroupKey(Value::Array(keys.into()))) {
+            Entry::Vacant(slot) => {
+                slot.insert(Group {
+                    len: 1,
+                    first: keep_first.then_some(record),
+                });
+            }
+            Entry::Occupied(mut slot) => slot.get_mut().len += 1,
+        }
+    }
+    Some(groups)
+}
+
+/// Fold a later chunk's groups into `groups`, keeping the earlier first records.
+fn merge_groups(groups: &mut Groups, later: Groups) {
+    for (key, group) in later {
+        match groups.entry(key) {
+            Entry::Vacant(slot) => {
+                slot.insert(group);
+            }
+            Entry::Occupied(mut slot) => slot.get_mut().len += group.len,
+        }
+    }
+}
+
+fn build_output(groups: Groups, output: &GroupOutput) -> Option<Value> {
+    let mut values = Vec::with_capacity(groups.len());
+    for group in groups.into_values() {
+        values.push(match output {
+            GroupOutput::Lengths => Value::Int(group.len as i64),
+            GroupOutput::Firsts => group.first?,
+            GroupOutput::Objects(fields) => {
+                let first = group.first?;
+                let mut obj = Vec::with_capacity(fields.len());
+                for (name, field) in fields {
+                    let value = match field {
+                        GroupField::Length => Value::Int(group.len as i64),
+                        GroupField::First(path) => field_of(&first, path)?,
+                    };
+                    obj.push((name.clone(), value));
+                }
+                Value::Object(obj.into())
+            }
+        });
+    }
+    Some(Value::Array(values.into()))
+}
+
+/// `value.a.b…`, or `None` where indexing would be an error.
+fn field_of(value: &Value, path: &[String]) -> Option<Value> {
+    let mut current = value.clone();
+    for name in path {
+        current = match &current {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k == name)
+                .map_or(Value::Null, |(_, v)| v.clone()),
+            Value::Null => Value::Null,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::parse;
+
+    fn plan(filter: &str) -> Option<GroupPlan> {
+        detect_group_plan(&parse(filter).unwrap(), &Env::empty())
+    }
+
+    #[test]
+    fn detects_group_shapes() {
+        let p = plan("group_by(.user) | map({user: .[0].user, n: length})").unwrap();
+        assert_eq!(
+            p.output,
+            GroupOutput::Objects(vec![
+                ("user".into(), GroupField::First(vec!["user".into()])),
+                ("n".into(), GroupField::Length),
+            ])
+        );
+        assert_eq!(p.rest, Filter::Identity);
+        let p = plan("group_by(.a, .b) | map(length) | add").unwrap();
+        assert_eq!(p.output, GroupOutput::Lengths);
+        assert_eq!(p.rest, parse("add").unwrap());
+        let p = plan("unique_by(.id) | map(.name)").unwrap();
+        assert_eq!(p.output, GroupOutput::Firsts);
+        assert_eq!(p.rest, parse("map(.name)").unwrap());
+        assert!(plan("group_by(.a) | map({x: first.b.c, y: .[0]})").is_some());
+    }
+
+    #[test]
+    fn other_shapes_are_left_alone() {
+        for filter in [
+            "group_by(.a)",
+            "group_by(.a) | map(add)",
+            "group_by(.a) | map({n: length, m: .[1].x})",
+            "group_by(.a) | map({(.[0].k): length})",
+            "map(.a) | group_by(.)",
+            "group_by(input) | map(length)",
+            "sort_by(.a)",
+        ] {
+            assert_eq!(plan(filter), None, "{filter}");
+        }
+        // The key can't read the caller's variables on a worker thread.
+        let env = Env::empty().bind_var("$k".into(), Value::Int(1));
+        let filter = parse("group_by(.[$k]) | map(length)").unwrap();
+        assert_eq!(detect_group_plan(&filter, &env), None);
+    }
+
+    #[test]
+    fn chunks_merge_in_input_order() {
+        let key = parse(".k").unwrap();
+        let mut groups =
+            group_chunk(b"{\"k\":2,\"i\":0}\n{\"k\":1,\"i\":1}\n", &key, true).unwrap();
+        let later = group_chunk(b"{\"k\":1,\"i\":2}\n{\"k\":1.0,\"i\":3}\n", &key, true).unwrap();
+        merge_groups(&mut groups, later);
+        let output = GroupOutput::Objects(vec![
+            ("i".into(), GroupField::First(vec!["i".into()])),
+            ("n".into(), GroupField::Length),
+        ]);
+        let Value::Array(result) = build_output(groups, &output).unwrap() else {
+            panic!("not an array");
+        };
+        let pairs: Vec<_> = result
+            .iter()
+            .map(|g| match g {
+                Value::Object(o) => (o[0].1.clone(), o[1].1.clone()),
+                _ => panic!("not an object"),
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                (Value::Int(1), Value::Int(3)),
+                (Value::Int(0), Value::Int(1))
+            ]
+        );
+        assert!(group_chunk(b"{\"k\":1}\n{bad\n", &key, false).is_none());
+        assert!(group_chunk(b"[1]\n", &key, false).is_none());
+    }
+}
@@ -0,0 +1,317 @@
+/// Parallel evaluation over one large top-level JSON array.
+///
+/// Filters that start by iterating the input (`.[] | f`, `map(f)`) only ever
+/// look at one element at a time, so a big array document can be split at
+/// its top-level commas into ~1MB runs of elements, each parsed and
+/// evaluated on its own rayon thread, with output concatenated in order.
+use anyhow::Result;
+use rayon::prelude::*;
+
+use std::io::Write;
+
+use super::ndjson::{SharedFilter, can_parallelize, debug_parallel_enabled};
+use crate::filter::{Env, Filter};
+use crate::output::{self, OutputConfig};
+use crate::simdjson;
+use crate::value::Value;
+
+/// Target bytes of array elements per chunk, as for NDJSON.
+const CHUNK_TARGET_SIZE: usize = 1024 * 1024;
+
+/// How a filter iterates the top-level array.
+#[derive(Debug, PartialEq)]
+enum ArrayPlan {
+    /// `.[] | f` — every output of `f` on every element, in order.
+    Each(Filter),
+    /// `map(f)` — the same outputs, collected into one array.
+    Map(Filter),
+}
+
+/// Output status of [`process_array`], for the caller's exit code.
+#[derive(Debug, Default)]
+pub struct ArrayStatus {
+    pub had_output: bool,
+    pub last_was_falsy: bool,
+    /// The uncaught error that stopped evaluation, if any. Output produced
+    /// before it has already been written, as with sequential evaluation.
+    pub error: Option<Value>,
+}
+
+/// Per-chunk evaluation result.
+#[derive(Default)]
+struct ChunkResult {
+    out: Vec<u8>,
+    values: Vec<Value>,
+    had_output: bool,
+    last_was_falsy: bool,
+    error: Option<Value>,
+}
+
+/// Evaluate `filter` over the top-level array in `json` in parallel.
+///
+/// Returns `Ok(None)` without writing anything when this path doesn't apply:
+/// the filter doesn't start by iterating its input (or needs the caller's
+/// variables), the document isn't a single array large enough to split, or a
+/// chunk fails to parse (the caller's sequential path reports that).
+pub fn process_array<W: Write>(
+    json: &[u8],
+    filter: &Filter,
+    config: &OutputConfig,
+    env: &Env,
+    out: &mut W,
+) -> Result<Option<ArrayStatus>> {
+    // Embedded NULs under --raw-output0 are reported mid-stream; leave those
+    // to the sequential path. So is a single thread, where building a Value
+    // tree per chunk loses to the lazy flat evaluator.
+    if config.null_separator || rayon::current_num_threads() < 2 || !can_parallelize(filter, env) {
+        return Ok(None);
+    }
+    let Some(plan) = detect_array_plan(filter) else {
+        return Ok(None);
+    };
+    let Some(chunks) = split_array_chunks(json, CHUNK_TARGET_SIZE) else {
+        return Ok(None);
+    };
+    if chunks.len() < 2 {
+        return Ok(None);
+    }
+    let (inner, collect) = match &plan {
+        ArrayPlan::Each(f) => (f, false),
+        ArrayPlan::Map(f) => (f, true),
+    };
+    let shared = SharedFilter::new(inner);
+    let results: Option<Vec<ChunkResult>> = chunks
+        .par_iter()
+        .map(|&(start, end)| eval_chunk(&json[start..end], shared.get(), config, collect))
+        .collect();
+    let Some(results) = results else {
+        return Ok(None);
+    };
+    // Only now is it certain the parallel path ran: a chunk that fails to
+    // parse hands the whole document back to the sequential path.
+    if debug_parallel_enabled() {
+        eprintln!("qj: debug: array parallel ({} chunks)", chunks.len());
+    }
+
+    let mut status = ArrayStatus::default();
+    if collect {
+        if let Some(err) = results.iter().find_map(|r| r.error.clone()) {
+            status.error = Some(err);
+            return Ok(Some(status));
+        }
+        let values: Vec<Value> = results.into_iter().flat_map(|r| r.values).collect();
+        output::write_value(out, &Value::Array(values.into()), config)?;
+        status.had_output = true;
+        return Ok(Some(status));
+    }
+    for result in results {
+        output::write_formatted(out, &result.out, config)?;
+        if result.had_output {
+            status.had_output = true;
+            status.last_was_falsy = result.last_was_falsy;
+        }
+        if result.error.is_some() {
+            status.error = result.error;
+            break;
+        }
+    }
+    Ok(Some(status))
+}
+
+/// Whether `filter` is `.[] | f` or `map(f)`, and `f` if so.
+fn detect_array_plan(filter: &Filter) -> Option<ArrayPlan> {
+    if let Filter::Builtin(name, args) = filter
+        && name == "map"
+        && args.len() == 1
+    {
+        return Some(ArrayPlan::Map(args[0].clone()));
+    }
+    strip_leading_iterate(filter).map(ArrayPlan::Each)
+}
+
+/// For a filter that begins with `.[]`, the rest of the pipeline after it.
+/// `(.[] | a) | b` and `.[] | (a | b)` both give `a | b`.
+fn strip_leading_iterate(filter: &Filter) -> Option<Filter> {
+    match filter {
+        Filter::Iterate => Some(Filter::Identity),
+        Filter::Pipe(lhs, rhs) => Some(match strip_leading_iterate(lhs)? {
+            Filter::Identity => rhs.as_ref().clone(),
+            rest => Filter::Pipe(Box::new(rest), rhs.clone()),
+        }),
+        _ => None,
+    }
+}
+
+/// Parse one run of comma-separated elements and evaluate `filter` on each.
+/// `None` if the run doesn't parse.
+fn eval_chunk(
+    elements: &[u8],
+    filter: &Filter,
+    config: &OutputConfig,
+    collect: bool,
+) -> Option<ChunkResult> {
+    let mut doc = Vec::with_capacity(elements.len() + 2 + simdjson::padding());
+    doc.push(b'[');
+    doc.extend_from_slice(elements);
+    doc.push(b']');
+    let json_len = doc.len();
+    doc.resize(json_len + simdjson::padding(), 0);
+    let Ok(Value::Array(items)) = simdjson::dom_parse_to_value_fast(&doc, json_len) else {
+        return None;
+    };
+
+    let env = Env::empty();
+    let mut result = ChunkResult::default();
+    for item in items.iter() {
+        crate::filter::eval::eval_filter_with_env(filter, item, &env, &mut |v| {
+            if collect {
+                result.values.push(v);
+                return;
+            }
+            result.had_output = true;
+            result.last_was_falsy = matches!(v, Value::Null | Value::Bool(false));
+            output::write_value(&mut result.out, &v, config).ok();
+        });
+        if let Some(err) = crate::filter::eval::take_last_error(&env) {
+            result.error = Some(err);
+            break;
+        }
+    }
+    Some(result)
+}
+
+/// Split a document holding one top-level array into runs of whole elements,
+/// each about `target_size` bytes, as `(start, end)` byte ranges without the
+/// separating commas.
+///
+/// Returns `None` unless `json` is a single non-empty array (surrounding
+/// whitespace aside) whose brackets and strings balance; anything else is
+/// left to the sequential path.
+fn split_array_chunks(json: &[u8], target_size: usize) -> Option<Vec<(usize, usize)>> {
+    let is_ws = |b: u8| matches!(b, b' ' | b'\t' | b'\r' | b'\n');
+    let mut i = json.iter().position(|&b| !is_ws(b))?;
+    if json[i] != b'[' {
+        return None;
+    }
+    i += 1;
+    let mut chunk_start = i;
+    let mut depth: u32 = 0;
+    let mut chunks = Vec::new();
+    let mut empty = true;
+    while i < json.len() {
+        match json[i] {
+            b'"' => {
+                // Jump to the closing quote, stepping over escapes.
+                i += 1;
+                loop {
+                    i += memchr::memchr2(b'"', b'\\', &json[i..])?;
+                    if json[i] == b'\\' {
+                        i += 2;
+                        if i >= json.len() {
+                            return None;
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                empty = false;
+            }
+            b'{' | b'[' => {
+                depth += 1;
+                empty = false;
+            }
+            b'}' | b']' if depth > 0 => depth -= 1,
+            b']' => {
+                if empty {
+                    return None;
+                }
+                chunks.push((chunk_start, i));
+                if !json[i + 1..].iter().all(|&b| is_ws(b)) {
+                    return None;
+                }
+                return Some(chunks);
+            }
+            b'}' => return None,
+            b',' if depth == 0 => {
+                if i - chunk_start >= target_size {
+                    chunks.push((chunk_start, i));
+                    chunk_start = i + 1;
+                }
+            }
+            b => {
+                if !is_ws(b) {
+                    empty = false;
+                }
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(filter: &str) -> Filter {
+        crate::filter::parse(filter).unwrap()
+    }
+
+    fn plan(filter: &str) -> Option<ArrayPlan> {
+        detect_array_plan(&parse(filter))
+    }
+
+    #[test]
+    fn detect_iterate_pipelines() {
+        assert_eq!(plan(".[]"), Some(ArrayPlan::Each(Filter::Identity)));
+        assert_eq!(plan(".[].a"), Some(ArrayPlan::Each(parse(".a"))));
+        assert_eq!(
+            plan(".[] | select(.x) | .y"),
+            Some(ArrayPlan::Each(parse("select(.x) | .y")))
+        );
+        assert_eq!(plan("map(.a + 1)"), Some(ArrayPlan::Map(parse(".a + 1"))));
+        // Anything that sees the whole array stays sequential.
+        for filter in [
+            ".",
+            "sort",
+            "group_by(.a)",
+            "reduce .[] as $x (0; . + $x)",
+            "[.[] | .a]",
+            "first(.[])",
+            "limit(2; .[])",
+            ".[]?",
+            ".[1:]",
+        ] {
+            assert_eq!(plan(filter), None, "{filter}");
+        }
+    }
+
+    #[test]
+    fn split_at_top_level_commas() {
+        let json = br#" [ {"a":[1,2]}, "x,]\"y", 3 ,[]] "#;
+        let chunks = split_array_chunks(json, 1).unwrap();
+        let parts: Vec<&str> = chunks
+            .iter()
+            .map(|&(s, e)| std::str::from_utf8(&json[s..e]).unwrap().trim())
+            .collect();
+        assert_eq!(parts, [r#"{"a":[1,2]}"#, r#""x,]\"y""#, "3", "[]"]);
+        // One run when everything fits.
+        assert_eq!(split_array_chunks(b"[1,2,3]", 100), Some(vec![(1, 6)]));
+    }
+
+    #[test]
+    fn split_rejects_non_arrays() {
+        for json in [
+            &b"{\"a\":1}"[..],
+            b"[]",
+            b" [ ] ",
+            b"[1,2",
+            b"[1,2] [3]",
+            b"[1,}",
+            b"[\"open]",
+            b"",
+        ] {
+            assert_eq!(split_array_chunks(json, 1), None, "{json:?}");
+        }
+    }
+}
@@ -0,0 +1,326 @@
+/// Parallel evaluation over a stream of concatenated JSON documents.
+///
+/// Other tools often pretty-print a stream of documents, each spanning many
+/// lines, which isn't NDJSON and doesn't parse as one document. A scanner
+/// finds where each document ends (bracket depth outside strings), and runs
+/// of ~1MB of whole documents are parsed and evaluated on their own rayon
+/// threads, with output and error messages written in input order.
+use anyhow::Result;
+use rayon::prelude::*;
+
+use std::io::Write;
+
+use super::ndjson::{SharedFilter, can_parallelize, debug_parallel_enabled};
+use crate::filter::{Env, Filter};
+use crate::output::{self, OutputConfig};
+use crate::simdjson;
+use crate::value::Value;
+
+/// Target bytes of documents per chunk, as for NDJSON.
+const CHUNK_TARGET_SIZE: usize = 1024 * 1024;
+
+/// Output status of [`process_documents`], for the caller's exit code.
+#[derive(Debug, Default)]
+pub struct DocumentsStatus {
+    pub had_output: bool,
+    pub last_was_falsy: bool,
+    /// Whether any document raised an uncaught error. Each one has been
+    /// reported, and evaluation went on with the next document.
+    pub had_error: bool,
+    /// Whether the last document raised one, which jq's exit status
+    /// follows.
+    pub last_failed: bool,
+}
+
+/// Per-chunk evaluation result.
+#[derive(Default)]
+struct ChunkResult {
+    out: Vec<u8>,
+    /// Uncaught errors, with the end offset of the document that raised
+    /// each one so the caller can report its line.
+    errors: Vec<(usize, Value)>,
+    had_output: bool,
+    last_was_falsy: bool,
+}
+
+/// Evaluate `filter` on each document of a concatenated stream in `json`,
+/// writing output to `out` and uncaught errors to `errors`, each located at
+/// its document's line of the input `env` is reading.
+///
+/// Returns `Ok(None)` without evaluating anything when this path doesn't
+/// apply: `json` isn't two or more whole documents that each parse, or
+/// `--raw-output0` is on (embedded NULs are reported mid-stream). The
+/// caller's sequential fallback handles those, malformed input included.
+/// Documents are only spread across threads when the filter doesn't need
+/// the caller's variables; otherwise they run in order on this thread.
+pub fn process_documents<W: Write, E: Write>(
+    json: &[u8],
+    filter: &Filter,
+    config: &OutputConfig,
+    env: &Env,
+    out: &mut W,
+    errors: &mut E,
+) -> Result<Option<DocumentsStatus>> {
+    if config.null_separator {
+        return Ok(None);
+    }
+    let Some(docs) = split_documents(json) else {
+        return Ok(None);
+    };
+    if docs.len() < 2 {
+        return Ok(None);
+    }
+    let chunks = group_documents(&docs, CHUNK_TARGET_SIZE);
+    let parallel =
+        chunks.len() > 1 && rayon::current_num_threads() >= 2 && can_parallelize(filter, env);
+
+    // Check every document first, so nothing is evaluated (and no `debug`
+    // output appears) when the sequential fallback has to take over.
+    let valid = if parallel {
+        chunks.par_iter().all(|docs| validate_chunk(json, docs))
+    } else {
+        chunks.iter().all(|docs| validate_chunk(json, docs))
+    };
+    if !valid {
+        return Ok(None);
+    }
+
+    let results: Vec<ChunkResult> = if parallel {
+        if debug_parallel_enabled() {
+            eprintln!("qj: debug: document parallel ({} chunks)", chunks.len());
+        }
+        let shared = SharedFilter::new(filter);
+        chunks
+            .par_iter()
+            .map(|docs| eval_chunk(json, docs, shared.get(), config, &Env::empty()))
+            .collect()
+    } else {
+        if debug_parallel_enabled() {
+            eprintln!("qj: debug: document sequential ({} docs)", docs.len());
+        }
+        vec![eval_chunk(json, &docs, filter, config, env)]
+    };
+
+    let mut status = DocumentsStatus::default();
+    let source = env.input_name();
+    let mut lines = crate::input::LineCounter::default();
+    let last_end = docs.last().map(|&(_, end)| end);
+    for result in results {
+        output::write_formatted(out, &result.out, config)?;
+        for (end, err) in &result.errors {
+            status.had_error = true;
+            status.last_failed = Some(*end) == last_end;
+            let location = format!("{source}:{}", lines.line_of_value(json, *end));
+            let _ = writeln!(
+                errors,
+                "{}",
+                output::format_uncaught_error_at(err, &location)
+            );
+        }
+        if result.had_output {
+            status.had_output = true;
+            status.last_was_falsy = result.last_was_falsy;
+        }
+    }
+    Ok(Some(status))
+}
+
+/// Copy `doc` into `scratch` with simdjson's padding after it.
+fn pad_into<'a>(doc: &[u8], scratch: &'a mut Vec<u8>) -> &'a [u8] {
+    scratch.clear();
+    scratch.extend_from_slice(doc);
+    scratch.resize(doc.len() + simdjson::padding(), 0);
+    scratch
+}
+
+/// Whether every document in the chunk is valid JSON.
+fn validate_chunk(json: &[u8], docs: &[(usize, usize)]) -> bool {
+    let mut scratch = Vec::new();
+    docs.iter().all(|&(start, end)| {
+        let doc = &json[start..end];
+        simdjson::dom_validate(pad_into(doc, &mut scratch), doc.len()).is_ok()
+    })
+}
+
+/// Parse and evaluate each document of a validated chunk. An uncaught error
+/// is reported in the chunk's `errors` and the next document goes on.
+fn eval_chunk(
+    json: &[u8],
+    docs: &[(usize, usize)],
+    filter: &Filter,
+    config: &OutputConfig,
+    env: &Env,
+) -> ChunkResult {
+    let mut result = ChunkResult::default();
+    let mut scratch = Vec::new();
+    for &(start, end) in docs {
+        let doc = &json[start..end];
+        let Ok(input) = simdjson::dom_parse_to_value_fast(pad_into(doc, &mut scratch), doc.len())
+        else {
+            continue;
+        };
+        crate::filter::eval::eval_filter_with_env(filter, &input, env, &mut |v| {
+            result.had_output = true;
+            result.last_was_falsy = matches!(v, Value::Null | Value::Bool(false));
+            output::write_value(&mut result.out, &v, config).ok();
+        });
+        if let Some(err) = crate::filter::eval::take_last_error(env) {
+            result.errors.push((end, err));
+        }
+    }
+    result
+}
+
+/// Group consecutive documents into runs of about `target_size` bytes.
+fn group_documents(docs: &[(usize, usize)], target_size: usize) -> Vec<&[(usize, usize)]> {
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    for (i, &(_, end)) in docs.iter().enumerate() {
+        if end - docs[first].0 >= target_size {
+            chunks.push(&docs[first..=i]);
+            first = i + 1;
+        }
+    }
+    if first < docs.len() {
+        chunks.push(&docs[first..]);
+    }
+    chunks
+}
+
+/// Find the documents in a buffer of concatenated JSON texts, as `(start,
+/// end)` byte ranges. Objects, arrays and strings end where their brackets
+/// or quotes balance; other scalars end at whitespace or where the next
+/// document starts.
+///
+/// Returns `None` if a closing bracket has no opener or a document is cut
+/// off; such input is left to the sequential path and its error reporting.
+fn split_documents(json: &[u8]) -> Option<Vec<(usize, usize)>> {
+    let is_ws = |b: u8| matches!(b, b' ' | b'\t' | b'\r' | b'\n');
+    let mut docs = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < json.len() && is_ws(json[i]) {
+            i += 1;
+        }
+        if i == json.len() {
+            return Some(docs);
+        }
+        let start = i;
+        match json[i] {
+            b'{' | b'[' => {
+                let mut depth: u32 = 0;
+                loop {
+                    match *json.get(i)? {
+                        b'"' => i = string_end(json, i)?,
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'"' => i = string_end(json, i)? + 1,
+            b'}' | b']' => return None,
+            _ => {
+                while i < json.len()
+                    && !is_ws(json[i])
+                    && !matches!(json[i], b'{' | b'[' | b'"' | b'}' | b']')
+                {
+                    i += 1;
+                }
+            }
+        }
+        docs.push((start, i));
+    }
+}
+
+/// The index of the quote closing the string that opens at `start`.
+fn string_end(json: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    loop {
+        i += memchr::memchr2(b'"', b'\\', json.get(i..)?)?;
+        if json[i] == b'"' {
+            return Some(i);
+        }
+        i += 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(json: &str) -> Option<Vec<&str>> {
+        let docs = split_documents(json.as_bytes())?;
+        Some(docs.iter().map(|&(s, e)| &json[s..e]).collect())
+    }
+
+    #[test]
+    fn split_concatenated_documents() {
+        assert_eq!(
+            split("{\n  \"a\": \"}{\\\"\"\n}\n[\n  1,\n  [2]\n]{}\"s\" 1 true\n"),
+            Some(vec![
+                "{\n  \"a\": \"}{\\\"\"\n}",
+                "[\n  1,\n  [2]\n]",
+                "{}",
+                "\"s\"",
+                "1",
+                "true"
+            ])
+        );
+        assert_eq!(split("  \n"), Some(vec![]));
+        assert_eq!(split("1[2]"), Some(vec!["1", "[2]"]));
+        for json in ["{\"a\":1}}", "{\"a\":1", "[\"open]", "\"x", "]"] {
+            assert_eq!(split(json), None, "{json:?}");
+        }
+    }
+
+    #[test]
+    fn group_into_runs() {
+        let docs = [(0, 4), (5, 9), (10, 30), (31, 32)];
+        let chunks = group_documents(&docs, 8);
+        assert_eq!(chunks, [&docs[..2], &docs[2..3], &docs[3..]]);
+        assert_eq!(group_documents(&docs, 100), [&docs[..]]);
+    }
+
+    #[test]
+    fn documents_match_sequential_evaluation() {
+        let json = b"{\"a\":1}\n{\n  \"a\": \"x\"\n}\n{\"a\":[3]}\n";
+        let filter = crate::filter::parse(".a + 1").unwrap();
+        let config = OutputConfig {
+            mode: output::OutputMode::Compact,
+            ..Default::default()
+        };
+        let (mut out, mut errors) = (Vec::new(), Vec::new());
+        let env = Env::empty();
+        env.set_input_name("docs.json");
+        let status = process_documents(json, &filter, &config, &env, &mut out, &mut errors)
+            .unwrap()
+            .unwrap();
+        assert_eq!(out, b"2\n");
+        assert!(status.had_output && status.had_error && status.last_failed);
+        // Each error names the line its document ends on.
+        let reported = String::from_utf8(errors.clone()).unwrap();
+        let locations: Vec<_> = reported
+            .lines()
+            .map(|l| l.split(')').next().unwrap())
+            .collect();
+        assert_eq!(
+            locations,
+            ["qj: error (at docs.json:4", "qj: error (at docs.json:5"],
+            "{reported}"
+        );
+
+        // A malformed document leaves everything to the fallback.
+        let bad = b"{\"a\":1}\n{\"a\":}\n{\"a\":3}\n";
+        let status =
+            process_documents(bad, &filter, &config, &Env::empty(), &mut out, &mut errors).unwrap();
+        assert!(status.is_none());
+    }
+}
@@ -0,0 +1,137 @@
+/// Parallel evaluation across several input files.
+///
+/// With more than one file on the command line and a filter that needs no
+/// cross-document state, each file can be read, parsed and evaluated on its
+/// own rayon thread. Output is buffered per file and handed back in argv
+/// order, so the caller writes exactly what sequential processing would.
+use rayon::prelude::*;
+
+use std::path::Path;
+
+use super::ndjson::{SharedFilter, can_parallelize, debug_parallel_enabled, is_ndjson};
+use crate::filter::{Env, Filter};
+use crate::output::{self, OutputConfig};
+use crate::simdjson;
+use crate::value::Value;
+
+/// Files bigger than this are left to the sequential path, which mmaps them
+/// and can split a big top-level array across threads on its own.
+const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Buffered result of evaluating the filter on one file.
+#[derive(Debug, Default)]
+pub struct FileOutput {
+    pub out: Vec<u8>,
+    pub had_output: bool,
+    pub last_was_falsy: bool,
+    /// The uncaught error that stopped evaluation, if any. `out` holds what
+    /// was produced before it.
+    pub error: Option<Value>,
+    /// jq's line number of the file's document, for reporting `error`.
+    pub line: usize,
+}
+
+/// Whether [`eval_files`] may be used for `filter`: more than one thread,
+/// no `--raw-output0` (embedded NULs are reported mid-stream), and a filter
+/// that is safe to run away from the caller's environment.
+pub fn can_eval_files(filter: &Filter, config: &OutputConfig, env: &Env) -> bool {
+    !config.null_separator && rayon::current_num_threads() >= 2 && can_parallelize(filter, env)
+}
+
+/// Evaluate `filter` on each file in `paths` in parallel, results in order.
+///
+/// An entry is `None` when that file should go through the sequential path
+/// instead: it can't be read (the caller reports the error against its
+/// path), is compressed, NDJSON (or `force_jsonl` is set), empty, very
+/// large, or isn't exactly one JSON document simdjson can parse.
+pub fn eval_files(
+    paths: &[String],
+    filter: &Filter,
+    config: &OutputConfig,
+    force_jsonl: bool,
+) -> Vec<Option<FileOutput>> {
+    if debug_parallel_enabled() {
+        eprintln!("qj: debug: file parallel ({} files)", paths.len());
+    }
+    let shared = SharedFilter::new(filter);
+    paths
+        .par_iter()
+        .map(|path| eval_file(path, shared.get(), config, force_jsonl))
+        .collect()
+}
+
+fn eval_file(
+    path: &str,
+    filter: &Filter,
+    config: &OutputConfig,
+    force_jsonl: bool,
+) -> Option<FileOutput> {
+    if force_jsonl || crate::decompress::is_compressed(path) {
+        return None;
+    }
+    let meta = std::fs::metadata(path).ok()?;
+    if !meta.is_file() || meta.len() == 0 || meta.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let buf = std::fs::read(Path::new(path)).ok()?;
+    if is_ndjson(&buf) {
+        return None;
+    }
+    let padded = simdjson::pad_buffer(&buf);
+    let input = simdjson::dom_parse_to_value_fast(&padded, buf.len()).ok()?;
+
+    let env = Env::empty();
+    let mut result = FileOutput::default();
+    crate::filter::eval::eval_filter_with_env(filter, &input, &env, &mut |v| {
+        result.had_output = true;
+        result.last_was_falsy = matches!(v, Value::Null | Value::Bool(false));
+        output::write_value(&mut result.out, &v, config).ok();
+    });
+    result.error = crate::filter::eval::take_last_error(&env);
+    if result.error.is_some() {
+        result.line = crate::input::line_of_last_value(&buf);
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(dir: &Path, name: &str, contents: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn eval_files_keeps_order_and_defers_odd_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let paths = vec![
+            write_temp(dir, "a.json", r#"{"x":1}"#),
+            write_temp(dir, "b.json", "{\"x\":2}\n{\"x\":3}\n"),
+            write_temp(dir, "c.json", ""),
+            dir.join("missing.json").to_string_lossy().into_owned(),
+            write_temp(dir, "d.json", r#"{"x":"s"}"#),
+        ];
+        let filter = crate::filter::parse(".x + 1").unwrap();
+        let config = OutputConfig {
+            mode: output::OutputMode::Compact,
+            ..Default::default()
+        };
+        let results = eval_files(&paths, &filter, &config, false);
+
+        assert_eq!(results.len(), 5);
+        let a = results[0].as_ref().unwrap();
+        assert_eq!(a.out, b"2\n");
+        assert!(a.had_output && a.error.is_none());
+        // NDJSON, empty and unreadable files go to the sequential path.
+        assert!(results[1].is_none());
+        assert!(results[2].is_none());
+        assert!(results[3].is_none());
+        let d = results[4].as_ref().unwrap();
+        assert!(d.out.is_empty() && !d.had_output);
+        assert!(d.error.is_some());
+    }
+}
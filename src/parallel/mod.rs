@@ -1 +1,31 @@
+pub mod aggregate;
+pub mod array;
+pub mod documents;
+pub mod files;
 pub mod ndjson;
+
+/// Detect P-core count on Apple Silicon via sysctlbyname(3), fall back to available_parallelism.
+/// Only runs on aarch64 macOS — Intel Macs don't have P/E core distinction.
+pub fn default_thread_count() -> usize {
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    {
+        let mut val: i32 = 0;
+        let mut size = std::mem::size_of::<i32>();
+        let name = b"hw.perflevel0.logicalcpu\0";
+        let ret = unsafe {
+            libc::sysctlbyname(
+                name.as_ptr() as *const libc::c_char,
+                &mut val as *mut i32 as *mut libc::c_void,
+                &mut size,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if ret == 0 && val > 0 {
+            return val as usize;
+        }
+    }
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
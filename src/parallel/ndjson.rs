@@ -9,10 +9,13 @@ use regex::Regex;
 
 use std::collections::HashSet;
 use std::io::{Read, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::filter::{BoolOp, CmpOp, Env, Filter};
 use crate::output::{self, OutputConfig};
 use crate::simdjson;
+use crate::value::Value;
 
 /// Detected fast-path strategy for NDJSON processing.
 /// Field-chain patterns bypass the Value tree entirely.
@@ -50,6 +53,12 @@ enum NdjsonFastPath {
     },
     /// `[.field1, .field2]` — multi-field array construction (batch extraction)
     MultiFieldArr { entries: Vec<Vec<String>> },
+    /// `[.field1, .field2] | @tsv` / `@csv` — one row per line, built from the
+    /// raw field values (raw output only; other modes use the evaluator)
+    Tabular {
+        entries: Vec<Vec<String>>,
+        csv: bool,
+    },
     /// `select(.f == lit) | {key: .field, ...}` — select then object construct
     SelectEqObj {
         pred_fields: Vec<String>,
@@ -80,6 +89,82 @@ enum NdjsonFastPath {
         pred: StringPred,
         out_fields: Vec<String>,
     },
+    /// `first(.field[] | select(cond))` or `limit(1; ...)` — walk the
+    /// elements in the flat buffer and stop at the first one `cond` keeps
+    FirstSelect { fields: Vec<String>, cond: Filter },
+}
+
+impl NdjsonFastPath {
+    /// Short name for `--debug-timing`, or `None` for the evaluator path.
+    fn name(&self) -> Option<&'static str> {
+        Some(match self {
+            NdjsonFastPath::None => return None,
+            NdjsonFastPath::FieldChain(_) => "field",
+            NdjsonFastPath::SelectEq { .. } => "select_eq",
+            NdjsonFastPath::Length(_) => "length",
+            NdjsonFastPath::Keys { .. } => "keys",
+            NdjsonFastPath::Type(_) => "type",
+            NdjsonFastPath::Has { .. } => "has",
+            NdjsonFastPath::SelectEqField { .. } => "select_eq_field",
+            NdjsonFastPath::MultiFieldObj { .. } => "multi_field_obj",
+            NdjsonFastPath::MultiFieldArr { .. } => "multi_field_arr",
+            NdjsonFastPath::Tabular { csv: false, .. } => "tsv",
+            NdjsonFastPath::Tabular { csv: true, .. } => "csv",
+            NdjsonFastPath::SelectEqObj { .. } => "select_eq_obj",
+            NdjsonFastPath::SelectEqArr { .. } => "select_eq_arr",
+            NdjsonFastPath::SelectCompound { .. } => "select_compound",
+            NdjsonFastPath::SelectStringPred { .. } => "select_string_pred",
+            NdjsonFastPath::SelectStringPredField { .. } => "select_string_pred_field",
+            NdjsonFastPath::FirstSelect { .. } => "first_select",
+        })
+    }
+
+    /// Whether this path writes what the evaluator would under `config`.
+    /// Paths that build compact JSON themselves or copy matched lines raw
+    /// can't indent, sort keys, escape to ASCII or colorize, so those configs
+    /// take the evaluator instead.
+    fn honors(&self, config: &OutputConfig) -> bool {
+        let color = config.color.is_enabled();
+        let plain = !config.sort_keys && !config.ascii_output && !color;
+        let compact = config.mode == output::OutputMode::Compact;
+        match self {
+            // Raw values go through `emit_raw_field`, which re-serializes
+            // whenever the config needs it; rows are built in raw mode only.
+            NdjsonFastPath::None
+            | NdjsonFastPath::FieldChain(_)
+            | NdjsonFastPath::SelectEqField { .. }
+            | NdjsonFastPath::SelectStringPredField { .. }
+            | NdjsonFastPath::Tabular { .. } => true,
+            // The matched element goes through `write_value`.
+            NdjsonFastPath::FirstSelect { .. } => true,
+            NdjsonFastPath::Length(_) | NdjsonFastPath::Has { .. } => !color,
+            // A type name, quoted unless in raw mode.
+            NdjsonFastPath::Type(_) => !color && config.mode != output::OutputMode::Logfmt,
+            // Matched lines are copied raw in compact mode only.
+            NdjsonFastPath::SelectEq { .. }
+            | NdjsonFastPath::SelectCompound { .. }
+            | NdjsonFastPath::SelectStringPred { .. } => !compact || plain,
+            NdjsonFastPath::Keys { .. }
+            | NdjsonFastPath::MultiFieldObj { .. }
+            | NdjsonFastPath::MultiFieldArr { .. }
+            | NdjsonFastPath::SelectEqObj { .. }
+            | NdjsonFastPath::SelectEqArr { .. } => compact && plain,
+        }
+    }
+
+    /// Whether this path can output `null` or `false`. Such paths record the
+    /// truthiness of each value they emit; the others only emit truthy values.
+    fn may_emit_falsy(&self) -> bool {
+        matches!(
+            self,
+            NdjsonFastPath::None
+                | NdjsonFastPath::FieldChain(_)
+                | NdjsonFastPath::Has { .. }
+                | NdjsonFastPath::SelectEqField { .. }
+                | NdjsonFastPath::SelectStringPredField { .. }
+                | NdjsonFastPath::FirstSelect { .. }
+        )
+    }
 }
 
 /// String predicate for select fast path.
@@ -92,25 +177,166 @@ enum StringPred {
 }
 
 /// Target size for parallel chunks.
-const CHUNK_TARGET_SIZE: usize = 1024 * 1024;
+pub(super) const CHUNK_TARGET_SIZE: usize = 1024 * 1024;
+
+/// Output, output status, and collected error messages from processing a chunk.
+struct ChunkResult {
+    out: Vec<u8>,
+    status: OutputStatus,
+    /// Errors to report on stderr, in line order. They are rendered once the
+    /// chunk's starting line number is known.
+    errors: Vec<LineError>,
+    /// Lines in the chunk, for numbering the lines of the chunks after it.
+    lines: usize,
+}
 
-/// Output, had-output flag, and collected error messages from processing a chunk.
-type ChunkResult = (Vec<u8>, bool, Vec<u8>);
+/// An error reported on stderr for one line of a chunk.
+struct LineError {
+    /// 1-based line number within the chunk.
+    line: usize,
+    kind: LineErrorKind,
+}
 
-/// Write per-line error messages to stderr. Returns true if any errors were present.
-fn flush_errors(errors: &[u8]) -> bool {
-    if !errors.is_empty() {
-        use std::io::Write;
-        let _ = std::io::stderr().write_all(errors);
-        true
-    } else {
-        false
+enum LineErrorKind {
+    /// The line isn't valid JSON. It produces no output and is reported in
+    /// place of its results.
+    BadJson(String),
+    /// Evaluating the line raised this uncaught error.
+    Uncaught(Value),
+}
+
+impl ChunkResult {
+    fn new(out: Vec<u8>, status: OutputStatus, lines: usize) -> Self {
+        ChunkResult {
+            out,
+            status,
+            errors: Vec::new(),
+            lines,
+        }
+    }
+
+    /// The chunk's error messages, located at lines numbered as if the
+    /// chunk started after `lines_before` lines of `source`.
+    fn render_errors(&self, source: &str, lines_before: usize) -> Vec<u8> {
+        let mut rendered = Vec::new();
+        for error in &self.errors {
+            let line = lines_before + error.line;
+            let _ = match &error.kind {
+                LineErrorKind::BadJson(message) => {
+                    writeln!(rendered, "qj: error (at line {line}): {message}")
+                }
+                LineErrorKind::Uncaught(err) => writeln!(
+                    rendered,
+                    "{}",
+                    output::format_uncaught_error_at(err, &format!("{source}:{line}"))
+                ),
+            };
+        }
+        rendered
+    }
+
+    /// Output, status and error messages of a chunk that starts the input
+    /// `env` is reading.
+    fn into_parts(self, env: &Env) -> (Vec<u8>, OutputStatus, Vec<u8>) {
+        let errors = self.render_errors(&env.input_name(), 0);
+        (self.out, self.status, errors)
+    }
+}
+
+/// Reassembles chunk results in input order, keeping the output status and
+/// the line count across chunks and windows.
+struct ChunkSink {
+    status: OutputStatus,
+    lines: usize,
+    /// The input being read, that error messages are located in.
+    source: Rc<str>,
+}
+
+impl ChunkSink {
+    /// A sink for the input `env` is reading.
+    fn new(env: &Env) -> Self {
+        ChunkSink {
+            status: OutputStatus::default(),
+            lines: 0,
+            source: env.input_name(),
+        }
+    }
+
+    /// Write a chunk's output to `out` and its error messages to `errors`.
+    /// Failing to write the messages (e.g. to a closed stderr) isn't fatal.
+    fn write(
+        &mut self,
+        chunk: ChunkResult,
+        config: &OutputConfig,
+        out: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> Result<()> {
+        output::write_formatted(out, &chunk.out, config)?;
+        if !chunk.errors.is_empty() {
+            let _ = errors.write_all(&chunk.render_errors(&self.source, self.lines));
+        }
+        self.status.merge(chunk.status);
+        self.lines += chunk.lines;
+        Ok(())
+    }
+}
+
+/// What main needs to know about NDJSON output for its exit status: whether
+/// any value was written, whether the last one was `null` or `false` (for
+/// `--exit-status`), how many lines weren't valid JSON and whether the last
+/// line raised an uncaught error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputStatus {
+    pub had_output: bool,
+    pub last_was_falsy: bool,
+    pub bad_lines: usize,
+    /// `None` until a non-blank line is seen. Like jq, which exits with the
+    /// status of its last input, an error on an earlier line doesn't count.
+    pub last_line_failed: Option<bool>,
+}
+
+impl OutputStatus {
+    /// Fold in the status of the chunk that follows. A chunk with no output
+    /// leaves `last_was_falsy` to whichever earlier chunk produced the last value.
+    fn merge(&mut self, next: OutputStatus) {
+        let bad_lines = self.bad_lines + next.bad_lines;
+        let last_line_failed = next.last_line_failed.or(self.last_line_failed);
+        if next.had_output {
+            *self = next;
+        }
+        self.bad_lines = bad_lines;
+        self.last_line_failed = last_line_failed;
+    }
+}
+
+/// Set by the hidden `--debug-parallel` flag.
+static DEBUG_PARALLEL: AtomicBool = AtomicBool::new(false);
+
+/// Report on stderr how each NDJSON window is scheduled (for `--debug-parallel`).
+pub fn set_debug_parallel(enabled: bool) {
+    DEBUG_PARALLEL.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether `--debug-parallel` is on.
+pub(super) fn debug_parallel_enabled() -> bool {
+    DEBUG_PARALLEL.load(Ordering::Relaxed)
+}
+
+/// `chunks` is `None` when the filter forces sequential evaluation.
+fn debug_schedule(chunks: Option<usize>) {
+    if !DEBUG_PARALLEL.load(Ordering::Relaxed) {
+        return;
+    }
+    match chunks {
+        Some(n) if n > 1 => eprintln!("qj: debug: ndjson parallel ({n} chunks)"),
+        Some(_) => eprintln!("qj: debug: ndjson sequential (single chunk)"),
+        None => eprintln!("qj: debug: ndjson sequential (filter uses bound variables)"),
     }
 }
 
 /// Process an NDJSON file: detect format, mmap + process in parallel.
 ///
-/// Returns `Ok(Some(had_output))` if the file was NDJSON and was processed.
+/// Returns `Ok(Some(status))` if the file was NDJSON and was processed.
 /// Returns `Ok(None)` if the file is not NDJSON (caller should handle as single-doc).
 ///
 /// Maps each window independently via mmap, processes it, then unmaps before the
@@ -124,7 +350,7 @@ pub fn process_ndjson_file<W: Write>(
     env: &Env,
     force_jsonl: bool,
     out: &mut W,
-) -> Result<Option<bool>> {
+) -> Result<Option<OutputStatus>> {
     #[cfg(unix)]
     if std::env::var_os("QJ_NO_MMAP").is_none() {
         return process_ndjson_file_mmap(path, filter, config, env, force_jsonl, out);
@@ -147,7 +373,7 @@ fn process_ndjson_file_mmap<W: Write>(
     env: &Env,
     force_jsonl: bool,
     out: &mut W,
-) -> Result<Option<bool>> {
+) -> Result<Option<OutputStatus>> {
     use std::os::unix::io::AsRawFd;
 
     let file =
@@ -189,22 +415,15 @@ fn process_ndjson_file_mmap<W: Write>(
     }
 
     // Set up filter analysis.
-    let needs_env = if env.is_empty() {
-        false
-    } else {
-        let mut var_refs = HashSet::new();
-        filter.collect_var_refs(&mut var_refs);
-        var_refs.iter().any(|v| env.get_var(v).is_some())
-    };
-    let use_parallel = !needs_env && filter.is_parallel_safe();
+    let use_parallel = can_parallelize(filter, env);
     let fast_path = if use_parallel {
-        detect_fast_path(filter)
+        fast_path_for(filter, config)
     } else {
         NdjsonFastPath::None
     };
 
     let ws = window_size();
-    let mut had_output = false;
+    let mut sink = ChunkSink::new(env);
     let mut file_offset: usize = 0;
 
     while file_offset < file_len {
@@ -231,36 +450,16 @@ fn process_ndjson_file_mmap<W: Write>(
         };
         let to_process = &window_data[..process_len];
 
-        if use_parallel {
-            let chunks = split_chunks(to_process, CHUNK_TARGET_SIZE);
-            if chunks.len() <= 1 {
-                let (chunk_out, ho, errs) =
-                    process_chunk(to_process, filter, config, &fast_path, env)?;
-                out.write_all(&chunk_out)?;
-                had_output |= ho;
-                flush_errors(&errs);
-            } else {
-                let shared = SharedFilter::new(filter);
-                let results: Result<Vec<ChunkResult>> = chunks
-                    .par_iter()
-                    .map(|&chunk| {
-                        let empty_env = Env::empty();
-                        process_chunk(chunk, shared.get(), config, &fast_path, &empty_env)
-                    })
-                    .collect();
-                let results = results?;
-                for (chunk_out, ho, errs) in results {
-                    out.write_all(&chunk_out)?;
-                    had_output |= ho;
-                    flush_errors(&errs);
-                }
-            }
-        } else {
-            let (chunk_out, ho, errs) = process_chunk(to_process, filter, config, &fast_path, env)?;
-            out.write_all(&chunk_out)?;
-            had_output |= ho;
-            flush_errors(&errs);
-        }
+        process_window(
+            to_process,
+            filter,
+            config,
+            &fast_path,
+            env,
+            use_parallel,
+            &mut sink,
+            out,
+        )?;
 
         file_offset += process_len;
 
@@ -287,7 +486,7 @@ fn process_ndjson_file_mmap<W: Write>(
         libc::munmap(base_ptr, file_len);
     }
 
-    Ok(Some(had_output))
+    Ok(Some(sink.status))
 }
 
 fn process_ndjson_file_streaming<W: Write>(
@@ -297,7 +496,7 @@ fn process_ndjson_file_streaming<W: Write>(
     env: &Env,
     force_jsonl: bool,
     out: &mut W,
-) -> Result<Option<bool>> {
+) -> Result<Option<OutputStatus>> {
     use std::io::Seek;
     let mut file =
         std::fs::File::open(path).with_context(|| format!("failed to open file: {path:?}"))?;
@@ -307,9 +506,9 @@ fn process_ndjson_file_streaming<W: Write>(
     {
         file.seek(std::io::SeekFrom::Start(0))
             .with_context(|| format!("failed to seek file: {path:?}"))?;
-        let ho = process_ndjson_streaming(&mut file, filter, config, env, out)
+        let status = process_ndjson_streaming(&mut file, filter, config, env, out)
             .with_context(|| format!("failed to process NDJSON: {path:?}"))?;
-        return Ok(Some(ho));
+        return Ok(Some(status));
     }
     Ok(None)
 }
@@ -324,9 +523,26 @@ pub fn is_ndjson(buf: &[u8]) -> bool {
         Some(pos) => pos,
         None => return false,
     };
+    if !starts_ndjson(&buf[..first_nl]) {
+        return false;
+    }
 
-    let first_line = &buf[..first_nl];
+    // Must have another non-empty line starting with { or [
+    let rest = &buf[first_nl + 1..];
+    for &b in rest {
+        match b {
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            b'{' | b'[' => return true,
+            _ => return false,
+        }
+    }
+    false
+}
 
+/// Whether `first_line` (without its newline) could open NDJSON: it starts
+/// with `{` or `[` and ends with `}` or `]`. [`is_ndjson`] then needs the
+/// next non-blank line to start the same way.
+pub(crate) fn starts_ndjson(first_line: &[u8]) -> bool {
     let first_byte = match first_line
         .iter()
         .find(|&&b| !matches!(b, b' ' | b'\t' | b'\r'))
@@ -345,20 +561,7 @@ pub fn is_ndjson(buf: &[u8]) -> bool {
         Some(&b) => b,
         None => return false,
     };
-    if last_byte != b'}' && last_byte != b']' {
-        return false;
-    }
-
-    // Must have another non-empty line starting with { or [
-    let rest = &buf[first_nl + 1..];
-    for &b in rest {
-        match b {
-            b' ' | b'\t' | b'\r' | b'\n' => continue,
-            b'{' | b'[' => return true,
-            _ => return false,
-        }
-    }
-    false
+    last_byte == b'}' || last_byte == b']'
 }
 
 /// Detect NDJSON by reading enough from a reader to find two complete lines.
@@ -377,6 +580,13 @@ pub fn is_ndjson(buf: &[u8]) -> bool {
 ///
 /// The caller should `seek(0)` after this returns if NDJSON is detected.
 pub fn detect_ndjson_from_reader<R: Read>(reader: &mut R) -> Result<bool> {
+    Ok(sniff_ndjson(reader)?.0)
+}
+
+/// Like [`detect_ndjson_from_reader`], but also returns the bytes consumed,
+/// for readers that cannot seek back (stdin). Chain them in front of the
+/// rest of the reader to see the whole input.
+pub fn sniff_ndjson<R: Read>(reader: &mut R) -> Result<(bool, Vec<u8>)> {
     // 64 KB covers most real-world NDJSON lines. Double up to 1 MB if needed.
     let mut buf = vec![0u8; 64 * 1024];
     let mut filled = 0;
@@ -387,19 +597,17 @@ pub fn detect_ndjson_from_reader<R: Read>(reader: &mut R) -> Result<bool> {
         filled += bytes_read;
 
         if is_ndjson(&buf[..filled]) {
-            return Ok(true);
+            buf.truncate(filled);
+            return Ok((true, buf));
         }
 
         // If we've read less than the buffer, we're at EOF.
-        if filled < buf.len() {
-            return Ok(false);
-        }
-
         // Need more data to decide (first line might be very long).
         // Stop at 1 MB — if the first two lines are > 1 MB, fall through
         // to single-doc parsing (which will detect NDJSON on the full buffer).
-        if buf.len() >= 1024 * 1024 {
-            return Ok(false);
+        if filled < buf.len() || buf.len() >= 1024 * 1024 {
+            buf.truncate(filled);
+            return Ok((false, buf));
         }
         buf.resize(buf.len() * 2, 0);
     }
@@ -451,25 +659,34 @@ pub fn process_ndjson(
     filter: &Filter,
     config: &OutputConfig,
     env: &Env,
-) -> Result<ChunkResult> {
-    let needs_env = if env.is_empty() {
-        false
-    } else {
-        let mut var_refs = HashSet::new();
-        filter.collect_var_refs(&mut var_refs);
-        var_refs.iter().any(|v| env.get_var(v).is_some())
-    };
-    if needs_env || !filter.is_parallel_safe() {
-        return process_chunk(data, filter, config, &NdjsonFastPath::None, env);
+) -> Result<(Vec<u8>, bool, Vec<u8>)> {
+    let (out, status, errors) = process_ndjson_with_status(data, filter, config, env)?;
+    Ok((out, status.had_output, errors))
+}
+
+/// Like [`process_ndjson`], but returns the full [`OutputStatus`], combining
+/// chunk results in input order.
+pub fn process_ndjson_with_status(
+    data: &[u8],
+    filter: &Filter,
+    config: &OutputConfig,
+    env: &Env,
+) -> Result<(Vec<u8>, OutputStatus, Vec<u8>)> {
+    if !can_parallelize(filter, env) {
+        debug_schedule(None);
+        let result = process_chunk(data, filter, config, &NdjsonFastPath::None, env)?;
+        return Ok(result.into_parts(env));
     }
 
     // Detect field-chain fast path: `.field` or `.field.nested.path`
     // Bypasses Value tree entirely — extracts raw JSON via C++ bridge.
-    let fast_path = detect_fast_path(filter);
+    let fast_path = fast_path_for(filter, config);
 
     let chunks = split_chunks(data, CHUNK_TARGET_SIZE);
+    debug_schedule(Some(chunks.len()));
     if chunks.len() <= 1 {
-        return process_chunk(data, filter, config, &fast_path, env);
+        let result = process_chunk(data, filter, config, &fast_path, env)?;
+        return Ok(result.into_parts(env));
     }
 
     // SAFETY: Value uses Arc (not Rc), so all filter literals are thread-safe.
@@ -486,18 +703,15 @@ pub fn process_ndjson(
 
     let results = results?;
 
-    let total_size: usize = results.iter().map(|(buf, _, _)| buf.len()).sum();
+    let total_size: usize = results.iter().map(|result| result.out.len()).sum();
     let mut out = Vec::with_capacity(total_size);
-    let mut had_output = false;
     let mut errors = Vec::new();
-
-    for (buf, ho, errs) in results {
-        out.extend_from_slice(&buf);
-        had_output |= ho;
-        errors.extend_from_slice(&errs);
+    let mut sink = ChunkSink::new(env);
+    for result in results {
+        sink.write(result, config, &mut out, &mut errors)?;
     }
 
-    Ok((out, had_output, errors))
+    Ok((out, sink.status, errors))
 }
 
 /// Like [`process_ndjson`], but forces the normal (non-fast-path) evaluator.
@@ -509,8 +723,10 @@ pub fn process_ndjson_no_fast_path(
     filter: &Filter,
     config: &OutputConfig,
     env: &Env,
-) -> Result<ChunkResult> {
-    process_chunk(data, filter, config, &NdjsonFastPath::None, env)
+) -> Result<(Vec<u8>, bool, Vec<u8>)> {
+    let (out, status, errors) =
+        process_chunk(data, filter, config, &NdjsonFastPath::None, env)?.into_parts(env);
+    Ok((out, status.had_output, errors))
 }
 
 /// Process an NDJSON buffer in fixed-size windows, writing output per-window.
@@ -523,23 +739,16 @@ pub fn process_ndjson_windowed<W: Write>(
     config: &OutputConfig,
     env: &Env,
     out: &mut W,
-) -> Result<bool> {
-    let needs_env = if env.is_empty() {
-        false
-    } else {
-        let mut var_refs = HashSet::new();
-        filter.collect_var_refs(&mut var_refs);
-        var_refs.iter().any(|v| env.get_var(v).is_some())
-    };
-    let use_parallel = !needs_env && filter.is_parallel_safe();
+) -> Result<OutputStatus> {
+    let use_parallel = can_parallelize(filter, env);
     let fast_path = if use_parallel {
-        detect_fast_path(filter)
+        fast_path_for(filter, config)
     } else {
         NdjsonFastPath::None
     };
 
     let window_size = window_size();
-    let mut had_output = false;
+    let mut sink = ChunkSink::new(env);
     let mut offset = 0;
 
     while offset < data.len() {
@@ -557,42 +766,61 @@ pub fn process_ndjson_windowed<W: Write>(
 
         let window_data = &data[offset..process_end];
 
-        if use_parallel {
-            let chunks = split_chunks(window_data, CHUNK_TARGET_SIZE);
-            if chunks.len() <= 1 {
-                let (chunk_out, ho, errs) =
-                    process_chunk(window_data, filter, config, &fast_path, env)?;
-                out.write_all(&chunk_out)?;
-                had_output |= ho;
-                flush_errors(&errs);
-            } else {
-                let shared = SharedFilter::new(filter);
-                let results: Result<Vec<ChunkResult>> = chunks
-                    .par_iter()
-                    .map(|&chunk| {
-                        let empty_env = Env::empty();
-                        process_chunk(chunk, shared.get(), config, &fast_path, &empty_env)
-                    })
-                    .collect();
-                let results = results?;
-                for (chunk_out, ho, errs) in results {
-                    out.write_all(&chunk_out)?;
-                    had_output |= ho;
-                    flush_errors(&errs);
-                }
-            }
-        } else {
-            let (chunk_out, ho, errs) =
-                process_chunk(window_data, filter, config, &fast_path, env)?;
-            out.write_all(&chunk_out)?;
-            had_output |= ho;
-            flush_errors(&errs);
-        }
+        process_window(
+            window_data,
+            filter,
+            config,
+            &fast_path,
+            env,
+            use_parallel,
+            &mut sink,
+            out,
+        )?;
 
         offset = process_end;
     }
 
-    Ok(had_output)
+    Ok(sink.status)
+}
+
+/// Process one window of lines, in parallel chunks unless the filter needs
+/// the caller's `env`, and write the results to `out` in input order.
+#[allow(clippy::too_many_arguments)]
+fn process_window<W: Write>(
+    window_data: &[u8],
+    filter: &Filter,
+    config: &OutputConfig,
+    fast_path: &NdjsonFastPath,
+    env: &Env,
+    use_parallel: bool,
+    sink: &mut ChunkSink,
+    out: &mut W,
+) -> Result<()> {
+    let mut stderr = std::io::stderr();
+    if !use_parallel {
+        // Sequential: env-dependent filters
+        debug_schedule(None);
+        let result = process_chunk(window_data, filter, config, fast_path, env)?;
+        return sink.write(result, config, out, &mut stderr);
+    }
+    let chunks = split_chunks(window_data, CHUNK_TARGET_SIZE);
+    debug_schedule(Some(chunks.len()));
+    if chunks.len() <= 1 {
+        let result = process_chunk(window_data, filter, config, fast_path, env)?;
+        return sink.write(result, config, out, &mut stderr);
+    }
+    let shared = SharedFilter::new(filter);
+    let results: Result<Vec<ChunkResult>> = chunks
+        .par_iter()
+        .map(|&chunk| {
+            let empty_env = Env::empty();
+            process_chunk(chunk, shared.get(), config, fast_path, &empty_env)
+        })
+        .collect();
+    for result in results? {
+        sink.write(result, config, out, &mut stderr)?;
+    }
+    Ok(())
 }
 
 /// Minimum window size (32 MiB). On single-core machines, 32 chunks is
@@ -632,25 +860,41 @@ pub fn process_ndjson_streaming<R: Read, W: Write>(
     config: &OutputConfig,
     env: &Env,
     out: &mut W,
-) -> Result<bool> {
-    let needs_env = if env.is_empty() {
-        false
-    } else {
-        let mut var_refs = HashSet::new();
-        filter.collect_var_refs(&mut var_refs);
-        var_refs.iter().any(|v| env.get_var(v).is_some())
-    };
-    let use_parallel = !needs_env && filter.is_parallel_safe();
+) -> Result<OutputStatus> {
+    let use_parallel = can_parallelize(filter, env);
     let fast_path = if use_parallel {
-        detect_fast_path(filter)
+        fast_path_for(filter, config)
     } else {
         NdjsonFastPath::None
     };
 
+    let mut sink = ChunkSink::new(env);
+    for_each_window(reader, |window_data| {
+        process_window(
+            window_data,
+            filter,
+            config,
+            &fast_path,
+            env,
+            use_parallel,
+            &mut sink,
+            out,
+        )
+    })?;
+
+    Ok(sink.status)
+}
+
+/// Read `reader` in `window_size()` windows that end on a line boundary and
+/// hand each to `process`. A line spanning a window boundary is carried into
+/// the next window; a single line longer than the window grows the buffer.
+pub(super) fn for_each_window<R: Read>(
+    reader: &mut R,
+    mut process: impl FnMut(&[u8]) -> Result<()>,
+) -> Result<()> {
     let window_size = window_size();
     let mut buf = vec![0u8; window_size];
     let mut carry_len: usize = 0;
-    let mut had_output = false;
 
     loop {
         // Read up to (window_size - carry_len) bytes after the carry region.
@@ -681,40 +925,7 @@ pub fn process_ndjson_streaming<R: Read, W: Write>(
             }
         };
 
-        let window_data = &buf[..process_len];
-
-        if use_parallel {
-            let chunks = split_chunks(window_data, CHUNK_TARGET_SIZE);
-            if chunks.len() <= 1 {
-                let (chunk_out, ho, errs) =
-                    process_chunk(window_data, filter, config, &fast_path, env)?;
-                out.write_all(&chunk_out)?;
-                flush_errors(&errs);
-                had_output |= ho;
-            } else {
-                let shared = SharedFilter::new(filter);
-                let results: Result<Vec<ChunkResult>> = chunks
-                    .par_iter()
-                    .map(|&chunk| {
-                        let empty_env = Env::empty();
-                        process_chunk(chunk, shared.get(), config, &fast_path, &empty_env)
-                    })
-                    .collect();
-                let results = results?;
-                for (chunk_out, ho, errs) in results {
-                    out.write_all(&chunk_out)?;
-                    had_output |= ho;
-                    flush_errors(&errs);
-                }
-            }
-        } else {
-            // Sequential: env-dependent filters
-            let (chunk_out, ho, errs) =
-                process_chunk(window_data, filter, config, &fast_path, env)?;
-            out.write_all(&chunk_out)?;
-            flush_errors(&errs);
-            had_output |= ho;
-        }
+        process(&buf[..process_len])?;
 
         if at_eof {
             break;
@@ -726,8 +937,63 @@ pub fn process_ndjson_streaming<R: Read, W: Write>(
         }
         carry_len = next_carry_len;
     }
+    Ok(())
+}
 
-    Ok(had_output)
+/// Parse NDJSON from a reader into `values` for `--slurp`, one window at a
+/// time. Each window's lines are parsed in parallel chunks and appended in
+/// order, so peak memory is the parsed values plus one window of raw bytes
+/// rather than the whole input. A leading UTF-8 BOM is skipped.
+pub fn slurp_ndjson_streaming<R: Read>(reader: &mut R, values: &mut Vec<Value>) -> Result<()> {
+    let mut first = true;
+    for_each_window(reader, |mut window_data| {
+        if std::mem::take(&mut first) {
+            window_data = window_data
+                .strip_prefix(b"\xEF\xBB\xBF")
+                .unwrap_or(window_data);
+        }
+        let chunks = split_chunks(window_data, CHUNK_TARGET_SIZE);
+        if chunks.len() <= 1 {
+            return crate::input::parse_lines(window_data, values);
+        }
+        let parsed: Result<Vec<Vec<Value>>> = chunks
+            .par_iter()
+            .map(|&chunk| {
+                let mut chunk_values = Vec::new();
+                crate::input::parse_lines(chunk, &mut chunk_values)?;
+                Ok(chunk_values)
+            })
+            .collect();
+        for chunk_values in parsed? {
+            values.extend(chunk_values);
+        }
+        Ok(())
+    })
+}
+
+/// [`slurp_ndjson_streaming`] for a file, if it is NDJSON (or `force_jsonl`).
+///
+/// Returns `Ok(false)` without touching `values` when the file is not NDJSON;
+/// the caller should slurp it as a regular JSON document.
+pub fn slurp_ndjson_file(
+    path: &std::path::Path,
+    force_jsonl: bool,
+    values: &mut Vec<Value>,
+) -> Result<bool> {
+    use std::io::Seek;
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("failed to open file: {path:?}"))?;
+    if !force_jsonl
+        && !detect_ndjson_from_reader(&mut file)
+            .with_context(|| format!("failed to read file: {path:?}"))?
+    {
+        return Ok(false);
+    }
+    file.seek(std::io::SeekFrom::Start(0))
+        .with_context(|| format!("failed to seek file: {path:?}"))?;
+    slurp_ndjson_streaming(&mut file, values)
+        .with_context(|| format!("failed to process NDJSON: {path:?}"))?;
+    Ok(true)
 }
 
 /// Read until `buf` is full or EOF, handling short reads and EINTR.
@@ -744,9 +1010,65 @@ fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
     Ok(total)
 }
 
+/// Whether lines can be evaluated independently on worker threads, each with
+/// an empty [`Env`]: the filter must be parallel-safe and not read any of the
+/// caller's variables. The fast paths are only used when this holds.
+///
+/// `$ENV` bound to the process snapshot doesn't count: workers see the same
+/// snapshot without the binding.
+pub(super) fn can_parallelize(filter: &Filter, env: &Env) -> bool {
+    let needs_env = if env.is_empty() {
+        false
+    } else {
+        let mut var_refs = HashSet::new();
+        filter.collect_var_refs(&mut var_refs);
+        var_refs.iter().any(|v| match env.get_var(v) {
+            Some(bound) if v == "$ENV" => !is_env_snapshot(bound),
+            Some(_) => true,
+            None => false,
+        })
+    };
+    !needs_env && filter.is_parallel_safe()
+}
+
+/// Whether `value` is the process environment snapshot itself.
+fn is_env_snapshot(value: &Value) -> bool {
+    match (value, crate::filter::env_object()) {
+        (Value::Object(bound), Value::Object(snapshot)) => std::sync::Arc::ptr_eq(bound, &snapshot),
+        _ => false,
+    }
+}
+
+/// Name of the fast path NDJSON processing would use for `filter`, or `None`
+/// if every line goes through the evaluator. Shown by `--debug-timing`.
+pub fn fast_path_name(filter: &Filter, config: &OutputConfig, env: &Env) -> Option<&'static str> {
+    if !can_parallelize(filter, env) {
+        return None;
+    }
+    fast_path_for(filter, config).name()
+}
+
+/// The fast path for `filter`, or the evaluator if that path can't honor
+/// `config`.
+fn fast_path_for(filter: &Filter, config: &OutputConfig) -> NdjsonFastPath {
+    let fast_path = detect_fast_path(filter);
+    if fast_path.honors(config) {
+        fast_path
+    } else {
+        NdjsonFastPath::None
+    }
+}
+
 fn detect_fast_path(filter: &Filter) -> NdjsonFastPath {
-    // Allow disabling fast path for benchmarking A/B comparisons.
-    if std::env::var_os("QJ_NO_FAST_PATH").is_some() {
+    // The raw-byte fast paths skip the evaluator `--profile` instruments.
+    if crate::filter::profile::is_enabled() {
+        return NdjsonFastPath::None;
+    }
+    // Allow disabling fast path for benchmarking A/B comparisons, and for
+    // `--eval-mode flat` / `tree`.
+    if std::env::var_os("QJ_NO_FAST_PATH").is_some()
+        || crate::filter::eval_mode() >= crate::filter::EvalMode::Flat
+    {
         return NdjsonFastPath::None;
     }
     let mut fields = Vec::new();
@@ -763,12 +1085,18 @@ fn detect_fast_path(filter: &Filter) -> NdjsonFastPath {
     if let Some(fp) = detect_select_string_pred_fast_path(filter) {
         return fp;
     }
+    if let Some(fp) = detect_tabular_fast_path(filter) {
+        return fp;
+    }
     if let Some(fp) = detect_multi_field_fast_path(filter) {
         return fp;
     }
     if let Some(fp) = detect_length_keys_fast_path(filter) {
         return fp;
     }
+    if let Some(fp) = detect_first_select_fast_path(filter) {
+        return fp;
+    }
     NdjsonFastPath::None
 }
 
@@ -785,28 +1113,28 @@ fn detect_fast_path(filter: &Filter) -> NdjsonFastPath {
 ///
 /// Safe because the filter is only read during evaluation and each thread
 /// creates its own Values and Env.
-struct SharedFilter {
+pub(super) struct SharedFilter {
     ptr: *const Filter,
 }
 unsafe impl Send for SharedFilter {}
 unsafe impl Sync for SharedFilter {}
 
 impl SharedFilter {
-    fn new(filter: &Filter) -> Self {
+    pub(super) fn new(filter: &Filter) -> Self {
         Self {
             ptr: filter as *const Filter,
         }
     }
 
-    fn get(&self) -> &Filter {
+    pub(super) fn get(&self) -> &Filter {
         // SAFETY: the pointer is valid for the lifetime of the caller's
         // borrow of the original Filter (ensured by process_ndjson's scope).
         unsafe { &*self.ptr }
     }
 }
 
-/// Process a single chunk of NDJSON lines sequentially.
-/// Returns (output_bytes, had_output, error_messages).
+/// Process a single chunk of NDJSON lines sequentially. A line that fails to
+/// parse leaves no output and is recorded in the result's `bad_lines`.
 fn process_chunk(
     chunk: &[u8],
     filter: &Filter,
@@ -818,6 +1146,7 @@ fn process_chunk(
     // may match, so reserve generously to avoid realloc/memmove during processing.
     let mut output_buf = Vec::with_capacity(chunk.len() / 2);
     let mut had_output = false;
+    let mut last_was_falsy = false;
 
     // Try fused chunk-level scanners — single pass with pre-built Finders.
     match fast_path {
@@ -826,7 +1155,7 @@ fn process_chunk(
             op,
             literal_bytes,
         } => {
-            if let Some(result) = process_chunk_select_eq_fused(
+            if let Some(lines) = process_chunk_select_eq_fused(
                 chunk,
                 fields,
                 *op,
@@ -835,9 +1164,14 @@ fn process_chunk(
                 &mut output_buf,
                 &mut had_output,
             ) {
-                result?;
-                let error_buf = Vec::new();
-                return Ok((output_buf, had_output, error_buf));
+                // Fused scanners only emit whole object lines, which are
+                // truthy, and never fail.
+                let status = OutputStatus {
+                    had_output,
+                    last_line_failed: (lines > 0).then_some(false),
+                    ..OutputStatus::default()
+                };
+                return Ok(ChunkResult::new(output_buf, status, lines));
             }
             // Fused scanner couldn't handle it — fall through to per-line path.
             output_buf.clear();
@@ -847,7 +1181,7 @@ fn process_chunk(
             conditions,
             bool_op,
         } => {
-            if let Some(result) = process_chunk_select_compound_fused(
+            if let Some(lines) = process_chunk_select_compound_fused(
                 chunk,
                 conditions,
                 *bool_op,
@@ -855,9 +1189,12 @@ fn process_chunk(
                 &mut output_buf,
                 &mut had_output,
             ) {
-                result?;
-                let error_buf = Vec::new();
-                return Ok((output_buf, had_output, error_buf));
+                let status = OutputStatus {
+                    had_output,
+                    last_line_failed: (lines > 0).then_some(false),
+                    ..OutputStatus::default()
+                };
+                return Ok(ChunkResult::new(output_buf, status, lines));
             }
             output_buf.clear();
             had_output = false;
@@ -874,13 +1211,20 @@ fn process_chunk(
         Some(simdjson::DomParser::new()?)
     };
 
-    let mut error_buf = Vec::new();
-
-    let mut start = 0;
-    for nl_pos in memchr_iter(b'\n', chunk) {
-        let line = &chunk[start..nl_pos];
-        start = nl_pos + 1;
-        process_line(
+    let mut errors = Vec::new();
+    let mut bad_lines = 0;
+    let mut last_line_failed = None;
+    let mut line_no = 0;
+
+    let mut run_line = |line: &[u8]| {
+        line_no += 1;
+        if line.iter().all(|&b| matches!(b, b' ' | b'\t' | b'\r')) {
+            return;
+        }
+        last_line_failed = Some(false);
+        let out_len = output_buf.len();
+        let prev_status = (had_output, last_was_falsy);
+        let result = process_line(
             line,
             filter,
             config,
@@ -888,29 +1232,74 @@ fn process_chunk(
             env,
             &mut output_buf,
             &mut had_output,
-            &mut error_buf,
+            &mut last_was_falsy,
             &mut scratch,
             &mut dom_parser,
-        )?;
+        );
+        match result {
+            // Surface the line's uncaught error (e.g. "Cannot index array
+            // with string"), set by whichever evaluator ran it.
+            Ok(()) => {
+                if let Some(err) = env.take_error() {
+                    last_line_failed = Some(true);
+                    errors.push(LineError {
+                        line: line_no,
+                        kind: LineErrorKind::Uncaught(err),
+                    });
+                }
+            }
+            // Drop whatever the line wrote before failing, along with any
+            // evaluation error it left behind, and report it instead.
+            Err(err) => {
+                output_buf.truncate(out_len);
+                (had_output, last_was_falsy) = prev_status;
+                env.take_error();
+                bad_lines += 1;
+                errors.push(LineError {
+                    line: line_no,
+                    kind: LineErrorKind::BadJson(describe_bad_line(line, &err)),
+                });
+            }
+        }
+    };
+
+    let mut start = 0;
+    for nl_pos in memchr_iter(b'\n', chunk) {
+        run_line(&chunk[start..nl_pos]);
+        start = nl_pos + 1;
     }
 
     // Handle last line without trailing newline
     if start < chunk.len() {
-        process_line(
-            &chunk[start..],
-            filter,
-            config,
-            fast_path,
-            env,
-            &mut output_buf,
-            &mut had_output,
-            &mut error_buf,
-            &mut scratch,
-            &mut dom_parser,
-        )?;
+        run_line(&chunk[start..]);
     }
 
-    Ok((output_buf, had_output, error_buf))
+    let status = OutputStatus {
+        had_output,
+        last_was_falsy,
+        bad_lines,
+        last_line_failed,
+    };
+    Ok(ChunkResult {
+        out: output_buf,
+        status,
+        errors,
+        lines: line_no,
+    })
+}
+
+/// Why `line` failed to process: serde_json's description of the first
+/// syntax error, located by column, or the processing error itself when the
+/// line is valid JSON that simdjson still rejected (e.g. nesting too deep).
+pub(crate) fn describe_bad_line(line: &[u8], err: &anyhow::Error) -> String {
+    match serde_json::from_slice::<serde_json::Value>(line) {
+        Err(syntax) => {
+            let text = syntax.to_string();
+            let what = text.split(" at line ").next().unwrap_or(&text);
+            format!("invalid JSON: {what} at column {}", syntax.column())
+        }
+        Ok(_) => format!("{err:#}"),
+    }
 }
 
 /// Unescape a JSON string interior (without surrounding quotes) into the output buffer.
@@ -995,19 +1384,6 @@ fn unescape_json_string(data: &[u8], out: &mut Vec<u8>) {
     }
 }
 
-/// Drain any per-line evaluation errors (set via `set_last_error`) into the
-/// error buffer. Matches jq's behavior of writing per-line type errors to
-/// stderr (e.g. "Cannot index array with string").
-fn drain_eval_errors(error_buf: &mut Vec<u8>) {
-    if let Some(err) = crate::filter::eval::take_last_error() {
-        let msg = match &err {
-            crate::value::Value::String(s) => s.as_str().to_owned(),
-            other => other.short_desc(),
-        };
-        let _ = writeln!(error_buf, "qj: error: {msg}");
-    }
-}
-
 /// Write the line terminator (newline, NUL, or nothing) after a fast-path output.
 #[inline]
 fn write_line_terminator(output_buf: &mut Vec<u8>, config: &OutputConfig) {
@@ -1018,37 +1394,66 @@ fn write_line_terminator(output_buf: &mut Vec<u8>, config: &OutputConfig) {
     }
 }
 
-/// Emit a raw field value — in raw mode, strip quotes and unescape; otherwise emit as-is.
+/// Whether a raw JSON value is `null` or `false`.
+#[inline]
+fn is_falsy_raw(raw: &[u8]) -> bool {
+    raw == b"null" || raw == b"false"
+}
+
+/// Emit a raw field value — in raw mode, strip quotes and unescape.
+///
+/// Scalars and plainly escaped strings are copied as-is. Containers, strings
+/// with escapes that jq would rewrite, and anything under `-a` or color are
+/// re-serialized so they follow the same formatting as the evaluator path.
 #[inline]
 fn emit_raw_field(output_buf: &mut Vec<u8>, raw: &[u8], config: &OutputConfig) {
-    if config.mode == output::OutputMode::Raw
-        && raw.len() >= 2
-        && raw[0] == b'"'
-        && raw[raw.len() - 1] == b'"'
-    {
+    let is_string = raw.len() >= 2 && raw[0] == b'"' && raw[raw.len() - 1] == b'"';
+    if is_string && config.mode == output::OutputMode::Raw && !config.ascii_output {
         let inner = &raw[1..raw.len() - 1];
         unescape_json_string(inner, output_buf);
-    } else {
+        return;
+    }
+    let verbatim = !config.color.is_enabled()
+        && match raw.first() {
+            Some(b'{' | b'[') => false,
+            Some(b'"') => !config.ascii_output && !output::has_rewritten_escapes(raw),
+            _ => true,
+        };
+    if verbatim {
         output_buf.extend_from_slice(raw);
+        return;
+    }
+    let padded = simdjson::pad_buffer(raw);
+    match simdjson::dom_parse_to_value(&padded, raw.len()) {
+        Ok(value) => {
+            output::write_value_body(output_buf, &value, config).ok();
+        }
+        Err(_) => output_buf.extend_from_slice(raw),
+    }
+}
+
+/// Append a raw value into a constructed compact object or array. A container
+/// holding repeated keys is re-serialized so only the last of each survives,
+/// as jq does, and so is text with escapes jq would print differently;
+/// everything else is copied as-is.
+fn push_raw_value(output_buf: &mut Vec<u8>, raw: &[u8]) {
+    if output::has_rewritten_escapes(raw)
+        || matches!(raw.first(), Some(b'{' | b'[')) && crate::input::has_duplicate_keys(raw)
+    {
+        let padded = simdjson::pad_buffer(raw);
+        if let Ok(value) = simdjson::dom_parse_to_value(&padded, raw.len()) {
+            output::write_compact(output_buf, &value, false).ok();
+            return;
+        }
     }
+    output_buf.extend_from_slice(raw);
 }
 
 /// Serialize a string as a JSON key with surrounding quotes.
 /// E.g., `actor` → `b"\"actor\""`, `key"with` → `b"\"key\\\"with\""`.
 fn json_key_bytes(key: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(key.len() + 2);
-    buf.push(b'"');
-    for &b in key.as_bytes() {
-        match b {
-            b'"' => buf.extend_from_slice(b"\\\""),
-            b'\\' => buf.extend_from_slice(b"\\\\"),
-            b if b < 0x20 => {
-                buf.extend_from_slice(format!("\\u{:04x}", b).as_bytes());
-            }
-            _ => buf.push(b),
-        }
-    }
-    buf.push(b'"');
+    output::write_json_string(&mut buf, key).ok();
     buf
 }
 
@@ -1077,7 +1482,7 @@ fn process_line(
     env: &Env,
     output_buf: &mut Vec<u8>,
     had_output: &mut bool,
-    error_buf: &mut Vec<u8>,
+    last_was_falsy: &mut bool,
     scratch: &mut Vec<u8>,
     dom_parser: &mut Option<simdjson::DomParser>,
 ) -> Result<()> {
@@ -1099,25 +1504,46 @@ fn process_line(
     // Fast paths are designed for JSON objects. Non-object lines (arrays,
     // bare values) fall back to the normal evaluator so they get the same
     // error semantics as single-doc mode (e.g. "Cannot index array with
-    // string" instead of silently returning null).
+    // string" instead of silently returning null), and so do lines that are
+    // cut off before their closing brace, which the lazy fast paths would
+    // read from as if complete. Lines with lone surrogate escapes also take
+    // the evaluator, since the raw-byte fast paths would copy the escape
+    // through instead of decoding it to U+FFFD.
     let none_path = NdjsonFastPath::None;
-    let fast_path = if !matches!(fast_path, NdjsonFastPath::None) && trimmed[0] != b'{' {
+    let fast_path = if !matches!(fast_path, NdjsonFastPath::None)
+        && (trimmed[0] != b'{'
+            || trimmed[trimmed.len() - 1] != b'}'
+            || crate::input::has_lone_surrogates(trimmed))
+    {
         &none_path
     } else {
         fast_path
     };
 
+    let out_len = output_buf.len();
     match fast_path {
         NdjsonFastPath::FieldChain(fields) => {
             let padded = prepare_padded(trimmed, scratch);
             let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
             let dp = dom_parser.as_mut().unwrap();
             let raw = dp
-                .find_field_raw(padded, trimmed.len(), &field_refs)
+                .field_chain_raw(padded, trimmed.len(), &field_refs)
                 .context("failed to extract field from NDJSON line")?;
-            *had_output = true;
-            emit_raw_field(output_buf, &raw, config);
-            write_line_terminator(output_buf, config);
+            if let Some(raw) = raw {
+                *had_output = true;
+                *last_was_falsy = is_falsy_raw(&raw);
+                emit_raw_field(output_buf, &raw, config);
+                write_line_terminator(output_buf, config);
+            } else {
+                // Indexing a non-object: the evaluator reports jq's error.
+                let flat_buf = simdjson::dom_parse_to_flat_buf(padded, trimmed.len())
+                    .context("failed to parse NDJSON line")?;
+                crate::flat_eval::eval_flat(filter, flat_buf.root(), env, &mut |v| {
+                    *had_output = true;
+                    *last_was_falsy = !v.is_truthy();
+                    output::write_value(output_buf, &v, config).ok();
+                });
+            }
         }
         NdjsonFastPath::SelectEq {
             fields,
@@ -1222,6 +1648,7 @@ fn process_line(
                 env,
                 output_buf,
                 had_output,
+                last_was_falsy,
                 scratch,
                 dom_parser.as_mut().unwrap(),
             )?;
@@ -1243,6 +1670,7 @@ fn process_line(
                 env,
                 output_buf,
                 had_output,
+                last_was_falsy,
                 scratch,
                 dom_parser.as_mut().unwrap(),
             )?;
@@ -1269,6 +1697,20 @@ fn process_line(
                 dom_parser.as_mut().unwrap(),
             )?;
         }
+        NdjsonFastPath::Tabular { entries, csv } => {
+            process_line_tabular(
+                trimmed,
+                entries,
+                *csv,
+                filter,
+                config,
+                env,
+                output_buf,
+                had_output,
+                scratch,
+                dom_parser.as_mut().unwrap(),
+            )?;
+        }
         NdjsonFastPath::SelectEqObj {
             pred_fields,
             op,
@@ -1340,10 +1782,25 @@ fn process_line(
                 env,
                 output_buf,
                 had_output,
+                last_was_falsy,
                 scratch,
                 dom_parser.as_mut().unwrap(),
             )?;
         }
+        NdjsonFastPath::FirstSelect { fields, cond } => {
+            process_line_first_select(
+                trimmed,
+                fields,
+                cond,
+                filter,
+                config,
+                env,
+                output_buf,
+                had_output,
+                last_was_falsy,
+                scratch,
+            )?;
+        }
         NdjsonFastPath::None => {
             // Lazy path: parse → flat buffer → navigate/eval → output
             // Only materializes the subtrees the filter actually accesses.
@@ -1352,17 +1809,17 @@ fn process_line(
                 .context("failed to parse NDJSON line")?;
             crate::flat_eval::eval_flat(filter, flat_buf.root(), env, &mut |v| {
                 *had_output = true;
+                *last_was_falsy = !v.is_truthy();
                 output::write_value(output_buf, &v, config).ok();
             });
         }
     }
 
-    // Surface per-line type errors (e.g. "Cannot index array with string").
-    // These are set by eval_flat/eval_filter_with_env via set_last_error()
-    // but not automatically surfaced in the NDJSON path. Draining here (after
-    // the match) catches errors from any code path — the None fallback, select
-    // predicate fallbacks, etc.
-    drain_eval_errors(error_buf);
+    // The remaining fast paths (and their evaluator fallbacks, which only run
+    // on object lines) emit objects, arrays, numbers or strings.
+    if output_buf.len() > out_len && !fast_path.may_emit_falsy() {
+        *last_was_falsy = false;
+    }
 
     Ok(())
 }
@@ -1461,30 +1918,25 @@ fn serialize_literal(filter: &Filter) -> Option<Vec<u8>> {
         Filter::Literal(Value::String(s)) => {
             // JSON-encode: "value"
             let mut buf = Vec::with_capacity(s.len() + 2);
-            buf.push(b'"');
-            // Escape special characters in the string
-            for &b in s.as_bytes() {
-                match b {
-                    b'"' => buf.extend_from_slice(b"\\\""),
-                    b'\\' => buf.extend_from_slice(b"\\\\"),
-                    b'\n' => buf.extend_from_slice(b"\\n"),
-                    b'\r' => buf.extend_from_slice(b"\\r"),
-                    b'\t' => buf.extend_from_slice(b"\\t"),
-                    b if b < 0x20 => {
-                        buf.extend_from_slice(format!("\\u{:04x}", b).as_bytes());
-                    }
-                    _ => buf.push(b),
-                }
-            }
-            buf.push(b'"');
+            output::write_json_string(&mut buf, s).ok();
             Some(buf)
         }
         Filter::Literal(Value::Int(n)) => Some(n.to_string().into_bytes()),
+        // Big integer literals are exact, so their digits compare bytewise
+        Filter::Literal(Value::Double(_, Some(raw)))
+            if crate::value::bigint::is_big_integer_text(raw) =>
+        {
+            Some(raw.as_bytes().to_vec())
+        }
         Filter::Literal(Value::Double(f, _)) => {
             // Use serde_json-style float formatting
-            let s = if f.fract() == 0.0 && f.is_finite() {
+            let s = if f.fract() == 0.0 && f.abs() < i64::MAX as f64 {
                 // Integers stored as float: avoid trailing ".0" for exact match
                 format!("{}", *f as i64)
+            } else if f.fract() == 0.0 && f.is_finite() {
+                // Beyond i64 an f64 is inexact: exponent form forces the
+                // full comparison instead of a byte mismatch
+                format!("{f:e}")
             } else {
                 format!("{}", f)
             };
@@ -1779,6 +2231,18 @@ fn find_field_value_raw_prebuilt<'a>(
     find_field_value_scan(line, field)
 }
 
+/// Whether the key `"field"` occurs again after `value` ends in `line`.
+///
+/// jq keeps the last of repeated object keys, while the byte scanners stop
+/// at the first, so a later occurrence means the found value may be stale.
+/// The key finder matches `"field"` without the colon so spaced lines are
+/// covered too; a string value that happens to equal the key only costs a
+/// fallback.
+fn key_repeats_after(line: &[u8], value: &[u8], key_finder: &memchr::memmem::Finder<'_>) -> bool {
+    let end = value.as_ptr() as usize - line.as_ptr() as usize + value.len();
+    key_finder.find(&line[end..]).is_some()
+}
+
 /// Extract a JSON value starting at position `start` in `line`.
 /// Returns the slice containing the complete value.
 fn extract_json_value(line: &[u8], start: usize) -> Option<&[u8]> {
@@ -1859,10 +2323,22 @@ fn extract_json_value(line: &[u8], start: usize) -> Option<&[u8]> {
 
 /// Find a nested field value by following a field chain.
 /// E.g., for fields `["actor", "login"]`, finds `.actor` then `.login` within it.
+///
+/// A missing field yields `null`. Returns `None` when the answer can't be read
+/// off the bytes: a non-object intermediate, or a key repeated after its first
+/// occurrence.
 fn find_field_chain_raw<'a>(line: &'a [u8], fields: &[String]) -> Option<&'a [u8]> {
     let mut current = line;
     for (i, field) in fields.iter().enumerate() {
-        let value = find_field_value_raw(current, field)?;
+        let Some(value) = find_field_value_raw(current, field) else {
+            // Missing at any level: `.a.b` on a missing `.a` is null.
+            return Some(b"null");
+        };
+        let pattern = build_field_pattern(field);
+        let key_finder = memchr::memmem::Finder::new(&pattern[..pattern.len() - 1]);
+        if key_repeats_after(current, value, &key_finder) {
+            return None;
+        }
         if i + 1 < fields.len() {
             // Intermediate field: must be an object to navigate into
             if value.first() != Some(&b'{') {
@@ -1899,13 +2375,18 @@ fn process_line_select_eq_raw(
 
     match evaluate_select_predicate(raw, literal_bytes, op) {
         Some(true) => {
-            *had_output = true;
             // Output the raw line directly. If the line has structural
             // whitespace (not already compact), fall back to simdjson minify.
             // Quick check: compact NDJSON starts with `{"` or `{}`, never `{ `.
-            if trimmed.len() > 1 && trimmed[0] == b'{' && trimmed[1] != b'"' && trimmed[1] != b'}' {
+            // Repeated keys anywhere need the parser to keep the last one,
+            // and escapes jq rewrites need re-serializing.
+            if trimmed.len() > 1 && trimmed[0] == b'{' && trimmed[1] != b'"' && trimmed[1] != b'}'
+                || crate::input::has_duplicate_keys(trimmed)
+                || output::has_rewritten_escapes(trimmed)
+            {
                 return None;
             }
+            *had_output = true;
             output_buf.extend_from_slice(trimmed);
             write_line_terminator(output_buf, config);
             Some(Ok(()))
@@ -1937,7 +2418,7 @@ fn process_line_select_compound_raw(
         BoolOp::And => {
             // All conditions must be true. Short-circuit on first false.
             for (fields, op, literal_bytes) in conditions {
-                let raw = find_field_chain_raw(trimmed, fields).unwrap_or(b"null");
+                let raw = find_field_chain_raw(trimmed, fields)?;
                 match evaluate_select_predicate(raw, literal_bytes, *op) {
                     Some(false) => return Some(Ok(())), // definite non-match, skip line
                     Some(true) => continue,             // this condition passed
@@ -1950,18 +2431,20 @@ fn process_line_select_compound_raw(
             // Any condition can be true. Short-circuit on first true.
             let mut any_ambiguous = false;
             for (fields, op, literal_bytes) in conditions {
-                let raw = find_field_chain_raw(trimmed, fields).unwrap_or(b"null");
+                let raw = find_field_chain_raw(trimmed, fields)?;
                 match evaluate_select_predicate(raw, literal_bytes, *op) {
                     Some(true) => {
                         // Definite match — output the line.
-                        *had_output = true;
                         if trimmed.len() > 1
                             && trimmed[0] == b'{'
                             && trimmed[1] != b'"'
                             && trimmed[1] != b'}'
+                            || crate::input::has_duplicate_keys(trimmed)
+                            || output::has_rewritten_escapes(trimmed)
                         {
                             return None;
                         }
+                        *had_output = true;
                         output_buf.extend_from_slice(trimmed);
                         write_line_terminator(output_buf, config);
                         return Some(Ok(()));
@@ -1981,10 +2464,13 @@ fn process_line_select_compound_raw(
     };
 
     if result {
-        *had_output = true;
-        if trimmed.len() > 1 && trimmed[0] == b'{' && trimmed[1] != b'"' && trimmed[1] != b'}' {
+        if trimmed.len() > 1 && trimmed[0] == b'{' && trimmed[1] != b'"' && trimmed[1] != b'}'
+            || crate::input::has_duplicate_keys(trimmed)
+            || output::has_rewritten_escapes(trimmed)
+        {
             return None;
         }
+        *had_output = true;
         output_buf.extend_from_slice(trimmed);
         write_line_terminator(output_buf, config);
     }
@@ -1995,8 +2481,9 @@ fn process_line_select_compound_raw(
 ///
 /// Instead of memchr for newlines + per-line byte scanner, this does a single
 /// pass over the chunk: finds newlines, locates the field, evaluates the
-/// predicate, and emits matching lines — all inline. Returns `None` if this
-/// fast path can't handle the filter (non-compact mode, field chains > 1 deep).
+/// predicate, and emits matching lines — all inline. Returns the number of
+/// lines scanned, or `None` if this fast path can't handle the filter
+/// (non-compact mode, field chains > 1 deep) or some line in the chunk.
 fn process_chunk_select_eq_fused(
     chunk: &[u8],
     fields: &[String],
@@ -2005,7 +2492,7 @@ fn process_chunk_select_eq_fused(
     config: &OutputConfig,
     output_buf: &mut Vec<u8>,
     had_output: &mut bool,
-) -> Option<Result<()>> {
+) -> Option<usize> {
     // Only handle compact mode with single top-level field.
     if config.mode != output::OutputMode::Compact || fields.len() != 1 {
         return None;
@@ -2016,9 +2503,11 @@ fn process_chunk_select_eq_fused(
     // scan time in profiles).
     let pattern = build_field_pattern(&fields[0]);
     let finder = memchr::memmem::Finder::new(&pattern);
+    let key_finder = memchr::memmem::Finder::new(&pattern[..pattern.len() - 1]);
 
     let len = chunk.len();
     let mut line_start: usize = 0;
+    let mut lines = 0;
 
     while line_start < len {
         // Find end of line.
@@ -2027,6 +2516,7 @@ fn process_chunk_select_eq_fused(
             .unwrap_or(len);
         let line = &chunk[line_start..nl_pos];
         line_start = nl_pos + 1;
+        lines += 1;
 
         // Trim trailing whitespace (CR, space, tab).
         let end = line
@@ -2040,9 +2530,14 @@ fn process_chunk_select_eq_fused(
             .unwrap_or(end);
         let trimmed = &line[start..end];
 
-        if trimmed.is_empty() || trimmed[0] != b'{' {
+        if trimmed.is_empty() {
             continue;
         }
+        // Anything but a whole object line (another JSON value, or a
+        // malformed line) needs the per-line path.
+        if trimmed[0] != b'{' || trimmed[trimmed.len() - 1] != b'}' {
+            return None;
+        }
 
         // Quick compactness check: compact NDJSON starts with `{"` or `{}`.
         if trimmed.len() > 1 && trimmed[1] != b'"' && trimmed[1] != b'}' {
@@ -2055,10 +2550,22 @@ fn process_chunk_select_eq_fused(
 
         // Evaluate predicate. Missing field → value is null in jq semantics.
         let raw = match value {
+            Some(v) if key_repeats_after(trimmed, v, &key_finder) => return None,
             Some(v) => v,
             None => b"null" as &[u8],
         };
         match evaluate_select_predicate(raw, literal_bytes, op) {
+            // Matched lines are copied verbatim, so a lone surrogate escape
+            // needs the per-line path to be decoded to U+FFFD, repeated keys
+            // need the parser to keep the last one, and other escapes jq
+            // rewrites need re-serializing.
+            Some(true)
+                if crate::input::has_lone_surrogates(trimmed)
+                    || crate::input::has_duplicate_keys(trimmed)
+                    || output::has_rewritten_escapes(trimmed) =>
+            {
+                return None;
+            }
             Some(true) => {
                 *had_output = true;
                 output_buf.extend_from_slice(trimmed);
@@ -2069,14 +2576,15 @@ fn process_chunk_select_eq_fused(
         }
     }
 
-    Some(Ok(()))
+    Some(lines)
 }
 
 /// Fused chunk-level scanner for SelectCompound (AND/OR) in compact mode.
 ///
 /// Pre-builds memmem Finders for each condition's first field, then loops
-/// over lines evaluating the compound predicate. Returns `None` to fall back
-/// to the per-line path on non-compact data or multi-field chains.
+/// over lines evaluating the compound predicate. Returns the number of lines
+/// scanned, or `None` to fall back to the per-line path on non-compact data,
+/// multi-field chains or lines that aren't whole objects.
 fn process_chunk_select_compound_fused(
     chunk: &[u8],
     conditions: &[(Vec<String>, CmpOp, Vec<u8>)],
@@ -2084,7 +2592,7 @@ fn process_chunk_select_compound_fused(
     config: &OutputConfig,
     output_buf: &mut Vec<u8>,
     had_output: &mut bool,
-) -> Option<Result<()>> {
+) -> Option<usize> {
     // Only handle compact mode with single-field conditions.
     if config.mode != output::OutputMode::Compact {
         return None;
@@ -2102,9 +2610,14 @@ fn process_chunk_select_compound_fused(
         .collect();
     let finders: Vec<memchr::memmem::Finder<'_>> =
         patterns.iter().map(memchr::memmem::Finder::new).collect();
+    let key_finders: Vec<memchr::memmem::Finder<'_>> = patterns
+        .iter()
+        .map(|p| memchr::memmem::Finder::new(&p[..p.len() - 1]))
+        .collect();
 
     let len = chunk.len();
     let mut line_start: usize = 0;
+    let mut lines = 0;
 
     while line_start < len {
         let nl_pos = memchr::memchr(b'\n', &chunk[line_start..])
@@ -2112,6 +2625,7 @@ fn process_chunk_select_compound_fused(
             .unwrap_or(len);
         let line = &chunk[line_start..nl_pos];
         line_start = nl_pos + 1;
+        lines += 1;
 
         let end = line
             .iter()
@@ -2123,9 +2637,14 @@ fn process_chunk_select_compound_fused(
             .unwrap_or(end);
         let trimmed = &line[start..end];
 
-        if trimmed.is_empty() || trimmed[0] != b'{' {
+        if trimmed.is_empty() {
             continue;
         }
+        // Anything but a whole object line (another JSON value, or a
+        // malformed line) needs the per-line path.
+        if trimmed[0] != b'{' || trimmed[trimmed.len() - 1] != b'}' {
+            return None;
+        }
 
         if trimmed.len() > 1 && trimmed[1] != b'"' && trimmed[1] != b'}' {
             return None;
@@ -2135,13 +2654,16 @@ fn process_chunk_select_compound_fused(
             BoolOp::And => {
                 let mut all_true = true;
                 for (i, (fields, op, literal_bytes)) in conditions.iter().enumerate() {
-                    let raw = find_field_value_raw_prebuilt(
+                    let raw = match find_field_value_raw_prebuilt(
                         trimmed,
                         &fields[0],
                         &finders[i],
                         patterns[i].len(),
-                    )
-                    .unwrap_or(b"null");
+                    ) {
+                        Some(v) if key_repeats_after(trimmed, v, &key_finders[i]) => return None,
+                        Some(v) => v,
+                        None => b"null",
+                    };
                     match evaluate_select_predicate(raw, literal_bytes, *op) {
                         Some(false) => {
                             all_true = false;
@@ -2157,13 +2679,16 @@ fn process_chunk_select_compound_fused(
                 let mut any_true = false;
                 let mut any_ambiguous = false;
                 for (i, (fields, op, literal_bytes)) in conditions.iter().enumerate() {
-                    let raw = find_field_value_raw_prebuilt(
+                    let raw = match find_field_value_raw_prebuilt(
                         trimmed,
                         &fields[0],
                         &finders[i],
                         patterns[i].len(),
-                    )
-                    .unwrap_or(b"null");
+                    ) {
+                        Some(v) if key_repeats_after(trimmed, v, &key_finders[i]) => return None,
+                        Some(v) => v,
+                        None => b"null",
+                    };
                     match evaluate_select_predicate(raw, literal_bytes, *op) {
                         Some(true) => {
                             any_true = true;
@@ -2184,13 +2709,19 @@ fn process_chunk_select_compound_fused(
         };
 
         if matched {
+            if crate::input::has_lone_surrogates(trimmed)
+                || crate::input::has_duplicate_keys(trimmed)
+                || output::has_rewritten_escapes(trimmed)
+            {
+                return None;
+            }
             *had_output = true;
             output_buf.extend_from_slice(trimmed);
             write_line_terminator(output_buf, config);
         }
     }
 
-    Some(Ok(()))
+    Some(lines)
 }
 
 /// Process a line with the select(.field op literal) fast path.
@@ -2385,8 +2916,17 @@ fn emit_select_match(
         let padded = prepare_padded(trimmed, scratch);
         let minified =
             simdjson::minify(padded, trimmed.len()).context("failed to minify matched line")?;
-        output_buf.extend_from_slice(&minified);
-        write_line_terminator(output_buf, config);
+        if crate::input::has_duplicate_keys(&minified) || output::has_rewritten_escapes(&minified) {
+            // The minifier keeps repeated keys and escapes as written;
+            // serializing the parsed value keeps only the last key and
+            // escapes strings as jq does.
+            let value = simdjson::dom_parse_to_value(padded, trimmed.len())
+                .context("failed to parse NDJSON line")?;
+            output::write_value(output_buf, &value, config)?;
+        } else {
+            output_buf.extend_from_slice(&minified);
+            write_line_terminator(output_buf, config);
+        }
     } else {
         // Non-compact mode: re-serialize for proper formatting (e.g. pretty-print).
         let padded = prepare_padded(trimmed, scratch);
@@ -2411,6 +2951,7 @@ fn process_line_select_string_pred_field(
     env: &Env,
     output_buf: &mut Vec<u8>,
     had_output: &mut bool,
+    last_was_falsy: &mut bool,
     scratch: &mut Vec<u8>,
     dp: &mut simdjson::DomParser,
 ) -> Result<()> {
@@ -2428,6 +2969,7 @@ fn process_line_select_string_pred_field(
                 .find_field_raw(padded, trimmed.len(), &out_refs)
                 .context("failed to extract output field from NDJSON line")?;
             *had_output = true;
+            *last_was_falsy = is_falsy_raw(&raw_out);
             emit_raw_field(output_buf, &raw_out, config);
             write_line_terminator(output_buf, config);
         }
@@ -2438,6 +2980,7 @@ fn process_line_select_string_pred_field(
                 .context("failed to parse NDJSON line")?;
             crate::filter::eval::eval_filter_with_env(filter, &value, env, &mut |v| {
                 *had_output = true;
+                *last_was_falsy = !v.is_truthy();
                 output::write_value(output_buf, &v, config).ok();
             });
         }
@@ -2445,6 +2988,44 @@ fn process_line_select_string_pred_field(
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// Fast-path detection: first(.field[] | select(cond))
+// ---------------------------------------------------------------------------
+
+/// `first(ITER | select(COND))` and `limit(1; ITER | select(COND))`, where
+/// ITER is `.[]` or `.field.chain[]`.
+fn detect_first_select_fast_path(filter: &Filter) -> Option<NdjsonFastPath> {
+    let body = match filter {
+        Filter::Builtin(name, args) if name == "first" && args.len() == 1 => &args[0],
+        Filter::Builtin(name, args)
+            if name == "limit"
+                && args.len() == 2
+                && matches!(args[0], Filter::Literal(Value::Int(1))) =>
+        {
+            &args[1]
+        }
+        _ => return None,
+    };
+    let Filter::Pipe(iter, sel) = body else {
+        return None;
+    };
+    let Filter::Select(cond) = sel.as_ref() else {
+        return None;
+    };
+    let mut fields = Vec::new();
+    match iter.as_ref() {
+        Filter::Iterate => {}
+        Filter::Pipe(chain, last)
+            if matches!(last.as_ref(), Filter::Iterate)
+                && crate::filter::collect_field_chain(chain, &mut fields) => {}
+        _ => return None,
+    }
+    Some(NdjsonFastPath::FirstSelect {
+        fields,
+        cond: cond.as_ref().clone(),
+    })
+}
+
 // ---------------------------------------------------------------------------
 // Fast-path detection: length / keys
 // ---------------------------------------------------------------------------
@@ -2585,6 +3166,20 @@ fn detect_multi_field_fast_path(filter: &Filter) -> Option<NdjsonFastPath> {
     None
 }
 
+/// `[field chains...] | @tsv` or `| @csv`.
+fn detect_tabular_fast_path(filter: &Filter) -> Option<NdjsonFastPath> {
+    let Filter::Pipe(lhs, rhs) = filter else {
+        return None;
+    };
+    let csv = match rhs.as_ref() {
+        Filter::Builtin(name, args) if args.is_empty() && name == "@tsv" => false,
+        Filter::Builtin(name, args) if args.is_empty() && name == "@csv" => true,
+        _ => return None,
+    };
+    let entries = try_multi_field_arr(lhs)?;
+    Some(NdjsonFastPath::Tabular { entries, csv })
+}
+
 /// Try to decompose an ObjectConstruct into (json_key_bytes, field_chain) pairs.
 /// Returns None if any key is an Expr or any value is not a field chain.
 fn try_multi_field_obj(filter: &Filter) -> Option<Vec<(Vec<u8>, Vec<String>)>> {
@@ -2601,6 +3196,15 @@ fn try_multi_field_obj(filter: &Filter) -> Option<Vec<(Vec<u8>, Vec<String>)>> {
             crate::filter::ObjKey::Name(s) => s,
             crate::filter::ObjKey::Expr(_) => return None,
         };
+        // A repeated key overwrites the earlier value; leave that to the evaluator.
+        if pairs
+            .iter()
+            .filter(|(k, _)| matches!(k, crate::filter::ObjKey::Name(n) if n == key_name))
+            .count()
+            > 1
+        {
+            return None;
+        }
         let mut fields = Vec::new();
         if !crate::filter::collect_field_chain(val_filter, &mut fields) || fields.is_empty() {
             return None;
@@ -2757,6 +3361,11 @@ fn process_line_type(
     match type_str {
         Some(s) => {
             *had_output = true;
+            let s = if config.mode == output::OutputMode::Raw {
+                s.trim_matches('"')
+            } else {
+                s
+            };
             output_buf.extend_from_slice(s.as_bytes());
             write_line_terminator(output_buf, config);
         }
@@ -2785,6 +3394,7 @@ fn process_line_has(
     env: &Env,
     output_buf: &mut Vec<u8>,
     had_output: &mut bool,
+    last_was_falsy: &mut bool,
     scratch: &mut Vec<u8>,
     dp: &mut simdjson::DomParser,
 ) -> Result<()> {
@@ -2793,6 +3403,7 @@ fn process_line_has(
     match dp.field_has(padded, trimmed.len(), &field_refs, key)? {
         Some(result) => {
             *had_output = true;
+            *last_was_falsy = !result;
             output_buf.extend_from_slice(if result { b"true" } else { b"false" });
             write_line_terminator(output_buf, config);
         }
@@ -2803,6 +3414,7 @@ fn process_line_has(
                 .context("failed to parse NDJSON line")?;
             crate::filter::eval::eval_filter_with_env(filter, &value, env, &mut |v| {
                 *had_output = true;
+                *last_was_falsy = !v.is_truthy();
                 output::write_value(output_buf, &v, config).ok();
             });
         }
@@ -2827,6 +3439,7 @@ fn process_line_select_eq_field(
     env: &Env,
     output_buf: &mut Vec<u8>,
     had_output: &mut bool,
+    last_was_falsy: &mut bool,
     scratch: &mut Vec<u8>,
     dp: &mut simdjson::DomParser,
 ) -> Result<()> {
@@ -2844,6 +3457,7 @@ fn process_line_select_eq_field(
                 .find_field_raw(padded, trimmed.len(), &out_refs)
                 .context("failed to extract output field from NDJSON line")?;
             *had_output = true;
+            *last_was_falsy = is_falsy_raw(&raw_out);
             emit_raw_field(output_buf, &raw_out, config);
             write_line_terminator(output_buf, config);
         }
@@ -2854,6 +3468,7 @@ fn process_line_select_eq_field(
                 .context("failed to parse NDJSON line")?;
             crate::filter::eval::eval_filter_with_env(filter, &value, env, &mut |v| {
                 *had_output = true;
+                *last_was_falsy = !v.is_truthy();
                 output::write_value(output_buf, &v, config).ok();
             });
         }
@@ -2892,7 +3507,7 @@ fn process_line_multi_field_obj(
         }
         output_buf.extend_from_slice(key_bytes);
         output_buf.push(b':');
-        output_buf.extend_from_slice(&raw_values[i]);
+        push_raw_value(output_buf, &raw_values[i]);
     }
     output_buf.push(b'}');
     *had_output = true;
@@ -2925,14 +3540,118 @@ fn process_line_multi_field_arr(
         if i > 0 {
             output_buf.push(b',');
         }
-        output_buf.extend_from_slice(&raw_values[i]);
+        push_raw_value(output_buf, &raw_values[i]);
+    }
+    output_buf.push(b']');
+    *had_output = true;
+    write_line_terminator(output_buf, config);
+    Ok(())
+}
+
+/// Process a line with the `[.f, ...] | @tsv` / `@csv` fast path.
+///
+/// Each raw field is escaped straight into the row: strings as `@tsv`/`@csv`
+/// quote them, numbers and booleans as their JSON text, null as an empty
+/// cell. Lines with an array or object field, and output modes other than
+/// plain `-r`, go through the evaluator so they get its errors and quoting.
+#[allow(clippy::too_many_arguments)]
+fn process_line_tabular(
+    trimmed: &[u8],
+    entries: &[Vec<String>],
+    csv: bool,
+    filter: &Filter,
+    config: &OutputConfig,
+    env: &Env,
+    output_buf: &mut Vec<u8>,
+    had_output: &mut bool,
+    scratch: &mut Vec<u8>,
+    dp: &mut simdjson::DomParser,
+) -> Result<()> {
+    if config.mode == output::OutputMode::Raw && !config.ascii_output {
+        let padded = prepare_padded(trimmed, scratch);
+        let field_chains: Vec<Vec<&str>> = entries
+            .iter()
+            .map(|fields| fields.iter().map(|s| s.as_str()).collect())
+            .collect();
+        let chain_refs: Vec<&[&str]> = field_chains.iter().map(|v| v.as_slice()).collect();
+        let raw_values = dp
+            .find_fields_raw(padded, trimmed.len(), &chain_refs)
+            .context("failed to batch-extract fields for @tsv/@csv row")?;
+
+        let row_start = output_buf.len();
+        let mut unescaped = Vec::new();
+        let mut complete = true;
+        for (i, raw) in raw_values.iter().enumerate() {
+            if i > 0 {
+                output_buf.push(if csv { b',' } else { b'\t' });
+            }
+            if !push_tabular_cell(output_buf, raw, csv, &mut unescaped) {
+                complete = false;
+                break;
+            }
+        }
+        if complete {
+            *had_output = true;
+            write_line_terminator(output_buf, config);
+            return Ok(());
+        }
+        output_buf.truncate(row_start);
     }
-    output_buf.push(b']');
-    *had_output = true;
-    write_line_terminator(output_buf, config);
+
+    let padded = prepare_padded(trimmed, scratch);
+    let value = simdjson::dom_parse_to_value(padded, trimmed.len())
+        .context("failed to parse NDJSON line")?;
+    crate::filter::eval::eval_filter_with_env(filter, &value, env, &mut |v| {
+        *had_output = true;
+        output::write_value(output_buf, &v, config).ok();
+    });
     Ok(())
 }
 
+/// Append one `@tsv`/`@csv` cell for a raw JSON scalar. Returns false for
+/// arrays and objects, which aren't valid in a row.
+fn push_tabular_cell(out: &mut Vec<u8>, raw: &[u8], csv: bool, unescaped: &mut Vec<u8>) -> bool {
+    match raw.first() {
+        Some(b'"') => {
+            let inner = &raw[1..raw.len() - 1];
+            let text = if memchr::memchr(b'\\', inner).is_some() {
+                unescaped.clear();
+                unescape_json_string(inner, unescaped);
+                unescaped.as_slice()
+            } else {
+                inner
+            };
+            if csv {
+                out.push(b'"');
+                for &b in text {
+                    if b == b'"' {
+                        out.push(b'"');
+                    }
+                    out.push(b);
+                }
+                out.push(b'"');
+            } else {
+                for &b in text {
+                    match b {
+                        b'\\' => out.extend_from_slice(b"\\\\"),
+                        b'\t' => out.extend_from_slice(b"\\t"),
+                        b'\n' => out.extend_from_slice(b"\\n"),
+                        b'\r' => out.extend_from_slice(b"\\r"),
+                        _ => out.push(b),
+                    }
+                }
+            }
+            true
+        }
+        Some(b'{' | b'[') | None => false,
+        Some(b'n') => true,
+        Some(_) => {
+            out.extend_from_slice(raw);
+            true
+        }
+    }
+}
+
 /// Process a line with the `select(.f == lit) | {key: .field, ...}` fast path.
 #[allow(clippy::too_many_arguments)]
 fn process_line_select_eq_obj(
@@ -2987,7 +3706,7 @@ fn process_line_select_eq_obj(
             }
             output_buf.extend_from_slice(key_bytes);
             output_buf.push(b':');
-            output_buf.extend_from_slice(&raw_values[i]);
+            push_raw_value(output_buf, &raw_values[i]);
         }
         output_buf.push(b'}');
         *had_output = true;
@@ -3048,7 +3767,7 @@ fn process_line_select_eq_arr(
             if i > 0 {
                 output_buf.push(b',');
             }
-            output_buf.extend_from_slice(&raw_values[i]);
+            push_raw_value(output_buf, &raw_values[i]);
         }
         output_buf.push(b']');
         *had_output = true;
@@ -3057,6 +3776,171 @@ fn process_line_select_eq_arr(
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// Processing: first(.field[] | select(cond))
+// ---------------------------------------------------------------------------
+
+/// Process a line with the `first(.field[] | select(cond))` fast path.
+///
+/// Elements are tested one at a time straight from the flat buffer, so the
+/// ones after the first match are never looked at (nor materialized). A
+/// target that isn't an array or object takes the lazy evaluator path for
+/// its error message.
+#[allow(clippy::too_many_arguments)]
+fn process_line_first_select(
+    trimmed: &[u8],
+    fields: &[String],
+    cond: &Filter,
+    filter: &Filter,
+    config: &OutputConfig,
+    env: &Env,
+    output_buf: &mut Vec<u8>,
+    had_output: &mut bool,
+    last_was_falsy: &mut bool,
+    scratch: &mut Vec<u8>,
+) -> Result<()> {
+    let padded = prepare_padded(trimmed, scratch);
+    let flat_buf = simdjson::dom_parse_to_flat_buf(padded, trimmed.len())
+        .context("failed to parse NDJSON line")?;
+    let mut target = Some(flat_buf.root());
+    for field in fields {
+        target = target.and_then(|t| t.get_field(field));
+    }
+    let mut emit = |v: Value| {
+        *had_output = true;
+        *last_was_falsy = !v.is_truthy();
+        output::write_value(output_buf, &v, config).ok();
+    };
+    let target = match target {
+        Some(t) if t.is_array() || t.is_object() => t,
+        _ => {
+            crate::flat_eval::eval_flat(filter, flat_buf.root(), env, &mut emit);
+            return Ok(());
+        }
+    };
+    let elems: Box<dyn Iterator<Item = crate::flat_value::FlatValue<'_>>> = if target.is_array() {
+        Box::new(target.array_iter())
+    } else {
+        Box::new(target.object_iter().map(|(_, v)| v))
+    };
+    for elem in elems {
+        // `select` passes the element on for a truthy output of `cond`;
+        // outputs after an error never happen.
+        let mut keep = false;
+        crate::flat_eval::eval_flat(cond, elem, env, &mut |v| {
+            keep |= v.is_truthy() && !env.has_error();
+        });
+        if keep {
+            // jq stops at the first match, so a later error in `cond` is moot.
+            env.take_error();
+            emit(elem.to_value());
+            break;
+        }
+        if env.has_error() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Return a filter string for every `NdjsonFastPath` variant.
+///
+/// Used by integration tests and fuzz targets to ensure every fast-path variant
+/// is exercised by differential tests (fast path vs normal evaluator). Uses an
+/// exhaustive match so that adding a new variant without a test filter is a
+/// compile error.
+#[doc(hidden)]
+pub fn all_fast_path_test_filters() -> Vec<&'static str> {
+    // This enum variant list must stay exhaustive. The compiler will error if a
+    // new NdjsonFastPath variant is added without a corresponding entry here.
+    #[deny(unreachable_patterns)]
+    fn _variant_coverage_check(fp: &NdjsonFastPath) {
+        match fp {
+            NdjsonFastPath::None => {}
+            NdjsonFastPath::FieldChain(_) => {}
+            NdjsonFastPath::SelectEq { .. } => {}
+            NdjsonFastPath::Length(_) => {}
+            NdjsonFastPath::Keys { .. } => {}
+            NdjsonFastPath::Type(_) => {}
+            NdjsonFastPath::Has { .. } => {}
+            NdjsonFastPath::SelectEqField { .. } => {}
+            NdjsonFastPath::MultiFieldObj { .. } => {}
+            NdjsonFastPath::MultiFieldArr { .. } => {}
+            NdjsonFastPath::Tabular { .. } => {}
+            NdjsonFastPath::SelectEqObj { .. } => {}
+            NdjsonFastPath::SelectEqArr { .. } => {}
+            NdjsonFastPath::SelectCompound { .. } => {}
+            NdjsonFastPath::SelectStringPred { .. } => {}
+            NdjsonFastPath::SelectStringPredField { .. } => {}
+            NdjsonFastPath::FirstSelect { .. } => {}
+        }
+    }
+
+    vec![
+        // FieldChain
+        ".name",
+        ".actor.login",
+        // SelectEq (various types + ops)
+        "select(.type == \"PushEvent\")",
+        "select(.count == 42)",
+        "select(.active == true)",
+        "select(.value == null)",
+        "select(.type != \"PushEvent\")",
+        "select(.count > 10)",
+        "select(.count < 100)",
+        "select(.count >= 50)",
+        "select(.count <= 50)",
+        "select(.name > \"m\")",
+        // Length
+        "length",
+        ".meta | length",
+        // Keys (sorted)
+        "keys",
+        ".meta | keys",
+        // Keys (unsorted)
+        "keys_unsorted",
+        ".meta | keys_unsorted",
+        // Type
+        "type",
+        ".meta | type",
+        // Has
+        "has(\"name\")",
+        ".meta | has(\"x\")",
+        // SelectEqField
+        "select(.type == \"PushEvent\") | .name",
+        "select(.count > 10) | .name",
+        // MultiFieldObj
+        "{name: .name, count: .count}",
+        "{type: .type, login: .actor.login}",
+        // MultiFieldArr
+        "[.name, .count]",
+        "[.type, .actor.login]",
+        // Tabular (the exhaustive test runs with -c, so this checks the fallback)
+        "[.name, .count] | @tsv",
+        "[.type, .actor.login] | @csv",
+        // SelectEqObj
+        "select(.type == \"PushEvent\") | {name: .name, count: .count}",
+        // SelectEqArr
+        "select(.type == \"PushEvent\") | [.name, .count]",
+        // SelectCompound (AND / OR)
+        "select(.type == \"PushEvent\" and .active == true)",
+        "select(.type == \"PushEvent\" or .type == \"CreateEvent\")",
+        "select(.count > 10 and .active == true)",
+        "select(.type != \"PushEvent\" or .count < 100)",
+        // SelectStringPred
+        "select(.name | test(\"^A\"))",
+        "select(.name | startswith(\"test\"))",
+        "select(.name | endswith(\".com\"))",
+        "select(.name | contains(\"oo\"))",
+        // SelectStringPredField
+        "select(.name | contains(\"oo\")) | .count",
+        // FirstSelect
+        "first(.items[] | select(. > 5))",
+        "limit(1; .meta[] | select(. >= 2))",
+        "first(.[] | select(type == \"string\"))",
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -5195,6 +6079,56 @@ mod tests {
         assert_eq!(n, 0);
     }
 
+    #[test]
+    fn output_status_merge_keeps_last_non_empty_chunk() {
+        let falsy = OutputStatus {
+            had_output: true,
+            last_was_falsy: true,
+            ..OutputStatus::default()
+        };
+        let mut status = OutputStatus::default();
+        status.merge(falsy);
+        status.merge(OutputStatus::default());
+        assert_eq!(status, falsy);
+        status.merge(OutputStatus {
+            bad_lines: 2,
+            last_line_failed: Some(true),
+            ..OutputStatus::default()
+        });
+        assert_eq!(status.last_line_failed, Some(true));
+        status.merge(OutputStatus {
+            had_output: true,
+            last_was_falsy: false,
+            bad_lines: 1,
+            last_line_failed: Some(false),
+        });
+        assert!(status.had_output && !status.last_was_falsy);
+        assert_eq!(status.bad_lines, 3);
+        assert_eq!(status.last_line_failed, Some(false));
+    }
+
+    #[test]
+    fn chunk_errors_number_bad_lines_from_chunk_start() {
+        let filter = crate::filter::parse(".a[0]").unwrap();
+        let config = OutputConfig {
+            mode: crate::output::OutputMode::Compact,
+            ..OutputConfig::default()
+        };
+        let data = b"{\"a\":[1]}\n{\"a\":[\n{\"a\":2}\n{\"a\":[3]}\n";
+        let result =
+            process_chunk(data, &filter, &config, &NdjsonFastPath::None, &Env::empty()).unwrap();
+        assert_eq!(result.out, b"1\n3\n");
+        assert_eq!(result.lines, 4);
+        assert_eq!(result.status.bad_lines, 1);
+        // The type error on line 3 comes after the report for line 2
+        let errors = String::from_utf8(result.render_errors("data.jsonl", 10)).unwrap();
+        assert_eq!(
+            errors,
+            "qj: error (at line 12): invalid JSON: EOF while parsing a list at column 6\n\
+             qj: error (at data.jsonl:13): Cannot index number with number\n"
+        );
+    }
+
     // ---- process_ndjson_streaming tests ----
 
     fn make_filter(expr: &str) -> Filter {
@@ -5210,9 +6144,9 @@ mod tests {
         let env = Env::empty();
         let mut cursor = std::io::Cursor::new(input);
         let mut out = Vec::new();
-        let ho = process_ndjson_streaming(&mut cursor, &filter, &config, &env, &mut out)
+        let status = process_ndjson_streaming(&mut cursor, &filter, &config, &env, &mut out)
             .expect("streaming failed");
-        (out, ho)
+        (out, status.had_output)
     }
 
     fn buffered_output(input: &[u8], filter_expr: &str) -> (Vec<u8>, bool) {
@@ -5298,92 +6232,3 @@ mod tests {
         assert_eq!(stream_out, buf_out);
     }
 }
-
-/// Return a filter string for every `NdjsonFastPath` variant.
-///
-/// Used by integration tests and fuzz targets to ensure every fast-path variant
-/// is exercised by differential tests (fast path vs normal evaluator). Uses an
-/// exhaustive match so that adding a new variant without a test filter is a
-/// compile error.
-#[doc(hidden)]
-pub fn all_fast_path_test_filters() -> Vec<&'static str> {
-    // This enum variant list must stay exhaustive. The compiler will error if a
-    // new NdjsonFastPath variant is added without a corresponding entry here.
-    #[deny(unreachable_patterns)]
-    fn _variant_coverage_check(fp: &NdjsonFastPath) {
-        match fp {
-            NdjsonFastPath::None => {}
-            NdjsonFastPath::FieldChain(_) => {}
-            NdjsonFastPath::SelectEq { .. } => {}
-            NdjsonFastPath::Length(_) => {}
-            NdjsonFastPath::Keys { .. } => {}
-            NdjsonFastPath::Type(_) => {}
-            NdjsonFastPath::Has { .. } => {}
-            NdjsonFastPath::SelectEqField { .. } => {}
-            NdjsonFastPath::MultiFieldObj { .. } => {}
-            NdjsonFastPath::MultiFieldArr { .. } => {}
-            NdjsonFastPath::SelectEqObj { .. } => {}
-            NdjsonFastPath::SelectEqArr { .. } => {}
-            NdjsonFastPath::SelectCompound { .. } => {}
-            NdjsonFastPath::SelectStringPred { .. } => {}
-            NdjsonFastPath::SelectStringPredField { .. } => {}
-        }
-    }
-
-    vec![
-        // FieldChain
-        ".name",
-        ".actor.login",
-        // SelectEq (various types + ops)
-        "select(.type == \"PushEvent\")",
-        "select(.count == 42)",
-        "select(.active == true)",
-        "select(.value == null)",
-        "select(.type != \"PushEvent\")",
-        "select(.count > 10)",
-        "select(.count < 100)",
-        "select(.count >= 50)",
-        "select(.count <= 50)",
-        "select(.name > \"m\")",
-        // Length
-        "length",
-        ".meta | length",
-        // Keys (sorted)
-        "keys",
-        ".meta | keys",
-        // Keys (unsorted)
-        "keys_unsorted",
-        ".meta | keys_unsorted",
-        // Type
-        "type",
-        ".meta | type",
-        // Has
-        "has(\"name\")",
-        ".meta | has(\"x\")",
-        // SelectEqField
-        "select(.type == \"PushEvent\") | .name",
-        "select(.count > 10) | .name",
-        // MultiFieldObj
-        "{name: .name, count: .count}",
-        "{type: .type, login: .actor.login}",
-        // MultiFieldArr
-        "[.name, .count]",
-        "[.type, .actor.login]",
-        // SelectEqObj
-        "select(.type == \"PushEvent\") | {name: .name, count: .count}",
-        // SelectEqArr
-        "select(.type == \"PushEvent\") | [.name, .count]",
-        // SelectCompound (AND / OR)
-        "select(.type == \"PushEvent\" and .active == true)",
-        "select(.type == \"PushEvent\" or .type == \"CreateEvent\")",
-        "select(.count > 10 and .active == true)",
-        "select(.type != \"PushEvent\" or .count < 100)",
-        // SelectStringPred
-        "select(.name | test(\"^A\"))",
-        "select(.name | startswith(\"test\"))",
-        "select(.name | endswith(\".com\"))",
-        "select(.name | contains(\"oo\"))",
-        // SelectStringPredField
-        "select(.name | contains(\"oo\")) | .count",
-    ]
-}
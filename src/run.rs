@@ -0,0 +1,416 @@
+//! Library-level entry point for embedding qj in other programs.
+//!
+//! Compiles a jq filter once and evaluates it against in-memory values,
+//! returning outputs and runtime errors as values instead of printing them.
+//!
+//! ```
+//! use qj::run::Program;
+//! use qj::value::Value;
+//!
+//! let program = Program::compile(".items[] | .name").unwrap();
+//! let input = Value::from(serde_json::json!({
+//!     "items": [{"name": "a"}, {"name": "b"}]
+//! }));
+//! let names: Vec<serde_json::Value> = program
+//!     .eval(&input)
+//!     .map(|r| r.unwrap().into())
+//!     .collect();
+//! assert_eq!(names, vec![serde_json::json!("a"), serde_json::json!("b")]);
+//! ```
+//!
+//! Each evaluation runs on a worker thread, one output ahead of the caller:
+//! outputs are computed as the iterator is advanced, so `take(1)` on an
+//! endless generator returns, and dropping the iterator stops the filter at
+//! its next output. A `Program` keeps its workers, and one whose evaluation
+//! was read to the end takes the next.
+
+use std::sync::{Arc, Mutex, mpsc};
+
+use anyhow::{Result, bail};
+
+use crate::filter::{Env, Filter};
+use crate::value::Value;
+
+/// A compiled jq filter, ready to be evaluated against any number of inputs.
+#[derive(Debug, Clone)]
+pub struct Program {
+    filter: Arc<Filter>,
+    /// Shared by clones, which evaluate the same filter.
+    workers: Arc<Workers>,
+}
+
+impl Program {
+    /// Parse a jq filter.
+    ///
+    /// `import`/`include` need module search paths and are rejected here.
+    ///
+    /// ```
+    /// use qj::run::Program;
+    ///
+    /// assert!(Program::compile(".a | length").is_ok());
+    /// assert!(Program::compile(".a |").is_err());
+    /// ```
+    pub fn compile(src: &str) -> Result<Program> {
+        let filter = crate::filter::parse(src)?;
+        if matches!(
+            filter,
+            Filter::Import { .. } | Filter::Include { .. } | Filter::ModuleDecl { .. }
+        ) {
+            bail!("modules are not supported by Program::compile");
+        }
+        Ok(Program {
+            filter: Arc::new(filter),
+            workers: Arc::default(),
+        })
+    }
+
+    /// Evaluate against `input` with no variables bound except an empty `$ARGS`.
+    pub fn eval(&self, input: &Value) -> impl Iterator<Item = Result<Value, EvalError>> + use<> {
+        self.eval_with_env(input, &EnvBuilder::new())
+    }
+
+    /// Evaluate against `input` with variables from an [`EnvBuilder`].
+    ///
+    /// Yields every output produced before the first uncaught error, then the
+    /// error itself (like jq, evaluation stops there).
+    ///
+    /// ```
+    /// use qj::run::{EnvBuilder, Program};
+    /// use qj::value::Value;
+    ///
+    /// let program = Program::compile(".[] | . + $offset").unwrap();
+    /// let vars = EnvBuilder::new().var("offset", Value::Int(10));
+    /// let input = Value::from(serde_json::json!([1, 2, "x"]));
+    /// let results: Vec<_> = program.eval_with_env(&input, &vars).collect();
+    /// assert_eq!(results[0].as_ref().unwrap(), &Value::Int(11));
+    /// assert_eq!(results[1].as_ref().unwrap(), &Value::Int(12));
+    /// assert!(results[2].is_err());
+    /// assert_eq!(results.len(), 3);
+    /// ```
+    pub fn eval_with_env(
+        &self,
+        input: &Value,
+        vars: &EnvBuilder,
+    ) -> impl Iterator<Item = Result<Value, EvalError>> + use<> {
+        // A rendezvous channel: the filter waits at each output until the
+        // caller asks for it, and stops once the receiver is gone.
+        let (tx, rx) = mpsc::sync_channel(0);
+        let job = Job {
+            input: input.clone(),
+            vars: vars.clone(),
+            tx,
+        };
+        let worker = self.workers.start(&self.filter, job);
+        Outputs {
+            rx,
+            worker: Some(worker),
+            workers: Arc::clone(&self.workers),
+        }
+    }
+}
+
+/// Stack size of the worker threads. The evaluator recurses up to its depth
+/// limit, which is sized for the 8 MiB stack a main thread usually has; the
+/// 2 MiB a spawned thread gets by default can run out first.
+const WORKER_STACK_SIZE: usize = 8 << 20;
+
+/// One evaluation, sent to a worker.
+struct Job {
+    input: Value,
+    vars: EnvBuilder,
+    tx: mpsc::SyncSender<Result<Value, EvalError>>,
+}
+
+impl Job {
+    fn run(self, filter: &Filter) {
+        let Job { input, vars, tx } = self;
+        let env = vars.build();
+        crate::filter::eval::eval_filter_until(filter, &input, &env, &mut |v| {
+            // Builtins record an error and return, but sibling branches
+            // (e.g. the `2` in `1, error("x"), 2`) still run; drop their output.
+            env.has_error() || tx.send(Ok(v)).is_ok()
+        });
+        if let Some(err) = env.take_error() {
+            let _ = tx.send(Err(EvalError { value: err }));
+        }
+    }
+}
+
+/// The threads a [`Program`] evaluates on, each reached through the sender
+/// of its job queue. Starting a thread per evaluation costs more than most
+/// evaluations, so idle workers wait here for the next job. A worker exits
+/// once its sender is dropped: with the `Program`, or by an iterator dropped
+/// before its evaluation finished.
+#[derive(Debug, Default)]
+struct Workers {
+    idle: Mutex<Vec<mpsc::Sender<Job>>>,
+}
+
+impl Workers {
+    /// Hand `job` to an idle worker, or to a new one if none is idle, and
+    /// return the worker.
+    fn start(&self, filter: &Arc<Filter>, mut job: Job) -> mpsc::Sender<Job> {
+        loop {
+            let idle = self.idle.lock().unwrap().pop();
+            let worker = idle.unwrap_or_else(|| spawn_worker(Arc::clone(filter)));
+            match worker.send(job) {
+                Ok(()) => return worker,
+                // The worker's thread is gone (a filter panicked on it).
+                Err(mpsc::SendError(unsent)) => job = unsent,
+            }
+        }
+    }
+}
+
+fn spawn_worker(filter: Arc<Filter>) -> mpsc::Sender<Job> {
+    let (jobs, queue) = mpsc::channel::<Job>();
+    std::thread::Builder::new()
+        .name("qj-eval".to_string())
+        .stack_size(WORKER_STACK_SIZE)
+        .spawn(move || {
+            for job in queue {
+                job.run(&filter);
+            }
+        })
+        .expect("failed to spawn thread");
+    jobs
+}
+
+/// The outputs of one evaluation, as [`Program::eval_with_env`] returns them.
+struct Outputs {
+    rx: mpsc::Receiver<Result<Value, EvalError>>,
+    /// The worker running the evaluation, until it is returned to `workers`.
+    worker: Option<mpsc::Sender<Job>>,
+    workers: Arc<Workers>,
+}
+
+impl Iterator for Outputs {
+    type Item = Result<Value, EvalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rx.recv() {
+            Ok(output) => Some(output),
+            Err(mpsc::RecvError) => {
+                // The job is done, so the worker is free for the next. One
+                // still running when this is dropped isn't: it may never
+                // reach another output to notice that nobody is listening.
+                if let Some(worker) = self.worker.take() {
+                    self.workers.idle.lock().unwrap().push(worker);
+                }
+                None
+            }
+        }
+    }
+}
+
+/// The variables for [`Program::eval_with_env`], the library counterpart of
+/// `--arg`, `--argjson` and `--args`.
+#[derive(Debug, Clone, Default)]
+pub struct EnvBuilder {
+    named: Vec<(String, Value)>,
+    positional: Vec<Value>,
+}
+
+impl EnvBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind `$name` (given without the `$`), also listed in `$ARGS.named`.
+    pub fn var(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.named.push((name.into(), value));
+        self
+    }
+
+    /// Append to `$ARGS.positional`.
+    pub fn positional(mut self, values: impl IntoIterator<Item = Value>) -> Self {
+        self.positional.extend(values);
+        self
+    }
+
+    /// An `Env` is tied to one thread, so each evaluation builds its own.
+    fn build(self) -> Env {
+        let mut env = Env::empty();
+        for (name, value) in &self.named {
+            env = env.bind_var(format!("${name}"), value.clone());
+        }
+        let args = Value::Object(Arc::new(vec![
+            (
+                "positional".to_string(),
+                Value::Array(Arc::new(self.positional)),
+            ),
+            ("named".to_string(), Value::Object(Arc::new(self.named))),
+        ]));
+        env.bind_var("$ARGS".to_string(), args)
+    }
+}
+
+/// An uncaught runtime error, carrying the value passed to `error` (or the
+/// message string for built-in errors).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    value: Value,
+}
+
+impl EvalError {
+    /// The raw error value, as `try ... catch .` would see it.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Value::String(s) => f.write_str(s),
+            other => write!(f, "{} (not a string)", other.short_desc()),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(program: &Program, input: serde_json::Value) -> Vec<Result<serde_json::Value, String>> {
+        program
+            .eval(&Value::from(input))
+            .map(|r| r.map(Into::into).map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn compile_error() {
+        let err = Program::compile(".a | | .b").unwrap_err();
+        assert!(!err.to_string().is_empty());
+        assert!(Program::compile("import \"foo\" as foo; .").is_err());
+    }
+
+    #[test]
+    fn multiple_outputs() {
+        let program = Program::compile(".[] | {v: ., sq: (. * .)}").unwrap();
+        assert_eq!(
+            run(&program, json!([1, 2])),
+            vec![Ok(json!({"v": 1, "sq": 1})), Ok(json!({"v": 2, "sq": 4}))]
+        );
+    }
+
+    #[test]
+    fn runtime_error_stops_outputs() {
+        let program = Program::compile(r#"1, error("boom"), 2"#).unwrap();
+        assert_eq!(
+            run(&program, json!(null)),
+            vec![Ok(json!(1)), Err("boom".to_string())]
+        );
+    }
+
+    #[test]
+    fn runtime_error_value_is_preserved() {
+        let program = Program::compile(r#"error({code: 7})"#).unwrap();
+        let results: Vec<_> = program.eval(&Value::Null).collect();
+        let err = results[0].as_ref().unwrap_err();
+        assert_eq!(
+            serde_json::Value::from(err.value().clone()),
+            json!({"code": 7})
+        );
+    }
+
+    #[test]
+    fn errors_do_not_leak_between_evaluations() {
+        let failing = Program::compile(".a").unwrap();
+        assert!(run(&failing, json!(5))[0].is_err());
+        let ok = Program::compile(".a").unwrap();
+        assert_eq!(run(&ok, json!({"a": 1})), vec![Ok(json!(1))]);
+    }
+
+    #[test]
+    fn concurrent_evaluations_keep_errors_separate() {
+        let program = Program::compile(".a").unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let program = program.clone();
+                std::thread::spawn(move || {
+                    let input = if i % 2 == 0 {
+                        json!({"a": i})
+                    } else {
+                        json!(i)
+                    };
+                    (0..100)
+                        .map(|_| run(&program, input.clone()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            for results in handle.join().unwrap() {
+                assert_eq!(results.len(), 1);
+                assert_eq!(results[0].is_ok(), i % 2 == 0);
+            }
+        }
+    }
+
+    #[test]
+    fn outputs_are_produced_as_they_are_asked_for() {
+        for filter in ["range(.; infinite)", "repeat(.)", "1, (repeat(.) | empty)"] {
+            let program = Program::compile(filter).unwrap();
+            let first: Vec<_> = program.eval(&Value::Int(1)).take(1).collect();
+            assert_eq!(first, vec![Ok(Value::Int(1))], "{filter}");
+        }
+        let program = Program::compile("range(infinite)").unwrap();
+        let mut outputs = program.eval(&Value::Null);
+        assert_eq!(outputs.nth(2), Some(Ok(Value::Int(2))));
+        assert_eq!(outputs.next(), Some(Ok(Value::Int(3))));
+    }
+
+    #[test]
+    fn workers_are_reused_and_deep_filters_fit_their_stack() {
+        // Close to the evaluator's depth limit.
+        let program = Program::compile("def f: if . < 26 then . + 1 | f else . end; f").unwrap();
+        for _ in 0..3 {
+            let mut outputs = program.eval(&Value::Int(0));
+            assert_eq!(outputs.next(), Some(Ok(Value::Int(26))));
+            assert_eq!(outputs.next(), None);
+            // Reused: a worker started for each evaluation would leave more idle.
+            assert_eq!(program.workers.idle.lock().unwrap().len(), 1);
+        }
+        // A worker left running isn't returned.
+        let program = Program::compile("repeat(.)").unwrap();
+        assert_eq!(program.eval(&Value::Null).next(), Some(Ok(Value::Null)));
+        assert!(program.workers.idle.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn caught_errors_are_not_reported() {
+        let program = Program::compile(r#"try error("x") catch ., 3"#).unwrap();
+        assert_eq!(
+            run(&program, json!(null)),
+            vec![Ok(json!("x")), Ok(json!(3))]
+        );
+    }
+
+    #[test]
+    fn env_builder_binds_vars_and_args() {
+        let program = Program::compile("[$name, $n + 1, $ARGS.named.n, $ARGS.positional]").unwrap();
+        let vars = EnvBuilder::new()
+            .var("name", Value::String("qj".into()))
+            .var("n", Value::Int(41))
+            .positional([Value::Bool(true)]);
+        let out: Vec<serde_json::Value> = program
+            .eval_with_env(&Value::Null, &vars)
+            .map(|r| r.unwrap().into())
+            .collect();
+        assert_eq!(out, vec![json!(["qj", 42, 41, [true]])]);
+    }
+
+    #[test]
+    fn default_args_are_empty() {
+        let program = Program::compile("$ARGS").unwrap();
+        assert_eq!(
+            run(&program, json!(null)),
+            vec![Ok(json!({"positional": [], "named": {}}))]
+        );
+    }
+}
@@ -7,8 +7,11 @@
 //   - JxParser bundles parser + document together (document borrows parser).
 
 #include "simdjson.h"
+#include <algorithm>
 #include <cstdlib>
 #include <cstring>
+#include <unordered_map>
+#include <unordered_set>
 #include <vector>
 
 using namespace simdjson;
@@ -29,6 +32,64 @@ static int64_t utf8_codepoint_count(std::string_view sv) {
     return count;
 }
 
+/// Objects with more keys than this are checked for duplicates with a hash
+/// set; smaller ones compare every pair.
+static const size_t DUPLICATE_SCAN_LIMIT = 16;
+
+/// Drop repeated keys, keeping each key at its first position. jq keeps one
+/// entry per key (the last value, at the first key's position), so this is
+/// what `keys_unsorted` and `length` see.
+static void dedupe_keys(std::vector<std::string_view>& keys) {
+    if (keys.size() < 2) return;
+    size_t kept = 0;
+    if (keys.size() <= DUPLICATE_SCAN_LIMIT) {
+        for (size_t i = 0; i < keys.size(); i++) {
+            if (std::find(keys.begin(), keys.begin() + kept, keys[i]) == keys.begin() + kept) {
+                keys[kept++] = keys[i];
+            }
+        }
+    } else {
+        std::unordered_set<std::string_view> seen;
+        for (size_t i = 0; i < keys.size(); i++) {
+            if (seen.insert(keys[i]).second) keys[kept++] = keys[i];
+        }
+    }
+    keys.resize(kept);
+}
+
+/// Keys of a DOM object as jq sees them: duplicates dropped.
+static std::vector<std::string_view> object_keys(dom::object obj) {
+    std::vector<std::string_view> keys;
+    for (auto field : obj) {
+        keys.push_back(field.key);
+    }
+    dedupe_keys(keys);
+    return keys;
+}
+
+/// Look up `key` in a DOM object element. Like jq, a repeated key resolves to
+/// its last value. Returns false if the key is missing.
+static bool find_last_key(dom::element obj, std::string_view key, dom::element& out) {
+    bool found = false;
+    for (auto field : dom::object(obj)) {
+        if (field.key == key) {
+            out = field.value;
+            found = true;
+        }
+    }
+    return found;
+}
+
+/// Sort object keys the way jq does: by their UTF-8 bytes, which is
+/// codepoint order. Bytes compare unsigned regardless of `char` signedness,
+/// matching Rust's `str` ordering used on the non-passthrough path.
+static void sort_keys_codepoint(std::vector<std::string_view>& keys) {
+    std::sort(keys.begin(), keys.end(), [](std::string_view a, std::string_view b) {
+        int c = std::memcmp(a.data(), b.data(), std::min(a.size(), b.size()));
+        return c != 0 ? c < 0 : a.size() < b.size();
+    });
+}
+
 // Opaque handle holding both the parser and the most recent document.
 // The document borrows internal parser buffers, so they must live together.
 struct JxParser {
@@ -64,6 +125,16 @@ size_t jx_simdjson_padding() {
     return SIMDJSON_PADDING;
 }
 
+const char* jx_simdjson_version() {
+    return SIMDJSON_VERSION;
+}
+
+// Name of the kernel simdjson selected for this CPU (e.g. "haswell").
+const char* jx_simdjson_implementation() {
+    static const std::string name = get_active_implementation()->name();
+    return name.c_str();
+}
+
 // ---------------------------------------------------------------------------
 // On-Demand parsing — caller must provide a buffer with SIMDJSON_PADDING
 // extra zeroed bytes after `len`.
@@ -308,6 +379,112 @@ static void patch_u32(std::vector<uint8_t>& out, size_t pos, uint32_t v) {
 
 static const int MAX_DEPTH = 1024;
 
+// Read the key of the object entry starting at `start` (a TAG_STRING token).
+static std::string_view flat_key_at(const std::vector<uint8_t>& out, size_t start) {
+    uint32_t len = static_cast<uint32_t>(out[start + 1]) |
+                   static_cast<uint32_t>(out[start + 2]) << 8 |
+                   static_cast<uint32_t>(out[start + 3]) << 16 |
+                   static_cast<uint32_t>(out[start + 4]) << 24;
+    return std::string_view(reinterpret_cast<const char*>(out.data()) + start + 5, len);
+}
+
+// Finish a flat object whose entries start at entries[base..]: if a key
+// repeats, rewrite the entries so each key appears once, at its first
+// position with its last value (as jq does). Pops this object's entries off
+// `entries` and returns the final entry count.
+static uint32_t finish_object_entries(std::vector<uint8_t>& out,
+                                      std::vector<size_t>& entries,
+                                      size_t base) {
+    size_t n = entries.size() - base;
+    const size_t* starts = entries.data() + base;
+    bool duplicate = false;
+    if (n > 1 && n <= DUPLICATE_SCAN_LIMIT) {
+        for (size_t i = 1; i < n && !duplicate; i++) {
+            std::string_view key = flat_key_at(out, starts[i]);
+            for (size_t j = 0; j < i; j++) {
+                if (flat_key_at(out, starts[j]) == key) { duplicate = true; break; }
+            }
+        }
+    } else if (n > DUPLICATE_SCAN_LIMIT) {
+        std::unordered_set<std::string_view> seen;
+        for (size_t i = 0; i < n && !duplicate; i++) {
+            duplicate = !seen.insert(flat_key_at(out, starts[i])).second;
+        }
+    }
+    if (!duplicate) {
+        entries.resize(base);
+        return static_cast<uint32_t>(n);
+    }
+
+    // Index of the last entry for each key, then copy the entries out in
+    // first-occurrence order with the last value's bytes.
+    size_t end = out.size();
+    auto entry_end = [&](size_t i) { return i + 1 < n ? starts[i + 1] : end; };
+    std::unordered_map<std::string_view, size_t> last;
+    for (size_t i = 0; i < n; i++) {
+        last[flat_key_at(out, starts[i])] = i;
+    }
+    std::vector<uint8_t> rebuilt;
+    rebuilt.reserve(end - starts[0]);
+    uint32_t kept = 0;
+    for (size_t i = 0; i < n; i++) {
+        std::string_view key = flat_key_at(out, starts[i]);
+        auto it = last.find(key);
+        if (it == last.end()) continue; // a later duplicate, already written
+        size_t from = it->second;
+        size_t key_len = 5 + key.size();
+        rebuilt.insert(rebuilt.end(), out.begin() + starts[i], out.begin() + starts[i] + key_len);
+        rebuilt.insert(rebuilt.end(), out.begin() + starts[from] + key_len,
+                       out.begin() + entry_end(from));
+        last.erase(it);
+        kept++;
+    }
+    out.resize(starts[0]);
+    out.insert(out.end(), rebuilt.begin(), rebuilt.end());
+    entries.resize(base);
+    return kept;
+}
+
+// Whether a raw number token simdjson refused (NUMBER_ERROR) is still
+// valid JSON number syntax: `-?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?`,
+// followed by nothing but whitespace.  simdjson rejects literals whose
+// exponent overflows a double, like 1e1000, which jq reads as +/-DBL_MAX.
+static bool is_json_number(std::string_view raw) {
+    size_t i = 0, n = raw.size();
+    auto digits = [&]() {
+        size_t start = i;
+        while (i < n && raw[i] >= '0' && raw[i] <= '9') i++;
+        return i > start;
+    };
+    if (i < n && raw[i] == '-') i++;
+    if (i < n && raw[i] == '0') {
+        i++;
+    } else if (!digits()) {
+        return false;
+    }
+    if (i < n && raw[i] == '.') {
+        i++;
+        if (!digits()) return false;
+    }
+    if (i < n && (raw[i] == 'e' || raw[i] == 'E')) {
+        i++;
+        if (i < n && (raw[i] == '+' || raw[i] == '-')) i++;
+        if (!digits()) return false;
+    }
+    for (; i < n; i++) {
+        if (raw[i] != ' ' && raw[i] != '\t' && raw[i] != '\n' && raw[i] != '\r') {
+            return false;
+        }
+    }
+    return true;
+}
+
+// Whether get_number() failed on a number we can still read from its text:
+// an integer beyond u64 (BIGINT_ERROR) or an out-of-range exponent.
+static bool number_from_text(simdjson::error_code err, std::string_view raw) {
+    return err == BIGINT_ERROR || (err == NUMBER_ERROR && is_json_number(raw));
+}
+
 // Emit a number from its raw JSON token, handling the case where simdjson
 // rejects integers beyond u64 (BIGINT_ERROR).  When get_number() succeeds
 // we go through emit_number(); otherwise we fall back to strtod + raw text.
@@ -358,6 +535,7 @@ static void emit_number(std::vector<uint8_t>& out,
 }
 
 static void flatten_ondemand(std::vector<uint8_t>& out,
+                              std::vector<size_t>& entries,
                               ondemand::value val, int depth) {
     if (depth > MAX_DEPTH) {
         throw simdjson::simdjson_error(simdjson::DEPTH_ERROR);
@@ -375,7 +553,7 @@ static void flatten_ondemand(std::vector<uint8_t>& out,
         case ondemand::json_type::number: {
             std::string_view raw = val.raw_json_token();
             auto num_result = val.get_number();
-            if (num_result.error() == BIGINT_ERROR) {
+            if (number_from_text(num_result.error(), raw)) {
                 emit_number_or_bigint(out, raw);
             } else {
                 ondemand::number num = num_result.value();
@@ -393,7 +571,7 @@ static void flatten_ondemand(std::vector<uint8_t>& out,
             emit_u32(out, 0); // placeholder
             uint32_t count = 0;
             for (auto element : val.get_array()) {
-                flatten_ondemand(out, element.value(), depth + 1);
+                flatten_ondemand(out, entries, element.value(), depth + 1);
                 count++;
             }
             patch_u32(out, count_pos, count);
@@ -404,13 +582,13 @@ static void flatten_ondemand(std::vector<uint8_t>& out,
             emit_u8(out, TAG_OBJECT_START);
             size_t count_pos = out.size();
             emit_u32(out, 0); // placeholder
-            uint32_t count = 0;
+            size_t base = entries.size();
             for (auto field : val.get_object()) {
+                entries.push_back(out.size());
                 emit_string(out, field.unescaped_key().value());
-                flatten_ondemand(out, field.value(), depth + 1);
-                count++;
+                flatten_ondemand(out, entries, field.value(), depth + 1);
             }
-            patch_u32(out, count_pos, count);
+            patch_u32(out, count_pos, finish_object_entries(out, entries, base));
             emit_u8(out, TAG_OBJECT_END);
             break;
         }
@@ -440,7 +618,8 @@ int jx_dom_to_flat(const char* buf, size_t len,
         if (type == ondemand::json_type::array ||
             type == ondemand::json_type::object) {
             // Non-scalar: use get_value() + recursive flatten
-            flatten_ondemand(flat, doc.get_value().value(), 0);
+            std::vector<size_t> entries;
+            flatten_ondemand(flat, entries, doc.get_value().value(), 0);
         } else {
             // Scalar document: handle directly from document
             switch (type) {
@@ -455,7 +634,7 @@ int jx_dom_to_flat(const char* buf, size_t len,
                 case ondemand::json_type::number: {
                     std::string_view raw = doc.raw_json_token();
                     auto num_result = doc.get_number();
-                    if (num_result.error() == BIGINT_ERROR) {
+                    if (number_from_text(num_result.error(), raw)) {
                         emit_number_or_bigint(flat, raw);
                     } else {
                         ondemand::number num = num_result.value();
@@ -468,8 +647,8 @@ int jx_dom_to_flat(const char* buf, size_t len,
                     emit_string(flat, doc.get_string().value());
                     break;
                 default:
-                    emit_u8(flat, TAG_NULL);
-                    break;
+                    // Not the start of any JSON value (e.g. a stray word).
+                    return static_cast<int>(simdjson::TAPE_ERROR);
             }
         }
 
@@ -521,6 +700,7 @@ static void skip_json_string(const char*& cursor) {
 }
 
 static void walk_element(std::vector<uint8_t>& flat,
+                          std::vector<size_t>& entries,
                           dom::element elem, const char*& cursor,
                           int depth) {
     if (depth > MAX_DEPTH) {
@@ -592,7 +772,7 @@ static void walk_element(std::vector<uint8_t>& flat,
             emit_u32(flat, 0); // placeholder
             uint32_t count = 0;
             for (dom::element child : dom::array(elem)) {
-                walk_element(flat, child, cursor, depth + 1);
+                walk_element(flat, entries, child, cursor, depth + 1);
                 count++;
             }
             patch_u32(flat, count_pos, count);
@@ -608,15 +788,15 @@ static void walk_element(std::vector<uint8_t>& flat,
             emit_u8(flat, TAG_OBJECT_START);
             size_t count_pos = flat.size();
             emit_u32(flat, 0);
-            uint32_t count = 0;
+            size_t base = entries.size();
             for (auto field : dom::object(elem)) {
                 advance_cursor(cursor);
                 skip_json_string(cursor); // skip key in original JSON
+                entries.push_back(flat.size());
                 emit_string(flat, field.key);
-                walk_element(flat, field.value, cursor, depth + 1);
-                count++;
+                walk_element(flat, entries, field.value, cursor, depth + 1);
             }
-            patch_u32(flat, count_pos, count);
+            patch_u32(flat, count_pos, finish_object_entries(flat, entries, base));
             advance_cursor(cursor);
             assert(*cursor == '}');
             cursor++; // skip '}'
@@ -649,7 +829,8 @@ int jx_dom_to_flat_via_tape(const char* buf, size_t len,
         std::vector<uint8_t> flat;
         flat.reserve(len);
         const char* cursor = buf;
-        walk_element(flat, root, cursor, 0);
+        std::vector<size_t> entries;
+        walk_element(flat, entries, root, cursor, 0);
         *out_len = flat.size();
         *out_ptr = new uint8_t[flat.size()];
         std::memcpy(*out_ptr, flat.data(), flat.size());
@@ -674,6 +855,15 @@ int jx_dom_validate(const char* buf, size_t len) {
         dom::parser parser;
         dom::element root;
         auto err = parser.parse(buf, len).get(root);
+        if (err == NUMBER_ERROR || err == BIGINT_ERROR) {
+            // DOM can't hold big or out-of-range numbers; the On-Demand
+            // flattener can, and still rejects anything else malformed.
+            uint8_t* flat = nullptr;
+            size_t flat_len = 0;
+            int rc = jx_dom_to_flat(buf, len, &flat, &flat_len);
+            delete[] flat;
+            return rc;
+        }
         return static_cast<int>(err);
     } catch (...) {
         return -1;
@@ -719,8 +909,7 @@ static int navigate_fields(
     for (size_t i = 0; i < field_count; i++) {
         std::string_view key(fields[i], field_lens[i]);
         if (result.type() != dom::element_type::OBJECT) return 1;
-        auto field_err = result.at_key(key).get(result);
-        if (field_err) return 1; // field not found
+        if (!find_last_key(result, key, result)) return 1; // field not found
     }
     return 0;
 }
@@ -738,7 +927,7 @@ static void json_escape(const std::string_view sv, std::string& out) {
             case '\r': out += "\\r";  break;
             case '\t': out += "\\t";  break;
             default:
-                if (static_cast<unsigned char>(c) < 0x20) {
+                if (static_cast<unsigned char>(c) < 0x20 || c == 0x7f) {
                     char hex[8];
                     snprintf(hex, sizeof(hex), "\\u%04x", static_cast<unsigned char>(c));
                     out += hex;
@@ -750,40 +939,70 @@ static void json_escape(const std::string_view sv, std::string& out) {
     out.push_back('"');
 }
 
+
+// Trim trailing whitespace from a raw_json() result.
+static std::string_view trim_raw_json(std::string_view raw) {
+    while (!raw.empty() && (raw.back() == ' ' || raw.back() == '\n' ||
+           raw.back() == '\r' || raw.back() == '\t' || raw.back() == ','))
+        raw.remove_suffix(1);
+    return raw;
+}
+
+// Whether `field` has the name `key`: 1 if so, 0 if not, -1 if its escapes
+// don't decode. Keys are compared as written unless they hold escapes, which
+// are decoded first (`"\u0061"` names `a`).
+static int field_key_matches(ondemand::field& field, std::string_view key) {
+    std::string_view written = field.escaped_key();
+    if (written.find('\\') == std::string_view::npos) return written == key;
+    std::string_view decoded;
+    if (field.unescaped_key().get(decoded)) return -1;
+    return decoded == key;
+}
+
 // Navigate a field chain using On-Demand API, returning the raw JSON bytes
 // of the leaf value (preserves original number representation, escapes, etc.).
-// Returns: 0 = found (raw set), 1 = null (field missing), 2 = parse error.
+// Like jq, a repeated key resolves to its last value, so each object on the
+// way is read to its end. On-Demand can't go back to an earlier field, so the
+// raw bytes of the last match are kept and re-parsed for the next segment.
+// Returns: 0 = found (raw set), 1 = null (field missing or null parent),
+// 2 = parse error, 3 = a parent on the way is neither an object nor null.
 static int navigate_fields_raw(
     ondemand::parser& parser,
     const char* buf, size_t len,
     const char* const* fields, const size_t* field_lens, size_t field_count,
     std::string_view& raw)
 {
-    auto sv = padded_string_view(buf, len, len + SIMDJSON_PADDING);
-    ondemand::document doc;
-    auto err = parser.iterate(sv).get(doc);
-    if (err) return 2;
-
-    ondemand::value current;
-    // Get first field from document root.
-    {
-        std::string_view key(fields[0], field_lens[0]);
-        auto r = doc.find_field(key);
-        if (r.error()) return 1;
-        current = r.value();
-    }
-    // Navigate remaining fields.
-    for (size_t i = 1; i < field_count; i++) {
-        std::string_view key(fields[i], field_lens[i]);
+    const char* buf_end = buf + len + SIMDJSON_PADDING;
+    std::string_view current(buf, len);
+    for (size_t i = 0; i < field_count; i++) {
+        auto sv = padded_string_view(current.data(), current.size(),
+                                     buf_end - current.data());
+        ondemand::document doc;
+        if (parser.iterate(sv).get(doc)) return 2;
         ondemand::object obj;
-        if (current.get_object().get(obj)) return 1;
-        auto r = obj.find_field(key);
-        if (r.error()) return 1;
-        current = r.value();
+        if (doc.get_object().get(obj)) {
+            size_t p = 0;
+            while (p < current.size() && (current[p] == ' ' || current[p] == '\n' ||
+                   current[p] == '\r' || current[p] == '\t'))
+                p++;
+            return p < current.size() && current[p] == 'n' ? 1 : 3;
+        }
+        std::string_view key(fields[i], field_lens[i]);
+        bool found = false;
+        for (auto field_result : obj) {
+            ondemand::field field;
+            if (std::move(field_result).get(field)) return 2;
+            int matches = field_key_matches(field, key);
+            if (matches < 0) return 2;
+            if (matches) {
+                if (field.value().raw_json().get(current)) return 2;
+                found = true;
+            }
+        }
+        if (!found) return 1;
+        current = trim_raw_json(current);
     }
-    auto raw_result = current.raw_json();
-    if (raw_result.error()) return 2;
-    raw = raw_result.value();
+    raw = current;
     // On-demand parsing is lazy — it may extract syntactically invalid values
     // from malformed JSON (e.g. unquoted strings). Quick-reject by checking the
     // first byte is a valid JSON value start.
@@ -797,14 +1016,6 @@ static int navigate_fields_raw(
     return 0;
 }
 
-// Trim trailing whitespace from a raw_json() result.
-static std::string_view trim_raw_json(std::string_view raw) {
-    while (!raw.empty() && (raw.back() == ' ' || raw.back() == '\n' ||
-           raw.back() == '\r' || raw.back() == '\t' || raw.back() == ','))
-        raw.remove_suffix(1);
-    return raw;
-}
-
 int jx_dom_find_field_raw(
     const char* buf, size_t len,
     const char** fields, const size_t* field_lens, size_t field_count,
@@ -815,7 +1026,7 @@ int jx_dom_find_field_raw(
         std::string_view raw;
         int nav = navigate_fields_raw(parser, buf, len, fields, field_lens, field_count, raw);
         if (nav == 2) return -1; // parse error
-        if (nav == 1) {
+        if (nav == 1 || nav == 3) {
             *out_ptr = new char[4];
             std::memcpy(*out_ptr, "null", 4);
             *out_len = 4;
@@ -829,6 +1040,47 @@ int jx_dom_find_field_raw(
     } catch (...) { return -1; }
 }
 
+// ---------------------------------------------------------------------------
+// DOM field chain — navigate fields, then minify the sub-tree found.
+//
+// Return codes via *out_len:
+//   >= 0 : success (minified JSON in *out_ptr; "null" for a missing field)
+//   -2   : a parent on the way is a scalar or array, which jq reports as an
+//          error (caller should fall back to normal pipeline)
+// Function return: 0 = success, -1 = error.
+// ---------------------------------------------------------------------------
+
+int jx_dom_field_chain(
+    const char* buf, size_t len,
+    const char** fields, const size_t* field_lens, size_t field_count,
+    char** out_ptr, size_t* out_len)
+{
+    try {
+        ondemand::parser parser;
+        std::string_view raw;
+        int nav = navigate_fields_raw(parser, buf, len, fields, field_lens, field_count, raw);
+        if (nav == 2) return -1; // parse error
+        if (nav == 3) {
+            *out_ptr = nullptr;
+            *out_len = static_cast<size_t>(-2);
+            return 0;
+        }
+        if (nav == 1) {
+            *out_ptr = new char[4];
+            std::memcpy(*out_ptr, "null", 4);
+            *out_len = 4;
+            return 0;
+        }
+        char* dst = new char[raw.size()];  // minified output is always <= input
+        size_t dst_len;
+        auto err = simdjson::minify(raw.data(), raw.size(), dst, dst_len);
+        if (err) { delete[] dst; return static_cast<int>(err); }
+        *out_ptr = dst;
+        *out_len = dst_len;
+        return 0;
+    } catch (...) { return -1; }
+}
+
 // ---------------------------------------------------------------------------
 // DOM field + length — navigate fields, then compute length.
 //
@@ -849,7 +1101,14 @@ int jx_dom_field_length(
         int nav = navigate_fields(buf, len, fields, field_lens, field_count, parser, result);
         if (nav == 2) return -1; // parse error
         if (nav == 1) {
-            // null → length 0
+            // Indexing a scalar on the way errors in jq: fall back.
+            if (result.type() != dom::element_type::OBJECT &&
+                result.type() != dom::element_type::NULL_VALUE) {
+                *out_ptr = nullptr;
+                *out_len = static_cast<size_t>(-2);
+                return 0;
+            }
+            // missing field or null → length 0
             *out_ptr = new char[1];
             (*out_ptr)[0] = '0';
             *out_len = 1;
@@ -862,7 +1121,7 @@ int jx_dom_field_length(
                 length = static_cast<int64_t>(dom::array(result).size());
                 break;
             case dom::element_type::OBJECT:
-                length = static_cast<int64_t>(dom::object(result).size());
+                length = static_cast<int64_t>(object_keys(dom::object(result)).size());
                 break;
             case dom::element_type::STRING:
                 length = utf8_codepoint_count(result.get_string().value());
@@ -914,11 +1173,8 @@ int jx_dom_field_keys(
             case dom::element_type::OBJECT: {
                 dom::object obj = dom::object(result);
                 // Collect keys
-                std::vector<std::string_view> keys;
-                for (auto field : obj) {
-                    keys.push_back(field.key);
-                }
-                if (sorted) std::sort(keys.begin(), keys.end());
+                std::vector<std::string_view> keys = object_keys(obj);
+                if (sorted) sort_keys_codepoint(keys);
                 // Build JSON array string
                 std::string s;
                 s.push_back('[');
@@ -990,8 +1246,7 @@ int jx_dom_find_fields_raw(
             for (size_t j = 0; j < chain_counts[i]; j++) {
                 std::string_view key(chains[i][j], chain_lens[i][j]);
                 if (cur.type() != dom::element_type::OBJECT) { found = false; break; }
-                auto field_err = cur.at_key(key).get(cur);
-                if (field_err) { found = false; break; }
+                if (!find_last_key(cur, key, cur)) { found = false; break; }
             }
 
             std::string s;
@@ -1033,10 +1288,13 @@ void jx_dom_parser_free(JxDomParser* p) {
     delete p;
 }
 
-int jx_dom_find_field_raw_reuse(
+// Shared by the reusable field lookups below. With `strict`, a value along
+// the chain that is neither an object nor null returns -2 instead of "null".
+static int find_field_raw_reuse(
     JxDomParser* p,
     const char* buf, size_t len,
     const char** fields, const size_t* field_lens, size_t field_count,
+    bool strict,
     char** out_ptr, size_t* out_len)
 {
     try {
@@ -1044,7 +1302,8 @@ int jx_dom_find_field_raw_reuse(
         int nav = navigate_fields_raw(p->ondemand, buf, len,
                                        fields, field_lens, field_count, raw);
         if (nav == 2) return -1;
-        if (nav == 1) {
+        if (nav == 3 && strict) return -2;
+        if (nav == 1 || nav == 3) {
             *out_ptr = new char[4];
             std::memcpy(*out_ptr, "null", 4);
             *out_len = 4;
@@ -1058,6 +1317,29 @@ int jx_dom_find_field_raw_reuse(
     } catch (...) { return -1; }
 }
 
+int jx_dom_find_field_raw_reuse(
+    JxDomParser* p,
+    const char* buf, size_t len,
+    const char** fields, const size_t* field_lens, size_t field_count,
+    char** out_ptr, size_t* out_len)
+{
+    return find_field_raw_reuse(p, buf, len, fields, field_lens, field_count,
+                                false, out_ptr, out_len);
+}
+
+// Like jx_dom_find_field_raw_reuse, but returns -2 when indexing would be an
+// error in jq (a string, number, boolean or array along the chain), so the
+// caller can leave the line to the evaluator.
+int jx_dom_field_chain_raw_reuse(
+    JxDomParser* p,
+    const char* buf, size_t len,
+    const char** fields, const size_t* field_lens, size_t field_count,
+    char** out_ptr, size_t* out_len)
+{
+    return find_field_raw_reuse(p, buf, len, fields, field_lens, field_count,
+                                true, out_ptr, out_len);
+}
+
 int jx_dom_find_fields_raw_reuse(
     JxDomParser* p,
     const char* buf, size_t len,
@@ -1113,9 +1395,7 @@ int jx_dom_field_length_reuse(
             case dom::element_type::OBJECT: {
                 dom::object obj;
                 if (result.get(obj)) return -2;
-                size_t count = 0;
-                for (auto kv : obj) { (void)kv; count++; }
-                s = std::to_string(count);
+                s = std::to_string(object_keys(obj).size());
                 break;
             }
             case dom::element_type::ARRAY: {
@@ -1158,11 +1438,8 @@ int jx_dom_field_keys_reuse(
             case dom::element_type::OBJECT: {
                 dom::object obj;
                 if (result.get(obj)) return -2;
-                std::vector<std::string_view> keys;
-                for (auto kv : obj) {
-                    keys.push_back(kv.key);
-                }
-                if (sorted) std::sort(keys.begin(), keys.end());
+                std::vector<std::string_view> keys = object_keys(obj);
+                if (sorted) sort_keys_codepoint(keys);
                 s = "[";
                 for (size_t i = 0; i < keys.size(); i++) {
                     if (i > 0) s += ",";
@@ -1220,8 +1497,7 @@ int jx_dom_array_map_field(
         for (size_t i = 0; i < prefix_count; i++) {
             std::string_view key(prefix[i], prefix_lens[i]);
             if (target.type() != dom::element_type::OBJECT) return -2;
-            auto field_err = target.at_key(key).get(target);
-            if (field_err) return -2;
+            if (!find_last_key(target, key, target)) return -2;
         }
         if (target.type() != dom::element_type::ARRAY) return -2;
 
@@ -1244,14 +1520,15 @@ int jx_dom_array_map_field(
             }
             if (elem.type() != dom::element_type::OBJECT) return -2;
 
-            // Navigate field chain within this element
+            // Navigate field chain within this element. Indexing anything
+            // but an object or null is an error: leave it to the evaluator.
             dom::element cur = elem;
             bool found = true;
             for (size_t i = 0; i < field_count; i++) {
                 std::string_view key(fields[i], field_lens[i]);
-                if (cur.type() != dom::element_type::OBJECT) { found = false; break; }
-                auto field_err = cur.at_key(key).get(cur);
-                if (field_err) { found = false; break; }
+                if (cur.type() == dom::element_type::NULL_VALUE) { found = false; break; }
+                if (cur.type() != dom::element_type::OBJECT) return -2;
+                if (!find_last_key(cur, key, cur)) { found = false; break; }
             }
 
             if (found) {
@@ -1303,8 +1580,7 @@ int jx_dom_array_map_fields_obj(
         for (size_t i = 0; i < prefix_count; i++) {
             std::string_view key(prefix[i], prefix_lens[i]);
             if (target.type() != dom::element_type::OBJECT) return -2;
-            auto field_err = target.at_key(key).get(target);
-            if (field_err) return -2;
+            if (!find_last_key(target, key, target)) return -2;
         }
         if (target.type() != dom::element_type::ARRAY) return -2;
 
@@ -1337,7 +1613,7 @@ int jx_dom_array_map_fields_obj(
                     // Extract field value
                     std::string_view field_name(fields[i], field_lens[i]);
                     dom::element val;
-                    bool found = !elem.at_key(field_name).get(val);
+                    bool found = find_last_key(elem, field_name, val);
                     if (found) {
                         out += simdjson::to_string(val);
                     } else {
@@ -1441,8 +1717,7 @@ int jx_dom_array_map_builtin(
         for (size_t i = 0; i < prefix_count; i++) {
             std::string_view key(prefix[i], prefix_lens[i]);
             if (target.type() != dom::element_type::OBJECT) return -2;
-            auto field_err = target.at_key(key).get(target);
-            if (field_err) return -2;
+            if (!find_last_key(target, key, target)) return -2;
         }
         if (target.type() != dom::element_type::ARRAY) return -2;
 
@@ -1462,7 +1737,7 @@ int jx_dom_array_map_builtin(
                 case 0: { // length
                     switch (elem.type()) {
                         case dom::element_type::OBJECT:
-                            out += std::to_string(dom::object(elem).size());
+                            out += std::to_string(object_keys(dom::object(elem)).size());
                             break;
                         case dom::element_type::ARRAY:
                             out += std::to_string(dom::array(elem).size());
@@ -1477,7 +1752,7 @@ int jx_dom_array_map_builtin(
                             break;
                         }
                         case dom::element_type::NULL_VALUE:
-                            out += "null";
+                            out += "0";
                             break;
                         default:
                             return -2;
@@ -1488,9 +1763,8 @@ int jx_dom_array_map_builtin(
                     switch (elem.type()) {
                         case dom::element_type::OBJECT: {
                             dom::object obj = dom::object(elem);
-                            std::vector<std::string_view> keys;
-                            for (auto field : obj) keys.push_back(field.key);
-                            if (sorted) std::sort(keys.begin(), keys.end());
+                            std::vector<std::string_view> keys = object_keys(obj);
+                            if (sorted) sort_keys_codepoint(keys);
                             out.push_back('[');
                             for (size_t i = 0; i < keys.size(); i++) {
                                 if (i > 0) out.push_back(',');
@@ -7,7 +7,7 @@ use std::sync::Arc;
 use crate::value::Value;
 
 use super::ffi::*;
-use super::types::{check, padding};
+use super::types::{check, pad_buffer, padding};
 
 /// simdjson CAPACITY error code — returned when input exceeds ~4GB single-document limit.
 pub const SIMDJSON_CAPACITY: i32 = 1;
@@ -25,6 +25,10 @@ pub(crate) const TAG_OBJECT_END: u8 = 8;
 
 /// Parse a JSON buffer via simdjson DOM API and return a `Value` tree.
 ///
+/// Unpaired surrogate escapes (`"\udead"`) make simdjson fail; like jq, we then
+/// retry with each one replaced by U+FFFD. The same applies to the flat-buffer
+/// parsers below.
+///
 /// `buf` must include SIMDJSON_PADDING extra zeroed bytes after `json_len`.
 pub fn dom_parse_to_value(buf: &[u8], json_len: usize) -> Result<Value> {
     assert!(buf.len() >= json_len + padding());
@@ -37,7 +41,14 @@ pub fn dom_parse_to_value(buf: &[u8], json_len: usize) -> Result<Value> {
     // Uses On-Demand path (not DOM tape walk) because this function is called from
     // fromjson with arbitrary user strings. The DOM parser may not handle all
     // malformed inputs the same way as On-Demand (different error propagation).
-    check(unsafe { jx_dom_to_flat(buf.as_ptr().cast(), json_len, &mut flat_ptr, &mut flat_len) })?;
+    let code =
+        unsafe { jx_dom_to_flat(buf.as_ptr().cast(), json_len, &mut flat_ptr, &mut flat_len) };
+    if code != 0
+        && let Some(fixed) = crate::input::replace_lone_surrogates(&buf[..json_len])
+    {
+        return dom_parse_to_value(&pad_buffer(&fixed), json_len);
+    }
+    check(code)?;
 
     // SAFETY: flat_ptr was heap-allocated by jx_dom_to_flat above and flat_len is
     // its byte count. We decode into a Value tree immediately; the pointer is freed
@@ -123,7 +134,14 @@ pub fn dom_parse_to_flat_buf(buf: &[u8], json_len: usize) -> Result<FlatBuffer>
     // SAFETY: buf points to a valid buffer with json_len + SIMDJSON_PADDING bytes
     // (asserted above). flat_ptr/flat_len are valid stack references used as output
     // parameters. C++ heap-allocates the flat token buffer.
-    check(unsafe { jx_dom_to_flat(buf.as_ptr().cast(), json_len, &mut flat_ptr, &mut flat_len) })?;
+    let code =
+        unsafe { jx_dom_to_flat(buf.as_ptr().cast(), json_len, &mut flat_ptr, &mut flat_len) };
+    if code != 0
+        && let Some(fixed) = crate::input::replace_lone_surrogates(&buf[..json_len])
+    {
+        return dom_parse_to_flat_buf(&pad_buffer(&fixed), json_len);
+    }
+    check(code)?;
     Ok(FlatBuffer::from_raw(flat_ptr, flat_len))
 }
 
@@ -138,9 +156,15 @@ pub fn dom_parse_to_flat_buf_tape(buf: &[u8], json_len: usize) -> Result<FlatBuf
     assert!(buf.len() >= json_len + padding());
     let mut flat_ptr: *mut u8 = std::ptr::null_mut();
     let mut flat_len: usize = 0;
-    check(unsafe {
+    let code = unsafe {
         jx_dom_to_flat_via_tape(buf.as_ptr().cast(), json_len, &mut flat_ptr, &mut flat_len)
-    })?;
+    };
+    if code != 0
+        && let Some(fixed) = crate::input::replace_lone_surrogates(&buf[..json_len])
+    {
+        return dom_parse_to_flat_buf_tape(&pad_buffer(&fixed), json_len);
+    }
+    check(code)?;
     Ok(FlatBuffer::from_raw(flat_ptr, flat_len))
 }
 
@@ -354,6 +378,50 @@ pub fn dom_find_field_raw(buf: &[u8], json_len: usize, fields: &[&str]) -> Resul
     Ok(result)
 }
 
+/// Navigate a chain of field names and return the minified JSON of the
+/// sub-tree found, for `.a.b.c` with `-c`.
+///
+/// `buf` must include SIMDJSON_PADDING extra zeroed bytes after `json_len`.
+/// A missing field or a null parent gives `b"null"`, as in jq. Returns
+/// `Ok(None)` when a parent on the way is a scalar or array: jq reports an
+/// error there, so the caller should fall back to the evaluator.
+pub fn dom_field_chain(buf: &[u8], json_len: usize, fields: &[&str]) -> Result<Option<Vec<u8>>> {
+    assert!(
+        buf.len() >= json_len + padding(),
+        "buffer must include SIMDJSON_PADDING extra bytes"
+    );
+    let field_ptrs: Vec<*const c_char> = fields.iter().map(|f| f.as_ptr().cast()).collect();
+    let field_lens: Vec<usize> = fields.iter().map(|f| f.len()).collect();
+    let mut out_ptr: *mut c_char = std::ptr::null_mut();
+    let mut out_len: usize = 0;
+    // SAFETY: buf points to a valid buffer with json_len + SIMDJSON_PADDING bytes
+    // (asserted above). field_ptrs/field_lens point to valid slices matching
+    // fields.len(). out_ptr/out_len are valid stack references. C++ heap-allocates
+    // the result.
+    check(unsafe {
+        jx_dom_field_chain(
+            buf.as_ptr().cast(),
+            json_len,
+            field_ptrs.as_ptr(),
+            field_lens.as_ptr(),
+            fields.len(),
+            &mut out_ptr,
+            &mut out_len,
+        )
+    })?;
+    if out_len == usize::MAX - 1 {
+        return Ok(None);
+    }
+    // SAFETY: out_ptr was heap-allocated by jx_dom_field_chain above and out_len
+    // is its byte count. We copy into a Vec immediately; the pointer is freed on the
+    // next line.
+    let result = unsafe { std::slice::from_raw_parts(out_ptr.cast::<u8>(), out_len) }.to_vec();
+    // SAFETY: out_ptr was allocated by C++ new[] in jx_dom_field_chain and has not
+    // been freed yet. After this call the pointer is not used again.
+    unsafe { jx_minify_free(out_ptr) };
+    Ok(Some(result))
+}
+
 /// DOM parse, navigate fields, and compute `length` in C++.
 ///
 /// Returns `Ok(Some(bytes))` on success (decimal string), `Ok(None)` if the
@@ -749,6 +817,48 @@ impl DomParser {
         Ok(result)
     }
 
+    /// Like [`find_field_raw`](Self::find_field_raw), but `None` when a value
+    /// along the chain is neither an object nor null, which jq reports as an
+    /// error rather than `null`.
+    pub fn field_chain_raw(
+        &mut self,
+        buf: &[u8],
+        json_len: usize,
+        fields: &[&str],
+    ) -> Result<Option<Vec<u8>>> {
+        assert!(
+            buf.len() >= json_len + padding(),
+            "buffer must include SIMDJSON_PADDING extra bytes"
+        );
+        let ptrs: Vec<*const c_char> = fields.iter().map(|f| f.as_ptr().cast::<c_char>()).collect();
+        let lens: Vec<usize> = fields.iter().map(|f| f.len()).collect();
+
+        let mut out_ptr: *mut c_char = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+
+        let rc = unsafe {
+            jx_dom_field_chain_raw_reuse(
+                self.ptr,
+                buf.as_ptr().cast(),
+                json_len,
+                ptrs.as_ptr(),
+                lens.as_ptr(),
+                fields.len(),
+                &mut out_ptr,
+                &mut out_len,
+            )
+        };
+
+        if rc == -2 {
+            return Ok(None);
+        }
+        check(rc)?;
+
+        let result = unsafe { std::slice::from_raw_parts(out_ptr.cast::<u8>(), out_len) }.to_vec();
+        unsafe { jx_minify_free(out_ptr) };
+        Ok(Some(result))
+    }
+
     /// Batch extract N field chains as raw JSON bytes.
     pub fn find_fields_raw(
         &mut self,
@@ -948,20 +1058,16 @@ impl Drop for DomParser {
 // ---------------------------------------------------------------------------
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::super::types::pad_buffer;
     use super::*;
 
     #[test]
     fn parse_deeply_nested_arrays() {
-        let mut json = Vec::new();
-        for _ in 0..1100 {
-            json.push(b'[');
-        }
+        let mut json = vec![b'['; 1100];
         json.push(b'1');
-        for _ in 0..1100 {
-            json.push(b']');
-        }
+        json.resize(json.len() + 1100, b']');
         let buf = pad_buffer(&json);
         assert!(dom_parse_to_value(&buf, json.len()).is_err());
     }
@@ -973,9 +1079,7 @@ mod tests {
             json.extend_from_slice(format!("{{\"k{}\":", i).as_bytes());
         }
         json.extend_from_slice(b"null");
-        for _ in 0..1100 {
-            json.push(b'}');
-        }
+        json.resize(json.len() + 1100, b'}');
         let buf = pad_buffer(&json);
         assert!(dom_parse_to_value(&buf, json.len()).is_err());
     }
@@ -1003,14 +1107,9 @@ mod tests {
 
     #[test]
     fn dom_parse_deeply_nested() {
-        let mut json = Vec::new();
-        for _ in 0..1100 {
-            json.push(b'[');
-        }
+        let mut json = vec![b'['; 1100];
         json.push(b'1');
-        for _ in 0..1100 {
-            json.push(b']');
-        }
+        json.resize(json.len() + 1100, b']');
         let buf = pad_buffer(&json);
         assert!(dom_parse_to_value(&buf, json.len()).is_err());
     }
@@ -1265,6 +1364,36 @@ mod tests {
         assert_eq!(std::str::from_utf8(&out).unwrap(), "null");
     }
 
+    #[test]
+    fn field_chain_minifies_sub_tree() {
+        let json = b"{\"a\": {\"b\": [1, {\"c\" : \"x y\"}],\n \"d\": 2}}";
+        let buf = pad_buffer(json);
+        let out = dom_field_chain(&buf, json.len(), &["a", "b"]).unwrap();
+        assert_eq!(out.as_deref(), Some(&br#"[1,{"c":"x y"}]"#[..]));
+        let out = dom_field_chain(&buf, json.len(), &["a", "missing"]).unwrap();
+        assert_eq!(out.as_deref(), Some(&b"null"[..]));
+    }
+
+    #[test]
+    fn field_chain_through_null_and_scalars() {
+        let json = br#"{"n":null,"s":"x","a":[1]}"#;
+        let buf = pad_buffer(json);
+        let out = dom_field_chain(&buf, json.len(), &["n", "x"]).unwrap();
+        assert_eq!(out.as_deref(), Some(&b"null"[..]));
+        // jq errors on these; the caller falls back.
+        assert_eq!(
+            dom_field_chain(&buf, json.len(), &["s", "x"]).unwrap(),
+            None
+        );
+        assert_eq!(
+            dom_field_chain(&buf, json.len(), &["a", "x"]).unwrap(),
+            None
+        );
+        let json = b" [1]";
+        let buf = pad_buffer(json);
+        assert_eq!(dom_field_chain(&buf, json.len(), &["x"]).unwrap(), None);
+    }
+
     #[test]
     fn field_raw_int_value() {
         let json = br#"{"count":42}"#;
@@ -1843,7 +1972,7 @@ mod tests {
         for _ in 0..depth {
             json.push_str(r#"{"x":"#);
         }
-        json.push_str("1");
+        json.push('1');
         for _ in 0..depth {
             json.push('}');
         }
@@ -2393,6 +2522,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn array_map_builtin_unsupported_element_returns_none() {
+        // jq errors on these; the evaluator reports it.
+        for (json, op) in [(&b"[{},true]"[..], 0), (b"[{},null]", 1), (b"[{},3]", 3)] {
+            let buf = pad_buffer(json);
+            let out = dom_array_map_builtin(&buf, json.len(), &[], op, true, "a", true).unwrap();
+            assert!(out.is_none(), "op {op}");
+        }
+    }
+
     #[test]
     fn array_map_builtin_non_array_returns_none() {
         let json = br#"{"a":1}"#;
@@ -2424,10 +2563,8 @@ mod tests {
         let buf = pad_buffer(json);
         let out = dom_array_map_builtin(&buf, json.len(), &[], 0, true, "", true).unwrap();
         let s = std::str::from_utf8(out.as_ref().unwrap()).unwrap();
-        // NOTE: jq returns [0,2,2] (null|length=0), but the C++ bridge returns
-        // null for length of null. This divergence is handled at the Rust eval
-        // layer when the passthrough result is used.
-        assert_eq!(s, "[null,2,2]");
+        // null | length is 0, as in jq
+        assert_eq!(s, "[0,2,2]");
     }
 
     #[test]
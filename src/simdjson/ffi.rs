@@ -18,6 +18,8 @@ unsafe extern "C" {
     pub(super) fn jx_parser_new() -> *mut JxParser;
     pub(super) fn jx_parser_free(p: *mut JxParser);
     pub(super) fn jx_simdjson_padding() -> usize;
+    pub(super) fn jx_simdjson_version() -> *const c_char;
+    pub(super) fn jx_simdjson_implementation() -> *const c_char;
 
     pub(super) fn jx_parse_ondemand(p: *mut JxParser, buf: *const c_char, len: usize) -> i32;
 
@@ -91,6 +93,16 @@ unsafe extern "C" {
         out_len: *mut usize,
     ) -> i32;
 
+    pub(super) fn jx_dom_field_chain(
+        buf: *const c_char,
+        len: usize,
+        fields: *const *const c_char,
+        field_lens: *const usize,
+        field_count: usize,
+        out_ptr: *mut *mut c_char,
+        out_len: *mut usize,
+    ) -> i32;
+
     pub(super) fn jx_dom_field_length(
         buf: *const c_char,
         len: usize,
@@ -128,6 +140,17 @@ unsafe extern "C" {
         out_len: *mut usize,
     ) -> i32;
 
+    pub(super) fn jx_dom_field_chain_raw_reuse(
+        p: *mut JxDomParser,
+        buf: *const c_char,
+        len: usize,
+        fields: *const *const c_char,
+        field_lens: *const usize,
+        field_count: usize,
+        out_ptr: *mut *mut c_char,
+        out_len: *mut usize,
+    ) -> i32;
+
     pub(super) fn jx_dom_find_fields_raw_reuse(
         p: *mut JxDomParser,
         buf: *const c_char,
@@ -5,7 +5,7 @@
 //! thread-safe. Each thread in the parallel NDJSON pipeline gets its own parser.
 
 use anyhow::{Result, bail};
-use std::ffi::c_char;
+use std::ffi::{CStr, c_char};
 use std::fs;
 use std::ops::Deref;
 use std::path::Path;
@@ -17,6 +17,23 @@ pub fn padding() -> usize {
     unsafe { jx_simdjson_padding() }
 }
 
+/// The simdjson release qj is built against.
+pub fn version() -> &'static str {
+    // SAFETY: a NUL-terminated string literal in the bridge.
+    unsafe { CStr::from_ptr(jx_simdjson_version()) }
+        .to_str()
+        .unwrap_or("unknown")
+}
+
+/// The SIMD kernel simdjson picked for this CPU at runtime, e.g. `haswell`
+/// or `arm64`.
+pub fn active_implementation() -> &'static str {
+    // SAFETY: a NUL-terminated string in a static that lives for the process.
+    unsafe { CStr::from_ptr(jx_simdjson_implementation()) }
+        .to_str()
+        .unwrap_or("unknown")
+}
+
 /// Read a file into a Vec with SIMDJSON_PADDING extra zeroed bytes at the end.
 pub fn read_padded(path: &Path) -> Result<Vec<u8>> {
     let data = fs::read(path)?;
@@ -336,6 +353,7 @@ pub fn iterate_many_extract_field(
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::*;
 
@@ -1,5 +1,7 @@
 use std::sync::{Arc, LazyLock};
 
+pub mod bigint;
+
 /// Maximum integer magnitude that f64 can represent exactly: 2^53.
 /// Beyond this, f64 loses precision on individual integers.
 const F64_INT_MAX: i64 = 1i64 << 53;
@@ -43,7 +45,8 @@ pub enum Value {
     Int(i64),
     /// f64 value + optional raw JSON text for literal preservation.
     /// `Some("75.80")` preserves the original formatting from JSON input.
-    /// `None` for computed values (arithmetic, filter literals).
+    /// `None` for computed values (arithmetic, most filter literals). Integers
+    /// beyond i64 keep their digits here and stay exact (see [`bigint`]).
     Double(f64, Option<Box<str>>),
     String(String),
     Array(Arc<Vec<Value>>),
@@ -51,14 +54,16 @@ pub enum Value {
 }
 
 /// PartialEq ignores the raw-text field on Double — two Doubles with the
-/// same f64 are equal regardless of original formatting.
+/// same f64 are equal regardless of original formatting — except that big
+/// integer literals compare exactly (see [`bigint`]).
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Null, Value::Null) => true,
             (Value::Bool(a), Value::Bool(b)) => a == b,
             (Value::Int(a), Value::Int(b)) => a == b,
-            (Value::Double(a, _), Value::Double(b, _)) => a == b,
+            (Value::Double(a, _), Value::Double(b, _)) => bigint::exact_cmp(self, other)
+                .map_or(a == b, |ord| ord == std::cmp::Ordering::Equal),
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Array(a), Value::Array(b)) => a == b,
             (Value::Object(a), Value::Object(b)) => a == b,
@@ -92,8 +97,20 @@ impl Value {
             Value::Null => "null".to_string(),
             Value::Bool(b) => format!("{b}"),
             Value::Int(n) => format!("{n}"),
+            // jq dumps NaN as null and infinities as the largest finite double
+            Value::Double(f, _) if f.is_nan() => "null".to_string(),
+            Value::Double(f, _) if f.is_infinite() => {
+                let dump = format!("{}1.7976931348623157e+308", if *f < 0.0 { "-" } else { "" });
+                format!("{}...", &dump[..11])
+            }
             Value::Double(f, _) => format!("{f}"),
             Value::String(s) => {
+                // Escaped as in JSON output, so control characters show as
+                // `\n`, `\u0000` and so on.
+                let mut buf = Vec::new();
+                crate::output::write_json_string(&mut buf, s).unwrap();
+                let dump = String::from_utf8(buf).unwrap_or_default();
+                let s = &dump[1..dump.len() - 1];
                 if s.len() > 10 {
                     // Truncate at ~10 bytes, aligned to char boundaries (matches jq)
                     let mut end = 10;
@@ -147,7 +164,41 @@ impl From<serde_json::Value> for Value {
     }
 }
 
+/// Numbers that JSON cannot represent follow jq's output rules: NaN becomes
+/// null and infinities clamp to the largest finite double.
+impl From<Value> for serde_json::Value {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(b),
+            Value::Int(n) => serde_json::Value::Number(n.into()),
+            Value::Double(f, _) => {
+                let f = if f.is_infinite() {
+                    f64::MAX.copysign(f)
+                } else {
+                    f
+                };
+                serde_json::Number::from_f64(f).map_or(serde_json::Value::Null, Into::into)
+            }
+            Value::String(s) => serde_json::Value::String(s),
+            Value::Array(a) => serde_json::Value::Array(
+                Arc::unwrap_or_clone(a)
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            ),
+            Value::Object(o) => serde_json::Value::Object(
+                Arc::unwrap_or_clone(o)
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::approx_constant)] // 3.14 literals are JSON test data
 mod tests {
     use super::*;
 
@@ -173,4 +224,18 @@ mod tests {
         assert!(Value::Array(Arc::new(vec![])).is_truthy());
         assert!(Value::Object(Arc::new(vec![])).is_truthy());
     }
+
+    #[test]
+    fn to_serde_json() {
+        let json = serde_json::json!({"a": [1, 2.5, null, true, "s"], "b": {}});
+        assert_eq!(serde_json::Value::from(Value::from(json.clone())), json);
+        assert_eq!(
+            serde_json::Value::from(Value::Double(f64::NAN, None)),
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            serde_json::Value::from(Value::Double(f64::NEG_INFINITY, None)),
+            serde_json::json!(-f64::MAX)
+        );
+    }
 }
@@ -0,0 +1,367 @@
+//! Exact integers beyond the i64 range.
+//!
+//! Integer literals too large for `Value::Int` are stored as `Value::Double`
+//! with their raw text, so they print unchanged. [`BigInt`] reads that text
+//! back as an exact integer so comparisons and `+`/`-`/`*` between integers
+//! don't round through f64; [`BigInt::into_value`] stores results the same
+//! way.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+use super::Value;
+
+/// Limb base: each limb holds nine decimal digits.
+const BASE: u64 = 1_000_000_000;
+
+/// Products longer than this many limbs are left to f64 (which overflows to
+/// infinity long before this) rather than growing without bound.
+const MAX_LIMBS: usize = 128;
+
+/// Sign and magnitude, with the magnitude in little-endian base-10⁹ limbs.
+/// Zero has no limbs and is never negative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+/// Whether `v` is an integer literal outside the i64 range, i.e. a number
+/// whose raw text carries more precision than its f64.
+pub fn is_big_literal(v: &Value) -> bool {
+    match v {
+        Value::Double(_, Some(raw)) => is_big_integer_text(raw),
+        _ => false,
+    }
+}
+
+/// Raw number text that is an integer outside the i64 range.
+pub fn is_big_integer_text(text: &str) -> bool {
+    is_integer_text(text) && text.parse::<i64>().is_err()
+}
+
+/// Exact ordering of two numbers when at least one is a big integer literal
+/// and both are integers. `None` means the caller should compare as f64.
+pub fn exact_cmp(a: &Value, b: &Value) -> Option<Ordering> {
+    if !is_big_literal(a) && !is_big_literal(b) {
+        return None;
+    }
+    Some(BigInt::from_value(a)?.cmp(&BigInt::from_value(b)?))
+}
+
+fn is_integer_text(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl BigInt {
+    /// Parse an optionally negative run of decimal digits.
+    pub fn parse(text: &str) -> Option<BigInt> {
+        if !is_integer_text(text) {
+            return None;
+        }
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let mut limbs = Vec::with_capacity(digits.len() / 9 + 1);
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(9);
+            limbs.push(digits[start..end].parse().ok()?);
+            end = start;
+        }
+        Some(BigInt::new(negative, limbs))
+    }
+
+    pub fn from_i64(n: i64) -> BigInt {
+        let mut magnitude = n.unsigned_abs();
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push((magnitude % BASE) as u32);
+            magnitude /= BASE;
+        }
+        BigInt::new(n < 0, limbs)
+    }
+
+    /// The exact integer a value holds: any `Int`, or a `Double` whose raw
+    /// text is an integer literal. Computed doubles have no exact form.
+    pub fn from_value(v: &Value) -> Option<BigInt> {
+        match v {
+            Value::Int(n) => Some(BigInt::from_i64(*n)),
+            Value::Double(_, Some(raw)) => BigInt::parse(raw),
+            _ => None,
+        }
+    }
+
+    /// `Int` when it fits, otherwise a `Double` carrying the exact text.
+    pub fn into_value(self) -> Value {
+        if let Some(n) = self.to_i64() {
+            return Value::Int(n);
+        }
+        let text = self.to_string();
+        let f = text.parse().unwrap_or(f64::NAN);
+        Value::Double(f, Some(text.into_boxed_str()))
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 3 {
+            return None;
+        }
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0i128, |acc, &limb| acc * BASE as i128 + limb as i128);
+        i64::try_from(if self.negative { -magnitude } else { magnitude }).ok()
+    }
+
+    /// `None` if the product would be unreasonably large.
+    pub fn checked_mul(&self, other: &BigInt) -> Option<BigInt> {
+        if self.limbs.len() + other.limbs.len() > MAX_LIMBS {
+            return None;
+        }
+        let mut product = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let cur = product[i + j] + a as u64 * b as u64 + carry;
+                product[i + j] = cur % BASE;
+                carry = cur / BASE;
+            }
+            product[i + other.limbs.len()] += carry;
+        }
+        let limbs = product.into_iter().map(|limb| limb as u32).collect();
+        Some(BigInt::new(self.negative != other.negative, limbs))
+    }
+
+    fn new(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match cmp_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Equal => BigInt::new(false, Vec::new()),
+            Ordering::Greater => {
+                BigInt::new(self.negative, sub_magnitudes(&self.limbs, &other.limbs))
+            }
+            Ordering::Less => {
+                BigInt::new(other.negative, sub_magnitudes(&other.limbs, &self.limbs))
+            }
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.limbs.clone())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => cmp_magnitudes(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((top, rest)) = self.limbs.split_last() else {
+            return f.write_str("0");
+        };
+        if self.negative {
+            f.write_str("-")?;
+        }
+        write!(f, "{top}")?;
+        for limb in rest.iter().rev() {
+            write!(f, "{limb:09}")?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let cur = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push((cur % BASE) as u32);
+        carry = cur / BASE;
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+    sum
+}
+
+/// `a - b` for magnitudes with `a >= b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut diff = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut cur = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = i64::from(cur < 0);
+        if cur < 0 {
+            cur += BASE as i64;
+        }
+        diff.push(cur as u32);
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for text in [
+            "0",
+            "-1",
+            "999999999",
+            "1000000000",
+            "99999999999999999999999999999",
+            "-100000000000000000000000000001",
+        ] {
+            assert_eq!(big(text).to_string(), text);
+        }
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(big("000123").to_string(), "123");
+        assert!(BigInt::parse("1e30").is_none());
+        assert!(BigInt::parse("-").is_none());
+        assert!(BigInt::parse("1.0").is_none());
+    }
+
+    #[test]
+    fn arithmetic_is_exact() {
+        let a = big("99999999999999999999999999999");
+        assert_eq!(
+            (&a + &BigInt::from_i64(1)).to_string(),
+            "100000000000000000000000000000"
+        );
+        assert_eq!((&a - &a).to_string(), "0");
+        assert_eq!(
+            (&BigInt::from_i64(5) - &a).to_string(),
+            "-99999999999999999999999999994"
+        );
+        assert_eq!(
+            a.checked_mul(&big("-3")).unwrap().to_string(),
+            "-299999999999999999999999999997"
+        );
+        assert_eq!(
+            big("123456789123456789")
+                .checked_mul(&big("987654321987654321"))
+                .unwrap()
+                .to_string(),
+            "121932631356500531347203169112635269"
+        );
+        let huge = big(&"9".repeat(9 * 100));
+        assert!(huge.checked_mul(&huge).is_none());
+    }
+
+    #[test]
+    fn ordering_and_i64_range() {
+        let mut values = [
+            big("100000000000000000000000000001"),
+            big("-100000000000000000000000000000"),
+            BigInt::from_i64(i64::MIN),
+            big("100000000000000000000000000000"),
+            BigInt::from_i64(0),
+        ];
+        values.sort();
+        let sorted: Vec<String> = values.iter().map(BigInt::to_string).collect();
+        assert_eq!(
+            sorted,
+            [
+                "-100000000000000000000000000000",
+                "-9223372036854775808",
+                "0",
+                "100000000000000000000000000000",
+                "100000000000000000000000000001",
+            ]
+        );
+        assert_eq!(BigInt::from_i64(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(big("9223372036854775807").to_i64(), Some(i64::MAX));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+    }
+
+    #[test]
+    fn big_literals() {
+        let lit = |text: &str| Value::Double(text.parse().unwrap(), Some(text.into()));
+        assert!(is_big_literal(&lit("99999999999999999999999999999")));
+        assert!(!is_big_literal(&lit("12")));
+        assert!(!is_big_literal(&lit("1e30")));
+        assert!(!is_big_literal(&Value::Double(1e30, None)));
+        assert_eq!(
+            exact_cmp(
+                &lit("100000000000000000000000000001"),
+                &lit("100000000000000000000000000000")
+            ),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            exact_cmp(
+                &lit("-99999999999999999999999999999"),
+                &Value::Int(i64::MIN)
+            ),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            exact_cmp(
+                &lit("99999999999999999999999999999"),
+                &Value::Double(1e29, None)
+            ),
+            None
+        );
+        assert_eq!(
+            big("-99999999999999999999999999999").into_value(),
+            Value::Double(-1e29, None)
+        );
+        assert_eq!(big("-42").into_value(), Value::Int(-42));
+    }
+}
@@ -32,15 +32,15 @@ pub fn discover_tools() -> Vec<Tool> {
         path: env!("CARGO_BIN_EXE_qj").to_string(),
     }];
     for name in ["jq", "jaq", "gojq"] {
-        if let Ok(output) = Command::new("which").arg(name).output() {
-            if output.status.success() {
-                let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                if !path.is_empty() {
-                    tools.push(Tool {
-                        name: name.to_string(),
-                        path,
-                    });
-                }
+        if let Ok(output) = Command::new("which").arg(name).output()
+            && output.status.success()
+        {
+            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !path.is_empty() {
+                tools.push(Tool {
+                    name: name.to_string(),
+                    path,
+                });
             }
         }
     }
@@ -155,10 +155,7 @@ pub fn run_tool_cached(
 
     // Cache miss: run and store (skip caching oversized outputs)
     let result = run_tool(tool, filter, input, extra_args);
-    if result
-        .as_ref()
-        .map_or(true, |s| s.len() <= MAX_CACHED_OUTPUT)
-    {
+    if result.as_ref().is_none_or(|s| s.len() <= MAX_CACHED_OUTPUT) {
         cache
             .results
             .entry(tool.name.clone())
@@ -281,6 +281,45 @@ fn builtin_sort() {
     assert_jq_compat("sort", "[3,1,2]");
 }
 
+#[test]
+fn sort_mixed_values_structurally() {
+    let input = r#"[{"b":1},[1,2,3],{"a":1,"b":0},"s",[1,2],{"a":2},null,[1,3],{"a":1},true,0]"#;
+    assert_eq!(
+        qj_compact("sort", input).trim(),
+        r#"[null,true,0,"s",[1,2],[1,2,3],[1,3],{"a":1},{"a":2},{"a":1,"b":0},{"b":1}]"#
+    );
+    assert_jq_compat("sort", input);
+    assert_jq_compat("sort", "[[1,2],[1,2,3],[1,3]]");
+    assert_jq_compat("[min, max]", r#"[[1,2],[1,3],{"b":1},{"a":1,"b":0}]"#);
+}
+
+#[test]
+fn compare_objects_like_jq() {
+    // Sorted key lists compare first, then the values in key order; the
+    // order keys were written in doesn't matter.
+    for filter in [
+        r#"{"a":1} < {"b":1}"#,
+        r#"{"b":1} < {"a":1,"b":0}"#,
+        r#"{"a":2} < {"a":1,"b":0}"#,
+        r#"{"a":1,"b":2} < {"a":1,"b":3}"#,
+        r#"{"a":[1,2]} > {"a":[1]}"#,
+        r#"{"b":1,"a":2} == {"a":2,"b":1}"#,
+        r#"{"b":1,"a":2} < {"a":2,"b":1}"#,
+        r#"[{"b":1,"a":2},{"a":2,"b":1}] | unique"#,
+        r#"[{"b":1,"a":2},{"a":2,"b":1}] | group_by(.) | length"#,
+    ] {
+        assert_jq_compat(filter, "null");
+    }
+    assert_eq!(
+        qj_compact(
+            r#"[{"a":1,"b":0} < {"b":1}, {"y":1,"x":2} == {"x":2,"y":1}]"#,
+            "null"
+        )
+        .trim(),
+        "[true,true]"
+    );
+}
+
 #[test]
 fn builtin_map() {
     let out = qj_compact("map(. + 10)", "[1,2,3]");
@@ -468,6 +507,65 @@ fn passthrough_field_pretty_not_affected() {
     assert_eq!(out, "{\n  \"x\": 1\n}\n");
 }
 
+#[test]
+fn passthrough_field_chain_fires() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("doc.json");
+    let path = path.to_str().unwrap();
+    let run = |args: &[&str], doc: &str| {
+        std::fs::write(path, doc).unwrap();
+        let mut args = args.to_vec();
+        args.insert(0, "--debug-timing");
+        args.push(path);
+        qj_exit(&args, "")
+    };
+    for (filter, doc, expected) in [
+        (".name", r#"{"name":"alice","age":30}"#, r#""alice""#),
+        (
+            ".data",
+            r#"{"data": {"x": 1, "y": [2, 3]}}"#,
+            r#"{"x":1,"y":[2,3]}"#,
+        ),
+        (".a.b.c", r#"{"a":{"b":{"c":42}}}"#, "42"),
+        (".a | .b", r#"{"a":{"b":[1, 2]}}"#, "[1,2]"),
+        (".missing", r#"{"name":"alice"}"#, "null"),
+        (".a.b.missing", r#"{"a":{"b":{"c":42}}}"#, "null"),
+        (".a.b", r#"{"a":null}"#, "null"),
+        (".a.b", r#"{"\u0061":{"b":1}}"#, "1"),
+        (r#"."q\"k""#, r#"{"q\"k":3}"#, "3"),
+    ] {
+        let (code, stdout, stderr) = run(&["-c", filter], doc);
+        assert_eq!((code, stdout.trim()), (0, expected), "{filter} on {doc}");
+        assert!(stderr.contains("(field passthrough)"), "{filter}: {stderr}");
+        assert_jq_compat(filter, doc);
+    }
+
+    // Indexing a scalar or array on the way is the evaluator's error.
+    for (filter, doc) in [(".x", "[1,2,3]"), (".a.b", r#"{"a":"s"}"#)] {
+        let (code, stdout, stderr) = run(&["-c", filter], doc);
+        assert_eq!((code, stdout.as_str()), (5, ""), "{filter}");
+        assert!(stderr.contains("Cannot index"), "{filter}: {stderr}");
+        assert!(!stderr.contains("passthrough)"), "{filter}: {stderr}");
+    }
+    // Escapes the evaluator rewrites, repeated keys and pretty output too.
+    for (args, doc, expected) in [
+        (
+            &["-c", ".a"][..],
+            r#"{"a":"\ud83d\ude00"}"#,
+            "\"\u{1f600}\"\n",
+        ),
+        (&["-c", ".a"], r#"{"a":{"k":1,"k":2}}"#, "{\"k\":2}\n"),
+        (&[".a"], r#"{"a":{"k":1}}"#, "{\n  \"k\": 1\n}\n"),
+    ] {
+        let (code, stdout, stderr) = run(args, doc);
+        assert_eq!((code, stdout.as_str()), (0, expected), "{args:?} on {doc}");
+        assert!(!stderr.contains("passthrough)"), "{args:?}: {stderr}");
+    }
+    // NDJSON field lookups decode escaped keys the same way.
+    let (_, stdout, _) = qj_exit(&["-c", ".a"], "{\"\\u0061\":1}\n{\"a\":2}\n");
+    assert_eq!(stdout, "1\n2\n");
+}
+
 // --- Passthrough: .field | length ---
 
 #[test]
@@ -565,6 +663,64 @@ fn passthrough_bare_keys_array() {
     assert_jq_compat("keys", "[10,20,30]");
 }
 
+#[test]
+fn keys_sort_by_codepoint_on_every_path() {
+    // jq orders keys by UTF-8 bytes (codepoints), not by locale: uppercase
+    // ASCII first, then lowercase, then accented, CJK, and astral-plane keys
+    let obj = r#"{"zebra":1,"é":2,"Z":3,"日本":4,"😀":5,"a":6,"Éa":7,"ｚ":8,"\u00ff":9,"😀x":10,"":11,"中":12}"#;
+    let expected = r#"["","Z","a","zebra","Éa","é","ÿ","中","日本","ｚ","😀","😀x"]"#;
+    let nested = format!(r#"{{"data":{obj}}}"#);
+    for (filter, input) in [
+        ("keys", obj),             // passthrough
+        (". | keys", obj),         // evaluator
+        ("[keys][0]", obj),        // evaluator, not simplifiable to bare keys
+        (".data | keys", &nested), // field passthrough
+        (".data | [keys][0]", &nested),
+    ] {
+        assert_eq!(
+            qj_compact(filter, input).trim(),
+            expected,
+            "filter: {filter}"
+        );
+        assert_jq_compat(filter, input);
+    }
+    let sorted = qj_args(&["-c", "-S", "."], obj);
+    let sorted_keys = qj_compact("keys_unsorted", &sorted);
+    assert_eq!(sorted_keys.trim(), expected);
+}
+
+#[test]
+fn passthrough_sort_keys_identity() {
+    let dir = tempfile::tempdir().unwrap();
+    let docs = [
+        r#"{"z":{"b":[{"y":1,"x":"é\n"},2.5,[{"d":{},"c":[]}]],"a":null},"é":true,"A":-0,"":[]}"#,
+        r#"[{"b":1,"a":{"d":"\"","c":"\\"}},"s",null]"#,
+        r#"{"a":1,"a":{"y":2,"x":3},"b":{"c":1,"c":2}}"#,
+        r#""just a string""#,
+    ];
+    for (i, doc) in docs.iter().enumerate() {
+        let path = dir.path().join(format!("{i}.json"));
+        std::fs::write(&path, doc).unwrap();
+        let path = path.to_str().unwrap();
+        assert_jq_compat_with_flags(&["-c", "-S", "."], doc);
+        let (_, from_file, _) = qj_exit(&["-c", "-S", ".", path], "");
+        assert_eq!(from_file, qj_args(&["-c", "-S", ". | ."], doc), "{doc}");
+    }
+
+    // The sorted identity passthrough fires with -S; a map over the input
+    // would copy unsorted objects, so it goes through the evaluator instead.
+    let path = dir.path().join("0.json");
+    let path = path.to_str().unwrap();
+    let (_, _, stderr) = qj_exit(&["--debug-timing", "-c", "-S", ".", path], "");
+    assert!(stderr.contains("(minify passthrough)"), "{stderr}");
+    let (_, _, stderr) = qj_exit(&["--debug-timing", "-c", "-S", "keys", path], "");
+    assert!(stderr.contains("(keys passthrough)"), "{stderr}");
+    let input = r#"[{"v":{"b":1,"a":2}},{"v":{"d":3,"c":4}}]"#;
+    assert_jq_compat_with_flags(&["-c", "-S", "map(.v)"], input);
+    assert_jq_compat_with_flags(&["-c", "-S", ".[] | .v"], input);
+    assert_jq_compat_with_flags(&["-c", "-S", "map({v})"], input);
+}
+
 #[test]
 fn passthrough_field_keys_array_value() {
     let out = qj_compact(".items | keys", r#"{"items":["x","y"]}"#);
@@ -928,6 +1084,76 @@ fn passthrough_prefix_iterate_type() {
     assert_jq_compat(".items[] | type", r#"{"items":[1,"hello"]}"#);
 }
 
+#[test]
+fn passthrough_map_builtin_errors_like_evaluator() {
+    // `[.[] | f]` never takes the passthrough; both forms must fail the same
+    // way, with no partial array on stdout.
+    for (f, input) in [
+        ("keys", "[{\"b\":1,\"a\":2},3]"),
+        ("keys", "[{\"a\":1},null]"),
+        ("keys", "[\"ab\",-3]"),
+        ("length", "[[1,2],true]"),
+        ("has(\"a\")", "[{\"a\":1},[1]]"),
+    ] {
+        let passthrough = qj_exit(&["-c", &format!("map({f})")], input);
+        let evaluator = qj_exit(&["-c", &format!("[.[] | {f}]")], input);
+        assert_eq!(passthrough.0, 5, "map({f}) on {input}");
+        assert_eq!(passthrough.1, "", "map({f}) on {input}");
+        assert_eq!(passthrough, evaluator, "map({f}) on {input}");
+    }
+    let (_, _, stderr) = qj_exit(&["-c", "map(keys)"], "[{\"a\":1},3]");
+    assert!(stderr.contains("number (3) has no keys"), "{stderr}");
+    let (_, _, stderr) = qj_exit(&["-c", "map(keys)"], "[\"ab\",-3]");
+    assert!(stderr.contains("string (\"ab\") has no keys"), "{stderr}");
+}
+
+#[test]
+fn passthrough_map_length_of_null_is_zero() {
+    for filter in ["map(length)", "[.[] | length]"] {
+        assert_eq!(qj_compact(filter, "[[1],null,\"ab\"]").trim(), "[1,0,2]");
+    }
+    assert_eq!(qj_compact(".[] | length", "[null]").trim(), "0");
+    assert_jq_compat("map(length)", "[[1],null,\"ab\"]");
+}
+
+#[test]
+fn length_and_type_error_on_unsupported_input() {
+    let (code, _, stderr) = qj_exit(&["length"], "true");
+    assert_eq!(code, 5);
+    assert!(stderr.contains("boolean (true) has no length"), "{stderr}");
+    let (code, _, stderr) = qj_exit(&["-c", "keys"], "null");
+    assert_eq!(code, 5);
+    assert!(stderr.contains("null (null) has no keys"), "{stderr}");
+    // The field passthroughs used to report `0` / `"null"` here.
+    for (filter, input) in [
+        (".a | length", "true"),
+        (".a | type", "true"),
+        (".a.b | length", "{\"a\":true}"),
+    ] {
+        let (code, out, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!((code, out.as_str()), (5, ""), "{filter}");
+        assert!(
+            stderr.contains("Cannot index boolean"),
+            "{filter}: {stderr}"
+        );
+    }
+}
+
+#[test]
+fn map_stops_at_first_error() {
+    let (code, out, stderr) = qj_exit(&["-c", "map(error)"], "[\"one\",\"two\"]");
+    assert_eq!((code, out.as_str()), (5, ""));
+    assert!(
+        stderr.contains("one") && !stderr.contains("two"),
+        "{stderr}"
+    );
+    let out = qj_compact(
+        "[.[] | try map(length) catch \"caught\"]",
+        "[[1,true],[[2]]]",
+    );
+    assert_eq!(out.trim(), r#"["caught",[1]]"#);
+}
+
 // --- Phase 7: Syntactic variant detection ---
 
 #[test]
@@ -1193,6 +1419,118 @@ fn builtin_from_entries_name_value() {
     assert_jq_compat("from_entries", r#"[{"name":"a","value":1}]"#);
 }
 
+#[test]
+fn to_entries_on_arrays_and_empty_containers() {
+    assert_eq!(
+        qj_compact("to_entries", "[10,20]").trim(),
+        r#"[{"key":0,"value":10},{"key":1,"value":20}]"#
+    );
+    assert_eq!(qj_compact("to_entries", "[]").trim(), "[]");
+    assert_eq!(qj_compact("to_entries", "{}").trim(), "[]");
+    assert_eq!(
+        qj_compact("to_entries | from_entries", r#"["x","y"]"#).trim(),
+        r#"{"0":"x","1":"y"}"#
+    );
+    let (code, _, stderr) = qj_exit(&["-c", "to_entries"], "1");
+    assert_eq!(code, 5);
+    assert!(stderr.contains("number (1) has no keys"), "{stderr}");
+}
+
+#[test]
+fn from_entries_key_and_value_spellings() {
+    let cases = [
+        (r#"[{"key":"a","value":1}]"#, r#"{"a":1}"#),
+        (r#"[{"k":"a","v":1}]"#, r#"{"a":1}"#),
+        (r#"[{"name":"a","value":1}]"#, r#"{"a":1}"#),
+        (r#"[{"Name":"a","Value":1}]"#, r#"{"a":1}"#),
+        (r#"[{"K":"a","v":1}]"#, r#"{"a":1}"#),
+        (r#"[{"Key":"a","Value":1}]"#, r#"{"a":1}"#),
+        (r#"[{"N":"a","value":1}]"#, r#"{"a":1}"#),
+        // A null `key` falls back to the other spellings.
+        (r#"[{"key":null,"k":"a","value":1}]"#, r#"{"a":1}"#),
+        // `value` wins over `v`, even when it is false or null.
+        (r#"[{"key":"a","value":false,"v":1}]"#, r#"{"a":false}"#),
+        (r#"[{"key":"a","value":null,"Value":1}]"#, r#"{"a":null}"#),
+        // Missing values are null.
+        (r#"[{"key":"a"}]"#, r#"{"a":null}"#),
+        // Numbers and booleans become their tostring text.
+        (
+            r#"[{"key":1,"value":1},{"key":true,"value":2},{"key":false,"value":3}]"#,
+            r#"{"1":1,"true":2,"false":3}"#,
+        ),
+        // Later duplicates win, keeping the first position.
+        (
+            r#"[{"key":"a","value":1},{"key":"b","value":2},{"key":"a","value":3}]"#,
+            r#"{"a":3,"b":2}"#,
+        ),
+        (r#"{"x":{"key":"a","value":1}}"#, r#"{"a":1}"#),
+        ("[]", "{}"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(
+            qj_compact("from_entries", input).trim(),
+            expected,
+            "input={input}"
+        );
+    }
+}
+
+#[test]
+fn object_construction_generator_keys() {
+    let input = r#"{"a":"x","b":"y","n":1}"#;
+    for (filter, expected) in [
+        ("{(.a,.b): 1}", "{\"x\":1}\n{\"y\":1}\n"),
+        // Keys are the outer loop, values the inner one.
+        (
+            "{(.a,.b): (1,2)}",
+            "{\"x\":1}\n{\"x\":2}\n{\"y\":1}\n{\"y\":2}\n",
+        ),
+        // Later entries vary fastest.
+        (
+            "{k: 0, (.a,.b): 1, c: (true,false)}",
+            concat!(
+                "{\"k\":0,\"x\":1,\"c\":true}\n{\"k\":0,\"x\":1,\"c\":false}\n",
+                "{\"k\":0,\"y\":1,\"c\":true}\n{\"k\":0,\"y\":1,\"c\":false}\n",
+            ),
+        ),
+        (r#"{"\(.a,.b)": .n}"#, "{\"x\":1}\n{\"y\":1}\n"),
+        // A key error ends the whole construction, as in jq.
+        (r#"try {(.a,.n,.b): 1} catch "bad""#, "{\"x\":1}\n\"bad\"\n"),
+    ] {
+        assert_eq!(qj_compact(filter, input), expected, "{filter}");
+        assert_jq_compat(filter, input);
+    }
+    for (filter, msg) in [
+        ("{(.n): 1}", "Cannot use number (1) as object key"),
+        ("{(.missing): 1}", "Cannot use null (null) as object key"),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{filter}");
+        assert!(stdout.is_empty(), "{filter}: {stdout}");
+        assert!(stderr.contains(msg), "{filter}: {stderr}");
+    }
+}
+
+#[test]
+fn from_entries_rejects_container_keys() {
+    for (input, msg) in [
+        (
+            r#"[{"key":{"a":1},"value":1}]"#,
+            r#"Cannot use object ({"a":1}) as object key"#,
+        ),
+        (
+            r#"[{"key":[1],"value":1}]"#,
+            "Cannot use array ([1]) as object key",
+        ),
+        ("[1]", r#"Cannot index number with "key""#),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-c", "from_entries"], input);
+        assert_eq!(code, 5, "input={input}");
+        assert!(stdout.is_empty(), "input={input}: {stdout}");
+        assert!(stderr.contains(msg), "input={input}: {stderr}");
+    }
+}
+
 // --- Builtin: flatten ---
 
 #[test]
@@ -1341,6 +1679,45 @@ fn builtin_sort_by() {
     assert_jq_compat("sort_by(.x)", r#"[{"x":3},{"x":1},{"x":2}]"#);
 }
 
+#[test]
+fn sort_by_multiple_keys() {
+    let people = r#"[{"last":"b","first":"y"},{"last":"a","first":"z"},{"last":"b","first":"x"}]"#;
+    assert_eq!(
+        qj_compact("sort_by(.last, .first) | map(.first)", people).trim(),
+        r#"["z","x","y"]"#
+    );
+    assert_jq_compat("sort_by(.last, .first)", people);
+    assert_jq_compat("group_by(.last, .first)", people);
+    assert_jq_compat("unique_by(.last)", people);
+    assert_jq_compat("min_by(.last, .first)", people);
+    assert_jq_compat("max_by(.last, .first)", people);
+}
+
+#[test]
+fn sort_by_keys_with_different_output_counts() {
+    // Fewer key outputs sort first when the shared prefix is equal.
+    let input = r#"[{"k":[1,2]},{"k":[1]},{"k":[]},{"k":[0,9,9]}]"#;
+    assert_eq!(
+        qj_compact("sort_by(.k[]) | map(.k | length)", input).trim(),
+        "[0,3,1,2]"
+    );
+    assert_jq_compat("sort_by(.k[])", input);
+    assert_jq_compat("group_by(.k[0])", input);
+    assert_jq_compat("unique_by(.k[])", input);
+    assert_jq_compat("min_by(.k[])", input);
+    assert_jq_compat("max_by(.k[])", input);
+}
+
+#[test]
+fn sort_by_key_errors_propagate() {
+    for f in ["sort_by", "group_by", "unique_by", "min_by", "max_by"] {
+        let filter = format!("{f}(if .a == 2 then error(\"bad key\") else .a end)");
+        let stderr = qj_err(&filter, r#"[{"a":1},{"a":2}]"#);
+        assert!(stderr.contains("bad key"), "{f}: {stderr}");
+        assert_jq_compat(&filter, r#"[{"a":1},{"a":2}]"#);
+    }
+}
+
 // --- Builtin: del ---
 
 #[test]
@@ -1402,6 +1779,108 @@ fn builtin_endswith() {
     assert_jq_compat(r#"endswith("xyz")"#, r#""hello""#);
 }
 
+// --- Builtin argument types: ltrimstr / rtrimstr / startswith / endswith / split / join ---
+
+#[test]
+fn string_builtin_arg_type_matrix() {
+    let values = [
+        "null",
+        "true",
+        "1.5",
+        r#""ab""#,
+        r#"["a",1,null,true]"#,
+        r#"{"k":"b"}"#,
+    ];
+    for builtin in [
+        "ltrimstr",
+        "rtrimstr",
+        "startswith",
+        "endswith",
+        "split",
+        "join",
+    ] {
+        for arg in values {
+            for input in values {
+                assert_jq_compat(&format!("{builtin}({arg})"), input);
+            }
+        }
+    }
+}
+
+#[test]
+fn string_builtin_arg_type_error_messages() {
+    let cases = [
+        (
+            "startswith(1)",
+            r#""ab""#,
+            "startswith() requires string inputs",
+        ),
+        ("endswith(\"b\")", "1", "endswith() requires string inputs"),
+        (
+            "split(1)",
+            r#""ab""#,
+            "split input and separator must be strings",
+        ),
+        (
+            "split(\"a\")",
+            "null",
+            "split input and separator must be strings",
+        ),
+        ("join(\",\")", "null", "Cannot iterate over null (null)"),
+        (
+            "join(\",\")",
+            "[1,[2]]",
+            r#"string ("1,") and array ([2]) cannot be added"#,
+        ),
+        (
+            "join(1)",
+            r#"["a","b"]"#,
+            r#"string ("a") and number (1) cannot be added"#,
+        ),
+    ];
+    for (filter, input, msg) in cases {
+        let (code, stdout, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{filter} on {input}");
+        assert!(stdout.is_empty(), "{filter} on {input}: {stdout}");
+        assert!(stderr.contains(msg), "{filter} on {input}: {stderr}");
+    }
+    // Non-string arguments and inputs pass through ltrimstr/rtrimstr.
+    assert_eq!(qj_compact("ltrimstr(1)", r#""1a""#).trim(), r#""1a""#);
+    assert_eq!(qj_compact(r#"rtrimstr("a")"#, "[1]").trim(), "[1]");
+    assert_eq!(
+        qj_compact("join(null)", r#"["a",1,null,true]"#).trim(),
+        r#""a1true""#
+    );
+    assert_eq!(
+        qj_compact(r#"join("-")"#, r#"{"x":"a","y":2}"#).trim(),
+        r#""a-2""#
+    );
+}
+
+#[test]
+fn regex_split_arg_type_matrix() {
+    let inputs = ["null", "1", r#""""#, r#""a,b""#, r#"["a,b"]"#];
+    let flags = ["null", r#""""#, r#""x""#, "1", "[1]"];
+    for input in inputs {
+        assert_jq_compat(r#"split(",")"#, input);
+        for flag in flags {
+            assert_jq_compat(&format!(r#"split(","; {flag})"#), input);
+            assert_jq_compat(&format!(r#"[splits(","; {flag})]"#), input);
+        }
+    }
+    // Bad flags fail on jq's `"g" + flags` before the input is checked.
+    let (code, _, stderr) = qj_exit(&["-c", r#"split(","; 1)"#], "null");
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains(r#"string ("g") and number (1) cannot be added"#),
+        "{stderr}"
+    );
+    // jq has no split/0.
+    let (code, _, stderr) = qj_exit(&["-c", "split"], r#""a,b""#);
+    assert_eq!(code, 5);
+    assert!(stderr.contains("split/0 is not defined"), "{stderr}");
+}
+
 // --- Builtin: tonumber / tostring ---
 
 #[test]
@@ -1414,6 +1893,73 @@ fn builtin_tonumber() {
     assert_jq_compat("tonumber", "42");
 }
 
+#[test]
+fn tonumber_strictness_matrix() {
+    // (input, stdout, exit code) as jq 1.7.1 gives them
+    let cases = [
+        (r#""42""#, "42", 0),
+        (r#""-17""#, "-17", 0),
+        (r#""+5""#, "5", 0),
+        (r#""007""#, "7", 0),
+        (r#""3.25""#, "3.25", 0),
+        (r#"".5""#, "0.5", 0),
+        (r#""1e3""#, "1000", 0),
+        (r#""2.5E-3""#, "0.0025", 0),
+        (r#""-0""#, "-0", 0),
+        (r#""1e999""#, "1.7976931348623157e+308", 0),
+        (r#""-1e999""#, "-1.7976931348623157e+308", 0),
+        (r#""0x10""#, "", 5),
+        (r#"" 42 ""#, "", 5),
+        (r#""42\n""#, "", 5),
+        (r#""""#, "", 5),
+        (r#""nan""#, "", 5),
+        (r#""infinity""#, "", 5),
+        (r#""1_000""#, "", 5),
+        (r#""1e""#, "", 5),
+        (r#""--1""#, "", 5),
+        ("12", "12", 0),
+        ("1.5", "1.5", 0),
+        ("true", "", 5),
+        ("null", "", 5),
+        ("[1]", "", 5),
+        (r#"{"a":1}"#, "", 5),
+    ];
+    for (input, stdout, code) in cases {
+        let (got_code, got_stdout, stderr) = qj_exit(&["-c", "tonumber"], input);
+        assert_eq!(
+            (got_stdout.trim(), got_code),
+            (stdout, code),
+            "tonumber on {input}"
+        );
+        if code != 0 {
+            assert!(stderr.contains("cannot be parsed as a number"), "{stderr}");
+        }
+    }
+    assert_eq!(
+        qj_compact("tonumber | isinfinite", r#""1e999""#).trim(),
+        "false"
+    );
+    // Integral strings stay exact
+    assert_eq!(
+        qj_compact("tonumber | . + 1", r#""9007199254740993""#).trim(),
+        "9007199254740994"
+    );
+    // jq 1.6 also accepts surrounding whitespace, and rounds big integers
+    for input in [
+        r#""0x10""#,
+        r#""""#,
+        r#""nan""#,
+        r#""1_000""#,
+        r#""+5""#,
+        r#"".5""#,
+        r#""1e999""#,
+        "true",
+        "[1]",
+    ] {
+        assert_jq_compat("tonumber", input);
+    }
+}
+
 #[test]
 fn builtin_tostring() {
     assert_eq!(qj_compact("tostring", "42").trim(), r#""42""#);
@@ -1506,6 +2052,122 @@ fn builtin_has_array() {
     assert_jq_compat("has(5)", "[10,20,30]");
 }
 
+/// Every input type × argument type for has/in/contains/inside, compared
+/// against jq including the error messages. Batched like the arithmetic
+/// type-pair test: one `try ... catch .` expression per combination.
+#[test]
+fn jq_compat_membership_type_matrix() {
+    if !jq_available() {
+        return;
+    }
+    let values = [
+        "null",
+        "true",
+        "false",
+        "0",
+        "1",
+        "1.5",
+        "(0-1)",
+        "nan",
+        "infinite",
+        r#""a""#,
+        r#""abc""#,
+        r#""""#,
+        "[]",
+        "[1,2]",
+        r#"[1,[2,3],"ab",{"k":[true]}]"#,
+        r#"[[1]]"#,
+        "{}",
+        r#"{"a":1}"#,
+        r#"{"a":[1,2],"b":{"c":"xyz"}}"#,
+        r#"{"a":[1],"b":{"c":"y"}}"#,
+    ];
+    // One batch per builtin: all four at once exceeds jq's bytecode limit.
+    let mut failures = Vec::new();
+    for builtin in ["has", "in", "contains", "inside"] {
+        let mut labels = Vec::new();
+        let mut try_exprs = Vec::new();
+        for input in &values {
+            for arg in &values {
+                let expr = format!("{input} | {builtin}({arg})");
+                try_exprs.push(format!("try ({expr}) catch ."));
+                labels.push(expr);
+            }
+        }
+        let filter = try_exprs.join(", ");
+
+        let (qj_stdout, _, _) = run_tool_full(env!("CARGO_BIN_EXE_qj"), &["-cn", &filter], "null");
+        let (jq_stdout, _, _) = run_tool_full("jq", &["-cn", &filter], "null");
+
+        let qj_lines: Vec<&str> = qj_stdout.lines().collect();
+        let jq_lines: Vec<&str> = jq_stdout.lines().collect();
+        for (i, label) in labels.iter().enumerate() {
+            let qj_line = qj_lines.get(i).copied().unwrap_or("<missing>");
+            let jq_line = jq_lines.get(i).copied().unwrap_or("<missing>");
+            if qj_line != jq_line {
+                failures.push(format!("  {label}\n    qj={qj_line:?} jq={jq_line:?}"));
+            }
+        }
+        if qj_lines.len() != jq_lines.len() {
+            failures.push(format!(
+                "  {builtin} output line count: qj={} jq={}",
+                qj_lines.len(),
+                jq_lines.len()
+            ));
+        }
+    }
+    if !failures.is_empty() {
+        panic!(
+            "{} membership mismatches vs jq:\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+#[test]
+fn membership_type_errors() {
+    let cases = [
+        (
+            r#"has("a")"#,
+            "[1]",
+            "Cannot check whether array has a string key",
+        ),
+        (
+            "has(0)",
+            "{}",
+            "Cannot check whether object has a number key",
+        ),
+        (
+            r#"in({"a":1})"#,
+            "0",
+            "Cannot check whether object has a number key",
+        ),
+        (
+            "contains(false)",
+            "true",
+            "boolean (true) and boolean (false) cannot have their containment checked",
+        ),
+        (
+            "inside([1])",
+            "1",
+            "array ([1]) and number (1) cannot have their containment checked",
+        ),
+    ];
+    for (filter, input, msg) in cases {
+        let (code, stdout, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{filter} on {input}");
+        assert!(stdout.is_empty());
+        assert!(stderr.contains(msg), "{filter} on {input}: {stderr}");
+    }
+    // null has no keys but is not an error
+    assert_eq!(qj_compact(r#"has("a"), has(0)"#, "null"), "false\nfalse\n");
+    assert_eq!(
+        qj_compact("[has(1.7, -0.5, 2)]", "[1,2]"),
+        "[true,true,false]\n"
+    );
+}
+
 // --- Builtin: type (e2e) ---
 
 #[test]
@@ -1560,6 +2222,23 @@ fn recursive_descent_strings() {
     assert_eq!(out.trim(), r#"["x","y"]"#);
 }
 
+#[test]
+fn recursive_descent_select_order() {
+    let input = r#"{"type":"error","msg":"a","sub":[{"type":"error","msg":"b"},{"type":"info"}],"z":{"type":"error","msg":"c"}}"#;
+    let out = qj_compact(r#"[.. | select(.type? == "error") | .msg]"#, input);
+    assert_eq!(out.trim(), r#"["a","b","c"]"#);
+    assert_jq_compat(r#"[.. | select(.type? == "error") | .msg]"#, input);
+    assert_jq_compat("[recurse | numbers]", r#"[1,[2,{"a":3}],4]"#);
+}
+
+#[test]
+fn recursive_descent_error_stops_output() {
+    let (code, stdout, stderr) = qj_exit(&["-c", ".. | .x"], r#"{"x":{"y":1}}"#);
+    assert_eq!(code, 5);
+    assert_eq!(stdout, "{\"y\":1}\nnull\n");
+    assert!(stderr.contains("Cannot index number"), "stderr: {stderr}");
+}
+
 // --- Language: Boolean and/or ---
 
 #[test]
@@ -1603,6 +2282,36 @@ fn try_operator_on_iteration() {
     assert!(out.trim().is_empty(), "expected no output, got: {out}");
 }
 
+#[test]
+fn try_operator_after_any_term() {
+    assert_jq_compat(".[] | fromjson?", r#"["1","{\"a\":2}","nope","[3","[4]"]"#);
+    assert_jq_compat("[.[] | fromjson?]", r#"["1","nope"]"#);
+    assert_jq_compat(".a?.b?", r#"{"a":{"b":1}}"#);
+    assert_jq_compat(
+        "[.[] | .a?.b?]",
+        r#"[{"a":{"b":1}},{"a":[1]},[2],"s",null]"#,
+    );
+    assert_jq_compat("map(.x)?", r#"{"a":1}"#);
+    assert_jq_compat("[map(.x)?]", "[1,2]");
+    assert_jq_compat("[.[] | (.a + 1)?]", r#"[{"a":1},{"a":"s"},null]"#);
+    assert_jq_compat("[.[] | (1 / .)?]", "[1,0,2]");
+    assert_jq_compat("[.[] | tonumber? * 2]", r#"["1","x","3"]"#);
+    // jq folds `1/0` at compile time, so this one is checked by hand.
+    assert_eq!(qj_compact("[(1/0)?]", "null").trim(), "[]");
+}
+
+#[test]
+fn try_operator_keeps_errors_after_it() {
+    // `?` binds tighter than unary minus: the negation fails outside it.
+    let (code, _, stderr) = qj_exit(&["-c", "--", "-.[0]?"], r#"["1"]"#);
+    assert_eq!(code, 5);
+    assert!(stderr.contains("cannot be negated"), "stderr: {stderr}");
+    let (code, stdout, stderr) = qj_exit(&["-c", ".[] | fromjson? | . + 1"], r#"["1","[2]"]"#);
+    assert_eq!(code, 5);
+    assert_eq!(stdout, "2\n");
+    assert!(stderr.contains("cannot be added"), "stderr: {stderr}");
+}
+
 // --- Language: Unary negation ---
 
 #[test]
@@ -1813,25 +2522,115 @@ fn large_integer_arithmetic_more_precise_than_jq() {
     assert_eq!(result, "505874924095815682");
 }
 
-// --- jq conformance tests ---
-// These run both qj and jq and verify identical output.
-// If jq is not installed, the tests pass (they only assert when both are available).
+#[test]
+fn big_integer_literals_sort_and_compare_exactly() {
+    // Beyond i64 these all round to the same f64, but keep their order
+    let input = "[100000000000000000000000000002, -100000000000000000000000000001, 100000000000000000000000000000, 100000000000000000000000000001, 5]";
+    assert_eq!(
+        qj_compact("sort", input).trim(),
+        "[-100000000000000000000000000001,5,100000000000000000000000000000,100000000000000000000000000001,100000000000000000000000000002]"
+    );
+    assert_eq!(
+        qj_compact(
+            "[unique | length, (max | tostring), (min | tostring)]",
+            input
+        )
+        .trim(),
+        r#"[5,"100000000000000000000000000002","-100000000000000000000000000001"]"#
+    );
+    assert_eq!(
+        qj_compact(
+            "[.[0] == .[2], .[2] < .[3], (.[3] | . == 100000000000000000000000000001)]",
+            input
+        )
+        .trim(),
+        "[false,true,true]"
+    );
+    // Objects with big integer values keep them through keys and entries
+    assert_eq!(
+        qj_compact(
+            "[keys, to_entries[0].value, (to_entries | from_entries | .a)]",
+            r#"{"b":100000000000000000000000000001,"a":-100000000000000000000000000001}"#
+        )
+        .trim(),
+        r#"[["a","b"],100000000000000000000000000001,-100000000000000000000000000001]"#
+    );
+}
 
-fn jq_available() -> bool {
-    Command::new("jq")
-        .arg("--version")
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .status()
-        .is_ok_and(|s| s.success())
+#[test]
+fn big_integer_literal_vs_computed_equality() {
+    // A literal and a computed value compare as f64, so 1e30 written out
+    // equals 1e30 computed; two literals compare digit for digit
+    assert_eq!(
+        qj_compact(
+            "[1000000000000000000000000000000 == pow(10; 30), 1e30 == 1000000000000000000000000000000, 1000000000000000000000000000001 == 1000000000000000000000000000000, 1000000000000000000000000000000 + 1 == 1000000000000000000000000000001]",
+            "null"
+        )
+        .trim(),
+        "[true,true,false,true]"
+    );
 }
 
-fn run_jq_compact(filter: &str, input: &str) -> Option<String> {
-    let output = Command::new("jq")
-        .args(["-c", filter])
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
+#[test]
+fn big_integer_arithmetic_stays_exact() {
+    let big = "99999999999999999999999999999";
+    assert_eq!(
+        qj_compact(
+            "[. + 1, . - 100000000000000000000000000000, . * -3, -., abs, length]",
+            big
+        )
+        .trim(),
+        "[100000000000000000000000000000,-1,-299999999999999999999999999997,-99999999999999999999999999999,99999999999999999999999999999,99999999999999999999999999999]"
+    );
+    // Results back in range become plain integers again
+    assert_eq!(
+        qj_compact(". - 99999999999999999999999999990 | . + 1", big).trim(),
+        "10"
+    );
+    // Mixing with a float, or dividing, goes through f64
+    assert_eq!(qj_compact("[. + 0.5, . / 1e29]", big).trim(), "[1e+29,1]");
+    // Filter literals are exact too
+    assert_eq!(
+        qj_compact("(-100000000000000000000000000001) + 2", "null").trim(),
+        "-99999999999999999999999999999"
+    );
+}
+
+#[test]
+fn big_integer_tostring_round_trip() {
+    let big = "-123456789012345678901234567890123";
+    assert_eq!(
+        qj_compact("[tostring, tojson, (tostring | tonumber | tostring), (. as $n | [.] | tojson | fromjson[0] == $n)]", big).trim(),
+        r#"["-123456789012345678901234567890123","-123456789012345678901234567890123","-123456789012345678901234567890123",true]"#
+    );
+    assert_eq!(qj_compact("have_literal_numbers", "null").trim(), "true");
+}
+
+// --- jq conformance tests ---
+// These run both qj and jq and verify identical output.
+// If jq is not installed, the tests pass (they only assert when both are available).
+
+fn jq_available() -> bool {
+    Command::new("jq")
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Whether the installed jq defines `name/arity` (older releases lack some builtins).
+fn jq_has_builtin(signature: &str) -> bool {
+    run_jq_compact(&format!("any(builtins[]; . == \"{signature}\")"), "null")
+        .is_some_and(|out| out.trim() == "true")
+}
+
+fn run_jq_compact(filter: &str, input: &str) -> Option<String> {
+    let output = Command::new("jq")
+        .args(["-c", filter])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
         .spawn()
         .and_then(|mut child| {
             use std::io::Write;
@@ -1843,7 +2642,7 @@ fn run_jq_compact(filter: &str, input: &str) -> Option<String> {
     if !output.status.success() {
         return None;
     }
-    Some(String::from_utf8(output.stdout).ok()?)
+    String::from_utf8(output.stdout).ok()
 }
 
 /// Run jq with custom args and return stdout, or None if jq fails.
@@ -1864,7 +2663,7 @@ fn run_jq(args: &[&str], input: &str) -> Option<String> {
     if !output.status.success() {
         return None;
     }
-    Some(String::from_utf8(output.stdout).ok()?)
+    String::from_utf8(output.stdout).ok()
 }
 
 /// Assert that qj and jq produce identical stdout AND agree on success/failure.
@@ -1993,8 +2792,8 @@ fn jq_compat_exhaustive_arithmetic_type_pairs() {
 
     let mut failures = Vec::new();
     for (i, label) in labels.iter().enumerate() {
-        let qj_line = qj_lines.get(i).map(|s| *s).unwrap_or("<missing>");
-        let jq_line = jq_lines.get(i).map(|s| *s).unwrap_or("<missing>");
+        let qj_line = qj_lines.get(i).copied().unwrap_or("<missing>");
+        let jq_line = jq_lines.get(i).copied().unwrap_or("<missing>");
         if qj_line != jq_line {
             failures.push(format!("  {label}\n    qj={qj_line:?} jq={jq_line:?}",));
         }
@@ -2079,6 +2878,182 @@ fn jq_compat_builtins() {
     assert_jq_compat("reverse", "[1,2,3]");
 }
 
+#[test]
+fn build_info_flag_prints_json() {
+    let (code, out, _) = qj_exit(&["--build-info", "--threads", "3"], "");
+    assert_eq!(code, 0);
+    let info: serde_json::Value = serde_json::from_str(&out).expect("build info is JSON");
+    for key in [
+        "version",
+        "simdjson_version",
+        "simdjson_implementation",
+        "default_threads",
+        "threads",
+        "pcore_detection",
+        "allocator",
+        "features",
+    ] {
+        assert!(info.get(key).is_some(), "missing {key}: {out}");
+    }
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["threads"], 3);
+    assert!(info["default_threads"].as_u64().unwrap() >= 1);
+    assert!(!info["simdjson_implementation"].as_str().unwrap().is_empty());
+    assert!(info["features"].is_array());
+
+    let (_, compact, _) = qj_exit(&["--build-info", "-c"], "");
+    assert_eq!(compact.lines().count(), 1, "{compact}");
+}
+
+#[test]
+fn build_info_builtin_matches_flag() {
+    let (_, flag, _) = qj_exit(&["--build-info", "-c", "--threads", "2"], "");
+    let (_, builtin, _) = qj_exit(&["-nc", "--threads", "2", "qj_build_info"], "");
+    assert_eq!(builtin, flag);
+    let out = qj_compact(r#"[builtins[] | select(. == "qj_build_info/0")]"#, "null");
+    assert_eq!(out.trim(), r#"["qj_build_info/0"]"#);
+}
+
+#[test]
+fn check_flag_accepts_valid_filter() {
+    let dir = tempfile::tempdir().unwrap();
+    let lib = dir.path().join("m.jq");
+    std::fs::write(&lib, "def inc: . + 1;").unwrap();
+    let prog = dir.path().join("prog.jq");
+    std::fs::write(
+        &prog,
+        "import \"m\" as m; def f($k): .[$k] | m::inc; f($name) as $v | [$v, $ARGS.named]",
+    )
+    .unwrap();
+    let (code, out, err) = qj_exit(
+        &[
+            "--check",
+            "-L",
+            dir.path().to_str().unwrap(),
+            "--arg",
+            "name",
+            "a",
+            "-f",
+            prog.to_str().unwrap(),
+        ],
+        "not json",
+    );
+    assert_eq!((code, out.as_str(), err.as_str()), (0, "", ""));
+    // The alias, and variables bound from files.
+    let data = dir.path().join("data.json");
+    std::fs::write(&data, "1 2").unwrap();
+    let (code, _, err) = qj_exit(
+        &[
+            "--dry-run",
+            "--slurpfile",
+            "s",
+            data.to_str().unwrap(),
+            "--rawfile",
+            "r",
+            lib.to_str().unwrap(),
+            "$s, $r",
+        ],
+        "",
+    );
+    assert_eq!(code, 0, "{err}");
+}
+
+#[test]
+fn check_flag_reports_undefined_functions() {
+    let (code, out, err) = qj_exit(&["--check", "foo | .a | bar(1; 2)"], "");
+    assert_eq!(code, 3);
+    assert_eq!(out, "");
+    assert!(err.contains("foo/0 is not defined"), "{err}");
+    assert!(err.contains("bar/2 is not defined"), "{err}");
+    assert!(err.contains("2 compile errors"), "{err}");
+    // Found in branches the optimizer would drop, too.
+    let (code, _, err) = qj_exit(&["--check", "if false then nope else . end"], "");
+    assert_eq!(code, 3);
+    assert!(err.contains("nope/0 is not defined"), "{err}");
+}
+
+#[test]
+fn check_flag_reports_wrong_arity() {
+    let (code, _, err) = qj_exit(&["--check", "length(1), test, def f: 1; f(2)"], "");
+    assert_eq!(code, 3);
+    assert!(
+        err.contains("length/1 is not defined (length takes 0 arguments)"),
+        "{err}"
+    );
+    assert!(
+        err.contains("test/0 is not defined (test takes 1 or 2 arguments)"),
+        "{err}"
+    );
+    assert!(err.contains("f/1 is not defined"), "{err}");
+}
+
+#[test]
+fn check_flag_reports_unbound_variables() {
+    let (code, _, err) = qj_exit(
+        &["--check", "--arg", "a", "1", "[$a, $b, (. as $c | $c), $c]"],
+        "",
+    );
+    assert_eq!(code, 3);
+    assert!(err.contains("$b is not defined"), "{err}");
+    assert!(err.contains("$c is not defined"), "{err}");
+    assert!(!err.contains("$a is not defined"), "{err}");
+    assert!(err.contains("2 compile errors"), "{err}");
+    let (code, _, err) = qj_exit(&["--check", "break $out"], "");
+    assert_eq!(code, 3);
+    assert!(err.contains("$*label-out is not defined"), "{err}");
+}
+
+#[test]
+fn break_without_label_is_a_compile_error() {
+    for filter in [
+        "break $out",
+        "[.[] | try (if . == 2 then break $x else . end) catch \"c\"]",
+        "(label $a | 1), break $a",
+        "def f: break $a; label $a | f",
+    ] {
+        let (code, out, err) = qj_exit(&["-c", filter], "[1,2]");
+        assert_eq!(code, 3, "{filter}");
+        assert_eq!(out, "", "{filter}");
+        assert!(err.contains("$*label-"), "{filter}: {err}");
+        assert!(err.contains("1 compile error"), "{filter}: {err}");
+    }
+}
+
+#[test]
+fn unknown_builtins_are_runtime_errors() {
+    let (code, out, err) = qj_exit(&["-c", "lenght"], "[1,2]");
+    assert_eq!(code, 5);
+    assert_eq!(out, "");
+    assert!(err.contains("lenght/0 is not defined"), "{err}");
+    let (code, out, err) = qj_exit(&["-c", ".[] | selct(.x)"], r#"[{"x":1}]"#);
+    assert_eq!(code, 5);
+    assert_eq!(out, "");
+    assert!(err.contains("selct/1 is not defined"), "{err}");
+    // Catchable like any runtime error, and a definition still wins.
+    assert_eq!(
+        qj_compact("try lenght catch .", "null").trim(),
+        r#""lenght/0 is not defined""#
+    );
+    assert_eq!(qj_compact("def lenght: 7; lenght", "null").trim(), "7");
+}
+
+#[test]
+fn unimplemented_jq_builtins_say_so() {
+    let (code, out, err) = qj_exit(&["-c", "input_filename"], "1");
+    assert_eq!(code, 5);
+    assert_eq!(out, "");
+    assert!(
+        err.contains("input_filename/0 is not supported by qj"),
+        "{err}"
+    );
+    let (code, _, err) = qj_exit(&["-c", "ldexp(.; 2)"], "1");
+    assert_eq!(code, 5);
+    assert!(err.contains("ldexp/2 is not supported by qj"), "{err}");
+    // Other arities of the name are still unknown, as in jq.
+    let (_, _, err) = qj_exit(&["-c", "halt(1)"], "1");
+    assert!(err.contains("halt/1 is not defined"), "{err}");
+}
+
 #[test]
 fn jq_compat_select() {
     assert_jq_compat(".[] | select(. > 2)", "[1,2,3,4,5]");
@@ -2131,6 +3106,46 @@ fn jq_compat_del() {
     assert_jq_compat("del(.a)", r#"{"a":1,"b":2}"#);
 }
 
+#[test]
+fn del_multiple_paths_at_once() {
+    let input = r#"{"a":1,"b":[0,1,2,3],"c":{"x":1,"y":2},"d":4}"#;
+    // Every path is collected before anything is deleted, so `.b[0]` and
+    // `.b[2]` both refer to the original array.
+    let out = qj_compact("del(.a, .b[0], .b[2], .c[]?)", input);
+    assert_eq!(out.trim(), r#"{"b":[1,3],"c":{},"d":4}"#);
+    assert_jq_compat("del(.a, .b[0], .b[2], .c[]?)", input);
+
+    let users = r#"{"users":[{"n":1,"inactive":true},{"n":2},{"n":3,"inactive":true}]}"#;
+    let out = qj_compact("del(.users[] | select(.inactive))", users);
+    assert_eq!(out.trim(), r#"{"users":[{"n":2}]}"#);
+    assert_jq_compat("del(.users[] | select(.inactive))", users);
+
+    let out = qj_compact("del(.b[0:2], .b[3])", input);
+    assert_eq!(out.trim(), r#"{"a":1,"b":[2],"c":{"x":1,"y":2},"d":4}"#);
+    assert_jq_compat("del(.b[0:2], .b[3])", input);
+
+    // Paths that don't exist are ignored
+    let out = qj_compact("del(.nope, .b[10], .c.z, .a.b?)", input);
+    assert_eq!(out.trim(), input);
+    assert_jq_compat("del(.nope, .b[10], .c.z, .a.b?)", input);
+}
+
+#[test]
+fn del_type_errors() {
+    let (code, _, stderr) = qj_exit(&["-c", "del(.a.b)"], r#"{"a":1}"#);
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains("Cannot index number with string \"b\""),
+        "{stderr}"
+    );
+    let (code, _, stderr) = qj_exit(&["-c", "del(.[0])"], r#"{"a":1}"#);
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains("Cannot index object with number"),
+        "{stderr}"
+    );
+}
+
 #[test]
 fn jq_compat_recursive_descent() {
     assert_jq_compat("[.. | numbers]", r#"{"a":1,"b":{"c":2}}"#);
@@ -2142,6 +3157,51 @@ fn jq_compat_any_all() {
     assert_jq_compat("all(. > 0)", "[1,2,3]");
 }
 
+#[test]
+fn any_all_short_circuit() {
+    // Each of these would take minutes if the generator ran to completion.
+    let start = std::time::Instant::now();
+    for (filter, expected) in [
+        ("any(range(1e9); . == 3)", "true"),
+        ("any(repeat(1); . == 1)", "true"),
+        ("all(range(1e9); . < 3)", "false"),
+        ("[1,2,-1] | all(.[], range(1e9); . > 0)", "false"),
+        ("any(1, error(\"x\"); . == 1)", "true"),
+        ("all(1, error(\"x\"); . == 2)", "false"),
+        ("any(1; true, error(\"x\"))", "true"),
+        ("isempty(range(1e9))", "false"),
+        ("isempty(1, error(\"x\"))", "false"),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-nc", filter], "");
+        assert_eq!((code, stdout.trim()), (0, expected), "{filter}: {stderr}");
+    }
+    assert!(start.elapsed().as_secs() < 30, "took {:?}", start.elapsed());
+}
+
+#[test]
+fn any_all_forms_and_errors() {
+    for filter in [
+        "any, all",
+        "any(. == false), all(type == \"number\")",
+        "[any(.[]; . == 1), all(.[]; . != null)]",
+    ] {
+        assert_jq_compat(filter, r#"{"a":false,"b":1}"#);
+    }
+    assert_jq_compat("[any, all]", "[]");
+    assert_jq_compat("[any(empty; .), all(empty; .)]", "null");
+    assert_jq_compat("any(. > 2), all(. > 0)", "[]");
+    // Errors before the answer is known still surface.
+    assert_jq_compat("try any(error(\"x\"), 1; . == 1) catch .", "null");
+    assert_jq_compat("try all(1; error(\"y\")) catch .", "null");
+    assert_jq_compat("try isempty(error(\"z\")) catch .", "null");
+    let (code, _, stderr) = qj_exit(&["any"], "3");
+    assert_eq!(code, 5);
+    assert_eq!(
+        stderr.trim(),
+        "qj: error (at <stdin>:0): Cannot iterate over number (3)"
+    );
+}
+
 // --- Phase 1: Operator Precedence ---
 
 #[test]
@@ -2191,6 +3251,37 @@ fn jq_compat_unique_sorted() {
     assert_jq_compat("unique", "[3,1,2,1,3]");
 }
 
+#[test]
+fn sort_nan_before_negative_infinity() {
+    let out = qj_compact(
+        "[1, nan, 0, -infinite, 0.5, nan, infinite, -3] | sort",
+        "null",
+    );
+    assert_eq!(
+        out.trim(),
+        "[null,null,-1.7976931348623157e+308,-3,0,0.5,1,1.7976931348623157e+308]"
+    );
+    for f in [
+        "[1, nan, 0] | sort",
+        "[nan, infinite, -infinite, 1, 0.5, -2, nan] | sort",
+        "[infinite, nan, -infinite] | sort",
+        "[[1, nan], [1, -infinite], [nan]] | sort",
+        "[nan, 3, nan, 1] | unique",
+        "[nan, nan] | unique",
+        "[nan, 1.5, -infinite] | min",
+        "[nan, 1.5, -infinite] | max",
+        "[-infinite, nan, 2] | min",
+        "[{a: 1}, {a: nan}, {a: -infinite}] | sort_by(.a)",
+        "[{a: 1}, {a: nan}, {a: -infinite}] | min_by(.a)",
+        "[{a: 1}, {a: nan}, {a: -infinite}] | max_by(.a)",
+        "[nan, 1, nan, -infinite] | group_by(.)",
+        "[{a: nan}, {a: 2}] | unique_by(.a)",
+        "[nan, -infinite, 0, 1] | bsearch(0), bsearch(-1), bsearch(5)",
+    ] {
+        assert_jq_compat(f, "null");
+    }
+}
+
 // --- Phase 1: range() ---
 
 #[test]
@@ -2269,6 +3360,33 @@ fn math_infinite_isinfinite() {
     assert_jq_compat("infinite | isinfinite", "null");
 }
 
+#[test]
+fn non_finite_numbers_serialize_like_jq() {
+    for filter in [
+        "[nan, infinite, -infinite]",
+        "{a: nan} | tojson",
+        "nan | tostring",
+        "[nan, -infinite] | tostring, @json, @text",
+        "{a: [nan], b: {c: infinite}}",
+    ] {
+        assert_jq_compat(filter, "null");
+    }
+    let (_, pretty, _) = qj_exit(&["-n", "[nan, infinite, -infinite]"], "");
+    assert_eq!(
+        pretty,
+        "[\n  null,\n  1.7976931348623157e+308,\n  -1.7976931348623157e+308\n]\n"
+    );
+    for flag in ["-r", "-a", "-S"] {
+        let (_, out, _) = qj_exit(&["-nc", flag, "{b: -infinite, a: nan}"], "");
+        let want = if flag == "-S" {
+            "{\"a\":null,\"b\":-1.7976931348623157e+308}\n"
+        } else {
+            "{\"b\":-1.7976931348623157e+308,\"a\":null}\n"
+        };
+        assert_eq!(out, want, "{flag}");
+    }
+}
+
 #[test]
 fn math_isfinite() {
     let out = qj_compact("isfinite", "42");
@@ -2328,6 +3446,23 @@ fn jq_compat_object_generator() {
     assert_jq_compat("[{x: (1,2)}]", "null");
 }
 
+#[test]
+fn jq_compat_object_mixed_key_forms() {
+    let input = r#"{"a":"A","b":"B","n":1}"#;
+    assert_jq_compat(
+        r#". as $obj | "v" as $x | {a: 1, $x, "b": 2, "c\(.n)": 3, @base64 "d\(.a)": 4, (.b): 5, "a", $obj,}"#,
+        input,
+    );
+    assert_jq_compat(
+        r#""v" as $x | [{$x, "k\(1,2)": (3,4), (.a,.b): .n}]"#,
+        input,
+    );
+    assert_jq_compat(r#"[{"\(.a,.b)-\(1,2)": true}]"#, input);
+    assert_jq_compat(r#"["\(1,2):\(3,4)"]"#, input);
+    assert_jq_compat("try {(.n): 1} catch .", input);
+    assert_jq_compat("{a: 1, b: 2, a: 3}", input);
+}
+
 // --- Phase 1: String Fixes + New Builtins ---
 
 #[test]
@@ -2358,6 +3493,37 @@ fn string_implode() {
     assert_jq_compat("implode", "[97,98,99]");
 }
 
+#[test]
+fn explode_implode_astral_plane() {
+    let out = qj_compact("explode", r#""🎉""#);
+    assert_eq!(out.trim(), "[127881]");
+    let out = qj_compact("implode", "[127881]");
+    assert_eq!(out.trim(), r#""🎉""#);
+    assert_jq_compat("explode", r#""a🎉b""#);
+    assert_jq_compat("explode | implode", r#""a🎉b""#);
+    // Lone surrogates and out-of-range codepoints become U+FFFD, as in
+    // jq 1.7 (1.6 aborts on them)
+    let out = qj_compact("implode | explode", "[55296,57343,1114112,-1]");
+    assert_eq!(out.trim(), "[65533,65533,65533,65533]");
+}
+
+#[test]
+fn ascii_codepoint_to_string() {
+    let out = qj_compact("[.[] | ascii]", "[65,97,48.5,0]");
+    assert_eq!(out.trim(), r#"["A","a","0","\u0000"]"#);
+    let (code, _, stderr) = qj_exit(&["-c", "ascii"], "128");
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains("number (128) is not an ASCII codepoint (0-127)"),
+        "{stderr}"
+    );
+    let out = qj_compact("[.[] | try ascii catch .]", r#"["A",-1,null]"#);
+    assert_eq!(
+        out.trim(),
+        r#"["string (\"A\") is not an ASCII codepoint (0-127)","number (-1) is not an ASCII codepoint (0-127)","null (null) is not an ASCII codepoint (0-127)"]"#
+    );
+}
+
 #[test]
 fn tojson_fromjson() {
     let out = qj_compact("[1,2] | tojson", "null");
@@ -2421,12 +3587,81 @@ fn indices_string() {
     assert_jq_compat(r#"indices("o")"#, r#""foobar""#);
 }
 
+#[test]
+fn indices_index_rindex_with_array_needles() {
+    let input = "[0,1,2,1,3,1,4]";
+    for (filter, expected) in [
+        ("indices([1,2])", "[1]"),
+        ("indices(1)", "[1,3,5]"),
+        ("[index([1,2]), rindex([1,2])]", "[1,1]"),
+        ("[index([1,3]), rindex([1])]", "[3,5]"),
+        ("indices([1,2,1,3,1,4,0,9])", "[]"),
+        ("indices([0,1,2,1,3,1,4])", "[0]"),
+        ("[indices([]), index([]), rindex([])]", "[[],null,null]"),
+    ] {
+        assert_eq!(qj_compact(filter, input).trim(), expected, "{filter}");
+        assert_jq_compat(filter, input);
+    }
+    // Overlapping matches, and needles containing null
+    assert_jq_compat("indices([1,1])", "[1,1,1]");
+    assert_jq_compat(
+        "[indices([null]), index([null,1]), rindex(null)]",
+        "[null,1,null]",
+    );
+    assert_eq!(
+        qj_compact(
+            "[indices([null]), index([null,1]), rindex(null)]",
+            "[null,1,null]"
+        )
+        .trim(),
+        "[[0,2],0,2]"
+    );
+}
+
+#[test]
+fn indices_on_null_and_mismatched_types() {
+    assert_eq!(
+        qj_compact("[indices(1), index(1), rindex(\"a\")]", "null").trim(),
+        "[null,null,null]"
+    );
+    for (filter, input, error) in [
+        ("indices([1])", "null", "Cannot index null with array"),
+        ("index(1)", r#""abc""#, "Cannot index string with number"),
+        (".[true]", "null", "Cannot index null with boolean"),
+    ] {
+        let (code, _, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{filter}");
+        assert!(stderr.contains(error), "{filter}: {stderr}");
+    }
+}
+
 #[test]
 fn trim_builtin() {
     let out = qj_compact("trim", r#""  hello  ""#);
     assert_eq!(out.trim(), r#""hello""#);
 }
 
+#[test]
+fn trim_strips_ascii_whitespace_only() {
+    let out = qj_compact(
+        "[trim, ltrim, rtrim]",
+        r#"" \t\n\r\f\u000bhi\u000b\f\r\n\t ""#,
+    );
+    assert_eq!(
+        out.trim(),
+        r#"["hi","hi\u000b\f\r\n\t "," \t\n\r\f\u000bhi"]"#
+    );
+    // NBSP, NEL, and the Unicode spaces are not whitespace to jq
+    let out = qj_compact(
+        "[trim, ltrim, rtrim] | map(explode)",
+        r#"" \u00a0\u0085x\u2028\u3000 ""#,
+    );
+    assert_eq!(
+        out.trim(),
+        "[[160,133,120,8232,12288],[160,133,120,8232,12288,32],[32,160,133,120,8232,12288]]"
+    );
+}
+
 #[test]
 fn jq_compat_string_builtins() {
     assert_jq_compat(r#"split("")"#, r#""abc""#);
@@ -2728,6 +3963,35 @@ fn jq_compat_abs() {
     assert_jq_compat("abs", "-42");
 }
 
+#[test]
+fn abs_keeps_kind_and_literal_text() {
+    // jq 1.7.1: `def abs: if type == "number" and . < 0 then -. else . end;`
+    // Non-negative numbers (and non-numbers) pass through untouched.
+    for (input, expected) in [
+        ("1.10", "1.10"),
+        ("-1.10", "1.1"),
+        ("-0.0", "-0.0"),
+        ("-5.5", "5.5"),
+        ("100000000000000000000000001", "100000000000000000000000001"),
+        (
+            "-100000000000000000000000001",
+            "100000000000000000000000001",
+        ),
+        (r#""abc""#, r#""abc""#),
+        ("null", "null"),
+        ("[-1]", "[-1]"),
+    ] {
+        assert_eq!(qj_compact("abs", input).trim(), expected, "{input}");
+    }
+}
+
+#[test]
+fn length_on_numbers_keeps_kind() {
+    assert_eq!(qj_compact("length", "-5.5").trim(), "5.5");
+    assert_eq!(qj_compact("length", "-7").trim(), "7");
+    assert_jq_compat("length", "-5.5");
+}
+
 #[test]
 fn debug_passthrough() {
     // debug should pass through the value
@@ -2781,6 +4045,121 @@ fn jq_compat_delpaths() {
     assert_jq_compat(r#"delpaths([["a"]])"#, r#"{"a":1,"b":2}"#);
 }
 
+#[test]
+fn delpaths_multiple_and_slice_paths() {
+    let input = r#"{"a":[0,1,2,3,4],"b":2}"#;
+    let out = qj_compact(
+        r#"delpaths([["a",0],["a",{"start":2,"end":4}],["b"]])"#,
+        input,
+    );
+    assert_eq!(out.trim(), r#"{"a":[1,4]}"#);
+    assert_jq_compat(
+        r#"delpaths([["a",0],["a",{"start":2,"end":4}],["b"]])"#,
+        input,
+    );
+    assert_jq_compat(r#"delpaths([["a",-1],["x","y"]])"#, input);
+    assert_jq_compat("delpaths([[]])", input);
+
+    let (code, _, stderr) = qj_exit(&["-c", "delpaths([\"a\"])"], input);
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains("Path must be specified as array, not string"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn numeric_string_keys_only_index_objects() {
+    let inputs = [
+        r#"{"0":{"1":2}}"#,
+        "[[1,2],[3]]",
+        r#"{"0":[5,{"1":6}]}"#,
+        r#"[{"0":1}]"#,
+        "null",
+    ];
+    let filters = [
+        r#"getpath(["0"])"#,
+        "getpath([0])",
+        r#"getpath(["0","1"])"#,
+        r#"getpath([0,"1"])"#,
+        r#"[getpath(["0"],["1"])]"#,
+        r#"setpath(["0"]; 9)"#,
+        "setpath([0]; 9)",
+        r#"setpath(["0",1]; 9)"#,
+        r#"delpaths([["0","1"]])"#,
+        "delpaths([[0,1]])",
+        r#"del(.["0"])"#,
+        "del(.[0])",
+        r#"del(.[0]["1"])"#,
+        "[paths]",
+        "[leaf_paths]",
+        r#".["0"] = 1"#,
+        ".[0] = 1",
+        r#".["0"]["1"] |= . + 1"#,
+        ".[0][1] |= . + 1",
+        "[paths as $p | getpath($p)]",
+    ];
+    for input in inputs {
+        for filter in filters {
+            assert_jq_compat(&format!("try ({filter}) catch ."), input);
+        }
+    }
+
+    let cases = [
+        (
+            r#"getpath(["0"])"#,
+            "[[1,2]]",
+            r#"Cannot index array with string "0""#,
+        ),
+        (
+            "getpath([0])",
+            r#"{"0":1}"#,
+            "Cannot index object with number",
+        ),
+        (
+            r#"setpath(["0"]; 9)"#,
+            "[1]",
+            r#"Cannot index array with string "0""#,
+        ),
+        (
+            "delpaths([[0,1]])",
+            r#"{"0":[1,2]}"#,
+            "Cannot index object with number",
+        ),
+        (".[0] = 1", r#"{"0":1}"#, "Cannot index object with number"),
+        (
+            r#".["0"] |= 1"#,
+            "[1]",
+            r#"Cannot index array with string "0""#,
+        ),
+    ];
+    for (filter, input, msg) in cases {
+        let (code, stdout, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{filter} on {input}");
+        // A failed update prints no half-updated value.
+        assert!(stdout.is_empty(), "{filter} on {input}: {stdout}");
+        assert!(stderr.contains(msg), "{filter} on {input}: {stderr}");
+    }
+    assert_eq!(
+        qj_compact(r#"[paths]"#, r#"{"0":{"1":2}}"#).trim(),
+        r#"[["0"],["0","1"]]"#
+    );
+}
+
+#[test]
+fn failed_update_outputs_nothing() {
+    let input = r#"{"a":"x","b":5}"#;
+    for filter in [".a += 1", ".b.c = 1", ".a[] = 1", ".a |= error(\"no\")"] {
+        let (code, stdout, _) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{filter}");
+        assert!(stdout.is_empty(), "{filter}: {stdout}");
+        assert_jq_compat(&format!("[try ({filter}) catch \"caught\"]"), input);
+    }
+    assert_jq_compat("[paths(type == \"number\")]", "5");
+    assert_jq_compat("[leaf_paths]", "null");
+    assert_jq_compat(".[0] |= empty", "null");
+}
+
 #[test]
 fn todate_builtin() {
     let out = qj_compact("todate", "0");
@@ -2860,30 +4239,151 @@ fn env_returns_real_vars() {
 }
 
 #[test]
-fn jq_compat_strftime_extended() {
-    assert_jq_compat(r#"0 | strftime("%Y-%m-%d")"#, "null");
-    assert_jq_compat(r#"0 | strftime("%H:%M:%S")"#, "null");
-    assert_jq_compat(r#"0 | strftime("%A")"#, "null");
-    assert_jq_compat(r#"0 | strftime("%j")"#, "null");
-}
-
-#[test]
-fn jq_compat_todate_fromdate_roundtrip() {
-    assert_jq_compat("0 | todate", "null");
-    assert_jq_compat("1705318245 | todate", "null");
-    assert_jq_compat(r#""1970-01-01T00:00:00Z" | fromdate"#, "null");
-    assert_jq_compat(r#""2024-01-15T11:30:45Z" | fromdate"#, "null");
+fn env_var_is_a_bound_variable() {
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+            .args(args)
+            .env("QJ_TEST_ENV", "v")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{args:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run(&["-n", "env == $ENV"]), "true\n");
+    assert_eq!(run(&["-n", "def f: $ENV.QJ_TEST_ENV; f"]), "\"v\"\n");
+    assert_eq!(run(&["-n", "$ENV as {QJ_TEST_ENV: $x} | $x"]), "\"v\"\n");
+    assert_eq!(
+        run(&["-n", "[1, 2] | map($ENV.QJ_TEST_ENV + tostring)"]),
+        "[\n  \"v1\",\n  \"v2\"\n]\n"
+    );
+    // Like jq, filter bindings shadow $ENV, and $ENV shadows --arg ENV.
+    assert_eq!(run(&["-n", "1 as $ENV | $ENV"]), "1\n");
+    assert_eq!(
+        run(&[
+            "-nc",
+            "--arg",
+            "ENV",
+            "x",
+            "[$ENV.QJ_TEST_ENV, $ARGS.named]"
+        ]),
+        "[\"v\",{\"ENV\":\"x\"}]\n"
+    );
 }
 
 #[test]
-fn jq_compat_significand() {
-    assert_jq_compat("1 | significand", "null");
-    assert_jq_compat("8 | significand", "null");
-    assert_jq_compat("0 | significand", "null");
+fn env_lookups_keep_ndjson_fast_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("logs.ndjson");
+    std::fs::write(
+        &file,
+        "{\"host\":\"web1\",\"msg\":\"a\"}\n{\"host\":\"web2\",\"msg\":\"b\"}\n",
+    )
+    .unwrap();
+    let run = |filter: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+            .args(["-c", "--debug-timing", filter])
+            .arg(&file)
+            .env("QJ_TEST_HOST", "web2")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{filter}");
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    for filter in [
+        "select(.host == env.QJ_TEST_HOST) | .msg",
+        "select(.host == $ENV.QJ_TEST_HOST) | .msg",
+        r#"select(.host == "\($ENV.QJ_TEST_HOST)") | .msg"#,
+    ] {
+        let (stdout, stderr) = run(filter);
+        assert_eq!(stdout, "\"b\"\n", "{filter}");
+        assert!(stderr.contains("fast path"), "{filter}: {stderr}");
+    }
+    // Shadowed lookups are evaluated as written.
+    let (stdout, _) =
+        run("def env: {QJ_TEST_HOST: \"web1\"}; select(.host == env.QJ_TEST_HOST) | .msg");
+    assert_eq!(stdout, "\"a\"\n");
+    let (stdout, _) =
+        run(r#"{QJ_TEST_HOST: "web1"} as $ENV | select(.host == $ENV.QJ_TEST_HOST) | .msg"#);
+    assert_eq!(stdout, "\"a\"\n");
 }
 
 #[test]
-fn variable_binding_basic() {
+fn constant_subexpressions_keep_ndjson_fast_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("logs.ndjson");
+    std::fs::write(
+        &file,
+        "{\"host\":\"web1\",\"msg\":{\"text\":\"a\"}}\n{\"host\":\"web2\",\"msg\":{\"text\":\"b\"}}\n",
+    )
+    .unwrap();
+    for filter in [
+        r#"if "prod" == "prod" then select(.host == "web2") else . end | .msg.text"#,
+        r#"select(true) | select(.host == "web" + "2") | . | .msg | .text"#,
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+            .args(["-c", "--debug-timing", filter])
+            .arg(&file)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "\"b\"\n",
+            "{filter}"
+        );
+        assert!(stderr.contains("fast path"), "{filter}: {stderr}");
+    }
+}
+
+#[test]
+fn field_paths_match_field_pipes() {
+    let input = r#"{"a":{"b":{"c":1}},"n":null,"s":"x"}"#;
+    assert_eq!(
+        qj_compact(".a.b.c, .n.x.y, .a.z.y", input).trim(),
+        "1\nnull\nnull"
+    );
+    assert_eq!(
+        qj_compact(
+            "([paths(..)] | length), path(.a.b.c), (.a.b.c |= . + 1 | .a)",
+            input
+        )
+        .trim(),
+        "4\n[\"a\",\"b\",\"c\"]\n{\"b\":{\"c\":2}}"
+    );
+    let (code, stdout, stderr) = qj_exit(&["-c", ".a.b.c.d"], input);
+    assert_eq!((code, stdout.as_str()), (5, ""));
+    assert!(stderr.contains("Cannot index number"), "{stderr}");
+    assert_eq!(qj_compact("[.s.t.u?]", input).trim(), "[]");
+}
+
+#[test]
+fn jq_compat_strftime_extended() {
+    assert_jq_compat(r#"0 | strftime("%Y-%m-%d")"#, "null");
+    assert_jq_compat(r#"0 | strftime("%H:%M:%S")"#, "null");
+    assert_jq_compat(r#"0 | strftime("%A")"#, "null");
+    assert_jq_compat(r#"0 | strftime("%j")"#, "null");
+}
+
+#[test]
+fn jq_compat_todate_fromdate_roundtrip() {
+    assert_jq_compat("0 | todate", "null");
+    assert_jq_compat("1705318245 | todate", "null");
+    assert_jq_compat(r#""1970-01-01T00:00:00Z" | fromdate"#, "null");
+    assert_jq_compat(r#""2024-01-15T11:30:45Z" | fromdate"#, "null");
+}
+
+#[test]
+fn jq_compat_significand() {
+    assert_jq_compat("1 | significand", "null");
+    assert_jq_compat("8 | significand", "null");
+    assert_jq_compat("0 | significand", "null");
+}
+
+#[test]
+fn variable_binding_basic() {
     assert_jq_compat(". as $x | $x", r#"{"a":1}"#);
 }
 
@@ -3017,6 +4517,48 @@ fn jq_compat_foreach() {
     assert_jq_compat("[foreach .[] as $x (0; . + $x)]", "[1,2,3]");
 }
 
+#[test]
+fn reduce_foreach_loop_variable_scoping() {
+    // Shadowing: the inner loop rebinds $i without touching the outer one
+    let nested = "reduce range(4) as $i (0; . + (reduce range($i) as $i (0; . + $i)) + $i)";
+    let out = qj_compact(nested, "null");
+    assert_eq!(out.trim(), "10");
+    assert_jq_compat(nested, "null");
+
+    let shadow = r#"1 as $x | [reduce (2, 3) as $x (0; . + $x), $x]"#;
+    let out = qj_compact(shadow, "null");
+    assert_eq!(out.trim(), "[5,1]");
+    assert_jq_compat(shadow, "null");
+
+    // The update captures $x in a function and a nested binding
+    let captured = "[foreach .[] as $x (0; def f: $x; . + f; [., ($x as $y | $y)])]";
+    let out = qj_compact(captured, "[1,2,3]");
+    assert_eq!(out.trim(), "[[1,1],[3,2],[6,3]]");
+    assert_jq_compat(captured, "[1,2,3]");
+
+    let paired = "reduce .[] as $x ([]; . + [[$x, (foreach range($x) as $x (0; . + $x))]])";
+    let out = qj_compact(paired, "[2,3]");
+    assert_eq!(out.trim(), "[[2,0,1],[3,0,1,3]]");
+    assert_jq_compat(paired, "[2,3]");
+
+    // Destructuring patterns still bind every element
+    let destructured = "reduce .[] as [$k, $v] ({}; .[$k] = $v)";
+    assert_jq_compat(destructured, r#"[["a",1],["b",2]]"#);
+}
+
+#[test]
+fn reduce_long_generator() {
+    let out = qj_compact("reduce range(200000) as $i (0; . + $i)", "null");
+    assert_eq!(out.trim(), "19999900000");
+    let out = qj_compact(
+        r#"reduce range(10000) as $i (""; . + "x") | length"#,
+        "null",
+    );
+    assert_eq!(out.trim(), "10000");
+    let out = qj_compact("[foreach range(100000) as $i (0; . + 1)] | last", "null");
+    assert_eq!(out.trim(), "100000");
+}
+
 #[test]
 fn jq_compat_try_catch() {
     assert_jq_compat("try .a catch .", r#"{"a":1}"#);
@@ -3024,6 +4566,45 @@ fn jq_compat_try_catch() {
     assert_jq_compat("try null catch .", "null");
 }
 
+#[test]
+fn jq_compat_error_values() {
+    // Non-string error values reach the handler untouched.
+    assert_jq_compat("try error({code: 42}) catch .code", "null");
+    assert_jq_compat("try error([1, {a: 2}]) catch .[1].a", "null");
+    assert_jq_compat(r#"try error("x") catch type"#, "null");
+    // `error` with no argument raises its input.
+    assert_jq_compat("[.[] | try error catch .]", r#"[1,"a",{"b":null},[2]]"#);
+    // Re-raising from a catch handler keeps the original value.
+    assert_jq_compat("try (try error({a: 1}) catch error) catch .a", "null");
+    assert_jq_compat(
+        "try (try error({a: 1}) catch error(. + {b: 2})) catch .",
+        "null",
+    );
+}
+
+#[test]
+fn error_uncaught_non_string_format() {
+    let (code, stdout, stderr) = qj_exit(&["error({a: 1, b: [1, 2]})"], "null");
+    assert_eq!(code, 5);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr.trim_end(),
+        r#"qj: error (at <stdin>:0) (not a string): {"a":1,"b":[1,2]}"#
+    );
+
+    let (code, _, stderr) = qj_exit(&["error"], "[1,2]\n");
+    assert_eq!(code, 5);
+    assert_eq!(
+        stderr.trim_end(),
+        "qj: error (at <stdin>:1) (not a string): [1,2]"
+    );
+
+    // String errors print their text without the marker.
+    let (code, _, stderr) = qj_exit(&[r#"error("boom")"#], "null\n");
+    assert_eq!(code, 5);
+    assert_eq!(stderr.trim_end(), "qj: error (at <stdin>:1): boom");
+}
+
 #[test]
 fn jq_compat_elif() {
     assert_jq_compat(
@@ -3105,6 +4686,38 @@ fn walk_identity() {
     assert_jq_compat("walk(.)", r#"{"a":[1,2],"b":"c"}"#);
 }
 
+#[test]
+fn walk_deeply_nested_array() {
+    // Built by the evaluator: the JSON parser caps nesting well below this.
+    let out = qj_compact(
+        r#"reduce range(10000) as $i (0; [.]) | walk(if type == "number" then . + 1 else . end) | flatten"#,
+        "null",
+    );
+    assert_eq!(out.trim(), "[1]");
+    let out = qj_compact(
+        r#"reduce range(10000) as $i ("x"; {a: .}) | walk(if type == "object" then .a else . end)"#,
+        "null",
+    );
+    assert_eq!(out.trim(), r#""x""#);
+}
+
+#[test]
+fn walk_wide_structure_every_level() {
+    let out = qj_compact(
+        r#"[range(2000) | {a: ., b: [., {c: .}]}] | walk(if type == "number" then . * 2 else . end)
+           == [range(2000) | . * 2 | {a: ., b: [., {c: .}]}]"#,
+        "null",
+    );
+    assert_eq!(out.trim(), "true");
+    let (code, stdout, stderr) = qj_exit(
+        &["-c", r#"walk(if . == 2 then error("two") else . end)"#],
+        "[1,[2],3]",
+    );
+    assert_eq!(code, 5);
+    assert!(stdout.is_empty(), "{stdout}");
+    assert!(stderr.contains("two"), "{stderr}");
+}
+
 #[test]
 fn jq_compat_variables() {
     assert_jq_compat(". as $x | $x", "42");
@@ -3189,6 +4802,117 @@ fn argjson_object() {
     );
 }
 
+#[test]
+fn argnum_and_argbool() {
+    let (code, stdout, stderr) = qj_exit(
+        &[
+            "-nc",
+            "--argnum",
+            "n",
+            "42",
+            "--argnum",
+            "f",
+            " -1.5 ",
+            "--argbool",
+            "t",
+            "true",
+            "--argbool",
+            "one",
+            "1",
+            "--argbool",
+            "zero",
+            "0",
+            "[$n + 1, $f, $t, $one, $zero], $ARGS.named",
+        ],
+        "",
+    );
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(
+        stdout,
+        "[43,-1.5,true,true,false]\n{\"n\":42,\"f\":-1.5,\"t\":true,\"one\":true,\"zero\":false}\n"
+    );
+}
+
+#[test]
+fn typed_args_reject_bad_values() {
+    for (flag, value, reason) in [
+        ("--argnum", "", r#"expected a number, got """#),
+        ("--argnum", "12px", r#"expected a number, got "12px""#),
+        ("--argnum", r#""5""#, r#"expected a number, got "\"5\"""#),
+        (
+            "--argbool",
+            "yes",
+            r#"expected true, false, 1 or 0, got "yes""#,
+        ),
+        ("--argbool", "", r#"expected true, false, 1 or 0, got """#),
+        ("--argjson", "", r#"invalid JSON text """#),
+        ("--argjson", "1 2", r#"invalid JSON text "1 2""#),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-n", flag, "count", value, "$count"], "");
+        assert_eq!(code, 2, "{flag} {value:?}");
+        assert_eq!(stdout, "");
+        assert_eq!(stderr, format!("qj: error: {flag} count: {reason}\n"));
+    }
+}
+
+#[test]
+fn env_args_bind_prefixed_variables() {
+    let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .args([
+            "-nc",
+            "--env-args",
+            "QJ_TEST_",
+            "--arg",
+            "user",
+            "alice",
+            "[$user, $Home], $ARGS.named",
+        ])
+        .env("QJ_TEST_user", "bob")
+        .env("QJ_TEST_Home", "/home/bob")
+        .env("QJ_TEST_", "skipped")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // --arg wins over the environment; the rest follow, sorted by name.
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "[\"alice\",\"/home/bob\"]\n{\"user\":\"alice\",\"Home\":\"/home/bob\"}\n"
+    );
+}
+
+#[test]
+fn repeated_named_arg_keeps_first() {
+    assert_jq_compat_with_flags(
+        &[
+            "-nc",
+            "--arg",
+            "x",
+            "1",
+            "--arg",
+            "x",
+            "2",
+            "$x, $ARGS.named",
+        ],
+        "",
+    );
+    assert_eq!(
+        qj_args(
+            &[
+                "-nc",
+                "--argnum",
+                "x",
+                "1",
+                "--argbool",
+                "x",
+                "0",
+                "$x, $ARGS.named"
+            ],
+            ""
+        ),
+        "1\n{\"x\":1}\n"
+    );
+}
+
 // --- --raw-input / -R ---
 
 #[test]
@@ -3261,6 +4985,55 @@ fn join_output_compact() {
     assert_eq!(qj_args(&["-cj", ".", "--"], r#"{"a":1}"#), r#"{"a":1}"#);
 }
 
+// --- --join ---
+
+#[test]
+fn join_flag_joins_array_outputs() {
+    assert_eq!(
+        qj_args(&["--join", ", ", ".[]"], r#"[["a","b"],"c",{"d":1}]"#),
+        "a, b\nc\n{\n  \"d\": 1\n}\n"
+    );
+    // Elements stringify as in join: null is empty, numbers and booleans JSON
+    assert_eq!(
+        qj_args(&["--join", "-", "."], r#"[1,null,true,"x",1.5]"#),
+        "1--true-x-1.5\n"
+    );
+}
+
+#[test]
+fn join_flag_empty_arrays() {
+    assert_eq!(qj_args(&["--join", ",", ".[]"], "[[],[\"a\"]]"), "\na\n");
+    assert_eq!(qj_args(&["-j", "--join", ",", ".[]"], "[[],[\"a\"]]"), "a");
+}
+
+#[test]
+fn join_flag_rejects_nested_containers() {
+    let (code, stdout, stderr) = qj_exit(&["--join", ",", ".[]"], r#"[["a"],[["b"]],["c"]]"#);
+    assert_eq!(code, 5);
+    assert_eq!(stdout, "a\n");
+    assert!(
+        stderr.contains(r#"string ("") and array (["b"]) cannot be added"#),
+        "{stderr}"
+    );
+    // NDJSON input takes the same route
+    let (_, stdout, stderr) = qj_exit(&["--join", ",", ".t"], "{\"t\":[\"a\"]}\n{\"t\":[{}]}\n");
+    assert_eq!(stdout, "a\n");
+    assert!(
+        stderr.contains("and object ({}) cannot be added"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn join_flag_conflicts() {
+    for flag in ["-c", "--raw-output0", "--logfmt", "--jsonl-output"] {
+        let (code, stdout, stderr) = qj_exit(&["--join", ",", flag, "."], "[]");
+        assert_eq!(code, 2, "{flag} should be rejected");
+        assert!(stdout.is_empty());
+        assert!(stderr.contains("cannot be used with"), "{flag}: {stderr}");
+    }
+}
+
 // --- -M (monochrome — no-op, but should not error) ---
 
 #[test]
@@ -3362,6 +5135,60 @@ fn assign_update_empty_deletion() {
     assert_jq_compat("(.[] | select(. >= 2)) |= empty", "[1,5,3,0,7]");
 }
 
+#[test]
+fn update_with_empty_deletes_once_every_path_is_visited() {
+    // jq 1.7 reads each path from the result so far and deletes the paths
+    // whose update is empty at the end, so no deletion shifts another path.
+    for (filter, input, expected) in [
+        (".[] |= empty", "[1,2,3,4,5]", "[]"),
+        ("(.[0], .[1]) |= empty", "[1,2,3]", "[3]"),
+        ("(.[0], .[0]) |= empty", "[1,2,3]", "[2,3]"),
+        ("(.[-1], .[0]) |= empty", "[1,2,3]", "[2]"),
+        (
+            "(.[0], .[1]) |= if . == 1 then empty else 10 end",
+            "[1,2]",
+            "[10]",
+        ),
+        (
+            "(.a[0], .a[1]) |= empty",
+            r#"{"a":[1,2,3]}"#,
+            r#"{"a":[3]}"#,
+        ),
+        (
+            "(.[] | .[]) |= empty",
+            r#"{"x":[1,2,3],"y":[4]}"#,
+            r#"{"x":[],"y":[]}"#,
+        ),
+        (".a[] |= empty", r#"{"a":[1,2,3]}"#, r#"{"a":[]}"#),
+        ("(., .a) |= empty", r#"{"a":1}"#, "null"),
+        (".[] |= select(. % 2 == 0)", "[1,2,3,4,5,6]", "[2,4,6]"),
+    ] {
+        assert_eq!(
+            qj_compact(filter, input).trim(),
+            expected,
+            "{filter} on {input}"
+        );
+    }
+    // Deleting object keys shifts nothing, so jq 1.6 agrees on these.
+    for (filter, input) in [
+        (".[] |= empty", r#"{"a":1,"b":2,"c":3,"d":4}"#),
+        ("(.a, .b) |= empty", r#"{"a":1,"b":2,"c":3}"#),
+        ("(.b.c, .a) |= empty", r#"{"a":1,"b":{"c":2,"d":3}}"#),
+        ("(.a, .a.b) |= empty", r#"{"a":{"b":1},"c":2}"#),
+        ("(.a, .a) |= empty", r#"{"a":1,"b":2}"#),
+        ("(.[] | select(. > 1)) |= empty", r#"{"a":1,"b":2,"c":3}"#),
+        ("del(.[] | select(. > 1))", r#"{"a":1,"b":2,"c":3}"#),
+        (
+            ".[] |= if . > 2 then empty else . end",
+            r#"{"a":1,"b":3,"c":2,"d":5}"#,
+        ),
+        (".[].a |= empty", r#"[{"a":1,"b":2},{"a":3}]"#),
+        (".[] |= empty", r#"{"a":[1],"b":{"c":1}}"#),
+    ] {
+        assert_jq_compat(filter, input);
+    }
+}
+
 #[test]
 fn assign_nested_path() {
     assert_eq!(
@@ -3380,6 +5207,119 @@ fn assign_auto_create_structure() {
     assert_jq_compat(".[2][3] = 1", "[4]");
 }
 
+/// Each root × path pair, assigned with `=`, `|=`, `+=` and `//=`: on a
+/// missing leaf all four give the same result. `Err` holds jq's message.
+#[test]
+fn assign_vivification_matrix() {
+    let cases: [(&str, &str, Result<&str, &str>); 16] = [
+        ("null", ".a.b", Ok(r#"{"a":{"b":1}}"#)),
+        ("null", ".a[1]", Ok(r#"{"a":[null,1]}"#)),
+        ("null", ".[0].b", Ok(r#"[{"b":1}]"#)),
+        ("null", r#".a["k"][2]"#, Ok(r#"{"a":{"k":[null,null,1]}}"#)),
+        ("{}", ".a.b", Ok(r#"{"a":{"b":1}}"#)),
+        ("{}", ".a[1]", Ok(r#"{"a":[null,1]}"#)),
+        ("{}", ".[0].b", Err("Cannot index object with number")),
+        ("{}", r#".a["k"][2]"#, Ok(r#"{"a":{"k":[null,null,1]}}"#)),
+        ("[]", ".a.b", Err(r#"Cannot index array with string "a""#)),
+        ("[]", ".a[1]", Err(r#"Cannot index array with string "a""#)),
+        ("[]", ".[0].b", Ok(r#"[{"b":1}]"#)),
+        (
+            "[]",
+            r#".a["k"][2]"#,
+            Err(r#"Cannot index array with string "a""#),
+        ),
+        ("5", ".a.b", Err(r#"Cannot index number with string "a""#)),
+        ("5", ".a[1]", Err(r#"Cannot index number with string "a""#)),
+        ("5", ".[0].b", Err("Cannot index number with number")),
+        (
+            "5",
+            r#".a["k"][2]"#,
+            Err(r#"Cannot index number with string "a""#),
+        ),
+    ];
+    for (root, path, expected) in cases {
+        for op in ["=", "|=", "+=", "//="] {
+            let filter = format!("{path} {op} 1");
+            let (code, stdout, stderr) = qj_exit(&["-c", &filter], root);
+            match expected {
+                Ok(out) => assert_eq!(stdout.trim(), out, "{root} | {filter}: {stderr}"),
+                Err(msg) => {
+                    assert_eq!(code, 5, "{root} | {filter}: {stdout}");
+                    assert!(stderr.contains(msg), "{root} | {filter}: {stderr}");
+                }
+            }
+            assert_jq_compat(&filter, root);
+        }
+    }
+}
+
+#[test]
+fn assign_through_existing_wrong_type_errors() {
+    for (input, filter, msg) in [
+        (
+            r#"{"a":5}"#,
+            ".a.b = 1",
+            r#"Cannot index number with string "b""#,
+        ),
+        (
+            r#"{"a":[1]}"#,
+            ".a.b |= 1",
+            r#"Cannot index array with string "b""#,
+        ),
+        (
+            r#"{"a":{"b":1}}"#,
+            ".a[1] += 1",
+            "Cannot index object with number",
+        ),
+        ("[1]", ".[-2] = 5", "Out of bounds negative array index"),
+        ("[]", ".[-1] |= 5", "Out of bounds negative array index"),
+        (
+            "[]",
+            "setpath([-1]; 5)",
+            "Out of bounds negative array index",
+        ),
+        (
+            "[1,2]",
+            ".[0:1] = 5",
+            "A slice of an array can only be assigned another array",
+        ),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{input} | {filter}: {stdout}");
+        assert!(stderr.contains(msg), "{input} | {filter}: {stderr}");
+    }
+}
+
+#[test]
+fn assign_slices_vivify_null() {
+    for (input, filter, expected) in [
+        ("null", ".a[1:] = [9]", r#"{"a":[9]}"#),
+        ("null", ".[1:2] |= [\"x\"]", r#"["x"]"#),
+        ("null", ".[1:2] |= empty", "null"),
+        (
+            "null",
+            r#"setpath(["a",{"start":0,"end":1}]; [7])"#,
+            r#"{"a":[7]}"#,
+        ),
+        (
+            "[1,2]",
+            r#"setpath([{"start":1,"end":2}]; ["x"])"#,
+            r#"[1,"x"]"#,
+        ),
+        (
+            "[1,2,3]",
+            r#"setpath([{"start":0,"end":2},1]; 9)"#,
+            "[1,9,3]",
+        ),
+    ] {
+        assert_eq!(
+            qj_compact(filter, input).trim(),
+            expected,
+            "{input} | {filter}"
+        );
+    }
+}
+
 #[test]
 fn assign_update_object_construction() {
     assert_eq!(
@@ -3480,6 +5420,150 @@ fn regex_splits() {
     assert_jq_compat(r#"[splits("[,;]")]"#, r#""a,b;c""#);
 }
 
+#[test]
+fn regex_flags_jq_compat() {
+    let cases = [
+        (r#"test("a # letter\n b"; "x")"#, r#""ab""#),
+        (r#"test("a[ ]b"; "x")"#, r#""a b""#),
+        (r#"test("a.b"; "m")"#, r#""a\nb""#),
+        (r#"test("a.b"; "s")"#, r#""a\nb""#),
+        (r#"test("a.b"; "p")"#, r#""a\nb""#),
+        (r#"test("^b")"#, r#""a\nb""#),
+        (r#"[match("X*"; "gn") | .offset]"#, r#""aXbXX""#),
+        (r#"[match("a+|ab"; "l") | .string]"#, r#""ab aaaa""#),
+        (r#"[match("a|ab"; "gl") | .string]"#, r#""ab aaaa""#),
+        (
+            r#"[match("A B # spaced"; "gix") | .offset]"#,
+            r#""ab xAB ab""#,
+        ),
+        (r#"split(", *"; null)"#, r#""a,b, c""#),
+        (r#"split("[0-9]+"; "g")"#, r#""a1b22c""#),
+        (r#"[splits("A"; "i")]"#, r#""xaYAz""#),
+        (r#"[splits(", *"; "n")]"#, r#""a,b, c""#),
+        (r#"gsub("O"; "0"; "i")"#, r#""fOobar""#),
+        (r#"sub("o"; "0"; "g")"#, r#""foobar""#),
+    ];
+    for (filter, input) in cases {
+        assert_jq_compat(filter, input);
+    }
+    // scan/2 is jq 1.7+
+    assert_eq!(
+        qj_compact(r#"[scan("X*"; "n")]"#, r#""aXbXX""#).trim(),
+        r#"["X","XX"]"#
+    );
+    assert_eq!(
+        qj_compact(r#"[splits(", *"; "n")]"#, r#""a,b, c""#).trim(),
+        r#"["a","b","c"]"#
+    );
+    assert_eq!(
+        qj_compact(r#"[match("a|ab"; "gl") | .offset]"#, r#""ab aaaa""#).trim(),
+        "[0,3,4,5,6]"
+    );
+    assert_eq!(
+        qj_compact(r#"split("[0-9]+"; "g")"#, r#""a1b22c""#).trim(),
+        r#"["a","b","c"]"#
+    );
+}
+
+#[test]
+fn regex_invalid_flags_error() {
+    let (code, stdout, stderr) = qj_exit(&["-c", r#"test("a"; "gq")"#], r#""ab""#);
+    assert_eq!(code, 5);
+    assert!(stdout.is_empty());
+    assert!(
+        stderr.contains("gq is not a valid modifier string"),
+        "{stderr}"
+    );
+    let (code, _, stderr) = qj_exit(&["-c", r#"[splits("a"; 1)]"#], r#""ab""#);
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains(r#"string ("g") and number (1) cannot be added"#),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn regex_zero_length_and_group_edges() {
+    let cases = [
+        (r#"[match(""; "g") | .offset]"#, r#""abc""#),
+        (r#"[match("x?"; "g") | [.offset, .length]]"#, r#""abc""#),
+        (r#"[match("b*"; "g") | [.offset, .length]]"#, r#""abc""#),
+        (r#"[match(""; "g") | .offset]"#, r#""""#),
+        (r#"[match("é"; "g") | [.offset, .length]]"#, r#""éaé""#),
+        (r#"[splits("")]"#, r#""abc""#),
+        (r#"[scan("")]"#, r#""abc""#),
+        (r#"[scan("c?")]"#, r#""abc""#),
+        (r#"[scan("(a)(b)")]"#, r#""abab""#),
+        (r#"[capture("(?<a>z)")]"#, r#""abc""#),
+        (r#"capture("(?<x>z)?b")"#, r#""abc""#),
+        (r#"[match("(a)(?<n>b)").captures[] | .name]"#, r#""abc""#),
+        (r#"[match("(z)?b").captures]"#, r#""abc""#),
+        (r#"test(["B", "i"]), test(["z"])"#, r#""abc""#),
+        (r#"sub("$"; "!")"#, r#""abc""#),
+    ];
+    for (filter, input) in cases {
+        assert_jq_compat(filter, input);
+    }
+    // jq 1.7 fixed global replacement of empty matches (1.6 loops forever)
+    // and reports the groups of a zero-width match as matching empty.
+    for (filter, input, expected) in [
+        (r#"gsub(""; "-")"#, r#""abc""#, r#""-a-b-c""#),
+        (r#"gsub("x?"; "-")"#, r#""abc""#, r#""-a-b-c""#),
+        (r#"gsub(""; "|")"#, r#""éa""#, r#""|é|a""#),
+        (r#"gsub(""; "-")"#, r#""""#, r#""-""#),
+        (r#"[match("$"; "g") | .offset]"#, r#""abc""#, "[3]"),
+        (r#"[scan("(b)?")]"#, r#""abc""#, r#"[[""],["b"],[""]]"#),
+        (r#"capture("(?<x>z)?")"#, r#""abc""#, r#"{"x":""}"#),
+        (
+            r#"[match("(?<x>z)?"; "g").captures[0] | [.offset, .string]]"#,
+            r#""ab""#,
+            r#"[[0,""],[1,""]]"#,
+        ),
+    ] {
+        assert_eq!(qj_compact(filter, input).trim(), expected, "{filter}");
+    }
+}
+
+#[test]
+fn regex_type_errors() {
+    for (filter, input, message) in [
+        (
+            r#"test("a")"#,
+            "123",
+            "number (123) cannot be matched, as it is not a string",
+        ),
+        (
+            r#"[scan("a")]"#,
+            "null",
+            "null (null) cannot be matched, as it is not a string",
+        ),
+        (
+            r#"sub("a"; "b")"#,
+            "[1]",
+            "array ([1]) cannot be matched, as it is not a string",
+        ),
+        (
+            r#"[splits("a")]"#,
+            "{}",
+            "object ({}) cannot be matched, as it is not a string",
+        ),
+        ("test(1)", r#""a""#, "number not a string or array"),
+        ("capture([])", r#""a""#, "array not a string or array"),
+        (r#"match(1; null)"#, r#""a""#, "number (1) is not a string"),
+        (
+            r#"test("(")"#,
+            r#""a""#,
+            "( (at offset 0) is not a valid regex: ",
+        ),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-c", filter], input);
+        assert_eq!(code, 5, "{filter}");
+        assert!(stdout.is_empty(), "{filter}: {stdout}");
+        assert!(stderr.contains(message), "{filter}: {stderr}");
+    }
+    assert_jq_compat(r#"[.[] | try test("a") catch .]"#, r#"["a", 1, "b"]"#);
+}
+
 // --- String interpolation ---
 
 #[test]
@@ -3525,6 +5609,53 @@ fn format_base64d() {
     assert_jq_compat("@base64d", r#""aGVsbG8=""#);
 }
 
+#[test]
+fn format_base64d_lenient_padding_and_lossy_utf8() {
+    // Padding is optional and decoding stops at the first `=`.
+    for (input, expected) in [
+        (r#""YWI""#, r#""ab""#),
+        (r#""YQ""#, r#""a""#),
+        (r#""YQ=""#, r#""a""#),
+        (r#""YWJj=""#, r#""abc""#),
+        (r#""YW=Jj""#, r#""a""#),
+    ] {
+        assert_eq!(qj_compact("@base64d", input).trim(), expected, "{input}");
+        assert_jq_compat("@base64d", input);
+    }
+    // Bytes that aren't UTF-8 decode to U+FFFD, one per bad sequence.
+    assert_eq!(
+        qj_compact("@base64d", r#""//4=""#).trim(),
+        "\"\u{fffd}\u{fffd}\""
+    );
+    assert_eq!(
+        qj_compact("@base64d | explode", r#""7aCAYfSQgIA=""#).trim(),
+        "[65533,97,65533]"
+    );
+    assert_jq_compat("@base64d | explode", r#""//4=""#);
+    assert_jq_compat("@base64d | explode", r#""4pyTYeKc""#);
+    // Unicode strings round-trip through their UTF-8 bytes.
+    assert_eq!(qj_compact("@base64", r#""✓ à""#).trim(), r#""4pyTIMOg""#);
+    assert_jq_compat("@base64 | ., @base64d", r#""✓ à la mode 😀""#);
+}
+
+#[test]
+fn format_base64d_errors() {
+    for (input, message) in [
+        (r#""Y""#, r#"string ("Y") trailing base64 byte found"#),
+        (
+            r#""YW\nJj""#,
+            r#"string ("YW\nJj") is not valid base64 data"#,
+        ),
+        (r#""YW Jj""#, r#"string ("YW Jj") is not valid base64 data"#),
+        (r#""YWJ!""#, r#"string ("YWJ!") is not valid base64 data"#),
+        ("1", r#"string ("1") trailing base64 byte found"#),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-c", "@base64d"], input);
+        assert_eq!((code, stdout.as_str()), (5, ""), "{input}");
+        assert!(stderr.contains(message), "{input}: {stderr}");
+    }
+}
+
 #[test]
 fn format_uri() {
     assert_eq!(
@@ -3559,12 +5690,63 @@ fn format_tsv() {
 }
 
 #[test]
-fn format_html() {
+fn format_csv_tsv_element_matrix() {
+    let elements = [
+        "null",
+        "true",
+        "false",
+        "0",
+        "-17",
+        "1.5",
+        r#""plain""#,
+        r#""say \"hi\"""#,
+        r#""a,b""#,
+        r#""tab\there""#,
+        r#""cr\rlf\n""#,
+        r#""back\\slash""#,
+        r#""""#,
+        "[1]",
+        r#"{"a":1}"#,
+    ];
+    for fmt in ["@csv", "@tsv"] {
+        for elem in elements {
+            assert_jq_compat(fmt, &format!("[{elem}]"));
+            assert_jq_compat(fmt, &format!("[1,{elem},null]"));
+        }
+        assert_jq_compat(fmt, &format!("[{}]", elements[..13].join(",")));
+        assert_jq_compat(&format!("[nan, infinite, -infinite] | {fmt}"), "null");
+    }
+    assert_eq!(
+        qj_raw("@tsv", r#"["a\tb","c\rd","e\\f",null,true]"#).trim_end_matches('\n'),
+        "a\\tb\tc\\rd\te\\\\f\t\ttrue"
+    );
+    assert_eq!(
+        qj_raw("@csv", r#"["x\"y",null,false,2]"#).trim_end_matches('\n'),
+        r#""x""y",,false,2"#
+    );
+}
+
+#[test]
+fn format_html() {
     assert_eq!(
         qj_compact("@html", r#""<b>bold</b>""#).trim(),
         r#""&lt;b&gt;bold&lt;/b&gt;""#
     );
     assert_jq_compat("@html", r#""<b>bold</b>""#);
+    // All five of jq's entities, `'` included
+    assert_eq!(
+        qj_raw("@html", r#""<a title=\"it's\">&</a>""#).trim_end(),
+        "&lt;a title=&quot;it&apos;s&quot;&gt;&amp;&lt;/a&gt;"
+    );
+    assert_jq_compat("@html", r#""<a title=\"it's\">&</a>""#);
+    // Only the interpolated values are escaped, not the literal markup
+    let template = r#"@html "<b>\(.name)</b> (\(.n))""#;
+    let input = r#"{"name":"T'n\"<x>","n":3}"#;
+    assert_eq!(
+        qj_raw(template, input).trim_end(),
+        "<b>T&apos;n&quot;&lt;x&gt;</b> (3)"
+    );
+    assert_jq_compat(template, input);
 }
 
 #[test]
@@ -3576,6 +5758,84 @@ fn format_sh() {
     assert_jq_compat("@sh", r#""hello world""#);
 }
 
+#[test]
+fn format_sh_quoting_jq_compat() {
+    for input in [
+        r#""it's""#,
+        r#""''""#,
+        r#""back\\slash""#,
+        r#""$HOME `id` $(id)""#,
+        r#""line\nbreak\ttab""#,
+        r#""""#,
+        r#"["a b", 1, 1.5, true, false, null, "x'y", ""]"#,
+        "[]",
+    ] {
+        assert_jq_compat("@sh", input);
+    }
+    for (filter, input) in [
+        ("@sh", r#"{"a":1}"#),
+        ("@sh", "[[1]]"),
+        ("@sh", r#"["a", {"b":2}]"#),
+        (r#"try @sh catch ."#, r#"[1, [2]]"#),
+    ] {
+        assert_jq_compat(filter, input);
+    }
+    let (code, stdout, stderr) = qj_exit(&["-r", "@sh"], r#"{"a":1}"#);
+    assert_eq!((code, stdout.as_str()), (5, ""));
+    assert!(
+        stderr.contains(r#"object ({"a":1}) can not be escaped for shell"#),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn format_sh_rejects_nul() {
+    // A shell word can't hold NUL, so rather than emit something the shell
+    // would cut short, @sh refuses it.
+    let (code, stdout, stderr) = qj_exit(&["-r", "@sh"], r#""a\u0000b""#);
+    assert_eq!((code, stdout.as_str()), (5, ""));
+    assert!(
+        stderr.contains(r#"string ("a\u0000b") can not be escaped for shell"#),
+        "{stderr}"
+    );
+    let (code, _, _) = qj_exit(&["-r", r#"@sh "echo \(.)""#], r#"["ok", "a\u0000b"]"#);
+    assert_eq!(code, 5);
+}
+
+#[cfg(unix)]
+#[test]
+fn format_sh_round_trips_through_the_shell() {
+    let strings = [
+        "it's",
+        "'",
+        "''\\''",
+        "back\\slash \\' \\\\",
+        "$HOME ${PATH} `id` $(id) !! *?[a]",
+        "line\nbreak\ttab\r",
+        "; rm -rf / #",
+        "\"double\" and 'single'",
+        "ünïcödé ✓",
+        "-n",
+        "",
+    ];
+    for original in strings {
+        let input = serde_json::to_string(original).unwrap();
+        let quoted = qj_raw("@sh", &input);
+        let quoted = quoted.strip_suffix('\n').unwrap();
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf %s {quoted}"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{quoted}");
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            original,
+            "{quoted}"
+        );
+    }
+}
+
 #[test]
 fn format_json() {
     assert_eq!(qj_compact("@json", "[1,2,3]").trim(), r#""[1,2,3]""#);
@@ -3610,6 +5870,53 @@ fn format_strings_on_non_strings() {
     assert_jq_compat("@urid", "null");
 }
 
+#[test]
+fn format_prefixed_interpolation() {
+    // The format applies to each interpolated value; the literal text is kept
+    let input = r#"{"obj":{"a":"x y"},"arr":["a b",1,null],"s":"it's <&> \"q\"","b":"aGk=","n":3}"#;
+    for (filter, expected) in [
+        (r#"@text "t: \(.obj)""#, r#"t: {"a":"x y"}"#),
+        (
+            r#"@json "data: \(.obj) \(.s)""#,
+            r#"data: {"a":"x y"} "it's <&> \"q\"""#,
+        ),
+        (
+            r#"@html "<i>\(.s)</i>""#,
+            "<i>it&apos;s &lt;&amp;&gt; &quot;q&quot;</i>",
+        ),
+        (r#"@csv "row: \(.arr)""#, r#"row: "a b",1,"#),
+        ("@tsv \"row: \\(.arr)\"", "row: a b\t1\t"),
+        (r#"@sh "echo \(.s) \(.n)""#, r#"echo 'it'\''s <&> "q"' 3"#),
+        (r#"@base64 "b64: \(.s)""#, "b64: aXQncyA8Jj4gInEi"),
+        (r#"@base64d "text: \(.b)""#, "text: hi"),
+    ] {
+        assert_eq!(qj_raw(filter, input), format!("{expected}\n"), "{filter}");
+        assert_jq_compat(filter, input);
+    }
+    // jq 1.7 percent-encodes `'`, which 1.6 left alone
+    assert_eq!(
+        qj_raw(r#"@uri "https://x.test/?q=\(.s)""#, input).trim_end(),
+        "https://x.test/?q=it%27s%20%3C%26%3E%20%22q%22"
+    );
+    // A literal with nothing interpolated is left as written, whatever the format
+    for filter in [
+        r#"@base64 "abc""#,
+        r#"@json "x\"y""#,
+        r#"@html "<b>""#,
+        r#"@csv "[1]""#,
+        r#"@sh "a b""#,
+    ] {
+        assert_jq_compat(filter, "null");
+    }
+    assert_eq!(qj_compact(r#"@csv "[1]""#, "null").trim(), r#""[1]""#);
+    assert_jq_compat(r#"{@base64 "d": 1, @base64 "e\(.n)": 2}"#, input);
+    // Each output of a generator is formatted on its own
+    assert_eq!(
+        qj_compact(r#"[@sh "x=\(.arr[])"]"#, input).trim(),
+        r#"["x='a b'","x=1","x=null"]"#
+    );
+}
+
 // --- Builtin: in ---
 
 #[test]
@@ -3927,6 +6234,287 @@ fn exit_code_5_precedes_exit_code_4() {
     assert!(stderr.contains("x"));
 }
 
+/// NDJSON log lines, large enough to be split into several parallel chunks.
+/// Line `i` has `level` "ERROR" only at `error_at`, and `ok` false only at
+/// the indices in `falsy_at`.
+fn multi_chunk_ndjson(lines: usize, error_at: Option<usize>, falsy_at: &[usize]) -> String {
+    let mut input = String::new();
+    for i in 0..lines {
+        let level = if Some(i) == error_at { "ERROR" } else { "INFO" };
+        let ok = !falsy_at.contains(&i);
+        input.push_str(&format!(
+            "{{\"i\":{i},\"level\":\"{level}\",\"ok\":{ok},\"msg\":\"request {i} handled\"}}\n"
+        ));
+    }
+    input
+}
+
+#[test]
+fn exit_status_multi_chunk_ndjson() {
+    const LINES: usize = 60_000;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("logs.ndjson");
+
+    let cases: &[(&str, Option<usize>, &[usize], i32)] = &[
+        // select: any output is success, no output is exit 4.
+        (r#"select(.level == "ERROR")"#, Some(LINES / 2), &[], 0),
+        (r#"select(.level == "ERROR")"#, None, &[], 4),
+        // The last value decides: falsy in the final line is exit 1 ...
+        (".ok", None, &[7, LINES - 1], 1),
+        // ... but an earlier falsy value followed by truthy ones is success.
+        (".ok", None, &[7, LINES - 2], 0),
+        // The final chunk produces nothing; the last output came from an earlier chunk.
+        ("select(.i < 10) | .ok", None, &[9], 1),
+        ("select(.i < 10) | .ok", None, &[8], 0),
+        (r#"select(.level == "ERROR") | .ok"#, Some(3), &[3], 1),
+        ("has(\"missing\")", None, &[], 1),
+        (".ok | not", None, &[LINES - 1], 0),
+    ];
+    for &(filter, error_at, falsy_at, expected) in cases {
+        let input = multi_chunk_ndjson(LINES, error_at, falsy_at);
+        std::fs::write(&path, &input).unwrap();
+        let (code, _, stderr) = qj_exit(&["-e", "-c", filter], &input);
+        assert_eq!(code, expected, "stdin, filter {filter}: {stderr}");
+        let (code, _, stderr) = qj_exit(&["-e", "-c", filter, path.to_str().unwrap()], "");
+        assert_eq!(code, expected, "file, filter {filter}: {stderr}");
+    }
+
+    // Runs on the parallel path, and raw output of the string "false" is truthy.
+    let input = multi_chunk_ndjson(LINES, None, &[LINES - 1]);
+    let (code, stdout, stderr) = qj_exit(&["-e", "--debug-parallel", "-r", ".ok"], &input);
+    assert_eq!(code, 1);
+    assert!(stderr.contains("ndjson parallel ("), "stderr: {stderr}");
+    assert_eq!(stdout.lines().last(), Some("false"));
+    let input = input.replace("\"ok\":false", "\"ok\":\"false\"");
+    let (code, _, _) = qj_exit(&["-e", "-r", ".ok"], &input);
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn exit_status_single_document_passthrough() {
+    assert_eq!(qj_exit(&["-e", "."], "null").0, 1);
+    assert_eq!(qj_exit(&["-e", "-c", "."], "false").0, 1);
+    assert_eq!(qj_exit(&["-e", "-c", "."], r#"{"a":false}"#).0, 0);
+    assert_eq!(qj_exit(&["-e", "has(\"b\")"], r#"{"a":1}"#).0, 1);
+    assert_eq!(
+        qj_exit(&["-e", "-c", ".[] | .a"], r#"[{"a":1},{"a":null}]"#).0,
+        1
+    );
+    assert_eq!(qj_exit(&["-e", "-c", "map(.a)"], r#"[{"a":null}]"#).0, 0);
+    assert_eq!(qj_exit(&["-e", "length"], "[]").0, 0);
+}
+
+#[test]
+fn exit_status_follows_the_last_output() {
+    // Only the last value counts, and only null and false are falsy.
+    for (filter, input, expected) in [
+        (".", "null", 1),
+        (".", "false", 1),
+        (".", "0", 0),
+        (".", r#""""#, 0),
+        (".[]", "[1, false, null]", 1),
+        (".[]", "[null, false, 0]", 0),
+        ("null, 1", "{}", 0),
+        ("1, null", "{}", 1),
+        (".", "false true", 0),
+        (".", "true\nfalse\n", 1),
+        (".a", "{\"a\":null}\n{\"a\":[]}\n", 0),
+        (".a", "{\"a\":1}\n{\"a\":false}\n", 1),
+    ] {
+        let (code, _, stderr) = qj_exit(&["-e", "-c", filter], input);
+        assert_eq!(code, expected, "{filter} on {input:?}: {stderr}");
+    }
+    // `type` of null is the string "null", printed bare with -r.
+    assert_eq!(qj_exit(&["-e", "-r", "type"], "null").0, 0);
+}
+
+#[test]
+fn exit_status_of_identity_passthrough() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("false.json");
+    std::fs::write(&path, "false\n").unwrap();
+    let path = path.to_str().unwrap();
+    let (code, stdout, stderr) = qj_exit(&["-e", "-c", "--debug-timing", ".", path], "");
+    assert!(stderr.contains("passthrough)"), "{stderr}");
+    assert_eq!((code, stdout.as_str()), (1, "false\n"));
+    let (code, _, _) = qj_exit(&["-e", ".", path], "");
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn ndjson_exit_status_follows_the_last_line() {
+    // As in jq, an uncaught error on the last line exits 5 whatever -e would
+    // say, and one on an earlier line doesn't change the exit status.
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rows.ndjson");
+    for (input, expected, stdout) in [
+        (
+            "{\"a\":{\"b\":1}}\n{\"a\":{\"b\":true}}\n{\"a\":2}\n",
+            5,
+            "1\ntrue\n",
+        ),
+        (
+            "{\"a\":{\"b\":1}}\n{\"a\":2}\n{\"a\":{\"b\":true}}\n",
+            0,
+            "1\ntrue\n",
+        ),
+        ("{\"a\":2}\n{\"a\":{\"b\":null}}\n\n", 1, "null\n"),
+    ] {
+        std::fs::write(&path, input).unwrap();
+        let path = path.to_str().unwrap();
+        let (code, out, stderr) = qj_exit(&["-e", "-c", ".a.b"], input);
+        assert_eq!((code, out.as_str()), (expected, stdout), "stdin {input:?}");
+        assert!(stderr.contains("Cannot index number"), "{stderr}");
+        let (code, out, _) = qj_exit(&["-e", "-c", ".a.b", path], "");
+        assert_eq!((code, out.as_str()), (expected, stdout), "file {input:?}");
+    }
+    let (code, _, _) = qj_exit(&["-c", ".a.b"], "{\"a\":1}\n{\"a\":2}\n");
+    assert_eq!(code, 5);
+}
+
+#[test]
+fn multi_document_exit_status_follows_the_last_document() {
+    // The same rule for several documents on one line, or across lines
+    // without being NDJSON.
+    for (input, expected, stdout) in [
+        ("{\"a\":{\"b\":1}} {\"a\":5} {\"a\":{\"b\":3}}", 0, "1\n3\n"),
+        ("{\"a\":{\"b\":1}} {\"a\":{\"b\":3}} {\"a\":5}", 5, "1\n3\n"),
+        ("{\n\"a\":{\"b\":1}\n}\n{\"a\":5}\n", 5, "1\n"),
+        ("[\n1\n]\n{\"a\":5}\n{\"a\":{\"b\":false}}\n", 1, "false\n"),
+    ] {
+        let (code, out, stderr) = qj_exit(&["-e", "-c", ".a.b"], input);
+        assert_eq!((code, out.as_str()), (expected, stdout), "{input:?}");
+        assert!(stderr.contains("Cannot index"), "{stderr}");
+    }
+}
+
+#[test]
+fn multi_file_exit_status_follows_the_last_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let ok = dir.path().join("ok.json");
+    let bad = dir.path().join("bad.json");
+    let docs = dir.path().join("docs.json");
+    std::fs::write(&ok, "{\"a\":{\"b\":1}}\n").unwrap();
+    std::fs::write(&bad, "{\"a\":5}\n").unwrap();
+    std::fs::write(&docs, "{\"a\":5} {\"a\":{\"b\":2}}\n").unwrap();
+    let [ok, bad, docs] = [&ok, &bad, &docs].map(|p| p.to_str().unwrap());
+    for (files, expected, stdout) in [
+        ([ok, bad, ok], 0, "1\n1\n"),
+        ([ok, ok, bad], 5, "1\n1\n"),
+        ([bad, ok, docs], 0, "1\n2\n"),
+        ([docs, ok, bad], 5, "2\n1\n"),
+    ] {
+        let mut args = vec!["-c", ".a.b"];
+        args.extend(files);
+        let (code, out, stderr) = qj_exit(&args, "");
+        assert_eq!((code, out.as_str()), (expected, stdout), "{files:?}");
+        assert!(stderr.contains("Cannot index number"), "{stderr}");
+    }
+}
+
+/// Run qj with stdout going to `stdout` and stdin from `input_path`, returning
+/// the exit code, stderr and how long it ran.
+#[cfg(unix)]
+fn qj_to_sink(
+    args: &[&str],
+    input_path: &std::path::Path,
+    stdout: std::process::Stdio,
+) -> (Option<i32>, String, std::time::Duration) {
+    let start = std::time::Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .args(args)
+        .stdin(std::fs::File::open(input_path).unwrap())
+        .stdout(stdout)
+        .stderr(std::process::Stdio::piped())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    (output.status.code(), stderr, start.elapsed())
+}
+
+#[cfg(unix)]
+#[test]
+fn write_error_is_reported_with_exit_2() {
+    if !std::path::Path::new("/dev/full").exists() {
+        return;
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let doc = dir.path().join("doc.json");
+    std::fs::write(&doc, r#"{"a":[1,2,3],"b":"x"}"#).unwrap();
+    let ndjson = dir.path().join("rows.ndjson");
+    let rows: String = (0..20_000).map(|i| format!("{{\"id\":{i}}}\n")).collect();
+    std::fs::write(&ndjson, rows).unwrap();
+    let (doc, ndjson) = (doc.to_str().unwrap(), ndjson.to_str().unwrap());
+    // Value-at-a-time output, passthrough, NDJSON and an endless generator.
+    for args in [
+        &["-c", ".a[]", doc][..],
+        &["."],
+        &["-c", ".", doc],
+        &["-c", ".id", ndjson],
+        &["-c", "{id}", ndjson],
+        &["-n", "range(1e12)"],
+        &["-n", "repeat(1)"],
+    ] {
+        let full = std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/full")
+            .unwrap();
+        let (code, stderr, elapsed) = qj_to_sink(args, std::path::Path::new(doc), full.into());
+        assert_eq!(code, Some(2), "{args:?}: {stderr}");
+        assert_eq!(
+            stderr.trim(),
+            "qj: error writing output: No space left on device (os error 28)",
+            "{args:?}"
+        );
+        assert!(elapsed.as_secs() < 10, "{args:?} took {elapsed:?}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn closed_reader_stops_output_promptly() {
+    use std::io::Read;
+
+    let dir = tempfile::tempdir().unwrap();
+    let empty = dir.path().join("empty");
+    std::fs::write(&empty, "").unwrap();
+    let start = std::time::Instant::now();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .args(["-n", "range(1e12)"])
+        .stdin(std::fs::File::open(&empty).unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut head = [0u8; 6];
+    child.stdout.take().unwrap().read_exact(&mut head).unwrap();
+    assert_eq!(&head, b"0\n1\n2\n");
+    // The reader is gone; like jq, qj goes quietly (SIGPIPE) and at once.
+    let status = child.wait().unwrap();
+    assert!(start.elapsed().as_secs() < 10, "took {:?}", start.elapsed());
+    assert!(matches!(status.code(), None | Some(0)), "{status:?}");
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn generators_stop_when_broken_out_of() {
+    for (filter, expected) in [
+        ("first(range(1e12))", "0"),
+        ("[limit(3; range(0; 1e12; 2))]", "[0,2,4]"),
+        ("label $out | range(1e12) | ., break $out", "0"),
+        ("first(range(1e12; 0; -1))", "1000000000000"),
+    ] {
+        assert_eq!(qj_compact(filter, "null").trim(), expected, "{filter}");
+    }
+}
+
 // --- --from-file tests ---
 
 #[test]
@@ -3971,6 +6559,43 @@ fn from_file_with_input_file() {
     std::fs::remove_file(&input_path).ok();
 }
 
+#[test]
+fn from_file_shebang_and_comments() {
+    let dir = tempfile::tempdir().unwrap();
+    let filter_path = dir.path().join("script.jq");
+    std::fs::write(
+        &filter_path,
+        r#"#!/usr/bin/env -S qj -f
+# Tag each item with a label.
+def tag($prefix): # takes the prefix as a $-param
+  "\($prefix)#\(.id)";   # '#' inside the string is kept
+
+.items[]
+| tag("item")  # trailing comment
+"#,
+    )
+    .unwrap();
+
+    let (code, stdout, stderr) = qj_exit(
+        &["-c", "-f", filter_path.to_str().unwrap()],
+        r#"{"items":[{"id":1},{"id":2}]}"#,
+    );
+    assert_eq!(code, 0, "stderr: {stderr}");
+    assert_eq!(stdout, "\"item#1\"\n\"item#2\"\n");
+}
+
+#[test]
+fn from_file_comments_only_is_empty_filter_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let filter_path = dir.path().join("empty.jq");
+    std::fs::write(&filter_path, "#!/usr/bin/env qj\n# nothing yet\n\n").unwrap();
+
+    let (code, stdout, stderr) = qj_exit(&["-f", filter_path.to_str().unwrap()], "1");
+    assert_eq!(code, 3);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("empty filter"), "stderr: {stderr}");
+}
+
 // ---------------------------------------------------------------------------
 // input / inputs builtins
 // ---------------------------------------------------------------------------
@@ -4013,6 +6638,255 @@ fn inputs_empty_queue() {
     assert_eq!(stdout.trim(), "[]");
 }
 
+#[test]
+fn inputs_reduce_large_ndjson_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("big.ndjson");
+    let mut data = String::new();
+    for i in 0..200_000 {
+        data.push_str(&format!("{{\"id\":{i},\"tag\":\"row\"}}\n"));
+    }
+    std::fs::write(&path, data).unwrap();
+    let path = path.to_str().unwrap();
+
+    let (code, stdout, _) = qj_exit(&["-n", "reduce inputs as $x (0; . + 1)", path], "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), "200000");
+
+    let (code, stdout, _) = qj_exit(
+        &["-n", "reduce inputs as $x (0; . + $x.id)", path, path],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), "39999800000");
+}
+
+#[test]
+fn first_and_limit_stop_reading_inputs() {
+    let (code, stdout, _) = qj_exit(&["-nc", "first(inputs), input"], "1\n2\n3\n");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "1\n2\n");
+    let (code, stdout, _) = qj_exit(&["-nc", "[limit(2; inputs)], [inputs]"], "1\n2\n3\n4\n");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[1,2]\n[3,4]\n");
+    let (code, stdout, _) = qj_exit(&["-nc", "[limit(0; inputs)], [inputs]"], "1\n2\n");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[]\n[1,2]\n");
+}
+
+#[test]
+fn first_record_is_output_before_stdin_closes() {
+    // The writer keeps stdin open, so a qj that reads to EOF never exits.
+    for (filter, input, expected) in [
+        ("first(inputs)", "1\n2\n", "1\n"),
+        ("limit(1; inputs)", "\"a\" \"b\"\n", "\"a\"\n"),
+        ("input", "{\n  \"a\": [1,\n 2]\n}\n{", "{\"a\":[1,2]}\n"),
+        ("isempty(inputs)", "null\n", "false\n"),
+        ("first(inputs)", "{\"a\":1}\n{\"a\":2}\n", "{\"a\":1}\n"),
+    ] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_qj"))
+            .args(["-nc", filter])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("failed to run qj");
+        let mut stdin = child.stdin.take().unwrap();
+        std::io::Write::write_all(&mut stdin, input.as_bytes()).unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while child.try_wait().unwrap().is_none() {
+            if std::time::Instant::now() > deadline {
+                child.kill().unwrap();
+                panic!("qj -nc '{filter}' waited for the end of stdin");
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let output = child.wait_with_output().unwrap();
+        drop(stdin);
+        assert!(output.status.success(), "{filter}");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            expected,
+            "{filter}"
+        );
+    }
+}
+
+#[test]
+fn inputs_stream_file_by_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = dir.path().join("first.ndjson");
+    let second = dir.path().join("second.ndjson");
+    let rows = |range: std::ops::Range<u32>| -> String {
+        range.map(|i| format!("{{\"x\":{}}}\n", i % 3)).collect()
+    };
+    std::fs::write(&first, rows(0..150_000)).unwrap();
+    std::fs::write(&second, rows(150_000..300_000)).unwrap();
+    let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+    let (code, stdout, _) = qj_exit(&["-n", "reduce inputs as $x (0; . + 1)", first, second], "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), "300000");
+    let (code, stdout, _) = qj_exit(
+        &["-n", "[inputs | select(.x == 1)] | length", first, second],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), "100000");
+
+    // -nR streams raw lines across files the same way
+    let (code, stdout, _) = qj_exit(
+        &["-nR", "reduce inputs as $l (0; . + 1)", first, second],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), "300000");
+}
+
+#[test]
+fn inputs_error_in_later_file_after_earlier_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("good.ndjson");
+    let bad = dir.path().join("bad.ndjson");
+    std::fs::write(&good, "{\"a\":1}\n{\"a\":2}\n").unwrap();
+    std::fs::write(&bad, "{\"b\":1}\n{\"b\":\n").unwrap();
+    let (good, bad) = (good.to_str().unwrap(), bad.to_str().unwrap());
+
+    // A malformed NDJSON line is reported after every value before it
+    let (code, stdout, stderr) = qj_exit(&["-nc", "inputs", good, bad], "");
+    assert_ne!(code, 0);
+    assert_eq!(stdout, "{\"a\":1}\n{\"a\":2}\n{\"b\":1}\n");
+    assert!(stderr.contains("(at line 2): invalid JSON"), "{stderr}");
+
+    // A missing second file is only opened once the first is used up
+    let missing = dir.path().join("missing.json");
+    let (code, stdout, stderr) = qj_exit(
+        &[
+            "-nc",
+            "first(inputs), input",
+            good,
+            missing.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(stdout, "{\"a\":1}\n{\"a\":2}\n");
+    let (code, stdout, stderr) = qj_exit(&["-nc", "inputs", good, missing.to_str().unwrap()], "");
+    assert_ne!(code, 0);
+    assert_eq!(stdout, "{\"a\":1}\n{\"a\":2}\n");
+    assert!(stderr.contains("failed to read file"), "{stderr}");
+}
+
+#[test]
+fn input_interleaves_with_dot() {
+    // Without -n, each value not consumed by `input` runs the filter once.
+    let (code, stdout, _) = qj_exit(&["-c", "[., input]"], "1\n2\n3\n4\n");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[1,2]\n[3,4]\n");
+
+    let ndjson = "{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n{\"a\":4}\n{\"a\":5}\n{\"a\":6}\n";
+    let (code, stdout, _) = qj_exit(&["-c", "{first: .a, second: input.a}"], ndjson);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "{\"first\":1,\"second\":2}\n{\"first\":3,\"second\":4}\n{\"first\":5,\"second\":6}\n"
+    );
+
+    // Running out of inputs mid-pair is an error, after the complete pairs.
+    let (code, stdout, _) = qj_exit(&["-c", "[., input]"], "1\n2\n3\n");
+    assert_eq!(code, 5);
+    assert_eq!(stdout, "[1,2]\n");
+}
+
+#[test]
+fn input_reads_across_files_and_raw_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.json");
+    let b = dir.path().join("b.json");
+    std::fs::write(&a, "{\"x\": [1,\n 2]}\n").unwrap();
+    std::fs::write(&b, "{\"y\":1}\n{\"y\":2}\n{\"y\":3}\n").unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let (code, stdout, _) = qj_exit(&["-c", "[., input]", a, b], "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[{\"x\":[1,2]},{\"y\":1}]\n[{\"y\":2},{\"y\":3}]\n");
+    let (code, stdout, _) = qj_exit(&["-nc", "[inputs]", a, b], "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[{\"x\":[1,2]},{\"y\":1},{\"y\":2},{\"y\":3}]\n");
+
+    let (code, stdout, _) = qj_exit(&["-nRc", "[inputs]"], "a\nb\r\n\nc");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[\"a\",\"b\",\"\",\"c\"]\n");
+}
+
+#[test]
+fn inputs_match_dot_across_input_modes() {
+    // (-n | not) × (-R | not) × (--jsonl | not) × (files | stdin): `inputs`
+    // sees values split and parsed the same way `.` would be.
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, text: &str| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    };
+    let json = ["{\"a\":1}\n[2]\n", "3\n\"x\"\n"];
+    let raw = ["a\nb\n", "c\n"];
+    let json_files = [write("a.json", json[0]), write("b.json", json[1])];
+    let raw_files = [write("a.txt", raw[0]), write("b.txt", raw[1])];
+    for null_input in [false, true] {
+        for raw_input in [false, true] {
+            for jsonl in [false, true] {
+                for from_files in [false, true] {
+                    let (texts, files) = if raw_input {
+                        (raw, &raw_files)
+                    } else {
+                        (json, &json_files)
+                    };
+                    let mut args = vec!["-c"];
+                    args.extend(null_input.then_some("-n"));
+                    args.extend(raw_input.then_some("-R"));
+                    args.extend(jsonl.then_some("--jsonl"));
+                    args.push("[., inputs]");
+                    let stdin = if from_files {
+                        args.extend(files.iter().map(String::as_str));
+                        String::new()
+                    } else {
+                        texts.concat()
+                    };
+                    let expected = match (null_input, raw_input) {
+                        (false, false) => "[{\"a\":1},[2],3,\"x\"]\n",
+                        (true, false) => "[null,{\"a\":1},[2],3,\"x\"]\n",
+                        (false, true) => "[\"a\",\"b\",\"c\"]\n",
+                        (true, true) => "[null,\"a\",\"b\",\"c\"]\n",
+                    };
+                    let (code, stdout, stderr) = qj_exit(&args, &stdin);
+                    assert_eq!((code, stdout.as_str()), (0, expected), "{args:?}: {stderr}");
+                    // jq has no --jsonl; its output is the same without it.
+                    if !jsonl && let Some(jq) = run_jq(&args, &stdin) {
+                        assert_eq!(stdout, jq, "{args:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    // A line that isn't one JSON value is reported and skipped, as for `.`.
+    let bad = "1 2\n3\n";
+    for args in [&["-c", "--jsonl", "."][..], &["-nc", "--jsonl", "inputs"]] {
+        let (code, stdout, stderr) = qj_exit(args, bad);
+        assert_eq!(code, 2, "{args:?}");
+        assert_eq!(stdout, "3\n", "{args:?}");
+        assert_eq!(
+            stderr, "qj: error (at line 1): invalid JSON: trailing characters at column 3\n",
+            "{args:?}"
+        );
+    }
+
+    // --seq records feed `input` too.
+    let (code, stdout, _) = qj_exit(&["--seq", "-c", "[., input]"], "\x1e1\n\x1e2\n");
+    assert_eq!((code, stdout.as_str()), (0, "\x1e[1,2]\n"));
+}
+
 // ---------------------------------------------------------------------------
 // Color output
 // ---------------------------------------------------------------------------
@@ -4111,34 +6985,332 @@ fn no_color_env_overridden_by_flag() {
     );
 }
 
-// ---------------------------------------------------------------------------
-// --rawfile
-// ---------------------------------------------------------------------------
+/// Run `qj -C` with `JQ_COLORS` set, returning (stdout, stderr).
+fn qj_jq_colors(jq_colors: &str, args: &[&str], input: &str) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .arg("-C")
+        .args(args)
+        .env("JQ_COLORS", jq_colors)
+        .env_remove("NO_COLOR")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            use std::io::Write;
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(input.as_bytes())
+                .unwrap();
+            child.wait_with_output()
+        })
+        .expect("failed to run qj");
+    assert!(output.status.success());
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
 
 #[test]
-fn rawfile_binding() {
-    let dir = std::env::temp_dir();
-    let path = dir.join("qj_test_rawfile.txt");
-    std::fs::write(&path, "hello world").unwrap();
+fn jq_colors_env_customizes_colors() {
+    let (stdout, stderr) = qj_jq_colors("4;31:0:0:1;35:0:0:0:7;33", &["-c", "[null, 12]"], "null");
+    assert!(stderr.is_empty(), "stderr: {stderr}");
+    assert!(stdout.contains("\x1b[4;31mnull\x1b[0m"), "{stdout:?}");
+    assert!(stdout.contains("\x1b[1;35m12\x1b[0m"), "{stdout:?}");
 
-    let (code, stdout, _) = qj_exit(
-        &[
-            "-nc",
-            "$content",
-            "--rawfile",
-            "content",
-            path.to_str().unwrap(),
-        ],
-        "",
-    );
-    assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), r#""hello world""#);
+    let (stdout, _) = qj_jq_colors("4;31:0:0:1;35:0:0:0:7;33", &["-c", "."], r#"{"k":1}"#);
+    assert!(stdout.contains("\x1b[7;33m\"k\"\x1b[0m"), "{stdout:?}");
+}
 
-    std::fs::remove_file(&path).ok();
+#[test]
+fn jq_colors_env_malformed_warns_and_uses_defaults() {
+    let (stdout, stderr) = qj_jq_colors("1;31:bogus", &["-c", "."], "null");
+    assert_eq!(stderr, "qj: warning: Failed to set $JQ_COLORS\n");
+    assert_eq!(stdout, "\x1b[0;90mnull\x1b[0m\n");
 }
 
 // ---------------------------------------------------------------------------
-// --slurpfile
+// --jsonl-output
+// ---------------------------------------------------------------------------
+
+/// `--jsonl-output` must print exactly what `-c` prints, whatever else is set.
+fn assert_jsonl_output_matches_compact(extra: &[&str], filter: &str, input: &str) {
+    let mut args = vec!["--jsonl-output"];
+    args.extend_from_slice(extra);
+    args.push(filter);
+    let (code, stdout, stderr) = qj_exit(&args, input);
+    assert_eq!(code, 0, "args {args:?}: {stderr}");
+
+    let mut compact_args = vec!["-c"];
+    compact_args.extend_from_slice(extra);
+    compact_args.push(filter);
+    let (_, expected, _) = qj_exit(&compact_args, input);
+    assert_eq!(stdout, expected, "args {args:?}");
+}
+
+#[test]
+fn jsonl_output_overrides_pretty_defaults() {
+    let input = "{\"a\":{\"b\":[1,2]},\"c\":\"x\"}\n[{\"d\":null}]\n3";
+    let (code, stdout, _) = qj_exit(&["--jsonl-output", "--indent", "4", "."], input);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "{\"a\":{\"b\":[1,2]},\"c\":\"x\"}\n[{\"d\":null}]\n3\n"
+    );
+    assert_jsonl_output_matches_compact(&["--tab"], ".", input);
+    assert_jsonl_output_matches_compact(&["-S"], ".", r#"{"b":{"z":1,"y":2},"a":[3]}"#);
+}
+
+#[test]
+fn jsonl_output_applies_to_passthrough_paths() {
+    let obj = r#"{"a":{"x":1,"y":[1,2]},"items":[{"id":1,"v":"p"},{"id":2,"v":"q"}]}"#;
+    for filter in [
+        ".",
+        ".a",
+        ".a | keys",
+        ".a | length",
+        ".items | map(.id)",
+        ".items | map({id, v})",
+        ".items | map(length)",
+        ".items[]",
+    ] {
+        assert_jsonl_output_matches_compact(&[], filter, obj);
+    }
+}
+
+#[test]
+fn jsonl_output_ndjson_input() {
+    let mut input = String::new();
+    for i in 0..50 {
+        input.push_str(&format!(
+            "{{\"i\":{i},\"tags\":[\"t{i}\"],\"o\":{{\"k\":{i}}}}}\n"
+        ));
+    }
+    for filter in [".", ".o", "{i, n: .tags[0]}", "select(.i % 7 == 0)"] {
+        assert_jsonl_output_matches_compact(&[], filter, &input);
+        assert_jsonl_output_matches_compact(&["-S"], filter, &input);
+    }
+    let (_, stdout, _) = qj_exit(&["--jsonl-output", ".o"], &input);
+    assert_eq!(stdout.lines().count(), 50);
+}
+
+#[test]
+fn jsonl_output_keeps_color_on_one_line() {
+    let input = r#"{"a":[1,{"b":null}],"c":"s"} {"d":true}"#;
+    let (code, stdout, _) = qj_exit(&["--jsonl-output", "-C", "."], input);
+    assert_eq!(code, 0);
+    assert!(stdout.contains("\x1b["), "expected ANSI codes: {stdout:?}");
+    assert_eq!(stdout.matches('\n').count(), 2);
+    assert!(stdout.ends_with('\n'));
+}
+
+#[test]
+fn jsonl_output_rejects_raw_and_join_flags() {
+    for flag in ["-r", "-j", "--raw-output0", "--seq"] {
+        let (code, stdout, stderr) = qj_exit(&["--jsonl-output", flag, "."], "1");
+        assert_eq!(code, 2, "{flag} should be rejected");
+        assert!(stdout.is_empty());
+        assert!(
+            stderr.contains("'--jsonl-output' cannot be used with"),
+            "{flag}: {stderr}"
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// --logfmt
+// ---------------------------------------------------------------------------
+
+#[test]
+fn logfmt_scalar_and_nested_values() {
+    let input = r#"{"ts":"2024-01-01T00:00:00Z","level":"info","n":3,"f":1.5,"ok":true,"no":false,"x":null,"tags":[1,2],"o":{"k":"v"},"e":{}}"#;
+    let (code, stdout, _) = qj_exit(&["--logfmt", "."], input);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "ts=2024-01-01T00:00:00Z level=info n=3 f=1.5 ok=true no=false x=null tags=[1,2] o=\"{\\\"k\\\":\\\"v\\\"}\" e={}\n"
+    );
+}
+
+#[test]
+fn logfmt_quotes_ambiguous_keys_and_values() {
+    let input = r#"{"msg":"hello world","eq":"a=b","q":"say \"hi\"","nl":"a\nb","empty":"","a key":1,"":2}"#;
+    let (code, stdout, _) = qj_exit(&["--logfmt", "."], input);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        r#"msg="hello world" eq="a=b" q="say \"hi\"" nl="a\nb" empty="" "a key"=1 ""=2"#.to_owned()
+            + "\n"
+    );
+}
+
+#[test]
+fn logfmt_sort_keys() {
+    let input = "{\"level\":\"warn\",\"b\":[{\"z\":1,\"a\":2}],\"a\":1}\n{\"c\":3,\"a\":4}\n";
+    let (code, stdout, _) = qj_exit(&["--logfmt", "-S", "."], input);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        r#"a=1 b="[{\"a\":2,\"z\":1}]" level=warn"#.to_owned() + "\na=4 c=3\n"
+    );
+    let (_, stdout, _) = qj_exit(&["--logfmt", "."], input);
+    assert_eq!(
+        stdout,
+        r#"level=warn b="[{\"z\":1,\"a\":2}]" a=1"#.to_owned() + "\nc=3 a=4\n"
+    );
+}
+
+#[test]
+fn logfmt_rejects_non_objects() {
+    let (code, stdout, stderr) = qj_exit(&["--logfmt", ".[]"], r#"[{"a":1},"s",{"b":2}]"#);
+    assert_eq!(code, 5);
+    assert_eq!(stdout, "a=1\n");
+    assert!(
+        stderr.contains(r#"Cannot format string ("s") as logfmt"#),
+        "{stderr}"
+    );
+
+    // NDJSON input takes the same route
+    let (code, stdout, stderr) = qj_exit(&["--logfmt", ".a"], "{\"a\":1}\n{\"a\":[2]}\n");
+    assert_eq!(code, 5);
+    assert!(stdout.is_empty());
+    assert!(
+        stderr.contains("Cannot format number (1) as logfmt"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Cannot format array ([2]) as logfmt"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn logfmt_conflicts_with_raw_flags() {
+    for flag in ["-r", "-j", "--raw-output0", "--jsonl-output"] {
+        let (code, stdout, stderr) = qj_exit(&["--logfmt", flag, "."], "{}");
+        assert_eq!(code, 2, "{flag} should be rejected");
+        assert!(stdout.is_empty());
+        assert!(stderr.contains("cannot be used with"), "{flag}: {stderr}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// --rawfile
+// ---------------------------------------------------------------------------
+
+#[test]
+fn rawfile_binding() {
+    let dir = std::env::temp_dir();
+    let path = dir.join("qj_test_rawfile.txt");
+    std::fs::write(&path, "hello world").unwrap();
+
+    let (code, stdout, _) = qj_exit(
+        &[
+            "-nc",
+            "$content",
+            "--rawfile",
+            "content",
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#""hello world""#);
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn rawfile_rejects_binary_with_a_clear_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("logo.bin");
+    std::fs::write(&path, b"\x89PNG\r\n\x1a\n\xff").unwrap();
+    let path = path.to_str().unwrap();
+    let (code, stdout, stderr) = qj_exit(&["-n", "--rawfile", "logo", path, "$logo"], "");
+    assert_eq!((code, stdout.as_str()), (2, ""));
+    assert!(
+        stderr.contains(&format!("--rawfile logo: {path} is not valid UTF-8")),
+        "{stderr}"
+    );
+    let (code, _, stderr) = qj_exit(&["-n", "--rawfile", "x", "/nonexistent/qj", "$x"], "");
+    assert_eq!(code, 2);
+    assert!(
+        stderr.contains("Could not open /nonexistent/qj"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn base64file_binds_file_bytes_as_base64() {
+    let dir = tempfile::tempdir().unwrap();
+    let binary = dir.path().join("logo.bin");
+    std::fs::write(&binary, b"\x89PNG\r\n\x1a\n\x00\xff").unwrap();
+    let (code, stdout, _) = qj_exit(
+        &[
+            "-nc",
+            "--base64file",
+            "logo",
+            binary.to_str().unwrap(),
+            "{img: $logo}",
+        ],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#"{"img":"iVBORw0KGgoA/w=="}"#);
+
+    // Control bytes and NUL survive the round trip through @base64d.
+    let bytes: Vec<u8> = (0u8..128).collect();
+    let ascii = dir.path().join("ascii.bin");
+    std::fs::write(&ascii, &bytes).unwrap();
+    let (_, stdout, _) = qj_exit(
+        &[
+            "-nc",
+            "--base64file",
+            "b",
+            ascii.to_str().unwrap(),
+            "$b | @base64d | explode | [length, .[0], .[127]]",
+        ],
+        "",
+    );
+    assert_eq!(stdout.trim(), "[128,0,127]");
+}
+
+#[test]
+fn file_bindings_populate_args_named() {
+    let dir = tempfile::tempdir().unwrap();
+    let text = dir.path().join("t.txt");
+    let json = dir.path().join("v.json");
+    std::fs::write(&text, "hi\n").unwrap();
+    std::fs::write(&json, "1 2").unwrap();
+    let (text, json) = (text.to_str().unwrap(), json.to_str().unwrap());
+    let args = [
+        "-nc",
+        "--arg",
+        "a",
+        "x",
+        "--rawfile",
+        "t",
+        text,
+        "--slurpfile",
+        "v",
+        json,
+        "--base64file",
+        "b",
+        text,
+        "$ARGS.named",
+    ];
+    let (_, stdout, _) = qj_exit(&args, "");
+    assert_eq!(
+        stdout.trim(),
+        r#"{"a":"x","t":"hi\n","v":[1,2],"b":"aGkK"}"#
+    );
+}
+
+// ---------------------------------------------------------------------------
+// --slurpfile
 // ---------------------------------------------------------------------------
 
 #[test]
@@ -4163,6 +7335,167 @@ fn slurpfile_binding() {
     std::fs::remove_file(&path).ok();
 }
 
+#[test]
+fn slurpfile_several_values_per_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("values.json");
+    std::fs::write(&path, "{\"a\":1} [2]\n3\n").unwrap();
+    let (code, stdout, _) = qj_exit(
+        &[
+            "-nc",
+            "$data",
+            "--slurpfile",
+            "data",
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!((code, stdout.as_str()), (0, "[{\"a\":1},[2],3]\n"));
+}
+
+// ---------------------------------------------------------------------------
+// import "NAME" as $NAME (data modules)
+// ---------------------------------------------------------------------------
+
+#[test]
+fn data_import_binds_file_values() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("countries.json"),
+        "{\"nl\":\"Netherlands\"}\n[1,\n 2]\n",
+    )
+    .unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/inner.json"), "\"in sub\"").unwrap();
+    std::fs::create_dir(dir.path().join("pkg")).unwrap();
+    std::fs::write(dir.path().join("pkg/pkg.json"), "\"in pkg\"").unwrap();
+    let lib = dir.path().to_str().unwrap();
+
+    for (filter, expected) in [
+        // Every value in the file, in one array.
+        (
+            "import \"countries\" as $c; $c[0].nl, $c::c[1][1]",
+            "\"Netherlands\"\n2\n",
+        ),
+        ("import \"sub/inner\" as $i; $i", "[\"in sub\"]\n"),
+        (
+            "import \"inner\" as $i {search: \"sub\"}; $i",
+            "[\"in sub\"]\n",
+        ),
+        ("import \"pkg\" as $p; $p", "[\"in pkg\"]\n"),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-nc", "-L", lib, filter], "");
+        assert_eq!((code, stdout.as_str()), (0, expected), "{filter}: {stderr}");
+    }
+}
+
+#[test]
+fn data_import_and_code_import_of_one_name() {
+    // `as $x` reads NAME.json and `as x` reads NAME.jq, whichever exists.
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("both.json"), "\"data\"").unwrap();
+    std::fs::write(dir.path().join("both.jq"), "def what: \"code\";").unwrap();
+    let lib = dir.path().to_str().unwrap();
+    let (code, stdout, _) = qj_exit(
+        &[
+            "-nc",
+            "-L",
+            lib,
+            "import \"both\" as $b; import \"both\" as b; $b, b::what",
+        ],
+        "",
+    );
+    assert_eq!((code, stdout.as_str()), (0, "[\"data\"]\n\"code\"\n"));
+}
+
+#[test]
+fn data_import_errors_name_the_module() {
+    let dir = tempfile::tempdir().unwrap();
+    let bad = dir.path().join("bad.json");
+    std::fs::write(&bad, "{\"a\":1}\n{\"a\":\n").unwrap();
+    let lib = dir.path().to_str().unwrap();
+
+    let (code, stdout, stderr) = qj_exit(&["-n", "-L", lib, "import \"missing\" as $m; $m"], "");
+    assert_ne!(code, 0);
+    assert_eq!(stdout, "");
+    assert!(
+        stderr.contains("data module not found: \"missing\""),
+        "{stderr}"
+    );
+    assert!(stderr.contains(lib), "{stderr}");
+
+    let (code, _, stderr) = qj_exit(&["-n", "-L", lib, "import \"bad\" as $b; $b"], "");
+    assert_ne!(code, 0);
+    let expected = format!("data module \"bad\": {}: EOF", bad.display());
+    assert!(stderr.contains(&expected), "{stderr}");
+}
+
+// ---------------------------------------------------------------------------
+// --argfile
+// ---------------------------------------------------------------------------
+
+#[test]
+fn argfile_binds_single_value() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("single.json");
+    std::fs::write(&path, "  {\"b\":1,\"a\":[1,2]}\n").unwrap();
+
+    let (code, stdout, _) = qj_exit(
+        &[
+            "-nc",
+            "$cfg.a[1], $ARGS.named",
+            "--argfile",
+            "cfg",
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "2\n{\"cfg\":{\"b\":1,\"a\":[1,2]}}\n");
+}
+
+#[test]
+fn argfile_multiple_documents_is_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("multi.json");
+    std::fs::write(&path, "{\"a\":1}\n  {\"b\":2}\n").unwrap();
+    let path = path.to_str().unwrap();
+
+    let (code, stdout, stderr) = qj_exit(&["-n", "$x", "--argfile", "x", path], "");
+    assert_eq!(code, 2);
+    assert!(stdout.is_empty());
+    assert!(
+        stderr.contains(path),
+        "stderr should name the file: {stderr}"
+    );
+    assert!(stderr.contains("byte offset 10"), "stderr: {stderr}");
+}
+
+#[test]
+fn argfile_invalid_json_reports_offset() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bad.json");
+    std::fs::write(&path, "[1,\n 2,,3]").unwrap();
+
+    let (code, _, stderr) = qj_exit(&["-n", "$x", "--argfile", "x", path.to_str().unwrap()], "");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("bad.json"), "stderr: {stderr}");
+    assert!(stderr.contains("byte offset 7"), "stderr: {stderr}");
+}
+
+#[test]
+fn argfile_missing_file_mentions_path() {
+    let (code, _, stderr) = qj_exit(
+        &["-n", "$x", "--argfile", "x", "/nonexistent/qj_argfile.json"],
+        "",
+    );
+    assert_eq!(code, 2);
+    assert!(
+        stderr.contains("/nonexistent/qj_argfile.json"),
+        "stderr: {stderr}"
+    );
+}
+
 // ---------------------------------------------------------------------------
 // $ARGS
 // ---------------------------------------------------------------------------
@@ -4290,113 +7623,1477 @@ fn raw_output0_embedded_nul_error() {
     );
 }
 
-#[test]
-fn raw_output0_embedded_nul_partial_output() {
-    // Values before the NUL-containing string should still be output
-    let input = r#"["ok","x\u0000y","fine"]"#;
-    let (code, stdout, stderr) = qj_exit(&["--raw-output0", ".[]"], input);
-    assert_eq!(code, 5);
-    assert_eq!(stdout.as_bytes(), b"ok\0", "should output 'ok' then stop");
-    assert!(stderr.contains("NUL"));
-}
-
-#[test]
-fn raw_output0_no_nul_in_string_ok() {
-    // Normal strings (no embedded NUL) should work fine
-    let (code, _, _) = qj_exit(&["--raw-output0", "."], r#""hello world""#);
-    assert_eq!(code, 0);
-}
-
 // ---------------------------------------------------------------------------
-// --ascii-output / -a
+// --raw-input0
 // ---------------------------------------------------------------------------
 
 #[test]
-fn ascii_output_escapes_non_ascii() {
-    let (code, stdout, _) = qj_exit(&["-ac", "."], r#""café""#);
+fn raw_input0_records_keep_newlines_and_spaces() {
+    let (code, stdout, _) = qj_exit(
+        &["-c", "--raw-input0", "."],
+        "my file.txt\0two\nlines\0 padded \0",
+    );
     assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), r#""caf\u00e9""#);
+    assert_eq!(stdout, "\"my file.txt\"\n\"two\\nlines\"\n\" padded \"\n");
 }
 
 #[test]
-fn ascii_output_surrogate_pairs() {
-    // Emoji (U+1F30D) should be encoded as surrogate pair
-    let (code, stdout, _) = qj_exit(&["-ac", "."], "\"\\ud83c\\udf0d\"");
+fn raw_input0_empty_records_and_trailing_record() {
+    // Consecutive NULs give empty records; text after the last NUL is still a record.
+    let (code, stdout, _) = qj_exit(&["-c", "--raw-input0", "length"], "a\0\0\0bc");
     assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), r#""\ud83c\udf0d"#.to_owned() + "\"");
+    assert_eq!(stdout, "1\n0\n0\n2\n");
+    let (_, stdout, _) = qj_exit(&["-c", "--raw-input0", "."], "\0");
+    assert_eq!(stdout, "\"\"\n");
+    let (_, stdout, _) = qj_exit(&["-c", "--raw-input0", "."], "");
+    assert_eq!(stdout, "");
 }
 
 #[test]
-fn ascii_output_escapes_keys() {
-    let (code, stdout, _) = qj_exit(&["-ac", "."], r#"{"café":"latte"}"#);
+fn raw_input0_slurp_collects_array() {
+    let (code, stdout, _) = qj_exit(&["-s", "-c", "--raw-input0", "."], "a\nb\0\0c\0");
     assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), r#"{"caf\u00e9":"latte"}"#);
+    assert_eq!(stdout, "[\"a\\nb\",\"\",\"c\"]\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a");
+    let b = dir.path().join("b");
+    std::fs::write(&a, "x\0y").unwrap();
+    std::fs::write(&b, "z\0").unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+    let (_, stdout, _) = qj_exit(&["-sc", "--raw-input0", ".", a, b], "");
+    assert_eq!(stdout, "[\"x\",\"y\",\"z\"]\n");
+    let (_, stdout, _) = qj_exit(&["-c", "--raw-input0", ".", a, b], "");
+    assert_eq!(stdout, "\"x\"\n\"y\"\n\"z\"\n");
 }
 
 #[test]
-fn ascii_output_with_raw_mode() {
-    // jq with -ra outputs JSON-encoded string (with quotes) when -a is active
-    let (code, stdout, _) = qj_exit(&["-ra", "."], r#""café""#);
+fn raw_input0_with_null_input_and_raw_output0() {
+    let (code, stdout, _) = qj_exit(&["-nc", "--raw-input0", "[inputs]"], "p q\0r\ns");
     assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), r#""caf\u00e9""#);
+    assert_eq!(stdout, "[\"p q\",\"r\\ns\"]\n");
+    // NUL in, NUL out: a safe round trip for `find -print0` style lists.
+    let (code, stdout, _) = qj_exit(
+        &["--raw-input0", "--raw-output0", "ascii_upcase"],
+        "a b\0c\nd\0",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "A B\0C\nD\0");
 }
 
+// ---------------------------------------------------------------------------
+// --csv-input / --tsv-input
+// ---------------------------------------------------------------------------
+
 #[test]
-fn ascii_output_pretty() {
-    let (code, stdout, _) = qj_exit(&["-a", "."], r#"{"café":"latté"}"#);
-    assert_eq!(code, 0);
+fn csv_input_quoting_and_empty_fields() {
+    let csv = "name,note,n\r\n\"Smith, J\",\"said \"\"hi\"\"\nbye\",1\r\nbob,,\r\n";
+    let (code, stdout, stderr) = qj_exit(&["-c", "--csv-input", "."], csv);
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(
+        stdout,
+        "{\"name\":\"Smith, J\",\"note\":\"said \\\"hi\\\"\\nbye\",\"n\":\"1\"}\n\
+         {\"name\":\"bob\",\"note\":\"\",\"n\":\"\"}\n"
+    );
+
+    let (_, stdout, _) = qj_exit(&["-c", "--csv-input", "--csv-numbers", ".n"], csv);
+    assert_eq!(stdout, "1\n\"\"\n");
+}
+
+#[test]
+fn tsv_input_records() {
+    let tsv = "id\ttext\n1\ta\\tb\n2\t\n";
+    let (code, stdout, _) = qj_exit(&["-c", "--tsv-input", "--csv-numbers", "."], tsv);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "{\"id\":1,\"text\":\"a\\tb\"}\n{\"id\":2,\"text\":\"\"}\n"
+    );
+}
+
+#[test]
+fn csv_input_ragged_row_is_an_error() {
+    let (code, stdout, stderr) = qj_exit(&["-c", "--csv-input", "."], "a,b\n1,2\n3,4,5\n");
+    assert_eq!(code, 5);
+    assert!(stdout.is_empty());
+    assert_eq!(
+        stderr,
+        "qj: error (at <stdin>): CSV line 3: expected 2 fields, found 3\n"
+    );
+}
+
+#[test]
+fn csv_input_slurp_and_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.csv");
+    let b = dir.path().join("b.csv");
+    std::fs::write(&a, "x,y\n1,2\n3,4\n").unwrap();
+    std::fs::write(&b, "y\n5\n").unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let (code, stdout, _) = qj_exit(&["-c", "--csv-input", "-s", "map(.y)", a, b], "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[\"2\",\"4\",\"5\"]\n");
+
+    let (_, stdout, _) = qj_exit(&["-c", "--csv-input", "-n", "[inputs.y]", a, b], "");
+    assert_eq!(stdout, "[\"2\",\"4\",\"5\"]\n");
+}
+
+#[test]
+fn csv_input_uses_parallel_ndjson_path() {
+    let mut csv = String::from("id,v\n");
+    for i in 0..200_000 {
+        csv.push_str(&format!("{i},\"v {i}\"\n"));
+    }
+    let (code, stdout, stderr) = qj_exit(
+        &[
+            "--debug-parallel",
+            "-c",
+            "--csv-input",
+            "select(.id == \"12345\") | .v",
+        ],
+        &csv,
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "\"v 12345\"\n");
+    assert!(stderr.contains("ndjson parallel"), "{stderr}");
+    assert!(!stderr.contains("(1 chunks)"), "{stderr}");
+}
+
+/// A top-level array of `n` records, several 1 MB chunks long.
+fn multi_chunk_array(n: usize) -> String {
+    let records: Vec<String> = (0..n)
+        .map(|i| {
+            format!(
+                r#"{{"i":{i},"tag":"t{i}","tags":["a","b"],"pad":"{}"}}"#,
+                "x".repeat(64)
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", records.join(",\n"))
+}
+
+/// Run `filter` over `input` on stdin and from a file, with the parallel
+/// array path available, and compare both with sequential evaluation.
+fn assert_array_parallel_matches(filter: &str, input: &str) -> (i32, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("array.json");
+    std::fs::write(&path, input).unwrap();
+    // Binding the input first keeps the filter off the parallel path.
+    let sequential = format!(". as $x | $x | ({filter})");
+    let expected = qj_exit(&["--threads", "4", "-c", &sequential], input);
+    for file in [None, Some(path.to_str().unwrap())] {
+        let mut args = vec!["--threads", "4", "--debug-parallel", "-c", filter];
+        args.extend(file);
+        let (code, stdout, stderr) = qj_exit(&args, if file.is_some() { "" } else { input });
+        assert!(stderr.contains("array parallel ("), "{filter}: {stderr}");
+        assert_eq!(
+            (code, &stdout),
+            (expected.0, &expected.1),
+            "{filter} {file:?}"
+        );
+    }
+    (expected.0, expected.1)
+}
+
+#[test]
+fn big_array_parallel_matches_sequential() {
+    let input = multi_chunk_array(40_000);
+    for filter in [
+        ".[] | .i + 1",
+        ".[] | select(.i % 1000 == 7) | {i, tag}",
+        ".[].tags | first",
+        "map(.i * 2)",
+        "map(select(.i < 3) | .tag)",
+        ".[]",
+    ] {
+        let (code, stdout) = assert_array_parallel_matches(filter, &input);
+        assert_eq!(code, 0, "{filter}");
+        assert!(!stdout.is_empty(), "{filter}");
+    }
+}
+
+#[test]
+fn big_array_parallel_errors_and_exit_status() {
+    // An error part-way through keeps the output before it, as sequentially.
+    let input = multi_chunk_array(40_000).replace(r#""i":30000,"#, r#""i":"x","#);
+    let (code, stdout) = assert_array_parallel_matches(".[] | .i + 1", &input);
+    assert_eq!(code, 5);
+    assert_eq!(stdout.lines().count(), 30_000);
+    // Like jq, an error inside map() leaves no partial array behind.
+    let (code, stdout, stderr) = qj_exit(
+        &["--threads", "4", "--debug-parallel", "-c", "map(.i + 1)"],
+        &input,
+    );
+    assert!(stderr.contains("array parallel ("), "{stderr}");
+    assert_eq!((code, stdout.as_str()), (5, ""));
+
+    // -e looks at the last output, which comes from the last chunk.
+    let input = multi_chunk_array(40_000);
+    let (code, _, _) = qj_exit(&["--threads", "4", "-e", "-c", ".[] | .i < 39999"], &input);
+    assert_eq!(code, 1);
+    let (code, _, _) = qj_exit(&["--threads", "4", "-e", "-c", ".[] | .i > 0"], &input);
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn big_array_parallel_falls_back() {
+    let input = multi_chunk_array(40_000);
+    // Filters that need the whole array, and documents that aren't one array.
+    for (filter, input) in [
+        ("[.[] | .i] | add", input.clone()),
+        ("first(.[] | .i)", input.clone()),
+        (".[] | .i", format!("{input} [1]")),
+        (".[] | .i", input.replace(r#""i":39999,"#, r#""i":nan,"#)),
+    ] {
+        let (_, _, stderr) = qj_exit(
+            &["--threads", "4", "--debug-parallel", "-c", filter],
+            &input,
+        );
+        assert!(!stderr.contains("array parallel"), "{filter}: {stderr}");
+    }
+    let (code, stdout, _) = qj_exit(&["--threads", "4", "-c", "first(.[] | .i)"], &input);
+    assert_eq!((code, stdout.as_str()), (0, "0\n"));
+}
+
+/// `count` pretty-printed records, one document after another, spanning
+/// several parallel chunks.
+fn pretty_documents(count: usize) -> String {
+    (0..count)
+        .map(|i| {
+            format!(
+                "{{\n  \"i\": {i},\n  \"tag\": \"t{i}}}{{\",\n  \"tags\": [\n    \"a\"\n  ]\n}}\n"
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn pretty_documents_match_jq() {
+    let input = "{\n  \"a\": 1,\n  \"s\": \"x}{\"\n}\n{\n  \"a\": 2\n}\n[\n  3\n]\n";
+    assert_jq_compat(".", input);
+    assert_jq_compat(".a? // .[0]", input);
+}
+
+#[test]
+fn pretty_documents_parallel_matches_sequential() {
+    let input = pretty_documents(30_000);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("docs.json");
+    std::fs::write(&path, &input).unwrap();
+    for filter in [".i", "select(.i % 1000 == 7) | {i, tag}", "."] {
+        let (code, sequential, stderr) = qj_exit(&["--threads", "1", "-c", filter], &input);
+        assert_eq!(code, 0, "{filter}: {stderr}");
+        assert!(!sequential.is_empty(), "{filter}");
+        for file in [None, Some(path.to_str().unwrap())] {
+            let mut args = vec!["--threads", "4", "--debug-parallel", "-c", filter];
+            args.extend(file);
+            let (code, stdout, stderr) = qj_exit(&args, if file.is_some() { "" } else { &input });
+            assert!(stderr.contains("document parallel ("), "{filter}: {stderr}");
+            assert_eq!((code, &stdout), (0, &sequential), "{filter} {file:?}");
+        }
+    }
+    // An error in one document is reported and the others still run. It
+    // isn't the last, so the exit status is 0.
+    let input = input.replace("\"i\": 20000,", "\"i\": \"x\",");
+    let (code, stdout, stderr) = qj_exit(&["--threads", "4", "-c", ".i + 1"], &input);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.lines().count(), 29_999);
+    assert_eq!(stderr.lines().count(), 1, "{stderr}");
+}
+
+#[test]
+fn pretty_documents_malformed_falls_back() {
+    let input = "{\n  \"a\": 1\n}\n{\n  \"a\":\n}\n{\n  \"a\": 3\n}\n";
+    let (code, stdout, stderr) =
+        qj_exit(&["--threads", "4", "--debug-parallel", "-c", ".a"], input);
+    assert!(!stderr.contains("document"), "{stderr}");
+    assert!(stderr.contains("qj: error"), "{stderr}");
+    assert_eq!((code, stdout.as_str()), (5, "1\n"));
+}
+
+/// Several small single-document files, one per value of `i`.
+fn numbered_files(dir: &std::path::Path, count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            let path = dir.join(format!("f{i:02}.json"));
+            std::fs::write(&path, format!("{{\"i\":{i},\"tags\":[\"t{i}\"]}}")).unwrap();
+            path.to_string_lossy().into_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn multiple_files_parallel_keeps_argv_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut files = numbered_files(dir.path(), 24);
+    // argv order, not name order, decides the output order.
+    files.reverse();
+    let ndjson = dir.path().join("rows.ndjson");
+    std::fs::write(&ndjson, "{\"i\":100}\n{\"i\":101}\n").unwrap();
+    files.insert(5, ndjson.to_string_lossy().into_owned());
+
+    let run = |threads: &str| {
+        let mut args = vec![
+            "--threads",
+            threads,
+            "--debug-parallel",
+            "-c",
+            "{i, t: .tags[0]}",
+        ];
+        args.extend(files.iter().map(String::as_str));
+        qj_exit(&args, "")
+    };
+    let (code, parallel, stderr) = run("4");
+    assert_eq!(code, 0, "{stderr}");
+    assert!(stderr.contains("file parallel"), "{stderr}");
+    let (_, sequential, stderr) = run("1");
+    assert!(!stderr.contains("file parallel"), "{stderr}");
+    assert_eq!(parallel, sequential);
+
+    let order: Vec<String> = parallel
+        .lines()
+        .map(|l| l.split(',').next().unwrap().to_string())
+        .collect();
+    let mut expected: Vec<String> = (0..24).rev().map(|i| format!("{{\"i\":{i}")).collect();
+    expected.splice(5..5, ["{\"i\":100".to_string(), "{\"i\":101".to_string()]);
+    assert_eq!(order, expected);
+}
+
+#[test]
+fn multiple_files_parallel_unreadable_file_exits_2() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut files = numbered_files(dir.path(), 6);
+    let missing = dir.path().join("missing.json");
+    files.insert(3, missing.to_string_lossy().into_owned());
+    let mut args = vec!["--threads", "4", "-c", ".i"];
+    args.extend(files.iter().map(String::as_str));
+    let (code, stdout, stderr) = qj_exit(&args, "");
+    assert_eq!(code, 2);
+    assert_eq!(stdout, "0\n1\n2\n3\n4\n5\n");
+    assert_eq!(stderr.lines().count(), 1, "{stderr}");
+    assert!(
+        stderr.starts_with(&format!(
+            "qj: error: Could not open file {}:",
+            missing.display()
+        )),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn uncaught_errors_name_source_and_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let files: Vec<String> = ["1", "\"x\"", "3"]
+        .iter()
+        .enumerate()
+        .map(|(i, doc)| {
+            let path = dir.path().join(format!("f{i}.json"));
+            std::fs::write(&path, format!("{{\"a\":{doc}}}\n")).unwrap();
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+    let mut args = vec!["-c", ".a + 1"];
+    args.extend(files.iter().map(String::as_str));
+    let (code, stdout, stderr) = qj_exit(&args, "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "2\n4\n");
+    let expected = format!("qj: error (at {}:1): ", files[1]);
+    assert!(stderr.starts_with(&expected), "{stderr}");
+    assert_eq!(stderr.lines().count(), 1, "{stderr}");
+
+    // An NDJSON record is located at its own line, in a file or on stdin.
+    let ndjson: String = (1..=100)
+        .map(|i| match i {
+            42 => "{\"a\":\"x\"}\n".to_string(),
+            _ => format!("{{\"a\":{i}}}\n"),
+        })
+        .collect();
+    let path = dir.path().join("data.jsonl");
+    std::fs::write(&path, &ndjson).unwrap();
+    let path = path.to_string_lossy().into_owned();
+    let (code, _, stderr) = qj_exit(&["-c", ".a + 1", &path], "");
+    assert_eq!(code, 0);
+    assert!(
+        stderr.starts_with(&format!("qj: error (at {path}:42): ")),
+        "{stderr}"
+    );
+    let (_, _, stderr) = qj_exit(&["-c", ".a + 1"], &ndjson);
+    assert!(
+        stderr.starts_with("qj: error (at <stdin>:42): "),
+        "{stderr}"
+    );
+
+    // A document spanning lines is located at the line it ends on.
+    let (_, _, stderr) = qj_exit(&[".a + 1"], "{\"a\":\n\"x\"\n}\n");
+    assert!(stderr.starts_with("qj: error (at <stdin>:3): "), "{stderr}");
+    let (_, _, stderr) = qj_exit(&["-R", "error"], "a\nb\n");
+    assert_eq!(
+        stderr,
+        "qj: error (at <stdin>:1): a\nqj: error (at <stdin>:2): b\n"
+    );
+    let (_, _, stderr) = qj_exit(&["-n", "error(\"x\")"], "");
+    assert_eq!(stderr, "qj: error (at <unknown>): x\n");
+}
+
+#[test]
+fn multiple_files_parallel_errors_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = numbered_files(dir.path(), 6);
+    let filter = r#"if .i % 2 == 1 then error("odd \(.i)") else .i end"#;
+    let run = |threads: &str| {
+        let mut args = vec!["--threads", threads, "-c", filter];
+        args.extend(files.iter().map(String::as_str));
+        qj_exit(&args, "")
+    };
+    let (code, stdout, stderr) = run("4");
+    assert_eq!(code, 5);
+    assert_eq!(stdout, "0\n2\n4\n");
+    let expected: String = [1, 3, 5]
+        .iter()
+        .map(|&i| format!("qj: error (at {}:0): odd {i}\n", files[i]))
+        .collect();
+    assert_eq!(stderr, expected);
+    assert_eq!(run("1"), (code, stdout, stderr));
+}
+
+#[test]
+fn raw_output0_embedded_nul_partial_output() {
+    // Values before the NUL-containing string should still be output
+    let input = r#"["ok","x\u0000y","fine"]"#;
+    let (code, stdout, stderr) = qj_exit(&["--raw-output0", ".[]"], input);
+    assert_eq!(code, 5);
+    assert_eq!(stdout.as_bytes(), b"ok\0", "should output 'ok' then stop");
+    assert!(stderr.contains("NUL"));
+}
+
+#[test]
+fn raw_output0_no_nul_in_string_ok() {
+    // Normal strings (no embedded NUL) should work fine
+    let (code, _, _) = qj_exit(&["--raw-output0", "."], r#""hello world""#);
+    assert_eq!(code, 0);
+}
+
+// ---------------------------------------------------------------------------
+// --ascii-output / -a
+// ---------------------------------------------------------------------------
+
+#[test]
+fn ascii_output_escapes_non_ascii() {
+    let (code, stdout, _) = qj_exit(&["-ac", "."], r#""café""#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#""caf\u00e9""#);
+}
+
+#[test]
+fn ascii_output_surrogate_pairs() {
+    // Emoji (U+1F30D) should be encoded as surrogate pair
+    let (code, stdout, _) = qj_exit(&["-ac", "."], "\"\\ud83c\\udf0d\"");
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#""\ud83c\udf0d"#.to_owned() + "\"");
+}
+
+#[test]
+fn ascii_output_escapes_keys() {
+    let (code, stdout, _) = qj_exit(&["-ac", "."], r#"{"café":"latte"}"#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#"{"caf\u00e9":"latte"}"#);
+}
+
+#[test]
+fn string_escapes_match_jq() {
+    // Every control character, DEL (escaped and raw), U+2028 (escaped and
+    // raw), and slashes (plain and escaped).
+    let mut input = String::from("\"");
+    let mut escaped = String::new();
+    for c in 0u8..0x20 {
+        input.push_str(&format!("\\u{c:04X}"));
+        escaped.push_str(match c {
+            0x08 => "\\b",
+            0x09 => "\\t",
+            0x0a => "\\n",
+            0x0c => "\\f",
+            0x0d => "\\r",
+            _ => "",
+        });
+        if !matches!(c, 0x08..=0x0a | 0x0c | 0x0d) {
+            escaped.push_str(&format!("\\u{c:04x}"));
+        }
+    }
+    input.push_str("\\u007F\x7f\\u2028\u{2028}/\\/\\\"\"");
+    escaped.push_str("\\u007f\\u007f\u{2028}\u{2028}//\\\"");
+    let ascii = escaped.replace('\u{2028}', "\\u2028");
+
+    for (args, expected) in [
+        (&["-c", "."][..], format!("\"{escaped}\"")),
+        (&["."], format!("\"{escaped}\"")),
+        (&["-c", "[.]"], format!("[\"{escaped}\"]")),
+        (&["-c", "{(.): 1}"], format!("{{\"{escaped}\":1}}")),
+        (&["-c", "{(.): 1} | keys"], format!("[\"{escaped}\"]")),
+        (&["-r", "tojson"], format!("\"{escaped}\"")),
+        (&["-r", "@json"], format!("\"{escaped}\"")),
+        (&["-r", "\"\\([.])\""], format!("[\"{escaped}\"]")),
+        (&["-r", "[.] | tostring"], format!("[\"{escaped}\"]")),
+        (&["-ac", "."], format!("\"{ascii}\"")),
+        (&["-ac", "{(.): 1}"], format!("{{\"{ascii}\":1}}")),
+    ] {
+        let stdout = qj_raw_bytes(args, input.as_bytes());
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            format!("{expected}\n"),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn ascii_output_with_raw_mode() {
+    // jq with -ra outputs JSON-encoded string (with quotes) when -a is active
+    let (code, stdout, _) = qj_exit(&["-ra", "."], r#""café""#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#""caf\u00e9""#);
+}
+
+#[test]
+fn ascii_output_pretty() {
+    let (code, stdout, _) = qj_exit(&["-a", "."], r#"{"café":"latté"}"#);
+    assert_eq!(code, 0);
     assert!(stdout.contains(r#""caf\u00e9""#));
     assert!(stdout.contains(r#""latt\u00e9""#));
 }
 
-#[test]
-fn ascii_output_ascii_passthrough() {
-    // Pure ASCII strings should be unchanged
-    let (code, stdout, _) = qj_exit(&["-ac", "."], r#""hello world""#);
-    assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), r#""hello world""#);
+#[test]
+fn ascii_output_ascii_passthrough() {
+    // Pure ASCII strings should be unchanged
+    let (code, stdout, _) = qj_exit(&["-ac", "."], r#""hello world""#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#""hello world""#);
+}
+
+#[test]
+fn ascii_output_jq_compat() {
+    if !jq_available() {
+        return;
+    }
+    // Test various non-ASCII strings against jq
+    for input in &[r#""café""#, r#""日本語""#, r#"{"ñ":"ü"}"#] {
+        let qj_out = {
+            let (code, stdout, _) = qj_exit(&["-ac", "."], input);
+            assert_eq!(code, 0);
+            stdout
+        };
+        let jq_out =
+            run_jq(&["-ac", "."], input).unwrap_or_else(|| panic!("jq failed on input={input:?}"));
+        assert_eq!(
+            qj_out.trim(),
+            jq_out.trim(),
+            "qj vs jq -ac mismatch: input={input:?}"
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// --profile
+// ---------------------------------------------------------------------------
+
+/// The `[calls, outputs, kind]` columns of the `--profile` row for `node`.
+fn profile_row(stderr: &str, node: &str) -> Vec<String> {
+    let line = stderr
+        .lines()
+        .find(|l| l.ends_with(&format!("  {node}")))
+        .unwrap_or_else(|| panic!("no profile row for {node:?} in:\n{stderr}"));
+    line.split_whitespace()
+        .skip(1)
+        .take(3)
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn profile_reports_nodes_without_changing_output() {
+    let filter = "[.items[] | select(.n > 1) | .n * 10]";
+    let input = r#"{"items":[{"n":1},{"n":2},{"n":3}]}"#;
+    let (code, stdout, stderr) = qj_exit(&["-c", "--profile", filter], input);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, qj_compact(filter, input));
+    assert!(stderr.starts_with("--- profile ---\n"), "{stderr}");
+    assert_eq!(profile_row(&stderr, "select(.n > 1)"), ["3", "2", "Select"]);
+    assert_eq!(profile_row(&stderr, ".n > 1"), ["3", "3", "Compare"]);
+    assert_eq!(profile_row(&stderr, ".n * 10"), ["2", "2", "Arith"]);
+    assert_eq!(profile_row(&stderr, ".[]"), ["1", "3", "Iterate"]);
+
+    let (_, _, stderr) = qj_exit(&["-c", filter], input);
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn profile_null_input_and_files() {
+    let (code, stdout, stderr) = qj_exit(
+        &["--profile", "-n", "reduce range(5) as $i (0; . + $i)"],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "10\n");
+    assert_eq!(
+        profile_row(&stderr, "reduce range(5) as $i (0; . + $i)"),
+        ["1", "1", "Reduce"]
+    );
+    assert_eq!(profile_row(&stderr, ". + $i"), ["5", "5", "Arith"]);
+
+    // Files take the same evaluator, passthrough included
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.json");
+    let b = dir.path().join("b.json");
+    std::fs::write(&a, r#"{"x":1}"#).unwrap();
+    std::fs::write(&b, r#"{"x":2}"#).unwrap();
+    let (code, stdout, stderr) = qj_exit(
+        &[
+            "-c",
+            "--profile",
+            ".",
+            a.to_str().unwrap(),
+            b.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "{\"x\":1}\n{\"x\":2}\n");
+    assert_eq!(profile_row(&stderr, "."), ["2", "2", "Identity"]);
+}
+
+#[test]
+fn profile_is_printed_on_runtime_errors() {
+    let (code, _, stderr) = qj_exit(&["--profile", ".a + 1"], r#"{"a":"s"}"#);
+    assert_eq!(code, 5);
+    assert!(stderr.contains("cannot be added"), "{stderr}");
+    assert_eq!(profile_row(&stderr, ".a + 1"), ["1", "0", "Arith"]);
+}
+
+// ---------------------------------------------------------------------------
+// --yaml-input
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "yaml")]
+const K8S_YAML: &str = "\
+kind: Deployment
+metadata: &meta
+  name: web
+  labels: {app: web}
+spec:
+  template:
+    metadata: *meta
+    spec:
+      containers:
+        - name: web
+          image: nginx:1.25
+        - name: sidecar
+          image: envoy:1.0
+---
+kind: Service
+metadata:
+  name: web
+  port: 80
+  version: \"1.10\"
+  zip: '02134'
+";
+
+#[cfg(feature = "yaml")]
+#[test]
+fn yaml_input_multi_document_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("deploy.yaml");
+    std::fs::write(&path, K8S_YAML).unwrap();
+    let path = path.to_str().unwrap();
+
+    let (code, stdout, _) = qj_exit(
+        &[
+            "--yaml-input",
+            "-r",
+            ".spec.template.spec.containers[]?.image",
+        ],
+        K8S_YAML,
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "nginx:1.25\nenvoy:1.0\n");
+
+    let (code, stdout, _) = qj_exit(&["--yaml-input", "-c", "-s", "map(.kind)", path], "");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[\"Deployment\",\"Service\"]\n");
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn yaml_input_expands_anchors_and_keeps_quoted_strings() {
+    let (_, stdout, _) = qj_exit(
+        &[
+            "--yaml-input",
+            "-c",
+            "select(.kind == \"Deployment\") | .spec.template.metadata",
+        ],
+        K8S_YAML,
+    );
+    assert_eq!(stdout, "{\"name\":\"web\",\"labels\":{\"app\":\"web\"}}\n");
+
+    let (_, stdout, _) = qj_exit(
+        &[
+            "--yaml-input",
+            "-c",
+            "select(.kind == \"Service\") | .metadata | [.port, .version, .zip]",
+        ],
+        K8S_YAML,
+    );
+    assert_eq!(stdout, "[80,\"1.10\",\"02134\"]\n");
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn yaml_input_rejects_non_json_values() {
+    let (code, _, stderr) = qj_exit(&["--yaml-input", "."], "? [a, b]\n: 1\n");
+    assert_eq!(code, 5);
+    assert_eq!(
+        stderr,
+        "qj: error (at <stdin>): YAML document 1: mapping keys must be strings, found sequence\n"
+    );
+    let (code, _, stderr) = qj_exit(&["--yaml-input", "."], "data: !!binary aGVsbG8=\n");
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains("!!binary values have no JSON equivalent"),
+        "{stderr}"
+    );
+}
+
+#[cfg(not(feature = "yaml"))]
+#[test]
+fn yaml_input_requires_feature() {
+    let (code, stdout, stderr) = qj_exit(&["--yaml-input", "."], "a: 1\n");
+    assert_eq!(code, 2);
+    assert!(stdout.is_empty());
+    assert!(
+        stderr.contains("built without the `yaml` feature"),
+        "{stderr}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// --relaxed
+// ---------------------------------------------------------------------------
+
+const RELAXED_CONFIG: &str = "\
+// service config
+{
+  name: 'web',        // unquoted key, single quotes
+  \"ports\": [80, 443,], /* trailing comma */
+  url: 'http://example.com/*not-a-comment*/',
+  note: \"it's // kept\",
+  nested: {$ref: 'a\\'b', enabled: true,},
+}
+";
+
+#[test]
+fn relaxed_input_accepts_config_style_json() {
+    let (code, stdout, stderr) = qj_exit(&["--relaxed", "-c", "."], RELAXED_CONFIG);
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(
+        stdout,
+        "{\"name\":\"web\",\"ports\":[80,443],\"url\":\"http://example.com/*not-a-comment*/\",\"note\":\"it's // kept\",\"nested\":{\"$ref\":\"a'b\",\"enabled\":true}}\n"
+    );
+
+    // Several values per input, and files work like stdin
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json5");
+    std::fs::write(&path, RELAXED_CONFIG).unwrap();
+    let (code, stdout, _) = qj_exit(
+        &[
+            "--relaxed",
+            "-c",
+            "[.ports[]] | add",
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "523\n");
+    let (code, stdout, _) = qj_exit(&["--relaxed", "-c", "-s", "."], "{a: 1,} // one\n[2,]");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[{\"a\":1},[2]]\n");
+}
+
+#[test]
+fn relaxed_input_errors_point_into_original_text() {
+    let (code, stdout, stderr) = qj_exit(
+        &["--relaxed", "."],
+        "{\n  // the port\n  port: 80,\n  host: localhost,\n}\n",
+    );
+    assert_eq!(code, 5);
+    assert!(stdout.is_empty());
+    assert_eq!(
+        stderr,
+        "qj: error (at <stdin>): relaxed JSON line 4, column 9: unexpected identifier 'localhost'\n"
+    );
+    let (code, _, stderr) = qj_exit(&["--relaxed", "."], "[1, 2 /* never closed\n");
+    assert_eq!(code, 5);
+    assert!(
+        stderr.contains("relaxed JSON line 1, column 7: unterminated comment"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn strict_input_still_rejects_relaxed_syntax() {
+    for input in ["{a: 1}", "[1,]", "// c\n1", "'x'"] {
+        let (code, _, _) = qj_exit(&["-c", "."], input);
+        assert_ne!(code, 0, "strict mode accepted {input:?}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Raw output (-r) formatting
+// ---------------------------------------------------------------------------
+
+#[test]
+fn raw_output_non_strings_follow_indent() {
+    let input = r#"{"s":"x\ny","o":{"b":[1,2]},"n":1.5}"#;
+    let (_, stdout, _) = qj_exit(&["-r", ".s, .o, .n"], input);
+    assert_eq!(stdout, "x\ny\n{\n  \"b\": [\n    1,\n    2\n  ]\n}\n1.5\n");
+    let (_, stdout, _) = qj_exit(&["-rc", ".o"], input);
+    assert_eq!(stdout, "{\"b\":[1,2]}\n");
+    let (_, stdout, _) = qj_exit(&["-j", "--tab", ".o, .s"], input);
+    assert_eq!(stdout, "{\n\t\"b\": [\n\t\t1,\n\t\t2\n\t]\n}x\ny");
+}
+
+#[test]
+fn raw_output_ndjson_field_values_match_single_doc() {
+    let ndjson = "{\"a\":{\"z\": 1,\"b\": [1, 2]},\"s\":\"y\\u0041\\/\"}\n{\"a\":3,\"s\":\"t\"}\n";
+    for args in [
+        &["-r", ".a"][..],
+        &["-rS", ".a"],
+        &["-rc", ".a"],
+        &[".a"],
+        &["-c", ".s"],
+        &["-ac", ".s"],
+        &["-r", ".s"],
+    ] {
+        let (code, ndjson_out, _) = qj_exit(args, ndjson);
+        assert_eq!(code, 0);
+        let mut expected = String::new();
+        for line in ndjson.lines() {
+            expected.push_str(&qj_exit(args, line).1);
+        }
+        assert_eq!(ndjson_out, expected, "args {args:?}");
+    }
+}
+
+#[test]
+fn raw_output_jq_compat_matrix() {
+    if !jq_available() {
+        return;
+    }
+    let inputs = [
+        r#""a\nb""#,
+        r#""\u0007bell\u0000x\u001b[0m""#,
+        r#""tab\there""#,
+        r#""café\n""#,
+        r#"["é\n",1,null,true,{"b":1,"a":"x"}]"#,
+        r#"{"b":[1,{"c":[]}],"a":"é","e":{}}"#,
+        "3",
+        "null",
+    ];
+    let arg_sets: [&[&str]; 10] = [
+        &["-r", "."],
+        &["-rj", "."],
+        &["-ra", "."],
+        &["-raj", "."],
+        &["-rc", "."],
+        &["-rS", "."],
+        &["-r", "--tab", "."],
+        &["-r", "--indent", "3", "."],
+        &["-r", ".[]?"],
+        &["-j", "..|scalars"],
+    ];
+    for args in arg_sets {
+        for input in inputs {
+            let (qj_stdout, _, qj_ok) = run_tool_full(env!("CARGO_BIN_EXE_qj"), args, input);
+            let (jq_stdout, _, jq_ok) = run_tool_full("jq", args, input);
+            assert_eq!(qj_stdout, jq_stdout, "args={args:?} input={input}");
+            assert_eq!(qj_ok, jq_ok, "args={args:?} input={input}");
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Lone surrogates (decoded to U+FFFD like jq 1.7)
+// ---------------------------------------------------------------------------
+
+#[test]
+fn lone_low_surrogate_value() {
+    assert_eq!(
+        qj_compact(".", r#"{"s":"\udead"}"#).trim(),
+        "{\"s\":\"\u{FFFD}\"}"
+    );
+    assert_eq!(qj_raw(".s", r#"{"s":"\udead"}"#).trim(), "\u{FFFD}");
+    assert_jq_compat(".", r#"{"s":"\udead"}"#);
+}
+
+#[test]
+fn lone_high_surrogate_value() {
+    // jq 1.6 rejects a lone high surrogate; 1.7 replaces it, so no jq compat here.
+    assert_eq!(
+        qj_compact(".", r#"{"s":"\ud83d"}"#).trim(),
+        "{\"s\":\"\u{FFFD}\"}"
+    );
+    assert_eq!(qj_compact(".", r#""\ud800A""#).trim(), "\"\u{FFFD}A\"");
+    assert_eq!(
+        qj_compact(".", r#""\ude00\ud83d\ude00\ud83d""#).trim(),
+        "\"\u{FFFD}\u{1F600}\u{FFFD}\""
+    );
+}
+
+#[test]
+fn lone_surrogate_keys() {
+    assert_eq!(
+        qj_compact(".", r#"{"\ud83d":1}"#).trim(),
+        "{\"\u{FFFD}\":1}"
+    );
+    assert_eq!(
+        qj_compact("keys", r#"{"\udc00":1}"#).trim(),
+        "[\"\u{FFFD}\"]"
+    );
+}
+
+#[test]
+fn lone_surrogate_ascii_output() {
+    let (code, stdout, _) = qj_exit(&["-ac", "."], r#"{"\ud800":"\udead"}"#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#"{"\ufffd":"\ufffd"}"#);
+    let (code, stdout, _) = qj_exit(&["-ar", ".s"], r#"{"s":"\udead"}"#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#""\ufffd""#);
+}
+
+#[test]
+fn surrogate_pair_keys_and_values() {
+    assert_eq!(
+        qj_compact(".", r#"{"\ud83d\ude00":"\ud83c\udf0d"}"#).trim(),
+        "{\"\u{1F600}\":\"\u{1F30D}\"}"
+    );
+    let (code, stdout, _) = qj_exit(&["-ac", "."], r#"{"\ud83d\ude00":"\ud83c\udf0d"}"#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), r#"{"\ud83d\ude00":"\ud83c\udf0d"}"#);
+}
+
+#[test]
+fn escaped_backslash_before_u_is_not_a_surrogate() {
+    assert_eq!(qj_compact(".", r#""\\udead""#).trim(), r#""\\udead""#);
+}
+
+#[test]
+fn lone_surrogate_passthrough_and_fromjson() {
+    // Passthrough paths fail inside simdjson and must fall back, not error.
+    assert_eq!(qj_compact("length", r#"{"s":"\udead"}"#).trim(), "1");
+    assert_eq!(qj_compact(".s | length", r#"{"s":"\udead"}"#).trim(), "1");
+    assert_eq!(
+        qj_compact("fromjson", r#""\"\\udead\"""#).trim(),
+        "\"\u{FFFD}\""
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Quoted and unicode field access
+// ---------------------------------------------------------------------------
+
+#[test]
+fn quoted_field_access() {
+    let input = r#"{"foo bar":1,"a.b":2,"q\"k":3,"emoji🎉key":4,"é":{"x y":5},"k":"é"}"#;
+    for filter in [
+        r#"."foo bar""#,
+        r#".["foo bar"]"#,
+        r#"."foo bar"?"#,
+        r#".["foo bar"]?"#,
+        r#"."a.b""#,
+        r#".["q\"k"]"#,
+        r#"."q\"k""#,
+        r#".["emoji🎉key"]"#,
+        r#"."emoji🎉key""#,
+        r#"."é"."x y""#,
+        r#"."é"["x y"]?"#,
+        r#"."\(.k)"."x y""#,
+        r#"."missing key"?"#,
+        r#"[paths]"#,
+    ] {
+        assert_jq_compat(filter, input);
+    }
+}
+
+#[test]
+fn quoted_field_paths() {
+    let input = r#"{"foo bar":1,"a.b":2,"q\"k":3,"é":{"x y":5}}"#;
+    for filter in [
+        r#"."foo bar" = 9"#,
+        r#".["a.b"] |= . + 1"#,
+        r#"."q\"k" += 1"#,
+        r#"."é"."x y" = null"#,
+        r#"."new key".inner = 1"#,
+        r#"del(."foo bar")"#,
+        r#"del(.["a.b"], ."é"."x y")"#,
+        r#"del(."q\"k")"#,
+        r#"path(."é"["x y"])"#,
+        r#"has("foo bar"), has("q\"k"), has("é"), has("e")"#,
+        r#"."é" | has("x y")"#,
+    ] {
+        assert_jq_compat(filter, input);
+    }
+}
+
+#[test]
+fn unicode_bare_field_is_an_error() {
+    // Bare field names are ASCII identifiers, in jq as well.
+    let (code, _, stderr) = qj_exit(&[".é"], "{}");
+    assert_ne!(code, 0);
+    assert!(stderr.contains("unexpected character 'é'"), "{stderr}");
+}
+
+// ---------------------------------------------------------------------------
+// Object key order (insertion order survives every transformation, like jq)
+// ---------------------------------------------------------------------------
+
+#[test]
+fn object_key_order_preserved() {
+    let input = r#"{"z":1,"b":{"y":1,"a":2,"q":{"k":1,"c":2}},"m":3,"10":4,"2":5}"#;
+    for filter in [
+        r#"with_entries(select(.key != "m"))"#,
+        "with_entries(.value |= tostring)",
+        "to_entries | from_entries",
+        "map_values(.)",
+        ".[] |= .",
+        "del(.m)",
+        "del(.b.y, .m)",
+        r#"delpaths([["m"], ["b", "y"], ["z"]])"#,
+        r#". + {"a": 9, "m": 0}"#,
+        r#"{"q": 0, "m": 1} + ."#,
+        r#". * {"b": {"q": {"a": 0, "k": 9}, "new": 1}, "2": {"x": 1}}"#,
+        r#"{"b": {"zz": 1}} * ."#,
+        ".b.y = 9 | .new = 1 | .z = 0",
+        r#"walk(if type == "object" then del(.a) else . end)"#,
+        "reduce paths as $p (.; setpath($p; getpath($p)))",
+        "keys_unsorted",
+    ] {
+        assert_jq_compat(filter, input);
+    }
+    // `-S` is the only thing that reorders.
+    assert_jq_compat_with_flags(&["-S", "-c", r#". * {"a": 1}"#], input);
+}
+
+#[test]
+fn object_key_order_ndjson_output_flags() {
+    // Fast paths that build output bytes themselves must yield to the
+    // evaluator when it has to indent, sort or escape.
+    let input =
+        "{\"b\":\"\u{e9}\",\"a\":{\"y\":1,\"x\":2},\"n\":1}\n{\"b\":\"x\",\"a\":[1],\"n\":2}\n";
+    for filter in [
+        "{b, a}",
+        "[.a, .b]",
+        "keys_unsorted",
+        "select(.n == 1)",
+        "select(.n == 1) | {a}",
+    ] {
+        for flags in [&[][..], &["-S"], &["-c", "-S"], &["-c", "-a"], &["--tab"]] {
+            let mut args = flags.to_vec();
+            args.push(filter);
+            assert_jq_compat_with_flags(&args, input);
+        }
+    }
+    assert_jq_compat_with_flags(&["-r", "type"], input);
+}
+
+// ---------------------------------------------------------------------------
+// Duplicate object keys (last one wins, in the first one's position, like jq)
+// ---------------------------------------------------------------------------
+
+#[test]
+fn duplicate_keys_keep_last() {
+    let input = r#"{"a":1,"b":2,"a":3}"#;
+    assert_eq!(qj_compact(".", input).trim(), r#"{"a":3,"b":2}"#);
+    assert_eq!(qj_compact(".a", input).trim(), "3");
+    assert_eq!(qj_compact("length", input).trim(), "2");
+    for filter in [
+        ".",
+        ".a",
+        "length",
+        "keys",
+        "keys_unsorted",
+        "to_entries",
+        "[.[]]",
+        "{a}",
+        "map_values(. + 1)",
+        "tojson",
+    ] {
+        assert_jq_compat(filter, input);
+    }
+    assert_jq_compat_with_flags(&["."], input);
+    // Nested and repeated more than once.
+    assert_jq_compat(".", r#"{"x":{"a":1,"a":2,"a":3},"y":[{"b":1,"b":2}]}"#);
+    assert_jq_compat(".x.a", r#"{"x":{"a":1,"a":2,"a":3}}"#);
+    assert_jq_compat("fromjson", r#""{\"a\":1,\"a\":2}""#);
+}
+
+#[test]
+fn duplicate_keys_in_construction() {
+    assert_jq_compat("{(.k): 1, (.k): 2}", r#"{"k":"x"}"#);
+    assert_jq_compat("{a: 1, b: 2, a: 3}", "null");
+}
+
+#[test]
+fn duplicate_keys_ndjson() {
+    let input = "{\"a\":1,\"b\":2,\"a\":3}\n{\"a\":3,\"a\":1}\n";
+    assert_eq!(qj_compact("select(.a == 1)", input), "{\"a\":1}\n");
+    for filter in [
+        ".",
+        ".a",
+        "length",
+        "keys",
+        "select(.a == 3)",
+        "select(.a == 1 or .b == 2)",
+        "[.a, .b]",
+        "{a}",
+    ] {
+        assert_jq_compat_ndjson(filter, input);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// --unbuffered
+// ---------------------------------------------------------------------------
+
+#[test]
+fn unbuffered_output_correctness() {
+    // --unbuffered should produce the same output as without it
+    let normal = qj_compact(".", r#"{"a":1}"#);
+    let (code, stdout, _) = qj_exit(&["-c", "--unbuffered", "."], r#"{"a":1}"#);
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), normal.trim());
+}
+
+#[test]
+fn unbuffered_with_multiple_values() {
+    let (code, stdout, _) = qj_exit(&["-c", "--unbuffered", ".[]"], "[1,2,3]");
+    assert_eq!(code, 0);
+    assert_eq!(stdout.trim(), "1\n2\n3");
+}
+
+// ---------------------------------------------------------------------------
+// -o / --output
+// ---------------------------------------------------------------------------
+
+/// Names of the entries in `dir`, sorted.
+fn dir_entries(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn output_file_written_on_success() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out.json");
+    let out_arg = out.to_str().unwrap();
+    let (code, stdout, _) = qj_exit(&["-c", ".[]", "-o", out_arg], "[1,{\"a\":2}]");
+    assert_eq!(code, 0);
+    assert!(stdout.is_empty());
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "1\n{\"a\":2}\n");
+
+    // Replaces an existing file; color stays off unless forced, --unbuffered
+    // writes through to the same file
+    let (code, _, _) = qj_exit(&["--unbuffered", ".", "--output", out_arg], "{\"b\":true}");
+    assert_eq!(code, 0);
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        "{\n  \"b\": true\n}\n"
+    );
+    let (code, _, _) = qj_exit(&["-C", "-c", ".", "-o", out_arg], "1");
+    assert_eq!(code, 0);
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        "\u{1b}[0;39m1\u{1b}[0m\n"
+    );
+
+    // -e statuses aren't failures: the output is kept
+    let (code, _, _) = qj_exit(&["-e", ".a", "-o", out_arg], "{\"a\":false}");
+    assert_eq!(code, 1);
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "false\n");
+    assert_eq!(dir_entries(dir.path()), ["out.json"]);
+}
+
+#[test]
+fn output_file_untouched_on_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out.json");
+    std::fs::write(&out, "original\n").unwrap();
+    let out_arg = out.to_str().unwrap();
+
+    // A runtime error after some output leaves no partial file
+    let (code, stdout, stderr) = qj_exit(&["-c", ".[] | error", "-o", out_arg], "[\"boom\"]");
+    assert_eq!(code, 5);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("boom"), "{stderr}");
+    let (code, _, _) = qj_exit(&["-c", "1, error(\"late\")", "-o", out_arg], "null");
+    assert_eq!(code, 5);
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "original\n");
+
+    // So does a missing input file
+    let missing = dir.path().join("missing.json");
+    let (code, _, _) = qj_exit(&[".", missing.to_str().unwrap(), "-o", out_arg], "");
+    assert_eq!(code, 2);
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "original\n");
+    assert_eq!(dir_entries(dir.path()), ["out.json"]);
+
+    let (code, _, stderr) = qj_exit(&[".", "-o", "/nonexistent-dir/out.json"], "1");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("Could not open"), "{stderr}");
+}
+
+#[test]
+fn output_dash_means_stdout() {
+    let (code, stdout, _) = qj_exit(&["-c", ".a", "-o", "-"], "{\"a\":[1]}");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "[1]\n");
+}
+
+// ---------------------------------------------------------------------------
+// -i / --in-place
+// ---------------------------------------------------------------------------
+
+#[test]
+fn in_place_rewrites_single_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data.json");
+    std::fs::write(&file, "{\"version\":1,\"name\":\"x\"}\n").unwrap();
+    let (code, stdout, _) = qj_exit(&["-i", ".version += 1", file.to_str().unwrap()], "");
+    assert_eq!(code, 0);
+    assert!(stdout.is_empty());
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "{\n  \"version\": 2,\n  \"name\": \"x\"\n}\n"
+    );
+    assert_eq!(dir_entries(dir.path()), ["data.json"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn in_place_keeps_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data.json");
+    std::fs::write(&file, "[1,2]").unwrap();
+    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o640)).unwrap();
+    let (code, _, _) = qj_exit(&["-c", "--in-place", "add", file.to_str().unwrap()], "");
+    assert_eq!(code, 0);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "3\n");
+    let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+}
+
+#[test]
+fn in_place_rewrites_each_globbed_file() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.json"), "{\"n\":1}\n{\"n\":2}\n").unwrap();
+    std::fs::write(dir.path().join("b.json"), "[10]").unwrap();
+    let pattern = dir.path().join("*.json");
+    let (code, _, _) = qj_exit(&["-c", "-i", ".", pattern.to_str().unwrap()], "");
+    assert_eq!(code, 0);
+    // Each file gets only its own outputs
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("a.json")).unwrap(),
+        "{\"n\":1}\n{\"n\":2}\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("b.json")).unwrap(),
+        "[10]\n"
+    );
+    assert_eq!(dir_entries(dir.path()), ["a.json", "b.json"]);
+}
+
+#[test]
+fn in_place_error_leaves_that_file_untouched() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.json");
+    let b = dir.path().join("b.json");
+    let c = dir.path().join("c.json");
+    std::fs::write(&a, "{\"n\":1}").unwrap();
+    std::fs::write(&b, "{\"n\":\"two\"}").unwrap();
+    std::fs::write(&c, "{\"n\":3}").unwrap();
+    let (code, _, stderr) = qj_exit(
+        &[
+            "-c",
+            "-i",
+            ".n += 1",
+            a.to_str().unwrap(),
+            b.to_str().unwrap(),
+            c.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(code, 5);
+    assert!(stderr.contains("cannot be added"), "{stderr}");
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "{\"n\":2}\n");
+    assert_eq!(std::fs::read_to_string(&b).unwrap(), "{\"n\":\"two\"}");
+    assert_eq!(std::fs::read_to_string(&c).unwrap(), "{\"n\":4}\n");
+    assert_eq!(dir_entries(dir.path()), ["a.json", "b.json", "c.json"]);
+
+    // A missing file is a file error; the rest are still rewritten
+    let missing = dir.path().join("missing.json");
+    let (code, _, stderr) = qj_exit(
+        &[
+            "-c",
+            "-i",
+            ".n",
+            missing.to_str().unwrap(),
+            a.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(code, 2);
+    assert!(stderr.contains("Could not rewrite"), "{stderr}");
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "2\n");
+    assert_eq!(dir_entries(dir.path()), ["a.json", "b.json", "c.json"]);
+}
+
+#[test]
+fn in_place_rejects_other_outputs_and_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data.json");
+    std::fs::write(&file, "1").unwrap();
+    let path = file.to_str().unwrap();
+
+    let (code, _, stderr) = qj_exit(&["-i", "."], "1");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("--in-place"), "{stderr}");
+    for args in [
+        ["-i", "-s", ".", path],
+        ["-i", "-n", ".", path],
+        ["-i", "-o", "-", path],
+    ] {
+        let (code, _, _) = qj_exit(&args, "");
+        assert_eq!(code, 2, "{args:?}");
+    }
+    let (code, _, stderr) = qj_exit(&["-i", "[., input]", path], "");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("input"), "{stderr}");
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "1");
+}
+
+// ---------------------------------------------------------------------------
+// --watch
+// ---------------------------------------------------------------------------
+
+#[cfg(unix)]
+#[test]
+fn watch_reruns_on_change_and_exits_on_interrupt() {
+    use std::io::{BufRead, BufReader};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("stats.json");
+    // Replace the file in one step so qj never reads it half written.
+    let replace = |text: &str| {
+        let tmp = dir.path().join("stats.tmp");
+        std::fs::write(&tmp, text).unwrap();
+        std::fs::rename(&tmp, &file).unwrap();
+    };
+    replace(r#"{"summary":1}"#);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .args(["--watch", "--watch-interval", "20", ".summary"])
+        .arg(&file)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if tx.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut seen = Vec::new();
+    let mut wait_for = |want: &str| {
+        while seen.last().map(String::as_str) != Some(want) {
+            let line = rx
+                .recv_timeout(Duration::from_secs(10))
+                .unwrap_or_else(|_| panic!("no {want:?} after {seen:?}"));
+            seen.push(line);
+        }
+    };
+
+    wait_for("1");
+    replace(r#"{"summary":22}"#);
+    wait_for("22");
+    // A run that fails is reported, and qj keeps watching.
+    replace("not json");
+    std::thread::sleep(Duration::from_millis(300));
+    replace(r#"{"summary":333}"#);
+    wait_for("333");
+    assert!(seen.contains(&"---".to_string()), "{seen:?}");
+
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("error"), "{stderr}");
 }
 
 #[test]
-fn ascii_output_jq_compat() {
-    if !jq_available() {
-        return;
-    }
-    // Test various non-ASCII strings against jq
-    for input in &[r#""café""#, r#""日本語""#, r#"{"ñ":"ü"}"#] {
-        let qj_out = {
-            let (code, stdout, _) = qj_exit(&["-ac", "."], input);
-            assert_eq!(code, 0);
-            stdout
-        };
-        let jq_out =
-            run_jq(&["-ac", "."], input).unwrap_or_else(|| panic!("jq failed on input={input:?}"));
-        assert_eq!(
-            qj_out.trim(),
-            jq_out.trim(),
-            "qj vs jq -ac mismatch: input={input:?}"
-        );
-    }
+fn watch_needs_input_files() {
+    let (code, _, stderr) = qj_exit(&["--watch", "."], "1");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("--watch needs input files"), "{stderr}");
+    let (code, _, _) = qj_exit(&["--watch", "-o", "out.json", ".", "in.json"], "");
+    assert_eq!(code, 2);
 }
 
 // ---------------------------------------------------------------------------
-// --unbuffered
+// --completions
 // ---------------------------------------------------------------------------
 
 #[test]
-fn unbuffered_output_correctness() {
-    // --unbuffered should produce the same output as without it
-    let normal = qj_compact(".", r#"{"a":1}"#);
-    let (code, stdout, _) = qj_exit(&["-c", "--unbuffered", "."], r#"{"a":1}"#);
-    assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), normal.trim());
+fn completions_script_for_each_shell() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let (code, stdout, stderr) = qj_exit(&["--completions", shell], "");
+        assert_eq!(code, 0, "{shell}: {stderr}");
+        for flag in ["compact-output", "raw-output", "slurp", "in-place"] {
+            assert!(stdout.contains(flag), "{shell} script lacks --{flag}");
+        }
+    }
+    let (code, _, _) = qj_exit(&["--completions", "tcsh"], "");
+    assert_eq!(code, 2);
 }
 
 #[test]
-fn unbuffered_with_multiple_values() {
-    let (code, stdout, _) = qj_exit(&["-c", "--unbuffered", ".[]"], "[1,2,3]");
+fn complete_filter_suggests_top_level_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data.json");
+    std::fs::write(&file, "{\"name\":1,\"nested\":{\"x\":2},\"my key\":3}").unwrap();
+    let path = file.to_str().unwrap();
+    let (code, stdout, _) = qj_exit(&["--complete-filter", ".n", path], "");
     assert_eq!(code, 0);
-    assert_eq!(stdout.trim(), "1\n2\n3");
+    assert_eq!(stdout, ".name\n.nested\n");
+    let (_, stdout, _) = qj_exit(&["--complete-filter", ".", path], "");
+    assert_eq!(stdout, ".name\n.nested\n.\"my key\"\n");
+
+    // Not JSON: no suggestions, no error
+    std::fs::write(&file, "not json").unwrap();
+    let (code, stdout, stderr) = qj_exit(&["--complete-filter", ".", path], "");
+    assert_eq!(code, 0);
+    assert!(stdout.is_empty() && stderr.is_empty());
 }
 
 // ---------------------------------------------------------------------------
@@ -5439,13 +10136,134 @@ fn parse_nan_in_string_preserved() {
     assert_eq!(out.trim(), "{\"key\":\"NaN\"}");
 }
 
+#[test]
+fn parse_out_of_range_exponents() {
+    // Literals past f64's range are read as ±infinity rather than rejected.
+    // Like other input numbers they keep their text; arithmetic clamps.
+    let input = "[1e1000,-1E+999]";
+    assert_eq!(qj_compact(".", input).trim(), input);
+    assert_eq!(
+        qj_compact("map(. + 0)", input).trim(),
+        "[1.7976931348623157e+308,-1.7976931348623157e+308]"
+    );
+    assert_eq!(
+        qj_compact("map(isinfinite), (.[0] > 1e300)", input).trim(),
+        "[true,true]\ntrue"
+    );
+    assert_eq!(
+        qj_compact(".a, .a + 0", "{\"a\":1}\n{\"a\":1e1000}\n").trim(),
+        "1\n1\n1e1000\n1.7976931348623157e+308"
+    );
+    assert_eq!(
+        qj_compact(r#"fromjson | . * 1"#, r#""-2e400""#).trim(),
+        "-1.7976931348623157e+308"
+    );
+    let (code, out, _) = qj_exit(&["-nc", "--argjson", "x", "1e1000", "$x + 0"], "");
+    assert_eq!((code, out.as_str()), (0, "1.7976931348623157e+308\n"));
+    // Malformed numbers are still errors
+    for bad in ["[1e]", "[01]", "[-]", "[1e1000x]"] {
+        let (code, _, _) = qj_exit(&["-c", "."], bad);
+        assert_ne!(code, 0, "{bad}");
+    }
+}
+
 // ---------------------------------------------------------------------------
-// try input catch . (break signal)
+// input / inputs at end of input
 // ---------------------------------------------------------------------------
 
+/// Run qj with `-c` and `args`, returning (exit code, stdout, stderr).
+fn qj_inputs(args: &[&str], input: &str) -> (i32, String, String) {
+    let mut all = vec!["-c"];
+    all.extend_from_slice(args);
+    qj_exit(&all, input)
+}
+
+#[test]
+fn try_input_catch_no_more_inputs() {
+    // jq 1.7 reports "No more inputs" (1.6 said "break").
+    assert_eq!(
+        qj_compact("try input catch .", "null").trim(),
+        r#""No more inputs""#
+    );
+    for (args, input, expected) in [
+        (&["-n", "try input catch ."][..], "", r#""No more inputs""#),
+        (
+            &["-n", "[inputs], (try input catch .)"],
+            "1 2",
+            "[1,2]\n\"No more inputs\"",
+        ),
+        (
+            &["[., (try input catch .)]"],
+            "1 2 3",
+            "[1,2]\n[3,\"No more inputs\"]",
+        ),
+        (
+            &["-n", "try (reduce range(5) as $i (0; . + input)) catch ."],
+            "1 2 3",
+            r#""No more inputs""#,
+        ),
+        (
+            &[
+                "-n",
+                "[foreach range(4) as $i (0; . + (try input catch 100))]",
+            ],
+            "1 2",
+            "[1,3,103,203]",
+        ),
+    ] {
+        let (code, stdout, stderr) = qj_inputs(args, input);
+        assert_eq!((code, stdout.trim()), (0, expected), "{args:?}: {stderr}");
+    }
+}
+
+#[test]
+fn input_past_end_is_an_uncaught_error() {
+    for (args, input, expected) in [
+        (&["-n", "input"][..], "", ""),
+        (&["-n", "[inputs], input"], "1 2", "[1,2]"),
+        (&["[., input]"], "1 2 3", "[1,2]"),
+        (&["-n", "def f: input; [f, f]"], "1", ""),
+    ] {
+        let (code, stdout, stderr) = qj_inputs(args, input);
+        assert_eq!((code, stdout.trim()), (5, expected), "{args:?}");
+        assert!(stderr.contains("No more inputs"), "{args:?}: {stderr}");
+    }
+}
+
 #[test]
-fn try_input_catch_break() {
-    assert_jq_compat("try input catch .", "null");
+fn inputs_ends_quietly_at_eof() {
+    for (args, input) in [
+        (&["-n", "[inputs]"][..], "1 2 3"),
+        (&["-n", "[inputs]"], ""),
+        (&["-n", "[inputs] | length"], "   \n"),
+        (&["-n", "reduce inputs as $x (0; . + $x)"], "1 2 3"),
+        (&["-n", "reduce inputs as $x (0; . + $x)"], ""),
+        (&["-n", "[foreach inputs as $x (0; . + $x)]"], "1 2 3"),
+        (&["-n", "first(inputs), [inputs]"], "1 2 3"),
+        (&["-n", "[limit(1; inputs)], input"], "1 2 3"),
+        (
+            &[
+                "-n",
+                "[label $out | inputs | if . == 2 then break $out else . end]",
+            ],
+            "1 2 3",
+        ),
+        (
+            &[
+                "-n",
+                "[label $out | inputs | if . == 2 then break $out else . end], [inputs]",
+            ],
+            "1 2 3",
+        ),
+        (&["-n", "label $f | input | ., break $f"], "1 2"),
+        (&["[., input]"], "1 2 3 4"),
+        (&["-n", "."], ""),
+    ] {
+        let args = [&["-c"][..], args].concat();
+        let (code, _, stderr) = qj_exit(&args, input);
+        assert_eq!((code, stderr.as_str()), (0, ""), "{args:?}");
+        assert_jq_compat_with_flags(&args, input);
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -5457,6 +10275,33 @@ fn tostring_large_number_preserves_raw() {
     assert_jq_compat("tostring", "100000000000000000000");
 }
 
+#[test]
+fn string_interp_preserves_raw_numbers() {
+    // Interpolation, @text and tostring all give the text direct output has
+    let input = r#"{"id":9999999999999999999,"u":18446744073709551615,"big":123456789012345678901234567890,"price":75.80}"#;
+    for field in ["id", "u", "big", "price"] {
+        let direct = qj_compact(&format!(".{field} | tostring"), input);
+        let raw = qj_compact(&format!(".{field}"), input);
+        assert_eq!(direct.trim(), format!("\"{}\"", raw.trim()));
+        assert_eq!(qj_compact(&format!("\"\\(.{field})\""), input), direct);
+        assert_eq!(qj_compact(&format!(".{field} | @text"), input), direct);
+    }
+    assert_eq!(
+        qj_compact(r#""id: \(.id), price: \(.price)""#, input).trim(),
+        r#""id: 9999999999999999999, price: 75.80""#
+    );
+    assert_eq!(
+        qj_compact(r#"@sh "echo \(.big)""#, input).trim(),
+        r#""echo 123456789012345678901234567890""#
+    );
+    // Computed numbers format as on output too
+    assert_eq!(
+        qj_compact(r#""\(nan) \(1/3) \(infinite)""#, "null").trim(),
+        r#""null 0.3333333333333333 1.7976931348623157e+308""#
+    );
+    assert_jq_compat(r#""\(nan) \(1/3) \(infinite) \(.a)""#, r#"{"a":1.5}"#);
+}
+
 // ---------------------------------------------------------------------------
 // Try/? operator on array construction (flat_eval error propagation)
 // ---------------------------------------------------------------------------
@@ -5612,6 +10457,108 @@ fn getpath_update_error_propagation() {
     );
 }
 
+// ---------------------------------------------------------------------------
+// Composite filters in path position: //, ?, if, select, first/last/limit
+// ---------------------------------------------------------------------------
+
+const PATH_DOC: &str =
+    r#"{"a":null,"b":2,"f":false,"t":true,"x":1,"items":[{"id":1},{"id":3},{"id":3}]}"#;
+
+#[test]
+fn path_of_alternative_try_and_if() {
+    for filter in [
+        "path(.a // .b)",
+        "[path(.f // .a // .x)]",
+        "[path(.items[].id // .b)]",
+        "[path(.a // .missing)]",
+        "path(.x.y? // .b)",
+        "[path(.a?, .x[0]?, .b)]",
+        "path(if .t then .x else .y end)",
+        "[path(if .f then .x else . end)]",
+        "[path(.items[] | if .id > 1 then .id else empty end)]",
+        "[path(.items[] | select(.id == 3))]",
+        "path(getpath([\"items\", 0]) | .id)",
+    ] {
+        assert_jq_compat(filter, PATH_DOC);
+    }
+    assert_eq!(qj_compact("path(.a // .b)", PATH_DOC).trim(), r#"["b"]"#);
+    assert_eq!(
+        qj_compact("[path(.f // .a // .x)]", PATH_DOC).trim(),
+        r#"[["x"]]"#
+    );
+    // Without `else` (jq 1.7) the input's own path is kept
+    assert_eq!(
+        qj_compact("[path(if .f then .x end)]", PATH_DOC).trim(),
+        "[[]]"
+    );
+}
+
+#[test]
+fn path_of_first_last_limit() {
+    for filter in [
+        "path(first(.items[] | select(.id == 3)))",
+        "[path(limit(2; .items[]))]",
+        "[path(first(.items[]) | .id)]",
+        "[path(first(empty))]",
+        "path(first(.b, error(\"late\")))",
+    ] {
+        assert_jq_compat(filter, PATH_DOC);
+    }
+    assert_eq!(
+        qj_compact("path(first(.items[] | select(.id == 3)))", PATH_DOC).trim(),
+        r#"["items",1]"#
+    );
+    assert_eq!(
+        qj_compact("[path(limit(2; .items[]))]", PATH_DOC).trim(),
+        r#"[["items",0],["items",1]]"#
+    );
+    // jq 1.6 ignores a zero limit; 1.7 gives nothing
+    assert_eq!(
+        qj_compact("[path(limit(0; .items[]))]", PATH_DOC).trim(),
+        "[]"
+    );
+    assert_eq!(
+        qj_compact("path(last(.items[] | select(.id == 3)))", PATH_DOC).trim(),
+        r#"["items",2]"#
+    );
+    let (code, _, stderr) = qj_exit(&["-c", "[path(limit(-1; .items[]))]"], PATH_DOC);
+    assert_eq!(code, 5);
+    assert!(stderr.contains("negative count"), "{stderr}");
+}
+
+#[test]
+fn assign_through_composite_paths() {
+    for filter in [
+        "(.a // .b) = 10",
+        "(.f // .a) = 10",
+        "(.x.y? // .b) |= . + 1",
+        "(if .t then .x else .y end) = 9",
+        "(.items[] | select(.id == 3) | .seen) = true",
+        "(first(.items[] | select(.id == 3)) | .v) = 1",
+        "(limit(2; .items[]) | .id) |= . * 10",
+        "del(first(.items[] | select(.id == 3)))",
+        "(getpath([\"items\", 0]) | .id) += 5",
+    ] {
+        assert_jq_compat(filter, PATH_DOC);
+    }
+    assert_eq!(
+        qj_compact("(.a // .b) = 10 | [.a, .b]", PATH_DOC).trim(),
+        "[null,10]"
+    );
+    assert_eq!(
+        qj_compact(
+            "(first(.items[] | select(.id == 3)) | .v) = 1 | .items",
+            PATH_DOC
+        )
+        .trim(),
+        r#"[{"id":1},{"id":3,"v":1},{"id":3}]"#
+    );
+    assert_eq!(
+        qj_compact("last(.items[]) |= {last: true} | .items[2]", PATH_DOC).trim(),
+        r#"{"last":true}"#
+    );
+}
+
 // ---------------------------------------------------------------------------
 // def in expression positions (after comma, after pipe, inside arrays)
 // ---------------------------------------------------------------------------
@@ -5652,7 +10599,7 @@ fn run_tool_full(cmd: &str, args: &[&str], input: &str) -> (String, String, bool
             let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
             child.wait_with_output()
         })
-        .expect(&format!("failed to run {cmd}"));
+        .unwrap_or_else(|_| panic!("failed to run {cmd}"));
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -5906,6 +10853,73 @@ fn ndjson_jq_compat_whitespace_only_lines() {
     );
 }
 
+// --- Field rows piped to @tsv / @csv (raw output) ---
+
+fn assert_jq_compat_ndjson_raw(filter: &str, ndjson_input: &str) {
+    if !jq_available() {
+        return;
+    }
+    let (qj_stdout, qj_stderr, _) =
+        run_tool_full(env!("CARGO_BIN_EXE_qj"), &["-r", filter], ndjson_input);
+    let (jq_stdout, _, _) = run_tool_full("jq", &["-r", filter], ndjson_input);
+    assert_eq!(
+        qj_stdout, jq_stdout,
+        "qj vs jq NDJSON mismatch: filter={filter:?}\nqj stderr: {qj_stderr}"
+    );
+}
+
+#[test]
+fn ndjson_jq_compat_tsv_csv_rows() {
+    let input = concat!(
+        r#"{"name":"tab\there","n":1,"ok":true,"user":{"id":7}}"#,
+        "\n",
+        r#"{"name":"q\"uote, \\ back\nline\r","n":-20,"ok":false,"user":{"id":null}}"#,
+        "\n",
+        r#"{"name":"caf\u00e9 \u2603","ok":null,"user":{}}"#,
+        "\n",
+        r#"{"name":"","n":0,"user":{"id":"x"}}"#,
+        "\n",
+    );
+    for filter in [
+        "[.name, .n, .ok, .user.id] | @tsv",
+        "[.name, .n, .ok, .user.id] | @csv",
+        "[.missing] | @tsv",
+        "[.user.id] | @csv",
+    ] {
+        assert_jq_compat_ndjson_raw(filter, input);
+    }
+}
+
+#[test]
+fn ndjson_tsv_csv_rows_with_container_field() {
+    // A row holding an array or object is an error for that line only
+    let input = "{\"a\":\"x\",\"b\":1}\n{\"a\":[1],\"b\":2}\n{\"a\":\"z\",\"b\":{}}\n{\"a\":\"w\",\"b\":3}\n";
+    for (filter, rows) in [
+        ("[.a, .b] | @tsv", "x\t1\nw\t3\n"),
+        ("[.a, .b] | @csv", "\"x\",1\n\"w\",3\n"),
+    ] {
+        let (_, stdout, stderr) = qj_exit(&["-r", filter], input);
+        assert_eq!(stdout, rows, "{filter}");
+        assert!(
+            stderr.contains("array ([1]) is not valid in a csv row"),
+            "{stderr}"
+        );
+        assert!(
+            stderr.contains("object ({}) is not valid in a csv row"),
+            "{stderr}"
+        );
+        assert_jq_compat_ndjson_raw(filter, input);
+    }
+    // Without -r the row is printed as a JSON string
+    assert_eq!(
+        qj_compact(
+            "[.a, .b] | @csv",
+            "{\"a\":\"x\",\"b\":1}\n{\"a\":\"y\",\"b\":2}\n"
+        ),
+        "\"\\\"x\\\",1\"\n\"\\\"y\\\",2\"\n"
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Negative zero (-0) preservation from JSON input
 // ---------------------------------------------------------------------------
@@ -6045,9 +11059,8 @@ fn passthrough_diff_identity_whitespace_heavy() {
 
 #[test]
 fn passthrough_diff_identity_escaped_strings() {
-    // Note: jq unescapes \/ to / while simdjson minify preserves it.
-    // Test only escapes where both agree.
     assert_jq_compat(".", r#"{"tab":"\t","newline":"\n","backslash":"\\"}"#);
+    assert_jq_compat(".", r#"{"slash":"\/","del":"\u007F"}"#);
 }
 
 #[test]
@@ -7091,7 +12104,7 @@ fn output_mode_raw_strings() {
     let raw = qj_with_args(&["-r", ".name"], r#"{"name":"alice"}"#);
     assert_eq!(raw.trim(), "alice");
 
-    // Non-string values should be identical between -r and -c
+    // Non-string scalars should be identical between -r and -c
     let raw_num = qj_with_args(&["-r", ".age"], r#"{"age":30}"#);
     let compact_num = qj_with_args(&["-c", ".age"], r#"{"age":30}"#);
     assert_eq!(raw_num.trim(), compact_num.trim());
@@ -7100,7 +12113,11 @@ fn output_mode_raw_strings() {
     let compact_null = qj_with_args(&["-c", ".x"], r#"{"x":null}"#);
     assert_eq!(raw_null.trim(), compact_null.trim());
 
+    // Containers keep the pretty/compact setting, like jq
     let raw_arr = qj_with_args(&["-r", ".x"], r#"{"x":[1,2]}"#);
+    let pretty_arr = qj_with_args(&[".x"], r#"{"x":[1,2]}"#);
+    assert_eq!(raw_arr.trim(), pretty_arr.trim());
+    let raw_arr = qj_with_args(&["-rc", ".x"], r#"{"x":[1,2]}"#);
     let compact_arr = qj_with_args(&["-c", ".x"], r#"{"x":[1,2]}"#);
     assert_eq!(raw_arr.trim(), compact_arr.trim());
 }
@@ -7225,7 +12242,7 @@ fn jq_compat_math_range() {
     assert_jq_compat("[range(5;0;-1)]", "null");
     // Wrong direction → empty
     assert_jq_compat("[range(0;5;-1)]", "null");
-    assert_jq_compat("[range(0;1;0)]", "null");
+    // A zero step is an error in qj (see generator_limit_and_range_step_edge_cases).
 }
 
 #[test]
@@ -7374,6 +12391,55 @@ fn jq_compat_date_gmtime_mktime() {
     assert_jq_compat("gmtime | mktime", "1705321800");
 }
 
+#[test]
+fn jq_compat_date_broken_down_time_roundtrip() {
+    for ts in [
+        "0",
+        "951782400",
+        "1425599621.25",
+        "1705321800",
+        "-86401.5",
+        "-2208988800",
+    ] {
+        assert_jq_compat("gmtime", ts);
+        assert_jq_compat("gmtime | mktime", ts);
+        assert_jq_compat("gmtime | todate", ts);
+        assert_jq_compat(r#"gmtime | strftime("%A %a %B %j %c %x %U")"#, ts);
+        assert_jq_compat(r#"strftime("%A %a %B %j %c %x %U")"#, ts);
+    }
+}
+
+#[test]
+fn jq_compat_date_mktime_rejects_malformed_arrays() {
+    assert_jq_compat("mktime", "[2024,0,15]");
+    assert_jq_compat("mktime", r#"[2024,0,15,0,0,"0",1,14]"#);
+    assert_jq_compat("mktime", "1705321800");
+    assert_jq_compat(r#"strftime("%Y")"#, "[2024,0,15]");
+    // Extra trailing elements are ignored.
+    assert_jq_compat("mktime", "[2024,0,15,12,30,0,1,14,99]");
+
+    let (_, _, stderr) = qj_exit(&["mktime"], "[2024,0,15]");
+    assert!(
+        stderr.contains("mktime requires parsed datetime inputs"),
+        "{stderr}"
+    );
+    let (_, _, stderr) = qj_exit(&["mktime"], "5");
+    assert!(stderr.contains("mktime requires array inputs"), "{stderr}");
+}
+
+#[test]
+fn date_mktime_normalizes_out_of_range_fields() {
+    // Day 0 is the last day of the previous month, like timegm.
+    assert_eq!(
+        qj_compact("mktime | todate", "[2024,2,0,0,0,0,0,0]").trim(),
+        r#""2024-02-29T00:00:00Z""#
+    );
+    assert_eq!(
+        qj_compact("mktime | todate", "[2023,12,1,24,0,0,0,0]").trim(),
+        r#""2024-01-02T00:00:00Z""#
+    );
+}
+
 #[test]
 fn jq_compat_date_strftime() {
     assert_jq_compat(r#"strftime("%Y-%m-%d")"#, "0");
@@ -7381,6 +12447,54 @@ fn jq_compat_date_strftime() {
     assert_jq_compat(r#"strftime("%Y")"#, "1705321800");
 }
 
+#[test]
+fn jq_compat_date_iso8601_aliases() {
+    assert_jq_compat("todateiso8601", "1705321800");
+    assert_eq!(
+        qj_compact("date", "1705321800").trim(),
+        qj_compact("todate", "1705321800").trim()
+    );
+    assert_jq_compat("fromdateiso8601", r#""2024-01-15T12:30:00Z""#);
+    assert_jq_compat("todateiso8601 | fromdateiso8601", "86400");
+    assert_jq_compat("fromdate", r#""not a date""#);
+    assert_jq_compat("fromdate", "5");
+    // Seconds are the one unit where jq (which ignores the unit) agrees.
+    if jq_has_builtin("dateadd/2") {
+        assert_jq_compat(r#"dateadd("seconds"; 30)"#, "1705321800");
+        assert_jq_compat(r#"datesub("seconds"; 30)"#, "1705321800");
+    }
+}
+
+#[test]
+fn date_dateadd_datesub_units() {
+    assert_eq!(qj_compact(r#"dateadd("minutes"; 2)"#, "0").trim(), "120");
+    assert_eq!(qj_compact(r#"dateadd("hours"; 1)"#, "0").trim(), "3600");
+    assert_eq!(
+        qj_compact(r#"dateadd("days"; 1) | todate"#, "1705321800").trim(),
+        r#""2024-01-16T12:30:00Z""#
+    );
+    assert_eq!(qj_compact(r#"datesub("days"; 1)"#, "86400").trim(), "0");
+    let (code, _, stderr) = qj_exit(&[r#"dateadd("weeks"; 1)"#], "0");
+    assert_eq!(code, 5);
+    assert!(stderr.contains(r#"unknown unit "weeks""#), "{stderr}");
+}
+
+#[test]
+fn date_fromdate_fraction_and_offset() {
+    assert_eq!(
+        qj_compact("fromdate", r#""2024-01-15T12:30:00.750Z""#).trim(),
+        "1705321800"
+    );
+    assert_eq!(
+        qj_compact("fromdate", r#""2024-01-15T12:30:00+00:00""#).trim(),
+        "1705321800"
+    );
+    assert_eq!(
+        qj_compact("fromdate", r#""1969-12-31T23:59:59.5Z""#).trim(),
+        "-1"
+    );
+}
+
 #[test]
 fn jq_compat_date_now() {
     // now returns a number > 0
@@ -7447,6 +12561,77 @@ fn jq_compat_pick() {
     assert_jq_compat("pick(.missing)", r#"{"a":1}"#);
 }
 
+#[test]
+fn pick_path_expressions() {
+    // The manual's examples, then deep paths, array gaps, iteration,
+    // slices and optional steps.
+    let manual = r#"{"a": 1, "b": {"c": 2, "d": 3}, "e": 4}"#;
+    let nested = r#"{"a":{"b":{"c":{"d":1,"e":2}},"f":3},"x":[1,2,3],"y":4}"#;
+    for (filter, input, expected) in [
+        ("pick(.a)", manual, r#"{"a":1}"#),
+        (
+            "pick(.a, .b.c, .x)",
+            manual,
+            r#"{"a":1,"b":{"c":2},"x":null}"#,
+        ),
+        ("pick(.[2], .[0], .[0])", "[1,2,3,4]", "[1,null,3]"),
+        ("pick(.a.b.c.d)", nested, r#"{"a":{"b":{"c":{"d":1}}}}"#),
+        ("pick(.x[2], .y)", nested, r#"{"x":[null,null,3],"y":4}"#),
+        ("pick(.q[1].z)", nested, r#"{"q":[null,{"z":null}]}"#),
+        ("pick(.x[])", nested, r#"{"x":[1,2,3]}"#),
+        ("pick(.x[1:])", nested, r#"{"x":[2,3]}"#),
+        ("pick(.x[.x[0]])", nested, r#"{"x":[null,2]}"#),
+        ("pick(.a.f.g?)", nested, "null"),
+        (
+            "pick(first(.x[]), (.a | .f))",
+            nested,
+            r#"{"x":[1],"a":{"f":3}}"#,
+        ),
+        ("pick(empty)", nested, "null"),
+    ] {
+        assert_eq!(qj_compact(filter, input).trim(), expected, "{filter}");
+    }
+    for (filter, msg) in [
+        (
+            "pick(keys)",
+            r#"Invalid path expression with result ["a","x","y"]"#,
+        ),
+        ("pick(.y, .a.f.g)", r#"Cannot index number with string "g""#),
+        ("pick(.x[-1])", "Out of bounds negative array index"),
+    ] {
+        let (code, stdout, stderr) = qj_exit(&["-c", filter], nested);
+        assert_eq!(code, 5, "{filter}");
+        assert!(stdout.is_empty(), "{filter}: {stdout}");
+        assert!(stderr.contains(msg), "{filter}: {stderr}");
+    }
+}
+
+#[test]
+fn paths_of_slices_and_computed_indices() {
+    let input = r#"{"x":[1,2,3,4],"i":1}"#;
+    for filter in [
+        "[path(.x[1:3], .x[.i:], .x[:-1])]",
+        "[path(.x[.i], .x[.i, 0])]",
+        "path(.x[1.5:])",
+        "del(.x[1:3][0])",
+        "del(.x[.i])",
+        ".x[.i] |= 9",
+        ".x[.i:] |= [0]",
+        r#"try path(.x["a":]) catch ."#,
+        "try path(.[1:2]) catch .",
+    ] {
+        assert_jq_compat(filter, input);
+    }
+    assert_eq!(
+        qj_compact("[path(.x[1:3], .x[.i])]", input).trim(),
+        r#"[["x",{"start":1,"end":3}],["x",1]]"#
+    );
+    assert_eq!(
+        qj_compact("del(.x[1:3][0])", input).trim(),
+        r#"{"x":[1,3,4],"i":1}"#
+    );
+}
+
 #[test]
 fn jq_compat_nth_extended() {
     assert_jq_compat("nth(0; .[])", "[10,20,30]");
@@ -7525,6 +12710,36 @@ fn jq_compat_destructure_nested() {
     assert_jq_compat(". as {a: {b: $x}} | $x", r#"{"a":{"b":99}}"#);
 }
 
+#[test]
+fn jq_compat_destructure_alternatives() {
+    // Examples from the jq manual's "Destructuring Alternative Operator" section.
+    let input = r#"[{"a":1,"b":2,"c":{"d":3,"e":4}},{"a":1,"b":2,"c":[{"d":3,"e":4}]}]"#;
+    assert_jq_compat(
+        ".[] as {$a, $b, c: {$d, $e}} ?// {$a, $b, c: [{$d, $e}]} | {$a, $b, $d, $e}",
+        input,
+    );
+    assert_jq_compat(
+        ".[] as {$a, $b, c: {$d}} ?// {$a, $b, c: [{$e}]} | {$a, $b, $d, $e}",
+        input,
+    );
+    assert_jq_compat(
+        r#".[] as [$a] ?// [$b] | if $a != null then error("err: \($a)") else {$a,$b} end"#,
+        "[[3]]",
+    );
+    // Variables only named in another alternative are bound to null.
+    assert_jq_compat(". as [$a] ?// {$a, $b} | [$a, $b]", "[1]");
+    assert_jq_compat(". as [$a] ?// {$a, $b} | [$a, $b]", r#"{"a":1,"b":2}"#);
+    // An error in the body retries the next alternative; the last one's error escapes.
+    assert_jq_compat(
+        r#"[.[] as [$a] ?// $a | if ($a|type) == "number" then error("x") else $a end]"#,
+        r#"[[1],{"a":2},[4,5]]"#,
+    );
+    assert_jq_compat(
+        r#"try (.[] as [$a] ?// $a | if ($a|type) == "number" then error("x") else $a end) catch ."#,
+        r#"[[1],3]"#,
+    );
+}
+
 #[test]
 fn jq_compat_destructure_in_reduce() {
     assert_jq_compat(
@@ -7544,6 +12759,41 @@ fn jq_compat_limit_repeat_until() {
     assert_jq_compat("[limit(10; 0 | recurse(. + 1))]", "null");
 }
 
+#[test]
+fn jq_compat_generator_edge_cases() {
+    for filter in [
+        "try [nth(-1; 1, 2)] catch .",
+        "try [first(empty)] catch .",
+        "try [first(1, error(\"x\"))] catch .",
+        "try [limit(1.5; 1, 2, 3)] catch .",
+        "try [range(0; 1; 0.1)] catch .",
+        "try [range(0; 1; 0.3)] catch .",
+        "try [range(5; 0; -2)] catch .",
+        "try [range(1.5)] catch .",
+        "try [range(\"a\")] catch .",
+        "try [range(0; \"a\")] catch .",
+        "try [nth(-1)] catch .",
+        "try [nth(5)] catch .",
+        "try [.[0] | nth(0)] catch .",
+    ] {
+        assert_jq_compat(filter, "[[1,2]]");
+    }
+}
+
+#[test]
+fn generator_limit_and_range_step_edge_cases() {
+    // jq 1.7+: a zero count yields nothing, a negative count is an error.
+    assert_eq!(qj_compact("[limit(0; 1, 2)]", "null").trim(), "[]");
+    assert_eq!(
+        qj_compact("try [limit(-1; 1, 2)] catch .", "null").trim(),
+        r#""limit doesn't support negative count""#
+    );
+    assert_eq!(
+        qj_compact("try [range(0; 10; 0)] catch .", "null").trim(),
+        r#""range cannot have a zero step""#
+    );
+}
+
 // =========================================================================
 // TEST_TODOS_3 #9: @base64 edge cases
 // =========================================================================
@@ -8147,6 +13397,125 @@ fn seq_flag_no_rs_input_silent() {
     assert!(stdout.is_empty(), "non-RS input should produce no output");
 }
 
+// ---------------------------------------------------------------------------
+// Output format parity across input paths
+// ---------------------------------------------------------------------------
+
+/// The same filter and output flags must give the same bytes whether a
+/// document comes from stdin (evaluator), a file (passthrough where eligible)
+/// or NDJSON lines (sequential and parallel chunks).
+#[test]
+fn output_flags_byte_identical_across_paths() {
+    let doc = r#"{"é":"x","b":[1,{"z":2,"a":"ü"}],"c":{"k":null,"j":false}}"#;
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("doc.json");
+    std::fs::write(&file, doc).unwrap();
+    let file = file.to_str().unwrap();
+    let lines = 3000;
+    let ndjson = format!("{doc}\n").repeat(lines);
+
+    let flag_sets: &[&[&str]] = &[
+        &[],
+        &["-c"],
+        &["-j"],
+        &["-r"],
+        &["-rj"],
+        &["--raw-output0"],
+        &["--seq"],
+        &["--seq", "-c"],
+        &["--tab"],
+        &["--indent", "0"],
+        &["--indent", "7"],
+        &["-S"],
+        &["-a", "-c"],
+        &["--unbuffered"],
+    ];
+    let filters = [
+        ".",
+        ".b",
+        ".c | keys",
+        "keys_unsorted",
+        "length",
+        ".b | length",
+        "type",
+        r#"has("c")"#,
+        ".b | map(type)",
+        ".[]",
+        ".c | .k, .j",
+    ];
+    for flags in flag_sets {
+        for filter in filters {
+            let mut args = flags.to_vec();
+            args.push(filter);
+            let single = qj_raw_bytes(&args, doc.as_bytes());
+            args.push(file);
+            assert_eq!(
+                qj_raw_bytes(&args, b""),
+                single,
+                "file vs stdin: {flags:?} {filter}"
+            );
+            args.pop();
+            args.splice(0..0, ["--threads", "4"]);
+            let expected = single.repeat(lines);
+            assert!(
+                qj_raw_bytes(&args, ndjson.as_bytes()) == expected,
+                "ndjson vs stdin: {flags:?} {filter}"
+            );
+        }
+    }
+}
+
+#[test]
+fn indent_zero_is_compact() {
+    assert_jq_compat_with_flags(&["--indent", "0", "."], r#"{"a":[1,{"b":2}]}"#);
+    assert_jq_compat_with_flags(&["--indent", "0", "keys"], r#"{"b":1,"a":2}"#);
+    let out = qj_raw_bytes(&["--indent", "0", ".a"], br#"{"a":[1,{"b":2}]}"#);
+    assert_eq!(out, b"[1,{\"b\":2}]\n");
+}
+
+#[test]
+fn keys_passthrough_pretty_prints() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("doc.json");
+    std::fs::write(&file, r#"{"b":{"y":1,"x":2},"a":0}"#).unwrap();
+    let file = file.to_str().unwrap();
+    let out = qj_raw_bytes(&["keys", file], b"");
+    assert_eq!(out, b"[\n  \"a\",\n  \"b\"\n]\n");
+    let out = qj_raw_bytes(&["--tab", ".b | keys_unsorted", file], b"");
+    assert_eq!(out, b"[\n\t\"y\",\n\t\"x\"\n]\n");
+    let out = qj_raw_bytes(&["-c", "keys", file], b"");
+    assert_eq!(out, b"[\"a\",\"b\"]\n");
+}
+
+#[test]
+fn raw_output_keeps_scalar_passthroughs() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("doc.json");
+    std::fs::write(&file, r#"{"a":{"b":"x"},"n":[1,2],"s":"hi"}"#).unwrap();
+    let file = file.to_str().unwrap();
+    let cases: [(&[&str], &[u8]); 6] = [
+        (&["-r", "type"], b"object\n"),
+        (&["-r", ".a.b | type"], b"string\n"),
+        (&["-j", ".n | type"], b"array"),
+        (&["-r", ".s | length"], b"2\n"),
+        (&["-j", "has(\"n\")"], b"true"),
+        (&["-r", ".a | has(\"c\")"], b"false\n"),
+    ];
+    for (args, expected) in cases {
+        let mut with_file = args.to_vec();
+        with_file.push(file);
+        assert_eq!(qj_raw_bytes(&with_file, b""), expected, "{args:?}");
+        with_file.insert(0, "--debug-timing");
+        let (_, _, stderr) = qj_exit(&with_file, "");
+        assert!(stderr.contains("passthrough)"), "{args:?}: {stderr}");
+    }
+    // Paths whose results can be strings or containers still take the
+    // evaluator under -r.
+    let (_, stdout, stderr) = qj_exit(&["--debug-timing", "-r", "keys", file], "");
+    assert_eq!(stdout, "[\n  \"a\",\n  \"n\",\n  \"s\"\n]\n");
+    assert!(!stderr.contains("passthrough)"), "{stderr}");
+}
+
 // ---------------------------------------------------------------------------
 // --stream-errors CLI flag
 // ---------------------------------------------------------------------------
@@ -15,7 +15,6 @@
 mod common;
 
 use serde::Deserialize;
-use std::process::Command;
 
 extern crate serde_json;
 
@@ -84,7 +83,7 @@ fn run_tool_with_post_args(
     if let Some(flags_str) = flags {
         cmd.args(flags_str.split_whitespace());
     } else {
-        cmd.args(&["-c", "--"]);
+        cmd.args(["-c", "--"]);
     }
     cmd.arg(filter);
     if let Some(pa) = post_args {
@@ -139,11 +138,14 @@ fn test_passes(output: Option<&str>, expected: &str) -> bool {
 fn hardcoded_tests(tools: &[common::Tool], verbose: bool) -> (Vec<Feature>, Vec<Vec<Vec<bool>>>) {
     use std::io::Write;
 
+    /// Runner that returns pass/fail for one tool.
+    type CaseRunner = Box<dyn Fn(&common::Tool) -> bool>;
+
     struct HardcodedTest {
         category: String,
         name: String,
-        /// Each sub-test: (description, runner that returns pass/fail per tool)
-        cases: Vec<(String, Box<dyn Fn(&common::Tool) -> bool>)>,
+        /// Each sub-test: (description, runner)
+        cases: Vec<(String, CaseRunner)>,
     }
 
     let tests: Vec<HardcodedTest> = vec![
@@ -401,7 +403,7 @@ fn run_all(verbose: bool) {
                     &mut cache,
                 );
                 let passed = if test.raw_compare.unwrap_or(false) {
-                    output.as_deref().map_or(false, |o| o == test.expected)
+                    output.as_deref().is_some_and(|o| o == test.expected)
                 } else {
                     test_passes(output.as_deref(), &test.expected)
                 };
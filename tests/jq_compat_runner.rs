@@ -83,10 +83,10 @@ fn parse_jq_test_file(content: &str) -> Vec<TestCase> {
 
         // Comments: check for section header
         if line.starts_with('#') {
-            if let Some(rest) = line.strip_prefix("# ") {
-                if let Some(display) = match_section_header(rest) {
-                    current_category = display.to_string();
-                }
+            if let Some(rest) = line.strip_prefix("# ")
+                && let Some(display) = match_section_header(rest)
+            {
+                current_category = display.to_string();
             }
             i += 1;
             continue;
@@ -200,9 +200,7 @@ fn run_all(verbose: bool) {
         let mut total_pass = 0usize;
         let mut total_tests = 0usize;
 
-        let tool_cats = cat_stats
-            .entry(tool.name.clone())
-            .or_insert_with(BTreeMap::new);
+        let tool_cats = cat_stats.entry(tool.name.clone()).or_default();
 
         for case in &cases {
             total_tests += 1;
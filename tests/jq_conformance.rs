@@ -130,7 +130,7 @@ fn run_all_cases() -> (Vec<TestCase>, Vec<TestResult>) {
         std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/jq_compat/jq.test");
     let content = std::fs::read_to_string(&test_file).expect("failed to read jq.test");
     let cases = parse_jq_test_file(&content);
-    let results: Vec<TestResult> = cases.iter().map(|c| run_test_case(c)).collect();
+    let results: Vec<TestResult> = cases.iter().map(run_test_case).collect();
     (cases, results)
 }
 
@@ -358,3 +358,52 @@ fn jq_conformance_verbose() {
     println!("  failed:  {failed}");
     println!("  errored: {errored}");
 }
+
+/// Outputs and error of `filter` on `input`, as compact JSON text.
+fn eval_outputs(filter: &qj::filter::Filter, input: &qj::value::Value) -> (Vec<String>, String) {
+    let env = qj::filter::Env::empty();
+    let mut outputs = Vec::new();
+    qj::filter::eval::eval(filter, input, &env, &mut |v| {
+        outputs.push(qj::output::format_compact(&v))
+    });
+    let error = env
+        .take_error()
+        .map(|e| qj::output::format_compact(&e))
+        .unwrap_or_default();
+    (outputs, error)
+}
+
+/// Every jq.test filter gives the same outputs and error with and without
+/// `optimize`. Runs in-process, so it's fast enough to run by default.
+#[test]
+fn jq_conformance_optimizer_equivalence() {
+    let test_file =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/jq_compat/jq.test");
+    let content = std::fs::read_to_string(&test_file).expect("failed to read jq.test");
+    let mut compared = 0;
+    for case in parse_jq_test_file(&content) {
+        if needs_module_flag(&case.filter) {
+            continue;
+        }
+        let Ok(filter) = qj::filter::parse(&case.filter) else {
+            continue;
+        };
+        if filter.uses_input_builtins() {
+            continue;
+        }
+        let padded = qj::simdjson::pad_buffer(case.input.as_bytes());
+        let Ok(input) = qj::simdjson::dom_parse_to_value(&padded, case.input.len()) else {
+            continue;
+        };
+        let optimized = qj::filter::optimize::optimize(&filter);
+        assert_eq!(
+            eval_outputs(&optimized, &input),
+            eval_outputs(&filter, &input),
+            "line {}: {}",
+            case.line_no,
+            case.filter
+        );
+        compared += 1;
+    }
+    assert!(compared > 400, "only {compared} cases compared");
+}
@@ -201,6 +201,17 @@ fn fast_vs_normal_keys() {
     assert_fast_path_matches_normal("keys", input);
 }
 
+#[test]
+fn fast_vs_normal_keys_non_ascii() {
+    let input =
+        "{\"zebra\":1,\"é\":2,\"Z\":3,\"日本\":4,\"😀\":5,\"\\u00e9a\":6}\n{\"ｚ\":1,\"a\":2}\n";
+    assert_fast_path_matches_normal("keys", input);
+    assert_eq!(
+        qj_stdin(&["-c", "keys"], input),
+        "[\"Z\",\"zebra\",\"é\",\"éa\",\"日本\",\"😀\"]\n[\"a\",\"ｚ\"]\n"
+    );
+}
+
 #[test]
 fn fast_vs_normal_select_test() {
     let input = "{\"msg\":\"error: disk full\"}\n{\"msg\":\"ok\"}\n{\"msg\":\"error: timeout\"}\n";
@@ -238,6 +249,14 @@ fn fast_vs_normal_select_float_vs_int() {
     assert_fast_path_matches_normal("select(.n == 1)", input);
 }
 
+#[test]
+fn fast_vs_normal_select_big_integer() {
+    let input = "{\"x\":100000000000000000000000000001}\n{\"x\":100000000000000000000000000000}\n{\"x\":1e29}\n";
+    assert_fast_path_matches_normal("select(.x == 100000000000000000000000000000)", input);
+    assert_fast_path_matches_normal("select(.x == 1e29)", input);
+    assert_fast_path_matches_normal("select(.x != 100000000000000000000000000001)", input);
+}
+
 #[test]
 fn fast_vs_normal_select_escaped_string() {
     // Escaped strings (\n) are handled correctly by both paths
@@ -245,6 +264,108 @@ fn fast_vs_normal_select_escaped_string() {
     assert_fast_path_matches_normal("select(.s == \"line1\\nline2\")", input);
 }
 
+#[test]
+fn fast_vs_normal_lone_surrogate() {
+    // Lone surrogates decode to U+FFFD on every path, including raw-line output.
+    let input = "{\"s\":\"\\udead\",\"id\":1}\n{\"s\":\"ok\",\"id\":2}\n";
+    for filter in [
+        ".s",
+        "select(.id == 1)",
+        "select(.id == 1 and .s != null)",
+        ".",
+    ] {
+        assert_fast_path_matches_normal(filter, input);
+    }
+    assert_eq!(qj_stdin(&["-c", ".s"], input), "\"\u{FFFD}\"\n\"ok\"\n");
+    assert_eq!(
+        qj_stdin(&["-c", "select(.id == 1)"], input),
+        "{\"s\":\"\u{FFFD}\",\"id\":1}\n"
+    );
+}
+
+#[test]
+fn fast_vs_normal_duplicate_keys() {
+    // jq keeps the last of repeated keys; raw-line paths must not copy or
+    // match on the first.
+    let input = "{\"a\":1,\"id\":1,\"a\":2}\n{\"a\":2,\"id\":2,\"a\":1}\n{\"x\":{\"b\":1,\"b\":2},\"id\":3}\n";
+    for filter in [
+        ".",
+        ".a",
+        ".x",
+        "select(.a == 1)",
+        "select(.a == 1 and .id > 0)",
+        "select(.id == 3)",
+        "select(.x.b == 2)",
+        "[.a, .id]",
+        "{a, x}",
+        "length",
+        "keys",
+    ] {
+        assert_fast_path_matches_normal(filter, input);
+    }
+    assert_eq!(
+        qj_stdin(&["-c", "select(.a == 1)"], input),
+        "{\"a\":1,\"id\":2}\n"
+    );
+}
+
+#[test]
+fn fast_vs_normal_rewritten_escapes() {
+    // Escapes jq prints differently (`\u` sequences, `\/`) and raw DEL bytes
+    // are re-serialized on paths that otherwise copy input bytes.
+    let input = "{\"s\":\"\\u00e9\\/\\u007F\x7f\",\"x\":{\"k\\u001F\":\"\\/\"},\"id\":1}\n{\"s\":\"ok\",\"x\":{},\"id\":2}\n";
+    for filter in [
+        ".",
+        ".s",
+        ".x",
+        "select(.id == 1)",
+        "select(.id == 1 and .id > 0)",
+        "select(.s | startswith(\"\u{e9}\"))",
+        "[.s, .x]",
+        "{s, x}",
+        "keys",
+    ] {
+        assert_fast_path_matches_normal(filter, input);
+    }
+    assert_eq!(
+        qj_stdin(&["-c", "select(.id == 1)"], input),
+        "{\"s\":\"\u{e9}/\\u007f\\u007f\",\"x\":{\"k\\u001f\":\"/\"},\"id\":1}\n"
+    );
+    assert_eq!(
+        qj_file(&["-c", "{x}"], input),
+        "{\"x\":{\"k\\u001f\":\"/\"}}\n{\"x\":{}}\n"
+    );
+}
+
+#[test]
+fn fast_paths_honor_output_flags() {
+    let input =
+        "{\"b\":\"\u{e9}\",\"a\":{\"y\":1,\"x\":2},\"n\":1}\n{\"b\":\"x\",\"a\":[1],\"n\":2}\n";
+    for run in [qj_stdin, qj_file] {
+        assert_eq!(
+            run(&["{b}"], input),
+            "{\n  \"b\": \"\u{e9}\"\n}\n{\n  \"b\": \"x\"\n}\n"
+        );
+        assert_eq!(
+            run(&["-c", "-S", "{b, a}"], input),
+            "{\"a\":{\"x\":2,\"y\":1},\"b\":\"\u{e9}\"}\n{\"a\":[1],\"b\":\"x\"}\n"
+        );
+        assert_eq!(
+            run(&["-c", "-S", "select(.n == 1)"], input),
+            "{\"a\":{\"x\":2,\"y\":1},\"b\":\"\u{e9}\",\"n\":1}\n"
+        );
+        assert_eq!(
+            run(&["-c", "-a", "[.b]"], input),
+            "[\"\\u00e9\"]\n[\"x\"]\n"
+        );
+        assert_eq!(run(&["-r", "type"], input), "object\nobject\n");
+        assert_eq!(
+            run(&["--tab", ".a | keys_unsorted"], input),
+            "[\n\t\"y\",\n\t\"x\"\n]\n[\n\t0\n]\n"
+        );
+    }
+}
+
 // Note: \u0041 vs "A" intentionally differs between fast/normal paths.
 // Fast path outputs the raw line (preserving \u0041), normal path re-serializes
 // (normalizing to "A"). Both are semantically correct. The fast path falls back
@@ -427,6 +548,82 @@ fn ndjson_malformed_line_mixed() {
     assert!(!stderr.is_empty(), "should report parse error on stderr");
 }
 
+/// Run qj on `content` as an NDJSON file (or on stdin), returning
+/// `(stdout, stderr, exit code)`.
+fn qj_ndjson_exit(args: &[&str], content: &str, from_stdin: bool) -> (String, String, i32) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.jsonl");
+    std::fs::write(&path, content).unwrap();
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_qj"));
+    cmd.args(args);
+    if from_stdin {
+        cmd.stdin(std::fs::File::open(&path).unwrap());
+    } else {
+        cmd.arg(&path);
+    }
+    let output = cmd.output().expect("failed to run qj");
+    (
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+        output.status.code().unwrap_or(-1),
+    )
+}
+
+#[test]
+fn ndjson_malformed_line_reported_and_skipped() {
+    let input = "{\"a\":1}\n{\"a\":2}\n{\"a\":\n{\"a\":4}\n{\"a\":5}\n";
+    // The identity, a lazy field fast path, a fused select scanner and the
+    // evaluator all skip line 3 and carry on.
+    for (filter, expected) in [
+        (".", "{\"a\":1}\n{\"a\":2}\n{\"a\":4}\n{\"a\":5}\n"),
+        (".a", "1\n2\n4\n5\n"),
+        ("select(.a == 4)", "{\"a\":4}\n"),
+        (".a + 1", "2\n3\n5\n6\n"),
+    ] {
+        for from_stdin in [false, true] {
+            let (stdout, stderr, code) = qj_ndjson_exit(&["-c", filter], input, from_stdin);
+            assert_eq!(stdout, expected, "filter {filter}, stdin {from_stdin}");
+            assert_eq!(
+                stderr,
+                "qj: error (at line 3): invalid JSON: EOF while parsing a value at column 5\n",
+                "filter {filter}, stdin {from_stdin}"
+            );
+            assert_eq!(code, 2, "filter {filter}, stdin {from_stdin}");
+        }
+    }
+
+    // Every bad line is reported, not just the first
+    let input = "{\"a\":1}\n{\"a\":2}\n{\"a\":1,]}\nxx\n{\"a\":3}\n";
+    let (stdout, stderr, code) = qj_ndjson_exit(&["-c", "."], input, false);
+    assert_eq!(stdout, "{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n");
+    assert_eq!(
+        stderr,
+        "qj: error (at line 3): invalid JSON: key must be a string at column 8\n\
+         qj: error (at line 4): invalid JSON: expected value at column 1\n"
+    );
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn ndjson_malformed_line_numbers_span_chunks() {
+    // Over a megabyte, so the lines are split across parallel chunks
+    let mut input = String::new();
+    for i in 1..=100_000 {
+        if i == 80_000 {
+            input.push_str("{\"id\":\n");
+        } else {
+            input.push_str(&format!("{{\"id\":{i},\"pad\":\"0123456789\"}}\n"));
+        }
+    }
+    let (stdout, stderr, code) = qj_ndjson_exit(&["-c", ".id"], &input, false);
+    assert_eq!(stdout.lines().count(), 99_999);
+    assert_eq!(
+        stderr,
+        "qj: error (at line 80000): invalid JSON: EOF while parsing a value at column 6\n"
+    );
+    assert_eq!(code, 2);
+}
+
 #[test]
 fn ndjson_whitespace_only_lines() {
     // Lines with only whitespace between valid JSON
@@ -447,6 +644,261 @@ fn ndjson_large_line_count_ordering() {
     assert_eq!(out, expected);
 }
 
+#[test]
+fn ndjson_tsv_rows_match_normal_path() {
+    let mut input = String::new();
+    for i in 0..5_000 {
+        input.push_str(&format!(
+            "{{\"id\":{i},\"name\":\"n\\t{i}\\\"\",\"on\":{},\"tag\":null}}\n",
+            i % 2 == 0
+        ));
+    }
+    for filter in ["[.id, .name, .on, .tag] | @tsv", "[.name, .id] | @csv"] {
+        let fast = qj_file(&["-r", filter], &input);
+        let normal = {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("input.jsonl");
+            std::fs::write(&path, &input).unwrap();
+            let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+                .args(["-r", filter])
+                .arg(&path)
+                .env("QJ_NO_FAST_PATH", "1")
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+        assert_eq!(fast.lines().count(), 5_000);
+        assert_eq!(fast, normal, "{filter}");
+    }
+    assert!(qj_file(&["-r", "[.id, .name] | @tsv"], &input).starts_with("0\tn\\t0\"\n"));
+}
+
+#[test]
+fn ndjson_debug_timing_names_fast_path() {
+    let input = "{\"a\":1,\"b\":\"x\"}\n{\"a\":2,\"b\":\"y\"}\n";
+    for (filter, label) in [
+        ("[.a, .b] | @tsv", "ndjson tsv fast path"),
+        ("[.a, .b] | @csv", "ndjson csv fast path"),
+        (".a", "ndjson field fast path"),
+        ("[.a, .b] | join(\",\")", "ndjson"),
+    ] {
+        let (stdout, stderr, code) =
+            qj_ndjson_exit(&["-r", "--debug-timing", filter], input, false);
+        assert_eq!(code, 0, "{stderr}");
+        assert_eq!(stdout.lines().count(), 2, "{filter}");
+        assert!(
+            stderr.contains(&format!("--- debug-timing ({label}): ")),
+            "{filter}: {stderr}"
+        );
+    }
+}
+
+#[test]
+fn ndjson_first_select_stops_at_first_match() {
+    // Elements after the match would fail `.ok`; jq never gets to them.
+    let input = "{\"items\":[{\"ok\":false},{\"ok\":true,\"n\":1},5]}\n\
+                 {\"items\":[{\"ok\":true,\"n\":2},\"x\",{\"ok\":true}]}\n\
+                 {\"items\":[{\"ok\":false}]}\n\
+                 {\"items\":{\"a\":{\"ok\":false},\"b\":{\"ok\":true,\"n\":3},\"c\":null}}\n";
+    for filter in [
+        "first(.items[] | select(.ok))",
+        "limit(1; .items[] | select(.ok))",
+    ] {
+        let (stdout, stderr, code) =
+            qj_ndjson_exit(&["-c", "--debug-timing", filter], input, false);
+        assert_eq!(code, 0, "{filter}: {stderr}");
+        assert_eq!(
+            stdout, "{\"ok\":true,\"n\":1}\n{\"ok\":true,\"n\":2}\n{\"ok\":true,\"n\":3}\n",
+            "{filter}"
+        );
+        assert!(
+            stderr.contains("--- debug-timing (ndjson first_select fast path): "),
+            "{filter}: {stderr}"
+        );
+        assert!(!stderr.contains("error"), "{filter}: {stderr}");
+    }
+
+    // An error before the first match is still reported, and a missing or
+    // scalar target errors like the evaluator.
+    let (stdout, stderr, code) = qj_ndjson_exit(
+        &["-c", "first(.items[] | select(.n == 2))"],
+        "{\"items\":[5,{\"n\":2}]}\n{\"items\":[{\"n\":2},5]}\n{\"items\":3}\n",
+        false,
+    );
+    // The last line fails, so jq exits 5.
+    assert_eq!(code, 5, "{stderr}");
+    assert_eq!(stdout, "{\"n\":2}\n");
+    assert_eq!(
+        stderr
+            .matches("Cannot index number with string \"n\"")
+            .count(),
+        1,
+        "{stderr}"
+    );
+    assert!(stderr.contains("Cannot iterate over number"), "{stderr}");
+}
+
+#[test]
+fn ndjson_first_select_matches_normal_path() {
+    let input = r#"{"items":[1,7,9],"meta":{"x":1,"y":2},"tags":["a",null,"b"]}
+{"items":[],"meta":{},"tags":[false,null]}
+{"items":null,"meta":{"z":3},"tags":"s"}
+[1,2,3]
+{"items":[6],"meta":{"w":2},"tags":[]}
+"#;
+    for filter in [
+        "first(.items[] | select(. > 5))",
+        "first(.[] | select(type == \"object\"))",
+        "limit(1; .meta[] | select(. >= 2))",
+        "first(.tags[] | select(. == null))",
+        "first(.tags[] | select(true, error(\"late\")))",
+    ] {
+        assert_fast_path_matches_normal(filter, input);
+    }
+}
+
+#[test]
+fn ndjson_object_literal_filter_runs_in_parallel() {
+    let mut input = String::new();
+    let mut expected = String::new();
+    for i in 0..100_000 {
+        input.push_str(&format!("{{\"i\":{i}}}\n"));
+        expected.push_str(&format!("{{\"i\":{i},\"tag\":1}}\n"));
+    }
+    let (stdout, stderr, success) =
+        qj_stdin_lossy(&["-c", "--debug-parallel", r#". + {"tag":1}"#], &input);
+    assert!(success, "stderr: {stderr}");
+    assert!(
+        stderr.contains("qj: debug: ndjson parallel ("),
+        "expected parallel scheduling, got: {stderr}"
+    );
+    assert_eq!(stdout, expected);
+
+    // A filter using a bound variable still has to run sequentially.
+    let (stdout, stderr, success) = qj_stdin_lossy(
+        &[
+            "-c",
+            "--debug-parallel",
+            "--argjson",
+            "t",
+            "1",
+            ". + {tag: $t}",
+        ],
+        &input,
+    );
+    assert!(success, "stderr: {stderr}");
+    assert!(stderr.contains("qj: debug: ndjson sequential (filter uses bound variables)"));
+    assert_eq!(stdout, expected);
+}
+
+#[test]
+fn ndjson_profile_aggregates_across_parallel_chunks() {
+    let mut input = String::new();
+    let mut expected = String::new();
+    for i in 0..100_000 {
+        input.push_str(&format!("{{\"i\":{i}}}\n"));
+        if i % 10 == 0 {
+            expected.push_str(&format!("{i}\n"));
+        }
+    }
+    let (stdout, stderr, success) = qj_stdin_lossy(
+        &[
+            "-c",
+            "--profile",
+            "--debug-parallel",
+            "select(.i % 10 == 0) | .i",
+        ],
+        &input,
+    );
+    assert!(success, "stderr: {stderr}");
+    assert!(stderr.contains("qj: debug: ndjson parallel ("), "{stderr}");
+    assert_eq!(stdout, expected);
+    // One row per node, summed over every record and worker thread
+    let select_row = stderr
+        .lines()
+        .find(|l| l.ends_with("select(.i % 10 == 0)"))
+        .unwrap_or_else(|| panic!("no select row in: {stderr}"));
+    let cols: Vec<&str> = select_row.split_whitespace().collect();
+    assert_eq!(&cols[1..4], ["100000", "10000", "Select"], "{select_row}");
+}
+
+fn qj_slurp_groups(filter: &str, path: &std::path::Path, fast: bool) -> (String, String) {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_qj"));
+    cmd.args(["-c", "-s", "--debug-parallel", filter]).arg(path);
+    if !fast {
+        cmd.env("QJ_NO_FAST_PATH", "1");
+    }
+    let output = cmd.output().expect("failed to run qj");
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn ndjson_slurp_group_by_aggregates_chunks_in_parallel() {
+    // Several ~1MB chunks; every group has members in each of them.
+    let users = [r#""bob""#, "null", r#""ann""#, "3", r#"{"a":1}"#, "3.0"];
+    let input: String = (0..60_000)
+        .map(|i| {
+            let user = users[i % users.len()];
+            format!(
+                "{{\"user\":{user},\"i\":{i},\"pad\":\"{}\"}}\n",
+                "x".repeat(20)
+            )
+        })
+        .collect();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.jsonl");
+    std::fs::write(&path, &input).unwrap();
+
+    // Sorted like jq sorts the keys; 3 and 3.0 are one group.
+    let cases = [
+        (
+            "group_by(.user) | map(length)",
+            "[10000,20000,10000,10000,10000]\n",
+        ),
+        (
+            "group_by(.user) | map({u: .[0].user, n: length, i: first.i})",
+            r#"[{"u":null,"n":10000,"i":1},{"u":3,"n":20000,"i":3},{"u":"ann","n":10000,"i":2},{"u":"bob","n":10000,"i":0},{"u":{"a":1},"n":10000,"i":4}]"#,
+        ),
+        ("unique_by(.user) | map(.i)", "[1,3,2,0,4]\n"),
+        ("group_by(.user | type) | map(length) | add", "60000\n"),
+    ];
+    for (filter, expected) in cases {
+        let (stdout, stderr) = qj_slurp_groups(filter, &path, true);
+        assert!(
+            stderr.contains("qj: debug: ndjson group aggregate ("),
+            "{filter}: {stderr}"
+        );
+        assert_eq!(stdout.trim_end(), expected.trim_end(), "{filter}");
+        let (normal, _) = qj_slurp_groups(filter, &path, false);
+        assert_eq!(stdout, normal, "{filter}");
+    }
+}
+
+#[test]
+fn ndjson_slurp_group_by_falls_back_on_errors() {
+    let input: String = (0..60_000)
+        .map(|i| match i {
+            45_000 => "{\"user\":\"x\"}\n".to_string(),
+            _ => format!(
+                "{{\"user\":{{\"id\":{}}},\"pad\":\"{}\"}}\n",
+                i % 3,
+                "x".repeat(20)
+            ),
+        })
+        .collect();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.jsonl");
+    std::fs::write(&path, &input).unwrap();
+    let filter = "group_by(.user.id) | map(length)";
+    let (stdout, stderr) = qj_slurp_groups(filter, &path, true);
+    assert!(!stderr.contains("group aggregate"), "{stderr}");
+    assert!(stderr.contains("Cannot index string with"), "{stderr}");
+    assert_eq!(stdout, "");
+}
+
 // --- Array NDJSON ---
 
 #[test]
@@ -467,6 +919,107 @@ fn ndjson_raw_output() {
     assert_eq!(out, "alice\nbob\n");
 }
 
+#[test]
+fn ndjson_env_var_in_parallel_workers() {
+    let input: String = (0..60_000)
+        .map(|i| format!("{{\"id\":{i},\"pad\":\"{}\"}}\n", "x".repeat(40)))
+        .collect();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.jsonl");
+    std::fs::write(&path, &input).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .args(["--threads", "4", "--debug-parallel", "-c"])
+        .arg("select(.id % 20000 == 1) | {id, v: $ENV.QJ_TEST_ENV}")
+        .arg(&path)
+        .env("QJ_TEST_ENV", "v")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("ndjson parallel"), "{stderr}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"id\":1,\"v\":\"v\"}\n{\"id\":20001,\"v\":\"v\"}\n{\"id\":40001,\"v\":\"v\"}\n"
+    );
+}
+
+#[test]
+fn ndjson_parallel_honors_ascii_and_sort_keys() {
+    let input: String = (0..40_000)
+        .map(|i| {
+            format!(
+                "{{\"name\":\"h\u{e9}llo \u{2713} \u{1f600} {i}\",\"z\":{i},\"a\":{{\"y\":1,\"b\":\"\u{e9}\\n\"}}}}\n"
+            )
+        })
+        .collect();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("input.jsonl");
+    std::fs::write(&path, &input).unwrap();
+    let run = |threads: &str, args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+            .args(["--threads", threads, "--debug-parallel"])
+            .args(args)
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        (output.stdout, stderr)
+    };
+    for args in [
+        &["-a", "-c", ".name"][..],
+        &["-a", "-r", ".name"],
+        &["-a", "-c", "."],
+        &["-S", "-c", "."],
+        &["-a", "-S", "."],
+        &["-a", "-S", "--indent", "1", ".a"],
+        &["-a", "-c", "{name, z}"],
+        &["-a", "-S", "-c", "[.a, .name]"],
+    ] {
+        let (parallel, stderr) = run("4", args);
+        assert!(stderr.contains("ndjson parallel"), "{args:?}: {stderr}");
+        let (sequential, _) = run("1", args);
+        assert!(
+            parallel == sequential,
+            "{args:?} differs between 4 threads and 1"
+        );
+    }
+    let (out, _) = run("4", &["-a", "-c", "select(.z == 1) | .name"]);
+    assert_eq!(out, b"\"h\\u00e9llo \\u2713 \\ud83d\\ude00 1\"\n");
+}
+
+#[test]
+fn ndjson_raw_output_mixed_fields() {
+    // Strings print unquoted; numbers, nulls and containers as JSON, pretty
+    // since -c is not given, as jq prints them.
+    let input = concat!(
+        "{\"message\":\"plain\",\"n\":1}\n",
+        "{\"message\":\"tab\\there \\\"q\\\" \u{e9}\",\"n\":2}\n",
+        "{\"n\":3}\n",
+        "{\"message\":42}\n",
+        "{\"message\":null}\n",
+        "{\"message\":{\"k\":\"v\"}}\n",
+        "{\"message\":[\"a\"]}\n",
+    );
+    for run in [qj_stdin, qj_file] {
+        assert_eq!(
+            run(&["-r", ".message"], input),
+            "plain\ntab\there \"q\" \u{e9}\nnull\n42\nnull\n{\n  \"k\": \"v\"\n}\n[\n  \"a\"\n]\n"
+        );
+        assert_eq!(
+            run(&["-j", ".message"], input),
+            "plaintab\there \"q\" \u{e9}null42null{\n  \"k\": \"v\"\n}[\n  \"a\"\n]"
+        );
+        assert_eq!(
+            run(&["-r", ".message | type"], input),
+            "string\nstring\nnull\nnumber\nnull\nobject\narray\n"
+        );
+        assert_eq!(
+            run(&["-j", ".message | type"], input),
+            "stringstringnullnumbernullobjectarray"
+        );
+    }
+}
+
 // --- Pretty output ---
 
 #[test]
@@ -554,7 +1107,7 @@ fn ndjson_field_chain_mixed_types() {
 fn ndjson_field_chain_special_chars_in_value() {
     // Values with quotes, backslashes, unicode
     let input = "{\"msg\":\"hello \\\"world\\\"\"}\n{\"msg\":\"line1\\nline2\"}\n";
-    let out = qj_stdin(&["-c", ".msg"], &input);
+    let out = qj_stdin(&["-c", ".msg"], input);
     assert_eq!(out, "\"hello \\\"world\\\"\"\n\"line1\\nline2\"\n");
 }
 
@@ -583,7 +1136,7 @@ fn ndjson_field_chain_large_values() {
 fn ndjson_field_chain_whitespace_in_json() {
     // Lines with extra whitespace (tabs, spaces) that get trimmed
     let input = "  {\"a\":1}  \n\t{\"a\":2}\t\n";
-    let out = qj_stdin(&["-c", ".a"], &input);
+    let out = qj_stdin(&["-c", ".a"], input);
     assert_eq!(out, "1\n2\n");
 }
 
@@ -591,7 +1144,7 @@ fn ndjson_field_chain_whitespace_in_json() {
 fn ndjson_field_chain_raw_output_escape() {
     // Raw output with escape sequences in the string
     let input = "{\"msg\":\"hello\\tworld\"}\n{\"msg\":\"foo\\nbar\"}\n";
-    let out = qj_stdin(&["-r", ".msg"], &input);
+    let out = qj_stdin(&["-r", ".msg"], input);
     assert_eq!(out, "hello\tworld\nfoo\nbar\n");
 }
 
@@ -779,7 +1332,7 @@ fn ndjson_select_unicode_escape() {
     // which don't byte-match "A", so falls back to normal eval which
     // normalizes the unicode escape. Output matches QJ_NO_FAST_PATH behavior.
     let input = "{\"s\":\"\\u0041\",\"id\":1}\n{\"s\":\"B\",\"id\":2}\n";
-    let out = qj_stdin(&["-c", "select(.s == \"A\")"], &input);
+    let out = qj_stdin(&["-c", "select(.s == \"A\")"], input);
     assert_eq!(out, "{\"s\":\"A\",\"id\":1}\n");
 }
 
@@ -787,7 +1340,7 @@ fn ndjson_select_unicode_escape() {
 fn ndjson_select_trailing_zero_float() {
     // 42.00 == 42 should match
     let input = "{\"n\":42.00}\n{\"n\":43}\n";
-    let out = qj_stdin(&["-c", "select(.n == 42)"], &input);
+    let out = qj_stdin(&["-c", "select(.n == 42)"], input);
     assert_eq!(out, "{\"n\":42.00}\n");
 }
 
@@ -805,7 +1358,7 @@ fn ndjson_select_type_mismatch_string_vs_int() {
 fn ndjson_select_float_ne() {
     // 1.0 != 1 should NOT output (they're equal), 2 != 1 should output
     let input = "{\"n\":1.0}\n{\"n\":2}\n";
-    let out = qj_stdin(&["-c", "select(.n != 1)"], &input);
+    let out = qj_stdin(&["-c", "select(.n != 1)"], input);
     assert_eq!(out, "{\"n\":2}\n");
 }
 
@@ -1393,7 +1946,11 @@ fn generate_diverse_ndjson(rng: &mut Rng, count: usize) -> String {
                 let ty = rng.pick(types);
                 let name = rng.pick(names);
                 let n = rng.range(-100, 200);
-                let active = if rng.next() % 2 == 0 { "true" } else { "false" };
+                let active = if rng.next().is_multiple_of(2) {
+                    "true"
+                } else {
+                    "false"
+                };
                 buf.push_str(&format!(
                     "{{\"type\":\"{ty}\",\"name\":\"{name}\",\"count\":{n},\"active\":{active},\"actor\":{{\"login\":\"{name}\"}},\"meta\":{{\"x\":1,\"y\":2}},\"items\":[1,2,3]}}\n"
                 ));
@@ -1404,9 +1961,7 @@ fn generate_diverse_ndjson(rng: &mut Rng, count: usize) -> String {
             }
             // Null fields
             5 => {
-                buf.push_str(&format!(
-                    "{{\"type\":null,\"name\":null,\"count\":null,\"active\":null}}\n"
-                ));
+                buf.push_str("{\"type\":null,\"name\":null,\"count\":null,\"active\":null}\n");
             }
             // Numeric edge cases: floats, scientific notation, negative, trailing zeros.
             // The On-Demand raw_json() path preserves these exactly as written.
@@ -1429,9 +1984,9 @@ fn generate_diverse_ndjson(rng: &mut Rng, count: usize) -> String {
             }
             // Type mismatches: count as string, name as number
             8 => {
-                buf.push_str(&format!(
-                    "{{\"type\":42,\"name\":999,\"count\":\"not_a_number\",\"active\":\"yes\"}}\n"
-                ));
+                buf.push_str(
+                    "{\"type\":42,\"name\":999,\"count\":\"not_a_number\",\"active\":\"yes\"}\n",
+                );
             }
             // Empty/minimal objects
             _ => {
@@ -1743,3 +2298,92 @@ fn ndjson_key_order_preserved() {
         "{\"z\":1,\"a\":2}\n{\"b\":3,\"a\":4}\n{\"m\":5,\"c\":6,\"a\":7}\n"
     );
 }
+
+// --- --slurp streams NDJSON window by window ---
+
+/// About 3 MB of NDJSON, so a 1 MB window (`QJ_WINDOW_SIZE=1`) sees several
+/// windows with records split across their boundaries.
+fn multi_window_ndjson() -> (String, usize) {
+    let n = 30_000;
+    let pad = "x".repeat(80);
+    let text = (0..n)
+        .map(|i| format!("{{\"i\":{i},\"pad\":\"{pad}\"}}\n"))
+        .collect();
+    (text, n)
+}
+
+fn qj_windowed(args: &[&str], stdin: &[u8]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .args(args)
+        .env("QJ_WINDOW_SIZE", "1")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            let mut pipe = child.stdin.take().unwrap();
+            let input = stdin.to_vec();
+            let writer = std::thread::spawn(move || pipe.write_all(&input));
+            let output = child.wait_with_output();
+            writer.join().unwrap().unwrap();
+            output
+        })
+        .expect("failed to run qj");
+    assert!(
+        output.status.success(),
+        "qj {:?} exited with {}: stderr={}",
+        args,
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+const SLURP_SUMMARY: &str = "[length, .[0].i, .[-1].i, (map(.i) | add)]";
+
+fn slurp_summary(n: usize) -> String {
+    format!("[{},0,{},{}]\n", n, n - 1, n * (n - 1) / 2)
+}
+
+#[test]
+fn slurp_ndjson_file_spanning_windows() {
+    let (text, n) = multi_window_ndjson();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("big.jsonl");
+    std::fs::write(&path, &text).unwrap();
+    let path = path.to_str().unwrap();
+
+    let out = qj_windowed(&["-c", "-s", SLURP_SUMMARY, path], b"");
+    assert_eq!(out, slurp_summary(n));
+    let out = qj_windowed(&["-c", "--jsonl", "-s", SLURP_SUMMARY, path], b"");
+    assert_eq!(out, slurp_summary(n));
+}
+
+#[test]
+fn slurp_ndjson_stdin_spanning_windows() {
+    let (text, n) = multi_window_ndjson();
+    let out = qj_windowed(&["-c", "-s", SLURP_SUMMARY], text.as_bytes());
+    assert_eq!(out, slurp_summary(n));
+
+    // With a BOM and --jsonl, which skips detection
+    let mut bom = b"\xEF\xBB\xBF".to_vec();
+    bom.extend_from_slice(text.as_bytes());
+    let out = qj_windowed(&["-c", "--jsonl", "-s", SLURP_SUMMARY], &bom);
+    assert_eq!(out, slurp_summary(n));
+}
+
+#[test]
+fn slurp_ndjson_matches_whole_document_slurp() {
+    // Small inputs: NDJSON slurp agrees with slurping a whitespace-separated stream
+    let ndjson = "{\"a\":1}\n[2,3]\n\n{\"a\":4}\n";
+    assert_eq!(
+        qj_stdin(&["-c", "-s", "."], ndjson),
+        "[{\"a\":1},[2,3],{\"a\":4}]\n"
+    );
+    assert_eq!(
+        qj_file(&["-c", "-s", "."], ndjson),
+        "[{\"a\":1},[2,3],{\"a\":4}]\n"
+    );
+    assert_eq!(qj_stdin(&["-c", "-s", "."], "{\"a\":1}"), "[{\"a\":1}]\n");
+    assert_eq!(qj_stdin(&["-c", "-s", "."], ""), "[]\n");
+}
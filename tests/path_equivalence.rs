@@ -0,0 +1,207 @@
+//! Runs each filter under every `--eval-mode` and asserts that stdout and the
+//! exit status don't change. A passthrough, NDJSON fast path or flat-eval
+//! shortcut that drifts from the tree evaluator fails here, and the first
+//! mode that disagrees with `tree` names the layer to look at.
+
+use std::path::Path;
+use std::process::Command;
+
+/// From most shortcuts to none; each mode turns one more layer off.
+const MODES: [&str; 4] = ["auto", "passthrough-off", "flat", "tree"];
+
+/// Every filter runs with each of these.
+const ARG_SETS: [&[&str]; 4] = [&["-c"], &[], &["-c", "-S"], &["-r"]];
+
+/// One document, spread over lines so it isn't taken for NDJSON.
+const DOCUMENT: &str = r#"{
+  "name": "qj",
+  "version": 1.50,
+  "big": 100000000000000000000,
+  "tags": ["fast", "json", "é"],
+  "nested": {"z": 1, "a": {"b": {"c": [1, 2.5, 3e2]}}, "empty": {}},
+  "nul": null,
+  "flag": false,
+  "escaped": "line\nbreak"
+}
+"#;
+
+const DOCUMENT_FILTERS: &[&str] = &[
+    ".",
+    ".name",
+    ".nested.a.b",
+    ".nested.a.b.c",
+    ".nested | .a | .b",
+    ".missing",
+    ".missing.x",
+    ".nul.x",
+    ".escaped",
+    ".big",
+    ".version",
+    ".tags.x",
+    ".version.x",
+    "length",
+    ".tags | length",
+    ".nested.empty | length",
+    "keys",
+    "keys_unsorted",
+    ".nested | keys",
+    "type",
+    ".big | type",
+    "has(\"name\")",
+    ".nested | has(\"a\")",
+    ".nested.a.b.c[1]",
+    "[.tags[]]",
+    "{name, version}",
+    ".tags | map(length)",
+    "to_entries | map(.key)",
+];
+
+/// A top-level array of records, for the array-map passthroughs.
+const ARRAY: &str = r#"[
+  {"id": 1, "name": "a", "tags": ["x"], "score": 1.0, "meta": {"k": "v"}},
+  {"id": 2, "name": "b", "tags": [], "score": null},
+  {"id": 3, "name": "cé", "tags": ["y", "z"], "score": -0.0, "extra": true},
+  {"id": 4, "name": null}
+]
+"#;
+
+const ARRAY_FILTERS: &[&str] = &[
+    "map(.name)",
+    ".[] | .name",
+    "map(.meta.k)",
+    "[.[] | .id]",
+    "map({id, name})",
+    ".[] | {id, score}",
+    "map(length)",
+    "map(keys)",
+    ".[] | keys",
+    "map(type)",
+    "map(has(\"tags\"))",
+    ".[0]",
+    ".[-1].name",
+    "length",
+    "map(select(.id > 1) | .name)",
+    ".[] | .tags[]?",
+    ".[] | .tags.x",
+    "first(.[] | select(.tags | length > 0))",
+    ".[] | .tags[0]?",
+    "map(-.name?)",
+];
+
+const NDJSON: &str = r#"{"id":1,"type":"a","msg":"hello","n":1.50,"tags":["x"],"user":{"name":"ann"}}
+{"id":2,"type":"b","msg":"wörld","n":null,"tags":[],"user":{"name":"bob"}}
+{"id":3,"type":"a","msg":"","n":3e2,"user":null}
+{"id":4,"type":"c","n":-0.0,"extra":{"z":1,"a":2}}
+"#;
+
+const NDJSON_FILTERS: &[&str] = &[
+    ".",
+    ".msg",
+    ".user.name",
+    ".n",
+    ".tags[0]",
+    "select(.type == \"a\")",
+    "select(.type == \"a\") | .msg",
+    "select(.type == \"a\") | {id, msg}",
+    "select(.type == \"a\") | [.id, .msg]",
+    "select(.type == .msg)",
+    "select(.id > 2)",
+    "select(.type == \"a\" and .id > 1)",
+    "select(.msg | startswith(\"h\"))",
+    "select(.msg | test(\"o\")) | .id",
+    "{id, msg}",
+    "[.id, .type]",
+    "[.id, .type] | @csv",
+    "[.id, .type] | @tsv",
+    "length",
+    "keys",
+    "type",
+    "has(\"msg\")",
+    ".user.name.x",
+];
+
+/// Stdout and exit code of `qj --eval-mode MODE ARGS FILTER FILE`.
+fn run(mode: &str, args: &[&str], filter: &str, file: &Path) -> (String, Option<i32>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+        .args(["--eval-mode", mode])
+        .args(args)
+        .arg(filter)
+        .arg(file)
+        .output()
+        .expect("failed to run qj");
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        output.status.code(),
+    )
+}
+
+fn assert_modes_agree(input: &str, filters: &[&str]) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("input.json");
+    std::fs::write(&file, input).unwrap();
+    let mut failures = Vec::new();
+    for filter in filters {
+        for args in ARG_SETS {
+            let tree = run("tree", args, filter, &file);
+            for mode in &MODES[..3] {
+                let got = run(mode, args, filter, &file);
+                if got != tree {
+                    failures.push(format!(
+                        "  {filter} {args:?}\n    {mode}={got:?}\n    tree={tree:?}"
+                    ));
+                }
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "eval modes disagree:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn document_filters_agree_across_eval_modes() {
+    assert_modes_agree(DOCUMENT, DOCUMENT_FILTERS);
+}
+
+#[test]
+fn array_filters_agree_across_eval_modes() {
+    assert_modes_agree(ARRAY, ARRAY_FILTERS);
+}
+
+#[test]
+fn ndjson_filters_agree_across_eval_modes() {
+    assert_modes_agree(NDJSON, NDJSON_FILTERS);
+}
+
+#[test]
+fn eval_mode_turns_off_passthroughs_and_fast_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let doc = dir.path().join("doc.json");
+    let lines = dir.path().join("lines.json");
+    std::fs::write(&doc, DOCUMENT).unwrap();
+    std::fs::write(&lines, NDJSON).unwrap();
+    let timing = |mode: &str, filter: &str, file: &Path| {
+        let output = Command::new(env!("CARGO_BIN_EXE_qj"))
+            .args(["--eval-mode", mode, "--debug-timing", "-c", filter])
+            .arg(file)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+    assert!(timing("auto", ".name", &doc).contains("(field passthrough)"));
+    for mode in ["passthrough-off", "flat", "tree"] {
+        assert!(
+            !timing(mode, ".name", &doc).contains("passthrough)"),
+            "{mode}"
+        );
+    }
+    assert!(timing("passthrough-off", ".msg", &lines).contains("(ndjson field fast path)"));
+    for mode in ["flat", "tree"] {
+        assert!(
+            !timing(mode, ".msg", &lines).contains("fast path"),
+            "{mode}"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Asserts the execution path of every `pipeline` benchmark filter, so a
+//! filter that silently drops off its passthrough or NDJSON fast path fails
+//! here rather than only slowing down the benchmarks.
+
+#[path = "../benches/pipeline_cases.rs"]
+mod cases;
+
+use qj::filter::{self, Env};
+use qj::output::{OutputConfig, OutputMode};
+use qj::parallel::ndjson;
+
+fn compact() -> OutputConfig {
+    OutputConfig {
+        mode: OutputMode::Compact,
+        ..OutputConfig::default()
+    }
+}
+
+#[test]
+fn document_filters_take_their_planned_path() {
+    for case in cases::DOC_CASES {
+        let filter = filter::parse(case.filter).unwrap();
+        let plan = filter::plan(&filter);
+        assert!(
+            (case.plan)(&plan),
+            "{}: `{}` planned {plan:?}",
+            case.name,
+            case.filter
+        );
+    }
+}
+
+#[test]
+fn ndjson_filters_take_their_fast_path() {
+    for case in cases::NDJSON_CASES {
+        let filter = filter::parse(case.filter).unwrap();
+        let fast_path = ndjson::fast_path_name(&filter, &compact(), &Env::empty());
+        assert_eq!(
+            fast_path, case.fast_path,
+            "{}: `{}`",
+            case.name, case.filter
+        );
+    }
+}
+
+#[test]
+fn ndjson_fast_paths_match_the_evaluator_on_bench_input() {
+    let data = cases::ndjson(200);
+    let (config, env) = (compact(), Env::empty());
+    for case in cases::NDJSON_CASES {
+        let filter = filter::parse(case.filter).unwrap();
+        let (fast, _, _) = ndjson::process_ndjson(&data, &filter, &config, &env).unwrap();
+        let (slow, _, _) =
+            ndjson::process_ndjson_no_fast_path(&data, &filter, &config, &env).unwrap();
+        assert!(!fast.is_empty(), "{}: no output", case.name);
+        assert_eq!(fast, slow, "{}: `{}`", case.name, case.filter);
+    }
+}